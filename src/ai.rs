@@ -1,75 +1,1014 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use async_openai::{
-    config::OpenAIConfig,
-    types::{ChatCompletionRequestSystemMessageArgs, CreateChatCompletionRequestArgs},
+    config::{Config, OpenAIConfig},
+    error::OpenAIError,
+    types::{
+        ChatCompletionRequestSystemMessageArgs, ChatCompletionStreamOptions,
+        CreateChatCompletionRequestArgs, CreateChatCompletionStreamResponse, FinishReason,
+    },
     Client,
 };
 use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use crate::domain::{AISummarizer, Commit};
+use crate::domain::{AISummarizer, HighlightsRequest, ProviderPing, SummaryRequest};
+use crate::pricing::{estimate_cost, estimate_tokens, CostEstimate};
+pub use crate::prompt::DETERMINISTIC_SEED;
+use crate::prompt::{
+    build_highlights_prompt, build_user_prompt, system_prompt, HIGHLIGHTS_SYSTEM_PROMPT,
+};
+use crate::routing::RoutingRule;
+
+/// Model used when the config doesn't name one
+pub const DEFAULT_MODEL: &str = "gpt-4";
+
+/// Endpoint used when the config doesn't name one
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Largest edit distance at which a model name still counts as a typo
+const MAX_TYPO_DISTANCE: usize = 3;
+
+/// The `[ai]` config table, for OpenAI-compatible gateways
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AiConfig {
+    /// Model used for summaries; `gpt-4` when unset
+    pub model: Option<String>,
+    /// API endpoint, such as `http://localhost:8080/v1` for a self-hosted
+    /// gateway; OpenAI's when unset
+    pub base_url: Option<String>,
+    /// PEM file with an extra root certificate, for gateways behind a corporate CA
+    pub ca_certificate: Option<PathBuf>,
+    /// Models by diary size, such as `[{ max_commits = 5, model = "gpt-4o-mini" }]`;
+    /// diaries no tier fits use `model`
+    pub routing: Vec<RoutingRule>,
+    /// Headers sent with every request, such as `X-Team-Id`
+    pub extra_headers: BTreeMap<String, String>,
+    /// Fields merged into every request body, overriding the ones git-diary sets
+    pub extra_body: Map<String, Value>,
+}
+
+impl AiConfig {
+    pub fn model(&self) -> &str {
+        self.model.as_deref().unwrap_or(DEFAULT_MODEL)
+    }
+
+    /// Host and port of the configured endpoint
+    pub fn endpoint(&self) -> Result<(String, u16)> {
+        let base_url = self.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL);
+        let url = reqwest::Url::parse(base_url)
+            .with_context(|| format!("Invalid base_url {:?}", base_url))?;
+        match (url.host_str(), url.port_or_known_default()) {
+            (Some(host), Some(port)) => Ok((host.to_string(), port)),
+            _ => bail!("base_url {:?} names no host and port", base_url),
+        }
+    }
+
+    /// Whether a connection to the endpoint opens within `timeout`, which
+    /// tells an offline machine apart before any work is done
+    pub fn is_reachable(&self, timeout: Duration) -> bool {
+        let Ok(endpoint) = self.endpoint() else {
+            return false;
+        };
+        // Resolving the host fails too when offline
+        let Ok(addresses) = endpoint.to_socket_addrs() else {
+            return false;
+        };
+        addresses
+            .into_iter()
+            .any(|address| TcpStream::connect_timeout(&address, timeout).is_ok())
+    }
+
+    /// Builds an API client for the configured endpoint that sends the extra headers
+    pub fn client(&self) -> Result<Client<OpenAIConfig>> {
+        Ok(self.client_with(self.http_client_builder()?.build()?))
+    }
+
+    /// Builds a summarizer for the configured endpoint and model, sending the extra
+    /// headers and extra body
+    pub fn summarizer(&self, max_tokens: u32) -> Result<AISummarizerImpl> {
+        let http_client = self.http_client_builder()?.build()?;
+        Ok(AISummarizerImpl::new(
+            self.client_with(http_client.clone()),
+            self.model().to_string(),
+            max_tokens,
+        )
+        .with_http_client(http_client)
+        .with_extra_body(self.extra_body.clone()))
+    }
+
+    /// Builds an API client for the configured endpoint on top of `http_client`
+    ///
+    /// For embedders and tests that bring their own transport, such as a
+    /// recording proxy; the extra headers and certificate are not applied, so
+    /// start from `http_client_builder` to keep them.
+    pub fn client_with(&self, http_client: reqwest::Client) -> Client<OpenAIConfig> {
+        let mut config = OpenAIConfig::new();
+        if let Some(base_url) = &self.base_url {
+            config = config.with_api_base(base_url.trim_end_matches('/'));
+        }
+
+        Client::with_config(config).with_http_client(http_client)
+    }
+
+    /// An HTTP client builder with the extra headers and root certificate applied
+    pub fn http_client_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder().default_headers(self.headers()?);
+        if let Some(path) = &self.ca_certificate {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+            let certificate = reqwest::Certificate::from_pem(&pem)
+                .with_context(|| format!("Invalid CA certificate {}", path.display()))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        Ok(builder)
+    }
+
+    /// Parses the extra headers, failing on names or values HTTP doesn't allow
+    pub fn headers(&self) -> Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.extra_headers {
+            let header_name = HeaderName::try_from(name.as_str())
+                .with_context(|| format!("Invalid header name {:?}", name))?;
+            let header_value = HeaderValue::try_from(value.as_str())
+                .with_context(|| format!("Invalid value for header {}", name))?;
+            headers.insert(header_name, header_value);
+        }
+        Ok(headers)
+    }
+}
+
+/// Ids of the models the provider offers, sorted
+pub async fn list_models(client: &Client<OpenAIConfig>) -> Result<Vec<String>> {
+    let response = client
+        .models()
+        .list()
+        .await
+        .context("Failed to list models")?;
+
+    let mut models: Vec<String> = response.data.into_iter().map(|model| model.id).collect();
+    models.sort();
+    Ok(models)
+}
+
+/// Fails unless `model` is one of the `available` models, suggesting the
+/// closest name for likely typos such as `gpt4-o`
+pub fn check_model(model: &str, available: &[String]) -> Result<()> {
+    if available.iter().any(|candidate| candidate == model) {
+        return Ok(());
+    }
+
+    let suggestion = available
+        .iter()
+        .map(|candidate| (edit_distance(model, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_TYPO_DISTANCE)
+        .min_by_key(|(distance, _)| *distance);
+    match suggestion {
+        Some((_, candidate)) => bail!(
+            "Model {:?} is not offered by the provider. Did you mean {:?}?",
+            model,
+            candidate
+        ),
+        None => bail!(
+            "Model {:?} is not offered by the provider; run `git-diary models` to see the ones that are",
+            model
+        ),
+    }
+}
+
+/// Edit distance between two strings in characters, counting a swap of two
+/// neighbouring characters as one edit like the `4-` in `gpt4-o`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    distances[0] = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
+
+/// Error code the API returns once the account has no credit left
+const INSUFFICIENT_QUOTA: &str = "insufficient_quota";
+
+/// The API account ran out of credit, so retrying the same request won't help
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExhausted {
+    /// Message from the API, such as "You exceeded your current quota"
+    pub message: String,
+}
+
+impl std::fmt::Display for QuotaExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The AI provider's quota is used up: {}\n\
+             Check the plan and billing details of the API account, or point `[ai]` \
+             in the config at another provider or a cheaper model",
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for QuotaExhausted {}
+
+/// Whether `error` came from a request refused for lack of quota
+pub fn is_quota_exhausted(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<QuotaExhausted>())
+}
+
+/// Reads a quota refusal out of an API error object
+fn quota_exhausted(error: &async_openai::error::ApiError) -> Option<QuotaExhausted> {
+    let refused = error.code.as_deref() == Some(INSUFFICIENT_QUOTA)
+        || error.r#type.as_deref() == Some(INSUFFICIENT_QUOTA);
+    refused.then(|| QuotaExhausted {
+        message: error.message.clone(),
+    })
+}
+
+/// The API refused the key, so nothing will work until the setup is fixed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticationFailed {
+    /// HTTP status of the refusal, 401 or 403
+    pub status: u16,
+    /// Message from the API, if it sent one
+    pub message: String,
+}
+
+impl std::fmt::Display for AuthenticationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "The AI provider refused the credentials ({}): {}\n\
+             Check OPENAI_API_KEY, or the headers under `[ai]` in the config",
+            self.status, self.message
+        )
+    }
+}
+
+impl std::error::Error for AuthenticationFailed {}
+
+/// Whether `error` came from a request refused for its credentials
+pub fn is_authentication_failed(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.is::<AuthenticationFailed>())
+}
+
+/// Tokens used by the summaries generated so far
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
 
 pub struct AISummarizerImpl {
     client: Client<OpenAIConfig>,
+    /// Transport of `client`, kept to look into failed streams
+    http_client: reqwest::Client,
     model: String,
     max_tokens: u32,
+    extra_body: Map<String, Value>,
+    deterministic: bool,
+    /// Usage per model, as routing may send requests to several
+    usage: Mutex<BTreeMap<String, TokenUsage>>,
 }
 
 impl AISummarizerImpl {
     pub fn new(client: Client<OpenAIConfig>, model: String, max_tokens: u32) -> Self {
         Self {
             client,
+            http_client: reqwest::Client::new(),
             model,
             max_tokens,
+            extra_body: Map::new(),
+            deterministic: false,
+            usage: Mutex::new(BTreeMap::new()),
         }
     }
-}
 
-#[async_trait]
-impl AISummarizer for AISummarizerImpl {
-    async fn summarize_commits(&self, commits: &[Commit]) -> Result<String> {
-        let request = CreateChatCompletionRequestArgs::default()
-            .model(&self.model)
+    /// Merges `extra_body` into every request body
+    pub fn with_extra_body(mut self, extra_body: Map<String, Value>) -> Self {
+        self.extra_body = extra_body;
+        self
+    }
+
+    /// Sends requests through `http_client` instead of the one the API client was built with
+    ///
+    /// Lets tests inject a mock transport and embedders reuse a client with
+    /// their own proxy or TLS settings.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.client = self.client.with_http_client(http_client.clone());
+        self.http_client = http_client;
+        self
+    }
+
+    /// Asks for reproducible output: temperature 0 and a fixed seed
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Total token usage of every summary generated by this summarizer
+    pub fn usage(&self) -> TokenUsage {
+        self.usage
+            .lock()
+            .unwrap()
+            .values()
+            .fold(TokenUsage::default(), |total, usage| TokenUsage {
+                prompt_tokens: total.prompt_tokens + usage.prompt_tokens,
+                completion_tokens: total.completion_tokens + usage.completion_tokens,
+            })
+    }
+
+    /// Token usage of each model that generated a summary
+    pub fn usage_by_model(&self) -> BTreeMap<String, TokenUsage> {
+        self.usage.lock().unwrap().clone()
+    }
+
+    /// The models that generated summaries joined with `+`, such as
+    /// `gpt-4o+gpt-4o-mini`; the configured model before any call
+    pub fn models_used(&self) -> String {
+        let usage = self.usage.lock().unwrap();
+        if usage.is_empty() {
+            return self.model.clone();
+        }
+        usage.keys().cloned().collect::<Vec<String>>().join("+")
+    }
+
+    /// Estimated cost of every summary so far, if all the models' prices are known
+    pub fn estimated_cost(&self) -> Option<f64> {
+        self.usage
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(model, usage)| {
+                estimate_cost(model, usage.prompt_tokens, usage.completion_tokens)
+            })
+            .sum()
+    }
+
+    /// Estimates the tokens and cost of summarizing `request`, before sending it
+    pub fn estimate(&self, request: &SummaryRequest) -> CostEstimate {
+        let model = request.model.as_deref().unwrap_or(&self.model);
+        let prompt_tokens = estimate_tokens(&system_prompt(request.length))
+            + estimate_tokens(&build_user_prompt(request));
+        CostEstimate::new(model.to_string(), prompt_tokens, self.max_tokens)
+    }
+
+    /// Builds the streaming chat completion request, with the extra body merged in
+    fn build_request_body(
+        &self,
+        model: &str,
+        system_prompt: String,
+        user_prompt: String,
+    ) -> Result<Value> {
+        let mut args = CreateChatCompletionRequestArgs::default();
+        args.model(model)
             .max_tokens(self.max_tokens)
+            .stream(true)
+            .stream_options(ChatCompletionStreamOptions {
+                include_usage: true,
+            })
             .messages([
                 ChatCompletionRequestSystemMessageArgs::default()
-                    .content("You are an expert software development analyst. Your task is to create a concise, professional diary entry summarizing recent development work based on Git commit messages.
-
-Instructions:
-1. Analyze the provided commit messages to understand the development activities
-2. Group related commits by theme (features, bug fixes, refactoring, documentation, etc.)
-3. Write a clear, narrative summary in diary format using past tense
-4. Focus on what was accomplished, not just what was changed
-5. Highlight significant features, improvements, or architectural decisions
-6. Mention any notable patterns or development trends
-7. Keep the tone professional but engaging
-8. Aim for 3-5 sentences that capture the essence of the work period
-
-Format your response as a diary entry starting with a brief overview, followed by key accomplishments grouped logically.")
+                    .content(system_prompt)
                     .build()?
                     .into(),
                 ChatCompletionRequestSystemMessageArgs::default()
-                    .content(format!("Commit messages to summarize:\n\n{}",
-                        commits.iter().map(|commit| commit.to_string()).collect::<Vec<String>>().join("\n")))
+                    .content(user_prompt)
                     .build()?
                     .into(),
-            ])
-            .build()?;
+            ]);
+        if self.deterministic {
+            // Seeds are best effort; providers that ignore them still get temperature 0
+            args.temperature(0.0).seed(DETERMINISTIC_SEED);
+        }
+
+        merge_extra_body(serde_json::to_value(args.build()?)?, &self.extra_body)
+    }
+}
+
+/// Tracks token usage and estimated cost while a summary streams in
+struct TokenBudget<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    prompt_tokens: u32,
+    completion_tokens: u32,
+}
+
+impl<'a> TokenBudget<'a> {
+    fn new(model: &'a str, max_tokens: u32, prompt_tokens: u32) -> Self {
+        Self {
+            model,
+            max_tokens,
+            prompt_tokens,
+            completion_tokens: 0,
+        }
+    }
+
+    /// Counts one streamed content chunk, which is roughly one token
+    fn record_chunk(&mut self) {
+        self.completion_tokens += 1;
+    }
+
+    /// Replaces the running estimates with the usage reported by the API
+    fn set_usage(&mut self, prompt_tokens: u32, completion_tokens: u32) {
+        self.prompt_tokens = prompt_tokens;
+        self.completion_tokens = completion_tokens;
+    }
+
+    /// Whether completion tokens have used 90% or more of `max_tokens`
+    fn near_limit(&self) -> bool {
+        self.completion_tokens as u64 * 10 >= self.max_tokens as u64 * 9
+    }
+
+    fn cost(&self) -> String {
+        estimate_cost(self.model, self.prompt_tokens, self.completion_tokens)
+            .map(|cost| format!(" (~${:.4})", cost))
+            .unwrap_or_default()
+    }
 
-        let response = self.client.chat().create(request).await?;
+    fn status_line(&self) -> String {
+        format!(
+            "⏳ Generating summary: {}/{} tokens{}",
+            self.completion_tokens,
+            self.max_tokens,
+            self.cost()
+        )
+    }
+
+    fn usage_line(&self) -> String {
+        format!(
+            "🧮 Used {} prompt + {} completion tokens{}",
+            self.prompt_tokens,
+            self.completion_tokens,
+            self.cost()
+        )
+    }
+}
+
+impl AISummarizerImpl {
+    /// Streams a completion for the prompts from `model`, showing progress and recording usage
+    async fn complete(
+        &self,
+        model: &str,
+        system_prompt: String,
+        user_prompt: String,
+    ) -> Result<String> {
+        let mut budget = TokenBudget::new(
+            model,
+            self.max_tokens,
+            estimate_tokens(&system_prompt) + estimate_tokens(&user_prompt),
+        );
+
+        let body = self.build_request_body(model, system_prompt, user_prompt)?;
+        let mut stream = match self
+            .client
+            .chat()
+            .create_stream_byot::<_, CreateChatCompletionStreamResponse>(body.clone())
+            .await
+        {
+            Ok(stream) => stream,
+            Err(e) => return Err(self.explain_error(&body, e).await),
+        };
+
+        // Only draw the status line on a terminal
+        let show_status = std::io::stderr().is_terminal();
+        let mut warned = false;
+        let mut truncated = false;
 
         let mut summary = String::new();
-        for choice in response.choices {
-            let content = choice.message.content.unwrap_or("No content".to_string());
-            summary.push_str(&content);
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => {
+                    if show_status {
+                        eprint!("\r\x1b[2K");
+                    }
+                    return Err(self.explain_error(&body, e).await);
+                }
+            };
+
+            for choice in chunk.choices {
+                if let Some(content) = choice.delta.content {
+                    summary.push_str(&content);
+                    budget.record_chunk();
+                }
+                if choice.finish_reason == Some(FinishReason::Length) {
+                    truncated = true;
+                }
+            }
+            if let Some(usage) = chunk.usage {
+                budget.set_usage(usage.prompt_tokens, usage.completion_tokens);
+            }
+
+            if show_status {
+                eprint!("\r\x1b[2K{}", budget.status_line());
+            }
+            if !warned && budget.near_limit() {
+                warned = true;
+                if show_status {
+                    eprint!("\r\x1b[2K");
+                }
+                eprintln!(
+                    "⚠️ Summary is close to max_tokens ({}) and may be cut off",
+                    self.max_tokens
+                );
+            }
+        }
+
+        if show_status {
+            eprint!("\r\x1b[2K");
+        }
+        eprintln!("{}", budget.usage_line());
+        {
+            let mut usage = self.usage.lock().unwrap();
+            let usage = usage.entry(model.to_string()).or_default();
+            usage.prompt_tokens += budget.prompt_tokens;
+            usage.completion_tokens += budget.completion_tokens;
+        }
+        if truncated {
+            eprintln!(
+                "⚠️ Summary was truncated at max_tokens ({})",
+                self.max_tokens
+            );
+        }
+
+        if summary.is_empty() {
+            summary.push_str("No content");
         }
 
         Ok(summary)
     }
 }
 
+impl AISummarizerImpl {
+    /// Turns a failed request into an error, telling an exhausted quota apart
+    /// from other failures
+    async fn explain_error(&self, body: &Value, error: OpenAIError) -> anyhow::Error {
+        match &error {
+            OpenAIError::ApiError(api_error) => {
+                if let Some(quota) = quota_exhausted(api_error) {
+                    return quota.into();
+                }
+            }
+            // A failed stream only reports the status code, and 429 also means
+            // a rate limit, so the reason is read from a one-token request
+            OpenAIError::StreamError(message) if message.contains("429") => {
+                if let Some(quota) = self.probe_quota(body).await {
+                    return quota.into();
+                }
+            }
+            _ => {}
+        }
+
+        error.into()
+    }
+
+    /// Sends `body` again without streaming, capped at one token, to read why it was refused
+    async fn probe_quota(&self, body: &Value) -> Option<QuotaExhausted> {
+        let response = self.send_one_token(body).await.ok()?;
+        let wrapped: WrappedError = response.json().await.ok()?;
+        quota_exhausted(&wrapped.error)
+    }
+
+    /// Sends `body` without streaming, capped at one token
+    async fn send_one_token(&self, body: &Value) -> Result<reqwest::Response> {
+        let mut body = body.clone();
+        let Some(fields) = body.as_object_mut() else {
+            bail!("Request body is not a JSON object");
+        };
+        fields.insert("stream".to_string(), Value::Bool(false));
+        fields.remove("stream_options");
+        // Newer models refuse `max_tokens`; `max_completion_tokens` replaces it
+        fields.remove("max_tokens");
+        fields.insert("max_completion_tokens".to_string(), Value::from(1));
+
+        let config = self.client.config();
+        Ok(self
+            .http_client
+            .post(config.url("/chat/completions"))
+            .headers(config.headers())
+            .query(&config.query())
+            .json(&body)
+            .send()
+            .await?)
+    }
+}
+
+/// Error body of a refused API request
+#[derive(Deserialize)]
+struct WrappedError {
+    error: async_openai::error::ApiError,
+}
+
+#[async_trait]
+impl AISummarizer for AISummarizerImpl {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String> {
+        let model = request.model.as_deref().unwrap_or(&self.model);
+        self.complete(
+            model,
+            system_prompt(request.length),
+            build_user_prompt(request),
+        )
+        .await
+    }
+
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String> {
+        self.complete(
+            &self.model,
+            HIGHLIGHTS_SYSTEM_PROMPT.to_string(),
+            build_highlights_prompt(request),
+        )
+        .await
+    }
+
+    /// Asks the configured model for one token, telling refused credentials
+    /// and an exhausted quota apart from other failures
+    async fn ping(&self) -> Result<ProviderPing> {
+        let body = self.build_request_body(&self.model, String::new(), "ping".to_string())?;
+        let started = Instant::now();
+        let response = self
+            .send_one_token(&body)
+            .await
+            .context("Failed to reach the AI provider")?;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            let error = serde_json::from_str::<WrappedError>(&text).ok();
+            if let Some(quota) = error.as_ref().and_then(|e| quota_exhausted(&e.error)) {
+                return Err(quota.into());
+            }
+            let message = error.map_or(text, |e| e.error.message);
+            if matches!(status.as_u16(), 401 | 403) {
+                return Err(AuthenticationFailed {
+                    status: status.as_u16(),
+                    message,
+                }
+                .into());
+            }
+            bail!("The AI provider answered {}: {}", status, message);
+        }
+
+        let answer: Value =
+            serde_json::from_str(&text).context("The AI provider's answer is not JSON")?;
+        Ok(ProviderPing {
+            // Gateways that leave the field out answer with the requested model
+            model: answer["model"].as_str().unwrap_or(&self.model).to_string(),
+            latency_ms,
+        })
+    }
+}
+
+/// Overlays `extra` on the top-level fields of a request body
+fn merge_extra_body(mut body: Value, extra: &Map<String, Value>) -> Result<Value> {
+    let Some(fields) = body.as_object_mut() else {
+        bail!("Request body is not a JSON object");
+    };
+    for (key, value) in extra {
+        fields.insert(key.clone(), value.clone());
+    }
+    Ok(body)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::Commit;
+
+    #[test]
+    fn test_token_budget() {
+        let mut budget = TokenBudget::new("gpt-4o-mini", 100, 1000);
+
+        for _ in 0..89 {
+            budget.record_chunk();
+        }
+        assert!(!budget.near_limit());
+        assert!(budget.status_line().contains("89/100 tokens"));
+
+        budget.record_chunk();
+        assert!(budget.near_limit());
+
+        budget.set_usage(1200, 95);
+        assert_eq!(
+            budget.usage_line(),
+            "🧮 Used 1200 prompt + 95 completion tokens (~$0.0002)"
+        );
+    }
+
+    #[test]
+    fn test_merge_extra_body() -> Result<()> {
+        let mut extra = Map::new();
+        extra.insert("user".to_string(), Value::from("team-a"));
+        extra.insert("temperature".to_string(), Value::from(0.2));
+
+        let body = merge_extra_body(
+            serde_json::json!({"model": "gpt-4o", "temperature": 1.0, "stream": true}),
+            &extra,
+        )?;
+
+        assert_eq!(
+            body,
+            serde_json::json!({"model": "gpt-4o", "temperature": 0.2, "stream": true, "user": "team-a"})
+        );
+        assert!(merge_extra_body(Value::Null, &extra).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_uses_the_routed_model() {
+        let summarizer = AISummarizerImpl::new(Client::new(), "gpt-4o".to_string(), 500);
+        let mut request = SummaryRequest::new(vec![Commit::new("Fix cache".to_string(), 0)]);
+
+        let estimate = summarizer.estimate(&request);
+        assert_eq!(estimate.model, "gpt-4o");
+        assert_eq!(estimate.max_completion_tokens, 500);
+        assert!(estimate.prompt_tokens > 0);
+
+        request.model = Some("gpt-4o-mini".to_string());
+        assert_eq!(summarizer.estimate(&request).model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn test_build_request_body_deterministic() -> Result<()> {
+        let summarizer = AISummarizerImpl::new(Client::new(), "gpt-4o".to_string(), 1000);
+        let body =
+            summarizer.build_request_body("gpt-4o", "system".to_string(), "user".to_string())?;
+        assert_eq!(body["stream"], true);
+        assert!(body.get("temperature").is_none());
+        assert!(body.get("seed").is_none());
+
+        let body = summarizer.with_deterministic(true).build_request_body(
+            "gpt-4o",
+            "system".to_string(),
+            "user".to_string(),
+        )?;
+        assert_eq!(body["temperature"], 0.0);
+        assert_eq!(body["seed"], DETERMINISTIC_SEED);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ai_config_headers() -> Result<()> {
+        let mut config = AiConfig::default();
+        config
+            .extra_headers
+            .insert("X-Team-Id".to_string(), "diary".to_string());
+        assert_eq!(config.headers()?["x-team-id"], "diary");
+
+        config
+            .extra_headers
+            .insert("Bad Header".to_string(), "value".to_string());
+        assert!(config.headers().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ai_config_endpoint_reachability() -> Result<()> {
+        let mut config = AiConfig::default();
+        assert_eq!(config.endpoint()?, ("api.openai.com".to_string(), 443));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        config.base_url = Some(format!("http://127.0.0.1:{}/v1", port));
+        assert_eq!(config.endpoint()?, ("127.0.0.1".to_string(), port));
+        assert!(config.is_reachable(Duration::from_secs(1)));
+
+        drop(listener);
+        assert!(!config.is_reachable(Duration::from_secs(1)));
+
+        config.base_url = Some("not a url".to_string());
+        assert!(config.endpoint().is_err());
+        assert!(!config.is_reachable(Duration::from_secs(1)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_model() {
+        let available = vec![
+            "gpt-4".to_string(),
+            "gpt-4o".to_string(),
+            "gpt-4o-mini".to_string(),
+        ];
+
+        assert!(check_model("gpt-4o", &available).is_ok());
+        assert_eq!(
+            check_model("gpt4-o", &available).unwrap_err().to_string(),
+            "Model \"gpt4-o\" is not offered by the provider. Did you mean \"gpt-4o\"?"
+        );
+        assert!(check_model("claude-sonnet", &available)
+            .unwrap_err()
+            .to_string()
+            .contains("run `git-diary models`"));
+        assert_eq!(AiConfig::default().model(), DEFAULT_MODEL);
+    }
+
+    #[tokio::test]
+    async fn test_injected_http_client() -> Result<()> {
+        use wiremock::matchers::{header, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        let stream = [
+            serde_json::json!({"id": "1", "object": "chat.completion.chunk", "created": 0, "model": "gpt-4o",
+                "choices": [{"index": 0, "delta": {"content": "Fixed the parser."}, "finish_reason": "stop"}]}),
+            serde_json::json!({"id": "1", "object": "chat.completion.chunk", "created": 0, "model": "gpt-4o",
+                "choices": [], "usage": {"prompt_tokens": 40, "completion_tokens": 4, "total_tokens": 44}}),
+        ]
+        .iter()
+        .map(|chunk| format!("data: {}\n\n", chunk))
+        .collect::<String>()
+            + "data: [DONE]\n\n";
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(header("x-fixture", "parser"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(stream, "text/event-stream"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let config = AiConfig {
+            base_url: Some(server.uri()),
+            ..AiConfig::default()
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("x-fixture", HeaderValue::from_static("parser"));
+        let summarizer = AISummarizerImpl::new(config.client()?, "gpt-4o".to_string(), 1000)
+            .with_http_client(
+                reqwest::Client::builder()
+                    .default_headers(headers)
+                    .build()?,
+            );
+
+        let summary = summarizer
+            .summarize_commits(&SummaryRequest::new(vec![Commit::new(
+                "Fix parser".to_string(),
+                1704067200,
+            )]))
+            .await?;
+
+        assert_eq!(summary, "Fixed the parser.");
+        assert_eq!(
+            summarizer.usage(),
+            TokenUsage {
+                prompt_tokens: 40,
+                completion_tokens: 4,
+            }
+        );
+        assert_eq!(summarizer.models_used(), "gpt-4o");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quota_exhaustion_is_reported_distinctly() -> Result<()> {
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let refusal = |kind: &str| {
+            ResponseTemplate::new(429).set_body_json(serde_json::json!({"error": {
+                "message": "You exceeded your current quota",
+                "type": kind,
+                "param": null,
+                "code": kind,
+            }}))
+        };
+        let request = SummaryRequest::new(vec![Commit::new("Fix parser".to_string(), 0)]);
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(refusal("insufficient_quota"))
+            .mount(&server)
+            .await;
+        let config = AiConfig {
+            base_url: Some(server.uri()),
+            ..AiConfig::default()
+        };
+        let error = config
+            .summarizer(100)?
+            .summarize_commits(&request)
+            .await
+            .unwrap_err();
+        assert!(is_quota_exhausted(&error));
+        assert!(error.to_string().contains("billing"));
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(refusal("rate_limit_exceeded"))
+            .mount(&server)
+            .await;
+        let config = AiConfig {
+            base_url: Some(server.uri()),
+            ..AiConfig::default()
+        };
+        let error = config
+            .summarizer(100)?
+            .summarize_commits(&request)
+            .await
+            .unwrap_err();
+        assert!(!is_quota_exhausted(&error));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ping() -> Result<()> {
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .and(body_partial_json(
+                serde_json::json!({"model": "gpt-4o", "max_completion_tokens": 1, "stream": false}),
+            ))
+            .and(|request: &wiremock::Request| {
+                !String::from_utf8_lossy(&request.body).contains("\"max_tokens\"")
+            })
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "1", "object": "chat.completion", "created": 0, "model": "gpt-4o-2024-08-06",
+                "choices": [{"index": 0, "message": {"role": "assistant", "content": "Hi"},
+                    "finish_reason": "length"}]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let config = AiConfig {
+            base_url: Some(server.uri()),
+            model: Some("gpt-4o".to_string()),
+            ..AiConfig::default()
+        };
+        let ping = config.summarizer(1)?.ping().await?;
+        assert_eq!(ping.model, "gpt-4o-2024-08-06");
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(
+                ResponseTemplate::new(401).set_body_json(serde_json::json!({"error": {
+                    "message": "Incorrect API key provided",
+                    "type": "invalid_request_error",
+                    "param": null,
+                    "code": "invalid_api_key",
+                }})),
+            )
+            .mount(&server)
+            .await;
+        let config = AiConfig {
+            base_url: Some(server.uri()),
+            ..AiConfig::default()
+        };
+        let error = config.summarizer(1)?.ping().await.unwrap_err();
+        assert!(is_authentication_failed(&error));
+        assert!(error.to_string().contains("Incorrect API key"));
+        assert!(!is_quota_exhausted(&error));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_budget_unknown_model() {
+        let budget = TokenBudget::new("local-model", 100, 10);
+
+        assert!(!budget.status_line().contains('$'));
+    }
 
     #[tokio::test]
     async fn test_summarize_commits_empty() {
@@ -81,8 +1020,8 @@ mod tests {
             return;
         }
 
-        let commits = Vec::new();
-        let result = summarizer.summarize_commits(&commits).await;
+        let request = SummaryRequest::new(Vec::new());
+        let result = summarizer.summarize_commits(&request).await;
 
         assert!(result.is_ok());
         // We just verify we get some response back
@@ -106,7 +1045,9 @@ mod tests {
             Commit::new("Fix bug in error handling".to_string(), 1704326400),
         ];
 
-        let result = summarizer.summarize_commits(&commits).await;
+        let result = summarizer
+            .summarize_commits(&SummaryRequest::new(commits))
+            .await;
 
         assert!(result.is_ok());
         let summary = result.unwrap();
@@ -136,7 +1077,9 @@ mod tests {
 
         let commits = vec![Commit::new("Test commit".to_string(), 1704067200)];
 
-        let result = summarizer.summarize_commits(&commits).await;
+        let result = summarizer
+            .summarize_commits(&SummaryRequest::new(commits))
+            .await;
 
         // The request should fail due to invalid model
         assert!(result.is_err());
@@ -0,0 +1,356 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::ledger::{Ledger, LedgerEntry};
+use crate::storage::DiaryStorageImpl;
+
+/// Directory of the archive holding the diaries and their sidecars
+pub const ARCHIVE_DIARY_DIR: &str = "diaries";
+
+/// Ledger entries of the archived diaries, one JSON object per line
+pub const ARCHIVE_LEDGER: &str = "ledger.jsonl";
+
+/// What to do with an archived diary whose name is taken by a different one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ConflictPolicy {
+    /// Keep the diary already there and leave the archived one out
+    Skip,
+    /// Replace the diary already there
+    Overwrite,
+    /// Import the archived diary under a new name, keeping both
+    #[default]
+    Rename,
+}
+
+/// Counts of what an export wrote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exported {
+    pub files: usize,
+    pub ledger_entries: usize,
+}
+
+/// Counts of what an import restored, and the files it wrote
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub written: Vec<PathBuf>,
+    /// Diaries already present with the same contents
+    pub unchanged: usize,
+    /// Diaries imported under a new name because theirs was taken
+    pub renamed: usize,
+    /// Diaries left out because theirs was taken
+    pub skipped: usize,
+    pub overwritten: usize,
+    pub ledger_entries: usize,
+}
+
+/// Writes every diary in `storage`, with sidecars and branch indexes, and the
+/// ledger entries of those diaries to a gzipped tarball at `out`
+pub fn export_archive(out: &Path, storage: &DiaryStorageImpl, ledger: &Ledger) -> Result<Exported> {
+    let diaries = storage.stored_diaries()?;
+    let file = File::create(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    let mut names = Vec::new();
+    for diary in &diaries {
+        let name = diary
+            .path
+            .file_name()
+            .context("Diary path has no file name")?;
+        builder
+            .append_path_with_name(&diary.path, Path::new(ARCHIVE_DIARY_DIR).join(name))
+            .with_context(|| format!("Failed to archive {}", diary.path.display()))?;
+        names.push(same_file_key(&diary.path));
+    }
+
+    let entries: Vec<LedgerEntry> = ledger
+        .entries()?
+        .into_iter()
+        .filter(|entry| names.contains(&same_file_key(Path::new(&entry.path))))
+        .collect();
+    let mut lines = String::new();
+    for entry in &entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    let mut header = tar::Header::new_gnu();
+    header.set_size(lines.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Local::now().timestamp().max(0) as u64);
+    builder.append_data(&mut header, ARCHIVE_LEDGER, lines.as_bytes())?;
+
+    builder
+        .into_inner()?
+        .finish()
+        .with_context(|| format!("Failed to write {}", out.display()))?;
+    Ok(Exported {
+        files: diaries.len(),
+        ledger_entries: entries.len(),
+    })
+}
+
+/// Restores an archive written by [`export_archive`] into `diary_dir` and
+/// adds its ledger entries to `ledger`, pointed at where the diaries now are
+///
+/// A diary and its sidecar move together: when either one's name is taken by
+/// a file with other contents, `policy` decides for both. Diaries already
+/// present unchanged and ledger entries already recorded aren't added twice,
+/// so importing the same archive again changes nothing.
+pub fn import_archive(
+    archive: &Path,
+    diary_dir: &Path,
+    ledger: &Ledger,
+    policy: ConflictPolicy,
+) -> Result<Imported> {
+    let (files, archived_entries) = read_archive(archive)?;
+    fs::create_dir_all(diary_dir)
+        .with_context(|| format!("Failed to create {}", diary_dir.display()))?;
+
+    // Diaries and their sidecars share a stem
+    let mut groups: BTreeMap<String, Vec<(String, Vec<u8>)>> = BTreeMap::new();
+    for (name, data) in files {
+        groups
+            .entry(stem(&name).to_string())
+            .or_default()
+            .push((name, data));
+    }
+
+    let mut imported = Imported::default();
+    // Where each archived file ended up; missing when it was skipped
+    let mut placed: HashMap<String, PathBuf> = HashMap::new();
+    for (stem, group) in groups {
+        let differs = |name: &str, data: &[u8]| {
+            fs::read(diary_dir.join(name)).is_ok_and(|existing| existing != data)
+        };
+        let conflict = group.iter().any(|(name, data)| differs(name, data));
+
+        let new_stem = match (conflict, policy) {
+            (true, ConflictPolicy::Skip) => {
+                imported.skipped += 1;
+                continue;
+            }
+            (true, ConflictPolicy::Rename) => free_stem(diary_dir, &stem, &group),
+            _ => stem.clone(),
+        };
+
+        let mut unchanged = true;
+        for (name, data) in &group {
+            let target = diary_dir.join(format!("{}{}", new_stem, &name[stem.len()..]));
+            if fs::read(&target).is_ok_and(|existing| existing == *data) {
+                placed.insert(name.clone(), target);
+                continue;
+            }
+            fs::write(&target, data)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+            unchanged = false;
+            imported.written.push(target.clone());
+            placed.insert(name.clone(), target);
+        }
+        match (unchanged, conflict, policy) {
+            (true, _, _) => imported.unchanged += 1,
+            (false, true, ConflictPolicy::Rename) => imported.renamed += 1,
+            (false, true, _) => imported.overwritten += 1,
+            (false, false, _) => {}
+        }
+    }
+
+    let recorded = ledger.entries()?;
+    for mut entry in archived_entries {
+        let name = Path::new(&entry.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Some(target) = placed.get(&name) else {
+            continue;
+        };
+        entry.path = target.to_string_lossy().to_string();
+        if recorded.iter().any(|existing| same_run(existing, &entry)) {
+            continue;
+        }
+        ledger.append(&entry)?;
+        imported.ledger_entries += 1;
+    }
+
+    Ok(imported)
+}
+
+/// Diary file names and contents, as archived
+type ArchivedFiles = Vec<(String, Vec<u8>)>;
+
+/// The diary files and ledger entries of an archive
+fn read_archive(archive: &Path) -> Result<(ArchivedFiles, Vec<LedgerEntry>)> {
+    let file =
+        File::open(archive).with_context(|| format!("Failed to open {}", archive.display()))?;
+    let mut tarball = tar::Archive::new(GzDecoder::new(file));
+
+    let mut files = Vec::new();
+    let mut entries = Vec::new();
+    for entry in tarball
+        .entries()
+        .with_context(|| format!("{} isn't a git-diary archive", archive.display()))?
+    {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+
+        if path == Path::new(ARCHIVE_LEDGER) {
+            for line in String::from_utf8_lossy(&data).lines() {
+                if !line.trim().is_empty() {
+                    entries
+                        .push(serde_json::from_str(line).context("Invalid archived ledger entry")?);
+                }
+            }
+            continue;
+        }
+        // Only plain file names below the diary directory, so nothing lands
+        // outside the directory it's imported into
+        let name = match path.strip_prefix(ARCHIVE_DIARY_DIR) {
+            Ok(name) if name.components().count() == 1 => name.to_string_lossy().to_string(),
+            _ => bail!("Unexpected file {} in archive", path.display()),
+        };
+        if name.starts_with('.') {
+            bail!("Unexpected file {} in archive", path.display());
+        }
+        files.push((name, data));
+    }
+
+    Ok((files, entries))
+}
+
+/// File name up to the first dot: a diary's and its sidecar's are the same
+fn stem(name: &str) -> &str {
+    name.split_once('.').map_or(name, |(stem, _)| stem)
+}
+
+/// `stem-imported`, or `stem-imported-2` and up, whichever no file of the
+/// group would collide with; one an earlier import wrote is used again
+fn free_stem(diary_dir: &Path, stem: &str, group: &[(String, Vec<u8>)]) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => format!("{}-imported", stem),
+            n => format!("{}-imported-{}", stem, n),
+        })
+        .find(|candidate| {
+            group.iter().all(|(name, data)| {
+                fs::read(diary_dir.join(format!("{}{}", candidate, &name[stem.len()..])))
+                    .map_or(true, |existing| existing == *data)
+            })
+        })
+        .unwrap_or_else(|| stem.to_string())
+}
+
+/// A path resolved through symlinks when it exists, to compare ledger paths
+fn same_file_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether two ledger entries record the same run, wherever its diary is now
+fn same_run(a: &LedgerEntry, b: &LedgerEntry) -> bool {
+    a.generated_at == b.generated_at
+        && a.start_date == b.start_date
+        && a.end_date == b.end_date
+        && a.model == b.model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::EntryKind;
+    use tempfile::TempDir;
+
+    fn entry(path: &Path, generated_at: i64) -> LedgerEntry {
+        LedgerEntry {
+            generated_at,
+            repo: "/src/app".to_string(),
+            start_date: "2024-01-01".to_string(),
+            end_date: "2024-01-07".to_string(),
+            end_time: None,
+            path: path.to_string_lossy().to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            prompt_tokens: 1000,
+            completion_tokens: 200,
+            cost: None,
+            feedback: None,
+            aborted: None,
+            kind: EntryKind::Diary,
+        }
+    }
+
+    #[test]
+    fn test_export_and_import_archive() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let old_dir = temp_dir.path().join("old");
+        fs::create_dir_all(&old_dir)?;
+        let diary = old_dir.join("git-diary-20240101-to-20240107.md");
+        fs::write(&diary, "# Week one")?;
+        fs::write(old_dir.join("git-diary-20240101-to-20240107.json"), "{}")?;
+        fs::write(old_dir.join("notes.txt"), "not a diary")?;
+        let old_ledger = Ledger::new(temp_dir.path().join("old-ledger.jsonl"));
+        old_ledger.append(&entry(&diary, 100))?;
+        old_ledger.append(&entry(Path::new("/elsewhere/other.md"), 200))?;
+
+        let archive = temp_dir.path().join("diaries.tar.gz");
+        let exported = export_archive(
+            &archive,
+            &DiaryStorageImpl::new(old_dir.to_string_lossy().to_string()),
+            &old_ledger,
+        )?;
+        assert_eq!(
+            exported,
+            Exported {
+                files: 2,
+                ledger_entries: 1
+            }
+        );
+
+        let new_dir = temp_dir.path().join("new");
+        let new_ledger = Ledger::new(temp_dir.path().join("new-ledger.jsonl"));
+        let imported = import_archive(&archive, &new_dir, &new_ledger, ConflictPolicy::Rename)?;
+        assert_eq!(imported.written.len(), 2);
+        assert_eq!(imported.ledger_entries, 1);
+        let restored = new_dir.join("git-diary-20240101-to-20240107.md");
+        assert_eq!(fs::read_to_string(&restored)?, "# Week one");
+        assert_eq!(
+            new_ledger.entries()?[0].path,
+            restored.to_string_lossy().to_string()
+        );
+
+        // Importing again changes nothing
+        let imported = import_archive(&archive, &new_dir, &new_ledger, ConflictPolicy::Rename)?;
+        assert_eq!(imported.unchanged, 1);
+        assert!(imported.written.is_empty());
+        assert_eq!(new_ledger.entries()?.len(), 1);
+
+        // A different diary by the same name is kept, and the archived one renamed
+        fs::write(&restored, "# Edited")?;
+        let imported = import_archive(&archive, &new_dir, &new_ledger, ConflictPolicy::Rename)?;
+        assert_eq!(imported.renamed, 1);
+        assert_eq!(fs::read_to_string(&restored)?, "# Edited");
+        assert!(new_dir
+            .join("git-diary-20240101-to-20240107-imported.md")
+            .exists());
+        assert!(new_dir
+            .join("git-diary-20240101-to-20240107-imported.json")
+            .exists());
+
+        let imported = import_archive(&archive, &new_dir, &new_ledger, ConflictPolicy::Rename)?;
+        assert_eq!(imported.unchanged, 1);
+
+        let imported = import_archive(&archive, &new_dir, &new_ledger, ConflictPolicy::Skip)?;
+        assert_eq!(imported.skipped, 1);
+        let imported = import_archive(&archive, &new_dir, &new_ledger, ConflictPolicy::Overwrite)?;
+        assert_eq!(imported.overwritten, 1);
+        assert_eq!(fs::read_to_string(&restored)?, "# Week one");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Serialize;
+use std::path::Path;
+
+use crate::domain::Commit;
+
+/// File extensions treated as images, compared case-insensitively
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "svg", "webp"];
+
+/// Folder next to the diaries that holds copied images
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// An image a commit changed and its message mentions, copied next to the diary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Attachment {
+    /// Path of the image in the repository
+    pub path: String,
+    /// Copy of the image relative to the diary, named after its content,
+    /// e.g. `attachments/3b18e512dba79e4c8300dd08aeb37f8e728b8dad.png`
+    pub file: String,
+}
+
+impl Attachment {
+    /// The copy of blob `blob_id` of the image at `path`; identical images
+    /// share one copy however often they are attached
+    pub fn new(path: String, blob_id: &str) -> Self {
+        let file = match Path::new(&path).extension() {
+            Some(extension) => format!(
+                "{}/{}.{}",
+                ATTACHMENTS_DIR,
+                blob_id,
+                extension.to_string_lossy().to_ascii_lowercase()
+            ),
+            None => format!("{}/{}", ATTACHMENTS_DIR, blob_id),
+        };
+        Self { path, file }
+    }
+}
+
+/// Images among the commit's files that its message refers to, by path or file name
+pub fn referenced_images(commit: &Commit) -> Vec<&str> {
+    let message = match &commit.body {
+        Some(body) => format!("{}\n{}", commit.message, body),
+        None => commit.message.clone(),
+    };
+    commit
+        .files
+        .iter()
+        .map(String::as_str)
+        .filter(|file| is_image(file))
+        .filter(|file| {
+            let name = Path::new(file)
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            message.contains(file) || (!name.is_empty() && message.contains(name.as_ref()))
+        })
+        .collect()
+}
+
+fn is_image(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| {
+        IMAGE_EXTENSIONS
+            .iter()
+            .any(|image| extension.eq_ignore_ascii_case(image))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_referenced_images() {
+        let commit = Commit::new("Document the sync flow".to_string(), 0)
+            .with_body("See sync-flow.PNG for the new retry path.".to_string())
+            .with_files(vec![
+                "docs/diagrams/sync-flow.PNG".to_string(),
+                "docs/diagrams/old-flow.png".to_string(),
+                "docs/sync.md".to_string(),
+            ]);
+        assert_eq!(
+            referenced_images(&commit),
+            vec!["docs/diagrams/sync-flow.PNG"]
+        );
+
+        let commit = Commit::new("Add docs/logo.svg".to_string(), 0)
+            .with_files(vec!["docs/logo.svg".to_string()]);
+        assert_eq!(referenced_images(&commit), vec!["docs/logo.svg"]);
+    }
+
+    #[test]
+    fn test_attachment_file_is_named_after_the_blob() {
+        assert_eq!(
+            Attachment::new("docs/Flow.PNG".to_string(), "3b18e512").file,
+            "attachments/3b18e512.png"
+        );
+    }
+}
@@ -0,0 +1,638 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+/// Code forges that git-diary can talk to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Forge {
+    #[cfg_attr(feature = "cli", value(name = "github"))]
+    GitHub,
+    #[cfg_attr(feature = "cli", value(name = "gitlab"))]
+    GitLab,
+    /// Bitbucket Cloud, or Bitbucket Server/Data Center with `auth.bitbucket.host`
+    #[cfg_attr(feature = "cli", value(name = "bitbucket"))]
+    Bitbucket,
+}
+
+impl Forge {
+    fn default_host(self) -> &'static str {
+        match self {
+            Forge::GitHub => "github.com",
+            Forge::GitLab => "gitlab.com",
+            Forge::Bitbucket => "bitbucket.org",
+        }
+    }
+
+    /// Environment variables checked for a token, in order
+    fn env_vars(self) -> &'static [&'static str] {
+        match self {
+            Forge::GitHub => &["GITHUB_TOKEN", "GH_TOKEN"],
+            Forge::GitLab => &["GITLAB_TOKEN", "GLAB_TOKEN"],
+            Forge::Bitbucket => &["BITBUCKET_TOKEN"],
+        }
+    }
+
+    /// CLI tool and arguments that print a stored token; Bitbucket has no such tool
+    fn cli_token_command(self, host: &str) -> Option<(&'static str, Vec<String>)> {
+        let command = match self {
+            Forge::GitHub => (
+                "gh",
+                vec![
+                    "auth".into(),
+                    "token".into(),
+                    "--hostname".into(),
+                    host.into(),
+                ],
+            ),
+            Forge::GitLab => (
+                "glab",
+                vec![
+                    "config".into(),
+                    "get".into(),
+                    "token".into(),
+                    "--host".into(),
+                    host.into(),
+                ],
+            ),
+            Forge::Bitbucket => return None,
+        };
+        Some(command)
+    }
+
+    /// User name sent with a token over HTTPS, as each forge documents for
+    /// access tokens in git remotes
+    #[cfg(feature = "git")]
+    fn token_username(self) -> &'static str {
+        match self {
+            Forge::GitHub => "x-access-token",
+            Forge::GitLab => "oauth2",
+            Forge::Bitbucket => "x-token-auth",
+        }
+    }
+
+    /// OAuth device authorization and token endpoints (RFC 8628); Bitbucket
+    /// doesn't offer the device flow
+    fn device_flow_endpoints(self, host: &str) -> Option<(String, String)> {
+        let endpoints = match self {
+            Forge::GitHub => (
+                format!("https://{}/login/device/code", host),
+                format!("https://{}/login/oauth/access_token", host),
+            ),
+            Forge::GitLab => (
+                format!("https://{}/oauth/authorize_device", host),
+                format!("https://{}/oauth/token", host),
+            ),
+            Forge::Bitbucket => return None,
+        };
+        Some(endpoints)
+    }
+
+    fn device_flow_scope(self) -> &'static str {
+        match self {
+            Forge::GitHub => "repo read:org",
+            Forge::GitLab => "read_api",
+            Forge::Bitbucket => "pullrequest",
+        }
+    }
+}
+
+impl Display for Forge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Forge::GitHub => write!(f, "GitHub"),
+            Forge::GitLab => write!(f, "GitLab"),
+            Forge::Bitbucket => write!(f, "Bitbucket"),
+        }
+    }
+}
+
+/// Per-forge authentication settings from the config file
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForgeAuthConfig {
+    /// Personal access token; takes precedence over every other source
+    pub token: Option<String>,
+    /// Host for self-hosted instances, e.g. `github.example.com`
+    pub host: Option<String>,
+    /// OAuth application client id, required for the device flow
+    pub oauth_client_id: Option<String>,
+}
+
+/// The `[auth]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub github: ForgeAuthConfig,
+    pub gitlab: ForgeAuthConfig,
+    pub bitbucket: ForgeAuthConfig,
+}
+
+impl AuthConfig {
+    pub fn forge(&self, forge: Forge) -> &ForgeAuthConfig {
+        match forge {
+            Forge::GitHub => &self.github,
+            Forge::GitLab => &self.gitlab,
+            Forge::Bitbucket => &self.bitbucket,
+        }
+    }
+}
+
+/// Where a credential was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CredentialSource {
+    Config,
+    Environment(&'static str),
+    Cli(&'static str),
+    DeviceFlow,
+}
+
+impl Display for CredentialSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CredentialSource::Config => write!(f, "config file"),
+            CredentialSource::Environment(var) => write!(f, "${}", var),
+            CredentialSource::Cli(tool) => write!(f, "{} CLI", tool),
+            CredentialSource::DeviceFlow => write!(f, "OAuth device flow"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Credential {
+    pub token: String,
+    pub source: CredentialSource,
+}
+
+/// Tokens obtained through the device flow, keyed by forge and host
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CredentialCache {
+    tokens: BTreeMap<String, String>,
+}
+
+/// Resolves forge credentials for every integration from one place
+///
+/// Sources are tried in order: config token, environment variables, the
+/// forge's own CLI (`gh`/`glab`), then tokens cached from a previous
+/// device-flow login.
+pub struct CredentialResolver {
+    config: AuthConfig,
+    cache_path: PathBuf,
+}
+
+impl CredentialResolver {
+    pub fn new(config: AuthConfig, cache_path: PathBuf) -> Self {
+        Self { config, cache_path }
+    }
+
+    pub fn host(&self, forge: Forge) -> String {
+        self.config
+            .forge(forge)
+            .host
+            .clone()
+            .unwrap_or_else(|| forge.default_host().to_string())
+    }
+
+    /// The forge whose configured or default host serves `url`
+    pub fn forge_for_url(&self, url: &str) -> Option<Forge> {
+        let host = crate::pulls::RemoteRepository::parse(url)?.host;
+        [Forge::GitHub, Forge::GitLab, Forge::Bitbucket]
+            .into_iter()
+            .find(|&forge| self.host(forge).eq_ignore_ascii_case(&host))
+    }
+
+    /// HTTPS credentials for the git remote at `url`: the forge's token with
+    /// the user name the forge expects, when `url` is on a known forge host
+    #[cfg(feature = "git")]
+    pub fn git_credential(&self, url: &str) -> Option<git2::Cred> {
+        let forge = self.forge_for_url(url)?;
+        let credential = self.resolve(forge).ok().flatten()?;
+        git2::Cred::userpass_plaintext(forge.token_username(), &credential.token).ok()
+    }
+
+    /// Looks up a credential without any user interaction
+    pub fn resolve(&self, forge: Forge) -> Result<Option<Credential>> {
+        self.resolve_with(
+            forge,
+            |name| std::env::var(name).ok(),
+            run_cli_token_command,
+        )
+    }
+
+    fn resolve_with(
+        &self,
+        forge: Forge,
+        env: impl Fn(&str) -> Option<String>,
+        cli: impl Fn(&str, &[String]) -> Option<String>,
+    ) -> Result<Option<Credential>> {
+        let forge_config = self.config.forge(forge);
+        let host = self.host(forge);
+
+        if let Some(token) = non_empty(forge_config.token.clone()) {
+            return Ok(Some(Credential {
+                token,
+                source: CredentialSource::Config,
+            }));
+        }
+
+        for var in forge.env_vars() {
+            if let Some(token) = non_empty(env(var)) {
+                return Ok(Some(Credential {
+                    token,
+                    source: CredentialSource::Environment(var),
+                }));
+            }
+        }
+
+        if let Some((tool, args)) = forge.cli_token_command(&host) {
+            if let Some(token) = non_empty(cli(tool, &args)) {
+                return Ok(Some(Credential {
+                    token,
+                    source: CredentialSource::Cli(tool),
+                }));
+            }
+        }
+
+        Ok(self
+            .load_cache()?
+            .tokens
+            .remove(&cache_key(forge, &host))
+            .map(|token| Credential {
+                token,
+                source: CredentialSource::DeviceFlow,
+            }))
+    }
+
+    /// Runs the OAuth device flow and caches the resulting token
+    pub async fn login(&self, forge: Forge) -> Result<Credential> {
+        let host = self.host(forge);
+        let Some((device_url, token_url)) = forge.device_flow_endpoints(&host) else {
+            bail!(
+                "{} doesn't support the device flow; set auth.{}.token or ${} to an access token",
+                forge,
+                forge.to_string().to_lowercase(),
+                forge.env_vars()[0]
+            );
+        };
+        let client_id = self
+            .config
+            .forge(forge)
+            .oauth_client_id
+            .clone()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Set auth.{}.oauth_client_id in the config to log in with the device flow",
+                    forge.to_string().to_lowercase()
+                )
+            })?;
+        let client = reqwest::Client::new();
+
+        let response = client
+            .post(&device_url)
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", client_id.as_str()),
+                ("scope", forge.device_flow_scope()),
+            ])
+            .send()
+            .await?
+            .text()
+            .await?;
+        let authorization: DeviceAuthorization = serde_json::from_str(&response)
+            .with_context(|| format!("Unexpected device authorization response: {}", response))?;
+
+        println!(
+            "🔑 Open {} and enter the code {}",
+            authorization.verification_uri, authorization.user_code
+        );
+
+        let mut interval = authorization.interval.unwrap_or(5);
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            let response = client
+                .post(&token_url)
+                .header("Accept", "application/json")
+                .form(&[
+                    ("client_id", client_id.as_str()),
+                    ("device_code", authorization.device_code.as_str()),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ])
+                .send()
+                .await?
+                .text()
+                .await?;
+
+            match parse_poll_response(&response)? {
+                PollOutcome::Token(token) => {
+                    let mut cache = self.load_cache()?;
+                    cache.tokens.insert(cache_key(forge, &host), token.clone());
+                    self.save_cache(&cache)?;
+
+                    return Ok(Credential {
+                        token,
+                        source: CredentialSource::DeviceFlow,
+                    });
+                }
+                PollOutcome::Pending => {}
+                PollOutcome::SlowDown => interval += 5,
+            }
+        }
+    }
+
+    fn load_cache(&self) -> Result<CredentialCache> {
+        if !self.cache_path.exists() {
+            return Ok(CredentialCache::default());
+        }
+
+        let raw =
+            fs::read_to_string(&self.cache_path).context("Failed to read credential cache")?;
+        serde_json::from_str(&raw)
+            .context("Credential cache is corrupt; delete it and log in again")
+    }
+
+    /// Writes the cache to a temporary file that only the owner can read,
+    /// then renames it into place, so the tokens are never readable by others
+    fn save_cache(&self, cache: &CredentialCache) -> Result<()> {
+        let dir = self
+            .cache_path
+            .parent()
+            .context("Credential cache path has no parent directory")?;
+        fs::create_dir_all(dir).context("Failed to create credential directory")?;
+        let file_name = self
+            .cache_path
+            .file_name()
+            .context("Credential cache path has no file name")?;
+        let temp_path = dir.join(format!(
+            ".{}.{}.tmp",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+
+        let written = (|| -> Result<()> {
+            // A stale file from a crashed run could have looser permissions
+            let _ = fs::remove_file(&temp_path);
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            let mut file = options.open(&temp_path)?;
+            file.write_all(serde_json::to_string_pretty(cache)?.as_bytes())?;
+            file.sync_all()?;
+            fs::rename(&temp_path, &self.cache_path)?;
+            Ok(())
+        })();
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        written.context("Failed to write credential cache")
+    }
+}
+
+/// Hides all but the last four characters of a token
+pub fn mask_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 4 {
+        return "****".to_string();
+    }
+    let visible: String = chars[chars.len() - 4..].iter().collect();
+    format!("****{}", visible)
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn cache_key(forge: Forge, host: &str) -> String {
+    format!("{}:{}", forge.to_string().to_lowercase(), host)
+}
+
+fn run_cli_token_command(tool: &str, args: &[String]) -> Option<String> {
+    let output = Command::new(tool).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceAuthorization {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    interval: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PollOutcome {
+    Token(String),
+    Pending,
+    SlowDown,
+}
+
+#[derive(Debug, Deserialize)]
+struct PollResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn parse_poll_response(raw: &str) -> Result<PollOutcome> {
+    let response: PollResponse =
+        serde_json::from_str(raw).with_context(|| format!("Unexpected token response: {}", raw))?;
+
+    if let Some(token) = response.access_token {
+        return Ok(PollOutcome::Token(token));
+    }
+
+    match response.error.as_deref() {
+        Some("authorization_pending") => Ok(PollOutcome::Pending),
+        Some("slow_down") => Ok(PollOutcome::SlowDown),
+        Some("expired_token") => bail!("The device code expired; run the login again"),
+        Some("access_denied") => bail!("Authorization was denied"),
+        Some(error) => bail!(
+            "Device flow failed: {} {}",
+            error,
+            response.error_description.unwrap_or_default()
+        ),
+        None => bail!("Token response had neither a token nor an error"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_resolver(config: AuthConfig, temp_dir: &TempDir) -> CredentialResolver {
+        CredentialResolver::new(config, temp_dir.path().join("credentials.json"))
+    }
+
+    #[test]
+    fn test_resolve_prefers_config_token() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config = AuthConfig {
+            github: ForgeAuthConfig {
+                token: Some("config-token".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let resolver = create_test_resolver(config, &temp_dir);
+
+        let credential = resolver
+            .resolve_with(
+                Forge::GitHub,
+                |_| Some("env-token".to_string()),
+                |_, _| None,
+            )?
+            .unwrap();
+
+        assert_eq!(credential.token, "config-token");
+        assert_eq!(credential.source, CredentialSource::Config);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_env_then_cli() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let resolver = create_test_resolver(AuthConfig::default(), &temp_dir);
+
+        let credential = resolver
+            .resolve_with(
+                Forge::GitHub,
+                |name| (name == "GH_TOKEN").then(|| "env-token".to_string()),
+                |_, _| Some("cli-token\n".to_string()),
+            )?
+            .unwrap();
+        assert_eq!(credential.source, CredentialSource::Environment("GH_TOKEN"));
+
+        let credential = resolver
+            .resolve_with(
+                Forge::GitLab,
+                |_| None,
+                |tool, args| {
+                    assert_eq!(tool, "glab");
+                    assert!(args.contains(&"gitlab.com".to_string()));
+                    Some("cli-token\n".to_string())
+                },
+            )?
+            .unwrap();
+        assert_eq!(credential.token, "cli-token");
+        assert_eq!(credential.source, CredentialSource::Cli("glab"));
+
+        // Bitbucket has no CLI to ask
+        assert!(resolver
+            .resolve_with(Forge::Bitbucket, |_| None, |_, _| panic!("no CLI"))?
+            .is_none());
+        let credential = resolver
+            .resolve_with(
+                Forge::Bitbucket,
+                |name| (name == "BITBUCKET_TOKEN").then(|| "bb-token".to_string()),
+                |_, _| None,
+            )?
+            .unwrap();
+        assert_eq!(credential.token, "bb-token");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_uses_cached_device_flow_token() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let resolver = create_test_resolver(AuthConfig::default(), &temp_dir);
+
+        assert!(resolver
+            .resolve_with(Forge::GitHub, |_| None, |_, _| None)?
+            .is_none());
+
+        let mut cache = CredentialCache::default();
+        cache
+            .tokens
+            .insert("github:github.com".to_string(), "cached-token".to_string());
+        resolver.save_cache(&cache)?;
+
+        let credential = resolver
+            .resolve_with(Forge::GitHub, |_| None, |_, _| None)?
+            .unwrap();
+        assert_eq!(credential.token, "cached-token");
+        assert_eq!(credential.source, CredentialSource::DeviceFlow);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(temp_dir.path().join("credentials.json"))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_forge_for_url() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = AuthConfig {
+            gitlab: ForgeAuthConfig {
+                host: Some("git.example.com".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let resolver = create_test_resolver(config, &temp_dir);
+
+        assert_eq!(
+            resolver.forge_for_url("https://github.com/jane/diary.git"),
+            Some(Forge::GitHub)
+        );
+        assert_eq!(
+            resolver.forge_for_url("git@git.example.com:team/diary.git"),
+            Some(Forge::GitLab)
+        );
+        assert_eq!(
+            resolver.forge_for_url("https://gitlab.com/team/diary.git"),
+            None
+        );
+        assert_eq!(resolver.forge_for_url("/srv/git/diary.git"), None);
+    }
+
+    #[test]
+    fn test_parse_poll_response() -> Result<()> {
+        assert_eq!(
+            parse_poll_response(r#"{"access_token": "abc", "token_type": "bearer"}"#)?,
+            PollOutcome::Token("abc".to_string())
+        );
+        assert_eq!(
+            parse_poll_response(r#"{"error": "authorization_pending"}"#)?,
+            PollOutcome::Pending
+        );
+        assert_eq!(
+            parse_poll_response(r#"{"error": "slow_down"}"#)?,
+            PollOutcome::SlowDown
+        );
+        assert!(parse_poll_response(r#"{"error": "access_denied"}"#).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mask_token() {
+        assert_eq!(mask_token("ghp_1234567890"), "****7890");
+        assert_eq!(mask_token("abc"), "****");
+    }
+}
@@ -0,0 +1,132 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+/// Most projects or languages listed by name; the rest are added up as "other"
+const MAX_SHARES_LISTED: usize = 5;
+
+/// Time a time tracker such as WakaTime recorded in the editor, including
+/// work that never made it into a commit
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CodingTime {
+    pub total_seconds: u64,
+    /// Time per project, most first
+    pub projects: Vec<TimeShare>,
+    /// Time per language, most first
+    pub languages: Vec<TimeShare>,
+}
+
+/// Time spent on one project or language
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TimeShare {
+    pub name: String,
+    pub seconds: u64,
+}
+
+impl Display for TimeShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, format_duration(self.seconds))
+    }
+}
+
+impl CodingTime {
+    /// Adds up seconds per project and per language, dropping anything under a minute
+    pub fn new(
+        total_seconds: f64,
+        projects: HashMap<String, f64>,
+        languages: HashMap<String, f64>,
+    ) -> Self {
+        Self {
+            total_seconds: total_seconds.round() as u64,
+            projects: shares(projects),
+            languages: shares(languages),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total_seconds < 60
+    }
+
+    /// The total followed by the split by project and by language, one line each
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} in total", format_duration(self.total_seconds))];
+        if !self.projects.is_empty() {
+            lines.push(format!("Projects: {}", list_shares(&self.projects)));
+        }
+        if !self.languages.is_empty() {
+            lines.push(format!("Languages: {}", list_shares(&self.languages)));
+        }
+        lines
+    }
+}
+
+fn shares(seconds: HashMap<String, f64>) -> Vec<TimeShare> {
+    let mut shares: Vec<TimeShare> = seconds
+        .into_iter()
+        .map(|(name, seconds)| TimeShare {
+            name,
+            seconds: seconds.round() as u64,
+        })
+        .filter(|share| share.seconds >= 60)
+        .collect();
+    shares.sort_by(|a, b| b.seconds.cmp(&a.seconds).then_with(|| a.name.cmp(&b.name)));
+    shares
+}
+
+fn list_shares(shares: &[TimeShare]) -> String {
+    let mut listed: Vec<String> = shares
+        .iter()
+        .take(MAX_SHARES_LISTED)
+        .map(ToString::to_string)
+        .collect();
+    let other: u64 = shares
+        .iter()
+        .skip(MAX_SHARES_LISTED)
+        .map(|share| share.seconds)
+        .sum();
+    if other > 0 {
+        listed.push(format!("other {}", format_duration(other)));
+    }
+    listed.join(", ")
+}
+
+/// `3h 20m`, or `45m` under an hour
+pub fn format_duration(seconds: u64) -> String {
+    let minutes = (seconds + 30) / 60;
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coding_time_lines() {
+        let projects = (1..=7)
+            .map(|i| (format!("project-{}", i), i as f64 * 600.0))
+            .chain([("scratch".to_string(), 20.0)])
+            .collect();
+        let languages = HashMap::from([
+            ("Rust".to_string(), 9000.0),
+            ("Markdown".to_string(), 2700.0),
+        ]);
+
+        let time = CodingTime::new(16820.0, projects, languages);
+
+        assert_eq!(
+            time.lines(),
+            vec![
+                "4h 40m in total",
+                "Projects: project-7 1h 10m, project-6 1h 0m, project-5 50m, project-4 40m, project-3 30m, other 30m",
+                "Languages: Rust 2h 30m, Markdown 45m",
+            ]
+        );
+        assert!(!time.is_empty());
+        assert!(CodingTime::new(30.0, HashMap::new(), HashMap::new()).is_empty());
+    }
+}
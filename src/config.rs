@@ -0,0 +1,401 @@
+use anyhow::{bail, Context, Result};
+use chrono::Locale;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ai::AiConfig;
+#[cfg(feature = "forge")]
+use crate::auth::AuthConfig;
+use crate::diary_repo::DiaryRepoConfig;
+use crate::domain::{MessageSource, WeekNumbering};
+use crate::empty::EmptyMessagePolicy;
+use crate::postprocess::{PostProcessConfig, PostProcessPipeline};
+use crate::prompt::PromptBudget;
+use crate::rewrite::{RewritePipeline, RewriteRuleConfig};
+#[cfg(feature = "forge")]
+use crate::tickets::TicketsConfig;
+#[cfg(feature = "timetrack")]
+use crate::timetrack::TimeTrackingConfig;
+
+/// Config file looked up in the current directory when `--config` is not given
+pub const DEFAULT_CONFIG_FILE: &str = ".git-diary.toml";
+
+/// Settings read from the TOML config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Hour (0-23) at which a new day starts, for people who work past midnight
+    pub day_cutoff_hour: u32,
+
+    /// Always scan commit messages for sensitive data, as with `--scan-sensitive`
+    pub scan_sensitive: bool,
+
+    /// Week numbering used in weekly rollup titles: `iso` or `us`
+    pub week_numbering: WeekNumbering,
+
+    /// Locale of day headings in weekly rollups, such as `de_DE`; English when unset
+    pub locale: Option<String>,
+
+    /// Similarity (0-100) above which a deleted and an added file count as a
+    /// rename; git's default of 50 when unset
+    pub rename_similarity: Option<u16>,
+
+    /// Always show a desktop notification when a diary is saved, as with `--notify`
+    pub notify: bool,
+
+    /// Always add the note on late-night and weekend commits, as with `--work-hours`
+    pub work_hours: bool,
+
+    /// Split squash merges with a bullet list into one entry per bullet; on when unset
+    pub expand_squash_merges: Option<bool>,
+
+    /// Collapse three or more dependency bumps into one entry; on when unset
+    pub group_dependency_updates: Option<bool>,
+
+    /// What to do with commits that have no message: `keep`, `drop` or `describe`
+    pub empty_messages: EmptyMessagePolicy,
+
+    /// Whether a diaries directory inside the repository goes into `.gitignore`:
+    /// `true` adds it without asking, `false` never asks; asks when unset
+    pub gitignore_diaries: Option<bool>,
+
+    /// Where squash merges are summarized from: `commit`, or `pull-request` for
+    /// the pull request's description when forge credentials are set up
+    pub message_source: MessageSource,
+
+    /// Goals the summary reports progress on, added to those in `goals.md`
+    /// next to the user config file
+    pub goals: Vec<String>,
+
+    /// Other emails and names you commit under, such as `["me@work.com", "Old Name <old@mail>"]`,
+    /// counted as one author and matched by `--mine`
+    pub identities: Vec<String>,
+
+    /// Share of the prompt each section may take, such as `{ total_tokens = 4000, commits = 0.7 }`;
+    /// sections are never cut when unset
+    pub prompt_budget: Option<PromptBudget>,
+
+    /// Tokens of commit diffs the summary prompt may quote, as with
+    /// `--diff-budget-tokens`; no diffs when unset
+    pub diff_budget_tokens: Option<u32>,
+
+    /// Project names the summary should use verbatim, mapped to an optional
+    /// description such as `SyncEngine = "the offline sync module"`
+    pub glossary: BTreeMap<String, String>,
+
+    /// Other clones whose commits go into this repository's diaries, such as
+    /// forks or mirrors, relative to the repository; commits found in several
+    /// are listed once
+    pub repos: Vec<PathBuf>,
+
+    /// JUnit XML reports of local test runs, such as `["target/nextest/**/junit.xml"]`,
+    /// relative to the repository; reports written in the period tell the summary
+    /// how the test suite fared
+    pub test_reports: Vec<String>,
+
+    /// Regex rewrite rules applied to commit messages, in order
+    pub rewrite: Vec<RewriteRuleConfig>,
+
+    /// Steps run over the AI summary, in order, such as `{ step = "wrap", width = 80 }`
+    pub post_process: Vec<PostProcessConfig>,
+
+    /// Ledger recording every generated diary; `ledger.jsonl` in the user data directory when unset
+    pub ledger: Option<PathBuf>,
+
+    /// How long diaries and ledger entries are kept by `git-diary prune`
+    pub retention: RetentionConfig,
+
+    /// Separate git repository every diary is also committed to
+    pub diary_repo: DiaryRepoConfig,
+
+    /// Extra headers and body fields for OpenAI-compatible gateways
+    pub ai: AiConfig,
+
+    /// Credentials for forge integrations
+    #[cfg(feature = "forge")]
+    pub auth: AuthConfig,
+
+    /// Accepted and ignored in builds without the `forge` feature
+    #[cfg(not(feature = "forge"))]
+    pub auth: Option<toml::Value>,
+
+    /// Time tracker the "Time by project and language" section is read from
+    #[cfg(feature = "timetrack")]
+    pub time_tracking: TimeTrackingConfig,
+
+    /// Accepted and ignored in builds without the `timetrack` feature
+    #[cfg(not(feature = "timetrack"))]
+    pub time_tracking: Option<toml::Value>,
+
+    /// Issue tracker the "Tickets" section is read from
+    #[cfg(feature = "forge")]
+    pub tickets: TicketsConfig,
+
+    /// Accepted and ignored in builds without the `forge` feature
+    #[cfg(not(feature = "forge"))]
+    pub tickets: Option<toml::Value>,
+}
+
+/// The `[retention]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Diaries that ended more than this many days ago are pruned
+    pub keep_days: Option<u32>,
+    /// Move pruned diaries to an `archive` folder instead of deleting them
+    pub archive: bool,
+}
+
+impl Config {
+    /// Loads the config from `path`, or from `.git-diary.toml` if it exists
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let default = PathBuf::from(DEFAULT_CONFIG_FILE);
+                if !default.exists() {
+                    return Ok(Self::default());
+                }
+                default
+            }
+        };
+
+        let raw = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let config =
+            Self::parse(&raw).with_context(|| format!("Invalid config file {}", path.display()))?;
+
+        Ok(config)
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        let config: Config = toml::from_str(raw)?;
+
+        if config.day_cutoff_hour > 23 {
+            bail!(
+                "day_cutoff_hour must be between 0 and 23, got {}",
+                config.day_cutoff_hour
+            );
+        }
+
+        if config
+            .rename_similarity
+            .is_some_and(|similarity| similarity > 100)
+        {
+            bail!(
+                "rename_similarity must be between 0 and 100, got {}",
+                config.rename_similarity.unwrap_or_default()
+            );
+        }
+
+        RewritePipeline::new(&config.rewrite)?;
+        PostProcessPipeline::new(&config.post_process)?;
+        config.ai.headers()?;
+
+        if let Some(locale) = &config.locale {
+            if Locale::try_from(locale.as_str()).is_err() {
+                bail!("Unknown locale {:?}", locale);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Locale for day headings, falling back to English
+    pub fn locale(&self) -> Locale {
+        self.locale
+            .as_deref()
+            .and_then(|locale| Locale::try_from(locale).ok())
+            .unwrap_or(Locale::en_US)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_config() -> Result<()> {
+        let config = Config::parse("day_cutoff_hour = 4\n")?;
+        assert_eq!(config.day_cutoff_hour, 4);
+
+        let config = Config::parse("")?;
+        assert_eq!(config.day_cutoff_hour, 0);
+        assert!(!config.scan_sensitive);
+
+        let config = Config::parse("scan_sensitive = true\n")?;
+        assert!(config.scan_sensitive);
+
+        let config = Config::parse("[retention]\nkeep_days = 180\narchive = true\n")?;
+        assert_eq!(config.retention.keep_days, Some(180));
+        assert!(config.retention.archive);
+
+        let config =
+            Config::parse("rename_similarity = 70\nexpand_squash_merges = false\nnotify = true\nempty_messages = \"describe\"\n")?;
+        assert_eq!(config.rename_similarity, Some(70));
+        assert_eq!(config.expand_squash_merges, Some(false));
+        assert_eq!(config.empty_messages, EmptyMessagePolicy::Describe);
+        assert!(config.notify);
+
+        let config = Config::parse("[prompt_budget]\ntotal_tokens = 4000\nnotes = 0.2\n")?;
+        let budget = config.prompt_budget.unwrap();
+        assert_eq!(budget.total_tokens, 4000);
+        assert_eq!(budget.notes, 0.2);
+        assert_eq!(budget.commits, PromptBudget::default().commits);
+
+        let config = Config::parse("message_source = \"pull-request\"\n")?;
+        assert_eq!(config.message_source, MessageSource::PullRequest);
+
+        let config = Config::parse("goals = [\"Ship the exporter\"]\n")?;
+        assert_eq!(config.goals, vec!["Ship the exporter"]);
+
+        let config = Config::parse("identities = [\"me@work.com\", \"Old Name <old@mail>\"]\n")?;
+        assert_eq!(
+            config.identities,
+            vec!["me@work.com", "Old Name <old@mail>"]
+        );
+
+        let config = Config::parse("[diary_repo]\npath = \"/home/me/diary\"\npush = true\n")?;
+        assert_eq!(
+            config.diary_repo.path.as_deref(),
+            Some(Path::new("/home/me/diary"))
+        );
+        assert!(config.diary_repo.push);
+        assert_eq!(config.diary_repo.remote, None);
+
+        let config = Config::parse("ledger = \"/var/lib/diary/ledger.jsonl\"\n")?;
+        assert_eq!(
+            config.ledger.as_deref(),
+            Some(Path::new("/var/lib/diary/ledger.jsonl"))
+        );
+
+        #[cfg(feature = "forge")]
+        {
+            let config = Config::parse("[auth.github]\nhost = \"github.example.com\"\n")?;
+            assert_eq!(
+                config.auth.github.host.as_deref(),
+                Some("github.example.com")
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_feature_tables() -> Result<()> {
+        // Loads whether or not this build has the features behind the tables
+        let config = Config::parse(
+            "[tickets]\nprovider = \"jira\"\njira_url = \"https://acme.atlassian.net\"\n",
+        )?;
+        #[cfg(feature = "forge")]
+        assert_eq!(
+            config.tickets.provider,
+            Some(crate::tickets::TicketTracker::Jira)
+        );
+        #[cfg(not(feature = "forge"))]
+        assert!(config.tickets.is_some());
+
+        let config = Config::parse(
+            "[auth.github]\nhost = \"github.example.com\"\n\n[time_tracking]\nprovider = \"wakatime\"\n",
+        )?;
+        #[cfg(feature = "forge")]
+        assert_eq!(
+            config.auth.github.host.as_deref(),
+            Some("github.example.com")
+        );
+        #[cfg(not(feature = "forge"))]
+        assert!(config.auth.is_some());
+        #[cfg(feature = "timetrack")]
+        assert_eq!(
+            config.time_tracking.provider,
+            Some(crate::timetrack::TimeTracker::Wakatime)
+        );
+        #[cfg(not(feature = "timetrack"))]
+        assert!(config.time_tracking.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rollup_settings() -> Result<()> {
+        let config = Config::parse("week_numbering = \"us\"\nlocale = \"de_DE\"\n")?;
+        assert_eq!(config.week_numbering, WeekNumbering::Us);
+        assert_eq!(config.locale(), Locale::de_DE);
+
+        let config = Config::parse("")?;
+        assert_eq!(config.week_numbering, WeekNumbering::Iso);
+        assert_eq!(config.locale(), Locale::en_US);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ai_config() -> Result<()> {
+        let config = Config::parse(
+            "[ai]\nrouting = [{ max_commits = 5, model = \"gpt-4o-mini\" }, { model = \"gpt-4o\" }]\n\n[ai.extra_headers]\nX-Team-Id = \"diary\"\n\n[ai.extra_body]\nuser = \"team-a\"\nmetadata = { tags = [\"diary\"] }\n",
+        )?;
+
+        assert_eq!(config.ai.extra_headers["X-Team-Id"], "diary");
+        assert_eq!(config.ai.routing.len(), 2);
+        assert_eq!(config.ai.routing[0].max_commits, Some(5));
+        assert_eq!(config.ai.routing[1].model, "gpt-4o");
+        assert_eq!(
+            serde_json::Value::Object(config.ai.extra_body),
+            serde_json::json!({"user": "team-a", "metadata": {"tags": ["diary"]}})
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rewrite_rules() -> Result<()> {
+        let config =
+            Config::parse(r#"rewrite = [{ match = "JIRA-(\\d+)", replace = "ticket $1" }]"#)?;
+
+        assert_eq!(config.rewrite.len(), 1);
+        assert_eq!(config.rewrite[0].pattern, r"JIRA-(\d+)");
+        assert!(Config::parse(r#"rewrite = [{ match = "(", replace = "" }]"#).is_err());
+
+        let config = Config::parse(
+            "[[post_process]]\nstep = \"wrap\"\nwidth = 72\n\n[[post_process]]\nstep = \"title\"\n",
+        )?;
+        assert_eq!(
+            config.post_process,
+            vec![
+                PostProcessConfig::Wrap { width: Some(72) },
+                PostProcessConfig::Title
+            ]
+        );
+        assert!(Config::parse("[[post_process]]\nstep = \"wrap\"\nwidth = 5\n").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_rejects_invalid_values() {
+        assert!(Config::parse("day_cutoff_hour = 24\n").is_err());
+        assert!(Config::parse("week_numbering = \"lunar\"\n").is_err());
+        assert!(Config::parse("locale = \"xx_YY\"\n").is_err());
+        assert!(Config::parse("rename_similarity = 101\n").is_err());
+        assert!(Config::parse("[ai.extra_headers]\n\"X Team\" = \"a\"\n").is_err());
+        assert!(Config::parse("unknown_key = true\n").is_err());
+    }
+
+    #[test]
+    fn test_load_config_from_path() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("config.toml");
+        fs::write(&path, "day_cutoff_hour = 3\n")?;
+
+        let config = Config::load(Some(&path))?;
+        assert_eq!(config.day_cutoff_hour, 3);
+
+        // An explicit path must exist
+        assert!(Config::load(Some(&temp_dir.path().join("missing.toml"))).is_err());
+
+        Ok(())
+    }
+}
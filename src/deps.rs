@@ -0,0 +1,277 @@
+use crate::domain::{Commit, LineStats};
+
+/// Fewest dependency updates that are collapsed into one entry
+pub const MIN_GROUPED_UPDATES: usize = 3;
+
+/// Dependencies named in the grouped entry; the rest are only counted
+const MAX_NAMED_UPDATES: usize = 3;
+
+/// Accounts whose commits are always dependency updates
+const UPDATE_BOTS: &[&str] = &["dependabot", "renovate"];
+
+/// One dependency bump recognized in a commit subject
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyUpdate {
+    pub name: String,
+    /// Version the dependency was moved to, when the subject says
+    pub version: Option<String>,
+}
+
+/// Recognizes dependabot and renovate style subjects such as
+/// `Bump tokio from 1.37.0 to 1.38.0` or `chore(deps): update rust crate serde to v1.0.203`
+///
+/// Loose phrasings like `Update foo to 2.0` only count when the commit comes
+/// from a bot or has a `deps` scope, so ordinary work isn't mistaken for a bump.
+pub fn parse_dependency_update(commit: &Commit) -> Option<DependencyUpdate> {
+    let (deps_scope, rest) = strip_deps_scope(commit.subject());
+    let from_bot = commit.author.as_ref().is_some_and(|author| {
+        let name = author.name.to_lowercase();
+        UPDATE_BOTS.iter().any(|bot| name.starts_with(bot))
+    });
+
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let verb = words.first()?.to_lowercase();
+    if !matches!(verb.as_str(), "bump" | "update" | "upgrade") {
+        return None;
+    }
+
+    let to = words.iter().rposition(|word| *word == "to")?;
+    let from = words[..to].iter().position(|word| *word == "from");
+    let name_end = from.unwrap_or(to);
+    let name = words[1..name_end]
+        .iter()
+        .rev()
+        .find(|word| !matches!(**word, "requirement" | "requirements"))?;
+    let version = words
+        .get(to + 1)
+        .map(|version| {
+            version
+                .trim_start_matches('v')
+                .trim_end_matches([',', '.', ';'])
+        })
+        .filter(|version| !version.is_empty());
+
+    let qualified = words[1..name_end]
+        .iter()
+        .any(|word| matches!(*word, "crate" | "dependency" | "module" | "requirement"));
+    let confident = deps_scope || from_bot || qualified || (verb == "bump" && from.is_some());
+    // A release commit bumps the project's own version, not a dependency
+    if !confident || name.eq_ignore_ascii_case("version") {
+        return None;
+    }
+
+    Some(DependencyUpdate {
+        name: name.trim_matches('`').to_string(),
+        version: version.map(str::to_string),
+    })
+}
+
+/// Splits `build(deps):` style prefixes off a subject, reporting whether one was there
+fn strip_deps_scope(subject: &str) -> (bool, &str) {
+    let Some((prefix, rest)) = subject.split_once(": ") else {
+        return (false, subject);
+    };
+    let scope = prefix
+        .split_once('(')
+        .and_then(|(_, scope)| scope.strip_suffix(')'));
+    match scope {
+        Some(scope) if scope.starts_with("deps") => (true, rest.trim_start()),
+        _ => (false, subject),
+    }
+}
+
+/// Collapses runs of dependency bumps into one synthetic entry
+///
+/// With at least `MIN_GROUPED_UPDATES` bumps in the list, they are replaced by
+/// a single commit such as `Updated 14 dependencies including tokio 1.38.0`,
+/// placed where the most recent bump was, so the prompt stays focused on
+/// the real work. Other commits are kept as they are.
+pub fn group_dependency_updates(commits: &[Commit]) -> Vec<Commit> {
+    let updates: Vec<Option<DependencyUpdate>> =
+        commits.iter().map(parse_dependency_update).collect();
+    let count = updates.iter().flatten().count();
+    if count < MIN_GROUPED_UPDATES {
+        return commits.to_vec();
+    }
+
+    let Some(newest) = updates.iter().rposition(Option::is_some) else {
+        return commits.to_vec();
+    };
+
+    let mut grouped = Vec::with_capacity(commits.len() - count + 1);
+    for (index, (commit, update)) in commits.iter().zip(&updates).enumerate() {
+        if index == newest {
+            grouped.push(grouped_commit(commits, &updates, newest));
+        } else if update.is_none() {
+            grouped.push(commit.clone());
+        }
+    }
+
+    grouped
+}
+
+fn grouped_commit(
+    commits: &[Commit],
+    updates: &[Option<DependencyUpdate>],
+    newest: usize,
+) -> Commit {
+    let bumped: Vec<(&Commit, &DependencyUpdate)> = commits
+        .iter()
+        .zip(updates)
+        .filter_map(|(commit, update)| Some((commit, update.as_ref()?)))
+        .collect();
+
+    // Most recent first, each dependency once
+    let mut named: Vec<&DependencyUpdate> = Vec::new();
+    for (_, update) in bumped.iter().rev() {
+        if !named.iter().any(|known| known.name == update.name) {
+            named.push(update);
+        }
+    }
+    let distinct = named.len();
+    let examples: Vec<String> = named
+        .iter()
+        .take(MAX_NAMED_UPDATES)
+        .map(|update| match &update.version {
+            Some(version) => format!("{} {}", update.name, version),
+            None => update.name.clone(),
+        })
+        .collect();
+
+    let template = &commits[newest];
+    let prefix = template
+        .reflog_action()
+        .map(|action| format!("{}: ", action))
+        .unwrap_or_default();
+
+    let mut commit = template.clone();
+    commit.message = match distinct {
+        1 => format!("{}Updated {}", prefix, examples[0]),
+        _ => format!(
+            "{}Updated {} dependencies including {}",
+            prefix,
+            distinct,
+            join_names(&examples)
+        ),
+    };
+    commit.body = None;
+    commit.id = None;
+    commit.renames = Vec::new();
+    commit.files = Vec::new();
+    for (bump, _) in &bumped {
+        for file in &bump.files {
+            if !commit.files.contains(file) {
+                commit.files.push(file.clone());
+            }
+        }
+    }
+    commit.line_stats = bumped
+        .iter()
+        .try_fold(LineStats::new(0, 0), |total, (bump, _)| {
+            let stats = bump.line_stats.as_ref()?;
+            Some(LineStats::new(
+                total.additions + stats.additions,
+                total.deletions + stats.deletions,
+            ))
+        });
+
+    commit
+}
+
+/// `a`, `a and b`, `a, b and c`
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Author;
+
+    fn commit(message: &str, time: i64) -> Commit {
+        Commit::new(message.to_string(), time)
+    }
+
+    #[test]
+    fn test_parse_dependency_update() {
+        let update = |message: &str| parse_dependency_update(&commit(message, 0));
+
+        assert_eq!(
+            update("commit: Bump tokio from 1.37.0 to 1.38.0"),
+            Some(DependencyUpdate {
+                name: "tokio".to_string(),
+                version: Some("1.38.0".to_string()),
+            })
+        );
+        assert_eq!(
+            update("chore(deps): update rust crate serde to v1.0.203"),
+            Some(DependencyUpdate {
+                name: "serde".to_string(),
+                version: Some("1.0.203".to_string()),
+            })
+        );
+        assert_eq!(
+            update("build(deps-dev): bump eslint from 8.0.0 to 9.1.0 in /web")
+                .map(|update| update.name),
+            Some("eslint".to_string())
+        );
+        assert_eq!(
+            update("Update clap requirement from 4.4 to 4.5").map(|update| update.name),
+            Some("clap".to_string())
+        );
+
+        assert_eq!(update("Update README to explain setup"), None);
+        assert_eq!(update("Bump version from 0.1.0 to 0.2.0"), None);
+        assert_eq!(update("Fix crash when bumping retries"), None);
+
+        let mut from_bot = commit("Update lodash to 4.17.21", 0);
+        from_bot.author = Some(Author::new(
+            "renovate[bot]".to_string(),
+            "bot@renovateapp.com".to_string(),
+        ));
+        assert_eq!(
+            parse_dependency_update(&from_bot).map(|update| update.name),
+            Some("lodash".to_string())
+        );
+    }
+
+    #[test]
+    fn test_group_dependency_updates() {
+        let mut serde = commit("commit: Bump serde from 1.0.200 to 1.0.203", 3);
+        serde.files = vec!["Cargo.lock".to_string()];
+        serde.line_stats = Some(LineStats::new(4, 4));
+        let mut tokio = commit("commit: Bump tokio from 1.37.0 to 1.38.0", 4);
+        tokio.files = vec!["Cargo.lock".to_string(), "Cargo.toml".to_string()];
+        tokio.line_stats = Some(LineStats::new(5, 5));
+
+        let commits = vec![
+            commit("commit: Bump clap from 4.4.0 to 4.5.0", 1),
+            commit("commit: Add retry loop", 2),
+            serde,
+            tokio,
+            commit("commit: Fix cache", 5),
+        ];
+
+        let grouped = group_dependency_updates(&commits);
+
+        let messages: Vec<&str> = grouped.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "commit: Add retry loop",
+                "commit: Updated 3 dependencies including tokio 1.38.0, serde 1.0.203 and clap 4.5.0",
+                "commit: Fix cache",
+            ]
+        );
+        assert_eq!(grouped[1].timestamp(), 4);
+        assert_eq!(grouped[1].files, vec!["Cargo.lock", "Cargo.toml"]);
+        assert_eq!(grouped[1].line_stats, None);
+
+        // Below the threshold nothing changes
+        assert_eq!(group_dependency_updates(&commits[..3]).len(), 3);
+    }
+}
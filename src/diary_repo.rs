@@ -0,0 +1,357 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::attachments::Attachment;
+#[cfg(feature = "forge")]
+use crate::auth::CredentialResolver;
+use crate::domain::{BranchIndex, DiaryContent, DiaryStorage};
+use crate::git::commit_files;
+
+/// Remote pushed to when the config doesn't name one
+pub const DEFAULT_REMOTE: &str = "origin";
+
+/// How often a push asks for credentials before giving up, so a rejected
+/// key doesn't make libgit2 retry forever
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// The `[diary_repo]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiaryRepoConfig {
+    /// Git repository every diary is also committed to; created when missing,
+    /// and off when unset
+    pub path: Option<PathBuf>,
+    /// Push to the remote after each commit
+    pub push: bool,
+    /// Remote to push to; `origin` when unset
+    pub remote: Option<String>,
+}
+
+/// Saves diaries with the wrapped storage, then commits a copy to a separate
+/// diary repository, one folder per source repository, and optionally pushes it
+///
+/// The diary is already saved when the copy is made, so failing to commit or
+/// push only warrants a warning.
+pub struct DiaryRepoStorage<S> {
+    inner: S,
+    repo_path: PathBuf,
+    folder: String,
+    remote: Option<String>,
+    #[cfg(feature = "forge")]
+    credentials: Option<CredentialResolver>,
+}
+
+impl<S: DiaryStorage> DiaryRepoStorage<S> {
+    /// Commits copies to `folder` of the repository at `repo_path`
+    pub fn new(inner: S, repo_path: PathBuf, folder: String) -> Self {
+        Self {
+            inner,
+            repo_path,
+            folder,
+            remote: None,
+            #[cfg(feature = "forge")]
+            credentials: None,
+        }
+    }
+
+    /// Pushes the current branch to `remote` after each commit
+    pub fn with_push(mut self, remote: String) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Pushes to HTTPS remotes on forge hosts with the forge's token
+    #[cfg(feature = "forge")]
+    pub fn with_credentials(mut self, credentials: CredentialResolver) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// HTTPS credentials from the forge token for `url`, if one is set up
+    fn forge_credential(&self, _url: &str) -> Option<git2::Cred> {
+        #[cfg(feature = "forge")]
+        if let Some(credentials) = &self.credentials {
+            return credentials.git_credential(_url);
+        }
+        None
+    }
+
+    /// Copies `path`, its JSON sidecar, if any, and its `attachments` into the
+    /// diary repository and commits them
+    ///
+    /// Attachments keep their place relative to the diary, so its image links
+    /// resolve in the diary repository too.
+    fn archive(&self, path: &str, period: &str, attachments: &[Attachment]) -> Result<()> {
+        let repo = open_or_init(&self.repo_path)?;
+        let folder = self.repo_path.join(&self.folder);
+        fs::create_dir_all(&folder)
+            .with_context(|| format!("Failed to create {}", folder.display()))?;
+
+        let path = Path::new(path);
+        let sidecar = path.with_extension("json");
+        let mut sources = vec![path];
+        if sidecar.is_file() {
+            sources.push(&sidecar);
+        }
+        let mut files = Vec::new();
+        for source in sources {
+            let file_name = source.file_name().context("Diary path has no file name")?;
+            fs::copy(source, folder.join(file_name))
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+            files.push(Path::new(&self.folder).join(file_name));
+        }
+        let diary_dir = path.parent().unwrap_or(Path::new(""));
+        for attachment in attachments {
+            let source = diary_dir.join(&attachment.file);
+            let copy = folder.join(&attachment.file);
+            if let Some(dir) = copy.parent() {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            fs::copy(&source, &copy)
+                .with_context(|| format!("Failed to copy {}", source.display()))?;
+            files.push(Path::new(&self.folder).join(&attachment.file));
+        }
+
+        let repo_path = self.repo_path.to_string_lossy();
+        let message = format!("Add {} diary for {}", self.folder, period);
+        let hash = commit_files(&repo_path, &files, &message)?;
+        println!("📚 Committed diary to {} as {}", repo_path, hash);
+
+        if let Some(remote) = &self.remote {
+            push(&repo, remote, |url| self.forge_credential(url))?;
+            println!("🚀 Pushed diary repository to {}", remote);
+        }
+        Ok(())
+    }
+
+    fn archive_or_warn(&self, path: &str, period: &str, attachments: &[Attachment]) {
+        if let Err(e) = self.archive(path, period, attachments) {
+            eprintln!(
+                "⚠️ Failed to commit the diary to {}: {:#}",
+                self.repo_path.display(),
+                e
+            );
+        }
+    }
+}
+
+impl<S: DiaryStorage> DiaryStorage for DiaryRepoStorage<S> {
+    fn save_diary(&self, content: &DiaryContent) -> Result<String> {
+        let path = self.inner.save_diary(content)?;
+        let period = format!("{} – {}", content.start_date, content.end_date);
+        self.archive_or_warn(&path, &period, &content.attachments);
+        Ok(path)
+    }
+
+    fn generate_file_name(&self, content: &DiaryContent) -> String {
+        self.inner.generate_file_name(content)
+    }
+
+    fn format_content(&self, content: &DiaryContent) -> String {
+        self.inner.format_content(content)
+    }
+
+    fn save_branch_index(&self, index: &BranchIndex) -> Result<String> {
+        let path = self.inner.save_branch_index(index)?;
+        let period = format!("{} – {}", index.start_date, index.end_date);
+        self.archive_or_warn(&path, &period, &[]);
+        Ok(path)
+    }
+}
+
+/// Opens the diary repository, creating it on first use
+fn open_or_init(path: &Path) -> Result<git2::Repository> {
+    let repo = if path.exists() {
+        git2::Repository::open(path)
+    } else {
+        git2::Repository::init(path)
+    };
+    repo.with_context(|| format!("Failed to open diary repository {}", path.display()))
+}
+
+/// Pushes the checked-out branch, authenticating with the SSH agent, the
+/// forge token from `forge_credential`, or git's credential helpers
+fn push(
+    repo: &git2::Repository,
+    remote_name: &str,
+    forge_credential: impl Fn(&str) -> Option<git2::Cred>,
+) -> Result<()> {
+    let head = repo.head()?;
+    let branch = head
+        .name()
+        .context("HEAD of the diary repository is not a branch")?;
+    let mut remote = repo
+        .find_remote(remote_name)
+        .with_context(|| format!("The diary repository has no remote '{}'", remote_name))?;
+
+    let config = repo.config()?;
+    let mut attempts = 0;
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str(
+                "No working credentials; add your key to ssh-agent or configure a git credential helper",
+            ));
+        }
+        if allowed.contains(git2::CredentialType::SSH_KEY) {
+            if let Some(username) = username {
+                return git2::Cred::ssh_key_from_agent(username);
+            }
+        }
+        if allowed.contains(git2::CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some(credential) = forge_credential(url) {
+                return Ok(credential);
+            }
+            return git2::Cred::credential_helper(&config, url, username);
+        }
+        git2::Cred::default()
+    });
+    callbacks.push_update_reference(|reference, status| match status {
+        Some(message) => Err(git2::Error::from_str(&format!(
+            "{} was rejected: {}",
+            reference, message
+        ))),
+        None => Ok(()),
+    });
+
+    let mut options = git2::PushOptions::new();
+    options.remote_callbacks(callbacks);
+    remote
+        .push(&[format!("{}:{}", branch, branch)], Some(&mut options))
+        .with_context(|| format!("Failed to push the diary repository to {}", remote_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{CommitOrder, MockDiaryStorage};
+    use tempfile::TempDir;
+
+    fn content() -> DiaryContent {
+        DiaryContent {
+            commits: Vec::new(),
+            summary: "Fixed the parser".to_string(),
+            start_date: "2024-05-06".to_string(),
+            end_date: "2024-05-06".to_string(),
+            rollup: None,
+            notes: Vec::new(),
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::Chronological,
+        }
+    }
+
+    #[test]
+    fn test_commits_and_pushes_diaries() -> Result<()> {
+        let work_dir = TempDir::new()?;
+        let diary = work_dir.path().join("git-diary-20240506-to-20240506.md");
+        fs::write(&diary, "# Diary")?;
+        fs::write(diary.with_extension("json"), "{}")?;
+
+        let remote_dir = TempDir::new()?;
+        git2::Repository::init_bare(remote_dir.path())?;
+        let repo_dir = TempDir::new()?;
+        let repo_path = repo_dir.path().join("diaries");
+        let repo = git2::Repository::init(&repo_path)?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Test User")?;
+        config.set_str("user.email", "test@example.com")?;
+        repo.remote("origin", &remote_dir.path().to_string_lossy())?;
+
+        let mut inner = MockDiaryStorage::new();
+        let saved = diary.to_string_lossy().to_string();
+        inner
+            .expect_save_diary()
+            .returning(move |_| Ok(saved.clone()));
+        let storage = DiaryRepoStorage::new(inner, repo_path.clone(), "app".to_string())
+            .with_push(DEFAULT_REMOTE.to_string());
+
+        assert_eq!(storage.save_diary(&content())?, diary.to_string_lossy());
+
+        let head = repo.head()?.peel_to_commit()?;
+        assert_eq!(
+            head.message(),
+            Some("Add app diary for 2024-05-06 – 2024-05-06")
+        );
+        let tree = head.tree()?;
+        assert!(tree
+            .get_path(Path::new("app/git-diary-20240506-to-20240506.md"))
+            .is_ok());
+        assert!(tree
+            .get_path(Path::new("app/git-diary-20240506-to-20240506.json"))
+            .is_ok());
+
+        let pushed = git2::Repository::open_bare(remote_dir.path())?;
+        let branch = head_branch(&repo)?;
+        assert_eq!(pushed.refname_to_id(&branch)?, head.id());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commits_attachments_next_to_the_diary() -> Result<()> {
+        let work_dir = TempDir::new()?;
+        let diary = work_dir.path().join("git-diary-20240506-to-20240506.md");
+        fs::write(&diary, "![docs/flow.png](attachments/3b18e512.png)")?;
+        fs::create_dir_all(work_dir.path().join("attachments"))?;
+        fs::write(work_dir.path().join("attachments/3b18e512.png"), "png")?;
+
+        let repo_dir = TempDir::new()?;
+        let repo = git2::Repository::init(repo_dir.path())?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Test User")?;
+        config.set_str("user.email", "test@example.com")?;
+
+        let mut inner = MockDiaryStorage::new();
+        let saved = diary.to_string_lossy().to_string();
+        inner
+            .expect_save_diary()
+            .returning(move |_| Ok(saved.clone()));
+        let storage =
+            DiaryRepoStorage::new(inner, repo_dir.path().to_path_buf(), "app".to_string());
+        let mut content = content();
+        content.attachments = vec![Attachment::new("docs/flow.png".to_string(), "3b18e512")];
+        storage.save_diary(&content)?;
+
+        let tree = repo.head()?.peel_to_commit()?.tree()?;
+        let image = tree.get_path(Path::new("app/attachments/3b18e512.png"))?;
+        assert_eq!(image.to_object(&repo)?.peel_to_blob()?.content(), b"png");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_failing_commit_keeps_the_saved_diary() -> Result<()> {
+        let file = tempfile::NamedTempFile::new()?;
+        let mut inner = MockDiaryStorage::new();
+        inner
+            .expect_save_diary()
+            .returning(|_| Ok("missing/diary.md".to_string()));
+        // A file where the repository should be can't be opened or created
+        let storage = DiaryRepoStorage::new(inner, file.path().to_path_buf(), "app".to_string());
+
+        assert_eq!(storage.save_diary(&content())?, "missing/diary.md");
+
+        Ok(())
+    }
+
+    fn head_branch(repo: &git2::Repository) -> Result<String> {
+        Ok(repo.head()?.name().unwrap_or_default().to_string())
+    }
+}
@@ -1,393 +1,4257 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use chrono::{DateTime, Local};
+use chrono::{
+    DateTime, Datelike, Days, Duration, Local, LocalResult, Locale, Months, NaiveDate,
+    NaiveDateTime, NaiveTime, Offset, TimeZone, Timelike, Weekday,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt::Display;
 use std::sync::Arc;
 
+use crate::attachments::Attachment;
+use crate::codingtime::CodingTime;
+use crate::deps::group_dependency_updates;
+use crate::empty::{apply_empty_message_policy, EmptyMessagePolicy};
+use crate::health::RepoHealth;
+use crate::identity::Identities;
+use crate::language::{detect_language, AUTO_LANGUAGE};
+use crate::pathfilter::PathFilter;
+use crate::perf::{performance_claims, PerformanceClaim};
+use crate::postprocess::PostProcessPipeline;
+use crate::prompt::{build_user_prompt, language_name, PromptBudget, SummaryLength};
+use crate::rewrite::RewritePipeline;
+use crate::routing::{route_model, RoutingRule};
+use crate::scan::{format_report, scan_commits, SensitiveScan};
+use crate::scope::infer_scopes;
+use crate::sessions::{part_of_day, split_sessions};
+use crate::squash::expand_squash_merges;
+use crate::structured::split_topics;
+use crate::testruns::{TestHealth, TestRun};
+use crate::worktime::WorkHours;
+
 #[cfg(test)]
 use mockall::{automock, predicate::*};
 
 // Core domain types
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Commit {
     pub message: String,
     time: i64,
+    /// Author of the commit, when the source records one
+    pub author: Option<Author>,
+    /// Timestamp the commit claimed before it was clamped for lying in the future
+    pub claimed_time: Option<i64>,
+    /// Files the commit moved or copied, when rename detection ran
+    pub renames: Vec<FileRename>,
+    /// Paths the commit changed, when the source provides them
+    pub files: Vec<String>,
+    /// Area of the code base inferred from `files`, for non-conventional commits
+    pub scope: Option<String>,
+    /// Message lines after the subject, when the source reads them separately
+    pub body: Option<String>,
+    /// Subject of the squash merge this entry was split out of
+    pub squashed_from: Option<String>,
+    /// Full hash of the commit, when the source knows it
+    pub id: Option<String>,
+    /// Lines added and removed, when the source diffs the commit
+    pub line_stats: Option<LineStats>,
+    /// Patch against the first parent, when the source was asked for diffs
+    #[serde(skip)]
+    pub diff: Option<String>,
+    /// When the change was originally written, which a rebase keeps
+    pub author_time: Option<i64>,
+    /// Author's offset from UTC in minutes, when the source records it
+    pub author_offset: Option<i32>,
+    /// Branch the commit was made on, when the source records it
+    pub branch: Option<String>,
+    /// Recorded only in the reflog, such as a commit that was later amended or
+    /// rebased away; only set when history and reflog are merged
+    pub reflog_only: bool,
 }
 
 impl Commit {
     pub fn new(message: String, time: i64) -> Self {
-        Self { message, time }
+        Self {
+            message,
+            time,
+            author: None,
+            claimed_time: None,
+            renames: Vec::new(),
+            files: Vec::new(),
+            scope: None,
+            body: None,
+            squashed_from: None,
+            id: None,
+            line_stats: None,
+            diff: None,
+            author_time: None,
+            author_offset: None,
+            branch: None,
+            reflog_only: false,
+        }
+    }
+
+    pub fn with_author(mut self, author: Author) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    pub fn with_renames(mut self, renames: Vec<FileRename>) -> Self {
+        self.renames = renames;
+        self
+    }
+
+    pub fn with_files(mut self, files: Vec<String>) -> Self {
+        self.files = files;
+        self
+    }
+
+    pub fn with_body(mut self, body: String) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn with_line_stats(mut self, line_stats: LineStats) -> Self {
+        self.line_stats = Some(line_stats);
+        self
+    }
+
+    pub fn with_diff(mut self, diff: String) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+
+    pub fn with_author_time(mut self, author_time: i64) -> Self {
+        self.author_time = Some(author_time);
+        self
+    }
+
+    /// Records the author's offset from UTC, e.g. `120` for UTC+02:00
+    pub fn with_author_offset(mut self, minutes: i32) -> Self {
+        self.author_offset = Some(minutes);
+        self
+    }
+
+    pub fn with_branch(mut self, branch: String) -> Self {
+        self.branch = Some(branch);
+        self
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.time
+    }
+
+    /// Returns the commit subject without a reflog action prefix such as `commit: `
+    pub fn subject(&self) -> &str {
+        match self.reflog_action() {
+            Some(action) => &self.message[action.len() + 2..],
+            None => &self.message,
+        }
+    }
+
+    /// Returns the reflog action the message starts with, such as `commit (amend)`
+    pub fn reflog_action(&self) -> Option<&str> {
+        self.message
+            .split_once(": ")
+            .map(|(action, _)| action)
+            .filter(|action| is_reflog_action(action))
+    }
+
+    /// Returns the type of a conventional commit subject such as `feat(cli)!: ...`
+    pub fn conventional_type(&self) -> Option<String> {
+        let (prefix, _) = self.subject().split_once(':')?;
+        let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+        let kind = match prefix.split_once('(') {
+            Some((kind, scope)) if scope.ends_with(')') => kind,
+            Some(_) => return None,
+            None => prefix,
+        };
+
+        (!kind.is_empty() && kind.chars().all(|c| c.is_ascii_lowercase())).then(|| kind.to_string())
+    }
+
+    /// Returns the pull request number from a squash-merge suffix such as
+    /// `Add export (#123)`, a `Merge pull request #123 from ...` subject, or
+    /// Bitbucket Cloud's `Merged in feature (pull request #123)`
+    pub fn pull_request(&self) -> Option<u64> {
+        let subject = self.squashed_from.as_deref().unwrap_or(self.subject());
+        let subject = subject.lines().next().unwrap_or_default().trim_end();
+        let number = match subject.strip_prefix("Merge pull request #") {
+            Some(rest) => rest.split(' ').next()?,
+            None => {
+                let rest = subject.strip_suffix(')')?;
+                match rest.rsplit_once("(pull request #") {
+                    Some((_, number)) => number,
+                    None => rest.rsplit_once("(#")?.1,
+                }
+            }
+        };
+        number.parse().ok()
+    }
+
+    /// Returns the subject of the commit this one reverts, if it is a revert
+    pub fn reverted_subject(&self) -> Option<&str> {
+        self.subject()
+            .strip_prefix("Revert \"")
+            .and_then(|rest| rest.strip_suffix('"'))
     }
 
     pub fn datetime(&self) -> Option<String> {
-        let datetime = DateTime::from_timestamp(self.time, 0);
-        datetime.map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        format_timestamp(self.time)
+    }
+
+    /// Switches the commit's time to the one `order` picks; commits without an
+    /// author time keep their committer time
+    pub fn apply_date_order(&mut self, order: DateOrder) {
+        let Some(author_time) = self.author_time else {
+            return;
+        };
+        self.time = match order {
+            DateOrder::Committer => self.time,
+            DateOrder::Author => author_time,
+            DateOrder::Newest => self.time.max(author_time),
+        };
+    }
+
+    /// Moves a future-dated commit back to `time`, remembering the date it claimed
+    pub fn clamp_to(&mut self, time: i64) {
+        if self.time > time {
+            self.claimed_time.get_or_insert(self.time);
+            self.time = time;
+        }
     }
 }
 
-impl Display for Commit {
+fn format_timestamp(timestamp: i64) -> Option<String> {
+    DateTime::from_timestamp(timestamp, 0).map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Lines a commit added and removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LineStats {
+    pub additions: usize,
+    pub deletions: usize,
+}
+
+impl LineStats {
+    pub fn new(additions: usize, deletions: usize) -> Self {
+        Self {
+            additions,
+            deletions,
+        }
+    }
+}
+
+/// A file moved or copied by a commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileRename {
+    pub from: String,
+    pub to: String,
+    /// The original file was kept
+    pub copied: bool,
+}
+
+impl FileRename {
+    pub fn new(from: String, to: String) -> Self {
+        Self {
+            from,
+            to,
+            copied: false,
+        }
+    }
+
+    pub fn copy(from: String, to: String) -> Self {
+        Self {
+            from,
+            to,
+            copied: true,
+        }
+    }
+}
+
+/// Name and email of a commit author
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+}
+
+impl Author {
+    pub fn new(name: String, email: String) -> Self {
+        Self { name, email }
+    }
+
+    /// Parses the `Name <email>` part of an identity line such as
+    /// `Jane Doe <jane@example.com> 1704067200 +0000`
+    pub fn parse_ident(ident: &str) -> Option<Self> {
+        let (name, rest) = ident.split_once('<')?;
+        let (email, _) = rest.split_once('>')?;
+
+        Some(Self::new(name.trim().to_string(), email.trim().to_string()))
+    }
+}
+
+/// Keeps only commits by one person, as identified by git's `user.name` and `user.email`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthorFilter {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl AuthorFilter {
+    /// Matches on email, ignoring case, or on the exact name
+    ///
+    /// Either is enough, so commits made with a different email on another
+    /// machine are still found by name.
+    pub fn matches(&self, author: &Author) -> bool {
+        let email_matches = self
+            .email
+            .as_ref()
+            .is_some_and(|email| email.eq_ignore_ascii_case(&author.email));
+        let name_matches = self.name.as_ref().is_some_and(|name| *name == author.name);
+
+        email_matches || name_matches
+    }
+}
+
+/// A project name the summary should use exactly as written
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GlossaryTerm {
+    pub term: String,
+    /// What the term refers to, for curated terms
+    pub description: Option<String>,
+}
+
+impl GlossaryTerm {
+    pub fn new(term: String) -> Self {
+        Self {
+            term,
+            description: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+}
+
+/// A note the developer added by hand with `git-diary note`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Note {
+    pub text: String,
+    pub time: i64,
+}
+
+impl Note {
+    pub fn new(text: String, time: i64) -> Self {
+        Self { text, time }
+    }
+}
+
+impl Display for Note {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let datetime = DateTime::from_timestamp(self.time, 0)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string());
         write!(
             f,
             "{}: {}",
-            self.datetime().unwrap_or("Invalid Date".to_string()),
-            self.message
+            datetime.unwrap_or("Invalid Date".to_string()),
+            self.text
         )
     }
 }
 
-#[derive(Debug)]
-pub struct DiaryContent {
+fn is_reflog_action(action: &str) -> bool {
+    action == "commit"
+        || action == "revert"
+        || action == "cherry-pick"
+        || (action.starts_with("commit (") && action.ends_with(')'))
+}
+
+/// Collapses revert commits together with the commits they revert
+///
+/// Each matched pair is replaced by a single entry noting that the work was
+/// attempted and reverted, so the summary does not report it as shipped.
+/// Reverts of commits outside the list are kept as they are.
+pub fn pair_reverts(commits: &[Commit]) -> Vec<Commit> {
+    let mut paired = vec![false; commits.len()];
+    let mut result = Vec::new();
+
+    for (i, commit) in commits.iter().enumerate() {
+        if paired[i] {
+            continue;
+        }
+
+        let original = commit.reverted_subject().and_then(|subject| {
+            (0..commits.len()).find(|&j| j != i && !paired[j] && commits[j].subject() == subject)
+        });
+
+        match original {
+            Some(j) => {
+                paired[j] = true;
+                let mut attempted = Commit::new(
+                    format!("Attempted and reverted: {}", commits[j].subject()),
+                    commit.time,
+                );
+                attempted.author = commit.author.clone();
+                result.push(attempted);
+            }
+            None => result.push(commit.clone()),
+        }
+    }
+
+    result
+}
+
+impl Display for Commit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: ",
+            self.datetime().unwrap_or("Invalid Date".to_string())
+        )?;
+        if let Some(scope) = &self.scope {
+            write!(f, "[{}] ", scope)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(claimed) = self.claimed_time.and_then(format_timestamp) {
+            write!(f, " (⚠️ future-dated: claims {})", claimed)?;
+        }
+        if self.reflog_only {
+            write!(f, " (reflog only)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Calendar unit used when computing a diary range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    Day,
+    /// A week beginning on the given weekday
+    Week(Weekday),
+    Month,
+}
+
+/// Which timestamp of a commit decides the period it falls in and the time shown
+///
+/// Rebases rewrite committer dates but keep author dates, so `committer`
+/// lists rebased work on the day of the rebase and `author` on the day it
+/// was first written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum DateOrder {
+    Author,
+    /// When the commit was made, or when HEAD moved to it for reflog entries
+    #[default]
+    Committer,
+    /// The later of the two
+    Newest,
+}
+
+/// Where the text summarized for a squash-merged pull request comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum MessageSource {
+    /// The commit message as written
+    #[default]
+    Commit,
+    /// The pull request's description from the forge, when it has one
+    PullRequest,
+}
+
+/// Longest pull request description sent in place of a commit message, in characters
+pub const MAX_DESCRIPTION_CHARS: usize = 2000;
+
+/// Order of the commit log section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum CommitOrder {
+    /// Oldest first
+    #[default]
+    Chronological,
+    /// Newest first
+    Reverse,
+    /// Most lines changed first; commits without line stats last
+    Impact,
+}
+
+impl CommitOrder {
+    /// Returns `commits` in this order
+    ///
+    /// The sorts are stable, so commits that tie keep their order in the
+    /// source and impact ties stay oldest first.
+    pub fn ordered(self, commits: &[Commit]) -> Vec<&Commit> {
+        let mut ordered: Vec<&Commit> = commits.iter().collect();
+        match self {
+            CommitOrder::Chronological => ordered.sort_by_key(|commit| commit.timestamp()),
+            CommitOrder::Reverse => {
+                ordered.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp()))
+            }
+            CommitOrder::Impact => {
+                ordered.sort_by_key(|commit| commit.timestamp());
+                ordered.sort_by_key(|commit| {
+                    std::cmp::Reverse(
+                        commit
+                            .line_stats
+                            .as_ref()
+                            .map(|stats| stats.additions + stats.deletions),
+                    )
+                });
+            }
+        }
+        ordered
+    }
+}
+
+/// Week numbering scheme used in weekly rollup titles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekNumbering {
+    /// ISO 8601: weeks start on Monday and week 1 holds the year's first Thursday
+    #[default]
+    Iso,
+    /// US: weeks start on Sunday and week 1 holds January 1
+    Us,
+}
+
+impl WeekNumbering {
+    /// Returns the year and week number `date` falls in
+    pub fn week_of(self, date: NaiveDate) -> (i32, u32) {
+        match self {
+            WeekNumbering::Iso => {
+                let week = date.iso_week();
+                (week.year(), week.week())
+            }
+            WeekNumbering::Us => {
+                let offset = NaiveDate::from_yo_opt(date.year(), 1)
+                    .map_or(0, |jan_1| jan_1.weekday().num_days_from_sunday());
+                (date.year(), (date.ordinal0() + offset) / 7 + 1)
+            }
+        }
+    }
+}
+
+/// How weekly rollups are numbered and which language day headings use
+#[derive(Debug, Clone, Copy)]
+pub struct RollupSettings {
+    pub numbering: WeekNumbering,
+    pub locale: Locale,
+}
+
+/// Commits of a week-long diary grouped by day
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WeekRollup {
+    /// Title such as "Week 19, 2024"
+    pub title: String,
+    /// Localized day heading with that day's commits, oldest first
+    pub days: Vec<(String, Vec<Commit>)>,
+}
+
+/// Sorts commits newest first, breaking ties by message and then author
+///
+/// Sources list commits in whatever order they store them; this gives a
+/// total order so output doesn't depend on it.
+pub fn sort_commits(commits: &mut [Commit]) {
+    commits.sort_by(|a, b| {
+        b.timestamp()
+            .cmp(&a.timestamp())
+            .then_with(|| a.message.cmp(&b.message))
+            .then_with(|| {
+                let email = |commit: &Commit| commit.author.as_ref().map(|a| a.email.clone());
+                email(a).cmp(&email(b))
+            })
+    });
+}
+
+/// Commits that belong to one pull request
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PullRequestGroup {
+    pub number: u64,
+    pub title: String,
+    /// Commits of the pull request, oldest first
     pub commits: Vec<Commit>,
-    pub summary: String,
+}
+
+/// Groups commits that reference a pull request, ordered by their first commit
+///
+/// Titles come from squash-merge subjects with the `(#123)` suffix removed.
+/// Commits without a pull request reference are left out.
+pub fn group_by_pull_request(commits: &[Commit]) -> Vec<PullRequestGroup> {
+    let mut sorted = commits.to_vec();
+    sorted.sort_by_key(Commit::timestamp);
+
+    let mut groups: Vec<PullRequestGroup> = Vec::new();
+    for commit in sorted {
+        let Some(number) = commit.pull_request() else {
+            continue;
+        };
+        match groups.iter_mut().find(|group| group.number == number) {
+            Some(group) => group.commits.push(commit),
+            None => groups.push(PullRequestGroup {
+                number,
+                title: pull_request_title(&commit, number),
+                commits: vec![commit],
+            }),
+        }
+    }
+
+    groups
+}
+
+fn pull_request_title(commit: &Commit, number: u64) -> String {
+    let subject = commit
+        .squashed_from
+        .as_deref()
+        .unwrap_or(commit.subject())
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_end();
+    match subject.strip_suffix(&format!("(#{})", number)) {
+        Some(title) if !title.trim().is_empty() => title.trim().to_string(),
+        _ => format!("Pull request #{}", number),
+    }
+}
+
+/// What a diary would be written from, without calling the AI or saving anything
+#[derive(Debug, Clone, Serialize)]
+pub struct DiaryPreview {
     pub start_date: String,
     pub end_date: String,
+    /// Commits the diary would list, after filters and grouping
+    pub commits: Vec<Commit>,
+    /// The prompt the summary would be requested with
+    pub prompt: String,
 }
 
-// Trait definitions for external dependencies
-#[cfg_attr(test, automock)]
-#[async_trait]
-pub trait GitRepository: Send + Sync {
-    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>>;
+/// A half-open time range `[start, end)` covered by a diary entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
 }
 
-#[cfg_attr(test, automock)]
-#[async_trait]
-pub trait AISummarizer: Send + Sync {
-    async fn summarize_commits(&self, commits: &[Commit]) -> Result<String>;
+impl DateRange {
+    pub fn new(start: DateTime<Local>, end: DateTime<Local>) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.start.timestamp() <= timestamp && timestamp < self.end.timestamp()
+    }
 }
 
-#[cfg_attr(test, automock)]
-pub trait DiaryStorage: Send + Sync {
-    fn save_diary(&self, content: &DiaryContent) -> Result<String>;
-    fn generate_file_name(&self, content: &DiaryContent) -> String;
-    fn format_markdown_content(&self, content: &DiaryContent) -> String;
+/// Input handed to the AI summarizer
+#[derive(Debug, Clone, Default)]
+pub struct SummaryRequest {
+    pub commits: Vec<Commit>,
+    /// Number of commits dropped by the `max_commits` cap
+    pub omitted_commits: usize,
+    /// Manual notes to give the summary more context
+    pub notes: Vec<Note>,
+    /// Work that left no commits, such as meetings, passed with `--context`
+    pub context: Vec<String>,
+    /// Project names to use verbatim
+    pub glossary: Vec<GlossaryTerm>,
+    /// Pull requests the developer reviewed in the period
+    pub reviews: Vec<Review>,
+    /// Issues the developer moved to another status in the period
+    pub tickets: Vec<TicketTransition>,
+    /// The developer's goals, for the summary to report progress on
+    pub goals: Vec<String>,
+    /// Past summaries the developer rated good, as examples of the style to write in
+    pub examples: Vec<DiarySummary>,
+    /// Share of the prompt each section may take; sections are never cut when unset
+    pub budget: Option<PromptBudget>,
+    /// How long the summary should be
+    pub length: SummaryLength,
+    /// Language code such as `ja` to write the summary in; the model's choice when unset
+    pub language: Option<String>,
+    /// Model picked by size-tiered routing; the summarizer's own when unset
+    pub model: Option<String>,
+    /// Tokens of commit diffs the prompt may include; small diffs are quoted
+    /// whole and the rest is shared out by diff size. No diffs when zero
+    pub diff_tokens: u32,
+    /// Asks the model to end the summary with topic tags
+    pub topics: bool,
+    /// How the test suite fared in the period's local test runs
+    pub tests: Option<TestHealth>,
 }
 
-#[cfg_attr(test, automock)]
-pub trait DateTimeProvider: Send + Sync {
-    fn now(&self) -> DateTime<Local>;
-    fn days_ago(&self, days: i64) -> DateTime<Local>;
+impl SummaryRequest {
+    pub fn new(commits: Vec<Commit>) -> Self {
+        Self {
+            commits,
+            omitted_commits: 0,
+            notes: Vec::new(),
+            context: Vec::new(),
+            glossary: Vec::new(),
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            goals: Vec::new(),
+            examples: Vec::new(),
+            budget: None,
+            length: SummaryLength::default(),
+            language: None,
+            model: None,
+            diff_tokens: 0,
+            topics: false,
+            tests: None,
+        }
+    }
+
+    /// Pairs reverts and keeps the `max_commits` most recent commits, newest first
+    pub fn capped(commits: &[Commit], max_commits: usize) -> Self {
+        let mut commits = pair_reverts(commits);
+        commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp()));
+
+        let omitted_commits = commits.len().saturating_sub(max_commits);
+        commits.truncate(max_commits);
+
+        Self {
+            commits,
+            omitted_commits,
+            notes: Vec::new(),
+            context: Vec::new(),
+            glossary: Vec::new(),
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            goals: Vec::new(),
+            examples: Vec::new(),
+            budget: None,
+            length: SummaryLength::default(),
+            language: None,
+            model: None,
+            diff_tokens: 0,
+            topics: false,
+            tests: None,
+        }
+    }
 }
 
-// DiaryGenerator implementation
-pub struct DiaryGenerator<G, A, S, D>
-where
-    G: GitRepository,
-    A: AISummarizer,
-    S: DiaryStorage,
-    D: DateTimeProvider,
-{
-    git_repo: Arc<G>,
-    ai_summarizer: Arc<A>,
-    storage: Arc<S>,
-    datetime_provider: Arc<D>,
-    days_to_include: i64,
+/// The summary of one stored diary, as mined for highlights
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiarySummary {
+    /// Dates the diary covers, such as "2024-05-06 – 2024-05-12"
+    pub period: String,
+    pub summary: String,
 }
 
-impl<G, A, S, D> DiaryGenerator<G, A, S, D>
-where
-    G: GitRepository,
-    A: AISummarizer,
-    S: DiaryStorage,
-    D: DateTimeProvider,
-{
-    pub fn new(
-        git_repo: Arc<G>,
-        ai_summarizer: Arc<A>,
-        storage: Arc<S>,
-        datetime_provider: Arc<D>,
-        days_to_include: i64,
-    ) -> Self {
+/// Input for resume-style highlights over a long period
+///
+/// Built from stored diary summaries, or from raw commits when there are no
+/// diaries to mine.
+#[derive(Debug, Clone, Default)]
+pub struct HighlightsRequest {
+    pub diaries: Vec<DiarySummary>,
+    pub commits: Vec<Commit>,
+    /// Number of commits dropped by the `max_commits` cap
+    pub omitted_commits: usize,
+}
+
+impl HighlightsRequest {
+    pub fn from_diaries(diaries: Vec<DiarySummary>) -> Self {
         Self {
-            git_repo,
-            ai_summarizer,
-            storage,
-            datetime_provider,
-            days_to_include,
+            diaries,
+            ..Self::default()
         }
     }
 
-    pub fn format_commit_logs(&self, commits: &[Commit]) -> String {
-        let mut logs = String::new();
-        logs.push_str(&format!("Last {} days commits:\n", self.days_to_include));
+    /// Keeps the `max_commits` most recent commits, listed oldest first
+    pub fn from_commits(commits: &[Commit], max_commits: usize) -> Self {
+        let SummaryRequest {
+            mut commits,
+            omitted_commits,
+            ..
+        } = SummaryRequest::capped(commits, max_commits);
+        commits.reverse();
 
-        for commit in commits.iter().rev() {
-            logs.push_str(&format!("{}\n", commit));
+        Self {
+            diaries: Vec::new(),
+            commits,
+            omitted_commits,
         }
+    }
+}
 
-        logs
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DiaryContent {
+    pub commits: Vec<Commit>,
+    pub summary: String,
+    pub start_date: String,
+    pub end_date: String,
+    /// Per-day grouping, set when the diary covers a calendar week
+    pub rollup: Option<WeekRollup>,
+    /// Manual notes merged into this diary, oldest first
+    pub notes: Vec<Note>,
+    /// Commits grouped by the pull request they reference
+    pub pull_requests: Vec<PullRequestGroup>,
+    /// The summary in further languages, for bilingual diaries
+    pub translations: Vec<TranslatedSummary>,
+    /// Work that left no commits, listed under "Other activities"
+    pub context: Vec<String>,
+    /// Branch the diary is limited to, for `--per-branch` diaries
+    pub branch: Option<String>,
+    /// Pull requests the developer reviewed, listed under "Reviews"
+    pub reviews: Vec<Review>,
+    /// Issues the developer moved to another status, listed under "Tickets"
+    pub tickets: Vec<TicketTransition>,
+    /// Model the summary was routed to by commit count; unset when the configured model wrote it
+    pub model: Option<String>,
+    /// TODO, test file and build config changes, with `--health`
+    pub health: Option<RepoHealth>,
+    /// One line per working session, oldest first, with `--sessions`
+    pub sessions: Vec<SessionSummary>,
+    /// Share of commits made late at night or on weekends, with `--work-hours`
+    pub work_hours: Option<WorkHours>,
+    /// Editor time by project and language, when a time tracker is configured
+    pub coding_time: Option<CodingTime>,
+    /// Images the commits added and mention, copied next to the diary, with `--attach-images`
+    pub attachments: Vec<Attachment>,
+    /// Performance work and the improvements it claims, listed under "Performance"
+    pub performance: Vec<PerformanceClaim>,
+    /// Topic tags the model gave the work, with `--ai-tags`
+    pub topics: Vec<String>,
+    /// Order the commit log lists `commits` in; not part of the sidecar
+    #[serde(skip)]
+    pub commit_order: CommitOrder,
+}
+
+impl DiaryContent {
+    /// The commits in the order of the commit log
+    pub fn commit_log(&self) -> Vec<&Commit> {
+        self.commit_order.ordered(&self.commits)
     }
+}
 
-    pub async fn generate_diary(&self) -> Result<String> {
-        let now = self.datetime_provider.now();
-        let days_ago = self.datetime_provider.days_ago(self.days_to_include);
+/// A pull request the developer reviewed during the period
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Review {
+    pub number: u64,
+    pub title: String,
+    /// Outcome of the latest review, such as `approved` or `changes requested`
+    pub state: String,
+    /// Reviews submitted on the pull request during the period
+    pub submissions: usize,
+}
 
-        let start_date = days_ago.format("%Y-%m-%d").to_string();
-        let end_date = now.format("%Y-%m-%d").to_string();
+impl Review {
+    pub fn new(number: u64, title: String, state: String) -> Self {
+        Self {
+            number,
+            title,
+            state,
+            submissions: 1,
+        }
+    }
 
-        // Get commits from git repository
-        let commits = self.git_repo.get_commits_since(days_ago.timestamp())?;
+    pub fn with_submissions(mut self, submissions: usize) -> Self {
+        self.submissions = submissions;
+        self
+    }
+}
 
-        // Format commit logs
-        let commit_logs = self.format_commit_logs(&commits);
+impl Display for Review {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} {} ({}", self.number, self.title, self.state)?;
+        if self.submissions > 1 {
+            write!(f, ", {} reviews", self.submissions)?;
+        }
+        write!(f, ")")
+    }
+}
 
-        // Print the commit logs
-        println!("{}", commit_logs);
+/// An issue the developer moved to another status during the period, such as
+/// to "In Review" or "Done"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TicketTransition {
+    /// Issue key, such as `PAY-142` or `#57`
+    pub key: String,
+    pub title: String,
+    /// Status the developer last moved the issue to in the period
+    pub status: String,
+}
 
-        // Get summary from AI
-        let summary = self.ai_summarizer.summarize_commits(&commits).await?;
+impl TicketTransition {
+    pub fn new(key: String, title: String, status: String) -> Self {
+        Self { key, title, status }
+    }
+}
 
-        // Print the summary
-        println!("Summary:");
-        println!("------------------------------------");
-        println!("{}", summary);
+/// "PAY-142 Retry failed payments (moved to Done)"
+impl Display for TicketTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} (moved to {})", self.key, self.title, self.status)
+    }
+}
 
-        // Create diary content
+/// Branch name used for commits whose branch is unknown, such as those on a detached HEAD
+pub const DETACHED_BRANCH: &str = "HEAD";
+
+/// One diary written by a `--per-branch` run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchDiary {
+    pub branch: String,
+    pub path: String,
+    pub commits: usize,
+}
+
+/// The index of a `--per-branch` run, linking the diary of each branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchIndex {
+    pub start_date: String,
+    pub end_date: String,
+    /// Most recently active branch first
+    pub diaries: Vec<BranchDiary>,
+}
+
+/// The summary written in one more language
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TranslatedSummary {
+    /// Language code, such as `ja`
+    pub language: String,
+    pub summary: String,
+}
+
+/// One line on a working session, for `--sessions`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SessionSummary {
+    /// Part of the day the session started in, such as `Morning`
+    pub label: String,
+    /// Local time of the first commit, as `HH:MM`
+    pub start: String,
+    /// Local time of the last commit, as `HH:MM`
+    pub end: String,
+    pub commits: usize,
+    pub summary: String,
+}
+
+/// "Morning (09:10–11:45): Fixed the flaky auth tests"
+impl Display for SessionSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}–{}): {}",
+            self.label, self.start, self.end, self.summary
+        )
+    }
+}
+
+/// What a minimal request to the AI provider showed, for `git-diary ping`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderPing {
+    /// Model that answered, as the provider resolved it, e.g. a dated
+    /// version of an alias such as `gpt-4o`
+    pub model: String,
+    /// Round trip of the request in milliseconds
+    pub latency_ms: u64,
+}
+
+// Trait definitions for external dependencies
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait GitRepository: Send + Sync {
+    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>>;
+}
+
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait AISummarizer: Send + Sync {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String>;
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String>;
+    /// Sends the smallest request the provider accepts, to check the setup
+    /// without paying for a summary; providers without such a request refuse
+    async fn ping(&self) -> Result<ProviderPing> {
+        bail!("This AI provider can't be checked without generating a summary")
+    }
+}
+
+/// Lets a shared summarizer sit inside middleware such as `RedactingSummarizer`
+#[async_trait]
+impl<T: AISummarizer + ?Sized> AISummarizer for Arc<T> {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String> {
+        self.as_ref().summarize_commits(request).await
+    }
+
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String> {
+        self.as_ref().summarize_highlights(request).await
+    }
+
+    async fn ping(&self) -> Result<ProviderPing> {
+        self.as_ref().ping().await
+    }
+}
+
+/// Looks up pull request titles on a forge
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait PullRequestResolver: Send + Sync {
+    /// Returns the title of pull request `number`, or `None` if it doesn't exist
+    async fn pull_request_title(&self, number: u64) -> Result<Option<String>>;
+
+    /// Returns the description of pull request `number`, or `None` if it
+    /// doesn't exist or has none
+    async fn pull_request_description(&self, number: u64) -> Result<Option<String>>;
+}
+
+/// Finds the code reviews the developer did, which leave no commits behind
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait ReviewSource: Send + Sync {
+    /// Pull requests reviewed between the `since` and `until` timestamps
+    async fn reviews_between(&self, since: i64, until: i64) -> Result<Vec<Review>>;
+}
+
+/// Finds the issues the developer moved on a tracker such as Jira or GitHub
+/// Projects, which are part of the day even without commits
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait TicketSource: Send + Sync {
+    /// Issues whose status the developer changed between the `since` and
+    /// `until` timestamps, with the last status they moved each one to
+    async fn transitions_between(&self, since: i64, until: i64) -> Result<Vec<TicketTransition>>;
+}
+
+/// Reads the time a time tracker recorded in the editor, which also covers
+/// work that left no commits
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait CodingTimeSource: Send + Sync {
+    /// Coding time between the `since` and `until` timestamps
+    async fn coding_time_between(&self, since: i64, until: i64) -> Result<CodingTime>;
+}
+
+/// Measures engineering hygiene from the diffs of a diary's commits
+#[cfg_attr(test, automock)]
+pub trait HealthSource: Send + Sync {
+    fn repo_health(&self, commits: &[Commit]) -> Result<RepoHealth>;
+}
+
+/// Reads the results of test runs made while working, such as JUnit reports
+#[cfg_attr(test, automock)]
+pub trait TestRunSource: Send + Sync {
+    /// Test runs that finished between the `since` and `until` timestamps
+    fn test_runs_between(&self, since: i64, until: i64) -> Result<Vec<TestRun>>;
+}
+
+/// Copies the images commits mention next to the diary
+#[cfg_attr(test, automock)]
+pub trait AttachmentSource: Send + Sync {
+    /// Copies each image `referenced_images` finds and returns where the copies are
+    fn attach(&self, commits: &[Commit]) -> Result<Vec<Attachment>>;
+}
+
+/// Asks before summary requests go out to the AI, e.g. with their estimated cost
+#[cfg_attr(test, automock)]
+pub trait SendConfirmation: Send + Sync {
+    /// Whether to send `request` `times` times, once per diary language
+    fn confirm(&self, request: &SummaryRequest, times: usize) -> Result<bool>;
+}
+
+/// Checks that a diary can be saved before anything is sent to the AI, so a
+/// run that would fail to write it costs nothing
+#[cfg_attr(test, automock)]
+pub trait OutputCheck: Send + Sync {
+    /// Fails, saying what to do about it, when the diary of `start_date` to
+    /// `end_date`, limited to `branch` if set, couldn't be saved
+    fn check_output(&self, start_date: &str, end_date: &str, branch: Option<String>) -> Result<()>;
+}
+
+/// Announces finished diaries, e.g. with a desktop notification
+#[cfg_attr(test, automock)]
+pub trait Notifier: Send + Sync {
+    fn notify(&self, message: &str) -> Result<()>;
+}
+
+/// One-line announcement of a saved diary, such as "Diary for May 7 generated — 14 commits"
+pub fn completion_message(content: &DiaryContent) -> String {
+    let day = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|date| date.format("%B %-d").to_string())
+            .unwrap_or_else(|_| date.to_string())
+    };
+    let period = if content.start_date == content.end_date {
+        day(&content.end_date)
+    } else {
+        format!("{} – {}", day(&content.start_date), day(&content.end_date))
+    };
+    let commits = match content.commits.len() {
+        1 => "1 commit".to_string(),
+        count => format!("{} commits", count),
+    };
+
+    format!("Diary for {} generated — {}", period, commits)
+}
+
+#[cfg_attr(test, automock)]
+pub trait DiaryStorage: Send + Sync {
+    fn save_diary(&self, content: &DiaryContent) -> Result<String>;
+    fn generate_file_name(&self, content: &DiaryContent) -> String;
+    fn format_content(&self, content: &DiaryContent) -> String;
+    /// Saves the index of a `--per-branch` run, returning its path
+    fn save_branch_index(&self, index: &BranchIndex) -> Result<String>;
+}
+
+/// Turns a wall-clock time into a point in time, whatever DST does to it
+///
+/// When clocks go back the earlier of the two instants is used. A time that
+/// is skipped when clocks go forward is read with the offset from before the
+/// change, so it lands as far past the gap as it was into it: 02:30 becomes 03:30.
+pub fn resolve_local<Tz: TimeZone>(tz: &Tz, local: NaiveDateTime) -> DateTime<Tz> {
+    match tz.from_local_datetime(&local) {
+        LocalResult::Single(datetime) | LocalResult::Ambiguous(datetime, _) => datetime,
+        LocalResult::None => {
+            let before = tz
+                .offset_from_utc_datetime(&(local - Duration::days(1)))
+                .fix();
+            tz.from_utc_datetime(&(local - Duration::seconds(before.local_minus_utc() as i64)))
+        }
+    }
+}
+
+/// The same wall-clock time `days` calendar days before `datetime`
+///
+/// Unlike subtracting `Duration::days`, a day back from a 23 or 25-hour DST
+/// day is still yesterday at this time.
+pub fn days_before<Tz: TimeZone>(datetime: &DateTime<Tz>, days: i64) -> DateTime<Tz> {
+    resolve_local(
+        &datetime.timezone(),
+        datetime.naive_local() - Duration::days(days),
+    )
+}
+
+#[cfg_attr(test, automock)]
+pub trait DateTimeProvider: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+
+    /// Returns the current time `days` calendar days ago
+    fn days_ago(&self, days: i64) -> DateTime<Local> {
+        days_before(&self.now(), days)
+    }
+
+    /// Hour (0-23) at which a new day starts; commits before it count toward the previous day
+    fn day_cutoff_hour(&self) -> u32 {
+        0
+    }
+
+    /// Returns the day `datetime` belongs to, taking the day cutoff into account
+    ///
+    /// The cutoff is taken off the wall-clock time, so it holds on DST days too.
+    fn logical_date(&self, datetime: DateTime<Local>) -> NaiveDate {
+        (datetime.naive_local() - Duration::hours(self.day_cutoff_hour() as i64)).date()
+    }
+
+    /// Returns the current day, taking the day cutoff into account
+    fn today(&self) -> NaiveDate {
+        self.logical_date(self.now())
+    }
+
+    /// Returns the last day covered by the half-open `range`
+    fn last_day(&self, range: &DateRange) -> NaiveDate {
+        self.logical_date(range.end - Duration::seconds(1))
+    }
+
+    /// Returns the local time at which `date` begins, i.e. midnight plus the day cutoff
+    fn start_of_day(&self, date: NaiveDate) -> DateTime<Local> {
+        let start = date.and_time(
+            NaiveTime::from_hms_opt(self.day_cutoff_hour(), 0, 0).unwrap_or(NaiveTime::MIN),
+        );
+        resolve_local(&Local, start)
+    }
+
+    /// Returns the start of the current week, where weeks begin on `weekday`
+    fn start_of_week(&self, weekday: Weekday) -> DateTime<Local> {
+        self.calendar_range(self.today(), Granularity::Week(weekday))
+            .start
+    }
+
+    /// Returns the calendar day, week or month containing `date`
+    fn calendar_range(&self, date: NaiveDate, granularity: Granularity) -> DateRange {
+        let (first, next) = match granularity {
+            Granularity::Day => (date, date + Days::new(1)),
+            Granularity::Week(weekday) => {
+                let offset = (7 + date.weekday().num_days_from_monday()
+                    - weekday.num_days_from_monday())
+                    % 7;
+                let first = date - Days::new(offset as u64);
+                (first, first + Days::new(7))
+            }
+            Granularity::Month => {
+                let first = date.with_day(1).unwrap_or(date);
+                (first, first + Months::new(1))
+            }
+        };
+
+        DateRange::new(self.start_of_day(first), self.start_of_day(next))
+    }
+}
+
+/// Default cap on commits sent to the AI summarizer
+pub const DEFAULT_MAX_COMMITS: usize = 500;
+
+// DiaryGenerator implementation
+//
+// The dependencies may be trait objects, see `DynDiaryGenerator`.
+pub struct DiaryGenerator<G, A, S, D>
+where
+    G: GitRepository + ?Sized,
+    A: AISummarizer + ?Sized,
+    S: DiaryStorage + ?Sized,
+    D: DateTimeProvider + ?Sized,
+{
+    git_repo: Arc<G>,
+    ai_summarizer: Arc<A>,
+    storage: Arc<S>,
+    datetime_provider: Arc<D>,
+    days_to_include: i64,
+    max_commits: usize,
+    sensitive_scan: SensitiveScan,
+    rollup: Option<RollupSettings>,
+    rewrites: RewritePipeline,
+    notes: Vec<Note>,
+    context: Vec<String>,
+    glossary: Vec<GlossaryTerm>,
+    goals: Vec<String>,
+    examples: Vec<DiarySummary>,
+    prompt_budget: Option<PromptBudget>,
+    date_order: DateOrder,
+    length: SummaryLength,
+    author_filter: Option<AuthorFilter>,
+    identities: Identities,
+    path_filter: Option<PathFilter>,
+    post_processing: PostProcessPipeline,
+    pull_request_resolver: Option<Arc<dyn PullRequestResolver>>,
+    review_source: Option<Arc<dyn ReviewSource>>,
+    ticket_source: Option<Arc<dyn TicketSource>>,
+    coding_time_source: Option<Arc<dyn CodingTimeSource>>,
+    routing: Vec<RoutingRule>,
+    health_source: Option<Arc<dyn HealthSource>>,
+    test_run_source: Option<Arc<dyn TestRunSource>>,
+    attachment_source: Option<Arc<dyn AttachmentSource>>,
+    work_hours: bool,
+    diff_budget_tokens: u32,
+    deterministic: bool,
+    expand_squash_merges: bool,
+    group_dependency_updates: bool,
+    empty_messages: EmptyMessagePolicy,
+    notifier: Option<Arc<dyn Notifier>>,
+    confirmation: Option<Arc<dyn SendConfirmation>>,
+    output_check: Option<Arc<dyn OutputCheck>>,
+    session_gap_minutes: Option<i64>,
+    commit_order: CommitOrder,
+    message_source: MessageSource,
+    languages: Vec<String>,
+    topics: bool,
+}
+
+/// A `DiaryGenerator` over trait objects, for callers that pick the
+/// dependencies at runtime or need one concrete type to store
+pub type DynDiaryGenerator =
+    DiaryGenerator<dyn GitRepository, dyn AISummarizer, dyn DiaryStorage, dyn DateTimeProvider>;
+
+impl<G, A, S, D> DiaryGenerator<G, A, S, D>
+where
+    G: GitRepository + 'static,
+    A: AISummarizer + 'static,
+    S: DiaryStorage + 'static,
+    D: DateTimeProvider + 'static,
+{
+    /// Erases the dependency types, keeping every setting
+    pub fn into_dyn(self) -> DynDiaryGenerator {
+        DiaryGenerator {
+            git_repo: self.git_repo,
+            ai_summarizer: self.ai_summarizer,
+            storage: self.storage,
+            datetime_provider: self.datetime_provider,
+            days_to_include: self.days_to_include,
+            max_commits: self.max_commits,
+            sensitive_scan: self.sensitive_scan,
+            rollup: self.rollup,
+            rewrites: self.rewrites,
+            notes: self.notes,
+            context: self.context,
+            date_order: self.date_order,
+            glossary: self.glossary,
+            goals: self.goals,
+            examples: self.examples,
+            prompt_budget: self.prompt_budget,
+            length: self.length,
+            author_filter: self.author_filter,
+            identities: self.identities,
+            path_filter: self.path_filter,
+            post_processing: self.post_processing,
+            pull_request_resolver: self.pull_request_resolver,
+            review_source: self.review_source,
+            ticket_source: self.ticket_source,
+            coding_time_source: self.coding_time_source,
+            routing: self.routing,
+            health_source: self.health_source,
+            test_run_source: self.test_run_source,
+            attachment_source: self.attachment_source,
+            work_hours: self.work_hours,
+            diff_budget_tokens: self.diff_budget_tokens,
+            deterministic: self.deterministic,
+            expand_squash_merges: self.expand_squash_merges,
+            group_dependency_updates: self.group_dependency_updates,
+            empty_messages: self.empty_messages,
+            notifier: self.notifier,
+            confirmation: self.confirmation,
+            output_check: self.output_check,
+            session_gap_minutes: self.session_gap_minutes,
+            commit_order: self.commit_order,
+            message_source: self.message_source,
+            languages: self.languages,
+            topics: self.topics,
+        }
+    }
+}
+
+impl<G, A, S, D> DiaryGenerator<G, A, S, D>
+where
+    G: GitRepository + ?Sized,
+    A: AISummarizer + ?Sized,
+    S: DiaryStorage + ?Sized,
+    D: DateTimeProvider + ?Sized,
+{
+    pub fn new(
+        git_repo: Arc<G>,
+        ai_summarizer: Arc<A>,
+        storage: Arc<S>,
+        datetime_provider: Arc<D>,
+        days_to_include: i64,
+    ) -> Self {
+        Self {
+            git_repo,
+            ai_summarizer,
+            storage,
+            datetime_provider,
+            days_to_include,
+            max_commits: DEFAULT_MAX_COMMITS,
+            sensitive_scan: SensitiveScan::Off,
+            rollup: None,
+            rewrites: RewritePipeline::default(),
+            notes: Vec::new(),
+            context: Vec::new(),
+            date_order: DateOrder::default(),
+            glossary: Vec::new(),
+            goals: Vec::new(),
+            examples: Vec::new(),
+            prompt_budget: None,
+            length: SummaryLength::default(),
+            author_filter: None,
+            identities: Identities::default(),
+            path_filter: None,
+            post_processing: PostProcessPipeline::default(),
+            pull_request_resolver: None,
+            review_source: None,
+            ticket_source: None,
+            coding_time_source: None,
+            routing: Vec::new(),
+            health_source: None,
+            test_run_source: None,
+            attachment_source: None,
+            work_hours: false,
+            diff_budget_tokens: 0,
+            deterministic: false,
+            expand_squash_merges: true,
+            group_dependency_updates: true,
+            empty_messages: EmptyMessagePolicy::default(),
+            notifier: None,
+            confirmation: None,
+            output_check: None,
+            session_gap_minutes: None,
+            commit_order: CommitOrder::default(),
+            message_source: MessageSource::default(),
+            languages: Vec::new(),
+            topics: false,
+        }
+    }
+
+    /// Caps how many commits are sent to the AI summarizer
+    pub fn with_max_commits(mut self, max_commits: usize) -> Self {
+        self.max_commits = max_commits;
+        self
+    }
+
+    /// Scans commit messages for sensitive data before they are sent to the AI
+    pub fn with_sensitive_scan(mut self, sensitive_scan: SensitiveScan) -> Self {
+        self.sensitive_scan = sensitive_scan;
+        self
+    }
+
+    /// Groups commits by day under a week-numbered title, for week-long ranges
+    pub fn with_week_rollup(mut self, settings: RollupSettings) -> Self {
+        self.rollup = Some(settings);
+        self
+    }
+
+    /// Splits squash merges with a bullet list into one entry per bullet; on by default
+    pub fn with_squash_expansion(mut self, expand_squash_merges: bool) -> Self {
+        self.expand_squash_merges = expand_squash_merges;
+        self
+    }
+
+    /// Collapses dependency bumps into one entry when there are several; on by default
+    pub fn with_dependency_grouping(mut self, group_dependency_updates: bool) -> Self {
+        self.group_dependency_updates = group_dependency_updates;
+        self
+    }
+
+    /// Keeps, drops or describes commits without a message; kept by default
+    pub fn with_empty_message_policy(mut self, policy: EmptyMessagePolicy) -> Self {
+        self.empty_messages = policy;
+        self
+    }
+
+    /// Rewrites commit messages before they are summarized and saved
+    pub fn with_rewrites(mut self, rewrites: RewritePipeline) -> Self {
+        self.rewrites = rewrites;
+        self
+    }
+
+    /// Adds manual notes to the prompt and the saved diary
+    pub fn with_notes(mut self, notes: Vec<Note>) -> Self {
+        self.notes = notes;
+        self
+    }
+
+    /// Adds work that left no commits to the prompt and the saved diary
+    pub fn with_context(mut self, context: Vec<String>) -> Self {
+        self.context = context;
+        self
+    }
+
+    /// Asks the summary to report progress on each of `goals`
+    pub fn with_goals(mut self, goals: Vec<String>) -> Self {
+        self.goals = goals;
+        self
+    }
+
+    /// Shows the summarizer past summaries the developer rated good, to write like them
+    pub fn with_examples(mut self, examples: Vec<DiarySummary>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Caps each prompt section at its share of `budget`
+    pub fn with_prompt_budget(mut self, budget: PromptBudget) -> Self {
+        self.prompt_budget = Some(budget);
+        self
+    }
+
+    /// Asks the summary to use these project names verbatim
+    pub fn with_glossary(mut self, glossary: Vec<GlossaryTerm>) -> Self {
+        self.glossary = glossary;
+        self
+    }
+
+    /// Chooses whether author or committer dates place commits in the period
+    pub fn with_date_order(mut self, date_order: DateOrder) -> Self {
+        self.date_order = date_order;
+        self
+    }
+
+    /// Sets how long the AI summary should be
+    pub fn with_length(mut self, length: SummaryLength) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Only includes commits whose author matches `filter`
+    pub fn with_author_filter(mut self, filter: AuthorFilter) -> Self {
+        self.author_filter = Some(filter);
+        self
+    }
+
+    /// Runs every summary through `pipeline` before it is printed and saved
+    pub fn with_post_processing(mut self, pipeline: PostProcessPipeline) -> Self {
+        self.post_processing = pipeline;
+        self
+    }
+
+    /// Only includes commits touching paths that pass `filter`
+    pub fn with_path_filter(mut self, filter: PathFilter) -> Self {
+        self.path_filter = Some(filter);
+        self
+    }
+
+    /// Counts commits by any of `identities` as one author, applied before the author filter
+    pub fn with_identities(mut self, identities: Identities) -> Self {
+        self.identities = identities;
+        self
+    }
+
+    /// Writes the summary in each of `languages`, such as `["en", "ja"]`, with one
+    /// model call per language; the first becomes the main summary and `auto`
+    /// stands for the language of the commit messages
+    pub fn with_languages(mut self, languages: Vec<String>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// Asks the model for topic tags such as `billing` or `ci`, kept in the
+    /// sidecar; models without structured output support are read leniently
+    pub fn with_topics(mut self, topics: bool) -> Self {
+        self.topics = topics;
+        self
+    }
+
+    /// Adds a "Repo health" paragraph computed from the commits' diffs
+    pub fn with_health_source(mut self, source: Arc<dyn HealthSource>) -> Self {
+        self.health_source = Some(source);
+        self
+    }
+
+    /// Tells the summary how the tests fared in the period's local test runs
+    pub fn with_test_run_source(mut self, source: Arc<dyn TestRunSource>) -> Self {
+        self.test_run_source = Some(source);
+        self
+    }
+
+    /// Embeds the images commits added and mention in their message
+    pub fn with_attachment_source(mut self, source: Arc<dyn AttachmentSource>) -> Self {
+        self.attachment_source = Some(source);
+        self
+    }
+
+    /// Adds a note on how many commits were made late at night or on weekends
+    pub fn with_work_hours(mut self, work_hours: bool) -> Self {
+        self.work_hours = work_hours;
+        self
+    }
+
+    /// Lets the prompt quote up to `tokens` tokens of commit diffs in total,
+    /// split between commits in proportion to their diff size
+    pub fn with_diff_budget(mut self, tokens: u32) -> Self {
+        self.diff_budget_tokens = tokens;
+        self
+    }
+
+    /// Sends diaries to a cheaper or stronger model depending on their commit count
+    pub fn with_model_routing(mut self, routing: Vec<RoutingRule>) -> Self {
+        self.routing = routing;
+        self
+    }
+
+    /// Replaces `auto` in the configured languages with the language most
+    /// commit messages are written in; it is dropped when none is detected
+    pub fn resolve_languages(&self, commits: &[Commit]) -> Vec<String> {
+        let mut languages = Vec::new();
+        for language in &self.languages {
+            if language != AUTO_LANGUAGE {
+                languages.push(language.clone());
+                continue;
+            }
+
+            match detect_language(commits.iter().map(Commit::subject)) {
+                Some(detected) => {
+                    println!("🌐 Commit messages are in {}", language_name(detected));
+                    if !languages.iter().any(|known| known == detected) {
+                        languages.push(detected.to_string());
+                    }
+                }
+                None => println!("⚠️ Couldn't detect the language of the commit messages"),
+            }
+        }
+
+        languages
+    }
+
+    /// Announces each saved diary through `notifier`
+    pub fn with_notifier(mut self, notifier: Arc<dyn Notifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Lists the commit log in `order`
+    pub fn with_commit_order(mut self, order: CommitOrder) -> Self {
+        self.commit_order = order;
+        self
+    }
+
+    /// Summarizes squash merges from their pull request descriptions with
+    /// `MessageSource::PullRequest`; needs a pull request resolver
+    pub fn with_message_source(mut self, source: MessageSource) -> Self {
+        self.message_source = source;
+        self
+    }
+
+    /// Adds a one-line summary of each working session, splitting sessions at
+    /// pauses longer than `gap_minutes`
+    pub fn with_session_summaries(mut self, gap_minutes: i64) -> Self {
+        self.session_gap_minutes = Some(gap_minutes);
+        self
+    }
+
+    /// Asks `confirmation` before anything is sent to the AI summarizer
+    pub fn with_confirmation(mut self, confirmation: Arc<dyn SendConfirmation>) -> Self {
+        self.confirmation = Some(confirmation);
+        self
+    }
+
+    /// Runs `check` on every diary's output path before its summary is requested
+    pub fn with_output_check(mut self, check: Arc<dyn OutputCheck>) -> Self {
+        self.output_check = Some(check);
+        self
+    }
+
+    /// Takes pull request titles from the forge instead of commit subjects
+    pub fn with_pull_request_resolver(mut self, resolver: Arc<dyn PullRequestResolver>) -> Self {
+        self.pull_request_resolver = Some(resolver);
+        self
+    }
+
+    /// Lists the pull requests the developer reviewed in a "Reviews" section
+    pub fn with_review_source(mut self, source: Arc<dyn ReviewSource>) -> Self {
+        self.review_source = Some(source);
+        self
+    }
+
+    /// Lists the issues the developer moved on a tracker in a "Tickets" section
+    pub fn with_ticket_source(mut self, source: Arc<dyn TicketSource>) -> Self {
+        self.ticket_source = Some(source);
+        self
+    }
+
+    /// Adds a "Time by project and language" section from a time tracker
+    pub fn with_coding_time_source(mut self, source: Arc<dyn CodingTimeSource>) -> Self {
+        self.coding_time_source = Some(source);
+        self
+    }
+
+    /// Coding time recorded during `range`; lookups that fail only warrant a warning
+    pub async fn fetch_coding_time(&self, range: &DateRange) -> Option<CodingTime> {
+        let source = self.coding_time_source.as_ref()?;
+        match source
+            .coding_time_between(range.start.timestamp(), range.end.timestamp())
+            .await
+        {
+            Ok(time) if time.is_empty() => None,
+            Ok(time) => Some(time),
+            Err(e) => {
+                println!("⚠️ Couldn't read coding time: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// How the tests fared in the runs made during `range`; reports that can't
+    /// be read only warrant a warning
+    pub fn fetch_test_health(&self, range: &DateRange) -> Option<TestHealth> {
+        let source = self.test_run_source.as_ref()?;
+        match source.test_runs_between(range.start.timestamp(), range.end.timestamp()) {
+            Ok(runs) => TestHealth::from_runs(&runs),
+            Err(e) => {
+                println!("⚠️ Couldn't read test reports: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Code reviews done during `range`; lookups that fail only warrant a warning
+    pub async fn fetch_reviews(&self, range: &DateRange) -> Vec<Review> {
+        let Some(source) = &self.review_source else {
+            return Vec::new();
+        };
+
+        match source
+            .reviews_between(range.start.timestamp(), range.end.timestamp())
+            .await
+        {
+            Ok(reviews) => reviews,
+            Err(e) => {
+                println!("⚠️ Couldn't look up code reviews: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Issues moved on the tracker during `range`; lookups that fail only
+    /// warrant a warning
+    pub async fn fetch_tickets(&self, range: &DateRange) -> Vec<TicketTransition> {
+        let Some(source) = &self.ticket_source else {
+            return Vec::new();
+        };
+
+        match source
+            .transitions_between(range.start.timestamp(), range.end.timestamp())
+            .await
+        {
+            Ok(tickets) => tickets,
+            Err(e) => {
+                println!("⚠️ Couldn't look up ticket status changes: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// The first line of a session's summary, post-processed like the diary's
+    /// and put back on one line should a step have wrapped it
+    fn session_line(&self, summary: &str) -> String {
+        let first = summary.trim().lines().next().unwrap_or_default();
+        self.post_processing
+            .apply(first)
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Summarizes each working session in a line, in the diary's main language
+    ///
+    /// A failed summary is reported and the sessions left out, as the daily
+    /// narrative already covers the work.
+    pub async fn summarize_sessions(
+        &self,
+        commits: &[Commit],
+        language: Option<String>,
+        model: Option<String>,
+    ) -> Vec<SessionSummary> {
+        let Some(gap_minutes) = self.session_gap_minutes else {
+            return Vec::new();
+        };
+
+        let mut summaries = Vec::new();
+        for session in split_sessions(commits, gap_minutes) {
+            let (Some(first), Some(last)) = (session.first(), session.last()) else {
+                continue;
+            };
+            let local = |timestamp| {
+                DateTime::from_timestamp(timestamp, 0)
+                    .map(|time| time.with_timezone(&Local))
+                    .unwrap_or_default()
+            };
+            let (start, end) = (local(first.timestamp()), local(last.timestamp()));
+
+            let mut request = SummaryRequest::capped(&session, self.max_commits);
+            request.glossary = self.glossary.clone();
+            request.length = SummaryLength::Short;
+            request.language = language.clone();
+            request.model = model.clone();
+            match self.ai_summarizer.summarize_commits(&request).await {
+                Ok(summary) => summaries.push(SessionSummary {
+                    label: part_of_day(start.hour()).to_string(),
+                    start: start.format("%H:%M").to_string(),
+                    end: end.format("%H:%M").to_string(),
+                    commits: session.len(),
+                    summary: self.session_line(&summary),
+                }),
+                Err(e) => {
+                    println!("⚠️ Couldn't summarize working sessions: {}", e);
+                    return Vec::new();
+                }
+            }
+        }
+
+        summaries
+    }
+
+    /// Groups commits by pull request, with titles from the forge when available
+    ///
+    /// A failed lookup is reported and the title from the commit subject kept,
+    /// so a flaky forge never costs the diary.
+    pub async fn build_pull_request_groups(&self, commits: &[Commit]) -> Vec<PullRequestGroup> {
+        let mut groups = group_by_pull_request(commits);
+
+        if let Some(resolver) = &self.pull_request_resolver {
+            for group in &mut groups {
+                match resolver.pull_request_title(group.number).await {
+                    Ok(Some(title)) => group.title = title,
+                    Ok(None) => {}
+                    Err(e) => {
+                        println!("⚠️ Couldn't look up pull request #{}: {}", group.number, e);
+                    }
+                }
+            }
+        }
+
+        groups
+    }
+
+    /// Replaces the messages of squash merges with their pull request's
+    /// description, keeping the subject for the pull request number
+    ///
+    /// Commits expanded from a squash message already list its bullets and are
+    /// left alone. A failed lookup keeps the commit message.
+    pub async fn describe_pull_requests(&self, commits: &mut [Commit]) {
+        let Some(resolver) = &self.pull_request_resolver else {
+            return;
+        };
+
+        let mut described = Vec::new();
+        for commit in commits {
+            let Some(number) = commit.pull_request() else {
+                continue;
+            };
+            if commit.squashed_from.is_some() || described.contains(&number) {
+                continue;
+            }
+            described.push(number);
+            match resolver.pull_request_description(number).await {
+                Ok(Some(description)) if !description.trim().is_empty() => {
+                    let description: String = description
+                        .trim()
+                        .chars()
+                        .take(MAX_DESCRIPTION_CHARS)
+                        .collect();
+                    commit.message = format!("{}\n\n{}", commit.subject(), description);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    println!("⚠️ Couldn't look up pull request #{}: {}", number, e);
+                }
+            }
+        }
+    }
+
+    /// Sorts commits by time, then message and author, so reruns over the same
+    /// history produce the same prompt and diary
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Builds the summarizer input, keeping only the most recent commits
+    pub fn build_summary_request(&self, commits: &[Commit]) -> SummaryRequest {
+        let mut request = SummaryRequest::capped(commits, self.max_commits);
+        request.notes = self.notes.clone();
+        request.context = self.context.clone();
+        request.glossary = self.glossary.clone();
+        request.goals = self.goals.clone();
+        request.examples = self.examples.clone();
+        request.budget = self.prompt_budget.clone();
+        request.length = self.length;
+        request.diff_tokens = self.diff_budget_tokens;
+        request
+    }
+
+    /// Clamps commits dated after now, returning how many there were
+    ///
+    /// Only ranges that reach the present can hold work from a skewed clock,
+    /// so commits are clamped to the last moment of the range that has
+    /// already happened. For ranges in the past they are left alone and
+    /// drop out of the range.
+    pub fn clamp_future_commits(&self, commits: &mut [Commit], range: &DateRange) -> usize {
+        let now = self.datetime_provider.now().timestamp();
+        if range.end.timestamp() < now || range.start.timestamp() > now {
+            return 0;
+        }
+
+        let latest = now.min(range.end.timestamp() - 1);
+        let mut clamped = 0;
+        for commit in commits.iter_mut().filter(|commit| commit.timestamp() > now) {
+            commit.clamp_to(latest);
+            clamped += 1;
+        }
+        clamped
+    }
+
+    pub fn build_week_rollup(
+        &self,
+        commits: &[Commit],
+        range: &DateRange,
+        settings: RollupSettings,
+    ) -> WeekRollup {
+        let (year, week) = settings
+            .numbering
+            .week_of(self.datetime_provider.logical_date(range.start));
+
+        let mut days: BTreeMap<NaiveDate, Vec<Commit>> = BTreeMap::new();
+        for commit in commits {
+            let Some(time) = DateTime::from_timestamp(commit.timestamp(), 0) else {
+                continue;
+            };
+            let date = self
+                .datetime_provider
+                .logical_date(time.with_timezone(&Local));
+            days.entry(date).or_default().push(commit.clone());
+        }
+
+        WeekRollup {
+            title: format!("Week {}, {}", week, year),
+            days: days
+                .into_iter()
+                .map(|(date, mut commits)| {
+                    commits.sort_by_key(Commit::timestamp);
+                    let heading = date
+                        .format_localized("%A, %-d %B", settings.locale)
+                        .to_string();
+                    (heading, commits)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn format_commit_logs(
+        &self,
+        commits: &[Commit],
+        start_date: &str,
+        end_date: &str,
+    ) -> String {
+        let mut logs = String::new();
+        logs.push_str(&format!("Commits from {} to {}:\n", start_date, end_date));
+
+        for commit in self.commit_order.ordered(commits) {
+            logs.push_str(&format!("{}\n", commit));
+        }
+
+        logs
+    }
+
+    /// Range covered by `generate_diary`: the last `days_to_include` days up to now
+    pub fn default_range(&self) -> DateRange {
+        DateRange::new(
+            self.datetime_provider.days_ago(self.days_to_include),
+            self.datetime_provider.now(),
+        )
+    }
+
+    /// First and last logical day of `range`, formatted as `YYYY-MM-DD`
+    pub fn date_labels(&self, range: &DateRange) -> (String, String) {
+        (
+            self.datetime_provider
+                .logical_date(range.start)
+                .format("%Y-%m-%d")
+                .to_string(),
+            self.datetime_provider
+                .last_day(range)
+                .format("%Y-%m-%d")
+                .to_string(),
+        )
+    }
+
+    /// Collects the commits in `range` and builds the prompt, but stops before
+    /// the AI is called; reviews are left out as they need the forge
+    pub async fn preview_for_range(&self, range: &DateRange) -> Result<DiaryPreview> {
+        let commits = self.collect_commits(range)?;
+        let (start_date, end_date) = self.date_labels(range);
+
+        let mut request = self.build_summary_request(&commits);
+        if self.message_source == MessageSource::PullRequest {
+            self.describe_pull_requests(&mut request.commits).await;
+        }
+        request.language = self.resolve_languages(&request.commits).first().cloned();
+
+        Ok(DiaryPreview {
+            start_date,
+            end_date,
+            prompt: build_user_prompt(&request),
+            commits,
+        })
+    }
+
+    pub async fn generate_diary(&self) -> Result<String> {
+        self.generate_diary_for_range(self.default_range()).await
+    }
+
+    pub async fn generate_diary_for_range(&self, range: DateRange) -> Result<String> {
+        let commits = self.collect_commits(&range)?;
+        self.write_diary(Some(&range), self.date_labels(&range), commits, None)
+            .await
+    }
+
+    /// Summarizes and saves commits collected elsewhere, such as from another
+    /// VCS or CI logs, without reading the git repository
+    ///
+    /// The commits go through the same filters as ones read from git, but all
+    /// of them are kept whatever their dates. `start_date` and `end_date` label
+    /// the diary, like `2024-05-07`. Code reviews and week rollups need a date
+    /// range, so these diaries go without them.
+    pub async fn generate_from_commits(
+        &self,
+        commits: Vec<Commit>,
+        start_date: String,
+        end_date: String,
+    ) -> Result<String> {
+        let commits = self.filter_commits(commits);
+        self.write_diary(None, (start_date, end_date), commits, None)
+            .await
+    }
+
+    /// Writes one diary per branch with commits in `range`, plus an index linking them
+    ///
+    /// Commits made on a detached HEAD, or read from a source that doesn't
+    /// record branches, are collected under `HEAD`. Manual notes and context
+    /// aren't tied to a branch, so every branch diary includes them. Returns
+    /// the path of the index and the diaries it links.
+    pub async fn generate_branch_diaries_for_range(
+        &self,
+        range: DateRange,
+    ) -> Result<(String, Vec<BranchDiary>)> {
+        let commits = self.collect_commits(&range)?;
+
+        let mut branches: Vec<(String, Vec<Commit>)> = Vec::new();
+        for commit in commits {
+            let branch = commit
+                .branch
+                .clone()
+                .unwrap_or_else(|| DETACHED_BRANCH.to_string());
+            match branches.iter_mut().find(|(name, _)| *name == branch) {
+                Some((_, commits)) => commits.push(commit),
+                None => branches.push((branch, vec![commit])),
+            }
+        }
+        if branches.is_empty() {
+            bail!("No branch has commits in this period");
+        }
+        // Most recently active branch first
+        branches.sort_by_key(|(_, commits)| {
+            std::cmp::Reverse(commits.iter().map(Commit::timestamp).max())
+        });
+
+        let mut diaries = Vec::new();
+        for (branch, commits) in branches {
+            println!("🌿 Branch {} ({} commit(s))", branch, commits.len());
+            let count = commits.len();
+            let path = self
+                .write_diary(
+                    Some(&range),
+                    self.date_labels(&range),
+                    commits,
+                    Some(branch.clone()),
+                )
+                .await?;
+            diaries.push(BranchDiary {
+                branch,
+                path,
+                commits: count,
+            });
+        }
+
+        let (start_date, end_date) = self.date_labels(&range);
+        let index_path = self.storage.save_branch_index(&BranchIndex {
+            start_date,
+            end_date,
+            diaries: diaries.clone(),
+        })?;
+
+        Ok((index_path, diaries))
+    }
+
+    /// Writes one diary per day of `range` that has commits, each with its own
+    /// summary, and returns their paths, oldest day first
+    ///
+    /// The commits are read once and split by logical day, so the day cutoff
+    /// applies. Manual notes and context aren't tied to a day, so every daily
+    /// diary includes them.
+    pub async fn generate_daily_diaries_for_range(&self, range: DateRange) -> Result<Vec<String>> {
+        let commits = self.collect_commits(&range)?;
+
+        let mut paths = Vec::new();
+        let mut date = self.datetime_provider.logical_date(range.start);
+        let last_day = self.datetime_provider.last_day(&range);
+        while date <= last_day {
+            let next = date + Days::new(1);
+            let day = DateRange::new(
+                self.datetime_provider.start_of_day(date).max(range.start),
+                self.datetime_provider.start_of_day(next).min(range.end),
+            );
+            let day_commits: Vec<Commit> = commits
+                .iter()
+                .filter(|commit| day.contains(commit.timestamp()))
+                .cloned()
+                .collect();
+            if !day_commits.is_empty() {
+                println!("📅 {} ({} commit(s))", date, day_commits.len());
+                let path = self
+                    .write_diary(Some(&day), self.date_labels(&day), day_commits, None)
+                    .await?;
+                paths.push(path);
+            }
+            date = next;
+        }
+        if paths.is_empty() {
+            bail!("No day in this period has commits");
+        }
+
+        Ok(paths)
+    }
+
+    /// Reads the commits in `range` and runs them through the filters, ready to summarize
+    fn collect_commits(&self, range: &DateRange) -> Result<Vec<Commit>> {
+        // Get commits from git repository, dropping anything past the end of the range
+        let mut commits = self.git_repo.get_commits_since(range.start.timestamp())?;
+        for commit in &mut commits {
+            commit.apply_date_order(self.date_order);
+        }
+        let future_dated = self.clamp_future_commits(&mut commits, range);
+        if future_dated > 0 {
+            println!(
+                "⚠️ {} commit(s) are dated in the future (clock skew?); they are listed at the current time",
+                future_dated
+            );
+        }
+        commits.retain(|commit| range.contains(commit.timestamp()));
+
+        Ok(self.filter_commits(commits))
+    }
+
+    /// Runs collected commits through identity normalization, the author and
+    /// path filters, rewrites and grouping
+    fn filter_commits(&self, mut commits: Vec<Commit>) -> Vec<Commit> {
+        self.identities.normalize(&mut commits);
+        if let Some(filter) = &self.author_filter {
+            commits.retain(|commit| {
+                commit
+                    .author
+                    .as_ref()
+                    .is_some_and(|author| filter.matches(author))
+            });
+        }
+        if let Some(filter) = &self.path_filter {
+            commits.retain(|commit| filter.matches(commit));
+        }
+        commits = apply_empty_message_policy(&commits, self.empty_messages);
+        if self.expand_squash_merges {
+            commits = expand_squash_merges(&commits);
+        }
+        if self.group_dependency_updates {
+            commits = group_dependency_updates(&commits);
+        }
+        if !self.rewrites.is_empty() {
+            commits = self.rewrites.apply_to_commits(&commits);
+        }
+        infer_scopes(&mut commits);
+        if self.deterministic {
+            sort_commits(&mut commits);
+        }
+
+        commits
+    }
+
+    /// Summarizes `commits` and saves them as the diary for the given start and end dates
+    ///
+    /// Without a `range`, code reviews and the week rollup are left out.
+    async fn write_diary(
+        &self,
+        range: Option<&DateRange>,
+        (start_date, end_date): (String, String),
+        commits: Vec<Commit>,
+        branch: Option<String>,
+    ) -> Result<String> {
+        // Format commit logs
+        let commit_logs = self.format_commit_logs(&commits, &start_date, &end_date);
+
+        // Print the commit logs
+        println!("{}", commit_logs);
+
+        // Get summary from AI
+        let mut request = self.build_summary_request(&commits);
+        if self.message_source == MessageSource::PullRequest {
+            self.describe_pull_requests(&mut request.commits).await;
+        }
+        if request.omitted_commits > 0 {
+            println!(
+                "Only the {} most recent commits are summarized ({} omitted)",
+                request.commits.len(),
+                request.omitted_commits
+            );
+        }
+        if self.sensitive_scan != SensitiveScan::Off {
+            let findings = scan_commits(&request.commits);
+            if !findings.is_empty() {
+                println!("{}", format_report(&findings));
+                if self.sensitive_scan == SensitiveScan::Block {
+                    bail!(
+                        "Refusing to send commits with sensitive data; pass --allow-sensitive to send them anyway"
+                    );
+                }
+            }
+        }
+        let reviews = match range {
+            Some(range) => self.fetch_reviews(range).await,
+            None => Vec::new(),
+        };
+        request.reviews = reviews.clone();
+        let tickets = match range {
+            Some(range) => self.fetch_tickets(range).await,
+            None => Vec::new(),
+        };
+        request.tickets = tickets.clone();
+        request.tests = range.and_then(|range| self.fetch_test_health(range));
+        if let Some(tests) = &request.tests {
+            println!("🧪 Tests: {}", tests);
+        }
+        request.model = route_model(&self.routing, commits.len()).map(str::to_string);
+        if let Some(model) = &request.model {
+            println!("🧭 Routing {} commit(s) to {}", commits.len(), model);
+        }
+        let languages = self.resolve_languages(&request.commits);
+        request.language = languages.first().cloned();
+        if let Some(check) = &self.output_check {
+            check.check_output(&start_date, &end_date, branch.clone())?;
+        }
+        if let Some(confirmation) = &self.confirmation {
+            if !confirmation.confirm(&request, languages.len().max(1))? {
+                bail!("Cancelled before sending anything to the AI");
+            }
+        }
+        request.topics = self.topics;
+        let (summary, topics) = match self.ai_summarizer.summarize_commits(&request).await? {
+            summary if self.topics => split_topics(&summary),
+            summary => (summary, Vec::new()),
+        };
+        let summary = self.post_processing.apply(&summary);
+        request.topics = false;
+
+        // Print the summary
+        println!("Summary:");
+        println!("------------------------------------");
+        println!("{}", summary);
+
+        if !topics.is_empty() {
+            println!("🏷️ Topics: {}", topics.join(", "));
+        }
+
+        let mut translations = Vec::new();
+        for language in languages.iter().skip(1) {
+            request.language = Some(language.clone());
+            let summary = self
+                .post_processing
+                .apply(&self.ai_summarizer.summarize_commits(&request).await?);
+            println!("Summary ({}):", language);
+            println!("------------------------------------");
+            println!("{}", summary);
+            translations.push(TranslatedSummary {
+                language: language.clone(),
+                summary,
+            });
+        }
+
+        let sessions = self
+            .summarize_sessions(&commits, languages.first().cloned(), request.model.clone())
+            .await;
+        for session in &sessions {
+            println!("🕒 {}", session);
+        }
+
+        // Create diary content
+        let rollup = self
+            .rollup
+            .zip(range)
+            .map(|(settings, range)| self.build_week_rollup(&commits, range, settings));
+        let pull_requests = self.build_pull_request_groups(&commits).await;
+        let health = self.health_source.as_ref().and_then(|source| {
+            source
+                .repo_health(&commits)
+                .inspect_err(|e| println!("⚠️ Couldn't measure repo health: {}", e))
+                .ok()
+        });
+        let work_hours = self.work_hours.then(|| WorkHours::from_commits(&commits));
+        let coding_time = match range {
+            Some(range) => self.fetch_coding_time(range).await,
+            None => None,
+        };
+        let attachments = match &self.attachment_source {
+            Some(source) => source
+                .attach(&commits)
+                .inspect_err(|e| println!("⚠️ Couldn't attach images: {}", e))
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+        let performance = performance_claims(&commits);
         let content = DiaryContent {
             commits,
             summary,
             start_date,
             end_date,
+            rollup,
+            notes: self.notes.clone(),
+            pull_requests,
+            translations,
+            context: self.context.clone(),
+            branch,
+            reviews,
+            tickets,
+            model: request.model,
+            health,
+            sessions,
+            work_hours,
+            coding_time,
+            attachments,
+            performance,
+            topics,
+            commit_order: self.commit_order,
+        };
+
+        // Save diary to storage
+        let file_path = self.storage.save_diary(&content)?;
+
+        // The diary is saved, so a failed notification only warrants a warning
+        if let Some(notifier) = &self.notifier {
+            if let Err(e) = notifier.notify(&completion_message(&content)) {
+                eprintln!("⚠️ Failed to send notification: {}", e);
+            }
+        }
+
+        Ok(file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use chrono::{Duration, FixedOffset, TimeZone};
+
+    // Test helper functions
+    fn create_test_commit(message: &str, time: i64) -> Commit {
+        Commit::new(message.to_string(), time)
+    }
+
+    fn create_test_commits() -> Vec<Commit> {
+        vec![
+            create_test_commit("First commit", 1704067200), // 2024-01-01
+            create_test_commit("Second commit", 1704153600), // 2024-01-02
+            create_test_commit("Third commit with special chars: !@#$%^&*()", 1704240000), // 2024-01-03
+            create_test_commit(&"A".repeat(500), 1704326400), // Very long commit message (2024-01-04)
+        ]
+    }
+
+    struct TestDateTimeProvider {
+        now: DateTime<Local>,
+        day_cutoff_hour: u32,
+    }
+
+    impl TestDateTimeProvider {
+        fn new(now: DateTime<Local>) -> Self {
+            Self {
+                now,
+                day_cutoff_hour: 0,
+            }
+        }
+
+        fn with_day_cutoff_hour(now: DateTime<Local>, day_cutoff_hour: u32) -> Self {
+            Self {
+                now,
+                day_cutoff_hour,
+            }
+        }
+    }
+
+    impl DateTimeProvider for TestDateTimeProvider {
+        fn now(&self) -> DateTime<Local> {
+            self.now
+        }
+
+        fn day_cutoff_hour(&self) -> u32 {
+            self.day_cutoff_hour
+        }
+    }
+
+    // Basic commit and display tests
+    #[test]
+    fn test_commit_datetime_formatting() {
+        let commit = create_test_commit("Test commit", 1714989869);
+
+        assert!(commit.datetime().is_some());
+        assert!(commit.to_string().contains("Test commit"));
+    }
+
+    #[test]
+    fn test_commit_subject_strips_reflog_action() {
+        assert_eq!(
+            create_test_commit("commit: Add parser", 0).subject(),
+            "Add parser"
+        );
+        assert_eq!(
+            create_test_commit("commit (amend): Add parser", 0).subject(),
+            "Add parser"
+        );
+        assert_eq!(
+            create_test_commit("fix: handle empty input", 0).subject(),
+            "fix: handle empty input"
+        );
+    }
+
+    // Revert detection tests
+    #[test]
+    fn test_pair_reverts() {
+        let commits = vec![
+            create_test_commit("commit: Revert \"Add dark mode\"", 1704240000),
+            create_test_commit("commit: Fix typo", 1704153600),
+            create_test_commit("commit: Add dark mode", 1704067200),
+        ];
+
+        let paired = pair_reverts(&commits);
+
+        assert_eq!(paired.len(), 2);
+        assert_eq!(paired[0].message, "Attempted and reverted: Add dark mode");
+        assert_eq!(paired[0].timestamp(), 1704240000);
+        assert_eq!(paired[1].message, "commit: Fix typo");
+    }
+
+    #[test]
+    fn test_pair_reverts_keeps_unmatched_revert() {
+        let commits = vec![create_test_commit("Revert \"Old feature\"", 1704067200)];
+
+        let paired = pair_reverts(&commits);
+
+        assert_eq!(paired.len(), 1);
+        assert_eq!(paired[0].message, "Revert \"Old feature\"");
+    }
+
+    #[test]
+    fn test_pair_reverts_revert_of_revert() {
+        let commits = vec![
+            create_test_commit("Revert \"Revert \"Add cache\"\"", 1704240000),
+            create_test_commit("Revert \"Add cache\"", 1704153600),
+            create_test_commit("Add cache", 1704067200),
+        ];
+
+        let paired = pair_reverts(&commits);
+
+        // The cache was restored, so only the revert is reported as rolled back
+        assert_eq!(paired.len(), 2);
+        assert_eq!(
+            paired[0].message,
+            "Attempted and reverted: Revert \"Add cache\""
+        );
+        assert_eq!(paired[1].message, "Add cache");
+    }
+
+    #[test]
+    fn test_highlights_request_from_commits() {
+        let commits = vec![
+            create_test_commit("Add export", 1704240000),
+            create_test_commit("Add billing", 1704067200),
+            create_test_commit("Add search", 1704153600),
+        ];
+
+        let request = HighlightsRequest::from_commits(&commits, 2);
+
+        let messages: Vec<&str> = request
+            .commits
+            .iter()
+            .map(|commit| commit.message.as_str())
+            .collect();
+        assert_eq!(messages, vec!["Add search", "Add export"]);
+        assert_eq!(request.omitted_commits, 1);
+        assert!(request.diaries.is_empty());
+    }
+
+    // Calendar helper tests
+    #[test]
+    fn test_calendar_range_day() {
+        let now = Local.with_ymd_and_hms(2024, 5, 8, 15, 30, 0).unwrap();
+        let provider = TestDateTimeProvider::new(now);
+
+        let range = provider.calendar_range(now.date_naive(), Granularity::Day);
+
+        assert_eq!(
+            range.start,
+            Local.with_ymd_and_hms(2024, 5, 8, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            range.end,
+            Local.with_ymd_and_hms(2024, 5, 9, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            provider.last_day(&range),
+            NaiveDate::from_ymd_opt(2024, 5, 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_start_of_week() {
+        // 2024-05-08 is a Wednesday
+        let now = Local.with_ymd_and_hms(2024, 5, 8, 15, 30, 0).unwrap();
+        let provider = TestDateTimeProvider::new(now);
+
+        assert_eq!(
+            provider.start_of_week(Weekday::Mon),
+            Local.with_ymd_and_hms(2024, 5, 6, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            provider.start_of_week(Weekday::Sun),
+            Local.with_ymd_and_hms(2024, 5, 5, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            provider.start_of_week(Weekday::Wed),
+            Local.with_ymd_and_hms(2024, 5, 8, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_day_cutoff_hour() {
+        // 02:30 still belongs to the previous day with a 4 AM cutoff
+        let now = Local.with_ymd_and_hms(2024, 5, 8, 2, 30, 0).unwrap();
+        let provider = TestDateTimeProvider::with_day_cutoff_hour(now, 4);
+
+        let today = provider.today();
+        assert_eq!(today, NaiveDate::from_ymd_opt(2024, 5, 7).unwrap());
+
+        let range = provider.calendar_range(today, Granularity::Day);
+        assert_eq!(
+            range.start,
+            Local.with_ymd_and_hms(2024, 5, 7, 4, 0, 0).unwrap()
+        );
+        assert_eq!(
+            range.end,
+            Local.with_ymd_and_hms(2024, 5, 8, 4, 0, 0).unwrap()
+        );
+        assert_eq!(provider.last_day(&range), today);
+        assert!(range.contains(now.timestamp()));
+    }
+
+    #[test]
+    fn test_week_numbering() {
+        // 2024 starts on a Monday; 2021 starts on a Friday
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        assert_eq!(WeekNumbering::Iso.week_of(date(2024, 5, 8)), (2024, 19));
+        assert_eq!(WeekNumbering::Us.week_of(date(2024, 5, 8)), (2024, 19));
+        assert_eq!(WeekNumbering::Iso.week_of(date(2021, 1, 2)), (2020, 53));
+        assert_eq!(WeekNumbering::Us.week_of(date(2021, 1, 2)), (2021, 1));
+        assert_eq!(WeekNumbering::Us.week_of(date(2021, 1, 3)), (2021, 2));
+    }
+
+    #[test]
+    fn test_build_week_rollup() {
+        let now = Local.with_ymd_and_hms(2024, 5, 12, 12, 0, 0).unwrap();
+        let provider = Arc::new(TestDateTimeProvider::with_day_cutoff_hour(now, 4));
+        let generator = DiaryGenerator::new(
+            Arc::new(MockGitRepository::new()),
+            Arc::new(MockAISummarizer::new()),
+            Arc::new(MockDiaryStorage::new()),
+            provider.clone(),
+            7,
+        );
+        let at = |d, h| {
+            Local
+                .with_ymd_and_hms(2024, 5, d, h, 0, 0)
+                .unwrap()
+                .timestamp()
+        };
+        let commits = vec![
+            create_test_commit("Late night fix", at(8, 2)),
+            create_test_commit("Wednesday work", at(8, 10)),
+            create_test_commit("Monday work", at(6, 9)),
+        ];
+        let range = provider.calendar_range(
+            NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(),
+            Granularity::Week(Weekday::Mon),
+        );
+
+        let rollup = generator.build_week_rollup(
+            &commits,
+            &range,
+            RollupSettings {
+                numbering: WeekNumbering::Iso,
+                locale: Locale::de_DE,
+            },
+        );
+
+        assert_eq!(rollup.title, "Week 19, 2024");
+        let days: Vec<(&str, Vec<&str>)> = rollup
+            .days
+            .iter()
+            .map(|(heading, commits)| {
+                let messages = commits.iter().map(|c| c.message.as_str()).collect();
+                (heading.as_str(), messages)
+            })
+            .collect();
+        assert_eq!(
+            days,
+            vec![
+                ("Montag, 6 Mai", vec!["Monday work"]),
+                ("Dienstag, 7 Mai", vec!["Late night fix"]),
+                ("Mittwoch, 8 Mai", vec!["Wednesday work"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_calendar_range_month() {
+        let now = Local.with_ymd_and_hms(2024, 3, 1, 9, 0, 0).unwrap();
+        let provider = TestDateTimeProvider::new(now);
+
+        // Last month, including the leap day
+        let date = NaiveDate::from_ymd_opt(2024, 2, 15).unwrap();
+        let range = provider.calendar_range(date, Granularity::Month);
+
+        assert_eq!(
+            range.start,
+            Local.with_ymd_and_hms(2024, 2, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            range.end,
+            Local.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            provider.last_day(&range),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+    }
+
+    /// Central European time in 2024: clocks go forward from 02:00 to 03:00 on
+    /// March 31 and back from 03:00 to 02:00 on October 27
+    #[derive(Debug, Clone, Copy)]
+    struct CentralEurope2024;
+
+    impl CentralEurope2024 {
+        fn winter() -> FixedOffset {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+
+        fn summer() -> FixedOffset {
+            FixedOffset::east_opt(7200).unwrap()
+        }
+
+        fn at(day: (u32, u32), time: (u32, u32)) -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2024, day.0, day.1)
+                .unwrap()
+                .and_hms_opt(time.0, time.1, 0)
+                .unwrap()
+        }
+    }
+
+    impl TimeZone for CentralEurope2024 {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Self
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            // Summer first, as it gives the earlier instant when both apply
+            let offsets: Vec<FixedOffset> = [Self::summer(), Self::winter()]
+                .into_iter()
+                .filter(|offset| {
+                    let utc = *local - Duration::seconds(offset.local_minus_utc() as i64);
+                    self.offset_from_utc_datetime(&utc) == *offset
+                })
+                .collect();
+            match offsets[..] {
+                [offset] => LocalResult::Single(offset),
+                [earlier, later] => LocalResult::Ambiguous(earlier, later),
+                _ => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            let summer_time = Self::at((3, 31), (1, 0))..Self::at((10, 27), (1, 0));
+            if summer_time.contains(utc) {
+                Self::summer()
+            } else {
+                Self::winter()
+            }
+        }
+    }
+
+    #[test]
+    fn test_days_before_keeps_the_wall_clock_time_across_dst() {
+        let tz = CentralEurope2024;
+        let local = |day, time| resolve_local(&tz, CentralEurope2024::at(day, time));
+
+        // March 31 has 23 hours, so 24 hours back from noon is 11:00 the day before
+        let noon = local((3, 31), (12, 0));
+        assert_eq!(
+            days_before(&noon, 1).naive_local(),
+            CentralEurope2024::at((3, 30), (12, 0))
+        );
+        assert_eq!((noon - days_before(&noon, 1)).num_hours(), 23);
+
+        // October 27 has 25 hours, so 24 hours back from just after midnight is still October 27
+        let after_midnight = local((10, 28), (0, 30));
+        assert_eq!(
+            days_before(&after_midnight, 1).naive_local(),
+            CentralEurope2024::at((10, 27), (0, 30))
+        );
+        assert_eq!(
+            days_before(&after_midnight, 7).naive_local(),
+            CentralEurope2024::at((10, 21), (0, 30))
+        );
+    }
+
+    #[test]
+    fn test_resolve_local_around_dst_changes() {
+        let tz = CentralEurope2024;
+
+        // Skipped when clocks go forward: as far past the gap as it was into it
+        let skipped = resolve_local(&tz, CentralEurope2024::at((3, 31), (2, 30)));
+        assert_eq!(
+            skipped.naive_local(),
+            CentralEurope2024::at((3, 31), (3, 30))
+        );
+        assert_eq!(skipped.offset(), &CentralEurope2024::summer());
+
+        // Repeated when clocks go back: the first time it's shown
+        let repeated = resolve_local(&tz, CentralEurope2024::at((10, 27), (2, 30)));
+        assert_eq!(
+            repeated.naive_utc(),
+            CentralEurope2024::at((10, 27), (0, 30))
+        );
+
+        // Day windows from midnight to midnight follow the clock, not 24-hour steps
+        let midnight = |day| resolve_local(&tz, CentralEurope2024::at(day, (0, 0)));
+        assert_eq!((midnight((4, 1)) - midnight((3, 31))).num_hours(), 23);
+        assert_eq!((midnight((10, 28)) - midnight((10, 27))).num_hours(), 25);
+        assert_eq!((midnight((10, 29)) - midnight((10, 28))).num_hours(), 24);
+    }
+
+    #[test]
+    fn test_logical_date_uses_the_wall_clock_time() {
+        let now = Local.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap();
+        let provider = TestDateTimeProvider::with_day_cutoff_hour(now, 4);
+
+        assert_eq!(
+            provider.logical_date(Local.with_ymd_and_hms(2024, 3, 31, 4, 30, 0).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 3, 31).unwrap()
+        );
+        assert_eq!(
+            provider.logical_date(Local.with_ymd_and_hms(2024, 3, 31, 3, 59, 0).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 3, 30).unwrap()
+        );
+        assert_eq!(
+            provider.days_ago(2).date_naive(),
+            NaiveDate::from_ymd_opt(2024, 3, 29).unwrap()
+        );
+    }
+
+    // DiaryGenerator tests
+    #[tokio::test]
+    async fn test_default_ping_refuses() {
+        struct Summarizer;
+
+        #[async_trait]
+        impl AISummarizer for Summarizer {
+            async fn summarize_commits(&self, _request: &SummaryRequest) -> Result<String> {
+                Ok(String::new())
+            }
+
+            async fn summarize_highlights(&self, _request: &HighlightsRequest) -> Result<String> {
+                Ok(String::new())
+            }
+        }
+
+        assert!(Summarizer.ping().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_success() {
+        // Setup mocks
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        let test_commits = create_test_commits();
+        let expected_file_path = "diaries/test-diary.md".to_string();
+
+        let expected_file_path_2 = expected_file_path.clone();
+
+        // Set mock expectations
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| Ok(test_commits.clone()));
+
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|_| Ok("This is a test summary".to_string()));
+
+        mock_storage
+            .expect_save_diary()
+            .returning(move |_| Ok(expected_file_path.clone()));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        );
+
+        // Execute
+        let result = generator.generate_diary().await;
+
+        // Verify
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), expected_file_path_2);
+    }
+
+    #[test]
+    fn test_commit_order() {
+        let mut big = create_test_commit("Rewrite parser", 300);
+        big.line_stats = Some(LineStats::new(400, 120));
+        let mut small = create_test_commit("Fix typo", 200);
+        small.line_stats = Some(LineStats::new(1, 1));
+        let mut also_small = create_test_commit("Bump version", 100);
+        also_small.line_stats = Some(LineStats::new(1, 1));
+        // Reflog order after a rebase: newest first, with a tie
+        let commits = vec![
+            big,
+            small,
+            create_test_commit("Merge main", 200),
+            also_small,
+        ];
+        let messages = |order: CommitOrder| {
+            order
+                .ordered(&commits)
+                .into_iter()
+                .map(|commit| commit.message.as_str())
+                .collect::<Vec<&str>>()
+        };
+
+        assert_eq!(
+            messages(CommitOrder::Chronological),
+            vec!["Bump version", "Fix typo", "Merge main", "Rewrite parser"]
+        );
+        assert_eq!(
+            messages(CommitOrder::Reverse),
+            vec!["Rewrite parser", "Fix typo", "Merge main", "Bump version"]
+        );
+        assert_eq!(
+            messages(CommitOrder::Impact),
+            vec!["Rewrite parser", "Bump version", "Fix typo", "Merge main"]
+        );
+    }
+
+    #[test]
+    fn test_apply_date_order() {
+        // Rebased on 2024-01-07, first written on 2024-01-02
+        let rebased =
+            Commit::new("Fix parser".to_string(), 1704585600).with_author_time(1704153600);
+        let ordered = |order| {
+            let mut commit = rebased.clone();
+            commit.apply_date_order(order);
+            commit.timestamp()
+        };
+
+        assert_eq!(ordered(DateOrder::Committer), 1704585600);
+        assert_eq!(ordered(DateOrder::Author), 1704153600);
+        assert_eq!(ordered(DateOrder::Newest), 1704585600);
+
+        let mut plain = Commit::new("Fix parser".to_string(), 1704585600);
+        plain.apply_date_order(DateOrder::Author);
+        assert_eq!(plain.timestamp(), 1704585600);
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_author_date_order() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        // Both rebased today; only one was written within the last day
+        let commits = vec![
+            Commit::new("Old work".to_string(), now.timestamp() - 60).with_author_time(1704067200),
+            Commit::new("Fresh work".to_string(), now.timestamp() - 60)
+                .with_author_time(now.timestamp() - 3600),
+        ];
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| Ok(commits.clone()));
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .withf(|request| {
+                request.commits.len() == 1 && request.commits[0].message == "Fresh work"
+            })
+            .returning(|_| Ok("Summary".to_string()));
+        mock_storage
+            .expect_save_diary()
+            .returning(|_| Ok("diaries/test-diary.md".to_string()));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            1,
+        )
+        .with_date_order(DateOrder::Author);
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_git_error() {
+        // Setup mocks
+        let mut mock_git_repo = MockGitRepository::new();
+        let mock_ai_summarizer = MockAISummarizer::new();
+        let mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        // Set mock expectations - simulate Git error
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Err(anyhow!("Git repository error")));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        );
+
+        // Execute
+        let result = generator.generate_diary().await;
+
+        // Verify
+        assert!(result.is_err());
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("Git repository error"));
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_ai_error() {
+        // Setup mocks
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        let test_commits = create_test_commits();
+
+        // Set mock expectations - Git success but AI error
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| Ok(test_commits.clone()));
+
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|_| Err(anyhow!("AI service error")));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        );
+
+        // Execute
+        let result = generator.generate_diary().await;
+
+        // Verify
+        assert!(result.is_err());
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("AI service error"));
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_storage_error() {
+        // Setup mocks
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        let test_commits = create_test_commits();
+
+        // Set mock expectations - Git and AI success, storage error
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| Ok(test_commits.clone()));
+
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|_| Ok("This is a test summary".to_string()));
+
+        mock_storage
+            .expect_save_diary()
+            .returning(|_| Err(anyhow!("Storage error")));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        );
+
+        // Execute
+        let result = generator.generate_diary().await;
+
+        // Verify
+        assert!(result.is_err());
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("Storage error"));
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_empty_commits() {
+        // Setup mocks
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        // Set mock expectations - return empty commit list
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(Vec::new()));
+
+        // AI should still be called even with empty commits
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| {
+                assert!(request.commits.is_empty());
+                Ok("No activity in the last 7 days".to_string())
+            });
+
+        mock_storage.expect_save_diary().returning(|content| {
+            assert!(content.commits.is_empty());
+            assert_eq!(content.summary, "No activity in the last 7 days");
+            Ok("diaries/empty-diary.md".to_string())
+        });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        );
+
+        // Execute
+        let result = generator.generate_diary().await;
+
+        // Verify
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dyn_diary_generator() -> Result<()> {
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let build = |file_path: &'static str| {
+            let mut mock_git_repo = MockGitRepository::new();
+            let mut mock_ai_summarizer = MockAISummarizer::new();
+            let mut mock_storage = MockDiaryStorage::new();
+            mock_git_repo
+                .expect_get_commits_since()
+                .returning(|_| Ok(create_test_commits()));
+            mock_ai_summarizer
+                .expect_summarize_commits()
+                .returning(|_| Ok("Summary".to_string()));
+            mock_storage
+                .expect_save_diary()
+                .returning(move |_| Ok(file_path.to_string()));
+            (mock_git_repo, mock_ai_summarizer, mock_storage)
+        };
+
+        // Built straight from trait objects
+        let (git_repo, ai_summarizer, storage) = build("diaries/dyn.md");
+        let git_repo: Arc<dyn GitRepository> = Arc::new(git_repo);
+        let ai_summarizer: Arc<dyn AISummarizer> = Arc::new(ai_summarizer);
+        let storage: Arc<dyn DiaryStorage> = Arc::new(storage);
+        let datetime_provider: Arc<dyn DateTimeProvider> = Arc::new(TestDateTimeProvider::new(now));
+        let from_objects =
+            DynDiaryGenerator::new(git_repo, ai_summarizer, storage, datetime_provider, 7);
+
+        // Or erased from a generic one
+        let (git_repo, ai_summarizer, storage) = build("diaries/erased.md");
+        let erased = DiaryGenerator::new(
+            Arc::new(git_repo),
+            Arc::new(ai_summarizer),
+            Arc::new(storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_max_commits(1)
+        .into_dyn();
+
+        // Both have the same type, so they fit in one collection
+        let generators: Vec<DynDiaryGenerator> = vec![from_objects, erased];
+        let mut paths = Vec::new();
+        for generator in &generators {
+            paths.push(generator.generate_diary().await?);
+        }
+
+        assert_eq!(paths, vec!["diaries/dyn.md", "diaries/erased.md"]);
+        assert_eq!(generators[1].max_commits, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_for_range_excludes_later_commits() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        let test_commits = create_test_commits();
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| Ok(test_commits.clone()));
+
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|_| Ok("This is a test summary".to_string()));
+
+        // Only the first two commits fall inside the range
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.commits.len(), 2);
+            Ok("diaries/range-diary.md".to_string())
+        });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        );
+
+        let range = DateRange::new(
+            Local.timestamp_opt(1704067200, 0).unwrap(),
+            Local.timestamp_opt(1704240000, 0).unwrap(),
+        );
+        let result = generator.generate_diary_for_range(range).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_max_commits() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+
+        let test_commits = create_test_commits();
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| Ok(test_commits.clone()));
+
+        // Only the two most recent commits reach the summarizer
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| {
+                assert_eq!(request.commits.len(), 2);
+                assert_eq!(request.omitted_commits, 2);
+                assert_eq!(request.length, SummaryLength::Long);
+                assert_eq!(request.commits[0].timestamp(), 1704326400);
+                assert_eq!(request.commits[1].timestamp(), 1704240000);
+                Ok("This is a test summary".to_string())
+            });
+
+        // The stored diary still lists every commit
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.commits.len(), 4);
+            Ok("diaries/test-diary.md".to_string())
+        });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            datetime_provider,
+            7,
+        )
+        .with_max_commits(2)
+        .with_length(SummaryLength::Long);
+
+        let result = generator.generate_diary().await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_rewrites_messages() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Fix JIRA-42", 1704326400)]));
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| {
+                assert_eq!(request.commits[0].message, "Fix ticket 42");
+                Ok("This is a test summary".to_string())
+            });
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.commits[0].message, "Fix ticket 42");
+            Ok("diaries/test-diary.md".to_string())
+        });
+
+        let rewrites = RewritePipeline::new(&[crate::rewrite::RewriteRuleConfig {
+            pattern: r"JIRA-(\d+)".to_string(),
+            replace: "ticket $1".to_string(),
+        }])
+        .unwrap();
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_rewrites(rewrites);
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_bilingual() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Add export", 1704326400)]));
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .times(2)
+            .returning(|request| Ok(format!("Summary in {:?}", request.language)));
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.summary, "Summary in Some(\"en\")");
+            assert_eq!(
+                content.translations,
+                vec![TranslatedSummary {
+                    language: "ja".to_string(),
+                    summary: "Summary in Some(\"ja\")".to_string(),
+                }]
+            );
+            Ok("diaries/test-diary.md".to_string())
+        });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_languages(vec!["en".to_string(), "ja".to_string()]);
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_routes_by_size() {
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let routing = vec![
+            RoutingRule {
+                max_commits: Some(1),
+                model: "gpt-4o-mini".to_string(),
+            },
+            RoutingRule {
+                max_commits: None,
+                model: "gpt-4o".to_string(),
+            },
+        ];
+        let create_generator = |commits: usize, expected: &'static str| {
+            let mut mock_git_repo = MockGitRepository::new();
+            let mut mock_ai_summarizer = MockAISummarizer::new();
+            let mut mock_storage = MockDiaryStorage::new();
+
+            mock_git_repo
+                .expect_get_commits_since()
+                .returning(move |_| {
+                    Ok((0..commits)
+                        .map(|i| create_test_commit("Fix cache", 1704326400 + i as i64))
+                        .collect())
+                });
+            mock_ai_summarizer
+                .expect_summarize_commits()
+                .returning(move |request| {
+                    assert_eq!(request.model.as_deref(), Some(expected));
+                    Ok("This is a test summary".to_string())
+                });
+            mock_storage.expect_save_diary().returning(move |content| {
+                assert_eq!(content.model.as_deref(), Some(expected));
+                Ok("diaries/test-diary.md".to_string())
+            });
+
+            DiaryGenerator::new(
+                Arc::new(mock_git_repo),
+                Arc::new(mock_ai_summarizer),
+                Arc::new(mock_storage),
+                Arc::new(TestDateTimeProvider::new(now)),
+                7,
+            )
+            .with_model_routing(routing.clone())
+        };
+
+        assert!(create_generator(1, "gpt-4o-mini")
+            .generate_diary()
+            .await
+            .is_ok());
+        assert!(create_generator(3, "gpt-4o").generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_repo_health() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_health = MockHealthSource::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Add tests", 1704326400)]));
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|_| Ok("This is a test summary".to_string()));
+        mock_health.expect_repo_health().returning(|commits| {
+            assert_eq!(commits.len(), 1);
+            Ok(RepoHealth {
+                test_files_added: 2,
+                ..RepoHealth::default()
+            })
+        });
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.health.as_ref().unwrap().test_files_added, 2);
+            Ok("diaries/test-diary.md".to_string())
+        });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_health_source(Arc::new(mock_health));
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_test_runs() {
+        use crate::testruns::{TestCase, TestOutcome};
+
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_test_runs = MockTestRunSource::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Fix parser", 1704326400)]));
+        mock_test_runs
+            .expect_test_runs_between()
+            .returning(|_, until| {
+                let run = |finished_at, outcome| TestRun {
+                    finished_at,
+                    cases: vec![TestCase {
+                        name: "parses_dates".to_string(),
+                        outcome,
+                    }],
+                };
+                Ok(vec![
+                    run(until - 20, TestOutcome::Failed),
+                    run(until - 10, TestOutcome::Passed),
+                ])
+            });
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .withf(|request| {
+                request
+                    .tests
+                    .as_ref()
+                    .is_some_and(|tests| tests.fixed == ["parses_dates"] && tests.is_green())
+            })
+            .returning(|_| Ok("This is a test summary".to_string()));
+        mock_storage
+            .expect_save_diary()
+            .returning(|_| Ok("diaries/test-diary.md".to_string()));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_test_run_source(Arc::new(mock_test_runs));
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[test]
+    fn test_resolve_auto_language() {
+        let generator = |languages: &[&str]| {
+            DiaryGenerator::new(
+                Arc::new(MockGitRepository::new()),
+                Arc::new(MockAISummarizer::new()),
+                Arc::new(MockDiaryStorage::new()),
+                Arc::new(TestDateTimeProvider::new(Local::now())),
+                7,
+            )
+            .with_languages(languages.iter().map(|l| l.to_string()).collect())
         };
+        let commits = vec![
+            create_test_commit("commit: キャッシュのバグを修正", 1704326400),
+            create_test_commit("commit: 設定画面を追加", 1704240000),
+        ];
+
+        assert_eq!(generator(&["auto"]).resolve_languages(&commits), vec!["ja"]);
+        assert_eq!(
+            generator(&["en", "auto"]).resolve_languages(&commits),
+            vec!["en", "ja"]
+        );
+        assert_eq!(
+            generator(&["ja", "auto"]).resolve_languages(&commits),
+            vec!["ja"]
+        );
+        assert!(generator(&["auto"]).resolve_languages(&[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_generate_from_commits() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+
+        mock_git_repo.expect_get_commits_since().never();
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .withf(|request| request.commits.len() == 2)
+            .returning(|_| Ok("Ported the build to the new CI".to_string()));
+        mock_storage
+            .expect_save_diary()
+            .withf(|content| {
+                content.start_date == "2019-03-01"
+                    && content.end_date == "2019-03-02"
+                    && content.commits.len() == 2
+                    && content.rollup.is_none()
+            })
+            .times(1)
+            .returning(|_| Ok("diaries/2019-03-01.md".to_string()));
+
+        // Dates far outside the default range are kept, empty messages still dropped
+        let commits = vec![
+            create_test_commit("Move builds to the new CI", 1551398400),
+            create_test_commit("", 1551402000),
+            create_test_commit("Cache dependencies in CI", 1551484800),
+        ];
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(Local::now())),
+            1,
+        )
+        .with_empty_message_policy(EmptyMessagePolicy::Drop);
+
+        let path = generator
+            .generate_from_commits(commits, "2019-03-01".to_string(), "2019-03-02".to_string())
+            .await
+            .unwrap();
+        assert_eq!(path, "diaries/2019-03-01.md");
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_notifies_on_completion() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_notifier = MockNotifier::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Add export", 1704326400)]));
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|_| Ok("This is a test summary".to_string()));
+        mock_storage
+            .expect_save_diary()
+            .returning(|_| Ok("diaries/test-diary.md".to_string()));
+        // A notification failure must not fail the run
+        mock_notifier
+            .expect_notify()
+            .withf(|message| message.ends_with("generated — 1 commit"))
+            .times(1)
+            .returning(|_| Err(anyhow!("no notification daemon")));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_notifier(Arc::new(mock_notifier));
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_summarize_sessions() {
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .withf(|request| request.length == SummaryLength::Short && request.notes.is_empty())
+            .times(2)
+            .returning(|request| {
+                Ok(format!(
+                    "## Worked on {}\nSecond line",
+                    request.commits[0].subject()
+                ))
+            });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(MockGitRepository::new()),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(MockDiaryStorage::new()),
+            Arc::new(TestDateTimeProvider::new(Local::now())),
+            1,
+        )
+        .with_notes(vec![Note::new("Planning meeting".to_string(), 0)])
+        .with_post_processing(
+            PostProcessPipeline::default().with_step(crate::postprocess::MarkdownStripper),
+        );
+        let commits = vec![
+            create_test_commit("Fix auth test", 1704096000),
+            create_test_commit("Retry auth", 1704099600),
+            create_test_commit("Start migrations", 1704124800),
+        ];
+
+        assert!(generator
+            .summarize_sessions(&commits, None, None)
+            .await
+            .is_empty());
+
+        let sessions = generator
+            .with_session_summaries(120)
+            .summarize_sessions(&commits, None, None)
+            .await;
+        let lines: Vec<(usize, &str)> = sessions
+            .iter()
+            .map(|session| (session.commits, session.summary.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (2, "Worked on Retry auth"),
+                (1, "Worked on Start migrations")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_stops_when_not_confirmed() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_confirmation = MockSendConfirmation::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Add export", 1704326400)]));
+        mock_ai_summarizer.expect_summarize_commits().never();
+        mock_storage.expect_save_diary().never();
+        mock_confirmation
+            .expect_confirm()
+            .withf(|request, times| request.commits.len() == 1 && *times == 1)
+            .times(1)
+            .returning(|_, _| Ok(false));
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_confirmation(Arc::new(mock_confirmation));
+
+        let error = generator.generate_diary().await.unwrap_err();
+        assert!(error.to_string().contains("Cancelled"));
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_checks_output_before_summarizing() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_check = MockOutputCheck::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(vec![create_test_commit("Add export", 1704326400)]));
+        mock_ai_summarizer.expect_summarize_commits().never();
+        mock_storage.expect_save_diary().never();
+        mock_check
+            .expect_check_output()
+            .withf(|_, end, branch| end == "2024-01-07" && branch.is_none())
+            .times(1)
+            .returning(|_, _, _| Err(anyhow!("Can't write to the diary directory")));
 
-        // Save diary to storage
-        let file_path = self.storage.save_diary(&content)?;
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_output_check(Arc::new(mock_check));
 
-        Ok(file_path)
+        let error = generator.generate_diary().await.unwrap_err();
+        assert!(error.to_string().contains("diary directory"));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::anyhow;
-    use chrono::{Duration, TimeZone};
+    #[test]
+    fn test_completion_message() {
+        let mut content = DiaryContent {
+            commits: create_test_commits(),
+            summary: String::new(),
+            start_date: "2024-05-07".to_string(),
+            end_date: "2024-05-07".to_string(),
+            rollup: None,
+            notes: Vec::new(),
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::default(),
+        };
+        assert_eq!(
+            completion_message(&content),
+            format!(
+                "Diary for May 7 generated — {} commits",
+                content.commits.len()
+            )
+        );
 
-    // Test helper functions
-    fn create_test_commit(message: &str, time: i64) -> Commit {
-        Commit::new(message.to_string(), time)
+        content.start_date = "2024-04-29".to_string();
+        content.commits.truncate(1);
+        assert_eq!(
+            completion_message(&content),
+            "Diary for April 29 – May 7 generated — 1 commit"
+        );
     }
 
-    fn create_test_commits() -> Vec<Commit> {
-        vec![
-            create_test_commit("First commit", 1704067200), // 2024-01-01
-            create_test_commit("Second commit", 1704153600), // 2024-01-02
-            create_test_commit("Third commit with special chars: !@#$%^&*()", 1704240000), // 2024-01-03
-            create_test_commit(&"A".repeat(500), 1704326400), // Very long commit message (2024-01-04)
-        ]
+    #[test]
+    fn test_parse_author_ident() {
+        assert_eq!(
+            Author::parse_ident("Jane Doe <jane@example.com> 1704067200 +0000"),
+            Some(Author::new(
+                "Jane Doe".to_string(),
+                "jane@example.com".to_string()
+            ))
+        );
+        assert_eq!(Author::parse_ident("Jane Doe"), None);
     }
 
-    struct TestDateTimeProvider {
-        now: DateTime<Local>,
-    }
+    #[tokio::test]
+    async fn test_diary_generator_future_dated_commits() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let future = now.timestamp() + 365 * 86400;
 
-    impl TestDateTimeProvider {
-        fn new(now: DateTime<Local>) -> Self {
-            Self { now }
-        }
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(move |_| {
+                Ok(vec![
+                    create_test_commit("Commit from a skewed clock", future),
+                    create_test_commit("Regular commit", 1704326400),
+                ])
+            });
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| {
+                assert_eq!(request.commits.len(), 2);
+                Ok("This is a test summary".to_string())
+            });
+        mock_storage.expect_save_diary().returning(move |content| {
+            let skewed = &content.commits[0];
+            assert_eq!(skewed.timestamp(), now.timestamp() - 1);
+            assert_eq!(skewed.claimed_time, Some(future));
+            assert!(skewed.to_string().contains("(⚠️ future-dated: claims "));
+            Ok("diaries/test-diary.md".to_string())
+        });
+
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        );
+
+        assert!(generator.generate_diary().await.is_ok());
     }
 
-    impl DateTimeProvider for TestDateTimeProvider {
-        fn now(&self) -> DateTime<Local> {
-            self.now
-        }
+    #[test]
+    fn test_clamp_future_commits_leaves_past_ranges_alone() {
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let provider = Arc::new(TestDateTimeProvider::new(now));
+        let generator = DiaryGenerator::new(
+            Arc::new(MockGitRepository::new()),
+            Arc::new(MockAISummarizer::new()),
+            Arc::new(MockDiaryStorage::new()),
+            provider.clone(),
+            7,
+        );
+        let mut commits = vec![create_test_commit("Future", now.timestamp() + 3600)];
 
-        fn days_ago(&self, days: i64) -> DateTime<Local> {
-            self.now - Duration::days(days)
-        }
+        let last_week = provider.calendar_range(
+            NaiveDate::from_ymd_opt(2023, 12, 25).unwrap(),
+            Granularity::Week(Weekday::Mon),
+        );
+        assert_eq!(generator.clamp_future_commits(&mut commits, &last_week), 0);
+
+        // Today's range ends at midnight, after now; the commit is clamped to now
+        let today = provider.calendar_range(provider.today(), Granularity::Day);
+        assert_eq!(generator.clamp_future_commits(&mut commits, &today), 1);
+        assert_eq!(commits[0].timestamp(), now.timestamp());
     }
 
-    // Basic commit and display tests
     #[test]
-    fn test_commit_datetime_formatting() {
-        let commit = create_test_commit("Test commit", 1714989869);
+    fn test_author_filter() {
+        let filter = AuthorFilter {
+            name: Some("Jane Doe".to_string()),
+            email: Some("jane@example.com".to_string()),
+        };
 
-        assert!(commit.datetime().is_some());
-        assert!(commit.to_string().contains("Test commit"));
+        let author = |name: &str, email: &str| Author::new(name.to_string(), email.to_string());
+        assert!(filter.matches(&author("J. Doe", "Jane@Example.com")));
+        assert!(filter.matches(&author("Jane Doe", "jane@laptop.local")));
+        assert!(!filter.matches(&author("John Roe", "john@example.com")));
+        assert!(!AuthorFilter::default().matches(&author("Jane Doe", "jane@example.com")));
     }
 
-    // DiaryGenerator tests
     #[tokio::test]
-    async fn test_diary_generator_success() {
-        // Setup mocks
+    async fn test_diary_generator_author_filter() {
         let mut mock_git_repo = MockGitRepository::new();
         let mut mock_ai_summarizer = MockAISummarizer::new();
         let mut mock_storage = MockDiaryStorage::new();
         let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
-        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
 
-        let test_commits = create_test_commits();
-        let expected_file_path = "diaries/test-diary.md".to_string();
+        mock_git_repo.expect_get_commits_since().returning(|_| {
+            Ok(vec![
+                create_test_commit("My change", 1704326400).with_author(Author::new(
+                    "Jane Doe".to_string(),
+                    "jane@example.com".to_string(),
+                )),
+                create_test_commit("Their change", 1704240000).with_author(Author::new(
+                    "John Roe".to_string(),
+                    "john@example.com".to_string(),
+                )),
+                create_test_commit("Unknown author", 1704153600),
+            ])
+        });
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| {
+                assert_eq!(request.commits.len(), 1);
+                assert_eq!(request.commits[0].message, "My change");
+                Ok("This is a test summary".to_string())
+            });
+        mock_storage
+            .expect_save_diary()
+            .returning(|_| Ok("diaries/test-diary.md".to_string()));
 
-        let expected_file_path_2 = expected_file_path.clone();
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(mock_ai_summarizer),
+            Arc::new(mock_storage),
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_author_filter(AuthorFilter {
+            name: None,
+            email: Some("jane@example.com".to_string()),
+        });
 
-        // Set mock expectations
-        mock_git_repo
-            .expect_get_commits_since()
-            .returning(move |_| Ok(test_commits.clone()));
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[test]
+    fn test_group_by_pull_request() {
+        let commits = vec![
+            create_test_commit("Merge pull request #12 from jane/export", 1704074400),
+            create_test_commit("Tidy README", 1704070800),
+            create_test_commit("commit: Add CSV export (#12)", 1704067200),
+            create_test_commit("Fix typo (#7)", 1704153600),
+            create_test_commit("Mention issue (see #3)", 1704153600),
+        ];
+
+        assert_eq!(commits[0].pull_request(), Some(12));
+        assert_eq!(commits[1].pull_request(), None);
+        assert_eq!(commits[4].pull_request(), None);
+        let bitbucket = create_test_commit("Merged in feature/sync (pull request #15)", 0);
+        assert_eq!(bitbucket.pull_request(), Some(15));
+
+        let groups = group_by_pull_request(&commits);
+        let summary: Vec<(u64, &str, usize)> = groups
+            .iter()
+            .map(|group| (group.number, group.title.as_str(), group.commits.len()))
+            .collect();
+        assert_eq!(summary, vec![(12, "Add CSV export", 2), (7, "Fix typo", 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_resolves_pull_request_titles() {
+        let mut mock_git_repo = MockGitRepository::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_resolver = MockPullRequestResolver::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
 
+        mock_git_repo.expect_get_commits_since().returning(|_| {
+            Ok(vec![
+                create_test_commit("Merge pull request #12 from jane/export", 1704326400),
+                create_test_commit("Fix typo (#7)", 1704240000),
+            ])
+        });
         mock_ai_summarizer
             .expect_summarize_commits()
             .returning(|_| Ok("This is a test summary".to_string()));
-
-        mock_storage
-            .expect_save_diary()
-            .returning(move |_| Ok(expected_file_path.clone()));
+        mock_resolver
+            .expect_pull_request_title()
+            .returning(|number| match number {
+                12 => Ok(Some("Add CSV export".to_string())),
+                _ => Err(anyhow!("rate limited")),
+            });
+        mock_storage.expect_save_diary().returning(|content| {
+            let titles: Vec<&str> = content
+                .pull_requests
+                .iter()
+                .map(|group| group.title.as_str())
+                .collect();
+            // A failed lookup keeps the title from the commit subject
+            assert_eq!(titles, vec!["Fix typo", "Add CSV export"]);
+            Ok("diaries/test-diary.md".to_string())
+        });
 
         let generator = DiaryGenerator::new(
             Arc::new(mock_git_repo),
             Arc::new(mock_ai_summarizer),
             Arc::new(mock_storage),
-            datetime_provider,
+            Arc::new(TestDateTimeProvider::new(now)),
+            7,
+        )
+        .with_pull_request_resolver(Arc::new(mock_resolver));
+
+        assert!(generator.generate_diary().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_preview_calls_neither_ai_nor_storage() -> Result<()> {
+        let mut mock_git_repo = MockGitRepository::new();
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        mock_git_repo
+            .expect_get_commits_since()
+            .returning(|_| Ok(create_test_commits()));
+        let generator = DiaryGenerator::new(
+            Arc::new(mock_git_repo),
+            Arc::new(MockAISummarizer::new()),
+            Arc::new(MockDiaryStorage::new()),
+            Arc::new(TestDateTimeProvider::new(now)),
             7,
         );
 
-        // Execute
-        let result = generator.generate_diary().await;
+        let preview = generator
+            .preview_for_range(&generator.default_range())
+            .await?;
 
-        // Verify
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), expected_file_path_2);
+        assert_eq!(preview.start_date, "2023-12-31");
+        assert_eq!(preview.end_date, "2024-01-07");
+        assert_eq!(preview.commits.len(), create_test_commits().len());
+        assert!(preview.prompt.contains("Second commit"));
+
+        Ok(())
     }
 
     #[tokio::test]
-    async fn test_diary_generator_git_error() {
-        // Setup mocks
+    async fn test_describe_pull_requests() {
+        let mut mock_resolver = MockPullRequestResolver::new();
+        mock_resolver
+            .expect_pull_request_description()
+            .times(2)
+            .returning(|number| match number {
+                12 => Ok(Some(
+                    "Adds `--format csv` for spreadsheet imports.\n".to_string(),
+                )),
+                _ => Ok(None),
+            });
+        let generator = DiaryGenerator::new(
+            Arc::new(MockGitRepository::new()),
+            Arc::new(MockAISummarizer::new()),
+            Arc::new(MockDiaryStorage::new()),
+            Arc::new(MockDateTimeProvider::new()),
+            1,
+        )
+        .with_pull_request_resolver(Arc::new(mock_resolver))
+        .with_message_source(MessageSource::PullRequest);
+
+        let mut expanded = create_test_commit("Retry on timeout", 1704153600);
+        expanded.squashed_from = Some("Harden sync (#9)".to_string());
+        let mut commits = vec![
+            create_test_commit("Add CSV export (#12)", 1704067200),
+            create_test_commit("Fix typo (#7)", 1704153600),
+            expanded,
+            create_test_commit("Tidy README", 1704070800),
+        ];
+        generator.describe_pull_requests(&mut commits).await;
+
+        let messages: Vec<&str> = commits.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Add CSV export (#12)\n\nAdds `--format csv` for spreadsheet imports.",
+                "Fix typo (#7)",
+                "Retry on timeout",
+                "Tidy README",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_includes_reviews() {
         let mut mock_git_repo = MockGitRepository::new();
-        let mock_ai_summarizer = MockAISummarizer::new();
-        let mock_storage = MockDiaryStorage::new();
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_reviews = MockReviewSource::new();
         let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
-        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
 
-        // Set mock expectations - simulate Git error
         mock_git_repo
             .expect_get_commits_since()
-            .returning(|_| Err(anyhow!("Git repository error").into()));
+            .returning(|_| Ok(vec![create_test_commit("Fix cache", 1704326400)]));
+        mock_reviews
+            .expect_reviews_between()
+            .times(1)
+            .returning(|since, until| {
+                assert!(since < until);
+                Ok(vec![Review::new(
+                    14,
+                    "Retry failed payments".to_string(),
+                    "approved".to_string(),
+                )])
+            });
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| {
+                assert_eq!(request.reviews.len(), 1);
+                Ok("This is a test summary".to_string())
+            });
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.reviews[0].title, "Retry failed payments");
+            Ok("diaries/test-diary.md".to_string())
+        });
 
         let generator = DiaryGenerator::new(
             Arc::new(mock_git_repo),
             Arc::new(mock_ai_summarizer),
             Arc::new(mock_storage),
-            datetime_provider,
+            Arc::new(TestDateTimeProvider::new(now)),
             7,
-        );
-
-        // Execute
-        let result = generator.generate_diary().await;
+        )
+        .with_review_source(Arc::new(mock_reviews));
 
-        // Verify
-        assert!(result.is_err());
-        let error = result.unwrap_err().to_string();
-        assert!(error.contains("Git repository error"));
+        assert!(generator.generate_diary().await.is_ok());
     }
 
     #[tokio::test]
-    async fn test_diary_generator_ai_error() {
-        // Setup mocks
+    async fn test_diary_generator_includes_tickets() {
         let mut mock_git_repo = MockGitRepository::new();
         let mut mock_ai_summarizer = MockAISummarizer::new();
-        let mock_storage = MockDiaryStorage::new();
+        let mut mock_storage = MockDiaryStorage::new();
+        let mut mock_tickets = MockTicketSource::new();
         let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
-        let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
 
-        let test_commits = create_test_commits();
-
-        // Set mock expectations - Git success but AI error
         mock_git_repo
             .expect_get_commits_since()
-            .returning(move |_| Ok(test_commits.clone()));
-
+            .returning(|_| Ok(vec![create_test_commit("Fix cache", 1704326400)]));
+        mock_tickets
+            .expect_transitions_between()
+            .times(1)
+            .returning(|_, _| {
+                Ok(vec![TicketTransition::new(
+                    "PAY-142".to_string(),
+                    "Retry failed payments".to_string(),
+                    "Done".to_string(),
+                )])
+            });
         mock_ai_summarizer
             .expect_summarize_commits()
-            .returning(|_| Err(anyhow!("AI service error").into()));
+            .returning(|request| {
+                assert_eq!(request.tickets[0].key, "PAY-142");
+                Ok("This is a test summary".to_string())
+            });
+        mock_storage.expect_save_diary().returning(|content| {
+            assert_eq!(content.tickets[0].status, "Done");
+            Ok("diaries/test-diary.md".to_string())
+        });
 
         let generator = DiaryGenerator::new(
             Arc::new(mock_git_repo),
             Arc::new(mock_ai_summarizer),
             Arc::new(mock_storage),
-            datetime_provider,
+            Arc::new(TestDateTimeProvider::new(now)),
             7,
-        );
+        )
+        .with_ticket_source(Arc::new(mock_tickets));
 
-        // Execute
-        let result = generator.generate_diary().await;
+        assert!(generator.generate_diary().await.is_ok());
+    }
 
-        // Verify
-        assert!(result.is_err());
-        let error = result.unwrap_err().to_string();
-        assert!(error.contains("AI service error"));
+    #[tokio::test]
+    async fn test_diary_generator_deterministic_order() {
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let create_generator = |commits: Vec<Commit>| {
+            let mut mock_git_repo = MockGitRepository::new();
+            let mut mock_ai_summarizer = MockAISummarizer::new();
+            let mut mock_storage = MockDiaryStorage::new();
+
+            mock_git_repo
+                .expect_get_commits_since()
+                .returning(move |_| Ok(commits.clone()));
+            mock_ai_summarizer
+                .expect_summarize_commits()
+                .returning(|_| Ok("This is a test summary".to_string()));
+            mock_storage.expect_save_diary().returning(|content| {
+                let messages: Vec<&str> = content
+                    .commits
+                    .iter()
+                    .map(|commit| commit.message.as_str())
+                    .collect();
+                assert_eq!(messages, vec!["Later", "Add a", "Add b"]);
+                Ok("diaries/test-diary.md".to_string())
+            });
+
+            DiaryGenerator::new(
+                Arc::new(mock_git_repo),
+                Arc::new(mock_ai_summarizer),
+                Arc::new(mock_storage),
+                Arc::new(TestDateTimeProvider::new(now)),
+                7,
+            )
+            .with_deterministic(true)
+        };
+
+        for commits in [
+            vec![
+                create_test_commit("Add b", 1704240000),
+                create_test_commit("Later", 1704326400),
+                create_test_commit("Add a", 1704240000),
+            ],
+            vec![
+                create_test_commit("Add a", 1704240000),
+                create_test_commit("Add b", 1704240000),
+                create_test_commit("Later", 1704326400),
+            ],
+        ] {
+            assert!(create_generator(commits).generate_diary().await.is_ok());
+        }
     }
 
     #[tokio::test]
-    async fn test_diary_generator_storage_error() {
-        // Setup mocks
+    async fn test_diary_generator_sensitive_scan() {
+        let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
+        let create_generator = |sensitive_scan: SensitiveScan, summarize_calls: usize| {
+            let mut mock_git_repo = MockGitRepository::new();
+            let mut mock_ai_summarizer = MockAISummarizer::new();
+            let mut mock_storage = MockDiaryStorage::new();
+
+            mock_git_repo.expect_get_commits_since().returning(|_| {
+                Ok(vec![create_test_commit(
+                    "Email report to ops@example.com",
+                    1704326400,
+                )])
+            });
+            mock_ai_summarizer
+                .expect_summarize_commits()
+                .times(summarize_calls)
+                .returning(|_| Ok("This is a test summary".to_string()));
+            mock_storage
+                .expect_save_diary()
+                .returning(|_| Ok("diaries/test-diary.md".to_string()));
+
+            DiaryGenerator::new(
+                Arc::new(mock_git_repo),
+                Arc::new(mock_ai_summarizer),
+                Arc::new(mock_storage),
+                Arc::new(TestDateTimeProvider::new(now)),
+                7,
+            )
+            .with_sensitive_scan(sensitive_scan)
+        };
+
+        let result = create_generator(SensitiveScan::Block, 0)
+            .generate_diary()
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("--allow-sensitive"));
+
+        let result = create_generator(SensitiveScan::Allow, 1)
+            .generate_diary()
+            .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_diary_generator_per_branch() -> Result<()> {
         let mut mock_git_repo = MockGitRepository::new();
         let mut mock_ai_summarizer = MockAISummarizer::new();
         let mut mock_storage = MockDiaryStorage::new();
         let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
         let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
 
-        let test_commits = create_test_commits();
-
-        // Set mock expectations - Git and AI success, storage error
+        let commits = vec![
+            Commit::new("Fix typo".to_string(), now.timestamp() - 60)
+                .with_branch("main".to_string()),
+            Commit::new("Add sync".to_string(), now.timestamp() - 120)
+                .with_branch("feature/sync".to_string()),
+            Commit::new("Try idea".to_string(), now.timestamp() - 180),
+            Commit::new("Add tests".to_string(), now.timestamp() - 240)
+                .with_branch("feature/sync".to_string()),
+        ];
         mock_git_repo
             .expect_get_commits_since()
-            .returning(move |_| Ok(test_commits.clone()));
-
+            .times(1)
+            .returning(move |_| Ok(commits.clone()));
         mock_ai_summarizer
             .expect_summarize_commits()
-            .returning(|_| Ok("This is a test summary".to_string()));
-
+            .times(3)
+            .returning(|request| Ok(format!("{} commit(s)", request.commits.len())));
         mock_storage
             .expect_save_diary()
-            .returning(|_| Err(anyhow!("Storage error").into()));
+            .times(3)
+            .returning(|content| {
+                Ok(format!(
+                    "diaries/{}.md",
+                    content.branch.as_deref().unwrap().replace('/', "-")
+                ))
+            });
+        mock_storage
+            .expect_save_branch_index()
+            .withf(|index| index.diaries.len() == 3)
+            .returning(|_| Ok("diaries/index.md".to_string()));
 
         let generator = DiaryGenerator::new(
             Arc::new(mock_git_repo),
             Arc::new(mock_ai_summarizer),
             Arc::new(mock_storage),
             datetime_provider,
-            7,
+            1,
         );
 
-        // Execute
-        let result = generator.generate_diary().await;
+        let (index, diaries) = generator
+            .generate_branch_diaries_for_range(generator.default_range())
+            .await?;
 
-        // Verify
-        assert!(result.is_err());
-        let error = result.unwrap_err().to_string();
-        assert!(error.contains("Storage error"));
+        assert_eq!(index, "diaries/index.md");
+        assert_eq!(
+            diaries,
+            vec![
+                BranchDiary {
+                    branch: "main".to_string(),
+                    path: "diaries/main.md".to_string(),
+                    commits: 1,
+                },
+                BranchDiary {
+                    branch: "feature/sync".to_string(),
+                    path: "diaries/feature-sync.md".to_string(),
+                    commits: 2,
+                },
+                BranchDiary {
+                    branch: DETACHED_BRANCH.to_string(),
+                    path: "diaries/HEAD.md".to_string(),
+                    commits: 1,
+                },
+            ]
+        );
+
+        Ok(())
     }
 
     #[tokio::test]
-    async fn test_diary_generator_empty_commits() {
-        // Setup mocks
+    async fn test_diary_generator_split_days() -> Result<()> {
         let mut mock_git_repo = MockGitRepository::new();
         let mut mock_ai_summarizer = MockAISummarizer::new();
         let mut mock_storage = MockDiaryStorage::new();
         let now = Local.with_ymd_and_hms(2024, 1, 7, 12, 0, 0).unwrap();
         let datetime_provider = Arc::new(TestDateTimeProvider::new(now));
+        let at = |day: u32, hour: u32| {
+            Local
+                .with_ymd_and_hms(2024, 1, day, hour, 0, 0)
+                .unwrap()
+                .timestamp()
+        };
 
-        // Set mock expectations - return empty commit list
+        let commits = vec![
+            Commit::new("Release".to_string(), at(7, 9)),
+            Commit::new("Fix typo".to_string(), at(5, 16)),
+            Commit::new("Add sync".to_string(), at(5, 10)),
+            Commit::new("Start sync".to_string(), at(2, 11)),
+        ];
         mock_git_repo
             .expect_get_commits_since()
-            .returning(|_| Ok(Vec::new()));
-
-        // AI should still be called even with empty commits
+            .times(1)
+            .returning(move |_| Ok(commits.clone()));
         mock_ai_summarizer
             .expect_summarize_commits()
-            .returning(|commits| {
-                assert!(commits.is_empty());
-                Ok("No activity in the last 7 days".to_string())
+            .times(3)
+            .returning(|request| Ok(format!("{} commit(s)", request.commits.len())));
+        mock_storage
+            .expect_save_diary()
+            .times(3)
+            .returning(|content| {
+                assert_eq!(content.start_date, content.end_date);
+                Ok(format!(
+                    "diaries/{}-{}.md",
+                    content.start_date, content.summary
+                ))
             });
 
-        mock_storage.expect_save_diary().returning(|content| {
-            assert!(content.commits.is_empty());
-            assert_eq!(content.summary, "No activity in the last 7 days");
-            Ok("diaries/empty-diary.md".to_string())
-        });
-
         let generator = DiaryGenerator::new(
             Arc::new(mock_git_repo),
             Arc::new(mock_ai_summarizer),
@@ -396,10 +4260,19 @@ mod tests {
             7,
         );
 
-        // Execute
-        let result = generator.generate_diary().await;
+        let paths = generator
+            .generate_daily_diaries_for_range(generator.default_range())
+            .await?;
 
-        // Verify
-        assert!(result.is_ok());
+        assert_eq!(
+            paths,
+            vec![
+                "diaries/2024-01-02-1 commit(s).md",
+                "diaries/2024-01-05-2 commit(s).md",
+                "diaries/2024-01-07-1 commit(s).md",
+            ]
+        );
+
+        Ok(())
     }
 }
@@ -0,0 +1,169 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::domain::Commit;
+
+/// Placeholder commit sources use when a commit has no message
+pub const NO_MESSAGE: &str = "No message";
+
+/// What happens to commits whose message is empty or whitespace only
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum EmptyMessagePolicy {
+    /// List them as "No message"
+    #[default]
+    Keep,
+    /// Leave them out of the diary and the prompt
+    Drop,
+    /// Describe them by the files they changed, such as "Touched 3 files in src/auth"
+    Describe,
+}
+
+/// Whether the commit's subject is blank or the `No message` placeholder
+pub fn has_empty_message(commit: &Commit) -> bool {
+    let subject = commit.subject().trim();
+    subject.is_empty() || subject == NO_MESSAGE
+}
+
+/// A description of the commit's changes, for when its author wrote none
+///
+/// `None` when the source didn't record which files changed.
+pub fn describe_changes(commit: &Commit) -> Option<String> {
+    let (first, rest) = commit.files.split_first()?;
+    let mut description = if rest.is_empty() {
+        format!("Touched {}", first)
+    } else {
+        match common_dir(&commit.files) {
+            Some(dir) => format!("Touched {} files in {}", commit.files.len(), dir),
+            None => format!("Touched {} files", commit.files.len()),
+        }
+    };
+    if let Some(stats) = &commit.line_stats {
+        description.push_str(&format!(" (+{} -{})", stats.additions, stats.deletions));
+    }
+
+    Some(description)
+}
+
+/// Drops or describes commits without a message, as `policy` says
+pub fn apply_empty_message_policy(commits: &[Commit], policy: EmptyMessagePolicy) -> Vec<Commit> {
+    match policy {
+        EmptyMessagePolicy::Keep => commits.to_vec(),
+        EmptyMessagePolicy::Drop => commits
+            .iter()
+            .filter(|commit| !has_empty_message(commit))
+            .cloned()
+            .collect(),
+        EmptyMessagePolicy::Describe => commits
+            .iter()
+            .map(|commit| {
+                let mut commit = commit.clone();
+                if has_empty_message(&commit) {
+                    if let Some(description) = describe_changes(&commit) {
+                        commit.message = match commit.reflog_action() {
+                            Some(action) => format!("{}: {}", action, description),
+                            None => description,
+                        };
+                    }
+                }
+                commit
+            })
+            .collect(),
+    }
+}
+
+/// Deepest directory holding all of `files`, if they share one
+fn common_dir(files: &[String]) -> Option<String> {
+    let mut common: Vec<&str> = Path::new(files.first()?)
+        .parent()?
+        .iter()
+        .filter_map(|part| part.to_str())
+        .collect();
+    for file in &files[1..] {
+        let parts: Vec<&str> = Path::new(file)
+            .parent()
+            .map(|dir| dir.iter().filter_map(|part| part.to_str()).collect())
+            .unwrap_or_default();
+        let shared = common
+            .iter()
+            .zip(&parts)
+            .take_while(|(a, b)| a == b)
+            .count();
+        common.truncate(shared);
+    }
+
+    (!common.is_empty()).then(|| common.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::LineStats;
+
+    fn commit(message: &str, files: &[&str]) -> Commit {
+        Commit::new(message.to_string(), 1704067200)
+            .with_files(files.iter().map(|file| file.to_string()).collect())
+    }
+
+    #[test]
+    fn test_describe_changes() {
+        let describe = |files: &[&str]| describe_changes(&commit("", files));
+
+        assert_eq!(
+            describe(&[
+                "src/auth/login.rs",
+                "src/auth/session.rs",
+                "src/auth/mod.rs"
+            ]),
+            Some("Touched 3 files in src/auth".to_string())
+        );
+        assert_eq!(
+            describe(&["src/auth/login.rs", "src/billing/invoice.rs"]),
+            Some("Touched 2 files in src".to_string())
+        );
+        assert_eq!(
+            describe(&["README.md", "src/main.rs"]),
+            Some("Touched 2 files".to_string())
+        );
+        assert_eq!(describe(&[]), None);
+
+        let mut single = commit("", &["docs/setup.md"]);
+        single.line_stats = Some(LineStats::new(12, 3));
+        assert_eq!(
+            describe_changes(&single),
+            Some("Touched docs/setup.md (+12 -3)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_empty_message_policy() {
+        let commits = vec![
+            commit("commit: Fix cache", &["src/cache.rs"]),
+            commit("commit: ", &["src/auth/login.rs", "src/auth/mod.rs"]),
+            commit("No message", &[]),
+            commit("   ", &["notes.txt"]),
+        ];
+        let messages = |policy| {
+            apply_empty_message_policy(&commits, policy)
+                .into_iter()
+                .map(|commit| commit.message)
+                .collect::<Vec<String>>()
+        };
+
+        assert_eq!(messages(EmptyMessagePolicy::Keep).len(), 4);
+        assert_eq!(
+            messages(EmptyMessagePolicy::Drop),
+            vec!["commit: Fix cache"]
+        );
+        assert_eq!(
+            messages(EmptyMessagePolicy::Describe),
+            vec![
+                "commit: Fix cache",
+                "commit: Touched 2 files in src/auth",
+                "No message",
+                "Touched notes.txt",
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use encoding_rs::Encoding;
+
+/// A commit message converted to UTF-8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedMessage {
+    pub text: String,
+    /// Why the conversion was lossy, if it was
+    pub warning: Option<String>,
+}
+
+/// Converts raw commit message bytes to UTF-8
+///
+/// `encoding` is the value of the commit's `encoding` header. Without one the
+/// message should already be UTF-8, as git assumes. Anything that can't be
+/// decoded is replaced with U+FFFD and reported in `warning`.
+pub fn decode_message(bytes: &[u8], encoding: Option<&str>) -> DecodedMessage {
+    if let Some(label) = encoding {
+        match Encoding::for_label(label.trim().as_bytes()) {
+            Some(encoding) => {
+                let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+                return DecodedMessage {
+                    text: text.into_owned(),
+                    warning: had_errors.then(|| {
+                        format!(
+                            "message is not valid {}; some characters were replaced",
+                            label
+                        )
+                    }),
+                };
+            }
+            None => {
+                return lossy(bytes, format!("unknown encoding {:?}", label));
+            }
+        }
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => DecodedMessage {
+            text: text.to_string(),
+            warning: None,
+        },
+        Err(_) => lossy(
+            bytes,
+            "message is not UTF-8 and has no encoding header".to_string(),
+        ),
+    }
+}
+
+fn lossy(bytes: &[u8], reason: String) -> DecodedMessage {
+    DecodedMessage {
+        text: String::from_utf8_lossy(bytes).into_owned(),
+        warning: Some(format!("{}; some characters were replaced", reason)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_message_with_encoding_header() {
+        // "修正" in Shift-JIS
+        let decoded = decode_message(b"\x8f\x43\x90\xb3", Some("Shift_JIS"));
+        assert_eq!(decoded.text, "修正");
+        assert_eq!(decoded.warning, None);
+
+        let decoded = decode_message(b"Caf\xe9", Some("ISO-8859-1"));
+        assert_eq!(decoded.text, "Café");
+        assert_eq!(decoded.warning, None);
+    }
+
+    #[test]
+    fn test_decode_message_falls_back_to_lossy() {
+        let decoded = decode_message("Café".as_bytes(), None);
+        assert_eq!(decoded.text, "Café");
+        assert_eq!(decoded.warning, None);
+
+        let decoded = decode_message(b"Caf\xe9", None);
+        assert_eq!(decoded.text, "Caf\u{fffd}");
+        assert!(decoded.warning.unwrap().contains("not UTF-8"));
+
+        let decoded = decode_message(b"Caf\xe9", Some("klingon"));
+        assert_eq!(decoded.text, "Caf\u{fffd}");
+        assert!(decoded.warning.unwrap().contains("unknown encoding"));
+    }
+}
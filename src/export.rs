@@ -0,0 +1,507 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use tempfile::TempDir;
+
+use crate::domain::{Author, Commit, GitRepository};
+use crate::encoding::decode_message;
+use crate::git::body_of;
+
+const BUNDLE_V2_SIGNATURE: &str = "# v2 git bundle";
+const BUNDLE_V3_SIGNATURE: &str = "# v3 git bundle";
+
+/// Reads commits from exported history instead of a live repository
+///
+/// Accepts either the output of `git fast-export` or a `.bundle` file
+/// created with `git bundle create`. The format is detected from the file
+/// header.
+pub struct ExportedRepositoryImpl {
+    path: String,
+}
+
+impl ExportedRepositoryImpl {
+    pub fn new(path: String) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait::async_trait]
+impl GitRepository for ExportedRepositoryImpl {
+    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
+        let data = fs::read(&self.path)
+            .with_context(|| format!("Failed to read exported history from {}", self.path))?;
+
+        let mut commits = if is_bundle(&data) {
+            read_bundle(&data)?
+        } else {
+            parse_fast_export(&data)?
+        };
+
+        // Match the reflog ordering: newest first
+        commits.retain(|commit| commit.timestamp() >= timestamp);
+        commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp()));
+
+        Ok(commits)
+    }
+}
+
+fn is_bundle(data: &[u8]) -> bool {
+    data.starts_with(BUNDLE_V2_SIGNATURE.as_bytes())
+        || data.starts_with(BUNDLE_V3_SIGNATURE.as_bytes())
+}
+
+/// Parses a `git fast-export` stream into commits
+///
+/// Only the committer time, author, branch and the first line of each
+/// commit message are kept. Blobs, tags and file modifications are skipped.
+pub fn parse_fast_export(input: &[u8]) -> Result<Vec<Commit>> {
+    let mut reader = StreamReader::new(input);
+    let mut commits = Vec::new();
+    let mut in_commit = false;
+    let mut committer_time = None;
+    let mut author = None;
+    let mut encoding = None;
+    let mut original_id = None;
+    let mut author_time = None;
+    let mut author_offset = None;
+    let mut branch = None;
+
+    while let Some(line) = reader.next_line() {
+        if let Some(reference) = line.strip_prefix(b"commit ") {
+            in_commit = true;
+            branch = String::from_utf8_lossy(reference)
+                .trim()
+                .strip_prefix("refs/heads/")
+                .map(str::to_string);
+            committer_time = None;
+            author = None;
+            encoding = None;
+            original_id = None;
+            author_time = None;
+            author_offset = None;
+        } else if let Some(rest) = line.strip_prefix(b"original-oid ") {
+            // Written by `git fast-export --show-original-ids`
+            original_id = Some(String::from_utf8_lossy(rest).trim().to_string());
+        } else if let Some(rest) = line.strip_prefix(b"author ") {
+            author = Author::parse_ident(&String::from_utf8_lossy(rest));
+            author_time = parse_ident_time(rest).ok();
+            author_offset = parse_ident_offset(rest);
+        } else if let Some(rest) = line.strip_prefix(b"committer ") {
+            committer_time = Some(parse_ident_time(rest)?);
+        } else if let Some(rest) = line.strip_prefix(b"encoding ") {
+            encoding = Some(String::from_utf8_lossy(rest).to_string());
+        } else if let Some(rest) = line.strip_prefix(b"data ") {
+            let data = reader.read_data(rest)?;
+
+            // The first data block after a commit header is its message
+            if in_commit {
+                let time =
+                    committer_time.ok_or_else(|| anyhow!("Commit without committer line"))?;
+                let decoded = decode_message(data, encoding.as_deref());
+                if let Some(warning) = decoded.warning {
+                    eprintln!("⚠️  Commit at {}: {}", time, warning);
+                }
+                let summary = decoded.text.lines().next().unwrap_or("").trim();
+                let summary = if summary.is_empty() {
+                    "No message"
+                } else {
+                    summary
+                };
+                let mut commit = Commit::new(summary.to_string(), time);
+                commit.author = author.take();
+                commit.id = original_id.take();
+                commit.author_time = author_time.take();
+                commit.author_offset = author_offset.take();
+                commit.branch = branch.take();
+                commits.push(commit);
+                in_commit = false;
+            }
+        }
+    }
+
+    Ok(commits)
+}
+
+/// Extracts the Unix timestamp from `Name <email> 1704067200 +0000`
+fn parse_ident_time(ident: &[u8]) -> Result<i64> {
+    let ident = String::from_utf8_lossy(ident);
+    let after_email = ident
+        .rsplit_once('>')
+        .map(|(_, rest)| rest)
+        .ok_or_else(|| anyhow!("Malformed identity line: {}", ident))?;
+
+    after_email
+        .split_whitespace()
+        .next()
+        .and_then(|seconds| seconds.parse().ok())
+        .ok_or_else(|| anyhow!("Malformed identity time: {}", ident))
+}
+
+/// The `+0900`-style offset after the time of an identity line, in minutes
+fn parse_ident_offset(ident: &[u8]) -> Option<i32> {
+    let ident = String::from_utf8_lossy(ident);
+    let offset = ident.rsplit_once('>')?.1.split_whitespace().nth(1)?;
+    let (sign, digits) = match offset.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+struct StreamReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StreamReader<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn next_line(&mut self) -> Option<&'a [u8]> {
+        if self.pos >= self.input.len() {
+            return None;
+        }
+
+        let rest = &self.input[self.pos..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.pos += (end + 1).min(rest.len());
+
+        Some(&rest[..end])
+    }
+
+    /// Reads the payload of a `data` command in either counted or delimited form
+    fn read_data(&mut self, spec: &[u8]) -> Result<&'a [u8]> {
+        if let Some(delimiter) = spec.strip_prefix(b"<<") {
+            let start = self.pos;
+            loop {
+                let line_start = self.pos;
+                let line = self
+                    .next_line()
+                    .ok_or_else(|| anyhow!("Unterminated delimited data in fast-export stream"))?;
+                if line == delimiter {
+                    return Ok(&self.input[start..line_start]);
+                }
+            }
+        }
+
+        let len: usize = std::str::from_utf8(spec)
+            .ok()
+            .and_then(|len| len.trim().parse().ok())
+            .ok_or_else(|| anyhow!("Malformed data length in fast-export stream"))?;
+
+        let end = self.pos + len;
+        if end > self.input.len() {
+            bail!("Truncated fast-export stream");
+        }
+
+        let data = &self.input[self.pos..end];
+        self.pos = end;
+
+        // An optional LF may follow the payload
+        if self.input.get(self.pos) == Some(&b'\n') {
+            self.pos += 1;
+        }
+
+        Ok(data)
+    }
+}
+
+struct BundleHeader {
+    prerequisites: Vec<git2::Oid>,
+    references: Vec<git2::Oid>,
+}
+
+/// Splits a bundle into its parsed header and the raw packfile
+fn parse_bundle(data: &[u8]) -> Result<(BundleHeader, &[u8])> {
+    let mut reader = StreamReader::new(data);
+    reader.next_line(); // signature
+
+    let mut header = BundleHeader {
+        prerequisites: Vec::new(),
+        references: Vec::new(),
+    };
+
+    loop {
+        let line = reader
+            .next_line()
+            .ok_or_else(|| anyhow!("Bundle header is not terminated"))?;
+        if line.is_empty() {
+            break;
+        }
+
+        let line = String::from_utf8_lossy(line);
+        if line.starts_with('@') {
+            // v3 capabilities
+            continue;
+        }
+
+        let (target, line) = match line.strip_prefix('-') {
+            Some(rest) => (&mut header.prerequisites, rest),
+            None => (&mut header.references, line.as_ref()),
+        };
+        let oid = line.split_whitespace().next().unwrap_or("");
+        target.push(git2::Oid::from_str(oid).context("Malformed object id in bundle header")?);
+    }
+
+    Ok((header, &data[reader.pos..]))
+}
+
+/// Unpacks a bundle into a scratch repository and walks its history
+fn read_bundle(data: &[u8]) -> Result<Vec<Commit>> {
+    let (header, pack) = parse_bundle(data)?;
+
+    let temp_dir = TempDir::new()?;
+    let repo = git2::Repository::init_bare(temp_dir.path())?;
+
+    {
+        let odb = repo.odb()?;
+        let mut writer = odb.packwriter()?;
+        writer.write_all(pack)?;
+        writer.commit()?;
+    }
+
+    // Walked by hand rather than with a revwalk, which fails on reaching the
+    // prerequisites an incremental bundle leaves out; history that isn't one
+    // of them must be there, so anything else missing is an error
+    let prerequisites: HashSet<git2::Oid> = header.prerequisites.iter().copied().collect();
+    let mut pending = header.references.clone();
+    let mut seen = HashSet::new();
+    let mut commits = Vec::new();
+    while let Some(oid) = pending.pop() {
+        if prerequisites.contains(&oid) || !seen.insert(oid) {
+            continue;
+        }
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("Bundle is missing commit {}", oid))?;
+        pending.extend(commit.parent_ids());
+        let decoded = decode_message(
+            commit.summary_bytes().unwrap_or(b"No message"),
+            commit.message_encoding(),
+        );
+        if let Some(warning) = decoded.warning {
+            eprintln!("⚠️  Commit {}: {}", oid, warning);
+        }
+        let author = commit.author();
+        let mut entry = Commit::new(decoded.text, commit.committer().when().seconds()).with_author(
+            Author::new(
+                String::from_utf8_lossy(author.name_bytes()).to_string(),
+                String::from_utf8_lossy(author.email_bytes()).to_string(),
+            ),
+        );
+        entry.body = body_of(&commit);
+        entry.id = Some(oid.to_string());
+        entry.author_time = Some(author.when().seconds());
+        entry.author_offset = Some(author.when().offset_minutes());
+        commits.push(entry);
+    }
+    // Newest first, as a revwalk by time would give them
+    commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp()));
+
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    const FAST_EXPORT: &str = "blob
+mark :1
+data 13
+Hello, world
+
+reset refs/heads/main
+commit refs/heads/main
+mark :2
+author Test User <test@example.com> 1704067200 +0000
+committer Test User <test@example.com> 1704067200 +0000
+data 15
+Initial commit
+M 100644 :1 README.md
+
+commit refs/heads/main
+mark :3
+author Test User <test@example.com> 1704153600 +0900
+committer Test User <test@example.com> 1704153600 +0900
+data <<EOF
+Add parser
+
+With a longer body.
+EOF
+from :2
+
+";
+
+    #[test]
+    fn test_parse_fast_export() -> Result<()> {
+        let commits = parse_fast_export(FAST_EXPORT.as_bytes())?;
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "Initial commit");
+        assert_eq!(commits[0].timestamp(), 1704067200);
+        assert_eq!(
+            commits[0]
+                .author
+                .as_ref()
+                .map(|author| author.email.as_str()),
+            Some("test@example.com")
+        );
+        assert_eq!(commits[1].message, "Add parser");
+        assert_eq!(commits[1].timestamp(), 1704153600);
+        assert_eq!(commits[1].author_time, Some(1704153600));
+        assert_eq!(commits[0].author_offset, Some(0));
+        assert_eq!(commits[1].author_offset, Some(540));
+        assert_eq!(commits[1].branch.as_deref(), Some("main"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fast_export_with_encoding() -> Result<()> {
+        let mut stream = b"commit refs/heads/main
+committer Test User <test@example.com> 1704067200 +0000
+encoding ISO-8859-1
+data 12
+Fix caf\xe9 bug
+"
+        .to_vec();
+        stream.extend_from_slice(b"\n");
+
+        let commits = parse_fast_export(&stream)?;
+
+        assert_eq!(commits[0].message, "Fix café bug");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_fast_export_truncated() {
+        let input = "commit refs/heads/main\ncommitter A <a@b> 1 +0000\ndata 100\nshort";
+
+        assert!(parse_fast_export(input.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_exported_repository_filters_and_orders() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("history.fi");
+        fs::write(&path, FAST_EXPORT)?;
+
+        let repo = ExportedRepositoryImpl::new(path.to_string_lossy().to_string());
+
+        let commits = repo.get_commits_since(0)?;
+        assert_eq!(commits[0].message, "Add parser");
+        assert_eq!(commits[1].message, "Initial commit");
+
+        let commits = repo.get_commits_since(1704100000)?;
+        assert_eq!(commits.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exported_repository_reads_bundle() -> Result<()> {
+        // Build a repository with two commits
+        let temp_dir = TempDir::new()?;
+        let repo = git2::Repository::init(temp_dir.path())?;
+        let mut index = repo.index()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+
+        let first_sig = git2::Signature::new(
+            "Test User",
+            "test@example.com",
+            &git2::Time::new(1704067200, 0),
+        )?;
+        let first = repo.commit(
+            Some("HEAD"),
+            &first_sig,
+            &first_sig,
+            "First commit",
+            &tree,
+            &[],
+        )?;
+        let first = repo.find_commit(first)?;
+
+        let second_sig = git2::Signature::new(
+            "Test User",
+            "test@example.com",
+            &git2::Time::new(1704153600, 0),
+        )?;
+        let head = repo.commit(
+            Some("HEAD"),
+            &second_sig,
+            &second_sig,
+            "Second commit\n\nDetails",
+            &tree,
+            &[&first],
+        )?;
+
+        // Pack it into a bundle
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(head)?;
+        let mut builder = repo.packbuilder()?;
+        builder.insert_walk(&mut revwalk)?;
+        let mut pack = git2::Buf::new();
+        builder.write_buf(&mut pack)?;
+
+        let mut bundle =
+            format!("{}\n{} refs/heads/main\n\n", BUNDLE_V2_SIGNATURE, head).into_bytes();
+        bundle.extend_from_slice(&pack);
+        let bundle_path = Path::new(temp_dir.path()).join("history.bundle");
+        fs::write(&bundle_path, bundle)?;
+
+        let exported = ExportedRepositoryImpl::new(bundle_path.to_string_lossy().to_string());
+        let commits = exported.get_commits_since(0)?;
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "Second commit");
+        assert_eq!(commits[1].message, "First commit");
+
+        // An incremental bundle stops at its prerequisite, but history that
+        // isn't one must be there
+        let mut revwalk = repo.revwalk()?;
+        revwalk.push(head)?;
+        revwalk.hide(first.id())?;
+        let mut builder = repo.packbuilder()?;
+        builder.insert_walk(&mut revwalk)?;
+        let mut pack = git2::Buf::new();
+        builder.write_buf(&mut pack)?;
+        let incremental = |header: String| -> Result<Vec<Commit>> {
+            let mut bundle = header.into_bytes();
+            bundle.extend_from_slice(&pack);
+            read_bundle(&bundle)
+        };
+
+        let commits = incremental(format!(
+            "{}\n-{} First commit\n{} refs/heads/main\n\n",
+            BUNDLE_V2_SIGNATURE,
+            first.id(),
+            head
+        ))?;
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "Second commit");
+        assert!(incremental(format!(
+            "{}\n{} refs/heads/main\n\n",
+            BUNDLE_V2_SIGNATURE, head
+        ))
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exported_repository_missing_file() {
+        let exported = ExportedRepositoryImpl::new("/path/that/does/not/exist".to_string());
+
+        assert!(exported.get_commits_since(0).is_err());
+    }
+}
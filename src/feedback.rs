@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::fs;
+
+use crate::domain::DiarySummary;
+use crate::formatter::SUMMARY_HEADING;
+use crate::ledger::{LedgerEntry, Rating};
+use crate::parser::parse_diary;
+
+/// Diaries that must be rated before good ones are shown to the summarizer,
+/// so a single rating doesn't set the style of every diary after it
+pub const MIN_RATINGS: usize = 3;
+
+/// Most past summaries shown to the summarizer as examples
+pub const MAX_EXAMPLES: usize = 2;
+
+/// Summaries of the diaries most recently rated good, newest rating first
+///
+/// Empty until [`MIN_RATINGS`] diaries were rated. Diaries that were since
+/// pruned, aren't Markdown or have no summary section are skipped.
+pub fn good_examples(entries: &[LedgerEntry]) -> Vec<DiarySummary> {
+    let mut rated: Vec<&LedgerEntry> = entries
+        .iter()
+        .filter(|entry| entry.feedback.is_some())
+        .collect();
+    if rated.len() < MIN_RATINGS {
+        return Vec::new();
+    }
+    rated.sort_by_key(|entry| {
+        std::cmp::Reverse(entry.feedback.as_ref().map(|feedback| feedback.rated_at))
+    });
+
+    let mut seen = HashSet::new();
+    rated
+        .into_iter()
+        .filter(|entry| {
+            entry
+                .feedback
+                .as_ref()
+                .is_some_and(|feedback| feedback.rating == Rating::Good)
+        })
+        .filter(|entry| seen.insert(entry.path.as_str()))
+        .filter_map(summary_of)
+        .take(MAX_EXAMPLES)
+        .collect()
+}
+
+/// The summary section of the Markdown diary `entry` was saved to
+fn summary_of(entry: &LedgerEntry) -> Option<DiarySummary> {
+    let markdown = fs::read_to_string(&entry.path).ok()?;
+    let parsed = parse_diary(&markdown).ok()?;
+    let summary = parsed
+        .section(SUMMARY_HEADING)
+        .map(|section| section.body.trim().to_string())
+        .filter(|body| !body.is_empty())?;
+
+    Some(DiarySummary {
+        period: parsed
+            .title
+            .unwrap_or_else(|| format!("{} – {}", entry.start_date, entry.end_date)),
+        summary,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::{EntryKind, Feedback};
+    use tempfile::TempDir;
+
+    fn rated_entry(path: &str, rating: Rating, rated_at: i64) -> LedgerEntry {
+        LedgerEntry {
+            generated_at: 1704067200,
+            repo: "/src/app".to_string(),
+            start_date: "2024-01-01".to_string(),
+            end_date: "2024-01-07".to_string(),
+            end_time: None,
+            path: path.to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            prompt_tokens: 1000,
+            completion_tokens: 200,
+            cost: None,
+            feedback: Some(Feedback {
+                rating,
+                note: None,
+                rated_at,
+            }),
+            aborted: None,
+            kind: EntryKind::Diary,
+        }
+    }
+
+    #[test]
+    fn test_good_examples() -> anyhow::Result<()> {
+        let temp_dir = TempDir::new()?;
+        let diary = |name: &str, title: &str, summary: &str| -> anyhow::Result<String> {
+            let path = temp_dir.path().join(name);
+            fs::write(
+                &path,
+                format!(
+                    "# {}\n\n## Commit Logs\n\n- 2024-01-01 00:00:00: Work\n\n## {}\n\n{}\n",
+                    title, SUMMARY_HEADING, summary
+                ),
+            )?;
+            Ok(path.to_string_lossy().into_owned())
+        };
+        let first = diary("first.md", "First week", "Shipped the exporter.")?;
+        let second = diary("second.md", "Second week", "Fixed the parser.")?;
+        let bad = diary("bad.md", "Bad week", "Did things.")?;
+
+        let mut entries = vec![
+            rated_entry(&first, Rating::Good, 100),
+            rated_entry(&bad, Rating::Bad, 200),
+        ];
+        assert!(good_examples(&entries).is_empty());
+
+        entries.push(rated_entry(&second, Rating::Good, 300));
+        entries.push(rated_entry("pruned.md", Rating::Good, 400));
+        let examples = good_examples(&entries);
+        assert_eq!(
+            examples,
+            vec![
+                DiarySummary {
+                    period: "Second week".to_string(),
+                    summary: "Fixed the parser.".to_string(),
+                },
+                DiarySummary {
+                    period: "First week".to_string(),
+                    summary: "Shipped the exporter.".to_string(),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1286 @@
+use std::path::Path;
+
+use crate::codingtime::CodingTime;
+use crate::domain::{BranchIndex, Commit, CommitOrder, DiaryContent};
+
+/// Heading of the section listing commits
+pub const COMMIT_LOGS_HEADING: &str = "Commit Logs";
+
+/// Heading of the section holding the AI summary
+pub const SUMMARY_HEADING: &str = "AI-generated Summary";
+
+/// Heading of the section holding notes added with `git-diary note`
+pub const MANUAL_NOTES_HEADING: &str = "Manual notes";
+
+/// Heading of the section listing work passed with `--context`
+pub const OTHER_ACTIVITIES_HEADING: &str = "Other activities";
+
+/// Heading of the section listing pull requests the developer reviewed
+pub const REVIEWS_HEADING: &str = "Reviews";
+/// Heading of the section listing issues the developer moved on the tracker
+pub const TICKETS_HEADING: &str = "Tickets";
+pub const SESSIONS_HEADING: &str = "Sessions";
+
+/// Heading of the paragraph on TODO, test and build changes, with `--health`
+pub const REPO_HEALTH_HEADING: &str = "Repo health";
+/// Heading of the note on late-night and weekend commits, with `--work-hours`
+pub const WORK_HOURS_HEADING: &str = "Working hours";
+/// Heading of the editor time from a time tracker, with `[time_tracking]` configured
+pub const CODING_TIME_HEADING: &str = "Time by project and language";
+/// Heading of the images commits mention, with `--attach-images`
+pub const IMAGES_HEADING: &str = "Images";
+/// Heading of the list of performance work and the improvements it claims
+pub const PERFORMANCE_HEADING: &str = "Performance";
+
+/// Heading of the group of commits outside any pull request
+pub const OTHER_COMMITS_HEADING: &str = "Other commits";
+
+/// Renders diary content into a specific output format
+pub trait DiaryFormatter: Send + Sync {
+    fn format(&self, content: &DiaryContent) -> String;
+
+    /// File extension used for diaries in this format, without the dot
+    fn extension(&self) -> &'static str;
+}
+
+/// The default Markdown output
+pub struct MarkdownFormatter;
+
+impl DiaryFormatter for MarkdownFormatter {
+    fn format(&self, content: &DiaryContent) -> String {
+        // Format commit logs, grouped by day for weekly rollups
+        let commit_logs = match grouped_commits(content) {
+            Some(groups) => groups
+                .iter()
+                .map(|(heading, commits)| {
+                    let mut day = format!("### {}\n\n", heading);
+                    for commit in commits {
+                        day.push_str(&format!("- {}\n", commit));
+                    }
+                    day
+                })
+                .collect::<Vec<String>>()
+                .join("\n"),
+            None => {
+                let mut commit_logs = String::new();
+                for commit in content.commit_log() {
+                    commit_logs.push_str(&format!("- {}\n", commit));
+                }
+                commit_logs
+            }
+        };
+
+        let mut notes = String::new();
+        if !content.notes.is_empty() {
+            notes.push_str(&format!("## {}\n\n", MANUAL_NOTES_HEADING));
+            for note in &content.notes {
+                notes.push_str(&format!("- {}\n", note));
+            }
+            notes.push_str("\n\n");
+        }
+        if !content.context.is_empty() {
+            notes.push_str(&format!("## {}\n\n", OTHER_ACTIVITIES_HEADING));
+            for activity in &content.context {
+                notes.push_str(&format!("- {}\n", activity));
+            }
+            notes.push_str("\n\n");
+        }
+        if !content.reviews.is_empty() {
+            notes.push_str(&format!("## {}\n\n", REVIEWS_HEADING));
+            for review in &content.reviews {
+                notes.push_str(&format!("- {}\n", review));
+            }
+            notes.push_str("\n\n");
+        }
+        if !content.tickets.is_empty() {
+            notes.push_str(&format!("## {}\n\n", TICKETS_HEADING));
+            for ticket in &content.tickets {
+                notes.push_str(&format!("- {}\n", ticket));
+            }
+            notes.push_str("\n\n");
+        }
+        if !content.sessions.is_empty() {
+            notes.push_str(&format!("## {}\n\n", SESSIONS_HEADING));
+            for session in &content.sessions {
+                notes.push_str(&format!("- {}\n", session));
+            }
+            notes.push_str("\n\n");
+        }
+        if let Some(health) = &content.health {
+            notes.push_str(&format!("## {}\n\n{}\n\n\n", REPO_HEALTH_HEADING, health));
+        }
+        if let Some(work_hours) = &content.work_hours {
+            notes.push_str(&format!(
+                "## {}\n\n{}\n\n\n",
+                WORK_HOURS_HEADING, work_hours
+            ));
+        }
+        if let Some(coding_time) = &content.coding_time {
+            notes.push_str(&format!("## {}\n\n", CODING_TIME_HEADING));
+            for line in coding_time.lines() {
+                notes.push_str(&format!("- {}\n", line));
+            }
+            notes.push_str("\n\n");
+        }
+        if !content.performance.is_empty() {
+            notes.push_str(&format!("## {}\n\n", PERFORMANCE_HEADING));
+            for claim in &content.performance {
+                notes.push_str(&format!("- {}\n", claim));
+            }
+            notes.push_str("\n\n");
+        }
+        if !content.attachments.is_empty() {
+            notes.push_str(&format!("## {}\n\n", IMAGES_HEADING));
+            for attachment in &content.attachments {
+                notes.push_str(&format!("![{}]({})\n\n", attachment.path, attachment.file));
+            }
+            notes.push('\n');
+        }
+
+        let mut translations = String::new();
+        for translation in &content.translations {
+            translations.push_str(&format!(
+                "\n## {}\n\n{}\n",
+                translated_summary_heading(&translation.language),
+                translation.summary
+            ));
+        }
+
+        // Create markdown content
+        format!(
+            "# {}\n\n## {}\n\n{}\n\n{}## {}\n\n{}\n{}",
+            title(content),
+            COMMIT_LOGS_HEADING,
+            commit_logs,
+            notes,
+            SUMMARY_HEADING,
+            content.summary,
+            translations
+        )
+    }
+
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+}
+
+/// Confluence/Jira wiki markup, for pasting into pages that don't render Markdown
+pub struct ConfluenceFormatter;
+
+impl DiaryFormatter for ConfluenceFormatter {
+    fn format(&self, content: &DiaryContent) -> String {
+        let mut commit_logs = String::new();
+        match grouped_commits(content) {
+            Some(groups) => {
+                for (heading, commits) in &groups {
+                    commit_logs.push_str(&format!("h3. {}\n\n", escape_confluence(heading)));
+                    for commit in commits {
+                        commit_logs
+                            .push_str(&format!("* {}\n", escape_confluence(&commit.to_string())));
+                    }
+                    commit_logs.push('\n');
+                }
+            }
+            None => {
+                for commit in content.commit_log() {
+                    commit_logs
+                        .push_str(&format!("* {}\n", escape_confluence(&commit.to_string())));
+                }
+            }
+        }
+
+        let mut notes = String::new();
+        if !content.notes.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", MANUAL_NOTES_HEADING));
+            for note in &content.notes {
+                notes.push_str(&format!("* {}\n", escape_confluence(&note.to_string())));
+            }
+            notes.push('\n');
+        }
+        if !content.context.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", OTHER_ACTIVITIES_HEADING));
+            for activity in &content.context {
+                notes.push_str(&format!("* {}\n", escape_confluence(activity)));
+            }
+            notes.push('\n');
+        }
+        if !content.reviews.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", REVIEWS_HEADING));
+            for review in &content.reviews {
+                notes.push_str(&format!("* {}\n", escape_confluence(&review.to_string())));
+            }
+            notes.push('\n');
+        }
+        if !content.tickets.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", TICKETS_HEADING));
+            for ticket in &content.tickets {
+                notes.push_str(&format!("* {}\n", escape_confluence(&ticket.to_string())));
+            }
+            notes.push('\n');
+        }
+        if !content.sessions.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", SESSIONS_HEADING));
+            for session in &content.sessions {
+                notes.push_str(&format!("* {}\n", escape_confluence(&session.to_string())));
+            }
+            notes.push('\n');
+        }
+        if let Some(health) = &content.health {
+            notes.push_str(&format!(
+                "h2. {}\n\n{}\n\n",
+                REPO_HEALTH_HEADING,
+                escape_confluence(&health.to_string())
+            ));
+        }
+        if let Some(work_hours) = &content.work_hours {
+            notes.push_str(&format!(
+                "h2. {}\n\n{}\n\n",
+                WORK_HOURS_HEADING,
+                escape_confluence(&work_hours.to_string())
+            ));
+        }
+        if let Some(coding_time) = &content.coding_time {
+            notes.push_str(&format!("h2. {}\n\n", CODING_TIME_HEADING));
+            for line in coding_time.lines() {
+                notes.push_str(&format!("* {}\n", escape_confluence(&line)));
+            }
+            notes.push('\n');
+        }
+        if !content.performance.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", PERFORMANCE_HEADING));
+            for claim in &content.performance {
+                notes.push_str(&format!("* {}\n", escape_confluence(&claim.to_string())));
+            }
+            notes.push('\n');
+        }
+        if !content.attachments.is_empty() {
+            notes.push_str(&format!("h2. {}\n\n", IMAGES_HEADING));
+            for attachment in &content.attachments {
+                notes.push_str(&format!("!{}|alt={}!\n", attachment.file, attachment.path));
+            }
+            notes.push('\n');
+        }
+
+        let mut translations = String::new();
+        for translation in &content.translations {
+            translations.push_str(&format!(
+                "\nh2. {}\n\n{}\n",
+                translated_summary_heading(&translation.language),
+                markdown_to_confluence(&translation.summary)
+            ));
+        }
+
+        format!(
+            "h1. {}\n\nh2. {}\n\n{}\n{}h2. {}\n\n{}\n{}",
+            title(content),
+            COMMIT_LOGS_HEADING,
+            commit_logs,
+            notes,
+            SUMMARY_HEADING,
+            markdown_to_confluence(&content.summary),
+            translations
+        )
+    }
+
+    fn extension(&self) -> &'static str {
+        "confluence"
+    }
+}
+
+/// Color scheme of HTML diaries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum HtmlTheme {
+    #[default]
+    Light,
+    Dark,
+    /// Black on white with serif text and no backgrounds, for paper and PDF
+    Print,
+}
+
+impl HtmlTheme {
+    fn css(self) -> &'static str {
+        match self {
+            HtmlTheme::Light => LIGHT_THEME_CSS,
+            HtmlTheme::Dark => DARK_THEME_CSS,
+            HtmlTheme::Print => PRINT_THEME_CSS,
+        }
+    }
+}
+
+/// Layout shared by all themes; colors come from the theme's variables
+const BASE_CSS: &str = "body { margin: 0; background: var(--bg); color: var(--fg); font: 16px/1.6 var(--font); }
+main { max-width: 46rem; margin: 0 auto; padding: 2rem 1.25rem; }
+h1, h2, h3 { line-height: 1.25; }
+h2 { border-bottom: 1px solid var(--border); padding-bottom: 0.3rem; margin-top: 2rem; }
+h3 { color: var(--muted); }
+ul, ol { padding-left: 1.5rem; }
+code { background: var(--code-bg); border-radius: 4px; padding: 0.1rem 0.3rem; font: 0.9em ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; }
+pre { background: var(--code-bg); border: 1px solid var(--border); border-radius: 6px; padding: 0.75rem; overflow-x: auto; }
+pre code { padding: 0; background: none; }
+";
+
+const LIGHT_THEME_CSS: &str = ":root { --bg: #ffffff; --fg: #1f2328; --muted: #59636e; --border: #d1d9e0; --code-bg: #f6f8fa; --font: system-ui, -apple-system, \"Segoe UI\", sans-serif; }
+";
+
+const DARK_THEME_CSS: &str = ":root { color-scheme: dark; --bg: #0d1117; --fg: #e6edf3; --muted: #9198a1; --border: #3d444d; --code-bg: #151b23; --font: system-ui, -apple-system, \"Segoe UI\", sans-serif; }
+";
+
+const PRINT_THEME_CSS: &str = ":root { --bg: #ffffff; --fg: #000000; --muted: #333333; --border: #000000; --code-bg: transparent; --font: Georgia, \"Times New Roman\", serif; }
+@page { margin: 2cm; }
+main { max-width: none; padding: 0; }
+h2, h3 { break-after: avoid; }
+li, pre { break-inside: avoid; }
+";
+
+/// A single self-contained HTML page, with the theme's CSS inlined and no
+/// external resources, so it can be emailed or archived as one file
+pub struct HtmlFormatter {
+    theme: HtmlTheme,
+}
+
+impl HtmlFormatter {
+    pub fn new(theme: HtmlTheme) -> Self {
+        Self { theme }
+    }
+}
+
+impl DiaryFormatter for HtmlFormatter {
+    fn format(&self, content: &DiaryContent) -> String {
+        let mut body = format!("<h1>{}</h1>\n", escape_html(&title(content)));
+
+        body.push_str(&format!("<h2>{}</h2>\n", COMMIT_LOGS_HEADING));
+        match grouped_commits(content) {
+            Some(groups) => {
+                for (heading, commits) in &groups {
+                    body.push_str(&format!("<h3>{}</h3>\n", escape_html(heading)));
+                    body.push_str(&html_list(commits.iter().map(Commit::to_string)));
+                }
+            }
+            None => {
+                body.push_str(&html_list(
+                    content.commit_log().into_iter().map(Commit::to_string),
+                ));
+            }
+        }
+
+        let sections = [
+            (
+                MANUAL_NOTES_HEADING,
+                content.notes.iter().map(|note| note.to_string()).collect(),
+            ),
+            (OTHER_ACTIVITIES_HEADING, content.context.clone()),
+            (
+                REVIEWS_HEADING,
+                content
+                    .reviews
+                    .iter()
+                    .map(|review| review.to_string())
+                    .collect(),
+            ),
+            (
+                TICKETS_HEADING,
+                content
+                    .tickets
+                    .iter()
+                    .map(|ticket| ticket.to_string())
+                    .collect(),
+            ),
+            (
+                SESSIONS_HEADING,
+                content
+                    .sessions
+                    .iter()
+                    .map(|session| session.to_string())
+                    .collect(),
+            ),
+            (
+                CODING_TIME_HEADING,
+                content
+                    .coding_time
+                    .as_ref()
+                    .map(CodingTime::lines)
+                    .unwrap_or_default(),
+            ),
+            (
+                PERFORMANCE_HEADING,
+                content
+                    .performance
+                    .iter()
+                    .map(|claim| claim.to_string())
+                    .collect(),
+            ),
+        ];
+        for (heading, items) in sections {
+            if !items.is_empty() {
+                body.push_str(&format!("<h2>{}</h2>\n", heading));
+                body.push_str(&html_list(items.into_iter()));
+            }
+        }
+        if let Some(health) = &content.health {
+            body.push_str(&format!(
+                "<h2>{}</h2>\n<p>{}</p>\n",
+                REPO_HEALTH_HEADING,
+                escape_html(&health.to_string())
+            ));
+        }
+        if let Some(work_hours) = &content.work_hours {
+            body.push_str(&format!(
+                "<h2>{}</h2>\n<p>{}</p>\n",
+                WORK_HOURS_HEADING,
+                escape_html(&work_hours.to_string())
+            ));
+        }
+        if !content.attachments.is_empty() {
+            body.push_str(&format!("<h2>{}</h2>\n", IMAGES_HEADING));
+            for attachment in &content.attachments {
+                body.push_str(&format!(
+                    "<figure><img src=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>\n",
+                    escape_html(&attachment.file),
+                    escape_html(&attachment.path),
+                    escape_html(&attachment.path)
+                ));
+            }
+        }
+
+        body.push_str(&format!(
+            "<h2>{}</h2>\n{}\n",
+            SUMMARY_HEADING,
+            markdown_to_html(&content.summary)
+        ));
+        for translation in &content.translations {
+            body.push_str(&format!(
+                "<h2>{}</h2>\n<div lang=\"{}\">\n{}\n</div>\n",
+                escape_html(&translated_summary_heading(&translation.language)),
+                escape_html(&translation.language),
+                markdown_to_html(&translation.summary)
+            ));
+        }
+
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>\n{}{}</style>\n</head>\n<body>\n<main>\n{}</main>\n</body>\n</html>\n",
+            escape_html(&title(content)),
+            BASE_CSS,
+            self.theme.css(),
+            body
+        )
+    }
+
+    fn extension(&self) -> &'static str {
+        "html"
+    }
+}
+
+/// `<ul>` of escaped items
+fn html_list(items: impl Iterator<Item = String>) -> String {
+    let mut list = String::from("<ul>\n");
+    for item in items {
+        list.push_str(&format!("<li>{}</li>\n", escape_html(&item)));
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+/// One row per commit for spreadsheets, e.g. for billing
+///
+/// Columns are date, sha, author, message, files, additions and deletions,
+/// oldest commit first. Fields the source did not record are left empty, and
+/// the summary and notes are not part of the export.
+pub struct CsvFormatter;
+
+/// Header row of the CSV export
+pub const CSV_HEADER: &str = "date,sha,author,message,files,additions,deletions";
+
+impl DiaryFormatter for CsvFormatter {
+    fn format(&self, content: &DiaryContent) -> String {
+        let mut csv = format!("{}\r\n", CSV_HEADER);
+        for commit in CommitOrder::Chronological.ordered(&content.commits) {
+            let fields = [
+                commit.datetime().unwrap_or_default(),
+                commit.id.clone().unwrap_or_default(),
+                commit
+                    .author
+                    .as_ref()
+                    .map(|author| author.name.clone())
+                    .unwrap_or_default(),
+                commit.subject().to_string(),
+                commit.files.join("; "),
+                commit
+                    .line_stats
+                    .map(|stats| stats.additions.to_string())
+                    .unwrap_or_default(),
+                commit
+                    .line_stats
+                    .map(|stats| stats.deletions.to_string())
+                    .unwrap_or_default(),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| escape_csv(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    fn extension(&self) -> &'static str {
+        "csv"
+    }
+}
+
+/// Quotes a field as RFC 4180 requires, and defuses cells spreadsheets would
+/// run as formulas
+fn escape_csv(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Heading of the summary in a further language, such as "AI-generated Summary (ja)"
+///
+/// The main summary keeps the plain heading, so tools that read stored
+/// diaries find it as before.
+pub fn translated_summary_heading(language: &str) -> String {
+    format!("{} ({})", SUMMARY_HEADING, language)
+}
+
+/// Diary title: the week label for weekly rollups, the date range otherwise,
+/// followed by the branch for `--per-branch` diaries
+fn title(content: &DiaryContent) -> String {
+    let title = match &content.rollup {
+        Some(rollup) => rollup.title.clone(),
+        None => format!("Git Diary ({} – {})", content.start_date, content.end_date),
+    };
+    match &content.branch {
+        Some(branch) => format!("{} on {}", title, branch),
+        None => title,
+    }
+}
+
+/// Markdown index of a `--per-branch` run, linking each branch's diary by file name
+pub fn format_branch_index(index: &BranchIndex) -> String {
+    let mut output = format!(
+        "# Git Diary branches ({} – {})\n\n",
+        index.start_date, index.end_date
+    );
+    for diary in &index.diaries {
+        let file_name = Path::new(&diary.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| diary.path.clone());
+        output.push_str(&format!(
+            "- [{}]({}): {} commit{}\n",
+            diary.branch,
+            file_name,
+            diary.commits,
+            if diary.commits == 1 { "" } else { "s" }
+        ));
+    }
+    output
+}
+
+/// Commit logs split into headed groups: days for weekly rollups, pull
+/// requests when commits reference them, `None` for a flat list
+fn grouped_commits(content: &DiaryContent) -> Option<Vec<(String, Vec<Commit>)>> {
+    if let Some(rollup) = &content.rollup {
+        return Some(rollup.days.clone());
+    }
+    if content.pull_requests.is_empty() {
+        return None;
+    }
+
+    let mut groups: Vec<(String, Vec<Commit>)> = content
+        .pull_requests
+        .iter()
+        .map(|group| {
+            (
+                format!("{} (#{})", group.title, group.number),
+                group.commits.clone(),
+            )
+        })
+        .collect();
+
+    let mut other: Vec<Commit> = content
+        .commits
+        .iter()
+        .filter(|commit| commit.pull_request().is_none())
+        .cloned()
+        .collect();
+    if !other.is_empty() {
+        other.sort_by_key(Commit::timestamp);
+        groups.push((OTHER_COMMITS_HEADING.to_string(), other));
+    }
+
+    Some(groups)
+}
+
+/// Escapes characters that start wiki markup macros or links
+fn escape_confluence(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '{' | '}' | '[' | ']' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Converts the Markdown subset models usually emit into wiki markup
+///
+/// Handles headings, bullet and numbered lists, fenced code blocks, bold
+/// text and inline code. Anything else is passed through escaped.
+fn markdown_to_confluence(markdown: &str) -> String {
+    let mut output = Vec::new();
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        if let Some(language) = line.trim_start().strip_prefix("```") {
+            if in_code_block || language.trim().is_empty() {
+                output.push("{code}".to_string());
+            } else {
+                output.push(format!("{{code:language={}}}", language.trim()));
+            }
+            in_code_block = !in_code_block;
+            continue;
+        }
+
+        if in_code_block {
+            output.push(line.to_string());
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        let heading_level = trimmed.chars().take_while(|&c| c == '#').count();
+        let converted =
+            if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+                format!(
+                    "h{}. {}",
+                    heading_level,
+                    convert_inline(trimmed[heading_level..].trim())
+                )
+            } else if let Some(item) = trimmed
+                .strip_prefix("- ")
+                .or_else(|| trimmed.strip_prefix("* "))
+            {
+                let depth = (line.len() - trimmed.len()) / 2 + 1;
+                format!("{} {}", "*".repeat(depth), convert_inline(item))
+            } else if let Some(item) = strip_numbered_prefix(trimmed) {
+                let depth = (line.len() - trimmed.len()) / 2 + 1;
+                format!("{} {}", "#".repeat(depth), convert_inline(item))
+            } else {
+                convert_inline(line)
+            };
+        output.push(converted);
+    }
+
+    // Close a fence the model forgot to close
+    if in_code_block {
+        output.push("{code}".to_string());
+    }
+
+    output.join("\n")
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Converts the Markdown subset models usually emit into HTML
+///
+/// Handles the same constructs as `markdown_to_confluence`, plus
+/// paragraphs; nested lists are flattened. Everything is escaped, so model
+/// output can't inject markup.
+fn markdown_to_html(markdown: &str) -> String {
+    let mut output = Vec::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    let mut list: Option<&str> = None;
+    let mut code_block: Option<Vec<String>> = None;
+
+    fn flush(output: &mut Vec<String>, paragraph: &mut Vec<String>, list: &mut Option<&str>) {
+        if !paragraph.is_empty() {
+            output.push(format!("<p>{}</p>", paragraph.join("\n")));
+            paragraph.clear();
+        }
+        if let Some(tag) = list.take() {
+            output.push(format!("</{}>", tag));
+        }
+    }
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            match code_block.take() {
+                Some(lines) => {
+                    output.push(format!("<pre><code>{}</code></pre>", lines.join("\n")));
+                }
+                None => {
+                    flush(&mut output, &mut paragraph, &mut list);
+                    code_block = Some(Vec::new());
+                }
+            }
+            continue;
+        }
+        if let Some(lines) = &mut code_block {
+            lines.push(escape_html(line));
+            continue;
+        }
+
+        let trimmed = line.trim_start();
+        let heading_level = trimmed.chars().take_while(|&c| c == '#').count();
+        let item = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .map(|item| ("ul", item))
+            .or_else(|| strip_numbered_prefix(trimmed).map(|item| ("ol", item)));
+
+        if trimmed.is_empty() {
+            flush(&mut output, &mut paragraph, &mut list);
+        } else if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+            flush(&mut output, &mut paragraph, &mut list);
+            output.push(format!(
+                "<h{level}>{}</h{level}>",
+                html_inline(trimmed[heading_level..].trim()),
+                level = heading_level
+            ));
+        } else if let Some((tag, item)) = item {
+            if list != Some(tag) {
+                flush(&mut output, &mut paragraph, &mut list);
+                output.push(format!("<{}>", tag));
+                list = Some(tag);
+            }
+            output.push(format!("<li>{}</li>", html_inline(item)));
+        } else {
+            if let Some(tag) = list.take() {
+                output.push(format!("</{}>", tag));
+            }
+            paragraph.push(html_inline(line.trim()));
+        }
+    }
+
+    // Close a fence the model forgot to close
+    if let Some(lines) = code_block {
+        output.push(format!("<pre><code>{}</code></pre>", lines.join("\n")));
+    }
+    flush(&mut output, &mut paragraph, &mut list);
+
+    output.join("\n")
+}
+
+/// Converts `**bold**` and `` `code` `` in escaped text
+fn html_inline(text: &str) -> String {
+    let escaped = escape_html(text);
+    let mut output = String::with_capacity(escaped.len());
+    let mut rest = escaped.as_str();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("**") {
+            if let Some(end) = after.find("**") {
+                output.push_str(&format!("<strong>{}</strong>", &after[..end]));
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                output.push_str(&format!("<code>{}</code>", &after[..end]));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    output
+}
+
+fn strip_numbered_prefix(line: &str) -> Option<&str> {
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    line[digits..].strip_prefix(". ")
+}
+
+/// Converts `**bold**` to `*bold*` and `` `code` `` to `{{code}}`
+fn convert_inline(text: &str) -> String {
+    let escaped = escape_confluence(text);
+    let mut output = String::with_capacity(escaped.len());
+    let mut rest = escaped.as_str();
+
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("**") {
+            if let Some(end) = after.find("**") {
+                output.push_str(&format!("*{}*", &after[..end]));
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                output.push_str(&format!("{{{{{}}}}}", &after[..end]));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attachments::Attachment;
+    use crate::codingtime::TimeShare;
+    use crate::domain::{
+        group_by_pull_request, Author, BranchDiary, LineStats, Note, Review, SessionSummary,
+        TicketTransition, TranslatedSummary, WeekRollup,
+    };
+    use crate::health::RepoHealth;
+    use crate::perf::PerformanceClaim;
+    use crate::worktime::WorkHours;
+
+    fn create_test_diary_content(summary: &str) -> DiaryContent {
+        DiaryContent {
+            commits: vec![
+                Commit::new("Second commit".to_string(), 1704153600),
+                Commit::new("Fix [bug] in {parser}".to_string(), 1704067200),
+            ],
+            summary: summary.to_string(),
+            start_date: "2024-01-01".to_string(),
+            end_date: "2024-01-07".to_string(),
+            rollup: None,
+            notes: Vec::new(),
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::default(),
+        }
+    }
+
+    #[test]
+    fn test_confluence_structure() {
+        let content = create_test_diary_content("Worked on the parser.");
+
+        let output = ConfluenceFormatter.format(&content);
+
+        assert!(
+            output.starts_with("h1. Git Diary (2024-01-01 – 2024-01-07)\n\nh2. Commit Logs\n\n")
+        );
+        assert!(output.contains("* 2024-01-01 00:00:00: Fix \\[bug\\] in \\{parser\\}\n"));
+        assert!(output.contains("h2. AI-generated Summary\n\nWorked on the parser.\n"));
+        assert_eq!(ConfluenceFormatter.extension(), "confluence");
+    }
+
+    #[test]
+    fn test_html_is_self_contained() {
+        let mut content = create_test_diary_content("Worked on the **parser**.");
+        content.notes = vec![Note::new("Paired on <script>".to_string(), 1704067200)];
+
+        let html = HtmlFormatter::new(HtmlTheme::Dark).format(&content);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"));
+        assert!(html.contains("<title>Git Diary (2024-01-01 – 2024-01-07)</title>"));
+        assert!(html.contains("--bg: #0d1117"));
+        assert!(html.contains("<li>2024-01-01 00:00:00: Fix [bug] in {parser}</li>"));
+        assert!(html.contains(
+            "<h2>Manual notes</h2>\n<ul>\n<li>2024-01-01 00:00:00: Paired on &lt;script&gt;</li>"
+        ));
+        assert!(html.contains(
+            "<h2>AI-generated Summary</h2>\n<p>Worked on the <strong>parser</strong>.</p>"
+        ));
+        // Nothing is fetched when the file is opened
+        assert!(!html.contains("http"));
+        assert!(!html.contains("<link"));
+        assert!(!html.contains("<script"));
+        assert_eq!(HtmlFormatter::new(HtmlTheme::Light).extension(), "html");
+
+        let print = HtmlFormatter::new(HtmlTheme::Print).format(&content);
+        assert!(print.contains("@page { margin: 2cm; }"));
+    }
+
+    #[test]
+    fn test_markdown_to_html() {
+        let html = markdown_to_html(
+            "## Highlights\n\nShipped the `SyncEngine`\nretry loop.\n\n- Fixed <b>cache</b>\n- Added tests\n1. First\n\n```rust\nfn a() {}\n",
+        );
+
+        assert_eq!(
+            html,
+            "<h2>Highlights</h2>\n<p>Shipped the <code>SyncEngine</code>\nretry loop.</p>\n<ul>\n<li>Fixed &lt;b&gt;cache&lt;/b&gt;</li>\n<li>Added tests</li>\n</ul>\n<ol>\n<li>First</li>\n</ol>\n<pre><code>fn a() {}</code></pre>"
+        );
+    }
+
+    #[test]
+    fn test_csv_format() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.commits = vec![
+            Commit::new("commit: Second, \"quoted\" commit".to_string(), 1704153600)
+                .with_id("9fceb02d0ae598e95dc970b74767f19372d61af8".to_string())
+                .with_author(Author::new(
+                    "Jane Doe".to_string(),
+                    "jane@example.com".to_string(),
+                ))
+                .with_files(vec!["src/a.rs".to_string(), "src/b.rs".to_string()])
+                .with_line_stats(LineStats::new(12, 3)),
+            Commit::new("-Initial commit".to_string(), 1704067200),
+        ];
+
+        let csv = CsvFormatter.format(&content);
+
+        assert_eq!(
+            csv,
+            "date,sha,author,message,files,additions,deletions\r\n\
+             2024-01-01 00:00:00,,,'-Initial commit,,,\r\n\
+             2024-01-02 00:00:00,9fceb02d0ae598e95dc970b74767f19372d61af8,Jane Doe,\"Second, \"\"quoted\"\" commit\",src/a.rs; src/b.rs,12,3\r\n"
+        );
+        assert_eq!(CsvFormatter.extension(), "csv");
+    }
+
+    #[test]
+    fn test_week_rollup_format() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.rollup = Some(WeekRollup {
+            title: "Week 1, 2024".to_string(),
+            days: vec![
+                (
+                    "Monday, 1 January".to_string(),
+                    vec![content.commits[1].clone()],
+                ),
+                (
+                    "Tuesday, 2 January".to_string(),
+                    vec![content.commits[0].clone()],
+                ),
+            ],
+        });
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.starts_with(
+            "# Week 1, 2024\n\n## Commit Logs\n\n### Monday, 1 January\n\n- 2024-01-01 00:00:00: Fix [bug] in {parser}\n\n### Tuesday, 2 January\n\n- 2024-01-02 00:00:00: Second commit\n\n\n## AI-generated Summary"
+        ));
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence
+            .starts_with("h1. Week 1, 2024\n\nh2. Commit Logs\n\nh3. Monday, 1 January\n\n* "));
+    }
+
+    #[test]
+    fn test_pull_request_groups_format() {
+        let mut content = create_test_diary_content("Shipped the export.");
+        content.commits = vec![
+            Commit::new("Add CSV export (#12)".to_string(), 1704067200),
+            Commit::new("Tidy README".to_string(), 1704070800),
+            Commit::new(
+                "Merge pull request #12 from jane/export".to_string(),
+                1704074400,
+            ),
+        ];
+        content.pull_requests = group_by_pull_request(&content.commits);
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "## Commit Logs\n\n### Add CSV export (#12)\n\n- 2024-01-01 00:00:00: Add CSV export (#12)\n- 2024-01-01 02:00:00: Merge pull request #12 from jane/export\n\n### Other commits\n\n- 2024-01-01 01:00:00: Tidy README\n"
+        ));
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence.contains("h3. Add CSV export (#12)\n\n* "));
+        assert!(confluence.contains("h3. Other commits\n\n* 2024-01-01 01:00:00: Tidy README\n"));
+    }
+
+    #[test]
+    fn test_manual_notes_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.notes = vec![Note::new("Paired with Alice".to_string(), 1704070800)];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "Second commit\n\n\n## Manual notes\n\n- 2024-01-01 01:00:00: Paired with Alice\n\n\n## AI-generated Summary"
+        ));
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence.contains(
+            "Second commit\n\nh2. Manual notes\n\n* 2024-01-01 01:00:00: Paired with Alice\n\nh2. AI-generated Summary"
+        ));
+    }
+
+    #[test]
+    fn test_other_activities_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.context = vec!["Design review [3h]".to_string()];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "Second commit\n\n\n## Other activities\n\n- Design review [3h]\n\n\n## AI-generated Summary"
+        ));
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence.contains(
+            "Second commit\n\nh2. Other activities\n\n* Design review \\[3h\\]\n\nh2. AI-generated Summary"
+        ));
+    }
+
+    #[test]
+    fn test_sessions_section() {
+        let mut content = create_test_diary_content("Worked on auth and migrations.");
+        content.sessions = vec![SessionSummary {
+            label: "Morning".to_string(),
+            start: "09:10".to_string(),
+            end: "11:45".to_string(),
+            commits: 3,
+            summary: "Fixed the flaky auth tests".to_string(),
+        }];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown
+            .contains("## Sessions\n\n- Morning (09:10–11:45): Fixed the flaky auth tests\n"));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(html.contains("<h2>Sessions</h2>"));
+    }
+
+    #[test]
+    fn test_reviews_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.reviews = vec![Review::new(
+            12,
+            "Retry failed payments".to_string(),
+            "approved".to_string(),
+        )];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "Second commit\n\n\n## Reviews\n\n- #12 Retry failed payments (approved)\n\n\n## AI-generated Summary"
+        ));
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence.contains("h2. Reviews\n\n* #12 Retry failed payments (approved)\n"));
+    }
+
+    #[test]
+    fn test_tickets_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.tickets = vec![TicketTransition::new(
+            "PAY-142".to_string(),
+            "Retry failed payments".to_string(),
+            "Done".to_string(),
+        )];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "## Tickets\n\n- PAY-142 Retry failed payments (moved to Done)\n\n\n## AI-generated Summary"
+        ));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(html.contains("<h2>Tickets</h2>"));
+    }
+
+    #[test]
+    fn test_repo_health_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        let mut health = RepoHealth::default();
+        health.add_line("// TODO: retry", true);
+        content.health = Some(health);
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "## Repo health\n\nTODO/FIXME markers: 1 added, 0 removed (net +1). Test files: 0 added, 0 removed. Build config unchanged.\n\n\n## AI-generated Summary"
+        ));
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence.contains("h2. Repo health\n\nTODO/FIXME markers: 1 added"));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(html.contains("<h2>Repo health</h2>\n<p>TODO/FIXME markers: 1 added"));
+    }
+
+    #[test]
+    fn test_work_hours_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.work_hours = Some(WorkHours {
+            commits: 5,
+            late_night: 2,
+            weekend: 0,
+        });
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains("## Working hours\n\n40% of commits after 22:00\n\n\n"));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(html.contains("<h2>Working hours</h2>\n<p>40% of commits after 22:00</p>"));
+    }
+
+    #[test]
+    fn test_coding_time_section() {
+        let mut content = create_test_diary_content("Worked on the parser.");
+        content.coding_time = Some(CodingTime {
+            total_seconds: 5400,
+            projects: vec![TimeShare {
+                name: "git-diary".to_string(),
+                seconds: 5400,
+            }],
+            languages: Vec::new(),
+        });
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains(
+            "## Time by project and language\n\n- 1h 30m in total\n- Projects: git-diary 1h 30m\n\n\n"
+        ));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(
+            html.contains("<h2>Time by project and language</h2>\n<ul>\n<li>1h 30m in total</li>")
+        );
+    }
+
+    #[test]
+    fn test_performance_section() {
+        let mut content = create_test_diary_content("Sped up startup.");
+        content.performance = vec![PerformanceClaim {
+            subject: "perf: Cache configs".to_string(),
+            figures: vec!["2.3x faster".to_string()],
+        }];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.contains("## Performance\n\n- perf: Cache configs (2.3x faster)\n\n\n"));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(
+            html.contains("<h2>Performance</h2>\n<ul>\n<li>perf: Cache configs (2.3x faster)</li>")
+        );
+    }
+
+    #[test]
+    fn test_images_section() {
+        let mut content = create_test_diary_content("Drew the sync flow.");
+        content.attachments = vec![Attachment::new(
+            "docs/sync-flow.png".to_string(),
+            "3b18e512",
+        )];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(
+            markdown.contains("## Images\n\n![docs/sync-flow.png](attachments/3b18e512.png)\n\n\n")
+        );
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(confluence
+            .contains("h2. Images\n\n!attachments/3b18e512.png|alt=docs/sync-flow.png!\n"));
+
+        let html = HtmlFormatter::new(HtmlTheme::Light).format(&content);
+        assert!(html
+            .contains("<figure><img src=\"attachments/3b18e512.png\" alt=\"docs/sync-flow.png\">"));
+    }
+
+    #[test]
+    fn test_translated_summaries() {
+        let mut content = create_test_diary_content("Worked on the **parser**.");
+        content.translations = vec![TranslatedSummary {
+            language: "ja".to_string(),
+            summary: "**パーサー**を改善しました。".to_string(),
+        }];
+
+        let markdown = MarkdownFormatter.format(&content);
+        assert!(markdown.ends_with(
+            "## AI-generated Summary\n\nWorked on the **parser**.\n\n## AI-generated Summary (ja)\n\n**パーサー**を改善しました。\n"
+        ));
+
+        // The main summary is still found under the plain heading
+        let parsed = crate::parser::parse_diary(&markdown).unwrap();
+        assert_eq!(
+            parsed.section(SUMMARY_HEADING).unwrap().body,
+            "Worked on the **parser**."
+        );
+
+        let confluence = ConfluenceFormatter.format(&content);
+        assert!(
+            confluence.ends_with("h2. AI-generated Summary (ja)\n\n*パーサー*を改善しました。\n")
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_confluence() {
+        let markdown = "## Highlights\n- Added **CSV export**\n  - with `--format csv`\n1. First\n```rust\nlet x = vec![1];\n```";
+
+        let output = markdown_to_confluence(markdown);
+
+        assert_eq!(
+            output,
+            "h2. Highlights\n* Added *CSV export*\n** with {{--format csv}}\n# First\n{code:language=rust}\nlet x = vec![1];\n{code}"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_confluence_closes_unterminated_code_block() {
+        let output = markdown_to_confluence("```\nfn main() {}");
+
+        assert_eq!(output, "{code}\nfn main() {}\n{code}");
+    }
+
+    #[test]
+    fn test_branch_diaries() {
+        let mut content = create_test_diary_content("Summary");
+        content.branch = Some("feature/sync".to_string());
+        assert!(MarkdownFormatter
+            .format(&content)
+            .starts_with("# Git Diary (2024-01-01 – 2024-01-07) on feature/sync\n"));
+
+        let index = BranchIndex {
+            start_date: "2024-01-01".to_string(),
+            end_date: "2024-01-07".to_string(),
+            diaries: vec![
+                BranchDiary {
+                    branch: "feature/sync".to_string(),
+                    path: "diaries/git-diary-20240101-to-20240107-branch-feature-sync.md"
+                        .to_string(),
+                    commits: 3,
+                },
+                BranchDiary {
+                    branch: "main".to_string(),
+                    path: "diaries/git-diary-20240101-to-20240107-branch-main.md".to_string(),
+                    commits: 1,
+                },
+            ],
+        };
+        assert_eq!(
+            format_branch_index(&index),
+            "# Git Diary branches (2024-01-01 – 2024-01-07)\n\n\
+             - [feature/sync](git-diary-20240101-to-20240107-branch-feature-sync.md): 3 commits\n\
+             - [main](git-diary-20240101-to-20240107-branch-main.md): 1 commit\n"
+        );
+    }
+}
@@ -1,42 +1,701 @@
 use anyhow::Result;
-use git2;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::domain::{Commit, GitRepository};
+use crate::attachments::{referenced_images, Attachment};
+use crate::domain::{
+    AttachmentSource, Author, AuthorFilter, Commit, FileRename, GitRepository, HealthSource,
+    LineStats,
+};
+use crate::encoding::decode_message;
+use crate::health::{FileChange, RepoHealth};
+
+/// Rename similarity threshold git itself uses
+pub const DEFAULT_RENAME_SIMILARITY: u16 = 50;
+
+/// Where commits are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum CommitSource {
+    /// What happened at the keyboard: every commit, amend and cherry-pick HEAD
+    /// moved to, falling back to history when the reflog has nothing, as in a
+    /// fresh clone
+    #[default]
+    Reflog,
+    /// What exists: the commits reachable from HEAD
+    Revwalk,
+    /// History plus the reflog events it no longer shows, such as amended or
+    /// rebased-away commits, which are marked as reflog only
+    Hybrid,
+}
 
 pub struct GitRepositoryImpl {
     repo_path: String,
+    rename_similarity: u16,
+    source: CommitSource,
+    diffs: bool,
 }
 
 impl GitRepositoryImpl {
     pub fn new(repo_path: String) -> Self {
-        Self { repo_path }
+        Self {
+            repo_path,
+            rename_similarity: DEFAULT_RENAME_SIMILARITY,
+            source: CommitSource::default(),
+            diffs: false,
+        }
     }
-}
 
-#[async_trait::async_trait]
-impl GitRepository for GitRepositoryImpl {
-    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
-        let repo = git2::Repository::open(&self.repo_path)?;
+    /// Chooses between the reflog, history or both; the reflog by default
+    pub fn with_source(mut self, source: CommitSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the similarity (0-100) used for rename and copy detection; `None` keeps git's default
+    pub fn with_rename_similarity(mut self, similarity: Option<u16>) -> Self {
+        self.rename_similarity = similarity.unwrap_or(DEFAULT_RENAME_SIMILARITY);
+        self
+    }
+
+    /// Keeps each commit's patch so the summary prompt can quote it
+    pub fn with_diffs(mut self, diffs: bool) -> Self {
+        self.diffs = diffs;
+        self
+    }
+
+    /// Adds the files `target` changed relative to its first parent, which
+    /// of them it renamed or copied, how many lines it added and removed,
+    /// and its patch when diffs were asked for
+    fn with_changes(
+        &self,
+        repo: &git2::Repository,
+        target: &git2::Commit,
+        commit: Commit,
+    ) -> Result<Commit> {
+        let commit_tree = target.tree()?;
+        let parent_tree = target.parent(0)?.tree()?;
+        let mut diff = repo.diff_tree_to_tree(Some(&parent_tree), Some(&commit_tree), None)?;
+        diff.find_similar(Some(
+            git2::DiffFindOptions::new()
+                .renames(true)
+                .copies(true)
+                .rename_threshold(self.rename_similarity)
+                .copy_threshold(self.rename_similarity),
+        ))?;
+
+        let mut files = Vec::new();
+        let mut renames = Vec::new();
+        for delta in diff.deltas() {
+            let from = delta
+                .old_file()
+                .path()
+                .map(|path| path.to_string_lossy().to_string());
+            let to = delta
+                .new_file()
+                .path()
+                .map(|path| path.to_string_lossy().to_string());
+            if let Some(path) = to.clone().or(from.clone()) {
+                files.push(path);
+            }
+            if let (Some(from), Some(to)) = (from, to) {
+                match delta.status() {
+                    git2::Delta::Renamed => renames.push(FileRename::new(from, to)),
+                    git2::Delta::Copied => renames.push(FileRename::copy(from, to)),
+                    _ => {}
+                }
+            }
+        }
+
+        let stats = diff.stats()?;
+        let line_stats = LineStats::new(stats.insertions(), stats.deletions());
+        let mut commit = commit
+            .with_files(files)
+            .with_renames(renames)
+            .with_line_stats(line_stats);
+
+        if self.diffs {
+            let mut patch = Vec::new();
+            diff.print(git2::DiffFormat::Patch, |_, _, line| {
+                if matches!(line.origin(), '+' | '-' | ' ') {
+                    patch.push(line.origin() as u8);
+                }
+                patch.extend_from_slice(line.content());
+                true
+            })?;
+            commit = commit.with_diff(String::from_utf8_lossy(&patch).into_owned());
+        }
+
+        Ok(commit)
+    }
+
+    /// Commits HEAD moved to since `timestamp`, newest first, as recorded in the reflog
+    ///
+    /// Each commit is attributed to the branch HEAD was on at the time, found
+    /// by replaying the `checkout: moving from A to B` entries backwards.
+    fn reflog_commits(&self, repo: &git2::Repository, timestamp: i64) -> Result<Vec<Commit>> {
         let reflogs = repo.reflog("HEAD")?;
         let reflogs = reflogs.iter();
 
         let mut commits = Vec::new();
+        let mut current_branch = head_branch(repo);
 
         for reflog in reflogs {
+            let branch = current_branch.clone();
+            if let Some(previous) = reflog.message().and_then(checked_out_from) {
+                current_branch = previous;
+            }
+
+            // Skip rather than stop: with a skewed clock, older-looking entries can
+            // sit in front of newer ones
             let time = reflog.committer().when();
             if time.seconds() < timestamp {
-                break;
+                continue;
+            }
+            // A clone records where HEAD started, not work done in the repository
+            if reflog
+                .message_bytes()
+                .is_some_and(|bytes| bytes.starts_with(b"clone: "))
+            {
+                continue;
+            }
+            let target = repo.find_commit(reflog.id_new()).ok();
+
+            // The reflog copies the subject bytes, so decode them with the commit's encoding
+            let encoding = target
+                .as_ref()
+                .and_then(|commit| commit.message_encoding().map(str::to_string));
+            let message = match reflog.message_bytes() {
+                Some(bytes) => {
+                    let decoded = decode_message(bytes, encoding.as_deref());
+                    if let Some(warning) = decoded.warning {
+                        eprintln!("⚠️  Commit {}: {}", reflog.id_new(), warning);
+                    }
+                    decoded.text
+                }
+                None => "No message".to_string(),
+            };
+            let mut commit = Commit::new(message, reflog.committer().when().seconds())
+                .with_id(reflog.id_new().to_string());
+            commit.branch = branch;
+            commit.author = target.as_ref().map(author_of);
+            commit.author_time = target
+                .as_ref()
+                .map(|target| target.author().when().seconds());
+            commit.author_offset = target
+                .as_ref()
+                .map(|target| target.author().when().offset_minutes());
+            commit.body = target.as_ref().and_then(body_of);
+            // Only entries that added the commit on top of the previous HEAD
+            // describe its changes; checkouts and resets just move HEAD around
+            if let Some(target) = target
+                .as_ref()
+                .filter(|target| target.parent_id(0).ok() == Some(reflog.id_old()))
+            {
+                commit = self.with_changes(repo, target, commit)?;
+            }
+            commits.push(commit);
+        }
+
+        Ok(commits)
+    }
+
+    /// History since `timestamp` merged with the reflog events it doesn't show, newest first
+    ///
+    /// History commits the reflog recorded with a more specific action, such as
+    /// `commit (amend)`, take that action. Commits, amends and cherry-picks the
+    /// reflog recorded but history no longer reaches are kept as reflog only;
+    /// checkouts and resets only move HEAD, so they are left out.
+    fn hybrid_commits(&self, repo: &git2::Repository, timestamp: i64) -> Result<Vec<Commit>> {
+        let mut commits = self.history_commits(repo, timestamp)?;
+        let reflog = self.reflog_commits(repo, timestamp)?;
+
+        let in_history: HashSet<String> = commits.iter().filter_map(|c| c.id.clone()).collect();
+        for mut event in reflog {
+            let Some(action) = event.reflog_action().map(str::to_string) else {
+                continue;
+            };
+            let Some(id) = event.id.clone().filter(|id| in_history.contains(id)) else {
+                event.reflog_only = true;
+                commits.push(event);
+                continue;
+            };
+
+            if !matches!(action.as_str(), "commit" | "commit (initial)") {
+                if let Some(commit) = commits
+                    .iter_mut()
+                    .find(|commit| commit.id.as_deref() == Some(id.as_str()))
+                {
+                    commit.message = format!("{}: {}", action, commit.subject());
+                }
+            }
+        }
+
+        commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp()));
+        Ok(commits)
+    }
+
+    /// Commits reachable from HEAD and committed since `timestamp`, newest first
+    ///
+    /// Messages get the `commit: ` prefix the reflog would have given them.
+    fn history_commits(&self, repo: &git2::Repository, timestamp: i64) -> Result<Vec<Commit>> {
+        let mut revwalk = repo.revwalk()?;
+        let branch = match repo.head() {
+            Ok(_) => {
+                revwalk.push_head()?;
+                head_branch(repo)
+            }
+            Err(e) => {
+                let branches = push_local_branches(repo, &mut revwalk)?;
+                if branches == 0 {
+                    // Nothing has been committed yet
+                    return Ok(Vec::new());
+                }
+                eprintln!(
+                    "⚠️  {}, so commits were read from all {} local branch(es)",
+                    describe_broken_head(repo, &e),
+                    branches
+                );
+                None
+            }
+        };
+        revwalk.set_sorting(git2::Sort::TIME)?;
+
+        let mut commits = Vec::new();
+        for oid in revwalk {
+            let target = repo.find_commit(oid?)?;
+            let time = target.committer().when().seconds();
+            if time < timestamp {
+                continue;
+            }
+
+            let subject = target.message_bytes().split(|&b| b == b'\n').next();
+            let decoded = decode_message(subject.unwrap_or_default(), target.message_encoding());
+            if let Some(warning) = decoded.warning {
+                eprintln!("⚠️  Commit {}: {}", target.id(), warning);
+            }
+            let mut commit = Commit::new(format!("commit: {}", decoded.text.trim_end()), time)
+                .with_id(target.id().to_string());
+            commit.branch = branch.clone();
+            commit.author = Some(author_of(&target));
+            commit.author_time = Some(target.author().when().seconds());
+            commit.author_offset = Some(target.author().when().offset_minutes());
+            commit.body = body_of(&target);
+            if target.parent_count() > 0 {
+                commit = self.with_changes(repo, &target, commit)?;
+            }
+            commits.push(commit);
+        }
+
+        Ok(commits)
+    }
+}
+
+/// Reads `user.name` and `user.email` from the repository's git config
+///
+/// Falls back to the global config when `repo_path` is not a repository, so
+/// `--mine` also works with exported history.
+pub fn configured_identity(repo_path: &str) -> Result<AuthorFilter> {
+    let config = match git2::Repository::open(repo_path) {
+        Ok(repo) => repo.config()?,
+        Err(_) => git2::Config::open_default()?,
+    };
+
+    Ok(AuthorFilter {
+        name: config.get_string("user.name").ok(),
+        email: config.get_string("user.email").ok(),
+    })
+}
+
+/// Returns the URL of remote `name`, if the repository has one
+pub fn remote_url(repo_path: &str, name: &str) -> Result<Option<String>> {
+    let repo =
+        git2::Repository::open(repo_path).map_err(|e| explain_git_error(e.into(), repo_path))?;
+    let url = match repo.find_remote(name) {
+        Ok(remote) => remote.url().map(str::to_string),
+        Err(_) => None,
+    };
+
+    Ok(url)
+}
+
+/// Returns the id and contents of the blob at `path` in commit `commit_id`,
+/// or `None` when the commit has no such file
+pub fn read_blob(
+    repo: &git2::Repository,
+    commit_id: &str,
+    path: &str,
+) -> Result<Option<(String, Vec<u8>)>> {
+    let tree = repo.find_commit(git2::Oid::from_str(commit_id)?)?.tree()?;
+    let entry = match tree.get_path(Path::new(path)) {
+        Ok(entry) => entry,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let Ok(blob) = entry.to_object(repo)?.into_blob() else {
+        return Ok(None);
+    };
+
+    Ok(Some((blob.id().to_string(), blob.content().to_vec())))
+}
+
+/// Copies the images commits mention out of the object database into the
+/// `attachments` folder of the diary directory
+pub struct ImageAttachments {
+    repo_path: String,
+    diary_dir: PathBuf,
+}
+
+impl ImageAttachments {
+    pub fn new(repo_path: String, diary_dir: PathBuf) -> Self {
+        Self {
+            repo_path,
+            diary_dir,
+        }
+    }
+}
+
+impl AttachmentSource for ImageAttachments {
+    /// Images are named after their blob id, so a copy is written only once;
+    /// commits without a hash, and images the commit deleted, are skipped
+    fn attach(&self, commits: &[Commit]) -> Result<Vec<Attachment>> {
+        let repo = git2::Repository::open(&self.repo_path)
+            .map_err(|e| explain_git_error(e.into(), &self.repo_path))?;
+
+        let mut attachments: Vec<Attachment> = Vec::new();
+        for commit in commits {
+            let Some(id) = commit.id.as_deref() else {
+                continue;
+            };
+            for path in referenced_images(commit) {
+                let Some((blob_id, content)) = read_blob(&repo, id, path)? else {
+                    continue;
+                };
+                let attachment = Attachment::new(path.to_string(), &blob_id);
+                if attachments.contains(&attachment) {
+                    continue;
+                }
+                let copy = self.diary_dir.join(&attachment.file);
+                if !copy.exists() {
+                    if let Some(dir) = copy.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    fs::write(&copy, content)?;
+                }
+                attachments.push(attachment);
+            }
+        }
+
+        Ok(attachments)
+    }
+}
+
+/// Returns the id of the commit HEAD points to, or `None` before the first commit
+pub fn head_id(repo_path: &str) -> Result<Option<String>> {
+    let repo =
+        git2::Repository::open(repo_path).map_err(|e| explain_git_error(e.into(), repo_path))?;
+    let id = match repo.head() {
+        Ok(head) => head.target().map(|id| id.to_string()),
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(id)
+}
+
+/// Where `path` sits in the work tree of the repository at `repo_path`, if it does
+///
+/// `path` must exist, as both sides are resolved through symlinks first.
+/// Paths inside the git directory are not part of the work tree.
+pub fn path_in_work_tree(repo_path: &str, path: &Path) -> Result<Option<PathBuf>> {
+    let repo =
+        git2::Repository::open(repo_path).map_err(|e| explain_git_error(e.into(), repo_path))?;
+    let Some(workdir) = repo.workdir() else {
+        return Ok(None);
+    };
+
+    let workdir = workdir.canonicalize()?;
+    let path = path.canonicalize()?;
+    if path.starts_with(repo.path().canonicalize()?) {
+        return Ok(None);
+    }
+    Ok(path.strip_prefix(&workdir).ok().map(Path::to_path_buf))
+}
+
+/// The git directory of the repository at `repo_path`, usually its `.git`;
+/// none when `repo_path` is not a repository
+pub fn git_dir(repo_path: &str) -> Option<PathBuf> {
+    git2::Repository::open(repo_path)
+        .ok()
+        .map(|repo| repo.path().to_path_buf())
+}
+
+/// Whether git ignores `path`, given relative to the work tree
+pub fn is_ignored(repo_path: &str, path: &Path) -> Result<bool> {
+    let repo =
+        git2::Repository::open(repo_path).map_err(|e| explain_git_error(e.into(), repo_path))?;
+    Ok(repo.is_path_ignored(path)?)
+}
+
+/// Appends the directory `dir`, relative to the work tree, to the repository's `.gitignore`
+pub fn ignore_directory(repo_path: &str, dir: &Path) -> Result<()> {
+    let gitignore = Path::new(repo_path).join(".gitignore");
+    let mut contents = match fs::read_to_string(&gitignore) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!("/{}/\n", dir.to_string_lossy().replace('\\', "/")));
+    fs::write(&gitignore, contents)?;
+    Ok(())
+}
+
+/// Commits `files`, given relative to the work tree, on top of HEAD
+///
+/// Only these files go into the commit; anything else the user has staged
+/// stays staged and uncommitted. Returns the short hash of the new commit.
+pub fn commit_files(repo_path: &str, files: &[PathBuf], message: &str) -> Result<String> {
+    let repo =
+        git2::Repository::open(repo_path).map_err(|e| explain_git_error(e.into(), repo_path))?;
+    let signature = repo.signature()?;
+    let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+
+    // Build the tree from HEAD plus the files, leaving the user's index out of it
+    let mut index = git2::Index::new()?;
+    if let Some(parent) = &parent {
+        index.read_tree(&parent.tree()?)?;
+    }
+    let mut staged = repo.index()?;
+    for file in files {
+        let contents = fs::read(Path::new(repo_path).join(file))?;
+        let path = file.to_string_lossy().replace('\\', "/");
+        let entry = git2::IndexEntry {
+            ctime: git2::IndexTime::new(0, 0),
+            mtime: git2::IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: contents.len() as u32,
+            id: repo.blob(&contents)?,
+            flags: path.len().min(0xfff) as u16,
+            flags_extended: 0,
+            path: path.into_bytes(),
+        };
+        index.add(&entry)?;
+        // Stage the same content, so the new commit doesn't show as a staged deletion
+        staged.add_path(file)?;
+    }
+    let tree = repo.find_tree(index.write_tree_to(&repo)?)?;
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    let id = repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    staged.write()?;
+
+    Ok(id.to_string()[..7].to_string())
+}
+
+/// Puts a readable explanation and a suggested fix in front of common libgit2 failures
+///
+/// The original `git2::Error` stays in the chain as the cause, so `{:#}` and
+/// `--verbose` style output still show what libgit2 reported. Errors that are
+/// not from libgit2, or that are not recognized, are returned unchanged.
+pub fn explain_git_error(error: anyhow::Error, repo_path: &str) -> anyhow::Error {
+    let Some(git_error) = error.downcast_ref::<git2::Error>() else {
+        return error;
+    };
+
+    let explanation = match (git_error.code(), git_error.class()) {
+        (git2::ErrorCode::NotFound, git2::ErrorClass::Repository) => format!(
+            "'{}' is not a git repository. Run git-diary from inside a repository, or use --from-export for exported history",
+            repo_path
+        ),
+        (git2::ErrorCode::NotFound, git2::ErrorClass::Os) => format!(
+            "'{}' does not exist. Run git-diary from inside a repository, or use --from-export for exported history",
+            repo_path
+        ),
+        (git2::ErrorCode::Locked, _) => format!(
+            "The repository at '{}' is locked by another git process. Wait for it to finish; if none is running, delete the stale .git/index.lock",
+            repo_path
+        ),
+        (git2::ErrorCode::UnbornBranch, _) => format!(
+            "HEAD in '{}' points to a branch with no commits yet. Make a first commit, or check out an existing branch",
+            repo_path
+        ),
+        (_, git2::ErrorClass::Odb | git2::ErrorClass::Object | git2::ErrorClass::Zlib) => {
+            format!(
+                "The object database of '{}' is damaged or missing objects. Run `git fsck` to find the problem, and `git fetch` or re-clone to restore missing objects",
+                repo_path
+            )
+        }
+        _ => return error,
+    };
+
+    error.context(explanation)
+}
+
+/// Short name of the branch HEAD is on; `None` for a detached HEAD
+fn head_branch(repo: &git2::Repository) -> Option<String> {
+    let head = repo.find_reference("HEAD").ok()?;
+    head.symbolic_target()?
+        .strip_prefix("refs/heads/")
+        .map(str::to_string)
+}
+
+/// Adds the tip of every local branch to `revwalk`, returning how many there were
+fn push_local_branches(repo: &git2::Repository, revwalk: &mut git2::Revwalk) -> Result<usize> {
+    let mut pushed = 0;
+    for reference in repo.references_glob("refs/heads/*")? {
+        // Symbolic refs under refs/heads point at other branches, which are pushed anyway
+        if let Some(id) = reference?.target() {
+            revwalk.push(id)?;
+            pushed += 1;
+        }
+    }
+    Ok(pushed)
+}
+
+/// Why HEAD doesn't lead to a commit, for the warning before walking all branches
+fn describe_broken_head(repo: &git2::Repository, error: &git2::Error) -> String {
+    let target = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_string));
+    match target {
+        Some(target) => format!("HEAD points to {}, which has no commits", target),
+        None => format!("HEAD can't be resolved ({})", error.message()),
+    }
+}
+
+/// The branch a `checkout: moving from A to B` reflog entry left, `Some(None)`
+/// when that was a detached HEAD, and `None` for entries that aren't checkouts
+fn checked_out_from(message: &str) -> Option<Option<String>> {
+    let (from, _) = message
+        .strip_prefix("checkout: moving from ")?
+        .split_once(" to ")?;
+    let detached = from.len() == 40 && from.chars().all(|c| c.is_ascii_hexdigit());
+
+    Some((!detached).then(|| from.to_string()))
+}
+
+/// Decodes the message lines after the subject, if there are any
+pub(crate) fn body_of(commit: &git2::Commit) -> Option<String> {
+    let body = decode_message(commit.body_bytes()?, commit.message_encoding()).text;
+    let body = body.trim();
+
+    (!body.is_empty()).then(|| body.to_string())
+}
+
+fn author_of(commit: &git2::Commit) -> Author {
+    let author = commit.author();
+    Author::new(
+        String::from_utf8_lossy(author.name_bytes()).to_string(),
+        String::from_utf8_lossy(author.email_bytes()).to_string(),
+    )
+}
+
+#[async_trait::async_trait]
+impl GitRepository for GitRepositoryImpl {
+    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
+        let repo = git2::Repository::open(&self.repo_path)
+            .map_err(|e| explain_git_error(e.into(), &self.repo_path))?;
+
+        match self.source {
+            CommitSource::Reflog => {}
+            CommitSource::Revwalk => {
+                return self
+                    .history_commits(&repo, timestamp)
+                    .map_err(|e| explain_git_error(e, &self.repo_path));
+            }
+            CommitSource::Hybrid => {
+                return self
+                    .hybrid_commits(&repo, timestamp)
+                    .map_err(|e| explain_git_error(e, &self.repo_path));
             }
-            commits.push(Commit::new(
-                reflog.message().unwrap_or("No message").to_string(),
-                reflog.committer().when().seconds(),
-            ));
+        }
+
+        let commits = self
+            .reflog_commits(&repo, timestamp)
+            .map_err(|e| explain_git_error(e, &self.repo_path))?;
+        if !commits.is_empty() {
+            return Ok(commits);
+        }
+
+        // A fresh clone has no reflog history yet, so read the commits themselves
+        let commits = self
+            .history_commits(&repo, timestamp)
+            .map_err(|e| explain_git_error(e, &self.repo_path))?;
+        if !commits.is_empty() {
+            eprintln!(
+                "ℹ️  The reflog has no entries for this period, so {} commit(s) were read from history instead",
+                commits.len()
+            );
         }
 
         Ok(commits)
     }
 }
 
+impl HealthSource for GitRepositoryImpl {
+    /// Diffs each commit against its first parent; commits without a hash,
+    /// such as grouped dependency updates, are skipped
+    fn repo_health(&self, commits: &[Commit]) -> Result<RepoHealth> {
+        let repo = git2::Repository::open(&self.repo_path)
+            .map_err(|e| explain_git_error(e.into(), &self.repo_path))?;
+
+        let mut health = RepoHealth::default();
+        let mut seen = HashSet::new();
+        for id in commits.iter().filter_map(|commit| commit.id.as_deref()) {
+            if !seen.insert(id) {
+                continue;
+            }
+
+            let commit = repo.find_commit(git2::Oid::from_str(id)?)?;
+            let parent_tree = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+            let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+            for delta in diff.deltas() {
+                let change = match delta.status() {
+                    git2::Delta::Added => FileChange::Added,
+                    git2::Delta::Deleted => FileChange::Deleted,
+                    _ => FileChange::Modified,
+                };
+                let file = match change {
+                    FileChange::Deleted => delta.old_file(),
+                    _ => delta.new_file(),
+                };
+                if let Some(path) = file.path() {
+                    health.add_file(&path.to_string_lossy(), change);
+                }
+            }
+            diff.print(git2::DiffFormat::Patch, |_, _, line| {
+                match line.origin() {
+                    '+' => health.add_line(&String::from_utf8_lossy(line.content()), true),
+                    '-' => health.add_line(&String::from_utf8_lossy(line.content()), false),
+                    _ => {}
+                }
+                true
+            })?;
+        }
+
+        Ok(health)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,24 +710,24 @@ mod tests {
         // Create a temporary directory for the test repository
         let temp_dir = TempDir::new()?;
         let repo_path = temp_dir.path().to_string_lossy().to_string();
-        
+
         // Initialize git repository
         let repo = git2::Repository::init(&repo_path)?;
         let signature = git2::Signature::now("Test User", "test@example.com")?;
-        
+
         // Create a test file
         let test_file_path = Path::new(&repo_path).join("test.txt");
         let mut file = fs::File::create(&test_file_path)?;
         writeln!(file, "Test content")?;
-        
+
         // Add and commit the file
         let mut index = repo.index()?;
         index.add_path(Path::new("test.txt"))?;
         index.write()?;
-        
+
         let tree_id = index.write_tree()?;
         let tree = repo.find_tree(tree_id)?;
-        
+
         repo.commit(
             Some("HEAD"),
             &signature,
@@ -77,7 +736,7 @@ mod tests {
             &tree,
             &[],
         )?;
-        
+
         Ok((temp_dir, repo_path))
     }
 
@@ -85,49 +744,606 @@ mod tests {
     fn test_get_commits_since() -> Result<()> {
         // Setup test repository
         let (_temp_dir, repo_path) = setup_test_repo()?;
-        
+
         // Create GitRepositoryImpl instance
         let git_repo = GitRepositoryImpl::new(repo_path);
-        
+
         // Get commits
         let timestamp = Local::now().timestamp() - 3600; // 1 hour ago
         let commits = git_repo.get_commits_since(timestamp)?;
-        
+
         // Verify we got the test commit
         assert!(!commits.is_empty());
         assert!(commits[0].message.contains("Test commit"));
-        
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commits_are_attributed_to_branches() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let main_branch = head_branch(&repo).unwrap();
+        let commit_on_head = |message: &str| -> Result<()> {
+            let parent = repo.head()?.peel_to_commit()?;
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &parent.tree()?,
+                &[&parent],
+            )?;
+            Ok(())
+        };
+
+        repo.branch("feature/sync", &repo.head()?.peel_to_commit()?, false)?;
+        repo.set_head("refs/heads/feature/sync")?;
+        commit_on_head("Add sync")?;
+        repo.set_head(&format!("refs/heads/{}", main_branch))?;
+        commit_on_head("Fix typo")?;
+
+        let commits =
+            GitRepositoryImpl::new(repo_path).get_commits_since(Local::now().timestamp() - 3600)?;
+        let branches: Vec<(&str, Option<&str>)> = commits
+            .iter()
+            .filter(|commit| {
+                commit
+                    .reflog_action()
+                    .is_some_and(|a| a.starts_with("commit"))
+            })
+            .map(|commit| (commit.subject(), commit.branch.as_deref()))
+            .collect();
+
+        assert_eq!(
+            branches,
+            vec![
+                ("Fix typo", Some(main_branch.as_str())),
+                ("Add sync", Some("feature/sync")),
+                ("Test commit", Some(main_branch.as_str())),
+            ]
+        );
+        assert_eq!(
+            checked_out_from(&format!("checkout: moving from {} to main", "a".repeat(40))),
+            Some(None)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_sources() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let parent = repo.head()?.peel_to_commit()?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add parser",
+            &parent.tree()?,
+            &[&parent],
+        )?;
+        // Amend the way git does, so the reflog records `commit (amend)`
+        let amended = repo.commit(
+            None,
+            &signature,
+            &signature,
+            "Add parser with tests",
+            &parent.tree()?,
+            &[&parent],
+        )?;
+        repo.head()?
+            .resolve()?
+            .set_target(amended, "commit (amend): Add parser with tests")?;
+        let since = Local::now().timestamp() - 3600;
+        let read = |source: CommitSource| -> Result<Vec<String>> {
+            Ok(GitRepositoryImpl::new(repo_path.clone())
+                .with_source(source)
+                .get_commits_since(since)?
+                .iter()
+                .map(|commit| {
+                    format!(
+                        "{}{}",
+                        commit.message,
+                        if commit.reflog_only { " [reflog]" } else { "" }
+                    )
+                })
+                .collect())
+        };
+
+        assert_eq!(
+            read(CommitSource::Revwalk)?,
+            vec!["commit: Add parser with tests", "commit: Test commit"]
+        );
+        assert_eq!(
+            read(CommitSource::Reflog)?,
+            vec![
+                "commit (amend): Add parser with tests",
+                "commit: Add parser",
+                "commit (initial): Test commit"
+            ]
+        );
+        // All three share a second, so only the entries are compared
+        let mut hybrid = read(CommitSource::Hybrid)?;
+        hybrid.sort();
+        assert_eq!(
+            hybrid,
+            vec![
+                "commit (amend): Add parser with tests",
+                "commit: Add parser [reflog]",
+                "commit: Test commit"
+            ]
+        );
+
         Ok(())
     }
-    
+
     #[test]
     fn test_get_commits_since_future_timestamp() -> Result<()> {
         // Setup test repository
         let (_temp_dir, repo_path) = setup_test_repo()?;
-        
+
         // Create GitRepositoryImpl instance
         let git_repo = GitRepositoryImpl::new(repo_path);
-        
+
         // Get commits with a future timestamp
         let timestamp = Local::now().timestamp() + 3600; // 1 hour in the future
         let commits = git_repo.get_commits_since(timestamp)?;
-        
+
         // Verify we got no commits
         assert!(commits.is_empty());
-        
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_commits_since_decodes_legacy_encoding() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let head = repo.head()?.peel_to_commit()?;
+
+        // git2 can't write an encoding header, so build the commit object by hand
+        let buffer = repo.commit_create_buffer(
+            &head.author(),
+            &head.committer(),
+            "Fix caf? bug",
+            &head.tree()?,
+            &[&head],
+        )?;
+        let buffer =
+            String::from_utf8(buffer.to_vec())?.replacen("\n\n", "\nencoding ISO-8859-1\n\n", 1);
+        let oid = repo
+            .odb()?
+            .write(git2::ObjectType::Commit, buffer.as_bytes())?;
+
+        // Reflog entries hold the subject bytes in the commit's encoding
+        let mut entry = format!(
+            "{} {} Test User <test@example.com> {} +0000\tcommit: Fix caf",
+            head.id(),
+            oid,
+            Local::now().timestamp()
+        )
+        .into_bytes();
+        entry.extend_from_slice(b"\xe9 bug\n");
+        fs::OpenOptions::new()
+            .append(true)
+            .open(Path::new(&repo_path).join(".git/logs/HEAD"))?
+            .write_all(&entry)?;
+
+        let git_repo = GitRepositoryImpl::new(repo_path);
+        let commits = git_repo.get_commits_since(0)?;
+
+        assert_eq!(commits[0].message, "commit: Fix café bug");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commits_carry_author_and_identity_is_read() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Test User")?;
+        config.set_str("user.email", "test@example.com")?;
+
+        let git_repo = GitRepositoryImpl::new(repo_path.clone());
+        let commits = git_repo.get_commits_since(0)?;
+        let identity = configured_identity(&repo_path)?;
+
+        assert_eq!(identity.email.as_deref(), Some("test@example.com"));
+        assert!(identity.matches(commits[0].author.as_ref().unwrap()));
+
+        assert_eq!(remote_url(&repo_path, "origin")?, None);
+        repo.remote("origin", "git@github.com:jane/git-diary.git")?;
+        assert_eq!(
+            remote_url(&repo_path, "origin")?.as_deref(),
+            Some("git@github.com:jane/git-diary.git")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_commits_since_detects_renames() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let head = repo.head()?.peel_to_commit()?;
+
+        fs::create_dir(Path::new(&repo_path).join("docs"))?;
+        fs::rename(
+            Path::new(&repo_path).join("test.txt"),
+            Path::new(&repo_path).join("docs/test.txt"),
+        )?;
+        let mut index = repo.index()?;
+        index.remove_path(Path::new("test.txt"))?;
+        index.add_path(Path::new("docs/test.txt"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Move test file\n\nKeeps the docs together.\n",
+            &tree,
+            &[&head],
+        )?;
+
+        let git_repo = GitRepositoryImpl::new(repo_path).with_rename_similarity(Some(90));
+        let commits = git_repo.get_commits_since(0)?;
+
+        let moved = commits
+            .iter()
+            .find(|commit| commit.message.contains("Move test file"))
+            .unwrap();
+        assert_eq!(
+            moved.renames,
+            vec![FileRename::new(
+                "test.txt".to_string(),
+                "docs/test.txt".to_string()
+            )]
+        );
+        assert_eq!(moved.files, vec!["docs/test.txt".to_string()]);
+        assert_eq!(moved.body.as_deref(), Some("Keeps the docs together."));
+        // A pure rename changes no lines
+        assert_eq!(moved.line_stats, Some(LineStats::new(0, 0)));
+        assert_eq!(
+            moved.id,
+            Some(repo.head()?.peel_to_commit()?.id().to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_commits_since_keeps_diffs() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let head = repo.head()?.peel_to_commit()?;
+
+        fs::write(Path::new(&repo_path).join("test.txt"), "Updated content\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("test.txt"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Update test file",
+            &tree,
+            &[&head],
+        )?;
+
+        let commits = GitRepositoryImpl::new(repo_path.clone()).get_commits_since(0)?;
+        assert!(commits.iter().all(|commit| commit.diff.is_none()));
+
+        let commits = GitRepositoryImpl::new(repo_path)
+            .with_diffs(true)
+            .get_commits_since(0)?;
+        let diff = commits
+            .iter()
+            .find(|commit| commit.message.contains("Update test file"))
+            .and_then(|commit| commit.diff.as_deref())
+            .unwrap();
+        assert!(diff.contains("+++ b/test.txt\n"));
+        assert!(diff.contains("\n-Test content\n+Updated content\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repo_health() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let head = repo.head()?.peel_to_commit()?;
+
+        fs::create_dir(Path::new(&repo_path).join("tests"))?;
+        fs::write(
+            Path::new(&repo_path).join("tests/sync.rs"),
+            "// TODO: cover timeouts\n",
+        )?;
+        fs::write(Path::new(&repo_path).join("Cargo.toml"), "[package]\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("tests/sync.rs"))?;
+        index.add_path(Path::new("Cargo.toml"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add sync tests",
+            &tree,
+            &[&head],
+        )?;
+
+        let git_repo = GitRepositoryImpl::new(repo_path);
+        let commits = git_repo.get_commits_since(0)?;
+        let health = git_repo.repo_health(&commits)?;
+
+        assert_eq!(health.todos_added, 1);
+        assert_eq!(health.todos_removed, 0);
+        assert_eq!(health.test_files_added, 1);
+        assert_eq!(
+            health.build_files_changed.into_iter().collect::<Vec<_>>(),
+            vec!["Cargo.toml"]
+        );
+
         Ok(())
     }
-    
+
     #[test]
     fn test_invalid_repository_path() {
         // Create GitRepositoryImpl with invalid path
         let git_repo = GitRepositoryImpl::new("/path/that/does/not/exist".to_string());
-        
+
         // Attempt to get commits
         let result = git_repo.get_commits_since(0);
-        
-        // Verify operation failed
-        assert!(result.is_err());
+
+        // Verify operation failed with an explanation
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "'/path/that/does/not/exist' does not exist. Run git-diary from inside a repository, or use --from-export for exported history"
+        );
     }
-}
 
+    #[test]
+    fn test_explain_git_error() {
+        let explain = |code, class| {
+            let error = git2::Error::new(code, class, "raw libgit2 message");
+            format!("{:#}", explain_git_error(error.into(), "/work/app"))
+        };
+
+        let not_a_repository = explain(git2::ErrorCode::NotFound, git2::ErrorClass::Repository);
+        assert!(not_a_repository.starts_with("'/work/app' is not a git repository"));
+        // The raw error stays in the chain
+        assert!(not_a_repository.contains(": raw libgit2 message"));
+
+        assert!(explain(git2::ErrorCode::Locked, git2::ErrorClass::Index)
+            .contains("delete the stale .git/index.lock"));
+        assert!(
+            explain(git2::ErrorCode::UnbornBranch, git2::ErrorClass::Reference)
+                .contains("no commits yet")
+        );
+        assert!(
+            explain(git2::ErrorCode::GenericError, git2::ErrorClass::Odb)
+                .contains("Run `git fsck`")
+        );
+        assert!(
+            explain(git2::ErrorCode::NotFound, git2::ErrorClass::Zlib).contains("Run `git fsck`")
+        );
+
+        // Unrecognized failures and other errors pass through untouched
+        assert!(explain(git2::ErrorCode::Auth, git2::ErrorClass::Http)
+            .starts_with("raw libgit2 message"));
+        assert_eq!(
+            format!(
+                "{:#}",
+                explain_git_error(anyhow::anyhow!("not git"), "/work/app")
+            ),
+            "not git"
+        );
+    }
+
+    #[test]
+    fn test_get_commits_since_falls_back_to_history_in_fresh_clone() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let clone_dir = TempDir::new()?;
+        git2::Repository::clone(&repo_path, clone_dir.path())?;
+
+        let git_repo = GitRepositoryImpl::new(clone_dir.path().to_string_lossy().to_string());
+        let commits = git_repo.get_commits_since(0)?;
+
+        // The clone's only reflog entry is the clone itself
+        assert_eq!(commits.len(), 1);
+        assert_eq!(commits[0].message, "commit: Test commit");
+        assert_eq!(
+            commits[0].author.as_ref().unwrap().email,
+            "test@example.com"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_head_pointing_at_a_missing_branch_walks_all_branches() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        let head = repo.head()?.peel_to_commit()?;
+        repo.branch("feature", &head, false)?;
+        let tree = head.tree()?;
+        repo.commit(
+            Some("refs/heads/feature"),
+            &signature,
+            &signature,
+            "Work on feature",
+            &tree,
+            &[&head],
+        )?;
+        // As after the default branch was renamed on another machine
+        let default_branch = head_branch(&repo).unwrap();
+        repo.find_branch(&default_branch, git2::BranchType::Local)?
+            .rename("trunk", false)?;
+        repo.set_head(&format!("refs/heads/{}", default_branch))?;
+        assert!(repo.head().is_err());
+
+        let commits = GitRepositoryImpl::new(repo_path.clone())
+            .with_source(CommitSource::Revwalk)
+            .get_commits_since(0)?;
+
+        let messages: Vec<&str> = commits.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages.contains(&"commit: Work on feature"));
+        assert!(messages.contains(&"commit: Test commit"));
+        assert!(commits.iter().all(|commit| commit.branch.is_none()));
+        assert_eq!(head_id(&repo_path)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unborn_head_has_no_commits() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        git2::Repository::init(temp_dir.path())?;
+        let repo_path = temp_dir.path().to_string_lossy().to_string();
+
+        for source in [
+            CommitSource::Reflog,
+            CommitSource::Revwalk,
+            CommitSource::Hybrid,
+        ] {
+            let commits = GitRepositoryImpl::new(repo_path.clone())
+                .with_source(source)
+                .get_commits_since(0)?;
+            assert!(commits.is_empty());
+        }
+        assert_eq!(head_id(&repo_path)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_attachments() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let signature = git2::Signature::now("Test User", "test@example.com")?;
+        fs::create_dir(Path::new(&repo_path).join("docs"))?;
+        fs::write(Path::new(&repo_path).join("docs/flow.png"), b"\x89PNG flow")?;
+        fs::write(
+            Path::new(&repo_path).join("docs/other.png"),
+            b"\x89PNG other",
+        )?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("docs/flow.png"))?;
+        index.add_path(Path::new("docs/other.png"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let parent = repo.head()?.peel_to_commit()?;
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Add flow.png diagram",
+            &tree,
+            &[&parent],
+        )?;
+
+        let commits = GitRepositoryImpl::new(repo_path.clone()).get_commits_since(0)?;
+        let diary_dir = TempDir::new()?;
+        let attachments =
+            ImageAttachments::new(repo_path, diary_dir.path().to_path_buf()).attach(&commits)?;
+
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].path, "docs/flow.png");
+        assert!(attachments[0].file.starts_with("attachments/"));
+        assert_eq!(
+            fs::read(diary_dir.path().join(&attachments[0].file))?,
+            b"\x89PNG flow"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_directory() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let diaries = Path::new(&repo_path).join("diaries");
+        fs::create_dir(&diaries)?;
+        fs::write(Path::new(&repo_path).join(".gitignore"), "target")?;
+
+        let relative = path_in_work_tree(&repo_path, &diaries)?.unwrap();
+        assert_eq!(relative, Path::new("diaries"));
+        assert!(!is_ignored(&repo_path, &relative)?);
+
+        ignore_directory(&repo_path, &relative)?;
+        assert_eq!(
+            fs::read_to_string(Path::new(&repo_path).join(".gitignore"))?,
+            "target\n/diaries/\n"
+        );
+        assert!(is_ignored(&repo_path, &relative)?);
+
+        let outside = TempDir::new()?;
+        assert_eq!(path_in_work_tree(&repo_path, outside.path())?, None);
+
+        // Diaries kept inside .git never show up in the work tree
+        let git_diaries = git_dir(&repo_path).unwrap().join("diary");
+        fs::create_dir(&git_diaries)?;
+        assert_eq!(path_in_work_tree(&repo_path, &git_diaries)?, None);
+        assert_eq!(git_dir(outside.path().to_str().unwrap()), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_files() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo()?;
+        let repo = git2::Repository::open(&repo_path)?;
+        let mut config = repo.config()?;
+        config.set_str("user.name", "Test User")?;
+        config.set_str("user.email", "test@example.com")?;
+
+        // Staged work of the user's own stays out of the diary commit
+        fs::write(Path::new(&repo_path).join("wip.txt"), "half done")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("wip.txt"))?;
+        index.write()?;
+        fs::create_dir(Path::new(&repo_path).join("diaries"))?;
+        fs::write(
+            Path::new(&repo_path).join("diaries/2024-05-06.md"),
+            "# Diary",
+        )?;
+
+        let hash = commit_files(
+            &repo_path,
+            &[PathBuf::from("diaries/2024-05-06.md")],
+            "Add diary",
+        )?;
+
+        let head = repo.head()?.peel_to_commit()?;
+        assert!(head.id().to_string().starts_with(&hash));
+        assert_eq!(head.message(), Some("Add diary"));
+        assert_eq!(head.parent_count(), 1);
+        assert_eq!(head_id(&repo_path)?, Some(head.id().to_string()));
+        let tree = head.tree()?;
+        assert!(tree.get_path(Path::new("diaries/2024-05-06.md")).is_ok());
+        assert!(tree.get_path(Path::new("test.txt")).is_ok());
+        assert!(tree.get_path(Path::new("wip.txt")).is_err());
+
+        let statuses = repo.statuses(None)?;
+        let changed: Vec<String> = statuses
+            .iter()
+            .filter_map(|s| s.path().map(str::to_string))
+            .collect();
+        assert_eq!(changed, vec!["wip.txt"]);
+
+        Ok(())
+    }
+}
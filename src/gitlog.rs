@@ -0,0 +1,194 @@
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+
+use crate::domain::{Author, Commit};
+
+/// Date layouts `git log` prints with the default, `--date=iso`,
+/// `--date=iso-strict` and `--date=rfc` options
+const DATE_FORMATS: &[&str] = &[
+    "%a %b %e %H:%M:%S %Y %z",
+    "%Y-%m-%d %H:%M:%S %z",
+    "%Y-%m-%dT%H:%M:%S%:z",
+    "%a, %e %b %Y %H:%M:%S %z",
+];
+
+/// Parses text pasted from `git log`, returning commits newest first
+///
+/// Expects the default `medium` layout (or `fuller`, which adds commit
+/// dates): a `commit <hash>` line, headers, a blank line and the message
+/// indented by four spaces. Commit headers take precedence over author
+/// dates, matching what the reflog-based reader records.
+pub fn parse_git_log(text: &str) -> Result<Vec<Commit>> {
+    let mut commits = Vec::new();
+    let mut current: Option<PendingCommit> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        if let Some(hash) = line.strip_prefix("commit ") {
+            if let Some(pending) = current.take() {
+                commits.push(pending.finish()?);
+            }
+            current = Some(PendingCommit::new(hash));
+            continue;
+        }
+
+        let Some(pending) = current.as_mut() else {
+            if line.trim().is_empty() {
+                continue;
+            }
+            bail!(
+                "Line {}: expected a \"commit <hash>\" line, found {:?}",
+                index + 1,
+                line
+            );
+        };
+
+        if let Some(message_line) = line.strip_prefix("    ") {
+            pending.message.push(message_line.to_string());
+        } else if line.trim().is_empty() {
+            // Editors often strip the indentation of blank message lines
+            pending.message.push(String::new());
+        } else if let Some(author) = line.strip_prefix("Author:") {
+            pending.author = Author::parse_ident(author);
+        } else if let Some(date) = line.strip_prefix("CommitDate:") {
+            pending.commit_date = Some(parse_date(date).with_context(|| {
+                format!(
+                    "Line {}: invalid date in commit {}",
+                    index + 1,
+                    pending.hash
+                )
+            })?);
+        } else if let Some(date) = line
+            .strip_prefix("Date:")
+            .or(line.strip_prefix("AuthorDate:"))
+        {
+            pending.author_date = Some(parse_date(date).with_context(|| {
+                format!(
+                    "Line {}: invalid date in commit {}",
+                    index + 1,
+                    pending.hash
+                )
+            })?);
+        }
+    }
+
+    if let Some(pending) = current {
+        commits.push(pending.finish()?);
+    }
+
+    Ok(commits)
+}
+
+fn parse_date(raw: &str) -> Result<i64> {
+    let raw = raw.trim();
+
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| DateTime::parse_from_str(raw, format).ok())
+        .map(|date| date.timestamp())
+        .with_context(|| format!("unrecognized date {:?}", raw))
+}
+
+/// A commit whose headers and message are still being read
+struct PendingCommit {
+    hash: String,
+    author: Option<Author>,
+    author_date: Option<i64>,
+    commit_date: Option<i64>,
+    message: Vec<String>,
+}
+
+impl PendingCommit {
+    fn new(hash: &str) -> Self {
+        Self {
+            hash: hash
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            author: None,
+            author_date: None,
+            commit_date: None,
+            message: Vec::new(),
+        }
+    }
+
+    fn finish(self) -> Result<Commit> {
+        let Some(time) = self.commit_date.or(self.author_date) else {
+            bail!("Commit {} has no date", self.hash);
+        };
+
+        let mut commit = Commit::new(self.message.join("\n").trim().to_string(), time);
+        commit.author = self.author;
+        Ok(commit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEDIUM_LOG: &str = "commit 8ad16da0f1e2 (HEAD -> main)
+Author: Jane Doe <jane@example.com>
+Date:   Tue Jan 2 10:00:00 2024 +0000
+
+    Add auth subcommand
+
+    Tokens are cached per host.
+
+commit 7171530be5a8
+Merge: 1234567 89abcde
+Author: Jane Doe <jane@example.com>
+Date:   Mon Jan 1 09:00:00 2024 +0100
+
+    Initial commit
+";
+
+    #[test]
+    fn test_parse_git_log() -> Result<()> {
+        let commits = parse_git_log(MEDIUM_LOG)?;
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(
+            commits[0].message,
+            "Add auth subcommand\n\nTokens are cached per host."
+        );
+        assert_eq!(commits[0].timestamp(), 1704189600);
+        assert_eq!(
+            commits[0].author,
+            Some(Author::new(
+                "Jane Doe".to_string(),
+                "jane@example.com".to_string()
+            ))
+        );
+        assert_eq!(commits[1].message, "Initial commit");
+        assert_eq!(commits[1].timestamp(), 1704096000);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_git_log_prefers_commit_date() -> Result<()> {
+        let log = "commit abc
+Author:     Jane Doe <jane@example.com>
+AuthorDate: 2024-01-01 09:00:00 +0000
+Commit:     Jane Doe <jane@example.com>
+CommitDate: 2024-01-03T12:00:00+00:00
+
+    Rebased change
+";
+
+        let commits = parse_git_log(log)?;
+
+        assert_eq!(commits[0].timestamp(), 1704283200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_git_log_rejects_unknown_input() {
+        assert!(parse_git_log("").unwrap().is_empty());
+        assert!(parse_git_log("abc123 Initial commit").is_err());
+        assert!(parse_git_log("commit abc\n\n    No date\n").is_err());
+        assert!(parse_git_log("commit abc\nDate: yesterday\n").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::domain::GlossaryTerm;
+use crate::formatter::SUMMARY_HEADING;
+use crate::parser::parse_diary;
+
+/// Summaries a term must appear in before it is treated as project terminology
+pub const MIN_SIGHTINGS: u32 = 2;
+
+/// Most learned terms put in a prompt, so the glossary stays small
+pub const MAX_LEARNED_TERMS: usize = 20;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LearnedTerms {
+    /// Number of summaries each term appeared in
+    sightings: BTreeMap<String, u32>,
+}
+
+/// Terminology learned from past summaries of one repository
+///
+/// Names the model spelled the same way across several diaries, such as
+/// `SyncEngine` or `billing_service`, are fed back into later prompts so
+/// they stay consistent. Like run state, the file can be recreated, so one
+/// that fails to parse is replaced.
+pub struct GlossaryStore {
+    path: PathBuf,
+}
+
+impl GlossaryStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Counts the terms used in `summary`
+    pub fn learn(&self, summary: &str) -> Result<()> {
+        let mut learned = self.load()?;
+        for term in extract_terms(summary) {
+            *learned.sightings.entry(term).or_default() += 1;
+        }
+        self.save(&learned)
+    }
+
+    /// Learns from the summary of a stored Markdown diary; other formats are skipped
+    pub fn learn_from_diary(&self, path: &Path) -> Result<()> {
+        if path.extension().is_none_or(|ext| ext != "md") {
+            return Ok(());
+        }
+
+        let markdown = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let diary = parse_diary(&markdown)?;
+        match diary.section(SUMMARY_HEADING) {
+            Some(summary) => self.learn(&summary.body),
+            None => Ok(()),
+        }
+    }
+
+    /// Terms seen in at least `MIN_SIGHTINGS` summaries, most frequent first
+    pub fn learned_terms(&self) -> Result<Vec<String>> {
+        let learned = self.load()?;
+        let mut terms: Vec<(String, u32)> = learned
+            .sightings
+            .into_iter()
+            .filter(|(_, count)| *count >= MIN_SIGHTINGS)
+            .collect();
+        terms.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        terms.truncate(MAX_LEARNED_TERMS);
+
+        Ok(terms.into_iter().map(|(term, _)| term).collect())
+    }
+
+    fn load(&self) -> Result<LearnedTerms> {
+        if !self.path.exists() {
+            return Ok(LearnedTerms::default());
+        }
+
+        let raw = fs::read_to_string(&self.path).context("Failed to read glossary file")?;
+        match serde_json::from_str(&raw) {
+            Ok(learned) => Ok(learned),
+            Err(e) => {
+                eprintln!("⚠️ Glossary file was corrupt ({}), starting fresh", e);
+                Ok(LearnedTerms::default())
+            }
+        }
+    }
+
+    fn save(&self, learned: &LearnedTerms) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Failed to create glossary directory")?;
+        }
+
+        let temp_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path).context("Failed to create glossary file")?;
+        file.write_all(serde_json::to_string_pretty(learned)?.as_bytes())
+            .context("Failed to write glossary file")?;
+        file.sync_all().context("Failed to flush glossary file")?;
+
+        fs::rename(&temp_path, &self.path).context("Failed to replace glossary file")?;
+
+        Ok(())
+    }
+}
+
+/// Merges the curated glossary from the config with learned terms
+///
+/// Curated terms come first and keep their descriptions; learned terms that
+/// repeat one of them are dropped.
+pub fn merge_glossary(curated: &BTreeMap<String, String>, learned: &[String]) -> Vec<GlossaryTerm> {
+    let mut glossary: Vec<GlossaryTerm> = curated
+        .iter()
+        .map(|(term, description)| {
+            GlossaryTerm::new(term.clone())
+                .with_description((!description.trim().is_empty()).then(|| description.clone()))
+        })
+        .collect();
+
+    for term in learned {
+        if !glossary
+            .iter()
+            .any(|known| known.term.eq_ignore_ascii_case(term))
+        {
+            glossary.push(GlossaryTerm::new(term.clone()));
+        }
+    }
+
+    glossary
+}
+
+/// Names in a summary that look like project terminology: `backticked`
+/// names plus CamelCase and snake_case identifiers
+pub fn extract_terms(summary: &str) -> BTreeSet<String> {
+    let mut terms = BTreeSet::new();
+
+    for (index, span) in summary.split('`').enumerate() {
+        // Odd pieces sit between backticks
+        if index % 2 == 1 {
+            let span = span.trim();
+            if !span.is_empty() && span.len() <= 40 && span.split_whitespace().count() <= 3 {
+                terms.insert(span.to_string());
+            }
+            continue;
+        }
+
+        for word in span.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+            if is_identifier(word) {
+                terms.insert(word.to_string());
+            }
+        }
+    }
+
+    terms
+}
+
+fn is_identifier(word: &str) -> bool {
+    let chars: Vec<char> = word.chars().collect();
+    let camel_case = chars
+        .windows(2)
+        .any(|pair| pair[0].is_lowercase() && pair[1].is_uppercase());
+    let snake_case = word.trim_matches('_').contains('_');
+
+    chars.len() >= 3 && (camel_case || snake_case)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_terms() {
+        let terms = extract_terms(
+            "Reworked the SyncEngine retry loop and renamed `billing service` in billing_service.rs; \
+             also tidied the README for GitHub users.",
+        );
+
+        assert_eq!(
+            terms.into_iter().collect::<Vec<_>>(),
+            vec!["GitHub", "SyncEngine", "billing service", "billing_service"]
+        );
+    }
+
+    #[test]
+    fn test_glossary_store_learns_repeated_terms() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = GlossaryStore::new(temp_dir.path().join("glossary/app.json"));
+
+        store.learn("Fixed a crash in SyncEngine.")?;
+        assert!(store.learned_terms()?.is_empty());
+
+        store.learn("SyncEngine now retries; started on `ledger_v2`.")?;
+        store.learn("Moved SyncEngine and `ledger_v2` behind a flag.")?;
+
+        assert_eq!(store.learned_terms()?, vec!["SyncEngine", "ledger_v2"]);
+
+        let diary = temp_dir.path().join("git-diary-20240101-to-20240101.md");
+        fs::write(
+            &diary,
+            "# Git Diary (2024-01-01 – 2024-01-01)\n\n## Commit Logs\n\n- Fix\n\n## AI-generated Summary\n\nTuned the HarborCache.\n",
+        )?;
+        store.learn_from_diary(&diary)?;
+        store.learn_from_diary(&diary)?;
+        assert!(store.learned_terms()?.contains(&"HarborCache".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_glossary() {
+        let curated = BTreeMap::from([
+            (
+                "SyncEngine".to_string(),
+                "the offline sync module".to_string(),
+            ),
+            ("Harbor".to_string(), String::new()),
+        ]);
+
+        let glossary = merge_glossary(
+            &curated,
+            &["syncengine".to_string(), "ledger_v2".to_string()],
+        );
+
+        assert_eq!(
+            glossary,
+            vec![
+                GlossaryTerm::new("Harbor".to_string()),
+                GlossaryTerm::new("SyncEngine".to_string())
+                    .with_description(Some("the offline sync module".to_string())),
+                GlossaryTerm::new("ledger_v2".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+/// Reads goals from a `goals.md` file: one per list item, such as
+/// `- Ship the exporter` or `- [ ] Cut p95 latency below 200ms`
+///
+/// Headings and prose are skipped so the file can hold notes around the
+/// list, and ticked checkboxes count as done and are left out.
+pub fn parse_goals(markdown: &str) -> Vec<String> {
+    markdown
+        .lines()
+        .filter_map(|line| list_item(line.trim()))
+        .filter_map(|item| match item.strip_prefix("[ ] ") {
+            Some(open) => Some(open),
+            None if item.starts_with("[x] ") || item.starts_with("[X] ") => None,
+            None => Some(item),
+        })
+        .map(str::trim)
+        .filter(|goal| !goal.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Goals from the config followed by those from the goals file, each once
+pub fn merge_goals(configured: &[String], from_file: &[String]) -> Vec<String> {
+    let mut goals: Vec<String> = Vec::new();
+    for goal in configured.iter().chain(from_file) {
+        if !goals.iter().any(|known| known.eq_ignore_ascii_case(goal)) {
+            goals.push(goal.clone());
+        }
+    }
+    goals
+}
+
+/// The text of a `-`, `*`, `+` or numbered list item
+fn list_item(line: &str) -> Option<&str> {
+    if let Some(item) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+    {
+        return Some(item);
+    }
+    let (number, item) = line.split_once(". ")?;
+    (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit())).then_some(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_goals() {
+        let markdown = "# Goals for week 19\n\
+                        \n\
+                        Focus on the exporter.\n\
+                        \n\
+                        - Ship the exporter\n\
+                        * [ ] Cut p95 latency below 200ms\n\
+                        - [x] Hire a designer\n\
+                        1. Write the migration guide\n\
+                        -\n";
+
+        assert_eq!(
+            parse_goals(markdown),
+            vec![
+                "Ship the exporter",
+                "Cut p95 latency below 200ms",
+                "Write the migration guide",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_goals() {
+        let configured = vec!["Ship the exporter".to_string()];
+        let from_file = vec![
+            "ship the exporter".to_string(),
+            "Write the migration guide".to_string(),
+        ];
+
+        assert_eq!(
+            merge_goals(&configured, &from_file),
+            vec!["Ship the exporter", "Write the migration guide"]
+        );
+    }
+}
@@ -0,0 +1,197 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Markers counted as open work
+const MARKERS: &[&str] = &["TODO", "FIXME"];
+
+/// Build and CI files, matched by file name
+const BUILD_FILES: &[&str] = &[
+    "Cargo.toml",
+    "build.rs",
+    "Makefile",
+    "CMakeLists.txt",
+    "package.json",
+    "tsconfig.json",
+    "pyproject.toml",
+    "setup.py",
+    "setup.cfg",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "settings.gradle",
+    "Dockerfile",
+    "docker-compose.yml",
+    "Jenkinsfile",
+    ".gitlab-ci.yml",
+];
+
+/// How a diff touched a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    Added,
+    Deleted,
+    Modified,
+}
+
+/// Engineering-hygiene numbers for a diary period, summed over its commits' diffs
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RepoHealth {
+    /// TODO and FIXME markers on added lines
+    pub todos_added: usize,
+    /// TODO and FIXME markers on removed lines
+    pub todos_removed: usize,
+    pub test_files_added: usize,
+    pub test_files_removed: usize,
+    /// Build and CI files that changed, sorted
+    pub build_files_changed: BTreeSet<String>,
+}
+
+impl RepoHealth {
+    /// Counts a file the diff added, deleted or modified
+    pub fn add_file(&mut self, path: &str, change: FileChange) {
+        if is_test_file(path) {
+            match change {
+                FileChange::Added => self.test_files_added += 1,
+                FileChange::Deleted => self.test_files_removed += 1,
+                FileChange::Modified => {}
+            }
+        }
+        if is_build_file(path) {
+            self.build_files_changed.insert(path.to_string());
+        }
+    }
+
+    /// Counts the markers on a line the diff added or removed
+    pub fn add_line(&mut self, line: &str, added: bool) {
+        let markers = count_markers(line);
+        if added {
+            self.todos_added += markers;
+        } else {
+            self.todos_removed += markers;
+        }
+    }
+
+    /// Change in the number of open markers
+    pub fn todo_delta(&self) -> i64 {
+        self.todos_added as i64 - self.todos_removed as i64
+    }
+}
+
+/// One paragraph for the "Repo health" section
+impl Display for RepoHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TODO/FIXME markers: {} added, {} removed (net {:+}). Test files: {} added, {} removed.",
+            self.todos_added,
+            self.todos_removed,
+            self.todo_delta(),
+            self.test_files_added,
+            self.test_files_removed
+        )?;
+        if self.build_files_changed.is_empty() {
+            write!(f, " Build config unchanged.")
+        } else {
+            let files: Vec<&str> = self
+                .build_files_changed
+                .iter()
+                .map(String::as_str)
+                .collect();
+            write!(f, " Build config changed: {}.", files.join(", "))
+        }
+    }
+}
+
+/// Occurrences of `TODO` or `FIXME` as whole words
+pub fn count_markers(line: &str) -> usize {
+    line.split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| MARKERS.contains(word))
+        .count()
+}
+
+/// Files under a `tests` directory or named like `foo_test.go`, `foo.spec.ts` or `test_foo.py`
+pub fn is_test_file(path: &str) -> bool {
+    let path = Path::new(path);
+    let in_test_dir = path.parent().is_some_and(|dir| {
+        dir.components().any(|component| {
+            matches!(
+                component.as_os_str().to_str(),
+                Some("test" | "tests" | "__tests__" | "spec")
+            )
+        })
+    });
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return in_test_dir;
+    };
+
+    in_test_dir
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with(".test")
+        || stem.ends_with(".spec")
+        || stem.ends_with("Test")
+}
+
+/// Build scripts, manifests and CI workflows
+pub fn is_build_file(path: &str) -> bool {
+    if path.starts_with(".github/workflows/") || path.starts_with(".circleci/") {
+        return true;
+    }
+    Path::new(path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| BUILD_FILES.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_paths() {
+        assert!(is_test_file("tests/cli.rs"));
+        assert!(is_test_file("src/__tests__/app.js"));
+        assert!(is_test_file("pkg/sync_test.go"));
+        assert!(is_test_file("web/app.spec.ts"));
+        assert!(is_test_file("test_parser.py"));
+        assert!(is_test_file("src/test/java/ParserTest.java"));
+        assert!(!is_test_file("src/testing.rs"));
+        assert!(!is_test_file("src/contest/main.rs"));
+
+        assert!(is_build_file("Cargo.toml"));
+        assert!(is_build_file("web/package.json"));
+        assert!(is_build_file(".github/workflows/ci.yml"));
+        assert!(!is_build_file("src/build.md"));
+    }
+
+    #[test]
+    fn test_repo_health() {
+        assert_eq!(count_markers("// TODO: retry, FIXME later"), 2);
+        assert_eq!(count_markers("let todos = TODOS;"), 0);
+
+        let mut health = RepoHealth::default();
+        health.add_line("// TODO: handle timeouts", true);
+        health.add_line("// FIXME: flaky", true);
+        health.add_line("# TODO remove", false);
+        health.add_file("tests/sync.rs", FileChange::Added);
+        health.add_file("tests/old.rs", FileChange::Deleted);
+        health.add_file("tests/cli.rs", FileChange::Modified);
+        health.add_file("Cargo.toml", FileChange::Modified);
+        health.add_file(".github/workflows/ci.yml", FileChange::Added);
+
+        assert_eq!(health.todo_delta(), 1);
+        assert_eq!(
+            health.to_string(),
+            "TODO/FIXME markers: 2 added, 1 removed (net +1). Test files: 1 added, 1 removed. \
+             Build config changed: .github/workflows/ci.yml, Cargo.toml."
+        );
+        assert_eq!(
+            RepoHealth::default().to_string(),
+            "TODO/FIXME markers: 0 added, 0 removed (net +0). Test files: 0 added, 0 removed. Build config unchanged."
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use chrono::{Months, NaiveDate};
+use std::fs;
+
+use crate::domain::DiarySummary;
+use crate::formatter::SUMMARY_HEADING;
+use crate::parser::parse_diary;
+use crate::storage::DiaryStorageImpl;
+
+/// First day of the period covered by `months` months up to `today`
+pub fn period_start(today: NaiveDate, months: u32) -> NaiveDate {
+    today
+        .checked_sub_months(Months::new(months))
+        .unwrap_or(NaiveDate::MIN)
+}
+
+/// Summaries of the stored Markdown diaries ending on or after `since`, oldest first
+///
+/// Diaries without a summary section are skipped, as are files that fail to
+/// parse, with a warning.
+pub fn stored_summaries(storage: &DiaryStorageImpl, since: NaiveDate) -> Result<Vec<DiarySummary>> {
+    let mut summaries = Vec::new();
+
+    for diary in storage.stored_diaries()? {
+        if diary.end_date < since || diary.path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+
+        let markdown = fs::read_to_string(&diary.path)
+            .with_context(|| format!("Failed to read {}", diary.path.display()))?;
+        let parsed = match parse_diary(&markdown) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("⚠️ Skipping {}: {}", diary.path.display(), e);
+                continue;
+            }
+        };
+
+        let Some(summary) = parsed
+            .section(SUMMARY_HEADING)
+            .map(|section| section.body.trim())
+            .filter(|body| !body.is_empty())
+        else {
+            continue;
+        };
+
+        let period = parsed.title.clone().unwrap_or_else(|| {
+            diary
+                .path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        summaries.push(DiarySummary {
+            period,
+            summary: summary.to_string(),
+        });
+    }
+
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_period_start() {
+        let today = NaiveDate::from_ymd_opt(2024, 8, 31).unwrap();
+
+        assert_eq!(
+            period_start(today, 6),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+        assert_eq!(period_start(today, 0), today);
+    }
+
+    #[test]
+    fn test_stored_summaries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path();
+        let diary = |title: &str, summary: &str| {
+            format!(
+                "# {}\n\n## Commit Logs\n\n- 2024-01-01 00:00:00: Work\n\n## {}\n\n{}\n",
+                title, SUMMARY_HEADING, summary
+            )
+        };
+
+        fs::write(
+            dir.join("git-diary-20240101-to-20240107.md"),
+            diary("Old week", "Too old."),
+        )?;
+        fs::write(
+            dir.join("git-diary-20240304-to-20240310.md"),
+            diary("March week", "Shipped CSV export."),
+        )?;
+        fs::write(
+            dir.join("git-diary-20240311-to-20240317.md"),
+            diary("Empty week", ""),
+        )?;
+        fs::write(dir.join("git-diary-20240304-to-20240310.json"), "{}")?;
+        fs::write(dir.join("notes.md"), diary("Not a diary", "Ignored."))?;
+
+        let storage = DiaryStorageImpl::new(dir.to_string_lossy().into_owned());
+        let summaries = stored_summaries(&storage, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap())?;
+
+        assert_eq!(
+            summaries,
+            vec![DiarySummary {
+                period: "March week".to_string(),
+                summary: "Shipped CSV export.".to_string(),
+            }]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::{bail, Result};
+
+use crate::domain::{Author, AuthorFilter, Commit};
+
+/// The names and emails one person commits under, such as a work and a
+/// personal address, counted as a single author
+///
+/// Commits by any of them are shown under the first name and the first email
+/// listed, so author counts, grouping and `--mine` treat them as one person.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identities {
+    identities: Vec<AuthorFilter>,
+}
+
+impl Identities {
+    pub fn new(identities: Vec<AuthorFilter>) -> Self {
+        Self { identities }
+    }
+
+    /// Parses `identities` from the config file: an email, a name, or both as
+    /// `Name <email>`
+    pub fn parse(identities: &[String]) -> Result<Self> {
+        identities
+            .iter()
+            .map(|identity| parse_identity(identity))
+            .collect::<Result<_>>()
+            .map(Self::new)
+    }
+
+    /// Puts `identity`, such as the one from git config, before the others so
+    /// its name and email are the ones shown
+    pub fn led_by(mut self, identity: AuthorFilter) -> Self {
+        if identity.name.is_some() || identity.email.is_some() {
+            self.identities.insert(0, identity);
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.identities.is_empty()
+    }
+
+    pub fn matches(&self, author: &Author) -> bool {
+        self.identities
+            .iter()
+            .any(|identity| identity.matches(author))
+    }
+
+    /// Matches the author the identities are normalized to
+    pub fn filter(&self) -> AuthorFilter {
+        AuthorFilter {
+            name: self.name().map(str::to_string),
+            email: self.email().map(str::to_string),
+        }
+    }
+
+    /// Gives every commit by one of the identities the same author
+    pub fn normalize(&self, commits: &mut [Commit]) {
+        for commit in commits {
+            if let Some(author) = commit.author.as_mut().filter(|author| self.matches(author)) {
+                if let Some(name) = self.name() {
+                    author.name = name.to_string();
+                }
+                if let Some(email) = self.email() {
+                    author.email = email.to_string();
+                }
+            }
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        self.identities
+            .iter()
+            .find_map(|identity| identity.name.as_deref())
+    }
+
+    fn email(&self) -> Option<&str> {
+        self.identities
+            .iter()
+            .find_map(|identity| identity.email.as_deref())
+    }
+}
+
+/// `me@work.com` is an email, `Old Name <old@mail>` a name and an email, and
+/// anything else a name
+fn parse_identity(identity: &str) -> Result<AuthorFilter> {
+    let identity = identity.trim();
+    let non_empty = |value: &str| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+
+    if identity.contains('<') || identity.contains('>') {
+        let Some(author) = Author::parse_ident(identity).filter(|_| identity.ends_with('>')) else {
+            bail!("Malformed identity {:?}, expected `Name <email>`", identity);
+        };
+        return Ok(AuthorFilter {
+            name: non_empty(&author.name),
+            email: non_empty(&author.email),
+        });
+    }
+    if identity.is_empty() {
+        bail!("Empty entry in identities");
+    }
+
+    Ok(if identity.contains('@') {
+        AuthorFilter {
+            name: None,
+            email: Some(identity.to_string()),
+        }
+    } else {
+        AuthorFilter {
+            name: Some(identity.to_string()),
+            email: None,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_by(name: &str, email: &str) -> Commit {
+        Commit::new("Change".to_string(), 1704067200)
+            .with_author(Author::new(name.to_string(), email.to_string()))
+    }
+
+    #[test]
+    fn test_identities_normalize_authors() -> Result<()> {
+        let identities = Identities::parse(&[
+            "me@work.com".to_string(),
+            "me@personal.dev".to_string(),
+            "Old Name <old@mail>".to_string(),
+        ])?
+        .led_by(AuthorFilter {
+            name: Some("Jane Doe".to_string()),
+            email: None,
+        });
+
+        let mut commits = vec![
+            commit_by("Jane D.", "ME@personal.dev"),
+            commit_by("Old Name", "old@laptop.local"),
+            commit_by("John Roe", "john@example.com"),
+        ];
+        identities.normalize(&mut commits);
+
+        let jane = Author::new("Jane Doe".to_string(), "me@work.com".to_string());
+        assert_eq!(commits[0].author.as_ref(), Some(&jane));
+        assert_eq!(commits[1].author.as_ref(), Some(&jane));
+        assert_eq!(commits[2].author.as_ref().unwrap().name, "John Roe");
+        assert!(identities.filter().matches(&jane));
+
+        assert!(Identities::parse(&["Old Name <old@mail".to_string()]).is_err());
+        assert!(Identities::parse(&[" ".to_string()]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+/// Value of `--language` that writes the diary in the language of the commit messages
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// Short words that give away a Latin-script language, checked in order
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "to", "for", "of", "in", "with", "from", "when", "add", "fix", "update",
+            "remove",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der",
+            "die",
+            "das",
+            "und",
+            "für",
+            "mit",
+            "nicht",
+            "beim",
+            "auf",
+            "hinzugefügt",
+            "behoben",
+            "entfernt",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le",
+            "la",
+            "les",
+            "des",
+            "du",
+            "et",
+            "pour",
+            "avec",
+            "dans",
+            "ajout",
+            "correction",
+            "suppression",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "del", "y", "para", "con", "añadir", "corregir", "eliminar",
+            "cuando",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "da", "do", "e", "para", "com", "não", "adiciona", "corrige", "remove",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "gli", "della", "e", "per", "con", "non", "aggiungi", "correggi", "rimuovi",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de",
+            "het",
+            "en",
+            "van",
+            "voor",
+            "met",
+            "niet",
+            "toegevoegd",
+            "verwijderd",
+        ],
+    ),
+];
+
+/// Fewest letters a message needs to get a vote
+const MIN_LETTERS: usize = 3;
+
+/// A CJK character says about as much as a short Latin word
+const CJK_WEIGHT: usize = 3;
+
+/// Detects the language most of `messages` are written in, as a code such as
+/// `ja` that `prompt::language_name` understands
+///
+/// Each message gets one vote, so a Japanese history with a few English
+/// merge commits still comes out as Japanese. Returns `None` when no message
+/// has enough letters to tell.
+pub fn detect_language<'a>(messages: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let mut votes: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for message in messages {
+        if let Some(language) = detect_message(message) {
+            *votes.entry(language).or_default() += 1;
+        }
+    }
+
+    votes
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(language, _)| language)
+}
+
+/// Picks the language of one message from its script, then from its stopwords
+fn detect_message(message: &str) -> Option<&'static str> {
+    let (mut kana, mut hangul, mut han, mut cyrillic, mut latin) = (0, 0, 0, 0, 0);
+    for c in message.chars() {
+        match c {
+            '\u{3040}'..='\u{30ff}' => kana += 1,
+            '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}' => hangul += 1,
+            '\u{4e00}'..='\u{9fff}' => han += 1,
+            '\u{0400}'..='\u{04ff}' => cyrillic += 1,
+            c if c.is_alphabetic() && c <= '\u{024f}' => latin += 1,
+            _ => {}
+        }
+    }
+
+    let cjk = (kana + hangul + han) * CJK_WEIGHT;
+    // Version tags and hashes say nothing about the language
+    if cjk + cyrillic + latin < MIN_LETTERS {
+        return None;
+    }
+    if cjk >= cyrillic && cjk >= latin {
+        // Japanese mixes kanji with kana; Chinese has kanji only
+        return Some(if kana > 0 {
+            "ja"
+        } else if hangul > 0 {
+            "ko"
+        } else {
+            "zh"
+        });
+    }
+    if cyrillic >= latin {
+        return Some("ru");
+    }
+
+    Some(latin_language(message))
+}
+
+/// Latin-script messages without telling stopwords are taken to be English
+fn latin_language(message: &str) -> &'static str {
+    let words: Vec<String> = message
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut best = ("en", 0);
+    for (language, stopwords) in STOPWORDS {
+        let hits = words
+            .iter()
+            .filter(|word| stopwords.contains(&word.as_str()))
+            .count();
+        if hits > best.1 {
+            best = (language, hits);
+        }
+    }
+
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_message() {
+        assert_eq!(detect_message("キャッシュのバグを修正"), Some("ja"));
+        assert_eq!(detect_message("`SyncEngine` のリトライを追加"), Some("ja"));
+        assert_eq!(detect_message("修复缓存错误"), Some("zh"));
+        assert_eq!(detect_message("캐시 버그 수정"), Some("ko"));
+        assert_eq!(detect_message("Исправить ошибку кэша"), Some("ru"));
+        assert_eq!(
+            detect_message("Fehler beim Laden der Konfiguration behoben"),
+            Some("de")
+        );
+        assert_eq!(
+            detect_message("Ajout de la page pour les factures"),
+            Some("fr")
+        );
+        assert_eq!(detect_message("Fix the cache for large files"), Some("en"));
+        assert_eq!(detect_message("Refactor parser"), Some("en"));
+        assert_eq!(detect_message("1.2.0"), None);
+    }
+
+    #[test]
+    fn test_detect_language_takes_the_majority() {
+        assert_eq!(
+            detect_language([
+                "キャッシュのバグを修正",
+                "Merge branch 'main'",
+                "設定画面を追加",
+                "v1.2.0",
+            ]),
+            Some("ja")
+        );
+        assert_eq!(detect_language(["Fix cache", "Add retries"]), Some("en"));
+        assert_eq!(detect_language(Vec::<&str>::new()), None);
+    }
+}
@@ -0,0 +1,596 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// One generated diary, as recorded in the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Unix timestamp of the run
+    pub generated_at: i64,
+    /// Repository path or export file the commits came from
+    pub repo: String,
+    pub start_date: String,
+    pub end_date: String,
+    /// Unix timestamp the covered range ended at, capped at the time of the run;
+    /// missing from entries written before it was recorded
+    #[serde(default)]
+    pub end_time: Option<i64>,
+    /// Where the diary was saved
+    pub path: String,
+    /// API provider; entries written before providers were recorded are OpenAI's
+    #[serde(default = "default_provider")]
+    pub provider: String,
+    pub model: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Estimated cost in USD, if the model's price is known
+    pub cost: Option<f64>,
+    /// Latest rating given with `git-diary feedback`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<Feedback>,
+    /// Why the run stopped before saving its diary, such as "interrupted by
+    /// SIGINT"; its tokens were still spent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aborted: Option<String>,
+    /// What the run produced; only diaries are picked up by `--since-last`
+    #[serde(default, skip_serializing_if = "EntryKind::is_diary")]
+    pub kind: EntryKind,
+}
+
+/// What a ledger entry's tokens were spent on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    /// A saved diary
+    #[default]
+    Diary,
+    /// Highlights printed by `git-diary highlights`
+    Highlights,
+    /// A summary recomputed by `git-diary regen` in a diary already recorded
+    Regen,
+}
+
+impl EntryKind {
+    fn is_diary(&self) -> bool {
+        *self == EntryKind::Diary
+    }
+}
+
+/// Whether a diary's summary was what the user wanted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Rating {
+    Good,
+    Bad,
+}
+
+/// A rating of one diary, with an optional note on what to do differently
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Feedback {
+    pub rating: Rating,
+    pub note: Option<String>,
+    /// Unix timestamp the rating was given at
+    pub rated_at: i64,
+}
+
+fn default_provider() -> String {
+    "openai".to_string()
+}
+
+impl LedgerEntry {
+    /// Moment the diary's commits ran up to, for picking up where it left off
+    pub fn covered_until(&self) -> i64 {
+        self.end_time.unwrap_or(self.generated_at)
+    }
+
+    /// Whether the run saved a diary, rather than being aborted or spending
+    /// its tokens on something else
+    ///
+    /// Highlights and regen entries written before kinds were recorded are
+    /// told apart by their path.
+    pub fn saved_diary(&self) -> bool {
+        self.aborted.is_none()
+            && self.kind.is_diary()
+            && self.path != "highlights"
+            && self.path != "regen"
+    }
+
+    /// Local month the diary was generated in, as `YYYY-MM`
+    pub fn month(&self) -> String {
+        DateTime::from_timestamp(self.generated_at, 0)
+            .map(|time| time.with_timezone(&Local).format("%Y-%m").to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Totals over a set of ledger entries
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LedgerTotals {
+    pub diaries: usize,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated cost of the entries with a known price
+    pub cost: f64,
+}
+
+impl LedgerTotals {
+    /// Aborted runs and other entries that saved no diary add their tokens and cost
+    pub fn of(entries: &[LedgerEntry]) -> Self {
+        entries.iter().fold(Self::default(), |totals, entry| Self {
+            diaries: totals.diaries + usize::from(entry.saved_diary()),
+            prompt_tokens: totals.prompt_tokens + entry.prompt_tokens as u64,
+            completion_tokens: totals.completion_tokens + entry.completion_tokens as u64,
+            cost: totals.cost + entry.cost.unwrap_or_default(),
+        })
+    }
+}
+
+/// Totals per `(provider, model)`, sorted by provider and model
+pub fn totals_by_model(entries: &[LedgerEntry]) -> Vec<((String, String), LedgerTotals)> {
+    let mut groups: BTreeMap<(String, String), Vec<LedgerEntry>> = BTreeMap::new();
+    for entry in entries {
+        groups
+            .entry((entry.provider.clone(), entry.model.clone()))
+            .or_default()
+            .push(entry.clone());
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, entries)| (key, LedgerTotals::of(&entries)))
+        .collect()
+}
+
+/// Append-only log of every generated diary, one JSON record per line
+///
+/// Several git-diary processes may share one ledger, so appends take an
+/// exclusive lock on the file and write each record with a single call;
+/// readers take a shared lock. A line that fails to parse is skipped with a
+/// warning instead of hiding the rest of the history.
+pub struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `entry` to the ledger
+    pub fn append(&self, entry: &LedgerEntry) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Failed to create ledger directory")?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open ledger")?;
+        file.lock().context("Failed to lock ledger")?;
+        file.write_all(line.as_bytes())
+            .context("Failed to write ledger")?;
+        file.sync_all().context("Failed to flush ledger")?;
+
+        Ok(())
+    }
+
+    /// Returns every entry, oldest first
+    pub fn entries(&self) -> Result<Vec<LedgerEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = File::open(&self.path).context("Failed to open ledger")?;
+        file.lock_shared().context("Failed to lock ledger")?;
+
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(&file).lines().enumerate() {
+            let line = line.context("Failed to read ledger")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => eprintln!(
+                    "⚠️ Skipping line {} of {}: {}",
+                    number + 1,
+                    self.path.display(),
+                    e
+                ),
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes entries generated before `cutoff`, returning them
+    ///
+    /// Lines that fail to parse are kept. With `dry_run` nothing is written.
+    pub fn remove_older_than(&self, cutoff: i64, dry_run: bool) -> Result<Vec<LedgerEntry>> {
+        let mut removed = Vec::new();
+        self.rewrite(|lines| {
+            lines.retain(|line| match serde_json::from_str::<LedgerEntry>(line) {
+                Ok(entry) if entry.generated_at < cutoff => {
+                    removed.push(entry);
+                    false
+                }
+                _ => true,
+            });
+            Ok(!dry_run && !removed.is_empty())
+        })?;
+
+        Ok(removed)
+    }
+
+    /// Rates the latest diary saved at `path`, returning its updated entry;
+    /// none when no diary in the ledger was saved there
+    pub fn rate(&self, path: &Path, feedback: Feedback) -> Result<Option<LedgerEntry>> {
+        let target = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut rated = None;
+        self.rewrite(|lines| {
+            for line in lines.iter_mut().rev() {
+                let Ok(mut entry) = serde_json::from_str::<LedgerEntry>(line) else {
+                    continue;
+                };
+                let saved = Path::new(&entry.path);
+                if saved == path || fs::canonicalize(saved).is_ok_and(|saved| saved == target) {
+                    entry.feedback = Some(feedback);
+                    *line = serde_json::to_string(&entry)?;
+                    rated = Some(entry);
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        })?;
+
+        Ok(rated)
+    }
+
+    /// Rewrites the ledger in place when `edit` returns true
+    ///
+    /// The file is rewritten under the exclusive lock, so concurrent appends
+    /// wait instead of landing in a file that is about to be replaced.
+    fn rewrite(&self, edit: impl FnOnce(&mut Vec<String>) -> Result<bool>) -> Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .context("Failed to open ledger")?;
+        file.lock().context("Failed to lock ledger")?;
+
+        let mut raw = String::new();
+        file.read_to_string(&mut raw)
+            .context("Failed to read ledger")?;
+        let mut lines: Vec<String> = raw
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if edit(&mut lines)? {
+            let kept: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+            file.set_len(0).context("Failed to truncate ledger")?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(kept.as_bytes())
+                .context("Failed to write ledger")?;
+            file.sync_all().context("Failed to flush ledger")?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the diary of `repo` whose range ended last, leaving out aborted
+    /// runs and entries that saved no diary
+    pub fn latest_for(&self, repo: &str) -> Result<Option<LedgerEntry>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .filter(|entry| entry.repo == repo && entry.saved_diary())
+            .max_by_key(LedgerEntry::covered_until))
+    }
+
+    /// Returns the latest diary already generated for `repo` over the same dates
+    pub fn find_duplicate(
+        &self,
+        repo: &str,
+        start_date: &str,
+        end_date: &str,
+    ) -> Result<Option<LedgerEntry>> {
+        Ok(self.entries()?.into_iter().rev().find(|entry| {
+            entry.repo == repo
+                && entry.start_date == start_date
+                && entry.end_date == end_date
+                && entry.saved_diary()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::TempDir;
+
+    fn create_test_entry(repo: &str, start_date: &str) -> LedgerEntry {
+        LedgerEntry {
+            generated_at: 1704067200,
+            repo: repo.to_string(),
+            start_date: start_date.to_string(),
+            end_date: "2024-01-07".to_string(),
+            end_time: Some(1704672000),
+            path: format!("diaries/git-diary-{}-to-20240107.md", start_date),
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            prompt_tokens: 1000,
+            completion_tokens: 200,
+            cost: Some(0.00027),
+            feedback: None,
+            aborted: None,
+            kind: EntryKind::Diary,
+        }
+    }
+
+    #[test]
+    fn test_ledger_round_trip_and_duplicates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let ledger = Ledger::new(temp_dir.path().join("nested/ledger.jsonl"));
+
+        assert!(ledger.entries()?.is_empty());
+
+        ledger.append(&create_test_entry("/src/app", "2024-01-01"))?;
+        ledger.append(&create_test_entry("/src/lib", "2024-01-01"))?;
+        let mut unpriced = create_test_entry("/src/app", "2024-01-06");
+        unpriced.cost = None;
+        ledger.append(&unpriced)?;
+
+        let entries = ledger.entries()?;
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[2], unpriced);
+
+        assert_eq!(
+            ledger.find_duplicate("/src/lib", "2024-01-01", "2024-01-07")?,
+            Some(entries[1].clone())
+        );
+        assert_eq!(
+            ledger.find_duplicate("/src/lib", "2024-01-06", "2024-01-07")?,
+            None
+        );
+
+        let mut legacy = create_test_entry("/src/lib", "2024-01-08");
+        legacy.generated_at = 1704700000;
+        legacy.end_time = None;
+        ledger.append(&legacy)?;
+        assert_eq!(ledger.latest_for("/src/lib")?, Some(legacy));
+        assert_eq!(
+            ledger
+                .latest_for("/src/app")?
+                .map(|entry| entry.covered_until()),
+            Some(1704672000)
+        );
+        assert_eq!(ledger.latest_for("/src/other")?, None);
+
+        // An aborted run neither counts as the latest diary nor as a duplicate
+        let mut aborted = create_test_entry("/src/lib", "2024-01-09");
+        aborted.generated_at = 1704800000;
+        aborted.end_time = Some(1704800000);
+        aborted.path = String::new();
+        aborted.aborted = Some("interrupted by SIGINT".to_string());
+        ledger.append(&aborted)?;
+        assert_eq!(
+            ledger
+                .latest_for("/src/lib")?
+                .map(|entry| entry.generated_at),
+            Some(1704700000)
+        );
+        assert_eq!(
+            ledger.find_duplicate("/src/lib", "2024-01-09", "2024-01-07")?,
+            None
+        );
+        let totals = LedgerTotals::of(&[entries[0].clone(), aborted]);
+        assert_eq!(totals.diaries, 1);
+        assert_eq!(totals.prompt_tokens, 2000);
+
+        // Neither do highlights, which cover up to the time they were mined
+        let mut highlights = create_test_entry("/src/lib", "2024-01-01");
+        highlights.generated_at = 1704900000;
+        highlights.end_time = None;
+        highlights.path = "highlights".to_string();
+        highlights.kind = EntryKind::Highlights;
+        ledger.append(&highlights)?;
+        let mut legacy_highlights = highlights.clone();
+        legacy_highlights.kind = EntryKind::Diary;
+        ledger.append(&legacy_highlights)?;
+        assert_eq!(
+            ledger
+                .latest_for("/src/lib")?
+                .map(|entry| entry.generated_at),
+            Some(1704700000)
+        );
+        assert_eq!(
+            ledger.find_duplicate("/src/lib", "2024-01-01", "2024-01-07")?,
+            Some(entries[1].clone())
+        );
+        let totals = LedgerTotals::of(&[entries[0].clone(), highlights]);
+        assert_eq!(totals.diaries, 1);
+        assert_eq!(totals.prompt_tokens, 2000);
+
+        // Nor does recomputing a diary's summary
+        let mut regen = create_test_entry("/src/lib", "2024-01-01");
+        regen.generated_at = 1705000000;
+        regen.end_time = None;
+        regen.path = "regen".to_string();
+        regen.kind = EntryKind::Regen;
+        ledger.append(&regen)?;
+        assert_eq!(
+            ledger
+                .latest_for("/src/lib")?
+                .map(|entry| entry.generated_at),
+            Some(1704700000)
+        );
+        assert_eq!(LedgerTotals::of(&[regen]).diaries, 0);
+
+        let totals = LedgerTotals::of(&entries);
+        assert_eq!(totals.diaries, 3);
+        assert_eq!(totals.prompt_tokens, 3000);
+        assert!((totals.cost - 0.00054).abs() < 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ledger_remove_older_than() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("ledger.jsonl");
+        let ledger = Ledger::new(path.clone());
+
+        for generated_at in [100, 200, 300] {
+            let mut entry = create_test_entry("/src/app", "2024-01-01");
+            entry.generated_at = generated_at;
+            ledger.append(&entry)?;
+        }
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"not json\n")?;
+
+        assert_eq!(ledger.remove_older_than(250, true)?.len(), 2);
+        assert_eq!(ledger.entries()?.len(), 3);
+
+        let removed = ledger.remove_older_than(250, false)?;
+        assert_eq!(removed.len(), 2);
+        assert_eq!(ledger.entries()?.len(), 1);
+        assert!(fs::read_to_string(&path)?.ends_with("not json\n"));
+
+        // Appends after pruning still land on their own line
+        ledger.append(&create_test_entry("/src/lib", "2024-01-01"))?;
+        assert_eq!(ledger.entries()?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ledger_rate() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let ledger = Ledger::new(temp_dir.path().join("ledger.jsonl"));
+        let diary = temp_dir.path().join("git-diary-20240101-to-20240107.md");
+        fs::write(&diary, "# Diary\n")?;
+
+        let mut entry = create_test_entry("/src/app", "2024-01-01");
+        entry.path = diary.to_string_lossy().into_owned();
+        ledger.append(&entry)?;
+        ledger.append(&create_test_entry("/src/lib", "2024-01-01"))?;
+
+        let feedback = Feedback {
+            rating: Rating::Good,
+            note: Some("Right level of detail".to_string()),
+            rated_at: 1704700000,
+        };
+        // The same file through a different path still finds the entry
+        let rated = ledger.rate(
+            &temp_dir.path().join(".").join(diary.file_name().unwrap()),
+            feedback.clone(),
+        )?;
+        assert_eq!(rated.map(|entry| entry.repo), Some("/src/app".to_string()));
+
+        let entries = ledger.entries()?;
+        assert_eq!(entries[0].feedback, Some(feedback.clone()));
+        assert_eq!(entries[1].feedback, None);
+
+        assert_eq!(ledger.rate(Path::new("elsewhere.md"), feedback)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_totals_by_model() {
+        // Mid-month timestamps, so the month is the same in every timezone
+        let mut may = create_test_entry("/src/app", "2024-05-13");
+        may.generated_at = 1715601600;
+        let mut mini = may.clone();
+        mini.model = "gpt-4o".to_string();
+        mini.cost = None;
+        let mut june = may.clone();
+        june.generated_at = 1718280000;
+
+        assert_eq!(may.month(), "2024-05");
+        assert_eq!(june.month(), "2024-06");
+
+        let entries: Vec<LedgerEntry> = [may.clone(), mini, may, june]
+            .into_iter()
+            .filter(|entry| entry.month() == "2024-05")
+            .collect();
+        let totals = totals_by_model(&entries);
+
+        let models: Vec<(&str, usize)> = totals
+            .iter()
+            .map(|((_, model), totals)| (model.as_str(), totals.diaries))
+            .collect();
+        assert_eq!(models, vec![("gpt-4o", 1), ("gpt-4o-mini", 2)]);
+        assert_eq!(totals[0].1.cost, 0.0);
+    }
+
+    #[test]
+    fn test_ledger_entry_defaults_provider() -> Result<()> {
+        let entry: LedgerEntry = serde_json::from_str(
+            r#"{"generated_at":1,"repo":"/src/app","start_date":"2024-01-01","end_date":"2024-01-01","path":"a.md","model":"gpt-4","prompt_tokens":1,"completion_tokens":1,"cost":null}"#,
+        )?;
+
+        assert_eq!(entry.provider, "openai");
+        assert_eq!(entry.covered_until(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ledger_concurrent_appends() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("ledger.jsonl");
+        let ledger = Arc::new(Ledger::new(path.clone()));
+
+        let handles: Vec<_> = (0..8)
+            .map(|writer| {
+                let ledger = Arc::clone(&ledger);
+                thread::spawn(move || -> Result<()> {
+                    for _ in 0..25 {
+                        ledger.append(&create_test_entry(
+                            &format!("/src/{}", writer),
+                            "2024-01-01",
+                        ))?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+
+        // A torn line left by a crashed writer doesn't hide the others
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)?
+            .write_all(b"{\"generated_at\": 17\n")?;
+
+        assert_eq!(Ledger::new(path).entries()?.len(), 200);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! Core of git-diary: commit filtering, prompt building and diary formatting
+//!
+//! Everything that touches the local machine or the network sits behind a
+//! cargo feature, so the core also builds for `wasm32-unknown-unknown`:
+//!
+//! - `git`: reading commits from repositories, bundles and fast-export streams,
+//!   and committing diaries to a separate diary repository
+//! - `openai`: summaries through the OpenAI API
+//! - `fs`: diary storage, run state, pending notes, the diary ledger and its
+//!   ratings, the learned glossary, the queue of runs put off while offline,
+//!   linting of stored diaries and mining them for highlights
+//! - `forge`: forge credential lookup, pull request titles and ticket status
+//!   changes from Jira or GitHub Projects
+//! - `timetrack`: coding time from WakaTime or ActivityWatch
+//! - `tui`: the terminal browser for stored diaries
+//! - `schema`: the JSON schema of the sidecar format
+//! - `server`: the local diary preview server
+//! - `notify`: desktop notifications when a diary is saved
+//! - `local-llm`: experimental offline summaries from a GGUF model; needs
+//!   llama.cpp's `llama-cli` installed separately and on `PATH`, as the
+//!   crate runs it rather than linking llama.cpp
+//! - `cli`: `git`, `openai` and `fs` plus the config file, platform
+//!   directories and the scheduler entries of `git-diary watch --install`,
+//!   used by the `git-diary` binary
+//! - `wasm`: browser bindings for the playground in `web/`
+//! - `archive`: backing diaries and their ledger entries up to a `.tar.gz`
+//!   and restoring them
+//!
+//! The default features are `cli`, `forge`, `timetrack`, `tui`, `schema`,
+//! `server` and `archive`. The binary leaves out the subcommands and sections of the
+//! features it's built without, so `--no-default-features --features cli`
+//! builds a slim `git-diary` that only writes diaries; embedders pick any
+//! subset, such as `--no-default-features --features git,openai`.
+
+pub mod attachments;
+pub mod codingtime;
+pub mod deps;
+pub mod domain;
+pub mod empty;
+pub mod encoding;
+pub mod formatter;
+pub mod gitlog;
+pub mod goals;
+pub mod health;
+pub mod identity;
+pub mod language;
+pub mod middleware;
+pub mod multirepo;
+pub mod parser;
+pub mod pathfilter;
+pub mod perf;
+pub mod postprocess;
+pub mod pricing;
+pub mod prompt;
+pub mod regen;
+pub mod related;
+pub mod rewrite;
+pub mod routing;
+pub mod rpc;
+pub mod scan;
+pub mod scope;
+pub mod sessions;
+pub mod sidecar;
+pub mod squash;
+pub mod structured;
+pub mod terminal;
+pub mod testruns;
+pub mod textlog;
+pub mod worktime;
+
+#[cfg(feature = "openai")]
+pub mod ai;
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "forge")]
+pub mod auth;
+#[cfg(feature = "cli")]
+pub mod config;
+#[cfg(feature = "git")]
+pub mod diary_repo;
+#[cfg(feature = "git")]
+pub mod export;
+#[cfg(feature = "fs")]
+pub mod feedback;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "fs")]
+pub mod glossary;
+#[cfg(feature = "fs")]
+pub mod highlights;
+#[cfg(feature = "fs")]
+pub mod ledger;
+#[cfg(feature = "fs")]
+pub mod lint;
+#[cfg(feature = "local-llm")]
+pub mod local;
+#[cfg(feature = "fs")]
+pub mod notes;
+#[cfg(feature = "notify")]
+pub mod notify;
+#[cfg(feature = "cli")]
+pub mod paths;
+#[cfg(feature = "server")]
+pub mod preview;
+#[cfg(feature = "forge")]
+pub mod pulls;
+#[cfg(feature = "fs")]
+pub mod queue;
+#[cfg(feature = "cli")]
+pub mod schedule;
+#[cfg(feature = "fs")]
+pub mod state;
+#[cfg(feature = "fs")]
+pub mod storage;
+#[cfg(feature = "forge")]
+pub mod tickets;
+#[cfg(feature = "timetrack")]
+pub mod timetrack;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "wasm")]
+pub mod wasm;
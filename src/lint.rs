@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::formatter::{COMMIT_LOGS_HEADING, SUMMARY_HEADING};
+use crate::parser::{parse_diary, ParsedDiary, Section};
+
+/// A structural problem found in a stored diary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LintIssue {
+    MalformedFrontmatter(String),
+    MissingTitle,
+    MissingSection(&'static str),
+    EmptySummary,
+}
+
+impl LintIssue {
+    /// Whether `fix_diary` can repair the issue without regenerating content
+    pub fn is_fixable(&self) -> bool {
+        matches!(self, LintIssue::MissingTitle | LintIssue::MissingSection(_))
+    }
+}
+
+impl Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintIssue::MalformedFrontmatter(reason) => {
+                write!(f, "malformed frontmatter: {}", reason)
+            }
+            LintIssue::MissingTitle => write!(f, "missing \"# Git Diary\" title"),
+            LintIssue::MissingSection(heading) => write!(f, "missing \"## {}\" section", heading),
+            LintIssue::EmptySummary => write!(f, "summary section is empty"),
+        }
+    }
+}
+
+/// Checks a diary's Markdown for structural problems
+pub fn lint_diary(markdown: &str) -> Vec<LintIssue> {
+    let diary = match parse_diary(markdown) {
+        Ok(diary) => diary,
+        Err(e) => return vec![LintIssue::MalformedFrontmatter(e.to_string())],
+    };
+
+    let mut issues = Vec::new();
+
+    if diary.title.is_none() {
+        issues.push(LintIssue::MissingTitle);
+    }
+
+    for heading in [COMMIT_LOGS_HEADING, SUMMARY_HEADING] {
+        if diary.section(heading).is_none() {
+            issues.push(LintIssue::MissingSection(heading));
+        }
+    }
+
+    if diary
+        .section(SUMMARY_HEADING)
+        .is_some_and(|section| section.body.is_empty())
+    {
+        issues.push(LintIssue::EmptySummary);
+    }
+
+    issues
+}
+
+/// Repairs fixable issues, returning the new Markdown if anything changed
+///
+/// A missing title is rebuilt from a `git-diary-YYYYMMDD-to-YYYYMMDD.md`
+/// file name when possible. Missing sections are added empty, in the order
+/// `DiaryStorageImpl` writes them.
+pub fn fix_diary(markdown: &str, file_name: &str) -> Option<String> {
+    let mut diary: ParsedDiary = parse_diary(markdown).ok()?;
+    let mut changed = false;
+
+    if diary.title.is_none() {
+        diary.title = Some(title_from_file_name(file_name));
+        changed = true;
+    }
+
+    if diary.section(COMMIT_LOGS_HEADING).is_none() {
+        diary.sections.insert(0, empty_section(COMMIT_LOGS_HEADING));
+        changed = true;
+    }
+
+    if diary.section(SUMMARY_HEADING).is_none() {
+        let position = diary
+            .sections
+            .iter()
+            .position(|section| section.heading == COMMIT_LOGS_HEADING)
+            .map_or(diary.sections.len(), |index| index + 1);
+        diary
+            .sections
+            .insert(position, empty_section(SUMMARY_HEADING));
+        changed = true;
+    }
+
+    changed.then(|| diary.render())
+}
+
+/// Lints the given files, fixing them in place when `fix` is set
+///
+/// Prints every remaining issue and returns how many there are.
+pub fn lint_files(paths: &[PathBuf], fix: bool) -> Result<usize> {
+    let mut remaining = 0;
+
+    for path in paths {
+        let markdown = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read diary {}", path.display()))?;
+        let mut issues = lint_diary(&markdown);
+
+        if fix && issues.iter().any(LintIssue::is_fixable) {
+            if let Some(fixed) = fix_diary(&markdown, &file_name(path)) {
+                fs::write(path, &fixed)
+                    .with_context(|| format!("Failed to write diary {}", path.display()))?;
+                println!("🔧 Fixed {}", path.display());
+                issues = lint_diary(&fixed);
+            }
+        }
+
+        for issue in &issues {
+            let hint = if issue.is_fixable() {
+                " (fixable with --fix)"
+            } else {
+                ""
+            };
+            println!("{}: {}{}", path.display(), issue, hint);
+        }
+        remaining += issues.len();
+    }
+
+    Ok(remaining)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn empty_section(heading: &str) -> Section {
+    Section {
+        heading: heading.to_string(),
+        body: String::new(),
+    }
+}
+
+fn title_from_file_name(file_name: &str) -> String {
+    let dates = file_name
+        .strip_prefix("git-diary-")
+        .and_then(|rest| rest.strip_suffix(".md"))
+        .and_then(|rest| rest.split_once("-to-"))
+        .and_then(|(start, end)| Some((format_compact_date(start)?, format_compact_date(end)?)));
+
+    match dates {
+        Some((start, end)) => format!("Git Diary ({} – {})", start, end),
+        None => "Git Diary".to_string(),
+    }
+}
+
+/// Turns `20240107` into `2024-01-07`
+fn format_compact_date(date: &str) -> Option<String> {
+    if date.len() != 8 || !date.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const VALID_DIARY: &str = "# Git Diary (2024-01-01 – 2024-01-07)
+
+## Commit Logs
+
+- 2024-01-01 00:00:00: First commit
+
+
+## AI-generated Summary
+
+Worked on things.
+";
+
+    #[test]
+    fn test_lint_valid_diary() {
+        assert!(lint_diary(VALID_DIARY).is_empty());
+    }
+
+    #[test]
+    fn test_lint_reports_issues() {
+        let issues = lint_diary("## AI-generated Summary\n\n");
+
+        assert_eq!(
+            issues,
+            vec![
+                LintIssue::MissingTitle,
+                LintIssue::MissingSection(COMMIT_LOGS_HEADING),
+                LintIssue::EmptySummary,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_malformed_frontmatter() {
+        let issues = lint_diary(&format!("---\nrepo: git-diary\n{}", VALID_DIARY));
+
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0], LintIssue::MalformedFrontmatter(_)));
+        assert!(!issues[0].is_fixable());
+    }
+
+    #[test]
+    fn test_fix_diary() {
+        let fixed = fix_diary(
+            "## AI-generated Summary\n\nWorked on things.\n",
+            "git-diary-20240101-to-20240107.md",
+        )
+        .unwrap();
+
+        assert!(fixed.starts_with("# Git Diary (2024-01-01 – 2024-01-07)\n\n## Commit Logs\n\n"));
+        assert!(fixed.contains("## AI-generated Summary\n\nWorked on things."));
+        assert!(lint_diary(&fixed).is_empty());
+    }
+
+    #[test]
+    fn test_fix_diary_leaves_valid_diary_alone() {
+        assert_eq!(
+            fix_diary(VALID_DIARY, "git-diary-20240101-to-20240107.md"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_lint_files_fix() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("git-diary-20240101-to-20240107.md");
+        fs::write(&path, "## Commit Logs\n\n- First commit\n")?;
+
+        // Missing title and summary are fixed, but the summary is still empty
+        let remaining = lint_files(std::slice::from_ref(&path), true)?;
+
+        assert_eq!(remaining, 1);
+        assert_eq!(
+            lint_diary(&fs::read_to_string(&path)?),
+            vec![LintIssue::EmptySummary]
+        );
+
+        Ok(())
+    }
+}
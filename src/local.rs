@@ -0,0 +1,250 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::process::Command;
+
+use crate::domain::{AISummarizer, HighlightsRequest, ProviderPing, SummaryRequest};
+use crate::prompt::{
+    build_highlights_prompt, build_user_prompt, system_prompt, DETERMINISTIC_SEED,
+    HIGHLIGHTS_SYSTEM_PROMPT,
+};
+
+/// llama.cpp's command-line program, looked up on `PATH`
+pub const DEFAULT_LLAMA_CLI: &str = "llama-cli";
+
+/// Marker llama.cpp prints once the model stops on its own
+const END_OF_TEXT: &str = "[end of text]";
+
+/// Summarizes with a GGUF model on this machine through llama.cpp, without a
+/// server or network access
+///
+/// llama.cpp is not bundled: its `llama-cli` program must be installed and
+/// on `PATH`, or set with `with_program`.
+///
+/// The one model file answers every request, so model routing has no effect.
+pub struct LocalSummarizer {
+    model_path: PathBuf,
+    program: String,
+    max_tokens: u32,
+    deterministic: bool,
+}
+
+impl LocalSummarizer {
+    pub fn new(model_path: PathBuf, max_tokens: u32) -> Self {
+        Self {
+            model_path,
+            program: DEFAULT_LLAMA_CLI.to_string(),
+            max_tokens,
+            deterministic: false,
+        }
+    }
+
+    /// Runs `program` instead of `llama-cli`, e.g. a build outside `PATH`
+    pub fn with_program(mut self, program: String) -> Self {
+        self.program = program;
+        self
+    }
+
+    /// Samples at temperature 0 with a fixed seed
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// File name of the model without its extension
+    pub fn model_name(&self) -> String {
+        self.model_path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("local")
+            .to_string()
+    }
+
+    /// Generates a completion for the prompts with llama.cpp
+    async fn complete(&self, system_prompt: String, user_prompt: String) -> Result<String> {
+        let prompt = format!("{}\n\n{}", system_prompt, user_prompt);
+        eprintln!("⏳ Generating summary with {} locally", self.model_name());
+        self.run(&prompt, self.max_tokens).await
+    }
+
+    /// Runs llama.cpp on `prompt`, returning at most `max_tokens` of completion
+    async fn run(&self, prompt: &str, max_tokens: u32) -> Result<String> {
+        if !self.model_path.is_file() {
+            bail!("Model file {} doesn't exist", self.model_path.display());
+        }
+
+        // Large diffs make prompts longer than a single argument may be, so
+        // llama.cpp reads the prompt from a file
+        let mut prompt_file =
+            tempfile::NamedTempFile::new().context("Failed to create the prompt file")?;
+        prompt_file
+            .write_all(prompt.as_bytes())
+            .context("Failed to write the prompt file")?;
+        let args = llama_args(
+            &self.model_path,
+            prompt_file.path(),
+            max_tokens,
+            self.deterministic,
+        );
+        let output = match Command::new(&self.program).args(&args).output().await {
+            Ok(output) => output,
+            Err(e) if e.kind() == ErrorKind::NotFound => bail!(
+                "{} not found; install llama.cpp to summarize with a local model",
+                self.program
+            ),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to run {}", self.program));
+            }
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let last_line = stderr.lines().last().unwrap_or_default();
+            bail!("{} failed ({}): {}", self.program, output.status, last_line);
+        }
+
+        let summary = clean_output(&String::from_utf8_lossy(&output.stdout));
+        if summary.is_empty() {
+            return Ok("No content".to_string());
+        }
+        Ok(summary)
+    }
+}
+
+#[async_trait]
+impl AISummarizer for LocalSummarizer {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String> {
+        self.complete(system_prompt(request.length), build_user_prompt(request))
+            .await
+    }
+
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String> {
+        self.complete(
+            HIGHLIGHTS_SYSTEM_PROMPT.to_string(),
+            build_highlights_prompt(request),
+        )
+        .await
+    }
+
+    /// Loads the model and generates one token
+    async fn ping(&self) -> Result<ProviderPing> {
+        let started = Instant::now();
+        self.run("ping", 1).await?;
+        Ok(ProviderPing {
+            model: self.model_name(),
+            latency_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+}
+
+/// Arguments for a single, non-interactive llama.cpp run that prints only the completion
+fn llama_args(
+    model_path: &Path,
+    prompt_file: &Path,
+    max_tokens: u32,
+    deterministic: bool,
+) -> Vec<String> {
+    let mut args = vec![
+        "--model".to_string(),
+        model_path.display().to_string(),
+        "--file".to_string(),
+        prompt_file.display().to_string(),
+        "--n-predict".to_string(),
+        max_tokens.to_string(),
+        "--no-display-prompt".to_string(),
+        "--no-conversation".to_string(),
+        "--log-disable".to_string(),
+    ];
+    if deterministic {
+        args.extend([
+            "--temp".to_string(),
+            "0".to_string(),
+            "--seed".to_string(),
+            DETERMINISTIC_SEED.to_string(),
+        ]);
+    }
+    args
+}
+
+/// The completion without the end-of-text marker and surrounding whitespace
+fn clean_output(stdout: &str) -> String {
+    stdout
+        .trim()
+        .trim_end_matches(END_OF_TEXT)
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Commit;
+
+    #[test]
+    fn test_llama_args() {
+        let args = llama_args(
+            Path::new("models/qwen.gguf"),
+            Path::new("/tmp/prompt.txt"),
+            250,
+            true,
+        );
+
+        assert_eq!(
+            &args[..4],
+            ["--model", "models/qwen.gguf", "--file", "/tmp/prompt.txt"]
+        );
+        assert!(args.contains(&"--no-conversation".to_string()));
+        assert!(args.ends_with(&["--seed".to_string(), "42".to_string()]));
+        assert!(
+            !llama_args(Path::new("m.gguf"), Path::new("p.txt"), 250, false)
+                .contains(&"--seed".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_long_prompt_goes_through_a_file() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let model = temp_dir.path().join("model.gguf");
+        std::fs::write(&model, "")?;
+        // Stands in for llama-cli and echoes the size of the prompt file
+        let program = temp_dir.path().join("llama-cli");
+        std::fs::write(&program, "#!/bin/sh\nwc -c < \"$4\"\n")?;
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755))?;
+        let summarizer =
+            LocalSummarizer::new(model, 250).with_program(program.to_string_lossy().to_string());
+
+        // Well over the 128 KiB a single argument may take on Linux
+        let prompt = "x".repeat(300 * 1024);
+        assert_eq!(summarizer.run(&prompt, 1).await?, prompt.len().to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_clean_output() {
+        assert_eq!(
+            clean_output("\nFixed the parser. [end of text]\n"),
+            "Fixed the parser."
+        );
+        assert_eq!(clean_output("  \n"), "");
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_file() {
+        let summarizer = LocalSummarizer::new(PathBuf::from("missing.gguf"), 250);
+        assert_eq!(summarizer.model_name(), "missing");
+
+        let error = summarizer
+            .summarize_commits(&SummaryRequest::new(vec![Commit::new(
+                "Fix parser".to_string(),
+                0,
+            )]))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("missing.gguf"));
+    }
+}
@@ -1,36 +1,807 @@
-use anyhow::Result;
-use async_openai::Client;
-use chrono::{DateTime, Duration, Local};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+#[cfg(feature = "server")]
+use async_trait::async_trait;
+use chrono::{DateTime, Days, Local, Months, NaiveDate, NaiveTime, Weekday};
+use clap::builder::RangedU64ValueParser;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+#[cfg(feature = "server")]
+use serde::Deserialize;
+#[cfg(feature = "server")]
+use serde_json::{json, Value};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::Arc;
+use std::time::Duration;
 
-// Declare modules
-mod ai;
-mod domain;
-mod git;
-mod storage;
+use git_diary::ai::{self, AISummarizerImpl, TokenUsage};
+#[cfg(feature = "archive")]
+use git_diary::archive::{self, ConflictPolicy};
+#[cfg(feature = "forge")]
+use git_diary::auth::{self, CredentialResolver, Forge};
+use git_diary::config::{Config, DEFAULT_CONFIG_FILE};
+use git_diary::diary_repo::{DiaryRepoStorage, DEFAULT_REMOTE};
+use git_diary::domain::{
+    self, AISummarizer, CommitOrder, DateOrder, DateRange, DateTimeProvider, DiaryGenerator,
+    DiaryPreview, DiaryStorage, GitRepository, Granularity, HighlightsRequest, MessageSource, Note,
+    PullRequestResolver, ReviewSource, RollupSettings, SendConfirmation, SummaryRequest,
+};
+use git_diary::empty::EmptyMessagePolicy;
+use git_diary::export::ExportedRepositoryImpl;
+use git_diary::feedback;
+use git_diary::formatter::{
+    ConfluenceFormatter, CsvFormatter, DiaryFormatter, HtmlFormatter, HtmlTheme, MarkdownFormatter,
+    SUMMARY_HEADING,
+};
+use git_diary::git::{self, CommitSource, GitRepositoryImpl, ImageAttachments};
+use git_diary::glossary::{self, GlossaryStore};
+use git_diary::goals;
+use git_diary::highlights;
+use git_diary::identity::Identities;
+use git_diary::ledger::{self, EntryKind, Feedback, Ledger, LedgerEntry, LedgerTotals, Rating};
+use git_diary::lint;
+use git_diary::middleware::SummarizerExt;
+use git_diary::multirepo::MultiRepository;
+use git_diary::notes::NoteStore;
+use git_diary::pathfilter::PathFilter;
+use git_diary::paths::AppPaths;
+use git_diary::postprocess::PostProcessPipeline;
+#[cfg(feature = "server")]
+use git_diary::preview::{
+    PreviewServer, PreviewSource, DEFAULT_POLL_SECONDS, DEFAULT_PREVIEW_PORT,
+};
+use git_diary::pricing::{context_window, estimate_cost, MIN_CONTEXT_TOKENS};
+use git_diary::prompt::SummaryLength;
+#[cfg(feature = "forge")]
+use git_diary::pulls::{
+    BitbucketCommitSource, ForgePullRequestResolver, GitHubReviewSource, RemoteRepository,
+};
+use git_diary::queue::{QueuedRun, RunQueue};
+use git_diary::regen::{self, RegenSection};
+use git_diary::rewrite::RewritePipeline;
+#[cfg(feature = "server")]
+use git_diary::rpc::{self, InvalidParams, RpcHandler, UnknownMethod};
+use git_diary::scan::SensitiveScan;
+use git_diary::schedule::{self, ScheduledRun, Scheduler};
+use git_diary::sessions::DEFAULT_SESSION_GAP_MINUTES;
+#[cfg(feature = "schema")]
+use git_diary::sidecar;
+use git_diary::state::StateStore;
+use git_diary::storage::{DiaryStorageImpl, TrackedStorage};
+use git_diary::terminal;
+use git_diary::testruns::JunitReports;
+use git_diary::textlog::{self, TextLogRepository, TEXT_LOG_CONTEXT};
+#[cfg(feature = "forge")]
+use git_diary::tickets::{GitHubProjectsSource, TicketTracker};
+#[cfg(feature = "tui")]
+use git_diary::tui::{self, Browser, BrowserAction};
+use git_diary::worktime::WorkHours;
 
-// Import necessary types from modules
-use ai::AISummarizerImpl;
-use domain::{DateTimeProvider, DiaryGenerator};
-use git::GitRepositoryImpl;
-use storage::DiaryStorageImpl;
+/// Where a command runs: the working directory and the platform directories
+///
+/// `main` builds it from the process; tests point it at fixtures.
+struct Env {
+    cwd: PathBuf,
+    paths: AppPaths,
+}
+
+impl Env {
+    fn from_process() -> Result<Self> {
+        let cwd = std::env::current_dir()?;
+        let mut paths = AppPaths::resolve(&cwd)?;
+        if let Some(git_dir) = git::git_dir(&cwd.to_string_lossy()) {
+            paths = paths.with_git_dir(&git_dir);
+        }
+        Ok(Self { cwd, paths })
+    }
+
+    /// Stores diaries in `--output` when given, relative to the working
+    /// directory; a leading `.git` is the repository's git directory, even in
+    /// worktrees where `.git` is a file
+    fn with_output(mut self, output: Option<&Path>) -> Self {
+        let Some(output) = output else {
+            return self;
+        };
+        let git_dir = git::git_dir(&self.repo_path());
+        let dir = match (output.strip_prefix(".git"), git_dir) {
+            (Ok(rest), Some(git_dir)) => git_dir.join(rest),
+            _ => self.cwd.join(output),
+        };
+        self.paths = self.paths.with_output_dir(dir);
+        self
+    }
+
+    /// The repository path recorded in the ledger and run state
+    fn repo_path(&self) -> String {
+        self.cwd.to_string_lossy().to_string()
+    }
+
+    /// Loads `--config`, else `.git-diary.toml` in the working directory,
+    /// else the user-wide config
+    fn load_config(&self, explicit: Option<&Path>) -> Result<Config> {
+        let local_config = self.cwd.join(DEFAULT_CONFIG_FILE);
+        let user_config = self.paths.config_file();
+        let path = match explicit {
+            Some(path) => Some(path.to_path_buf()),
+            None if local_config.exists() => Some(local_config),
+            None if user_config.exists() => Some(user_config),
+            None => None,
+        };
+        Config::load(path.as_deref())
+    }
+
+    /// Storage for diaries of the current repository
+    fn diary_storage(&self) -> DiaryStorageImpl {
+        DiaryStorageImpl::new(self.paths.diary_dir().to_string_lossy().to_string())
+    }
+
+    /// Manual notes waiting for the next diary
+    fn note_store(&self) -> NoteStore {
+        NoteStore::new(self.paths.notes_file().to_string_lossy().to_string())
+    }
+
+    fn ledger(&self, config: &Config) -> Ledger {
+        Ledger::new(
+            config
+                .ledger
+                .clone()
+                .unwrap_or_else(|| self.paths.ledger_file()),
+        )
+    }
+
+    /// Where tokens from the OAuth device flow are cached
+    #[cfg(feature = "forge")]
+    fn credential_cache_path(&self) -> PathBuf {
+        self.paths.credentials_file()
+    }
+
+    /// Open goals listed in `goals.md`; none when the file doesn't exist
+    fn goals_from_file(&self) -> Result<Vec<String>> {
+        let path = self.paths.goals_file();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let markdown = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(goals::parse_goals(&markdown))
+    }
+}
 
 /// Generate a diary from Git commits using AI summarization
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
+#[command(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Config file to use instead of `.git-diary.toml` in the current directory or
+    /// `config.toml` in the user config directory
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Directory to store and look up diaries in; `.git/diary/` keeps them
+    /// inside the git directory, where later runs find them without this option
+    #[arg(long, global = true, value_name = "DIR")]
+    output: Option<PathBuf>,
+
+    /// Options for the default `generate` command
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a diary (the default when no subcommand is given)
+    Generate(GenerateArgs),
+    /// Check stored diaries for broken structure
+    Lint(LintArgs),
+    /// Show or set up credentials for forge integrations
+    #[cfg(feature = "forge")]
+    Auth(AuthArgs),
+    /// Add a manual note to the next diary
+    Note(NoteArgs),
+    /// Rate a generated diary; summaries rated good guide future ones
+    Feedback(FeedbackArgs),
+    /// Summarize a plain-text work log, such as meeting notes, instead of commits
+    SummarizeFile(SummarizeFileArgs),
+    /// Recompute one section of a stored diary, keeping manual edits to the others
+    Regen(RegenArgs),
+    /// Print a stored diary with its Markdown rendered for the terminal
+    Show(ShowArgs),
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// List generated diaries with their token usage and cost
+    List(ListArgs),
+    /// Summarize token usage and estimated spend for a month
+    Costs(CostsArgs),
+    /// Delete or archive old diaries and ledger entries
+    Prune(PruneArgs),
+    /// Bundle the diaries, their sidecars and ledger entries into a .tar.gz
+    #[cfg(feature = "archive")]
+    ExportArchive(ExportArchiveArgs),
+    /// Restore diaries and ledger entries from an `export-archive` bundle
+    #[cfg(feature = "archive")]
+    ImportArchive(ImportArchiveArgs),
+    /// Turn months of diaries into resume-style bullet points
+    Highlights(HighlightsArgs),
+    /// Browse stored diaries in a terminal UI
+    #[cfg(feature = "tui")]
+    Browse(BrowseArgs),
+    /// Show where config, diaries, ledger and state are stored
+    Paths(PathsArgs),
+    /// List the provider's models and check the configured one
+    Models(ModelsArgs),
+    /// Send the provider a one-token request to check credentials, model and latency
+    Ping(PingArgs),
+    /// Print the JSON schema of the `--sidecar-json` output
+    #[cfg(feature = "schema")]
+    Schema,
+    /// Answer JSON-RPC requests on stdin and stdout, for editor extensions
+    #[cfg(feature = "server")]
+    Serve(ServeArgs),
+    /// List runs queued with `--queue-offline`, or generate them once back online
+    Queue(QueueArgs),
+    /// Generate a diary every day at a set time, or have the system scheduler do it
+    Watch(WatchArgs),
+}
+
+#[derive(Args)]
+struct GenerateArgs {
     /// Number of days to look back for commits
     #[arg(short, long, default_value_t = 1)]
     days: i64,
+
+    /// Calendar period to cover instead of a number of days
+    #[arg(short, long, value_enum, conflicts_with = "days")]
+    period: Option<Period>,
+
+    /// Cover everything since the last diary of this repository in the ledger,
+    /// so scheduled runs never miss or repeat commits
+    #[arg(long, conflicts_with_all = ["days", "period"])]
+    since_last: bool,
+
+    /// First day of the week used by week periods
+    #[arg(long, default_value = "monday")]
+    week_start: Weekday,
+
+    /// Maximum number of commits sent to the AI; older commits beyond this are left out
+    #[arg(long, default_value_t = domain::DEFAULT_MAX_COMMITS,
+          value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    max_commits: usize,
+
+    /// Read commits from a `git fast-export` stream or bundle file instead of the current repository
+    #[arg(long, value_name = "FILE")]
+    from_export: Option<String>,
+
+    /// Read commits from the Bitbucket Cloud or Server repository `origin` points to,
+    /// through its API, instead of the local clone
+    #[arg(long, conflicts_with = "from_export")]
+    from_forge: bool,
+
+    /// Output format of the saved diary
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    format: Format,
+
+    /// Color scheme of `--format html` diaries
+    #[arg(long, value_enum, default_value_t = HtmlTheme::Light)]
+    theme: HtmlTheme,
+
+    /// Also write a `.json` file with commits, stats, tags and the summary next to the diary
+    #[arg(long)]
+    sidecar_json: bool,
+
+    /// Ask the model for topic tags, kept in the sidecar; JSON from models without
+    /// structured output support is repaired, or read as a plain `Topics:` line
+    #[arg(long)]
+    ai_tags: bool,
+
+    /// Length of the AI summary
+    #[arg(short, long, value_enum, default_value_t = SummaryLength::Medium)]
+    length: SummaryLength,
+
+    /// Language of the summary, e.g. `ja`, or `auto` to use the language of the commit messages
+    #[arg(long, value_name = "LANG", conflicts_with = "bilingual")]
+    language: Option<String>,
+
+    /// Write the summary in two or more languages, e.g. `en,ja`; one section per language
+    #[arg(long, value_name = "LANGS", value_delimiter = ',', num_args = 1..)]
+    bilingual: Vec<String>,
+
+    /// Reproducible output for snapshot tests: temperature 0, a fixed seed and stable commit order
+    #[arg(long)]
+    deterministic: bool,
+
+    /// Add a "Reviews" section with the pull requests you reviewed on GitHub in the period
+    #[arg(long)]
+    reviews: bool,
+
+    /// What to do with commits that have no message; overrides `empty_messages` in the config
+    #[arg(long, value_enum, value_name = "POLICY")]
+    empty_messages: Option<EmptyMessagePolicy>,
+
+    /// Summarize squash merges from the commit message or their pull request's
+    /// description; overrides `message_source` in the config
+    #[arg(long, value_enum, value_name = "SOURCE")]
+    message_source: Option<MessageSource>,
+
+    /// Add a "Repo health" paragraph: TODO/FIXME, test file and build config changes in the period
+    #[arg(long)]
+    health: bool,
+
+    /// Copy images that commits added and mention into the diary folder and embed them
+    #[arg(long)]
+    attach_images: bool,
+
+    /// Let the summary prompt quote up to N tokens of commit diffs, shared
+    /// between commits by diff size; overrides `diff_budget_tokens` in the config
+    #[arg(long, value_name = "N")]
+    diff_budget_tokens: Option<u32>,
+
+    /// Add a note on the share of commits made after 22:00 or on weekends, in each author's time zone
+    #[arg(long)]
+    work_hours: bool,
+
+    /// Which commit date places commits in the period; `author` keeps rebased work on the day it was written
+    #[arg(long, value_enum, default_value_t = DateOrder::Committer)]
+    date_order: DateOrder,
+
+    /// Order of the commit log: oldest first, newest first, or most lines changed first
+    #[arg(long, value_enum, default_value_t = CommitOrder::Chronological)]
+    order: CommitOrder,
+
+    /// Read commits from the reflog (what you did), history (what exists) or both
+    #[arg(long, value_enum, default_value_t = CommitSource::Reflog)]
+    source: CommitSource,
+
+    /// Write one diary per branch with commits in the period, plus an index linking them
+    #[arg(long)]
+    per_branch: bool,
+
+    /// Write one diary per day of the period, each with its own summary, instead of one combined diary
+    #[arg(long, conflicts_with = "per_branch")]
+    split_days: bool,
+
+    /// Work that left no commits, such as "3 hours in design review"; repeatable
+    #[arg(long, value_name = "TEXT")]
+    context: Vec<String>,
+
+    /// Show a desktop notification when the diary is saved, e.g. for runs from cron
+    #[arg(long)]
+    notify: bool,
+
+    /// Add a one-line summary of each working session, such as "Morning: fixed flaky auth tests"
+    #[arg(long)]
+    sessions: bool,
+
+    /// Minutes without a commit that end a working session
+    #[arg(long, value_name = "MINUTES", default_value_t = DEFAULT_SESSION_GAP_MINUTES, requires = "sessions")]
+    session_gap: i64,
+
+    /// Show the estimated tokens and cost and ask before sending anything to the AI
+    #[arg(long)]
+    confirm: bool,
+
+    /// Answer yes to --confirm, for scripts and other runs without a terminal
+    #[arg(long, requires = "confirm")]
+    yes: bool,
+
+    /// Only include commits touching paths matching this glob, such as "src/payments/**";
+    /// a leading `!` excludes, as in "!**/tests/**". Can be given several times
+    #[arg(long = "paths", value_name = "GLOB", allow_hyphen_values = true)]
+    paths: Vec<String>,
+
+    /// Only include your own commits, matched by `user.email`/`user.name` from git config
+    /// and the `identities` in the config file
+    #[arg(long)]
+    mine: bool,
+
+    /// Scan commit messages for emails, tokens and internal hostnames before summarizing
+    #[arg(long)]
+    scan_sensitive: bool,
+
+    /// Send commit messages even if the scan finds sensitive data
+    #[arg(long)]
+    allow_sensitive: bool,
+
+    /// Mask emails, tokens and internal hostnames instead of sending them to the model
+    #[arg(long)]
+    redact_sensitive: bool,
+
+    /// Where summaries come from: the API in the config, or a model file on this machine
+    #[arg(long, value_enum, default_value_t = Provider::Openai)]
+    provider: Provider,
+
+    /// GGUF model file for `--provider local`
+    #[arg(long, value_name = "PATH", required_if_eq("provider", "local"))]
+    model_path: Option<PathBuf>,
+
+    /// Add the diaries directory to `.gitignore` without asking when it's inside the repository
+    #[arg(long)]
+    gitignore_diaries: bool,
+
+    /// Print the commits and the prompt without sending or saving anything
+    #[arg(long)]
+    preview: bool,
+
+    /// Commit the new diary when the diaries directory is inside the repository
+    #[arg(long, conflicts_with = "gitignore_diaries")]
+    commit_diary: bool,
+
+    /// When the AI provider can't be reached, queue the run and generate it in
+    /// the background once back online, e.g. for hooks and scheduled runs
+    #[arg(long, conflicts_with = "preview")]
+    queue_offline: bool,
+
+    /// Give up on generating after this many seconds; as with Ctrl-C, files
+    /// already saved are removed and the run is recorded as aborted
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+}
+
+impl GenerateArgs {
+    fn sensitive_scan(&self, config: &Config) -> SensitiveScan {
+        if !(self.scan_sensitive || config.scan_sensitive) {
+            SensitiveScan::Off
+        } else if self.allow_sensitive || self.redact_sensitive {
+            // Redacted messages are safe to send, the scan only reports findings
+            SensitiveScan::Allow
+        } else {
+            SensitiveScan::Block
+        }
+    }
+
+    /// Languages to write the summary in, the main one first
+    fn languages(&self) -> Vec<String> {
+        match &self.language {
+            Some(language) => vec![language.clone()],
+            None => self.bilingual.clone(),
+        }
+    }
+}
+
+#[derive(Args)]
+struct LintArgs {
+    /// Diary files to check; defaults to every diary in the diaries directory
+    files: Vec<PathBuf>,
+
+    /// Repair fixable issues in place
+    #[arg(long)]
+    fix: bool,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// Only list diaries of this repository path or export file
+    #[arg(long)]
+    repo: Option<String>,
+}
+
+#[derive(Args)]
+struct CostsArgs {
+    /// Month to report on; the current month when omitted
+    #[arg(long, value_name = "YYYY-MM", value_parser = parse_month)]
+    month: Option<String>,
+}
+
+/// Accepts `YYYY-MM`, as used in ledger reports
+fn parse_month(value: &str) -> Result<String, String> {
+    chrono::NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d")
+        .map(|date| date.format("%Y-%m").to_string())
+        .map_err(|_| format!("expected a month like 2024-05, got {:?}", value))
+}
+
+#[derive(Args)]
+struct PruneArgs {
+    /// Keep diaries that ended within this many days; defaults to `retention.keep_days`
+    #[arg(long)]
+    keep_days: Option<u32>,
+
+    /// Move old diaries to an `archive` folder in the diary directory instead of deleting them
+    #[arg(long)]
+    archive: bool,
+
+    /// Only list what would be pruned
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[cfg(feature = "archive")]
+#[derive(Args)]
+struct ExportArchiveArgs {
+    /// Archive to write, such as `diaries-2024.tar.gz`
+    #[arg(long, value_name = "FILE")]
+    out: PathBuf,
+}
+
+#[cfg(feature = "archive")]
+#[derive(Args)]
+struct ImportArchiveArgs {
+    /// Archive written by `git-diary export-archive`
+    archive: PathBuf,
+
+    /// What to do when a diary by the same name but with other contents exists
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Rename)]
+    on_conflict: ConflictPolicy,
+}
+
+#[derive(Args)]
+struct PathsArgs {
+    /// Move files from the `diaries/` and `~/.git-diary` locations older versions used
+    #[arg(long)]
+    migrate: bool,
+}
+
+#[derive(Args)]
+struct ModelsArgs {
+    /// Context size in tokens a model needs to be marked as suitable
+    #[arg(long, default_value_t = MIN_CONTEXT_TOKENS)]
+    min_context: u32,
+}
+
+#[derive(Args)]
+struct PingArgs {
+    /// Print the result as a JSON object, for scripts
+    #[arg(long)]
+    json: bool,
+}
+
+#[cfg(feature = "server")]
+#[derive(Args)]
+struct ServeArgs {
+    /// Serve the latest diary as HTML on localhost instead, regenerating it on
+    /// refresh and when new commits land
+    #[arg(long)]
+    preview: bool,
+
+    /// Port of the preview server
+    #[arg(long, default_value_t = DEFAULT_PREVIEW_PORT, requires = "preview")]
+    port: u16,
+
+    /// How often the preview checks for new commits, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_POLL_SECONDS, requires = "preview")]
+    poll: u64,
+
+    /// Options of `git-diary generate` for the previewed diary, after `--`
+    #[arg(last = true, requires = "preview")]
+    generate: Vec<String>,
+}
+
+#[derive(Args)]
+struct QueueArgs {
+    /// Generate the queued runs, waiting for the AI provider to be reachable,
+    /// until the queue is empty
+    #[arg(long)]
+    work: bool,
+
+    /// How often to check whether the AI provider can be reached, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_QUEUE_POLL_SECONDS, requires = "work")]
+    poll: u64,
+}
+
+#[derive(Args)]
+struct WatchArgs {
+    /// Local time of the daily run
+    #[arg(long, value_name = "HH:MM", default_value = "18:00", value_parser = parse_time)]
+    at: NaiveTime,
+
+    /// Register the daily run with the system scheduler instead, so it survives
+    /// reboots: a systemd user timer, a launchd agent or a Scheduled Task
+    #[arg(long, conflicts_with = "uninstall")]
+    install: bool,
+
+    /// Remove the daily run of this repository from the system scheduler
+    #[arg(long)]
+    uninstall: bool,
+
+    /// Options of each run, after `--`, such as `-- --format html`; defaults to `--since-last`
+    #[arg(last = true, value_name = "GENERATE_OPTIONS")]
+    generate: Vec<String>,
+}
+
+/// Accepts `HH:MM`, as used by `watch --at`
+fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .map_err(|_| format!("expected a time like 18:00, got {:?}", value))
+}
+
+#[derive(Args)]
+struct HighlightsArgs {
+    /// Number of months to look back
+    #[arg(long, default_value_t = 6)]
+    months: u32,
+
+    /// Mine raw commits even when stored diaries cover the period
+    #[arg(long)]
+    from_commits: bool,
+
+    /// Maximum number of commits to send when mining raw commits
+    #[arg(long, default_value_t = domain::DEFAULT_MAX_COMMITS,
+          value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    max_commits: usize,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Args)]
+struct BrowseArgs {
+    /// Options used when regenerating a diary from the browser
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+#[cfg(feature = "forge")]
+#[derive(Args)]
+struct AuthArgs {
+    /// Forge to check; all forges when omitted
+    #[arg(value_enum)]
+    forge: Option<Forge>,
+
+    /// Log in through the OAuth device flow and cache the token
+    #[arg(long, requires = "forge")]
+    login: bool,
+}
+
+#[derive(Args)]
+struct NoteArgs {
+    /// Text of the note, e.g. "Paired with Alice on the cache bug"
+    #[arg(required = true)]
+    text: Vec<String>,
+}
+
+#[derive(Args)]
+struct FeedbackArgs {
+    /// Diary file to rate, as listed by `git-diary list`
+    file: PathBuf,
+
+    /// Whether the summary was what you wanted
+    #[arg(value_enum)]
+    rating: Rating,
+
+    /// What was good or what to do differently
+    #[arg(long)]
+    note: Option<String>,
+}
+
+#[derive(Args)]
+struct SummarizeFileArgs {
+    /// Text log with one activity per line, or blocks starting with a time like `09:30`
+    file: PathBuf,
+
+    /// Day of times without a date, until a date line sets another; defaults to today
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    date: Option<NaiveDate>,
+
+    /// Output format of the saved diary
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    format: Format,
+
+    /// Color scheme of `--format html` diaries
+    #[arg(long, value_enum, default_value_t = HtmlTheme::Light)]
+    theme: HtmlTheme,
+
+    /// Length of the AI summary
+    #[arg(short, long, value_enum, default_value_t = SummaryLength::Medium)]
+    length: SummaryLength,
+}
+
+#[derive(Args)]
+struct ShowArgs {
+    /// Markdown diary to print; defaults to the latest one in the diaries directory
+    file: Option<PathBuf>,
+
+    /// Print the Markdown as stored instead of rendering it
+    #[arg(long)]
+    raw: bool,
+}
+
+#[derive(Args)]
+struct RegenArgs {
+    /// Markdown diary to update, along with its `.json` sidecar when there is one
+    file: PathBuf,
+
+    /// Section to recompute
+    #[arg(long, value_enum, default_value_t = RegenSection::Summary)]
+    section: RegenSection,
+
+    /// Length of the new AI summary
+    #[arg(short, long, value_enum, default_value_t = SummaryLength::Medium)]
+    length: SummaryLength,
+
+    /// Where the new summary comes from: the API in the config, or a model file on this machine
+    #[arg(long, value_enum, default_value_t = Provider::Openai)]
+    provider: Provider,
+
+    /// GGUF model file for `--provider local`
+    #[arg(long, value_name = "PATH", required_if_eq("provider", "local"))]
+    model_path: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CompletionsArgs {
+    /// Shell to generate the script for
+    #[arg(value_enum)]
+    shell: Shell,
+}
+
+/// Named calendar periods
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Period {
+    Today,
+    Yesterday,
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+}
+
+impl Period {
+    fn resolve<D: DateTimeProvider>(self, provider: &D, week_start: Weekday) -> DateRange {
+        let today = provider.today();
+        let week = Granularity::Week(week_start);
+
+        match self {
+            Period::Today => provider.calendar_range(today, Granularity::Day),
+            Period::Yesterday => provider.calendar_range(today - Days::new(1), Granularity::Day),
+            Period::ThisWeek => DateRange::new(provider.start_of_week(week_start), provider.now()),
+            Period::LastWeek => provider.calendar_range(today - Days::new(7), week),
+            Period::ThisMonth => provider.calendar_range(today, Granularity::Month),
+            Period::LastMonth => {
+                provider.calendar_range(today - Months::new(1), Granularity::Month)
+            }
+        }
+    }
+}
+
+/// Backends that write the summaries
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Provider {
+    /// The OpenAI-compatible API from the `[ai]` config table
+    Openai,
+    /// A GGUF model run offline by llama.cpp; needs the `local-llm` feature
+    Local,
+}
+
+impl Provider {
+    /// Name recorded in the ledger
+    fn name(self) -> &'static str {
+        match self {
+            Provider::Openai => "openai",
+            Provider::Local => "local",
+        }
+    }
+}
+
+/// Diary output formats
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Format {
+    Markdown,
+    /// Confluence/Jira wiki markup
+    Confluence,
+    /// One row per commit with date, sha, author, message, files and line counts, for spreadsheets
+    Csv,
+    /// A single self-contained page with inlined CSS, for emailing or archiving
+    Html,
+}
+
+impl Format {
+    fn formatter(self, theme: HtmlTheme) -> Box<dyn DiaryFormatter> {
+        match self {
+            Format::Markdown => Box::new(MarkdownFormatter),
+            Format::Confluence => Box::new(ConfluenceFormatter),
+            Format::Csv => Box::new(CsvFormatter),
+            Format::Html => Box::new(HtmlFormatter::new(theme)),
+        }
+    }
 }
 
 // Simple DateTime provider implementation
-struct LocalDateTimeProvider;
+struct LocalDateTimeProvider {
+    day_cutoff_hour: u32,
+}
 
 impl LocalDateTimeProvider {
-    fn new() -> Self {
-        Self
+    fn new(day_cutoff_hour: u32) -> Self {
+        Self { day_cutoff_hour }
     }
 }
 
@@ -39,49 +810,2331 @@ impl DateTimeProvider for LocalDateTimeProvider {
         Local::now()
     }
 
-    fn days_ago(&self, days: i64) -> DateTime<Local> {
-        Local::now() - Duration::days(days)
+    fn day_cutoff_hour(&self) -> u32 {
+        self.day_cutoff_hour
+    }
+}
+
+/// Asks on the terminal before the summary requests are sent
+struct TerminalConfirmation {
+    summarizer: Arc<AISummarizerImpl>,
+    assume_yes: bool,
+}
+
+impl SendConfirmation for TerminalConfirmation {
+    fn confirm(&self, request: &SummaryRequest, times: usize) -> Result<bool> {
+        let estimate = self.summarizer.estimate(request).times(times);
+        println!(
+            "💰 About to summarize {} commit(s): {}",
+            request.commits.len(),
+            estimate
+        );
+        if self.assume_yes {
+            return Ok(true);
+        }
+        if !std::io::stdin().is_terminal() {
+            bail!("--confirm can't ask without a terminal; pass --yes to send anyway");
+        }
+
+        ask("Send it?")
     }
 }
 
+/// Asks a yes/no question on the terminal
+fn ask(question: &str) -> Result<bool> {
+    print!("{} [Y/n] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(is_yes(&answer))
+}
+
+/// An empty answer takes the default, yes
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
+}
+
+/// Exit code when the AI provider refused the request for lack of quota,
+/// so schedulers can tell it from failures worth retrying
+const QUOTA_EXHAUSTED_EXIT_CODE: u8 = 3;
+
+/// Exit code when the AI provider refused the credentials
+const AUTHENTICATION_FAILED_EXIT_CODE: u8 = 4;
+
+/// How often the queue worker checks whether the AI provider can be reached, in seconds
+const DEFAULT_QUEUE_POLL_SECONDS: u64 = 60;
+
+/// How long the AI provider may take to accept a connection before the
+/// machine counts as offline
+const REACHABILITY_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Parse command-line arguments
-    let args = Args::parse();
-
-    // Get current directory as repo path
-    let repo_path = std::env::current_dir()?.to_string_lossy().to_string();
-
-    // Create dependencies
-    let git_repo = Arc::new(GitRepositoryImpl::new(repo_path));
-    let ai_summarizer = Arc::new(AISummarizerImpl::new(
-        Client::new(),
-        "gpt-4".to_string(),
-        1000,
-    ));
-    let storage = Arc::new(DiaryStorageImpl::new("diaries".to_string()));
-    let datetime_provider = Arc::new(LocalDateTimeProvider::new());
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // Completions need neither the home directory nor a config, so they still
+    // work where either can't be found
+    if let Some(Command::Completions(args)) = &cli.command {
+        run_completions(args);
+        return ExitCode::SUCCESS;
+    }
+    let result = match Env::from_process() {
+        Ok(env) => {
+            let env = env.with_output(cli.output.as_deref());
+            run(cli, &env).await
+        }
+        Err(e) => Err(e),
+    };
 
-    // Create diary generator
-    let generator = DiaryGenerator::new(
-        git_repo,
-        ai_summarizer,
-        storage,
-        datetime_provider,
-        args.days, // Use the parsed number of days
-    );
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            if ai::is_quota_exhausted(&e) {
+                ExitCode::from(QUOTA_EXHAUSTED_EXIT_CODE)
+            } else if ai::is_authentication_failed(&e) {
+                ExitCode::from(AUTHENTICATION_FAILED_EXIT_CODE)
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+/// Runs one parsed command line in `env`
+async fn run(cli: Cli, env: &Env) -> Result<()> {
+    let config = env.load_config(cli.config.as_deref())?;
+
+    match cli.command {
+        Some(Command::Generate(args)) => run_generate_or_queue(env, &args, &config).await,
+        Some(Command::Lint(args)) => run_lint(env, &args),
+        #[cfg(feature = "forge")]
+        Some(Command::Auth(args)) => run_auth(env, &args, &config).await,
+        Some(Command::Note(args)) => run_note(env, &args),
+        Some(Command::Feedback(args)) => run_feedback(env, &args, &config),
+        Some(Command::SummarizeFile(args)) => run_summarize_file(env, &args, &config).await,
+        Some(Command::Regen(args)) => run_regen(env, &args, &config).await,
+        Some(Command::Show(args)) => run_show(env, &args),
+        Some(Command::Completions(args)) => {
+            run_completions(&args);
+            Ok(())
+        }
+        Some(Command::List(args)) => run_list(env, &args, &config),
+        Some(Command::Costs(args)) => run_costs(env, &args, &config),
+        Some(Command::Prune(args)) => run_prune(env, &args, &config),
+        #[cfg(feature = "archive")]
+        Some(Command::ExportArchive(args)) => run_export_archive(env, &args, &config),
+        #[cfg(feature = "archive")]
+        Some(Command::ImportArchive(args)) => run_import_archive(env, &args, &config),
+        Some(Command::Highlights(args)) => run_highlights(env, &args, &config).await,
+        #[cfg(feature = "tui")]
+        Some(Command::Browse(args)) => run_browse(env, &args, &config).await,
+        Some(Command::Paths(args)) => run_paths(env, &args, &config),
+        Some(Command::Models(args)) => run_models(&args, &config).await,
+        Some(Command::Ping(args)) => run_ping(&args, &config).await,
+        #[cfg(feature = "schema")]
+        Some(Command::Schema) => {
+            println!("{}", serde_json::to_string_pretty(&sidecar::json_schema())?);
+            Ok(())
+        }
+        #[cfg(feature = "server")]
+        Some(Command::Serve(args)) if args.preview => run_preview(env, &args, &config).await,
+        #[cfg(feature = "server")]
+        Some(Command::Serve(_)) => run_serve(env, &config).await,
+        Some(Command::Queue(args)) => run_queue(env, &args).await,
+        Some(Command::Watch(args)) => {
+            run_watch(env, &args, cli.config.as_deref(), cli.output.as_deref()).await
+        }
+        None => run_generate_or_queue(env, &cli.generate, &config).await,
+    }
+}
+
+/// Generates a diary for `range`, or for the period the arguments select when `None`
+async fn run_generate(
+    env: &Env,
+    args: &GenerateArgs,
+    config: &Config,
+    range: Option<DateRange>,
+) -> Result<Generated> {
+    // Get current directory as repo path, unless reading exported history
+    let source = match &args.from_export {
+        Some(path) => path.clone(),
+        None => env.repo_path(),
+    };
 
-    // Generate diary
-    match generator.generate_diary().await {
-        Ok(file_path) => {
+    let state = StateStore::new(env.paths.state_file().to_string_lossy().to_string());
+    if let Some(last_run) = state.last_run(&source)? {
+        if let Some(last_run) = DateTime::from_timestamp(last_run, 0) {
+            println!(
+                "🕒 Last diary for this repository was generated at {}",
+                last_run.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            );
+        }
+    }
+
+    // Pick the commit source
+    let result = match &args.from_export {
+        Some(_) => {
+            let git_repo = Arc::new(ExportedRepositoryImpl::new(source.clone()));
+            generate(env, args, config, range, &source, git_repo).await
+        }
+        #[cfg(feature = "forge")]
+        None if args.from_forge => {
+            let git_repo = Arc::new(forge_commit_source(env, config)?);
+            generate(env, args, config, range, &source, git_repo).await
+        }
+        #[cfg(not(feature = "forge"))]
+        None if args.from_forge => {
+            bail!("--from-forge needs git-diary built with the `forge` feature")
+        }
+        None => {
+            let repository = |path: String| {
+                GitRepositoryImpl::new(path)
+                    .with_rename_similarity(config.rename_similarity)
+                    .with_source(args.source)
+                    .with_diffs(diff_budget_tokens(args, config) > 0)
+            };
+            if config.repos.is_empty() {
+                generate(
+                    env,
+                    args,
+                    config,
+                    range,
+                    &source,
+                    Arc::new(repository(source.clone())),
+                )
+                .await
+            } else {
+                let mut repositories =
+                    MultiRepository::new().with_repository(Arc::new(repository(source.clone())));
+                for path in &config.repos {
+                    repositories = repositories.with_repository(Arc::new(repository(
+                        env.cwd.join(path).to_string_lossy().to_string(),
+                    )));
+                }
+                generate(env, args, config, range, &source, Arc::new(repositories)).await
+            }
+        }
+    };
+
+    match &result {
+        Ok(Generated::Diary(file_path)) => {
+            state.record_run(&source, Local::now().timestamp())?;
             println!("✨ Successfully generated diary!");
             println!("📝 File saved to: {}", file_path);
         }
-        Err(e) => {
-            eprintln!("❌ Error generating diary: {}", e);
-            return Err(e);
+        Ok(Generated::Preview(preview)) => {
+            println!(
+                "👀 Preview for {} – {}: {} commit(s); nothing was sent or saved",
+                preview.start_date,
+                preview.end_date,
+                preview.commits.len()
+            );
+            println!("{}", preview.prompt);
+        }
+        Err(e) => eprintln!("❌ Error generating diary: {}", e),
+    }
+
+    result
+}
+
+/// Generates a diary, or with `--queue-offline` queues the run while the AI
+/// provider can't be reached
+async fn run_generate_or_queue(env: &Env, args: &GenerateArgs, config: &Config) -> Result<()> {
+    let offline = || {
+        args.queue_offline
+            && matches!(args.provider, Provider::Openai)
+            && !config.ai.is_reachable(REACHABILITY_TIMEOUT)
+    };
+    if !offline() {
+        match run_generate(env, args, config, None).await {
+            Ok(_) => return Ok(()),
+            // The connection may have dropped while generating
+            Err(e) if !offline() => return Err(e),
+            Err(_) => {}
+        }
+    }
+    queue_run(env, args, config)
+}
+
+/// Waits for Ctrl-C, SIGTERM on Unix, or `timeout` to pass, returning what
+/// stopped the run
+async fn interruption(timeout: Option<Duration>) -> String {
+    let timed_out = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    tokio::select! {
+        signal = shutdown_signal() => format!("interrupted by {}", signal),
+        // `Duration`'s debug output reads "300s"
+        _ = timed_out => format!("timed out after {:?}", timeout.unwrap_or_default()),
+    }
+}
+
+/// Waits for Ctrl-C, or SIGTERM on Unix, returning the signal's name; never
+/// returns when the handlers can't be installed
+async fn shutdown_signal() -> &'static str {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
         }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => "SIGINT",
+        _ = terminate => "SIGTERM",
+    }
+}
+
+/// Removes the diaries an interrupted run saved, and their sidecars, returning
+/// how many files were removed
+fn remove_saved(paths: &[String], sidecar_json: bool) -> usize {
+    let mut removed = 0;
+    for path in paths.iter().map(Path::new) {
+        let sidecar = sidecar_json.then(|| path.with_extension("json"));
+        for file in std::iter::once(path.to_path_buf()).chain(sidecar) {
+            match std::fs::remove_file(&file) {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("⚠️ Failed to remove {}: {}", file.display(), e),
+            }
+        }
+    }
+    removed
+}
+
+/// Queues the run for the period it would cover now and starts the
+/// background worker, unless one is already working through the queue
+fn queue_run(env: &Env, args: &GenerateArgs, config: &Config) -> Result<()> {
+    let source = match &args.from_export {
+        Some(path) => path.clone(),
+        None => env.repo_path(),
+    };
+    let provider = LocalDateTimeProvider::new(config.day_cutoff_hour);
+    let range = selected_range(args, &env.ledger(config), &source, &provider)?;
+
+    let queue = RunQueue::new(env.paths.queue_file());
+    let waiting = queue.push(QueuedRun {
+        repo: env.cwd.clone(),
+        args: std::env::args_os()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect(),
+        start: range.start.timestamp(),
+        end: range.end.timestamp(),
+        queued_at: Local::now().timestamp(),
+    })?;
+    println!(
+        "📥 The AI provider can't be reached; queued the diary for {} – {} ({} run(s) waiting)",
+        range.start.format("%Y-%m-%d %H:%M"),
+        range.end.format("%Y-%m-%d %H:%M"),
+        waiting
+    );
+
+    // The worker runs until the queue is empty; a new one replaces any that crashed
+    if !queue.has_worker()? {
+        spawn_queue_worker(env)?;
+        println!(
+            "🔁 It will be generated in the background once back online; the log is at {}",
+            env.paths.queue_log_file().display()
+        );
+    }
+    Ok(())
+}
+
+/// Starts `git-diary queue --work` in the background, logging to the queue log
+fn spawn_queue_worker(env: &Env) -> Result<()> {
+    let log_path = env.paths.queue_log_file();
+    if let Some(dir) = log_path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
     }
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .with_context(|| format!("Failed to open {}", log_path.display()))?;
 
+    std::process::Command::new(std::env::current_exe()?)
+        .args(["queue", "--work"])
+        .stdin(std::process::Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
+        .context("Failed to start the queue worker")?;
     Ok(())
 }
+
+/// Lists the queued runs, or with `--work` generates them in order whenever
+/// the AI provider can be reached, until none are left
+async fn run_queue(env: &Env, args: &QueueArgs) -> Result<()> {
+    let queue = RunQueue::new(env.paths.queue_file());
+    if !args.work {
+        let pending = queue.pending()?;
+        if pending.is_empty() {
+            println!("📭 No runs are queued");
+        }
+        for run in &pending {
+            println!(
+                "{}  {} – {}  git-diary {}",
+                run.repo.display(),
+                format_timestamp(run.start),
+                format_timestamp(run.end),
+                run.args.join(" ")
+            );
+        }
+        return Ok(());
+    }
+
+    let Some(mut worker) = queue.claim_worker()? else {
+        println!("🔁 Another worker is already generating the queued runs");
+        return Ok(());
+    };
+    let poll = Duration::from_secs(args.poll.max(1));
+    loop {
+        work_through_queue(&queue, poll).await?;
+        // A run queued just before the queue emptied saw this worker alive,
+        // so look again once the lock is released
+        drop(worker);
+        if queue.pending()?.is_empty() {
+            break;
+        }
+        match queue.claim_worker()? {
+            Some(lock) => worker = lock,
+            None => return Ok(()),
+        }
+    }
+    println!("✅ All queued runs were generated");
+    Ok(())
+}
+
+/// Generates the queued runs in order whenever the AI provider can be
+/// reached, until none are left
+async fn work_through_queue(queue: &RunQueue, poll: Duration) -> Result<()> {
+    while let Some(run) = queue.pending()?.into_iter().next() {
+        let (run_env, run_args, run_config) = match queued_generate(&run) {
+            Ok(generate) => generate,
+            Err(e) => {
+                eprintln!(
+                    "❌ Dropped the queued run in {}: {:#}",
+                    run.repo.display(),
+                    e
+                );
+                queue.remove(&run)?;
+                continue;
+            }
+        };
+        if !run_config.ai.is_reachable(REACHABILITY_TIMEOUT) {
+            tokio::time::sleep(poll).await;
+            continue;
+        }
+
+        println!(
+            "📤 Generating the queued diary for {} in {}",
+            format_timestamp(run.start),
+            run.repo.display()
+        );
+        let range = DateRange::new(local_time(run.start)?, local_time(run.end)?);
+        match run_generate(&run_env, &run_args, &run_config, Some(range)).await {
+            Ok(_) => queue.remove(&run)?,
+            // Offline again; the run is retried once the provider is back
+            Err(_) if !run_config.ai.is_reachable(REACHABILITY_TIMEOUT) => {}
+            Err(e) => {
+                eprintln!(
+                    "❌ Dropped the queued run in {}: {:#}",
+                    run.repo.display(),
+                    e
+                );
+                queue.remove(&run)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Runs `git-diary generate` in the repository every day at `--at`, or
+/// registers or removes that run with the system scheduler
+async fn run_watch(
+    env: &Env,
+    args: &WatchArgs,
+    config: Option<&Path>,
+    output: Option<&Path>,
+) -> Result<()> {
+    let run = ScheduledRun::new(
+        std::env::current_exe()?,
+        watch_run_args(args, config, output)?,
+        env.cwd.clone(),
+        args.at,
+    );
+
+    if args.install || args.uninstall {
+        let scheduler = Scheduler::for_platform()?;
+        if args.uninstall {
+            if scheduler.uninstall(&run.name())? {
+                println!("🗑️  Removed the {} {}", scheduler.description(), run.name());
+            } else {
+                println!("📭 No daily run is registered for {}", env.cwd.display());
+            }
+            return Ok(());
+        }
+        let run = run.with_environment(schedule::current_credentials());
+        if !run
+            .environment
+            .iter()
+            .any(|(name, _)| name == "OPENAI_API_KEY")
+        {
+            eprintln!(
+                "⚠️  OPENAI_API_KEY isn't set, so the scheduled run can only use a local model"
+            );
+        }
+        for path in scheduler.install(&run)? {
+            println!("📝 {}", path.display());
+        }
+        println!(
+            "⏰ Registered the {} {}: git-diary {} at {} every day",
+            scheduler.description(),
+            run.name(),
+            run.args.join(" "),
+            args.at.format("%H:%M")
+        );
+        return Ok(());
+    }
+
+    loop {
+        let now = Local::now();
+        let next = schedule::next_run(&now, args.at);
+        println!(
+            "⏰ Next diary at {}; press Ctrl-C to stop",
+            next.format("%Y-%m-%d %H:%M")
+        );
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        let status = tokio::process::Command::new(&run.program)
+            .args(&run.args)
+            .current_dir(&run.repo)
+            .status()
+            .await
+            .context("Failed to start git-diary")?;
+        if !status.success() {
+            eprintln!(
+                "⚠️ The daily run failed ({}); trying again tomorrow",
+                status
+            );
+        }
+    }
+}
+
+/// The command line of each `watch` run, checked up front so a typo doesn't
+/// only show up in tomorrow's run
+fn watch_run_args(
+    args: &WatchArgs,
+    config: Option<&Path>,
+    output: Option<&Path>,
+) -> Result<Vec<String>> {
+    let mut run_args = vec!["generate".to_string()];
+    if let Some(config) = config {
+        run_args.push("--config".to_string());
+        run_args.push(std::path::absolute(config)?.to_string_lossy().to_string());
+    }
+    if let Some(output) = output {
+        run_args.push("--output".to_string());
+        run_args.push(output.to_string_lossy().to_string());
+    }
+    if args.generate.is_empty() {
+        run_args.push("--since-last".to_string());
+    } else {
+        run_args.extend(args.generate.iter().cloned());
+    }
+
+    let argv = std::iter::once("git-diary".to_string()).chain(run_args.iter().cloned());
+    if let Err(e) = Cli::try_parse_from(argv) {
+        let message = e.to_string();
+        bail!(
+            "Invalid options for the daily run: {}",
+            message
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim_start_matches("error: ")
+        );
+    }
+    Ok(run_args)
+}
+
+/// The environment, options and config of a queued run, read in the
+/// directory it was started in so relative paths resolve as they did then
+fn queued_generate(run: &QueuedRun) -> Result<(Env, GenerateArgs, Config)> {
+    std::env::set_current_dir(&run.repo)
+        .with_context(|| format!("Failed to enter {}", run.repo.display()))?;
+    let argv = std::iter::once("git-diary".to_string()).chain(run.args.iter().cloned());
+    let cli = Cli::try_parse_from(argv)?;
+    let env = Env::from_process()?.with_output(cli.output.as_deref());
+    let config = env.load_config(cli.config.as_deref())?;
+    let args = match cli.command {
+        Some(Command::Generate(args)) => args,
+        None => cli.generate,
+        Some(_) => bail!("Only diary runs can be queued"),
+    };
+    Ok((env, args, config))
+}
+
+fn local_time(timestamp: i64) -> Result<DateTime<Local>> {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.with_timezone(&Local))
+        .with_context(|| format!("Invalid timestamp {}", timestamp))
+}
+
+fn format_timestamp(timestamp: i64) -> String {
+    match local_time(timestamp) {
+        Ok(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+/// What a `generate` run produced
+enum Generated {
+    /// Path of the saved diary, or of the index of `--per-branch` diaries
+    Diary(String),
+    /// The commits and prompt of a `--preview` run
+    Preview(DiaryPreview),
+}
+
+async fn generate<G: GitRepository>(
+    env: &Env,
+    args: &GenerateArgs,
+    config: &Config,
+    range: Option<DateRange>,
+    source: &str,
+    git_repo: Arc<G>,
+) -> Result<Generated> {
+    // Create dependencies; usage and cost are only tracked for the API
+    let (backend, ai_summarizer): (Arc<dyn AISummarizer>, Option<Arc<AISummarizerImpl>>) =
+        match args.provider {
+            Provider::Openai => {
+                let ai_summarizer = Arc::new(
+                    config
+                        .ai
+                        .summarizer(args.length.max_tokens())?
+                        .with_deterministic(args.deterministic),
+                );
+                (ai_summarizer.clone(), Some(ai_summarizer))
+            }
+            Provider::Local => (
+                local_summarizer(
+                    args.model_path.as_deref(),
+                    args.length.max_tokens(),
+                    args.deterministic,
+                )?,
+                None,
+            ),
+        };
+    let summarizer: Arc<dyn AISummarizer> = if args.redact_sensitive {
+        Arc::new(backend.redacted())
+    } else {
+        backend
+    };
+    let ledger = env.ledger(config);
+    let local_storage = env
+        .diary_storage()
+        .with_formatter(args.format.formatter(args.theme))
+        .with_sidecar_json(args.sidecar_json);
+    let storage: Arc<dyn DiaryStorage> = match &config.diary_repo.path {
+        Some(repo_path) => {
+            let mut storage = DiaryRepoStorage::new(
+                local_storage,
+                repo_path.clone(),
+                env.paths.repo_name().to_string(),
+            );
+            if config.diary_repo.push {
+                let remote = config
+                    .diary_repo
+                    .remote
+                    .as_deref()
+                    .unwrap_or(DEFAULT_REMOTE);
+                storage = storage.with_push(remote.to_string());
+                #[cfg(feature = "forge")]
+                {
+                    storage = storage.with_credentials(CredentialResolver::new(
+                        config.auth.clone(),
+                        env.credential_cache_path(),
+                    ));
+                }
+            }
+            Arc::new(storage)
+        }
+        None => Arc::new(local_storage),
+    };
+    // Remembers what was saved, so an interrupted run can remove it again
+    let storage = Arc::new(TrackedStorage::new(storage));
+    let datetime_provider = Arc::new(LocalDateTimeProvider::new(config.day_cutoff_hour));
+    let range = match range {
+        Some(range) => range,
+        None => selected_range(args, &ledger, source, datetime_provider.as_ref())?,
+    };
+
+    let notes = env.note_store().pending()?;
+    let glossary_store = GlossaryStore::new(env.paths.glossary_file());
+    let glossary = glossary::merge_glossary(&config.glossary, &glossary_store.learned_terms()?);
+    let goals = goals::merge_goals(&config.goals, &env.goals_from_file()?);
+
+    // Create diary generator
+    let mut generator = DiaryGenerator::new(
+        git_repo,
+        summarizer,
+        storage.clone() as Arc<dyn DiaryStorage>,
+        datetime_provider,
+        args.days, // Use the parsed number of days
+    )
+    .with_max_commits(args.max_commits)
+    .with_length(args.length)
+    .with_deterministic(args.deterministic)
+    .with_sensitive_scan(args.sensitive_scan(config))
+    .with_languages(args.languages())
+    .with_topics(args.ai_tags)
+    .with_output_check(Arc::new(
+        env.diary_storage()
+            .with_formatter(args.format.formatter(args.theme)),
+    ))
+    .with_model_routing(config.ai.routing.clone())
+    .with_squash_expansion(config.expand_squash_merges.unwrap_or(true))
+    .with_dependency_grouping(config.group_dependency_updates.unwrap_or(true))
+    .with_empty_message_policy(args.empty_messages.unwrap_or(config.empty_messages))
+    .with_rewrites(RewritePipeline::new(&config.rewrite)?)
+    .with_post_processing(PostProcessPipeline::new(&config.post_process)?)
+    .with_notes(notes.clone())
+    .with_context(args.context.clone())
+    .with_date_order(args.date_order)
+    .with_commit_order(args.order)
+    .with_glossary(glossary)
+    .with_goals(goals)
+    .with_examples(feedback::good_examples(&ledger.entries()?));
+    if let Some(budget) = &config.prompt_budget {
+        generator = generator.with_prompt_budget(budget.clone());
+    }
+    match diff_budget_tokens(args, config) {
+        0 => {}
+        _ if args.from_export.is_some() => {
+            eprintln!("⚠️ --diff-budget-tokens needs a repository, not an export file")
+        }
+        tokens => generator = generator.with_diff_budget(tokens),
+    }
+    if !args.paths.is_empty() {
+        generator = generator.with_path_filter(PathFilter::new(&args.paths)?);
+    }
+    let mut identities = Identities::parse(&config.identities)?;
+    if args.mine {
+        identities = identities.led_by(git::configured_identity(&env.repo_path())?);
+        if identities.is_empty() {
+            bail!("--mine needs user.email or user.name set in git config, or identities in the config file");
+        }
+        generator = generator.with_author_filter(identities.filter());
+    }
+    if !identities.is_empty() {
+        generator = generator.with_identities(identities);
+    }
+    if args.sessions {
+        generator = generator.with_session_summaries(args.session_gap);
+    }
+    if args.confirm {
+        match &ai_summarizer {
+            Some(ai_summarizer) => {
+                generator = generator.with_confirmation(Arc::new(TerminalConfirmation {
+                    summarizer: ai_summarizer.clone(),
+                    assume_yes: args.yes,
+                }));
+            }
+            None => eprintln!("⚠️ --confirm is skipped: a local model costs nothing to run"),
+        }
+    }
+    if args.notify || config.notify {
+        #[cfg(feature = "notify")]
+        {
+            generator = generator.with_notifier(Arc::new(git_diary::notify::DesktopNotifier));
+        }
+        #[cfg(not(feature = "notify"))]
+        eprintln!("⚠️ Desktop notifications need git-diary built with the `notify` feature");
+    }
+    let message_source = args.message_source.unwrap_or(config.message_source);
+    let resolver = match args.from_export {
+        None => pull_request_resolver(env, config)?,
+        Some(_) => None,
+    };
+    match resolver {
+        Some(resolver) => {
+            generator = generator
+                .with_pull_request_resolver(resolver)
+                .with_message_source(message_source);
+        }
+        None if message_source == MessageSource::PullRequest => {
+            eprintln!("⚠️ Pull request descriptions need forge credentials for origin; see `git-diary auth`")
+        }
+        None => {}
+    }
+    if args.health {
+        match args.from_export {
+            None => {
+                generator = generator
+                    .with_health_source(Arc::new(GitRepositoryImpl::new(source.to_string())));
+            }
+            Some(_) => eprintln!("⚠️ --health needs a repository, not an export file"),
+        }
+    }
+    if args.attach_images {
+        match args.from_export {
+            None if args.from_forge => {
+                eprintln!(
+                    "⚠️ --attach-images needs commits from the local repository, not --from-forge"
+                )
+            }
+            None => {
+                generator = generator.with_attachment_source(Arc::new(ImageAttachments::new(
+                    source.to_string(),
+                    env.paths.diary_dir(),
+                )));
+            }
+            Some(_) => eprintln!("⚠️ --attach-images needs a repository, not an export file"),
+        }
+    }
+    if !config.test_reports.is_empty() {
+        generator = generator.with_test_run_source(Arc::new(JunitReports::new(
+            env.cwd.clone(),
+            config.test_reports.clone(),
+        )));
+    }
+    if args.work_hours || config.work_hours {
+        generator = generator.with_work_hours(true);
+    }
+    #[cfg(feature = "timetrack")]
+    if let Some(source) = config.time_tracking.source()? {
+        generator = generator.with_coding_time_source(source);
+    }
+    if args.reviews {
+        if let Some(source) = review_source(env, config)? {
+            generator = generator.with_review_source(source);
+        }
+    }
+    #[cfg(feature = "forge")]
+    if let Some(source) = ticket_source(env, config)? {
+        generator = generator.with_ticket_source(source);
+    }
+    // A daily diary has no week to roll up
+    if !args.split_days && matches!(args.period, Some(Period::ThisWeek | Period::LastWeek)) {
+        generator = generator.with_week_rollup(RollupSettings {
+            numbering: config.week_numbering,
+            locale: config.locale(),
+        });
+    }
+
+    if args.preview {
+        return Ok(Generated::Preview(
+            generator.preview_for_range(&range).await?,
+        ));
+    }
+    let (start_date, end_date) = generator.date_labels(&range);
+    let end_time = range.end.min(Local::now()).timestamp();
+    if let Some(previous) = ledger.find_duplicate(source, &start_date, &end_date)? {
+        println!(
+            "⚠️ A diary for {} – {} was already generated for this repository: {}",
+            start_date, end_date, previous.path
+        );
+    }
+
+    // Generate diary, one per day, or one per branch and the index linking them
+    let generation = async {
+        if args.per_branch {
+            let (index_path, diaries) = generator.generate_branch_diaries_for_range(range).await?;
+            let paths = diaries.into_iter().map(|diary| diary.path).collect();
+            Ok::<_, anyhow::Error>((index_path, paths))
+        } else if args.split_days {
+            // The ledger records the run under the most recent day's diary
+            let paths = generator.generate_daily_diaries_for_range(range).await?;
+            Ok((paths.last().cloned().unwrap_or_default(), paths))
+        } else {
+            let file_path = generator.generate_diary_for_range(range).await?;
+            Ok((file_path.clone(), vec![file_path]))
+        }
+    };
+    let run_usage = || match &ai_summarizer {
+        Some(ai_summarizer) => (
+            ai_summarizer.models_used(),
+            ai_summarizer.usage(),
+            ai_summarizer.estimated_cost(),
+        ),
+        None => (
+            model_file_name(args.model_path.as_deref()),
+            TokenUsage::default(),
+            Some(0.0),
+        ),
+    };
+    let ledger_entry = |path: String, aborted: Option<String>| {
+        let (model, usage, cost) = run_usage();
+        LedgerEntry {
+            generated_at: Local::now().timestamp(),
+            repo: source.to_string(),
+            start_date: start_date.clone(),
+            end_date: end_date.clone(),
+            end_time: Some(end_time),
+            path,
+            provider: args.provider.name().to_string(),
+            model,
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            cost,
+            feedback: None,
+            aborted,
+            kind: EntryKind::Diary,
+        }
+    };
+
+    // Dropping the generation on a signal or timeout cancels the AI request in flight
+    let (file_path, diary_paths) = tokio::select! {
+        generated = generation => generated?,
+        reason = interruption(args.timeout.map(Duration::from_secs)) => {
+            let saved = storage.saved();
+            let removed = remove_saved(&saved, args.sidecar_json);
+            ledger.append(&ledger_entry(String::new(), Some(reason.clone())))?;
+            // Commits in the diary repository may already be pushed, so they
+            // are left for the user to revert
+            let left_behind = match &config.diary_repo.path {
+                Some(repo_path) if !saved.is_empty() => format!(
+                    "; the copies already committed to the diary repository {} were left there",
+                    repo_path.display()
+                ),
+                _ => String::new(),
+            };
+            bail!(
+                "Run {}; removed {} local file(s) it had saved{}, and recorded it in the ledger",
+                reason,
+                removed,
+                left_behind
+            );
+        }
+    };
+
+    let period = format!("{} – {}", start_date, end_date);
+    ledger.append(&ledger_entry(file_path.clone(), None))?;
+
+    // The notes are part of this diary now
+    env.note_store().remove(&notes)?;
+
+    // The diary is saved, so failing to learn from it only warrants a warning
+    for path in &diary_paths {
+        if let Err(e) = glossary_store.learn_from_diary(Path::new(path)) {
+            eprintln!("⚠️ Failed to update the glossary: {}", e);
+        }
+    }
+
+    if args.from_export.is_none() {
+        let mut written = diary_paths.clone();
+        if !written.contains(&file_path) {
+            written.push(file_path.clone());
+        }
+        if args.sidecar_json {
+            let sidecars: Vec<String> = written
+                .iter()
+                .map(|path| Path::new(path).with_extension("json"))
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+            written.extend(sidecars);
+        }
+        if let Err(e) = keep_diaries_out_of_history(env, args, config, &written, &period) {
+            eprintln!("⚠️ Failed to update git for the diaries directory: {}", e);
+        }
+    }
+
+    Ok(Generated::Diary(file_path))
+}
+
+/// When the diaries directory is inside the repository, commits the new diary
+/// with `--commit-diary`, or otherwise offers to add the directory to `.gitignore`
+fn keep_diaries_out_of_history(
+    env: &Env,
+    args: &GenerateArgs,
+    config: &Config,
+    written: &[String],
+    period: &str,
+) -> Result<()> {
+    let repo_path = env.repo_path();
+    let Some(diary_dir) = git::path_in_work_tree(&repo_path, &env.paths.diary_dir())? else {
+        if args.commit_diary {
+            eprintln!("⚠️ --commit-diary only commits diaries stored in the work tree");
+        }
+        return Ok(());
+    };
+
+    if args.commit_diary {
+        let mut files = Vec::new();
+        for path in written {
+            if let Some(file) = git::path_in_work_tree(&repo_path, Path::new(path))? {
+                files.push(file);
+            }
+        }
+        let message = format!("Add git-diary entry for {}", period);
+        let hash = git::commit_files(&repo_path, &files, &message)?;
+        println!("📦 Committed diary as {}", hash);
+        return Ok(());
+    }
+
+    if config.gitignore_diaries == Some(false) || git::is_ignored(&repo_path, &diary_dir)? {
+        return Ok(());
+    }
+    let ignore = if args.gitignore_diaries || config.gitignore_diaries == Some(true) {
+        true
+    } else if std::io::stdin().is_terminal() {
+        ask(&format!(
+            "{}/ is inside the repository. Add it to .gitignore?",
+            diary_dir.display()
+        ))?
+    } else {
+        eprintln!(
+            "⚠️ {}/ is inside the repository; pass --gitignore-diaries to add it to .gitignore or --commit-diary to commit diaries",
+            diary_dir.display()
+        );
+        false
+    };
+    if ignore {
+        git::ignore_directory(&repo_path, &diary_dir)?;
+        println!("🙈 Added {}/ to .gitignore", diary_dir.display());
+    }
+    Ok(())
+}
+
+/// The period the arguments select: since the last diary, a calendar
+/// period, or the last `--days`
+fn selected_range<D: DateTimeProvider>(
+    args: &GenerateArgs,
+    ledger: &Ledger,
+    source: &str,
+    provider: &D,
+) -> Result<DateRange> {
+    if args.since_last {
+        if let Some(range) = since_last_range(ledger, source, provider.now())? {
+            return Ok(range);
+        }
+    }
+    Ok(match args.period {
+        Some(period) => period.resolve(provider, args.week_start),
+        None => DateRange::new(provider.days_ago(args.days), provider.now()),
+    })
+}
+
+/// Range from where the last diary of `source` ended up to `now`
+///
+/// `None` when the ledger has no diary for `source` yet, so the default range is used.
+fn since_last_range(
+    ledger: &Ledger,
+    source: &str,
+    now: DateTime<Local>,
+) -> Result<Option<DateRange>> {
+    let Some(last) = ledger.latest_for(source)? else {
+        println!("🆕 No earlier diary for this repository in the ledger; using the default range");
+        return Ok(None);
+    };
+
+    let start = DateTime::from_timestamp(last.covered_until(), 0)
+        .map(|start| start.with_timezone(&Local))
+        .unwrap_or(now);
+    if start >= now {
+        bail!(
+            "The last diary ({}) already covers everything up to now",
+            last.path
+        );
+    }
+    println!(
+        "⏩ Continuing from the last diary, which ended at {}",
+        start.format("%Y-%m-%d %H:%M:%S")
+    );
+
+    Ok(Some(DateRange::new(start, now)))
+}
+
+fn run_note(env: &Env, args: &NoteArgs) -> Result<()> {
+    let note = Note::new(args.text.join(" "), Local::now().timestamp());
+    env.note_store().add(note)?;
+
+    println!("📌 Note saved; it will be included in the next diary");
+    Ok(())
+}
+
+/// Summarizes a text log through the same summarizer, filters and storage
+/// as commits, without reading the repository
+async fn run_summarize_file(env: &Env, args: &SummarizeFileArgs, config: &Config) -> Result<()> {
+    let text = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("Failed to read {}", args.file.display()))?;
+    let date = args.date.unwrap_or_else(|| Local::now().date_naive());
+    let items = textlog::parse_text_log(&text, &Local, date)?;
+    let day_of = |timestamp: i64| local_time(timestamp).map(|time| time.date_naive());
+    let start_date = day_of(
+        items
+            .iter()
+            .map(|item| item.timestamp())
+            .min()
+            .unwrap_or_default(),
+    )?;
+    let end_date = day_of(
+        items
+            .iter()
+            .map(|item| item.timestamp())
+            .max()
+            .unwrap_or_default(),
+    )?;
+    println!(
+        "📄 Summarizing {} item(s) from {}",
+        items.len(),
+        args.file.display()
+    );
+
+    let ai_summarizer = Arc::new(config.ai.summarizer(args.length.max_tokens())?);
+    let storage = env
+        .diary_storage()
+        .with_formatter(args.format.formatter(args.theme));
+    let generator = DiaryGenerator::new(
+        Arc::new(TextLogRepository::new(items.clone())),
+        ai_summarizer.clone(),
+        Arc::new(storage),
+        Arc::new(LocalDateTimeProvider::new(config.day_cutoff_hour)),
+        1,
+    )
+    .with_length(args.length)
+    .with_rewrites(RewritePipeline::new(&config.rewrite)?)
+    .with_post_processing(PostProcessPipeline::new(&config.post_process)?)
+    .with_context(vec![TEXT_LOG_CONTEXT.to_string()])
+    .with_output_check(Arc::new(
+        env.diary_storage()
+            .with_formatter(args.format.formatter(args.theme)),
+    ));
+    let file_path = generator
+        .generate_from_commits(items, start_date.to_string(), end_date.to_string())
+        .await?;
+
+    let usage = ai_summarizer.usage();
+    env.ledger(config).append(&LedgerEntry {
+        generated_at: Local::now().timestamp(),
+        repo: args.file.canonicalize()?.to_string_lossy().to_string(),
+        start_date: start_date.to_string(),
+        end_date: end_date.to_string(),
+        end_time: None,
+        path: file_path.clone(),
+        provider: Provider::Openai.name().to_string(),
+        model: ai_summarizer.models_used(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        cost: ai_summarizer.estimated_cost(),
+        feedback: None,
+        aborted: None,
+        kind: EntryKind::Diary,
+    })?;
+
+    println!("✨ Successfully generated diary!");
+    println!("📝 File saved to: {}", file_path);
+    Ok(())
+}
+
+/// Prints a diary, rendered with styles only when stdout is a terminal and
+/// `NO_COLOR` isn't set
+fn run_show(env: &Env, args: &ShowArgs) -> Result<()> {
+    let path = match &args.file {
+        Some(file) => env.cwd.join(file),
+        None => match env.diary_storage().list_diaries()?.pop() {
+            Some(latest) => latest,
+            None => bail!("No diaries stored yet; run `git-diary` to generate one"),
+        },
+    };
+    let markdown = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read diary {}", path.display()))?;
+
+    if args.raw {
+        print!("{}", markdown);
+    } else {
+        let styled = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        print!("{}", terminal::render_markdown(&markdown, styled));
+    }
+    Ok(())
+}
+
+/// Recomputes one section of a Markdown diary and its sidecar; the commits
+/// come from the sidecar when there is one, as it keeps authors and time zones
+async fn run_regen(env: &Env, args: &RegenArgs, config: &Config) -> Result<()> {
+    let path = env.cwd.join(&args.file);
+    if path.extension().and_then(|extension| extension.to_str()) != Some("md") {
+        bail!(
+            "regen only edits Markdown diaries, which {} isn't",
+            args.file.display()
+        );
+    }
+    let markdown = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let sidecar_path = path.with_extension("json");
+    let mut sidecar: Option<serde_json::Value> = match std::fs::read_to_string(&sidecar_path) {
+        Ok(json) => Some(
+            serde_json::from_str(&json)
+                .with_context(|| format!("Invalid sidecar {}", sidecar_path.display()))?,
+        ),
+        Err(_) => None,
+    };
+    let commits = match &sidecar {
+        Some(sidecar) => regen::commits_from_sidecar(sidecar),
+        None => regen::commits_from_log(&markdown, &Local),
+    };
+    if commits.is_empty() {
+        bail!("{} lists no commits to recompute from", args.file.display());
+    }
+
+    let day_of = |timestamp: Option<i64>| {
+        local_time(timestamp.unwrap_or_default()).map(|time| time.date_naive().to_string())
+    };
+    let mut summary = None;
+    let updated = match args.section {
+        RegenSection::Summary => {
+            let mut request =
+                regen::summary_request(&markdown, &commits, domain::DEFAULT_MAX_COMMITS);
+            request.length = args.length;
+            let (new_summary, model, usage, cost) = match args.provider {
+                Provider::Openai => {
+                    let ai_summarizer = config.ai.summarizer(args.length.max_tokens())?;
+                    let new_summary = ai_summarizer.summarize_commits(&request).await?;
+                    let usage = ai_summarizer.usage();
+                    let cost = estimate_cost(
+                        ai_summarizer.model(),
+                        usage.prompt_tokens,
+                        usage.completion_tokens,
+                    );
+                    (new_summary, ai_summarizer.model().to_string(), usage, cost)
+                }
+                Provider::Local => {
+                    let local = local_summarizer(
+                        args.model_path.as_deref(),
+                        args.length.max_tokens(),
+                        false,
+                    )?;
+                    let new_summary = local.summarize_commits(&request).await?;
+                    (
+                        new_summary,
+                        model_file_name(args.model_path.as_deref()),
+                        TokenUsage::default(),
+                        Some(0.0),
+                    )
+                }
+            };
+            let new_summary = PostProcessPipeline::new(&config.post_process)?.apply(&new_summary);
+            println!("{}", new_summary);
+
+            // Recorded for its cost only, so `--since-last` still starts from the last diary
+            env.ledger(config).append(&LedgerEntry {
+                generated_at: Local::now().timestamp(),
+                repo: env.repo_path(),
+                start_date: day_of(commits.iter().map(|commit| commit.timestamp()).min())?,
+                end_date: day_of(commits.iter().map(|commit| commit.timestamp()).max())?,
+                end_time: None,
+                path: "regen".to_string(),
+                provider: args.provider.name().to_string(),
+                model,
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                cost,
+                feedback: None,
+                aborted: None,
+                kind: EntryKind::Regen,
+            })?;
+            let updated = regen::replace_section(&markdown, SUMMARY_HEADING, &new_summary)?;
+            summary = Some(new_summary);
+            updated
+        }
+        section => match regen::markdown_heading(section)
+            .filter(|heading| regen::section_span(&markdown, heading).is_some())
+        {
+            Some(heading) => regen::replace_section(
+                &markdown,
+                heading,
+                &WorkHours::from_commits(&commits).to_string(),
+            )?,
+            None if sidecar.is_none() => bail!(
+                "{} has no .json sidecar to recompute {} in; write one with --sidecar-json",
+                args.file.display(),
+                if section == RegenSection::Tags {
+                    "tags"
+                } else {
+                    "stats"
+                }
+            ),
+            None => markdown.clone(),
+        },
+    };
+
+    if updated != markdown {
+        std::fs::write(&path, &updated)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("📝 Updated {}", path.display());
+    }
+    if let Some(sidecar) = sidecar.as_mut() {
+        regen::update_sidecar(sidecar, args.section, &commits, summary.as_deref())?;
+        std::fs::write(&sidecar_path, serde_json::to_string_pretty(sidecar)?)
+            .with_context(|| format!("Failed to write {}", sidecar_path.display()))?;
+        println!("📝 Updated {}", sidecar_path.display());
+    }
+    Ok(())
+}
+
+fn run_feedback(env: &Env, args: &FeedbackArgs, config: &Config) -> Result<()> {
+    let feedback = Feedback {
+        rating: args.rating,
+        note: args.note.clone(),
+        rated_at: Local::now().timestamp(),
+    };
+    if env
+        .ledger(config)
+        .rate(&env.cwd.join(&args.file), feedback)?
+        .is_none()
+    {
+        bail!(
+            "{} is not in the ledger; only generated diaries can be rated",
+            args.file.display()
+        );
+    }
+
+    match args.rating {
+        Rating::Good => {
+            println!("👍 Rated good; it may be shown as an example to future summaries")
+        }
+        Rating::Bad => println!("👎 Rated bad"),
+    }
+    Ok(())
+}
+
+fn run_lint(env: &Env, args: &LintArgs) -> Result<()> {
+    let files = if args.files.is_empty() {
+        env.diary_storage().list_diaries()?
+    } else {
+        args.files.clone()
+    };
+
+    let remaining = lint::lint_files(&files, args.fix)?;
+    if remaining > 0 {
+        bail!("{} issue(s) found in stored diaries", remaining);
+    }
+
+    println!("✅ {} diaries checked, no issues found", files.len());
+    Ok(())
+}
+
+fn run_list(env: &Env, args: &ListArgs, config: &Config) -> Result<()> {
+    let mut entries = env.ledger(config).entries()?;
+    if let Some(repo) = &args.repo {
+        entries.retain(|entry| entry.repo == *repo);
+    }
+
+    for entry in &entries {
+        let generated_at = DateTime::from_timestamp(entry.generated_at, 0)
+            .map(|time| {
+                time.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let cost = entry
+            .cost
+            .map(|cost| format!(" ~${:.4}", cost))
+            .unwrap_or_default();
+        let path = match &entry.aborted {
+            Some(reason) => format!("(aborted: {})", reason),
+            None => entry.path.clone(),
+        };
+        println!(
+            "{}  {} – {}  {}  {}  {} tokens{}",
+            generated_at,
+            entry.start_date,
+            entry.end_date,
+            entry.repo,
+            path,
+            entry.prompt_tokens + entry.completion_tokens,
+            cost
+        );
+    }
+
+    println!("📊 {}", format_totals(&LedgerTotals::of(&entries)));
+
+    Ok(())
+}
+
+fn run_costs(env: &Env, args: &CostsArgs, config: &Config) -> Result<()> {
+    let month = args
+        .month
+        .clone()
+        .unwrap_or_else(|| Local::now().format("%Y-%m").to_string());
+    let mut entries = env.ledger(config).entries()?;
+    entries.retain(|entry| entry.month() == month);
+
+    println!("💰 Costs for {}", month);
+    for ((provider, model), totals) in ledger::totals_by_model(&entries) {
+        println!("  {} {}: {}", provider, model, format_totals(&totals));
+    }
+    println!("Total: {}", format_totals(&LedgerTotals::of(&entries)));
+
+    Ok(())
+}
+
+#[cfg(feature = "archive")]
+fn run_export_archive(env: &Env, args: &ExportArchiveArgs, config: &Config) -> Result<()> {
+    let exported = archive::export_archive(&args.out, &env.diary_storage(), &env.ledger(config))?;
+    println!(
+        "📦 Exported {} file(s) and {} ledger entries to {}",
+        exported.files,
+        exported.ledger_entries,
+        args.out.display()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "archive")]
+fn run_import_archive(env: &Env, args: &ImportArchiveArgs, config: &Config) -> Result<()> {
+    let diary_dir = env.paths.diary_dir();
+    let imported = archive::import_archive(
+        &args.archive,
+        &diary_dir,
+        &env.ledger(config),
+        args.on_conflict,
+    )?;
+    for path in &imported.written {
+        println!("📥 {}", path.display());
+    }
+    println!(
+        "✅ Restored {} file(s) and {} ledger entries into {}",
+        imported.written.len(),
+        imported.ledger_entries,
+        diary_dir.display()
+    );
+    if imported.unchanged > 0 {
+        println!("⏭️  {} diaries were already there", imported.unchanged);
+    }
+    if imported.renamed > 0 {
+        println!(
+            "⚠️ {} diaries were imported with an -imported suffix, as theirs differed",
+            imported.renamed
+        );
+    }
+    if imported.overwritten > 0 {
+        println!(
+            "⚠️ Overwrote {} diaries that differed",
+            imported.overwritten
+        );
+    }
+    if imported.skipped > 0 {
+        println!(
+            "⚠️ Left out {} diaries that differ from the ones already there",
+            imported.skipped
+        );
+    }
+    Ok(())
+}
+
+fn run_prune(env: &Env, args: &PruneArgs, config: &Config) -> Result<()> {
+    let Some(keep_days) = args.keep_days.or(config.retention.keep_days) else {
+        bail!("Pass --keep-days or set retention.keep_days in the config");
+    };
+    let archive = args.archive || config.retention.archive;
+
+    let today = Local::now().date_naive();
+    let cutoff = today - Days::new(keep_days.into());
+    let verb = match (args.dry_run, archive) {
+        (true, _) => "Would prune",
+        (false, true) => "Archived",
+        (false, false) => "Deleted",
+    };
+
+    let storage = env.diary_storage();
+    let old_diaries: Vec<_> = storage
+        .stored_diaries()?
+        .into_iter()
+        .filter(|diary| diary.end_date < cutoff)
+        .collect();
+    for diary in &old_diaries {
+        if !args.dry_run {
+            if archive {
+                storage.archive_diary(diary)?;
+            } else {
+                storage.delete_diary(diary)?;
+            }
+        }
+        println!("🗑️  {} {}", verb, diary.path.display());
+    }
+
+    let cutoff_timestamp =
+        domain::resolve_local(&Local, cutoff.and_time(NaiveTime::MIN)).timestamp();
+    let removed = env
+        .ledger(config)
+        .remove_older_than(cutoff_timestamp, args.dry_run)?;
+
+    println!(
+        "{} {} diary file(s) and {} ledger entries from before {}",
+        verb,
+        old_diaries.len(),
+        removed.len(),
+        cutoff
+    );
+
+    Ok(())
+}
+
+async fn run_models(args: &ModelsArgs, config: &Config) -> Result<()> {
+    let models = ai::list_models(&config.ai.client()?).await?;
+    let configured = config.ai.model();
+
+    println!("🤖 {} model(s) offered by the provider:", models.len());
+    for model in &models {
+        let (mark, context) = match context_window(model) {
+            Some(tokens) if tokens >= args.min_context => ("✅", format!("{} tokens", tokens)),
+            Some(tokens) => ("⚠️ ", format!("{} tokens, too small", tokens)),
+            None => ("❔", "context size unknown".to_string()),
+        };
+        let current = if model == configured {
+            " ← configured"
+        } else {
+            ""
+        };
+        println!("  {} {} ({}){}", mark, model, context, current);
+    }
+
+    ai::check_model(configured, &models)?;
+    println!("✅ The configured model {} is available", configured);
+    for rule in &config.ai.routing {
+        ai::check_model(&rule.model, &models)?;
+        println!("✅ The routed model {} is available", rule.model);
+    }
+    Ok(())
+}
+
+/// Checks the configured provider with a one-token request; failures still
+/// end with an error, so the exit code tells scripts what went wrong
+async fn run_ping(args: &PingArgs, config: &Config) -> Result<()> {
+    let (host, port) = config.ai.endpoint()?;
+    let requested = config.ai.model();
+    let result = config.ai.summarizer(1)?.ping().await;
+
+    if args.json {
+        let report = match &result {
+            Ok(ping) => serde_json::json!({
+                "ok": true,
+                "endpoint": format!("{}:{}", host, port),
+                "requested_model": requested,
+                "model": ping.model,
+                "latency_ms": ping.latency_ms,
+            }),
+            Err(e) => serde_json::json!({
+                "ok": false,
+                "endpoint": format!("{}:{}", host, port),
+                "requested_model": requested,
+                "authenticated": !ai::is_authentication_failed(e),
+                "quota_exhausted": ai::is_quota_exhausted(e),
+                "error": e.to_string(),
+            }),
+        };
+        println!("{}", report);
+    }
+
+    let ping = result?;
+    if !args.json {
+        println!("✅ {}:{} accepted the credentials", host, port);
+        if ping.model == requested {
+            println!("🤖 Model: {}", ping.model);
+        } else {
+            println!("🤖 Model: {} (configured as {})", ping.model, requested);
+        }
+        println!("⏱️ Latency: {} ms", ping.latency_ms);
+    }
+    Ok(())
+}
+
+fn run_paths(env: &Env, args: &PathsArgs, config: &Config) -> Result<()> {
+    let paths = &env.paths;
+    if args.migrate {
+        let migrated = paths.migrate()?;
+        for migration in &migrated {
+            println!(
+                "📦 Moved {} to {}",
+                migration.from.display(),
+                migration.to.display()
+            );
+        }
+        println!("✅ {} item(s) migrated", migrated.len());
+        return Ok(());
+    }
+
+    let config_file = paths.config_file();
+    let locations = [
+        ("Config", config_file.clone()),
+        ("Goals", paths.goals_file()),
+        ("Diaries", paths.diary_dir()),
+        ("Ledger", env.ledger(config).path().to_path_buf()),
+        ("Notes", paths.notes_file()),
+        ("State", paths.state_file()),
+        ("Queue", paths.queue_file()),
+        ("Credentials", paths.credentials_file()),
+    ];
+    for (name, path) in &locations {
+        let missing = if path.exists() {
+            ""
+        } else {
+            " (not created yet)"
+        };
+        println!("{:<12} {}{}", format!("{}:", name), path.display(), missing);
+    }
+    if env.cwd.join(DEFAULT_CONFIG_FILE).exists() {
+        println!(
+            "ℹ️  {} in this directory is used instead of {}",
+            DEFAULT_CONFIG_FILE,
+            config_file.display()
+        );
+    }
+
+    let pending = paths.pending_migrations();
+    if !pending.is_empty() {
+        println!(
+            "ℹ️  {} item(s) are still read from the locations older versions used; run `git-diary paths --migrate` to move them",
+            pending.len()
+        );
+    }
+
+    Ok(())
+}
+
+async fn run_highlights(env: &Env, args: &HighlightsArgs, config: &Config) -> Result<()> {
+    let today = Local::now().date_naive();
+    let since = highlights::period_start(today, args.months);
+
+    let diaries = if args.from_commits {
+        Vec::new()
+    } else {
+        highlights::stored_summaries(&env.diary_storage(), since)?
+    };
+
+    let source = env.repo_path();
+    let request = if diaries.is_empty() {
+        let since_timestamp =
+            domain::resolve_local(&Local, since.and_time(NaiveTime::MIN)).timestamp();
+        let commits = GitRepositoryImpl::new(source.clone())
+            .with_rename_similarity(config.rename_similarity)
+            .get_commits_since(since_timestamp)?;
+        if commits.is_empty() {
+            bail!("No diaries or commits found since {}", since);
+        }
+        println!("⛏️  Mining {} commits since {}", commits.len(), since);
+        HighlightsRequest::from_commits(&commits, args.max_commits)
+    } else {
+        println!("⛏️  Mining {} diaries since {}", diaries.len(), since);
+        HighlightsRequest::from_diaries(diaries)
+    };
+
+    let ai_summarizer = config.ai.summarizer(1000)?;
+    let highlights = ai_summarizer.summarize_highlights(&request).await?;
+    println!("{}", highlights.trim());
+
+    let usage = ai_summarizer.usage();
+    env.ledger(config).append(&LedgerEntry {
+        generated_at: Local::now().timestamp(),
+        repo: source,
+        start_date: since.to_string(),
+        end_date: today.to_string(),
+        end_time: None,
+        path: "highlights".to_string(),
+        provider: "openai".to_string(),
+        model: ai_summarizer.model().to_string(),
+        prompt_tokens: usage.prompt_tokens,
+        completion_tokens: usage.completion_tokens,
+        cost: estimate_cost(
+            ai_summarizer.model(),
+            usage.prompt_tokens,
+            usage.completion_tokens,
+        ),
+        feedback: None,
+        aborted: None,
+        kind: EntryKind::Highlights,
+    })?;
+
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+async fn run_browse(env: &Env, args: &BrowseArgs, config: &Config) -> Result<()> {
+    let storage = env.diary_storage();
+    let load = || tui::load_entries(&storage, &env.ledger(config).entries()?);
+    let mut browser = Browser::new(load()?);
+
+    loop {
+        match tui::run(&mut browser)? {
+            BrowserAction::Quit => return Ok(()),
+            BrowserAction::OpenInEditor(path) => {
+                let editor = std::env::var("VISUAL")
+                    .or_else(|_| std::env::var("EDITOR"))
+                    .unwrap_or_else(|_| "vi".to_string());
+                let status = std::process::Command::new(&editor).arg(&path).status();
+                if !status.is_ok_and(|status| status.success()) {
+                    eprintln!("⚠️ {} exited with an error", editor);
+                }
+                browser.set_entries(load()?);
+            }
+            BrowserAction::Regenerate(start_date, end_date) => {
+                let provider = LocalDateTimeProvider::new(config.day_cutoff_hour);
+                let range = DateRange::new(
+                    provider.calendar_range(start_date, Granularity::Day).start,
+                    provider.calendar_range(end_date, Granularity::Day).end,
+                );
+                return run_generate(env, &args.generate, config, Some(range))
+                    .await
+                    .map(|_| ());
+            }
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+/// Parameters of the `generate` and `preview` methods of `git-diary serve`
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GenerateParams {
+    /// Options of `git-diary generate`, such as `["--period", "this-week"]`
+    args: Vec<String>,
+}
+
+#[cfg(feature = "server")]
+impl GenerateParams {
+    fn parse(params: Value) -> Result<GenerateArgs> {
+        let params: Option<GenerateParams> =
+            serde_json::from_value(params).map_err(|e| InvalidParams(e.to_string()))?;
+        let argv = ["git-diary".to_string(), "generate".to_string()]
+            .into_iter()
+            .chain(params.unwrap_or_default().args);
+        match Cli::try_parse_from(argv) {
+            Ok(Cli {
+                command: Some(Command::Generate(args)),
+                ..
+            }) => Ok(args),
+            Ok(_) => Err(InvalidParams("expected options of generate".to_string()).into()),
+            Err(e) => Err(InvalidParams(e.to_string()).into()),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+/// The methods `git-diary serve` answers
+struct EditorApi<'a> {
+    env: &'a Env,
+    config: &'a Config,
+}
+
+#[cfg(feature = "server")]
+#[async_trait]
+impl RpcHandler for EditorApi<'_> {
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        match method {
+            "generate" | "preview" => {
+                let mut args = GenerateParams::parse(params)?;
+                args.preview = method == "preview";
+                match run_generate(self.env, &args, self.config, None).await? {
+                    Generated::Diary(path) => Ok(json!({ "path": path })),
+                    Generated::Preview(preview) => Ok(serde_json::to_value(preview)?),
+                }
+            }
+            "listDiaries" => {
+                let diaries: Vec<Value> = self
+                    .env
+                    .diary_storage()
+                    .stored_diaries()?
+                    .into_iter()
+                    .filter(|diary| diary.path.extension().is_none_or(|ext| ext != "json"))
+                    .map(|diary| {
+                        json!({
+                            "path": diary.path,
+                            "start_date": diary.start_date.to_string(),
+                            "end_date": diary.end_date.to_string(),
+                        })
+                    })
+                    .collect();
+                Ok(Value::Array(diaries))
+            }
+            _ => Err(UnknownMethod(method.to_string()).into()),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+/// Serves the editor API until the editor sends `shutdown` or closes stdin
+async fn run_serve(env: &Env, config: &Config) -> Result<()> {
+    let protocol = take_stdout()?;
+    eprintln!("🔌 Serving JSON-RPC on stdin/stdout");
+    rpc::serve(
+        &EditorApi { env, config },
+        std::io::stdin().lock(),
+        protocol,
+    )
+    .await
+}
+
+#[cfg(feature = "server")]
+/// Regenerates the previewed diary as HTML
+struct DiaryPage<'a> {
+    env: &'a Env,
+    config: &'a Config,
+    args: GenerateArgs,
+}
+
+#[cfg(feature = "server")]
+#[async_trait]
+impl PreviewSource for DiaryPage<'_> {
+    fn revision(&self) -> Result<String> {
+        Ok(git::head_id(&self.env.repo_path())?.unwrap_or_default())
+    }
+
+    async fn render(&self) -> Result<String> {
+        match run_generate(self.env, &self.args, self.config, None).await? {
+            Generated::Diary(path) => {
+                std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))
+            }
+            Generated::Preview(_) => bail!("--preview shows a prompt, not a diary"),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+/// Serves the latest diary on localhost until interrupted
+async fn run_preview(env: &Env, args: &ServeArgs, config: &Config) -> Result<()> {
+    let argv = ["git-diary".to_string(), "generate".to_string()]
+        .into_iter()
+        .chain(args.generate.iter().cloned());
+    let Cli {
+        command: Some(Command::Generate(mut generate)),
+        ..
+    } = Cli::try_parse_from(argv)?
+    else {
+        bail!("Expected options of generate after --");
+    };
+    generate.format = Format::Html;
+
+    let listener = tokio::net::TcpListener::bind(("127.0.0.1", args.port))
+        .await
+        .with_context(|| format!("Failed to listen on port {}", args.port))?;
+    println!("🌐 Previewing the diary at http://localhost:{}/", args.port);
+    let page = DiaryPage {
+        env,
+        config,
+        args: generate,
+    };
+    PreviewServer::new(page)
+        .with_poll_interval(Duration::from_secs(args.poll.max(1)))
+        .serve(listener)
+        .await
+}
+
+#[cfg(feature = "server")]
+/// Points stdout at stderr and returns the original stdout for the protocol,
+/// so the progress messages printed while generating can't corrupt it
+#[cfg(unix)]
+fn take_stdout() -> Result<Box<dyn Write>> {
+    use std::os::fd::FromRawFd;
+
+    std::io::stdout().flush()?;
+    // SAFETY: only the process's own standard descriptors are duplicated, and
+    // the File takes sole ownership of the new one
+    unsafe {
+        let protocol = libc::dup(libc::STDOUT_FILENO);
+        if protocol < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Box::new(std::fs::File::from_raw_fd(protocol)))
+    }
+}
+
+#[cfg(all(feature = "server", not(unix)))]
+fn take_stdout() -> Result<Box<dyn Write>> {
+    eprintln!("⚠️ Progress messages share stdout with the protocol on this platform");
+    Ok(Box::new(std::io::stdout()))
+}
+
+fn format_totals(totals: &LedgerTotals) -> String {
+    format!(
+        "{} diaries, {} prompt + {} completion tokens, ~${:.4}",
+        totals.diaries, totals.prompt_tokens, totals.completion_tokens, totals.cost
+    )
+}
+
+/// The llama.cpp summarizer for `--provider local`
+#[cfg(feature = "local-llm")]
+fn local_summarizer(
+    model_path: Option<&Path>,
+    max_tokens: u32,
+    deterministic: bool,
+) -> Result<Arc<dyn AISummarizer>> {
+    let Some(model_path) = model_path else {
+        bail!("--provider local needs --model-path");
+    };
+    Ok(Arc::new(
+        git_diary::local::LocalSummarizer::new(model_path.to_path_buf(), max_tokens)
+            .with_deterministic(deterministic),
+    ))
+}
+
+#[cfg(not(feature = "local-llm"))]
+fn local_summarizer(
+    _model_path: Option<&Path>,
+    _max_tokens: u32,
+    _deterministic: bool,
+) -> Result<Arc<dyn AISummarizer>> {
+    bail!("--provider local needs git-diary built with the `local-llm` feature")
+}
+
+/// Model name recorded for `--provider local`: the model file's name without extension
+fn model_file_name(model_path: Option<&Path>) -> String {
+    model_path
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Tokens of diffs the summary may quote: the flag's, else the config's, else none
+fn diff_budget_tokens(args: &GenerateArgs, config: &Config) -> u32 {
+    args.diff_budget_tokens
+        .or(config.diff_budget_tokens)
+        .unwrap_or(0)
+}
+
+/// Looks up pull request titles on the forge hosting `origin`, when credentials are available
+#[cfg(feature = "forge")]
+fn pull_request_resolver(
+    env: &Env,
+    config: &Config,
+) -> Result<Option<Arc<dyn PullRequestResolver>>> {
+    Ok(
+        forge_credentials(env, config)?.map(|(forge, repository, token)| {
+            Arc::new(ForgePullRequestResolver::new(forge, repository, token))
+                as Arc<dyn PullRequestResolver>
+        }),
+    )
+}
+
+#[cfg(not(feature = "forge"))]
+fn pull_request_resolver(
+    _env: &Env,
+    _config: &Config,
+) -> Result<Option<Arc<dyn PullRequestResolver>>> {
+    Ok(None)
+}
+
+/// Finds the pull requests you reviewed on the forge hosting `origin`
+#[cfg(feature = "forge")]
+fn review_source(env: &Env, config: &Config) -> Result<Option<Arc<dyn ReviewSource>>> {
+    match forge_credentials(env, config)? {
+        Some((Forge::GitHub, repository, token)) => {
+            Ok(Some(Arc::new(GitHubReviewSource::new(repository, token))))
+        }
+        Some((forge, _, _)) => {
+            eprintln!("⚠️ --reviews only supports GitHub, not {}", forge);
+            Ok(None)
+        }
+        None => {
+            eprintln!("⚠️ --reviews needs GitHub credentials for origin; see `git-diary auth`");
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(not(feature = "forge"))]
+fn review_source(_env: &Env, _config: &Config) -> Result<Option<Arc<dyn ReviewSource>>> {
+    eprintln!("⚠️ --reviews needs git-diary built with the `forge` feature");
+    Ok(None)
+}
+
+/// Reads commits through the API of the Bitbucket repository `origin` points to
+#[cfg(feature = "forge")]
+fn forge_commit_source(env: &Env, config: &Config) -> Result<BitbucketCommitSource> {
+    match forge_credentials(env, config)? {
+        Some((Forge::Bitbucket, repository, token)) => {
+            Ok(BitbucketCommitSource::new(repository, token))
+        }
+        Some((forge, _, _)) => bail!("--from-forge only supports Bitbucket, not {}", forge),
+        None => bail!("--from-forge needs Bitbucket credentials for origin; see `git-diary auth`"),
+    }
+}
+
+/// The forge hosting `origin` and a token for it, when credentials are available
+#[cfg(feature = "forge")]
+fn forge_credentials(
+    env: &Env,
+    config: &Config,
+) -> Result<Option<(Forge, RemoteRepository, String)>> {
+    let repo_path = env.repo_path();
+    let Some(repository) =
+        git::remote_url(&repo_path, "origin")?.and_then(|url| RemoteRepository::parse(&url))
+    else {
+        return Ok(None);
+    };
+
+    let resolver = CredentialResolver::new(config.auth.clone(), env.credential_cache_path());
+    for forge in [Forge::GitHub, Forge::GitLab, Forge::Bitbucket] {
+        if resolver.host(forge) == repository.host {
+            return Ok(resolver
+                .resolve(forge)?
+                .map(|credential| (forge, repository, credential.token)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The tracker in `[tickets]`; GitHub Projects reads the issues of `origin`
+/// with its GitHub credentials
+#[cfg(feature = "forge")]
+fn ticket_source(env: &Env, config: &Config) -> Result<Option<Arc<dyn domain::TicketSource>>> {
+    let tickets = &config.tickets;
+    match tickets.provider {
+        None => Ok(None),
+        Some(TicketTracker::Jira) => Ok(Some(Arc::new(tickets.jira_source()?))),
+        Some(TicketTracker::GithubProjects) => match forge_credentials(env, config)? {
+            Some((Forge::GitHub, repository, token)) => Ok(Some(Arc::new(
+                GitHubProjectsSource::new(repository, token).with_statuses(tickets.statuses()),
+            ))),
+            _ => {
+                eprintln!(
+                    "⚠️ GitHub Projects tickets need a GitHub origin and credentials; see `git-diary auth`"
+                );
+                Ok(None)
+            }
+        },
+    }
+}
+
+#[cfg(feature = "forge")]
+async fn run_auth(env: &Env, args: &AuthArgs, config: &Config) -> Result<()> {
+    let resolver = CredentialResolver::new(config.auth.clone(), env.credential_cache_path());
+
+    if let (true, Some(forge)) = (args.login, args.forge) {
+        resolver.login(forge).await?;
+        println!("✅ Logged in to {} ({})", forge, resolver.host(forge));
+        return Ok(());
+    }
+
+    let forges = match args.forge {
+        Some(forge) => vec![forge],
+        None => vec![Forge::GitHub, Forge::GitLab, Forge::Bitbucket],
+    };
+    for forge in forges {
+        match resolver.resolve(forge)? {
+            Some(credential) => println!(
+                "✅ {} ({}): token {} from {}",
+                forge,
+                resolver.host(forge),
+                auth::mask_token(&credential.token),
+                credential.source
+            ),
+            None => println!(
+                "❌ {} ({}): no credentials found",
+                forge,
+                resolver.host(forge)
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+fn run_completions(args: &CompletionsArgs) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(args.shell, &mut command, name, &mut std::io::stdout());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_generate_params() -> Result<()> {
+        let args = GenerateParams::parse(json!({"args": ["--period", "this-week", "--sessions"]}))?;
+        assert!(matches!(args.period, Some(Period::ThisWeek)));
+        assert!(args.sessions);
+
+        assert_eq!(GenerateParams::parse(Value::Null)?.days, 1);
+        let result = GenerateParams::parse(json!({"args": ["--period", "decade"]}));
+        assert!(result.is_err_and(|e| e.is::<InvalidParams>()));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn test_serve_preview_args() {
+        let cli = Cli::try_parse_from([
+            "git-diary",
+            "serve",
+            "--preview",
+            "--port",
+            "9000",
+            "--",
+            "--period",
+            "this-week",
+        ])
+        .unwrap();
+        let Some(Command::Serve(args)) = cli.command else {
+            panic!("expected serve");
+        };
+        assert!(args.preview);
+        assert_eq!(args.port, 9000);
+        assert_eq!(args.generate, vec!["--period", "this-week"]);
+
+        assert!(Cli::try_parse_from(["git-diary", "serve", "--port", "9000"]).is_err());
+    }
+
+    #[test]
+    fn test_queue_args() {
+        let cli = Cli::try_parse_from(["git-diary", "queue", "--work", "--poll", "5"]).unwrap();
+        let Some(Command::Queue(args)) = cli.command else {
+            panic!("expected queue");
+        };
+        assert!(args.work);
+        assert_eq!(args.poll, 5);
+
+        assert!(Cli::try_parse_from(["git-diary", "queue", "--poll", "5"]).is_err());
+        assert!(Cli::try_parse_from(["git-diary", "--queue-offline", "--preview"]).is_err());
+        assert!(Cli::try_parse_from(["git-diary", "generate", "--queue-offline"]).is_ok());
+    }
+
+    #[test]
+    fn test_max_commits_must_be_positive() {
+        assert!(Cli::try_parse_from(["git-diary", "generate", "--max-commits", "0"]).is_err());
+        assert!(Cli::try_parse_from(["git-diary", "highlights", "--max-commits", "0"]).is_err());
+        let cli = Cli::try_parse_from(["git-diary", "generate", "--max-commits", "5"]).unwrap();
+        let Some(Command::Generate(args)) = cli.command else {
+            panic!("expected generate");
+        };
+        assert_eq!(args.max_commits, 5);
+    }
+
+    #[tokio::test]
+    async fn test_interruption_removes_saved_diaries() -> Result<()> {
+        assert_eq!(
+            interruption(Some(Duration::from_millis(10))).await,
+            "timed out after 10ms"
+        );
+
+        let temp_dir = tempfile::TempDir::new()?;
+        let diary = temp_dir.path().join("git-diary-20240101-to-20240101.md");
+        std::fs::write(&diary, "# Diary\n")?;
+        std::fs::write(diary.with_extension("json"), "{}")?;
+        let saved = vec![
+            diary.to_string_lossy().into_owned(),
+            temp_dir
+                .path()
+                .join("gone.md")
+                .to_string_lossy()
+                .into_owned(),
+        ];
+
+        assert_eq!(remove_saved(&saved, true), 2);
+        assert!(std::fs::read_dir(temp_dir.path())?.next().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_yes() {
+        assert!(is_yes("\n"));
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" Yes "));
+        assert!(!is_yes("n\n"));
+        assert!(!is_yes("nope"));
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(parse_month("2024-05"), Ok("2024-05".to_string()));
+        assert_eq!(parse_month("2024-5"), Ok("2024-05".to_string()));
+        assert!(parse_month("2024-13").is_err());
+        assert!(parse_month("May").is_err());
+    }
+
+    #[test]
+    fn test_since_last_range() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let ledger = Ledger::new(temp_dir.path().join("ledger.jsonl"));
+        let now = Local.with_ymd_and_hms(2024, 5, 7, 18, 0, 0).unwrap();
+
+        assert!(since_last_range(&ledger, "/src/app", now)?.is_none());
+
+        let last_end = Local.with_ymd_and_hms(2024, 5, 6, 23, 30, 0).unwrap();
+        ledger.append(&LedgerEntry {
+            generated_at: last_end.timestamp(),
+            repo: "/src/app".to_string(),
+            start_date: "2024-05-06".to_string(),
+            end_date: "2024-05-06".to_string(),
+            end_time: Some(last_end.timestamp()),
+            path: "diaries/git-diary-20240506-to-20240506.md".to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-4".to_string(),
+            prompt_tokens: 1,
+            completion_tokens: 1,
+            cost: None,
+            feedback: None,
+            aborted: None,
+            kind: EntryKind::Diary,
+        })?;
+
+        let range = since_last_range(&ledger, "/src/app", now)?.unwrap();
+        assert_eq!((range.start, range.end), (last_end, now));
+        assert!(since_last_range(&ledger, "/src/app", last_end).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_run_args() -> Result<()> {
+        let watch = |argv: &[&str]| match Cli::try_parse_from(argv) {
+            Ok(Cli {
+                command: Some(Command::Watch(args)),
+                ..
+            }) => args,
+            _ => panic!("not a watch command"),
+        };
+
+        assert_eq!(
+            watch_run_args(&watch(&["git-diary", "watch", "--install"]), None, None)?,
+            vec!["generate", "--since-last"]
+        );
+        let args = watch(&[
+            "git-diary",
+            "watch",
+            "--at",
+            "09:30",
+            "--",
+            "--format",
+            "html",
+        ]);
+        assert_eq!(args.at, NaiveTime::from_hms_opt(9, 30, 0).unwrap());
+        assert_eq!(
+            watch_run_args(&args, None, Some(Path::new(".git/diary")))?,
+            vec!["generate", "--output", ".git/diary", "--format", "html"]
+        );
+
+        let error = watch_run_args(&watch(&["git-diary", "watch", "--", "--nope"]), None, None)
+            .unwrap_err();
+        assert!(error.to_string().contains("--nope"));
+        assert!(Cli::try_parse_from(["git-diary", "watch", "--at", "6pm"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_completions_cover_subcommands() {
+        let mut script = Vec::new();
+        clap_complete::generate(Shell::Bash, &mut Cli::command(), "git-diary", &mut script);
+        let script = String::from_utf8(script).unwrap();
+
+        let subcommands = [
+            "generate",
+            "lint",
+            "note",
+            "feedback",
+            "summarize-file",
+            "regen",
+            "show",
+            "completions",
+            "list",
+            "costs",
+            "prune",
+            "highlights",
+            "paths",
+            "models",
+            "ping",
+            "watch",
+        ];
+        // Subcommands left out of slim builds
+        let optional = [
+            ("auth", cfg!(feature = "forge")),
+            ("browse", cfg!(feature = "tui")),
+            ("schema", cfg!(feature = "schema")),
+            ("export-archive", cfg!(feature = "archive")),
+            ("import-archive", cfg!(feature = "archive")),
+        ];
+        let optional = optional
+            .into_iter()
+            .filter_map(|(subcommand, built)| built.then_some(subcommand));
+
+        for subcommand in subcommands.into_iter().chain(optional) {
+            assert!(script.contains(subcommand));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_in_env() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        let env = Env {
+            cwd: root.join("app"),
+            paths: AppPaths::new(
+                root.join("config"),
+                root.join("data"),
+                root.join("cache"),
+                root.join("state"),
+                &root.join("app"),
+            ),
+        };
+
+        run(
+            Cli::try_parse_from(["git-diary", "note", "Reviewed the billing RFC"])?,
+            &env,
+        )
+        .await?;
+
+        assert_eq!(env.note_store().pending()?.len(), 1);
+        assert!(root.join("data/notes.json").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_inside_git_dir() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let root = temp_dir.path();
+        git2::Repository::init(root.join("app"))?;
+        let env = || Env {
+            cwd: root.join("app"),
+            paths: AppPaths::new(
+                root.join("config"),
+                root.join("data"),
+                root.join("cache"),
+                root.join("state"),
+                &root.join("app"),
+            ),
+        };
+
+        let git_dir = git::git_dir(&env().repo_path()).unwrap();
+        let output = env().with_output(Some(Path::new(".git/diary/")));
+        assert_eq!(output.paths.diary_dir(), git_dir.join("diary"));
+
+        let output = env().with_output(Some(Path::new("notes/diary")));
+        assert_eq!(output.paths.diary_dir(), root.join("app/notes/diary"));
+        assert_eq!(
+            env().with_output(None).paths.diary_dir(),
+            env().paths.diary_dir()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::domain::{AISummarizer, HighlightsRequest, ProviderPing, SummaryRequest};
+use crate::prompt::{build_highlights_prompt, build_user_prompt};
+use crate::scan::redact;
+
+/// Wraps summarizers in middleware, innermost first:
+/// `summarizer.redacted().cached().logged()` logs every call, answers
+/// repeated requests from the cache and redacts whatever reaches the model.
+pub trait SummarizerExt: AISummarizer + Sized {
+    /// Reports the size and duration of every call on stderr
+    fn logged(self) -> LoggingSummarizer<Self> {
+        LoggingSummarizer::new(self)
+    }
+
+    /// Answers identical requests from memory instead of calling the model again
+    fn cached(self) -> CachingSummarizer<Self> {
+        CachingSummarizer::new(self)
+    }
+
+    /// Masks emails, secrets and internal hostnames before the request is passed on
+    fn redacted(self) -> RedactingSummarizer<Self> {
+        RedactingSummarizer::new(self)
+    }
+}
+
+impl<S: AISummarizer + Sized> SummarizerExt for S {}
+
+/// Logs each request and how long the wrapped summarizer took
+pub struct LoggingSummarizer<S> {
+    inner: S,
+}
+
+impl<S: AISummarizer> LoggingSummarizer<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<S: AISummarizer> AISummarizer for LoggingSummarizer<S> {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String> {
+        let started = Instant::now();
+        let result = self.inner.summarize_commits(request).await;
+        eprintln!(
+            "🪵 Summarized {} commit(s) in {:.1}s: {}",
+            request.commits.len(),
+            started.elapsed().as_secs_f64(),
+            outcome(&result)
+        );
+        result
+    }
+
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String> {
+        let started = Instant::now();
+        let result = self.inner.summarize_highlights(request).await;
+        eprintln!(
+            "🪵 Mined {} diaries and {} commit(s) for highlights in {:.1}s: {}",
+            request.diaries.len(),
+            request.commits.len(),
+            started.elapsed().as_secs_f64(),
+            outcome(&result)
+        );
+        result
+    }
+
+    async fn ping(&self) -> Result<ProviderPing> {
+        self.inner.ping().await
+    }
+}
+
+fn outcome(result: &Result<String>) -> String {
+    match result {
+        Ok(summary) => format!("{} characters", summary.chars().count()),
+        Err(e) => format!("failed ({})", e),
+    }
+}
+
+/// Remembers answers by prompt, so a request is only sent once per process
+///
+/// Failures are not cached.
+pub struct CachingSummarizer<S> {
+    inner: S,
+    cache: Mutex<HashMap<String, String>>,
+}
+
+impl<S: AISummarizer> CachingSummarizer<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    fn cached(&self, key: &str) -> Option<String> {
+        self.cache.lock().unwrap().get(key).cloned()
+    }
+
+    fn store(&self, key: String, summary: &str) {
+        self.cache.lock().unwrap().insert(key, summary.to_string());
+    }
+}
+
+#[async_trait]
+impl<S: AISummarizer> AISummarizer for CachingSummarizer<S> {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String> {
+        // The length picks the system prompt and routing the model, so both are part of the key
+        let key = format!(
+            "commits\n{}\n{}\n{}",
+            request.length.sentences(),
+            request.model.as_deref().unwrap_or_default(),
+            build_user_prompt(request)
+        );
+        if let Some(summary) = self.cached(&key) {
+            return Ok(summary);
+        }
+
+        let summary = self.inner.summarize_commits(request).await?;
+        self.store(key, &summary);
+        Ok(summary)
+    }
+
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String> {
+        let key = format!("highlights\n{}", build_highlights_prompt(request));
+        if let Some(summary) = self.cached(&key) {
+            return Ok(summary);
+        }
+
+        let summary = self.inner.summarize_highlights(request).await?;
+        self.store(key, &summary);
+        Ok(summary)
+    }
+
+    /// Never answered from the cache, as it checks the provider as it is now
+    async fn ping(&self) -> Result<ProviderPing> {
+        self.inner.ping().await
+    }
+}
+
+/// Redacts sensitive data from everything the wrapped summarizer sees
+pub struct RedactingSummarizer<S> {
+    inner: S,
+}
+
+impl<S: AISummarizer> RedactingSummarizer<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<S: AISummarizer> AISummarizer for RedactingSummarizer<S> {
+    async fn summarize_commits(&self, request: &SummaryRequest) -> Result<String> {
+        let mut request = request.clone();
+        for commit in &mut request.commits {
+            commit.message = redact(&commit.message);
+            commit.body = commit.body.as_deref().map(redact);
+            commit.squashed_from = commit.squashed_from.as_deref().map(redact);
+            commit.diff = commit.diff.as_deref().map(redact);
+        }
+        for note in &mut request.notes {
+            note.text = redact(&note.text);
+        }
+        for activity in &mut request.context {
+            *activity = redact(activity);
+        }
+        for review in &mut request.reviews {
+            review.title = redact(&review.title);
+        }
+        for ticket in &mut request.tickets {
+            ticket.title = redact(&ticket.title);
+        }
+        for goal in &mut request.goals {
+            *goal = redact(goal);
+        }
+
+        self.inner.summarize_commits(&request).await
+    }
+
+    async fn summarize_highlights(&self, request: &HighlightsRequest) -> Result<String> {
+        let mut request = request.clone();
+        for commit in &mut request.commits {
+            commit.message = redact(&commit.message);
+            commit.body = commit.body.as_deref().map(redact);
+            commit.squashed_from = commit.squashed_from.as_deref().map(redact);
+        }
+        for diary in &mut request.diaries {
+            diary.summary = redact(&diary.summary);
+        }
+
+        self.inner.summarize_highlights(&request).await
+    }
+
+    async fn ping(&self) -> Result<ProviderPing> {
+        self.inner.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Commit, MockAISummarizer, TicketTransition};
+    use anyhow::anyhow;
+
+    fn request(message: &str) -> SummaryRequest {
+        SummaryRequest::new(vec![Commit::new(message.to_string(), 1704067200)])
+    }
+
+    #[tokio::test]
+    async fn test_composed_middleware() -> Result<()> {
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .times(2)
+            .returning(|request| Ok(format!("Summary of {}", request.commits[0].message)));
+
+        let summarizer = mock_ai_summarizer.redacted().cached().logged();
+
+        assert_eq!(
+            summarizer
+                .summarize_commits(&request("Reply to jane@example.com"))
+                .await?,
+            "Summary of Reply to [redacted email address]"
+        );
+        // Served from the cache: the mock only allows two calls
+        assert_eq!(
+            summarizer
+                .summarize_commits(&request("Reply to jane@example.com"))
+                .await?,
+            "Summary of Reply to [redacted email address]"
+        );
+        assert_eq!(
+            summarizer.summarize_commits(&request("Fix cache")).await?,
+            "Summary of Fix cache"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redacting_summarizer_redacts_diffs() -> Result<()> {
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| Ok(request.commits[0].diff.clone().unwrap_or_default()));
+
+        let summarizer = mock_ai_summarizer.redacted();
+        let request =
+            SummaryRequest::new(vec![Commit::new("Add sample env".to_string(), 1704067200)
+                .with_diff("+API_KEY=hunter2\n+notify ops@example.com\n".to_string())]);
+
+        assert_eq!(
+            summarizer.summarize_commits(&request).await?,
+            "[redacted possible secret]\n+notify [redacted email address]\n"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redacting_summarizer_redacts_ticket_titles() -> Result<()> {
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .returning(|request| Ok(request.tickets[0].title.clone()));
+
+        let summarizer = mock_ai_summarizer.redacted();
+        let mut request = request("Fix login");
+        request.tickets = vec![TicketTransition::new(
+            "PAY-142".to_string(),
+            "Rotate creds on db01.corp".to_string(),
+            "Done".to_string(),
+        )];
+
+        assert_eq!(
+            summarizer.summarize_commits(&request).await?,
+            "Rotate creds on [redacted internal hostname]"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_caching_summarizer_skips_failures() {
+        let mut mock_ai_summarizer = MockAISummarizer::new();
+        let mut calls = 0;
+        mock_ai_summarizer
+            .expect_summarize_commits()
+            .times(2)
+            .returning(move |_| {
+                calls += 1;
+                match calls {
+                    1 => Err(anyhow!("rate limited")),
+                    _ => Ok("Summary".to_string()),
+                }
+            });
+
+        let summarizer = mock_ai_summarizer.cached();
+
+        assert!(summarizer
+            .summarize_commits(&request("Fix cache"))
+            .await
+            .is_err());
+        assert_eq!(
+            summarizer
+                .summarize_commits(&request("Fix cache"))
+                .await
+                .unwrap(),
+            "Summary"
+        );
+    }
+}
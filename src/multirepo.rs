@@ -0,0 +1,106 @@
+use anyhow::Result;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::domain::{Commit, GitRepository};
+
+/// Commits of several repositories, such as a project and its forks or
+/// mirrors, read as one
+///
+/// A commit whose id an earlier repository already listed is left out, so
+/// work pushed to a mirror isn't reported twice. Repositories are read in
+/// the order they were added; within one repository commits are kept as it
+/// lists them, repeats included, and commits without an id are always kept.
+#[derive(Default)]
+pub struct MultiRepository {
+    repositories: Vec<Arc<dyn GitRepository>>,
+}
+
+impl MultiRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `repository` after the others
+    pub fn with_repository(mut self, repository: Arc<dyn GitRepository>) -> Self {
+        self.repositories.push(repository);
+        self
+    }
+}
+
+impl GitRepository for MultiRepository {
+    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut commits: Vec<Commit> = Vec::new();
+        for repository in &self.repositories {
+            let listed = repository.get_commits_since(timestamp)?;
+            let ids: Vec<String> = listed
+                .iter()
+                .filter_map(|commit| commit.id.clone())
+                .collect();
+            commits.extend(
+                listed
+                    .into_iter()
+                    .filter(|commit| commit.id.as_ref().is_none_or(|id| !seen.contains(id))),
+            );
+            seen.extend(ids);
+        }
+        // Match the reflog ordering: newest first
+        commits.sort_by_key(|commit| std::cmp::Reverse(commit.timestamp()));
+        Ok(commits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::MockGitRepository;
+
+    fn commit(message: &str, time: i64, id: Option<&str>) -> Commit {
+        let commit = Commit::new(message.to_string(), time);
+        match id {
+            Some(id) => commit.with_id(id.to_string()),
+            None => commit,
+        }
+    }
+
+    fn repository(commits: Vec<Commit>) -> Arc<dyn GitRepository> {
+        let mut repository = MockGitRepository::new();
+        repository
+            .expect_get_commits_since()
+            .returning(move |_| Ok(commits.clone()));
+        Arc::new(repository)
+    }
+
+    #[test]
+    fn test_multi_repository_dedups_mirrored_commits() -> Result<()> {
+        let origin = repository(vec![
+            commit("commit: Add export", 30, Some("a1")),
+            commit("commit (amend): Add export", 30, Some("a1")),
+            commit("commit: Fix typo", 10, Some("b2")),
+        ]);
+        let mirror = repository(vec![
+            commit("pull: Add export", 40, Some("a1")),
+            commit("commit: Mirror-only fix", 20, Some("c3")),
+            commit("Exported change", 5, None),
+        ]);
+
+        let commits = MultiRepository::new()
+            .with_repository(origin)
+            .with_repository(mirror)
+            .get_commits_since(0)?;
+
+        let messages: Vec<&str> = commits.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "commit: Add export",
+                "commit (amend): Add export",
+                "commit: Mirror-only fix",
+                "commit: Fix typo",
+                "Exported change",
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::domain::Note;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PendingNotes {
+    notes: Vec<Note>,
+}
+
+/// Notes added with `git-diary note` that wait for the next diary
+///
+/// Unlike run state, notes are written by hand and can't be recreated, so a
+/// file that fails to parse is reported instead of being replaced.
+pub struct NoteStore {
+    path: PathBuf,
+}
+
+impl NoteStore {
+    pub fn new(path: String) -> Self {
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+
+    /// Appends a note to the pending notes
+    pub fn add(&self, note: Note) -> Result<()> {
+        let mut pending = self.load()?;
+        pending.notes.push(note);
+        self.save(&pending)
+    }
+
+    /// Returns the pending notes, oldest first
+    pub fn pending(&self) -> Result<Vec<Note>> {
+        Ok(self.load()?.notes)
+    }
+
+    /// Drops the given notes once they made it into a diary
+    ///
+    /// Notes added while the diary was being generated are kept.
+    pub fn remove(&self, merged: &[Note]) -> Result<()> {
+        let mut pending = self.load()?;
+        pending.notes.retain(|note| !merged.contains(note));
+        self.save(&pending)
+    }
+
+    fn load(&self) -> Result<PendingNotes> {
+        if !self.path.exists() {
+            return Ok(PendingNotes::default());
+        }
+
+        let raw = fs::read_to_string(&self.path).context("Failed to read notes file")?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Notes file {} is corrupt", self.path.display()))
+    }
+
+    fn save(&self, pending: &PendingNotes) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Failed to create notes directory")?;
+        }
+
+        let temp_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path).context("Failed to create notes file")?;
+        file.write_all(serde_json::to_string_pretty(pending)?.as_bytes())
+            .context("Failed to write notes file")?;
+        file.sync_all().context("Failed to flush notes file")?;
+
+        fs::rename(&temp_path, &self.path).context("Failed to replace notes file")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_store(temp_dir: &TempDir) -> NoteStore {
+        NoteStore::new(
+            temp_dir
+                .path()
+                .join("diaries/.notes.json")
+                .to_string_lossy()
+                .to_string(),
+        )
+    }
+
+    #[test]
+    fn test_note_store_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = create_test_store(&temp_dir);
+
+        assert!(store.pending()?.is_empty());
+
+        let first = Note::new("Paired with Alice".to_string(), 1704067200);
+        let second = Note::new("Reviewed the cache design".to_string(), 1704070800);
+        store.add(first.clone())?;
+        let merged = store.pending()?;
+        store.add(second.clone())?;
+
+        // Only notes that made it into the diary are removed
+        store.remove(&merged)?;
+        assert_eq!(create_test_store(&temp_dir).pending()?, vec![second]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_note_store_keeps_corrupt_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = create_test_store(&temp_dir);
+        store.add(Note::new("Paired with Alice".to_string(), 1704067200))?;
+
+        let path = temp_dir.path().join("diaries/.notes.json");
+        fs::write(&path, "{\"notes\": [")?;
+
+        assert!(store.pending().is_err());
+        assert!(store
+            .add(Note::new("Another".to_string(), 1704070800))
+            .is_err());
+        assert_eq!(fs::read_to_string(&path)?, "{\"notes\": [");
+
+        Ok(())
+    }
+}
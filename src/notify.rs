@@ -0,0 +1,22 @@
+use anyhow::{Context, Result};
+
+use crate::domain::Notifier;
+
+/// Shows notifications through the desktop's notification center
+///
+/// Uses the freedesktop notification service on Linux and the native
+/// notification APIs on macOS and Windows.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, message: &str) -> Result<()> {
+        notify_rust::Notification::new()
+            .appname("git-diary")
+            .summary("git-diary")
+            .body(message)
+            .show()
+            .context("Failed to show desktop notification")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::{bail, Result};
+
+/// A `## ` section of a diary file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub heading: String,
+    pub body: String,
+}
+
+/// A diary file parsed back into its parts
+///
+/// This is the inverse of `DiaryStorageImpl::format_markdown_content`, used
+/// by tooling that inspects or edits stored diaries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedDiary {
+    /// Raw `key: value` lines between the leading `---` fences
+    pub frontmatter: Option<Vec<(String, String)>>,
+    /// Text of the `# ` title line
+    pub title: Option<String>,
+    /// Everything between the title and the first `## ` section, kept as is
+    pub preamble: String,
+    pub sections: Vec<Section>,
+}
+
+impl ParsedDiary {
+    pub fn section(&self, heading: &str) -> Option<&Section> {
+        self.sections
+            .iter()
+            .find(|section| section.heading == heading)
+    }
+
+    /// Renders the diary back to Markdown
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        if let Some(frontmatter) = &self.frontmatter {
+            output.push_str("---\n");
+            for (key, value) in frontmatter {
+                output.push_str(&format!("{}: {}\n", key, value));
+            }
+            output.push_str("---\n\n");
+        }
+
+        if let Some(title) = &self.title {
+            output.push_str(&format!("# {}\n\n", title));
+        }
+
+        if !self.preamble.is_empty() {
+            output.push_str(&format!("{}\n\n", self.preamble));
+        }
+
+        for section in &self.sections {
+            output.push_str(&format!("## {}\n\n", section.heading));
+            if !section.body.is_empty() {
+                output.push_str(&format!("{}\n\n", section.body));
+            }
+        }
+
+        format!("{}\n", output.trim_end())
+    }
+}
+
+/// Parses a diary written in the crate's Markdown format
+///
+/// Fails only on malformed frontmatter; missing titles or sections are
+/// reported through the returned structure.
+pub fn parse_diary(markdown: &str) -> Result<ParsedDiary> {
+    let mut diary = ParsedDiary::default();
+    let mut lines = markdown.lines().peekable();
+
+    if lines.peek().map(|line| line.trim_end()) == Some("---") {
+        lines.next();
+        let mut entries = Vec::new();
+        let mut terminated = false;
+
+        for line in lines.by_ref() {
+            if line.trim_end() == "---" {
+                terminated = true;
+                break;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.split_once(':') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    entries.push((key.trim().to_string(), value.trim().to_string()))
+                }
+                _ => bail!("Malformed frontmatter line: {}", line),
+            }
+        }
+
+        if !terminated {
+            bail!("Frontmatter is not terminated by ---");
+        }
+        diary.frontmatter = Some(entries);
+    }
+
+    let mut current: Option<Section> = None;
+    let mut in_fence = false;
+    for line in lines {
+        let heading = if in_fence {
+            None
+        } else {
+            line.strip_prefix("## ")
+        };
+        if is_fence(line) {
+            in_fence = !in_fence;
+        }
+
+        if let Some(heading) = heading {
+            if let Some(section) = current.take() {
+                diary.sections.push(finish_section(section));
+            }
+            current = Some(Section {
+                heading: heading.trim().to_string(),
+                body: String::new(),
+            });
+        } else if let Some(section) = current.as_mut() {
+            section.body.push_str(line);
+            section.body.push('\n');
+        } else if diary.title.is_none() && diary.preamble.trim().is_empty() && !in_fence {
+            match line.strip_prefix("# ") {
+                Some(title) => diary.title = Some(title.trim().to_string()),
+                None => {
+                    diary.preamble.push_str(line);
+                    diary.preamble.push('\n');
+                }
+            }
+        } else {
+            diary.preamble.push_str(line);
+            diary.preamble.push('\n');
+        }
+    }
+    diary.preamble = diary.preamble.trim().to_string();
+
+    if let Some(section) = current.take() {
+        diary.sections.push(finish_section(section));
+    }
+
+    Ok(diary)
+}
+
+/// Opens or closes a fenced code block, whose lines are never headings
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+fn finish_section(mut section: Section) -> Section {
+    section.body = section.body.trim().to_string();
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIARY: &str = "# Git Diary (2024-01-01 – 2024-01-07)
+
+## Commit Logs
+
+- 2024-01-01 00:00:00: First commit
+- 2024-01-02 00:00:00: Second commit
+
+
+## AI-generated Summary
+
+Worked on things.
+";
+
+    #[test]
+    fn test_parse_diary() -> Result<()> {
+        let diary = parse_diary(DIARY)?;
+
+        assert_eq!(diary.frontmatter, None);
+        assert_eq!(
+            diary.title.as_deref(),
+            Some("Git Diary (2024-01-01 – 2024-01-07)")
+        );
+        assert_eq!(diary.sections.len(), 2);
+        assert_eq!(
+            diary.section("Commit Logs").unwrap().body,
+            "- 2024-01-01 00:00:00: First commit\n- 2024-01-02 00:00:00: Second commit"
+        );
+        assert_eq!(
+            diary.section("AI-generated Summary").unwrap().body,
+            "Worked on things."
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_diary_frontmatter() -> Result<()> {
+        let markdown = format!("---\nrepo: git-diary\ntags: rust, cli\n---\n\n{}", DIARY);
+
+        let diary = parse_diary(&markdown)?;
+
+        assert_eq!(
+            diary.frontmatter,
+            Some(vec![
+                ("repo".to_string(), "git-diary".to_string()),
+                ("tags".to_string(), "rust, cli".to_string()),
+            ])
+        );
+        assert_eq!(diary.sections.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_diary_malformed_frontmatter() {
+        assert!(parse_diary("---\nrepo: git-diary\n\n# Title\n").is_err());
+        assert!(parse_diary("---\nnot a pair\n---\n# Title\n").is_err());
+    }
+
+    #[test]
+    fn test_render_round_trip() -> Result<()> {
+        let diary = parse_diary(DIARY)?;
+
+        let rendered = diary.render();
+
+        assert_eq!(parse_diary(&rendered)?, diary);
+        assert!(rendered.starts_with("# Git Diary"));
+        assert!(rendered.ends_with("Worked on things.\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_round_trip_keeps_preamble_and_fences() -> Result<()> {
+        let markdown = "# Git Diary (2024-01-01 – 2024-01-07)
+
+Written on the train.
+# Notes from the retro
+
+## Commit Logs
+
+- 2024-01-01 00:00:00: First commit
+
+## AI-generated Summary
+
+Worked on things.
+
+```markdown
+## Not a section
+```
+";
+
+        let diary = parse_diary(markdown)?;
+
+        assert_eq!(
+            diary.preamble,
+            "Written on the train.\n# Notes from the retro"
+        );
+        assert_eq!(diary.sections.len(), 2);
+        assert_eq!(diary.render(), markdown);
+
+        Ok(())
+    }
+}
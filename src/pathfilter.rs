@@ -0,0 +1,99 @@
+use anyhow::{bail, Context, Result};
+use glob::{MatchOptions, Pattern};
+
+use crate::domain::Commit;
+
+/// `*` stays within one directory; `**` crosses them
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// Keeps only commits touching certain paths, such as one feature area of a monorepo
+///
+/// Built from globs like `src/payments/**`; a leading `!` excludes what it
+/// matches, as in `!**/tests/**`. A commit is kept when at least one of its
+/// files matches an including glob, or any glob when there are only
+/// exclusions, and no excluding glob. A glob without wildcards also matches
+/// everything below it, so `docs` covers `docs/setup.md`.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+    pub fn new(globs: &[String]) -> Result<Self> {
+        let mut filter = Self::default();
+        for glob in globs {
+            let (patterns, glob) = match glob.strip_prefix('!') {
+                Some(glob) => (&mut filter.exclude, glob),
+                None => (&mut filter.include, glob.as_str()),
+            };
+            let glob = glob.trim_start_matches("./").trim_end_matches('/');
+            if glob.is_empty() {
+                bail!("Empty path glob");
+            }
+
+            patterns.push(compile(glob)?);
+            if !glob.contains(['*', '?', '[']) {
+                patterns.push(compile(&format!("{}/**", glob))?);
+            }
+        }
+        Ok(filter)
+    }
+
+    /// Whether `path`, relative to the repository root, passes the filter
+    pub fn matches_path(&self, path: &str) -> bool {
+        let matches = |pattern: &Pattern| pattern.matches_with(path, MATCH_OPTIONS);
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    /// Whether `commit` touched a path that passes; commits without a file
+    /// list never do
+    pub fn matches(&self, commit: &Commit) -> bool {
+        commit.files.iter().any(|path| self.matches_path(path))
+    }
+}
+
+fn compile(glob: &str) -> Result<Pattern> {
+    Pattern::new(glob).with_context(|| format!("Invalid path glob {:?}", glob))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit_touching(files: &[&str]) -> Commit {
+        Commit::new("Change".to_string(), 1704067200)
+            .with_files(files.iter().map(|file| file.to_string()).collect())
+    }
+
+    #[test]
+    fn test_path_filter() -> Result<()> {
+        let filter = PathFilter::new(&["src/payments/**".to_string(), "!**/tests/**".to_string()])?;
+
+        assert!(filter.matches_path("src/payments/refund.rs"));
+        assert!(filter.matches_path("src/payments/stripe/client.rs"));
+        assert!(!filter.matches_path("src/payments/tests/refund.rs"));
+        assert!(!filter.matches_path("src/orders/cart.rs"));
+
+        assert!(filter.matches(&commit_touching(&["README.md", "src/payments/refund.rs"])));
+        assert!(!filter.matches(&commit_touching(&["src/payments/tests/refund.rs"])));
+        assert!(!filter.matches(&commit_touching(&[])));
+
+        // Only exclusions keep everything else; a plain directory covers what's below it
+        let filter = PathFilter::new(&["!docs".to_string(), "!*.md".to_string()])?;
+        assert!(filter.matches_path("src/main.rs"));
+        assert!(!filter.matches_path("docs/setup.txt"));
+        assert!(!filter.matches_path("README.md"));
+        assert!(filter.matches_path("src/README.md"));
+
+        assert!(PathFilter::new(&["src/[".to_string()]).is_err());
+        assert!(PathFilter::new(&["!".to_string()]).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,475 @@
+use anyhow::{anyhow, bail, Context, Result};
+use directories::{BaseDirs, ProjectDirs};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Diary directory older versions wrote to, relative to the repository
+pub const LEGACY_DIARY_DIR: &str = "diaries";
+
+/// Diary directory inside the repository's git directory, used once it exists,
+/// as after `--output .git/diary`
+pub const GIT_DIARY_DIR: &str = "diary";
+
+/// Where git-diary keeps its config, diaries, ledger, run state and caches
+///
+/// Follows the platform's conventions: the XDG base directories on Linux,
+/// `~/Library/Application Support` on macOS and Known Folders on Windows.
+/// Files from the relative `diaries/` directory and `~/.git-diary` that
+/// older versions used are still read until `git-diary paths --migrate`
+/// moves them.
+///
+/// Diaries go to the directory given with `--output` when there is one, else
+/// to `.git/diary` when it exists, so they stay out of the work tree.
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    pub config_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub state_dir: PathBuf,
+    /// Name of the repository directory
+    repo_name: String,
+    /// The name and a hash of the repository's path, which keeps its diaries
+    /// apart from other repositories, even those of the same name
+    repo_key: String,
+    legacy_user_dir: Option<PathBuf>,
+    legacy_diary_dir: PathBuf,
+    git_diary_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+}
+
+/// A file or directory left at an old location, and where it belongs now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl AppPaths {
+    /// Resolves the platform directories for the repository at `repo_dir`
+    pub fn resolve(repo_dir: &Path) -> Result<Self> {
+        let dirs = ProjectDirs::from("", "", "git-diary")
+            .ok_or_else(|| anyhow!("Could not determine the home directory"))?;
+        let home = BaseDirs::new()
+            .ok_or_else(|| anyhow!("Could not determine the home directory"))?
+            .home_dir()
+            .to_path_buf();
+
+        Ok(Self::new(
+            dirs.config_dir().to_path_buf(),
+            dirs.data_dir().to_path_buf(),
+            dirs.cache_dir().to_path_buf(),
+            // Only Linux has a separate state directory
+            dirs.state_dir()
+                .unwrap_or(dirs.data_local_dir())
+                .to_path_buf(),
+            repo_dir,
+        )
+        .with_legacy_user_dir(home.join(".git-diary")))
+    }
+
+    pub fn new(
+        config_dir: PathBuf,
+        data_dir: PathBuf,
+        cache_dir: PathBuf,
+        state_dir: PathBuf,
+        repo_dir: &Path,
+    ) -> Self {
+        let repo_name = repo_dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "default".to_string());
+        let canonical = repo_dir
+            .canonicalize()
+            .unwrap_or_else(|_| repo_dir.to_path_buf());
+        let repo_key = format!(
+            "{}-{:08x}",
+            repo_name,
+            fnv1a(canonical.to_string_lossy().as_bytes())
+        );
+
+        Self {
+            config_dir,
+            data_dir,
+            cache_dir,
+            state_dir,
+            repo_name,
+            repo_key,
+            legacy_user_dir: None,
+            legacy_diary_dir: repo_dir.join(LEGACY_DIARY_DIR),
+            git_diary_dir: None,
+            output_dir: None,
+        }
+    }
+
+    /// Sets the repository's git directory, whose `diary` directory is used
+    /// for diaries once it exists
+    pub fn with_git_dir(mut self, git_dir: &Path) -> Self {
+        self.git_diary_dir = Some(git_dir.join(GIT_DIARY_DIR));
+        self
+    }
+
+    /// Writes and reads diaries in `dir`, as given with `--output`
+    pub fn with_output_dir(mut self, dir: PathBuf) -> Self {
+        self.output_dir = Some(dir);
+        self
+    }
+
+    /// Sets the per-user directory older versions used, usually `~/.git-diary`
+    pub fn with_legacy_user_dir(mut self, dir: PathBuf) -> Self {
+        self.legacy_user_dir = Some(dir);
+        self
+    }
+
+    /// The user-wide config file, read when the repository has no `.git-diary.toml`
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    /// Name of the repository directory
+    pub fn repo_name(&self) -> &str {
+        &self.repo_name
+    }
+
+    /// Directory this repository's diaries are written to
+    pub fn diary_dir(&self) -> PathBuf {
+        if let Some(dir) = &self.output_dir {
+            return dir.clone();
+        }
+        if let Some(dir) = self.git_diary_dir.as_ref().filter(|dir| dir.is_dir()) {
+            return dir.clone();
+        }
+        if self.legacy_diary_dir.is_dir() {
+            return self.legacy_diary_dir.clone();
+        }
+        self.prefer_legacy(
+            self.data_dir.join("diaries").join(&self.repo_name),
+            self.data_dir.join("diaries").join(&self.repo_key),
+        )
+    }
+
+    pub fn ledger_file(&self) -> PathBuf {
+        self.prefer_legacy(
+            self.legacy_user_file("ledger.jsonl"),
+            self.data_dir.join("ledger.jsonl"),
+        )
+    }
+
+    /// Manual notes waiting for the next diary
+    pub fn notes_file(&self) -> PathBuf {
+        self.prefer_legacy(
+            self.legacy_diary_dir.join(".notes.json"),
+            self.data_dir.join("notes.json"),
+        )
+    }
+
+    /// When each repository last got a diary
+    pub fn state_file(&self) -> PathBuf {
+        self.prefer_legacy(
+            self.legacy_diary_dir.join(".state.json"),
+            self.state_dir.join("state.json"),
+        )
+    }
+
+    /// Diary runs waiting for the machine to come back online, for all repositories
+    pub fn queue_file(&self) -> PathBuf {
+        self.state_dir.join("queue.json")
+    }
+
+    /// Output of the worker that generates queued runs in the background
+    pub fn queue_log_file(&self) -> PathBuf {
+        self.state_dir.join("queue.log")
+    }
+
+    /// Goals written by hand as a Markdown list, next to the user config file
+    pub fn goals_file(&self) -> PathBuf {
+        self.config_dir.join("goals.md")
+    }
+
+    /// Terminology learned from this repository's summaries
+    pub fn glossary_file(&self) -> PathBuf {
+        self.prefer_legacy(
+            self.data_dir
+                .join("glossary")
+                .join(format!("{}.json", self.repo_name)),
+            self.data_dir
+                .join("glossary")
+                .join(format!("{}.json", self.repo_key)),
+        )
+    }
+
+    /// Tokens from the OAuth device flow; losing them only means logging in again
+    pub fn credentials_file(&self) -> PathBuf {
+        self.prefer_legacy(
+            self.legacy_user_file("credentials.json"),
+            self.cache_dir.join("credentials.json"),
+        )
+    }
+
+    fn legacy_user_file(&self, name: &str) -> Option<PathBuf> {
+        self.legacy_user_dir.as_ref().map(|dir| dir.join(name))
+    }
+
+    fn prefer_legacy(&self, legacy: impl Into<Option<PathBuf>>, current: PathBuf) -> PathBuf {
+        match legacy.into() {
+            Some(legacy) if legacy.exists() && !current.exists() => legacy,
+            _ => current,
+        }
+    }
+
+    /// Files still at a location older versions used, in the order they are moved
+    pub fn pending_migrations(&self) -> Vec<Migration> {
+        let candidates = [
+            (
+                self.legacy_user_file("ledger.jsonl"),
+                self.data_dir.join("ledger.jsonl"),
+            ),
+            (
+                self.legacy_user_file("credentials.json"),
+                self.cache_dir.join("credentials.json"),
+            ),
+            (
+                Some(self.legacy_diary_dir.join(".notes.json")),
+                self.data_dir.join("notes.json"),
+            ),
+            (
+                Some(self.legacy_diary_dir.join(".state.json")),
+                self.state_dir.join("state.json"),
+            ),
+            // Older versions keyed these by the repository name alone
+            (
+                Some(
+                    self.data_dir
+                        .join("glossary")
+                        .join(format!("{}.json", self.repo_name)),
+                ),
+                self.data_dir
+                    .join("glossary")
+                    .join(format!("{}.json", self.repo_key)),
+            ),
+            (
+                Some(self.data_dir.join("diaries").join(&self.repo_name)),
+                self.data_dir.join("diaries").join(&self.repo_key),
+            ),
+            // Last, so the hidden files above have left the directory
+            (
+                Some(self.legacy_diary_dir.clone()),
+                self.data_dir.join("diaries").join(&self.repo_key),
+            ),
+        ];
+
+        candidates
+            .into_iter()
+            .filter_map(|(from, to)| Some((from?, to)))
+            .filter(|(from, _)| from.exists())
+            .map(|(from, to)| Migration { from, to })
+            .collect()
+    }
+
+    /// Moves everything from the old locations, refusing to overwrite
+    pub fn migrate(&self) -> Result<Vec<Migration>> {
+        let migrations = self.pending_migrations();
+        for (i, migration) in migrations.iter().enumerate() {
+            if let Some(other) = migrations[..i]
+                .iter()
+                .find(|other| other.to == migration.to)
+            {
+                bail!(
+                    "Cannot move both {} and {} to {}; merge them by hand",
+                    other.from.display(),
+                    migration.from.display(),
+                    migration.to.display()
+                );
+            }
+            if migration.to.exists() {
+                bail!(
+                    "Cannot move {} to {}: the destination already exists",
+                    migration.from.display(),
+                    migration.to.display()
+                );
+            }
+        }
+
+        for migration in &migrations {
+            if let Some(parent) = migration.to.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            move_path(&migration.from, &migration.to)?;
+        }
+
+        Ok(migrations)
+    }
+}
+
+/// 32-bit FNV-1a, stable across builds unlike the standard library's hasher
+pub(crate) fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Renames `from` to `to`, copying when they are on different file systems
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            move_path(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        fs::remove_dir(from)?;
+    } else {
+        fs::copy(from, to)
+            .with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+        fs::remove_file(from)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn paths_in(root: &Path) -> AppPaths {
+        AppPaths::new(
+            root.join("config"),
+            root.join("data"),
+            root.join("cache"),
+            root.join("state"),
+            &root.join("work/app"),
+        )
+        .with_legacy_user_dir(root.join("home/.git-diary"))
+    }
+
+    #[test]
+    fn test_paths_follow_platform_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let paths = paths_in(root);
+
+        assert_eq!(paths.config_file(), root.join("config/config.toml"));
+        assert_eq!(paths.goals_file(), root.join("config/goals.md"));
+        assert_eq!(
+            paths.diary_dir(),
+            root.join("data/diaries").join(&paths.repo_key)
+        );
+        assert_eq!(paths.ledger_file(), root.join("data/ledger.jsonl"));
+        assert_eq!(paths.notes_file(), root.join("data/notes.json"));
+        assert_eq!(paths.state_file(), root.join("state/state.json"));
+        assert_eq!(
+            paths.credentials_file(),
+            root.join("cache/credentials.json")
+        );
+        assert!(paths.pending_migrations().is_empty());
+    }
+
+    #[test]
+    fn test_diary_dir_inside_git_dir() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        let paths = paths_in(root).with_git_dir(&root.join("work/app/.git"));
+        assert_eq!(
+            paths.diary_dir(),
+            root.join("data/diaries").join(&paths.repo_key)
+        );
+
+        // Once `--output .git/diary` created it, later runs find it without the option
+        fs::create_dir_all(root.join("work/app/.git/diary"))?;
+        assert_eq!(paths.diary_dir(), root.join("work/app/.git/diary"));
+
+        let paths = paths.with_output_dir(root.join("elsewhere"));
+        assert_eq!(paths.diary_dir(), root.join("elsewhere"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_locations_are_used_until_migrated() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("work/app/diaries"))?;
+        fs::write(
+            root.join("work/app/diaries/git-diary-20240101-to-20240107.md"),
+            "# Diary",
+        )?;
+        fs::write(root.join("work/app/diaries/.state.json"), "{}")?;
+        fs::create_dir_all(root.join("home/.git-diary"))?;
+        fs::write(root.join("home/.git-diary/ledger.jsonl"), "")?;
+        let paths = paths_in(root);
+
+        assert_eq!(paths.diary_dir(), root.join("work/app/diaries"));
+        assert_eq!(
+            paths.state_file(),
+            root.join("work/app/diaries/.state.json")
+        );
+        assert_eq!(
+            paths.ledger_file(),
+            root.join("home/.git-diary/ledger.jsonl")
+        );
+        assert_eq!(paths.pending_migrations().len(), 3);
+
+        let migrated = paths.migrate()?;
+
+        assert_eq!(migrated.len(), 3);
+        assert!(paths.pending_migrations().is_empty());
+        assert!(!root.join("work/app/diaries").exists());
+        let diary_dir = root.join("data/diaries").join(&paths.repo_key);
+        assert!(diary_dir.join("git-diary-20240101-to-20240107.md").exists());
+        assert!(!diary_dir.join(".state.json").exists());
+        assert_eq!(paths.state_file(), root.join("state/state.json"));
+        assert_eq!(fs::read_to_string(paths.state_file())?, "{}");
+        assert_eq!(paths.ledger_file(), root.join("data/ledger.jsonl"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repositories_of_the_same_name_are_kept_apart() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        let paths = |repo: &str| {
+            AppPaths::new(
+                root.join("config"),
+                root.join("data"),
+                root.join("cache"),
+                root.join("state"),
+                &root.join(repo),
+            )
+        };
+        let (a, b) = (paths("a/app"), paths("b/app"));
+
+        assert!(a.repo_key.starts_with("app-"));
+        assert_ne!(a.diary_dir(), b.diary_dir());
+        assert_ne!(a.glossary_file(), b.glossary_file());
+
+        // Diaries stored under the name alone are used until migrated
+        fs::create_dir_all(root.join("data/diaries/app"))?;
+        assert_eq!(a.diary_dir(), root.join("data/diaries/app"));
+        assert_eq!(a.migrate()?.len(), 1);
+        assert_eq!(a.diary_dir(), root.join("data/diaries").join(&a.repo_key));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_refuses_to_overwrite() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("home/.git-diary"))?;
+        fs::write(root.join("home/.git-diary/ledger.jsonl"), "old")?;
+        fs::create_dir_all(root.join("data"))?;
+        fs::write(root.join("data/ledger.jsonl"), "new")?;
+        let paths = paths_in(root);
+
+        assert!(paths.migrate().is_err());
+        assert_eq!(fs::read_to_string(root.join("data/ledger.jsonl"))?, "new");
+        assert!(root.join("home/.git-diary/ledger.jsonl").exists());
+
+        Ok(())
+    }
+}
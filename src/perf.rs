@@ -0,0 +1,119 @@
+use regex::Regex;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+use crate::domain::Commit;
+
+/// Figures such as `2.3x faster`, `40% less memory` or `from 120ms to 80ms`
+static FIGURE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?ix)
+        \b\d+(?:\.\d+)?\s*(?:x|×|%)\s+
+            (?:faster|slower|quicker|speedup|smaller|(?:less|fewer|more|lower|higher)(?:\s+[a-z]+)?)
+        |
+        \bfrom\s+\d+(?:\.\d+)?\s*(?:ns|µs|us|ms|s|kb|mb|gb)\s+to\s+\d+(?:\.\d+)?\s*(?:ns|µs|us|ms|s|kb|mb|gb)\b
+        ",
+    )
+    .expect("valid figure pattern")
+});
+
+/// A performance change and the improvements its message claims
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PerformanceClaim {
+    pub subject: String,
+    /// Figures quoted from the message, such as `2.3x faster`; empty when it names none
+    pub figures: Vec<String>,
+}
+
+impl Display for PerformanceClaim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.figures.is_empty() {
+            return write!(f, "{}", self.subject);
+        }
+        write!(f, "{} ({})", self.subject, self.figures.join(", "))
+    }
+}
+
+/// Figures in `text` that claim a measured change, in order
+pub fn claimed_figures(text: &str) -> Vec<String> {
+    FIGURE
+        .find_iter(text)
+        .map(|figure| figure.as_str().to_string())
+        .collect()
+}
+
+/// Whether the commit is performance work: a `perf:` commit, a change to
+/// benchmarks under `benches/`, or a message claiming a measured speedup
+pub fn is_performance_work(commit: &Commit) -> bool {
+    commit.conventional_type().as_deref() == Some("perf")
+        || commit
+            .files
+            .iter()
+            .any(|file| file.starts_with("benches/") || file.contains("/benches/"))
+        || !claimed_figures(&message_of(commit)).is_empty()
+}
+
+/// The performance work among `commits`, in their order, with the figures they claim
+pub fn performance_claims(commits: &[Commit]) -> Vec<PerformanceClaim> {
+    commits
+        .iter()
+        .filter(|commit| is_performance_work(commit))
+        .map(|commit| PerformanceClaim {
+            subject: commit.subject().to_string(),
+            figures: claimed_figures(&message_of(commit)),
+        })
+        .collect()
+}
+
+fn message_of(commit: &Commit) -> String {
+    match &commit.body {
+        Some(body) => format!("{}\n{}", commit.subject(), body),
+        None => commit.subject().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claimed_figures() {
+        assert_eq!(
+            claimed_figures("Cache parsed configs: 2.3x faster startup, 40% less memory"),
+            vec!["2.3x faster", "40% less memory"]
+        );
+        assert_eq!(
+            claimed_figures("Batch inserts, p95 from 120ms to 80ms"),
+            vec!["from 120ms to 80ms"]
+        );
+        assert!(claimed_figures("Bump version to 2.3").is_empty());
+    }
+
+    #[test]
+    fn test_performance_claims() {
+        let commits = vec![
+            Commit::new("commit: perf(parser): Avoid copying tokens".to_string(), 0),
+            Commit::new("Add sync bench".to_string(), 0)
+                .with_files(vec!["crates/sync/benches/sync.rs".to_string()]),
+            Commit::new("Cache configs".to_string(), 0)
+                .with_body("Startup is 2.3x faster.".to_string()),
+            Commit::new("Fix typo".to_string(), 0),
+        ];
+
+        let claims = performance_claims(&commits);
+
+        assert_eq!(
+            claims
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+            vec![
+                "perf(parser): Avoid copying tokens",
+                "Add sync bench",
+                "Cache configs (2.3x faster)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use anyhow::{bail, Context, Result};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+/// Column summaries are wrapped at when a `wrap` step gives no width
+pub const DEFAULT_WRAP_WIDTH: usize = 80;
+
+/// Issue references linked when a `link-issues` step gives no pattern
+pub const DEFAULT_ISSUE_PATTERN: &str = r"#(\d+)";
+
+/// Words left lowercase in titles, unless they come first
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+    "via", "with",
+];
+
+/// A step run over the AI's summary before it is printed and saved
+pub trait SummaryPostProcessor: Send + Sync {
+    fn process(&self, summary: &str) -> String;
+}
+
+/// A `post_process` entry from the config file, such as
+/// `{ step = "link-issues", url = "https://github.com/acme/app/issues/{id}" }`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "step", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum PostProcessConfig {
+    /// Turns issue references into links; `{id}` in `url` is replaced by the
+    /// first capture group of `pattern`, or the whole match without one
+    LinkIssues {
+        url: String,
+        pattern: Option<String>,
+    },
+    /// Hard-wraps long lines at `width` columns
+    Wrap { width: Option<usize> },
+    /// Removes headings, rules and code fences around the whole summary,
+    /// which would break the structure of the diary
+    StripMarkdown,
+    /// Makes the first line a title-cased title, followed by a blank line
+    Title,
+}
+
+/// Post-processing steps applied to summaries, in order
+///
+/// Each step sees the output of the previous one, like the rewrite rules
+/// for commit messages. Steps from the config file come first; embedders can
+/// add their own with [`PostProcessPipeline::with_step`].
+#[derive(Default)]
+pub struct PostProcessPipeline {
+    steps: Vec<Box<dyn SummaryPostProcessor>>,
+}
+
+impl PostProcessPipeline {
+    pub fn new(steps: &[PostProcessConfig]) -> Result<Self> {
+        let mut pipeline = Self::default();
+        for step in steps {
+            pipeline = match step {
+                PostProcessConfig::LinkIssues { url, pattern } => pipeline.with_step(
+                    IssueLinker::new(url, pattern.as_deref().unwrap_or(DEFAULT_ISSUE_PATTERN))?,
+                ),
+                PostProcessConfig::Wrap { width } => {
+                    pipeline.with_step(LineWrapper::new(width.unwrap_or(DEFAULT_WRAP_WIDTH))?)
+                }
+                PostProcessConfig::StripMarkdown => pipeline.with_step(MarkdownStripper),
+                PostProcessConfig::Title => pipeline.with_step(TitleLine),
+            };
+        }
+        Ok(pipeline)
+    }
+
+    /// Adds `step` after the others
+    pub fn with_step(mut self, step: impl SummaryPostProcessor + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Runs every step over `summary`, in order
+    pub fn apply(&self, summary: &str) -> String {
+        self.steps
+            .iter()
+            .fold(summary.to_string(), |summary, step| step.process(&summary))
+    }
+}
+
+/// Links issue references such as `#42` to the issue tracker
+pub struct IssueLinker {
+    url: String,
+    pattern: Regex,
+}
+
+impl IssueLinker {
+    pub fn new(url: &str, pattern: &str) -> Result<Self> {
+        if !url.contains("{id}") {
+            bail!("Issue link URL {:?} has no {{id}} placeholder", url);
+        }
+        let pattern =
+            Regex::new(pattern).with_context(|| format!("Invalid issue pattern {:?}", pattern))?;
+        Ok(Self {
+            url: url.to_string(),
+            pattern,
+        })
+    }
+}
+
+impl SummaryPostProcessor for IssueLinker {
+    fn process(&self, summary: &str) -> String {
+        self.pattern
+            .replace_all(summary, |captures: &Captures| {
+                let reference = &captures[0];
+                let start = captures.get(0).map_or(0, |m| m.start());
+                let end = captures.get(0).map_or(0, |m| m.end());
+                // Already a link, or part of a longer word or URL
+                let before = summary[..start].chars().next_back();
+                let after = summary[end..].chars().next();
+                if before.is_some_and(|c| c == '[' || c == '/' || c == '&' || c.is_alphanumeric())
+                    || after == Some(']')
+                {
+                    return reference.to_string();
+                }
+
+                let id = captures.get(1).map_or(reference, |id| id.as_str());
+                format!("[{}]({})", reference, self.url.replace("{id}", id))
+            })
+            .into_owned()
+    }
+}
+
+/// Hard-wraps lines longer than `width`, keeping list items and quotes
+/// indented under their marker
+///
+/// Code blocks, headings and table rows are left alone, as are words longer
+/// than a line, such as URLs.
+pub struct LineWrapper {
+    width: usize,
+}
+
+impl LineWrapper {
+    pub fn new(width: usize) -> Result<Self> {
+        if width < 20 {
+            bail!(
+                "Wrap width {} is too narrow; use at least 20 columns",
+                width
+            );
+        }
+        Ok(Self { width })
+    }
+
+    fn wrap_line(&self, line: &str, wrapped: &mut Vec<String>) {
+        let body = line.trim_start();
+        let indent = line.len() - body.len();
+        let marker = list_marker(body);
+        let (first_prefix, rest) = line.split_at(indent + marker);
+        let continuation = " ".repeat(first_prefix.chars().count());
+
+        let mut current = first_prefix.to_string();
+        let mut empty = true;
+        for word in rest.split_whitespace() {
+            if !empty && current.chars().count() + 1 + word.chars().count() > self.width {
+                wrapped.push(current);
+                current = continuation.clone();
+                empty = true;
+            }
+            if !empty {
+                current.push(' ');
+            }
+            current.push_str(word);
+            empty = false;
+        }
+        wrapped.push(current);
+    }
+}
+
+impl SummaryPostProcessor for LineWrapper {
+    fn process(&self, summary: &str) -> String {
+        let mut wrapped = Vec::new();
+        let mut in_code = false;
+        for line in summary.lines() {
+            let body = line.trim_start();
+            if body.starts_with("```") {
+                in_code = !in_code;
+            }
+            if in_code
+                || body.starts_with("```")
+                || body.starts_with('#')
+                || body.starts_with('|')
+                || line.chars().count() <= self.width
+            {
+                wrapped.push(line.to_string());
+            } else {
+                self.wrap_line(line, &mut wrapped);
+            }
+        }
+        wrapped.join("\n")
+    }
+}
+
+/// Length of the list or quote marker at the start of `body`, with its space
+fn list_marker(body: &str) -> usize {
+    for marker in ["- ", "* ", "+ ", "> "] {
+        if body.starts_with(marker) {
+            return marker.len();
+        }
+    }
+    let digits = body.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && body[digits..].starts_with(". ") {
+        return digits + 2;
+    }
+    0
+}
+
+/// Removes Markdown that doesn't belong inside a diary section: headings,
+/// horizontal rules and a code fence around the whole summary
+pub struct MarkdownStripper;
+
+impl SummaryPostProcessor for MarkdownStripper {
+    fn process(&self, summary: &str) -> String {
+        let trimmed = summary.trim();
+        let unfenced = trimmed
+            .strip_prefix("```")
+            .and_then(|rest| rest.strip_suffix("```"))
+            // Drop the info string, such as `markdown`, after the opening fence
+            .and_then(|inner| inner.split_once('\n').map(|(_, body)| body))
+            .filter(|body| !body.contains("```"))
+            .unwrap_or(trimmed);
+
+        let mut lines = Vec::new();
+        for line in unfenced.lines() {
+            let body = line.trim();
+            let is_rule = body.len() >= 3
+                && ['-', '*', '_']
+                    .iter()
+                    .any(|&c| body.chars().all(|ch| ch == c || ch == ' '));
+            if is_rule {
+                continue;
+            }
+            match heading_text(line) {
+                Some(title) => lines.push(title.to_string()),
+                None => lines.push(line.to_string()),
+            }
+        }
+        lines.join("\n").trim().to_string()
+    }
+}
+
+/// The title of an ATX heading such as `## Highlights`: one to six `#` at
+/// the start of the line, indented by at most three spaces, then a space
+///
+/// Anything else starting with `#`, such as `#42 fixed`, is not a heading.
+fn heading_text(line: &str) -> Option<&str> {
+    let body = line.trim_start_matches(' ');
+    if line.len() - body.len() > 3 {
+        return None;
+    }
+    let level = body.chars().take_while(|&c| c == '#').count();
+    let rest = &body[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+/// Makes the first line a title: its first sentence, title-cased and without
+/// a final period, followed by a blank line
+pub struct TitleLine;
+
+impl SummaryPostProcessor for TitleLine {
+    fn process(&self, summary: &str) -> String {
+        let summary = summary.trim();
+        let (first, rest) = summary.split_once('\n').unwrap_or((summary, ""));
+        let first = first.replace("**", "");
+        let first = heading_text(&first)
+            .unwrap_or(&first)
+            .trim()
+            .trim_start_matches(['-', ' ']);
+        let (title, remainder) = match first.split_once(". ") {
+            Some((title, remainder)) => (title, remainder.trim()),
+            None => (first.trim_end_matches('.'), ""),
+        };
+
+        let body: Vec<&str> = [remainder, rest.trim()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect();
+        if body.is_empty() {
+            title_case(title)
+        } else {
+            format!("{}\n\n{}", title_case(title), body.join("\n"))
+        }
+    }
+}
+
+/// Capitalizes every word but short connecting words; words that already
+/// have capitals, such as `CSV` or `iOS`, are kept as written
+fn title_case(text: &str) -> String {
+    text.split(' ')
+        .enumerate()
+        .map(|(i, word)| {
+            let lower = word.to_lowercase();
+            if word.chars().any(char::is_uppercase)
+                || (i > 0 && SMALL_WORDS.contains(&lower.as_str()))
+            {
+                return word.to_string();
+            }
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_linker() -> Result<()> {
+        let linker = IssueLinker::new(
+            "https://github.com/acme/app/issues/{id}",
+            DEFAULT_ISSUE_PATTERN,
+        )?;
+
+        assert_eq!(
+            linker.process("Fixed #12 and [#7](https://x/7); see issues#3 or C#2."),
+            "Fixed [#12](https://github.com/acme/app/issues/12) and [#7](https://x/7); \
+             see issues#3 or C#2."
+        );
+
+        let jira = IssueLinker::new("https://acme.atlassian.net/browse/{id}", r"\bPAY-\d+\b")?;
+        assert_eq!(
+            jira.process("Closed PAY-81."),
+            "Closed [PAY-81](https://acme.atlassian.net/browse/PAY-81)."
+        );
+
+        assert!(IssueLinker::new("https://x/issues", DEFAULT_ISSUE_PATTERN).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_wrapper() -> Result<()> {
+        let wrapper = LineWrapper::new(30)?;
+
+        assert_eq!(
+            wrapper.process(
+                "Short line.\n- A list item that is much too long for one line\n```\nlet code = \"is never wrapped at all\";\n```"
+            ),
+            "Short line.\n- A list item that is much too\n  long for one line\n```\nlet code = \"is never wrapped at all\";\n```"
+        );
+        assert!(LineWrapper::new(5).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_markdown_stripper_and_title() {
+        assert_eq!(
+            MarkdownStripper
+                .process("```markdown\n## Overview\nShipped export.\n---\n- Fixed tests\n```"),
+            "Overview\nShipped export.\n- Fixed tests"
+        );
+
+        assert_eq!(
+            TitleLine.process(
+                "**shipped the CSV export for iOS users.** Tests were fixed too.\n- Fixed tests"
+            ),
+            "Shipped the CSV Export for iOS Users\n\nTests were fixed too.\n- Fixed tests"
+        );
+        assert_eq!(TitleLine.process("a quiet day."), "A Quiet Day");
+    }
+
+    #[test]
+    fn test_only_headings_lose_their_hashes() {
+        assert_eq!(
+            MarkdownStripper.process(
+                "#42 is fixed
+### Next
+####### Not a heading
+    # code"
+            ),
+            "#42 is fixed
+Next
+####### Not a heading
+    # code"
+        );
+        assert_eq!(
+            TitleLine.process(
+                "#42 fixed.
+More"
+            ),
+            "#42 Fixed
+
+More"
+        );
+        assert_eq!(TitleLine.process("## a quiet day"), "A Quiet Day");
+    }
+
+    #[test]
+    fn test_post_process_pipeline() -> Result<()> {
+        let steps: Vec<PostProcessConfig> = toml::from_str::<toml::Table>(
+            "steps = [{ step = \"strip-markdown\" }, { step = \"title\" }, { step = \"link-issues\", url = \"https://x/{id}\" }]",
+        )?["steps"]
+            .clone()
+            .try_into()?;
+        let pipeline = PostProcessPipeline::new(&steps)?;
+
+        assert_eq!(
+            pipeline.apply("# Fixed login\nClosed #4."),
+            "Fixed Login\n\nClosed [#4](https://x/4)."
+        );
+        assert!(PostProcessPipeline::default().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::automock;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::formatter::escape_html;
+
+/// Port the preview server listens on when none is given
+pub const DEFAULT_PREVIEW_PORT: u16 = 7878;
+/// How often the preview checks for new commits, in seconds
+pub const DEFAULT_POLL_SECONDS: u64 = 10;
+/// How often an open page asks whether a newer diary is available, in milliseconds
+const PAGE_POLL_MILLIS: u64 = 5000;
+/// How long a client may take to send its request or read the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Produces the diary the preview server shows
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait PreviewSource: Send + Sync {
+    /// Changes whenever the diary may be out of date, such as HEAD's commit id
+    fn revision(&self) -> Result<String>;
+
+    /// Regenerates the diary and returns it as an HTML page
+    async fn render(&self) -> Result<String>;
+}
+
+/// Serves the latest diary as HTML on localhost, regenerating it when the
+/// page's refresh button is pressed or the source's revision changes
+///
+/// Each connection is read in its own task, so an idle client can't hold up
+/// the others, but requests are answered one at a time, so a page asking
+/// during a regeneration waits for the new diary.
+///
+/// Only requests addressed to a loopback host are answered, which keeps DNS
+/// rebinding pages out, and a refresh needs the token embedded in the page, so
+/// other sites can't start a paid regeneration.
+pub struct PreviewServer<S> {
+    source: S,
+    poll_interval: Duration,
+    token: String,
+    page: Option<String>,
+    error: Option<String>,
+    version: u64,
+    revision: Option<String>,
+}
+
+impl<S: PreviewSource> PreviewServer<S> {
+    pub fn new(source: S) -> Self {
+        Self {
+            source,
+            poll_interval: Duration::from_secs(DEFAULT_POLL_SECONDS),
+            token: session_token(),
+            page: None,
+            error: None,
+            version: 0,
+            revision: None,
+        }
+    }
+
+    /// Checks the source's revision every `interval` instead of every 10 seconds
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Answers requests on `listener` until the process is stopped
+    pub async fn serve(mut self, listener: TcpListener) -> Result<()> {
+        self.regenerate_if_changed().await;
+        let start = tokio::time::Instant::now() + self.poll_interval;
+        let mut ticker = tokio::time::interval_at(start, self.poll_interval);
+        let (sender, mut requests) = mpsc::channel(16);
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted.context("Failed to accept a connection")?;
+                    let sender = sender.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = exchange(stream, sender).await {
+                            eprintln!("⚠️ Preview request failed: {:#}", e);
+                        }
+                    });
+                }
+                Some((request, reply)) = requests.recv() => {
+                    let response = self.respond(&request).await;
+                    // The client may have hung up while waiting
+                    let _ = reply.send(response);
+                }
+                _ = ticker.tick() => self.regenerate_if_changed().await,
+            }
+        }
+    }
+
+    async fn regenerate_if_changed(&mut self) {
+        let revision = match self.source.revision() {
+            Ok(revision) => revision,
+            Err(e) => {
+                eprintln!("⚠️ Couldn't check for new commits: {:#}", e);
+                return;
+            }
+        };
+        if self.revision.as_ref() != Some(&revision) {
+            self.revision = Some(revision);
+            self.regenerate().await;
+        }
+    }
+
+    async fn regenerate(&mut self) {
+        println!("🔄 Regenerating the preview");
+        match self.source.render().await {
+            Ok(page) => {
+                self.page = Some(page);
+                self.error = None;
+            }
+            Err(e) => {
+                eprintln!("⚠️ Failed to regenerate the preview: {:#}", e);
+                self.error = Some(format!("{:#}", e));
+            }
+        }
+        self.version += 1;
+    }
+
+    async fn respond(&mut self, request: &Request) -> Response {
+        if !request.host.as_deref().is_some_and(is_loopback) {
+            return Response::status("403 Forbidden");
+        }
+        let (path, query) = request
+            .path
+            .split_once('?')
+            .unwrap_or((request.path.as_str(), ""));
+        match (request.method.as_str(), path) {
+            ("GET", "/") => Response::html(self.page()),
+            ("GET", "/version") => Response::text(self.version.to_string()),
+            ("POST", "/refresh") => {
+                let same_origin = request.origin.as_deref().is_none_or(|origin| {
+                    origin
+                        .split_once("://")
+                        .is_some_and(|(_, host)| is_loopback(host))
+                });
+                let token = query
+                    .split('&')
+                    .find_map(|pair| pair.strip_prefix("token="));
+                if !same_origin || token != Some(self.token.as_str()) {
+                    return Response::status("403 Forbidden");
+                }
+                self.regenerate().await;
+                Response::redirect("/")
+            }
+            (_, "/" | "/version" | "/refresh") => Response::status("405 Method Not Allowed"),
+            _ => Response::status("404 Not Found"),
+        }
+    }
+
+    /// The diary with a refresh button and a script that reloads the page once
+    /// a newer diary is available
+    fn page(&self) -> String {
+        let mut toolbar = format!(
+            "<form method=\"post\" action=\"/refresh?token={}\" style=\"position:fixed;top:1em;right:1em\"><button>Refresh</button></form>\n\
+             <script>\n\
+             setInterval(async () => {{\n\
+             const response = await fetch(\"/version\");\n\
+             if ((await response.text()) !== \"{}\") location.reload();\n\
+             }}, {});\n\
+             </script>\n",
+            self.token, self.version, PAGE_POLL_MILLIS
+        );
+        if let Some(error) = &self.error {
+            toolbar.push_str(&format!(
+                "<p role=\"alert\">⚠️ Regenerating failed: {}</p>\n",
+                escape_html(error)
+            ));
+        }
+
+        let page = self
+            .page
+            .as_deref()
+            .unwrap_or("<!DOCTYPE html>\n<html>\n<body>\n<p>No diary yet.</p>\n</body>\n</html>\n");
+        match page.rfind("</body>") {
+            Some(end) => format!("{}{}{}", &page[..end], toolbar, &page[end..]),
+            None => format!("{}{}", page, toolbar),
+        }
+    }
+}
+
+/// The parts of a request the server looks at
+struct Request {
+    method: String,
+    path: String,
+    host: Option<String>,
+    origin: Option<String>,
+}
+
+/// Reads a request from `stream`, hands it to the server and writes back its
+/// response, giving up on clients that stall
+async fn exchange(
+    mut stream: TcpStream,
+    server: mpsc::Sender<(Request, oneshot::Sender<Response>)>,
+) -> Result<()> {
+    let request = tokio::time::timeout(CLIENT_TIMEOUT, read_request(&mut stream))
+        .await
+        .context("Timed out reading the request")??;
+    let (reply, response) = oneshot::channel();
+    server
+        .send((request, reply))
+        .await
+        .context("The preview server stopped")?;
+    let response = response.await.context("The preview server stopped")?;
+
+    tokio::time::timeout(CLIENT_TIMEOUT, async {
+        stream.write_all(&response.into_bytes()).await?;
+        stream.shutdown().await
+    })
+    .await
+    .context("Timed out writing the response")??;
+    Ok(())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // No route takes a body, so reading stops after the headers
+    let mut host = None;
+    let mut origin = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = Some(value.trim().to_string());
+            if name.eq_ignore_ascii_case("host") {
+                host = value;
+            } else if name.eq_ignore_ascii_case("origin") {
+                origin = value;
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    Ok(Request {
+        method: parts.next().unwrap_or_default().to_string(),
+        path: parts.next().unwrap_or_default().to_string(),
+        host,
+        origin,
+    })
+}
+
+/// Whether `host`, with or without a port, names this machine's loopback interface
+fn is_loopback(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(bracketed) => bracketed.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    matches!(name, "localhost" | "127.0.0.1" | "::1")
+}
+
+/// A random token for the refresh form, unique to this server
+fn session_token() -> String {
+    (0..2)
+        .map(|_| {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u128(
+                std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos(),
+            );
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+struct Response {
+    status: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+impl Response {
+    fn html(body: String) -> Self {
+        Self {
+            status: "200 OK",
+            headers: vec![("Content-Type", "text/html; charset=utf-8".to_string())],
+            body,
+        }
+    }
+
+    fn text(body: String) -> Self {
+        Self {
+            status: "200 OK",
+            headers: vec![("Content-Type", "text/plain; charset=utf-8".to_string())],
+            body,
+        }
+    }
+
+    fn redirect(location: &str) -> Self {
+        Self {
+            status: "303 See Other",
+            headers: vec![("Location", location.to_string())],
+            body: String::new(),
+        }
+    }
+
+    fn status(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in self.headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str(&format!(
+            "Content-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+            self.body.len(),
+            self.body
+        ));
+        response.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::AsyncReadExt;
+
+    async fn request(address: SocketAddr, method: &str, path: &str) -> Result<String> {
+        request_with(address, method, path, "Host: localhost").await
+    }
+
+    async fn request_with(
+        address: SocketAddr,
+        method: &str,
+        path: &str,
+        headers: &str,
+    ) -> Result<String> {
+        let mut stream = TcpStream::connect(address).await?;
+        stream
+            .write_all(format!("{} {} HTTP/1.1\r\n{}\r\n\r\n", method, path, headers).as_bytes())
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    /// The refresh form's action, with its token
+    fn refresh_path(page: &str) -> String {
+        let start = page.find("/refresh?token=").unwrap();
+        let end = start + page[start..].find('"').unwrap();
+        page[start..end].to_string()
+    }
+
+    #[tokio::test]
+    async fn test_preview_server() -> Result<()> {
+        let renders = Arc::new(AtomicUsize::new(0));
+        let mut source = MockPreviewSource::new();
+        source
+            .expect_revision()
+            .returning(|| Ok("abc123".to_string()));
+        let counter = renders.clone();
+        source.expect_render().returning(move || {
+            let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(format!("<html><body><p>Diary {}</p></body></html>", count))
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server = PreviewServer::new(source).with_poll_interval(Duration::from_secs(3600));
+        tokio::spawn(server.serve(listener));
+
+        let page = request(address, "GET", "/").await?;
+        assert!(page.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(page.contains("<p>Diary 1</p><form method=\"post\" action=\"/refresh?token="));
+        assert!(page.ends_with("</script>\n</body></html>"));
+        assert!(request(address, "GET", "/version")
+            .await?
+            .ends_with("\r\n\r\n1"));
+
+        let refresh = refresh_path(&page);
+        let refreshed = request(address, "POST", &refresh).await?;
+        assert!(refreshed.starts_with("HTTP/1.1 303 See Other\r\nLocation: /\r\n"));
+        assert!(request(address, "GET", "/")
+            .await?
+            .contains("<p>Diary 2</p>"));
+        assert!(request(address, "GET", "/version")
+            .await?
+            .ends_with("\r\n\r\n2"));
+
+        assert!(request(address, "GET", "/diaries")
+            .await?
+            .starts_with("HTTP/1.1 404 Not Found"));
+        assert!(request(address, "DELETE", "/")
+            .await?
+            .starts_with("HTTP/1.1 405 Method Not Allowed"));
+        // The revision didn't change, so only the refresh regenerated
+        assert_eq!(renders.load(Ordering::SeqCst), 2);
+
+        // Other sites can neither refresh nor, through DNS rebinding, read the diary
+        for (path, headers) in [
+            ("/refresh", "Host: localhost"),
+            ("/refresh?token=guess", "Host: localhost"),
+            (
+                refresh.as_str(),
+                "Host: localhost\r\nOrigin: https://evil.example",
+            ),
+            (refresh.as_str(), "Host: evil.example"),
+        ] {
+            assert!(request_with(address, "POST", path, headers)
+                .await?
+                .starts_with("HTTP/1.1 403 Forbidden"));
+        }
+        assert!(request_with(address, "GET", "/", "Host: evil.example:7878")
+            .await?
+            .starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(request_with(address, "GET", "/version", "Host: [::1]:7878")
+            .await?
+            .starts_with("HTTP/1.1 200 OK"));
+        assert_eq!(renders.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idle_client_does_not_block_others() -> Result<()> {
+        let mut source = MockPreviewSource::new();
+        source
+            .expect_revision()
+            .returning(|| Ok("abc123".to_string()));
+        source
+            .expect_render()
+            .returning(|| Ok("<body>Diary</body>".to_string()));
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        tokio::spawn(PreviewServer::new(source).serve(listener));
+
+        let mut idle = TcpStream::connect(address).await?;
+        idle.write_all(b"GET / HTTP/1.1\r\n").await?;
+        let page =
+            tokio::time::timeout(Duration::from_secs(5), request(address, "GET", "/")).await??;
+        assert!(page.contains("Diary"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_regeneration_keeps_the_last_diary() {
+        let mut source = MockPreviewSource::new();
+        let mut renders = 0;
+        source.expect_render().returning(move || {
+            renders += 1;
+            match renders {
+                1 => Ok("<body>Diary</body>".to_string()),
+                _ => Err(anyhow::anyhow!("API key <missing>")),
+            }
+        });
+        let mut server = PreviewServer::new(source);
+
+        server.regenerate().await;
+        server.regenerate().await;
+
+        let page = server.page();
+        assert!(page.starts_with("<body>Diary"));
+        assert!(page.contains("Regenerating failed: API key &lt;missing&gt;"));
+    }
+}
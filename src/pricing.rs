@@ -0,0 +1,144 @@
+/// USD price per million tokens as (prompt, completion)
+///
+/// Longer prefixes must come first so `gpt-4o-mini` is not priced as `gpt-4o`.
+const PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4.1-nano", 0.10, 0.40),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5-turbo", 0.50, 1.50),
+];
+
+/// Context window in tokens, matched by prefix like the prices
+const CONTEXT_WINDOWS: &[(&str, u32)] = &[
+    ("gpt-4o-mini", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+];
+
+/// Context a model needs for a day of commits plus the longest summary
+pub const MIN_CONTEXT_TOKENS: u32 = 8_192;
+
+/// Returns the context window of a model in tokens, if it is known
+pub fn context_window(model: &str) -> Option<u32> {
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, tokens)| *tokens)
+}
+
+/// Estimates the cost in USD of a request, if the model's price is known
+pub fn estimate_cost(model: &str, prompt_tokens: u32, completion_tokens: u32) -> Option<f64> {
+    PRICES
+        .iter()
+        .find(|(prefix, _, _)| model.starts_with(prefix))
+        .map(|(_, prompt_price, completion_price)| {
+            (prompt_tokens as f64 * prompt_price + completion_tokens as f64 * completion_price)
+                / 1_000_000.0
+        })
+}
+
+/// Rough token count for text, about four characters per token for English
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(4) as u32
+}
+
+/// Worst-case size and cost of summary requests before they are sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    pub model: String,
+    pub prompt_tokens: u32,
+    /// Completion tokens if every summary runs up to `max_tokens`
+    pub max_completion_tokens: u32,
+}
+
+impl CostEstimate {
+    pub fn new(model: String, prompt_tokens: u32, max_completion_tokens: u32) -> Self {
+        Self {
+            model,
+            prompt_tokens,
+            max_completion_tokens,
+        }
+    }
+
+    /// The estimate for sending the same request `times` times
+    pub fn times(self, times: usize) -> Self {
+        let times = times as u32;
+        Self {
+            prompt_tokens: self.prompt_tokens * times,
+            max_completion_tokens: self.max_completion_tokens * times,
+            ..self
+        }
+    }
+
+    /// Cost in USD at most, if the model's price is known
+    pub fn cost(&self) -> Option<f64> {
+        estimate_cost(&self.model, self.prompt_tokens, self.max_completion_tokens)
+    }
+}
+
+/// "~1200 prompt + up to 500 completion tokens on gpt-4o-mini (~$0.0005)"
+impl std::fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "~{} prompt + up to {} completion tokens on {}",
+            self.prompt_tokens, self.max_completion_tokens, self.model
+        )?;
+        match self.cost() {
+            Some(cost) => write!(f, " (~${:.4})", cost),
+            None => write!(f, " (price unknown)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_cost() {
+        let cost = estimate_cost("gpt-4o-mini", 1_000_000, 1_000_000).unwrap();
+        assert!((cost - 0.75).abs() < 1e-9);
+
+        let cost = estimate_cost("gpt-4", 1000, 500).unwrap();
+        assert!((cost - 0.06).abs() < 1e-9);
+
+        assert_eq!(estimate_cost("llama3", 1000, 1000), None);
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("gpt-4o-2024-08-06"), Some(128_000));
+        assert_eq!(context_window("gpt-4.1-mini"), Some(1_047_576));
+        assert_eq!(context_window("gpt-4-0613"), Some(8_192));
+        assert_eq!(context_window("llama3"), None);
+    }
+
+    #[test]
+    fn test_cost_estimate() {
+        let estimate = CostEstimate::new("gpt-4o-mini".to_string(), 1000, 500).times(2);
+        assert_eq!(estimate.prompt_tokens, 2000);
+        assert_eq!(
+            estimate.to_string(),
+            "~2000 prompt + up to 1000 completion tokens on gpt-4o-mini (~$0.0009)"
+        );
+        assert_eq!(
+            CostEstimate::new("llama3".to_string(), 10, 5).to_string(),
+            "~10 prompt + up to 5 completion tokens on llama3 (price unknown)"
+        );
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+    }
+}
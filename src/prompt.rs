@@ -0,0 +1,1035 @@
+use serde::Deserialize;
+
+use crate::domain::{Commit, FileRename, HighlightsRequest, SummaryRequest};
+use crate::perf::performance_claims;
+use crate::pricing::estimate_tokens;
+use crate::related::related_commits;
+use crate::structured::MAX_TOPICS;
+
+/// Seed sent with `--deterministic`, to the API or a local model
+pub const DETERMINISTIC_SEED: i64 = 42;
+
+/// Most rename descriptions listed for a single commit
+const MAX_RENAME_DESCRIPTIONS: usize = 5;
+
+/// Most groups of related commits pointed out to the model
+const MAX_RELATED_GROUPS: usize = 10;
+
+/// How long the generated summary should be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SummaryLength {
+    /// A line or two, for standups
+    Short,
+    /// A paragraph, for daily diaries
+    #[default]
+    Medium,
+    /// Several paragraphs, for weekly rollups
+    Long,
+}
+
+impl SummaryLength {
+    /// Sentence count the model is asked to aim for
+    pub fn sentences(self) -> &'static str {
+        match self {
+            SummaryLength::Short => "1-2",
+            SummaryLength::Medium => "3-5",
+            SummaryLength::Long => "10-15",
+        }
+    }
+
+    /// Completion token limit, growing roughly fourfold per step
+    pub fn max_tokens(self) -> u32 {
+        match self {
+            SummaryLength::Short => 250,
+            SummaryLength::Medium => 1000,
+            SummaryLength::Long => 4000,
+        }
+    }
+}
+
+/// Builds the instructions sent as the system message of every summary request
+pub fn system_prompt(length: SummaryLength) -> String {
+    format!("You are an expert software development analyst. Your task is to create a concise, professional diary entry summarizing recent development work based on Git commit messages.
+
+Instructions:
+1. Analyze the provided commit messages to understand the development activities
+2. Group related commits by theme (features, bug fixes, refactoring, documentation, etc.)
+3. Write a clear, narrative summary in diary format using past tense
+4. Focus on what was accomplished, not just what was changed
+5. Highlight significant features, improvements, or architectural decisions
+6. Mention any notable patterns or development trends
+7. Keep the tone professional but engaging
+8. Aim for {} sentences that capture the essence of the work period
+9. Entries starting with \"Attempted and reverted:\" describe work that was rolled back; mention it as attempted and reverted, never as shipped
+10. Bracketed notes such as \"[moved module X to Y (40 files)]\" come from rename detection; describe mass moves as a single restructuring, not as many separate changes
+
+Format your response as a diary entry starting with a brief overview, followed by key accomplishments grouped logically.", length.sentences())
+}
+
+/// Share of the prompt each section may take when a `[prompt_budget]` is configured
+///
+/// The shares are relative to each other. A section that needs less than its
+/// share hands the rest to the sections that need more, in proportion to their
+/// shares, so a long list of notes can't crowd out the commits.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PromptBudget {
+    /// Tokens all sections may take together
+    pub total_tokens: u32,
+    pub commits: f64,
+    pub notes: f64,
+    pub context: f64,
+    pub reviews: f64,
+    pub tickets: f64,
+    pub goals: f64,
+    pub glossary: f64,
+}
+
+impl Default for PromptBudget {
+    fn default() -> Self {
+        Self {
+            total_tokens: 6000,
+            commits: 0.6,
+            notes: 0.1,
+            context: 0.1,
+            reviews: 0.05,
+            tickets: 0.05,
+            goals: 0.05,
+            glossary: 0.1,
+        }
+    }
+}
+
+impl PromptBudget {
+    /// Tokens for each section, given how many each one needs
+    ///
+    /// Sections are settled in rounds: every section that fits its allowance
+    /// gets what it needs, and the rest of its allowance is shared out among
+    /// those still over theirs.
+    pub fn allocate(&self, needs: &[(f64, u32)]) -> Vec<u32> {
+        let mut allowances = vec![0; needs.len()];
+        let mut open: Vec<usize> = (0..needs.len()).collect();
+        let mut remaining = self.total_tokens;
+
+        loop {
+            let shares: f64 = open.iter().map(|&i| needs[i].0.max(0.0)).sum();
+            let allowance = |i: usize| {
+                if shares > 0.0 {
+                    (remaining as f64 * needs[i].0.max(0.0) / shares) as u32
+                } else {
+                    0
+                }
+            };
+            let (fitting, over): (Vec<usize>, Vec<usize>) =
+                open.iter().partition(|&&i| needs[i].1 <= allowance(i));
+            if fitting.is_empty() {
+                for &i in &over {
+                    allowances[i] = allowance(i);
+                }
+                return allowances;
+            }
+            for &i in &fitting {
+                allowances[i] = needs[i].1;
+                remaining -= needs[i].1;
+            }
+            open = over;
+        }
+    }
+}
+
+/// A section of the user prompt: a heading and one line per item
+struct Section {
+    heading: String,
+    lines: Vec<String>,
+    share: f64,
+    /// Noun for the notice about left-out lines, such as "notes"
+    items: &'static str,
+}
+
+impl Section {
+    fn new(heading: &str, lines: Vec<String>, share: f64, items: &'static str) -> Self {
+        Self {
+            heading: heading.to_string(),
+            lines,
+            share,
+            items,
+        }
+    }
+
+    fn tokens(&self) -> u32 {
+        self.lines
+            .iter()
+            .map(|line| estimate_tokens(line) + 1)
+            .sum()
+    }
+
+    /// Keeps the leading lines that fit in `tokens` and notes how many were left out
+    ///
+    /// A first line that doesn't fit on its own is cut short, so the section
+    /// never disappears entirely.
+    fn truncate(&mut self, tokens: u32) {
+        let mut used = 0;
+        let mut kept = 0;
+        for line in &self.lines {
+            let cost = estimate_tokens(line) + 1;
+            if used + cost > tokens {
+                break;
+            }
+            used += cost;
+            kept += 1;
+        }
+        if kept == 0 && !self.lines.is_empty() {
+            let chars = (tokens as usize * 4).saturating_sub(1);
+            let cut: String = self.lines[0].chars().take(chars).collect();
+            self.lines[0] = format!("{}…", cut);
+            kept = 1;
+        }
+
+        let omitted = self.lines.len() - kept;
+        if omitted > 0 {
+            self.lines.truncate(kept);
+            self.lines.push(format!(
+                "({} more {} left out to fit the prompt budget)",
+                omitted, self.items
+            ));
+        }
+    }
+}
+
+/// Builds the user message listing the commits to summarize
+///
+/// With a prompt budget, sections over their share keep their leading lines;
+/// commits come newest first, so the oldest are the ones left out.
+pub fn build_user_prompt(request: &SummaryRequest) -> String {
+    let budget = request.budget.clone().unwrap_or_default();
+    let mut sections = vec![Section::new(
+        "Commit messages to summarize:",
+        request.commits.iter().map(commit_line).collect(),
+        budget.commits,
+        "commits",
+    )];
+
+    if !request.notes.is_empty() {
+        sections.push(Section::new(
+            "Manual notes from the developer, to use as extra context:",
+            request.notes.iter().map(|note| note.to_string()).collect(),
+            budget.notes,
+            "notes",
+        ));
+    }
+
+    if !request.context.is_empty() {
+        sections.push(Section::new(
+            "Other activities the developer mentioned, which left no commits; include them in the summary:",
+            request
+                .context
+                .iter()
+                .map(|activity| format!("- {}", activity))
+                .collect(),
+            budget.context,
+            "activities",
+        ));
+    }
+
+    if !request.reviews.is_empty() {
+        sections.push(Section::new(
+            "Pull requests the developer reviewed; mention them briefly, such as \"Reviewed 4 PRs, notably the payment retry logic\":",
+            request
+                .reviews
+                .iter()
+                .map(|review| format!("- {}", review))
+                .collect(),
+            budget.reviews,
+            "reviews",
+        ));
+    }
+
+    if !request.tickets.is_empty() {
+        sections.push(Section::new(
+            "Issues the developer moved on the tracker; weave them into the narrative, such as \"moved PAY-142 to Done\", \
+             even when no commit mentions them:",
+            request
+                .tickets
+                .iter()
+                .map(|ticket| format!("- {}", ticket))
+                .collect(),
+            budget.tickets,
+            "tickets",
+        ));
+    }
+
+    if !request.goals.is_empty() {
+        sections.push(Section::new(
+            "The developer's goals; end the diary entry with one line per goal stating the progress made, \
+             such as \"Goal: ship exporter — shipped CSV export\", or \"no progress\" when no work touched it:",
+            request
+                .goals
+                .iter()
+                .map(|goal| format!("- {}", goal))
+                .collect(),
+            budget.goals,
+            "goals",
+        ));
+    }
+
+    if !request.glossary.is_empty() {
+        sections.push(Section::new(
+            "Project terminology; use these names exactly as written instead of paraphrasing them:",
+            request
+                .glossary
+                .iter()
+                .map(|entry| match &entry.description {
+                    Some(description) => format!("- {}: {}", entry.term, description),
+                    None => format!("- {}", entry.term),
+                })
+                .collect(),
+            budget.glossary,
+            "terms",
+        ));
+    }
+
+    if request.budget.is_some() {
+        let needs: Vec<(f64, u32)> = sections
+            .iter()
+            .map(|section| (section.share, section.tokens()))
+            .collect();
+        for (section, tokens) in sections.iter_mut().zip(budget.allocate(&needs)) {
+            section.truncate(tokens);
+        }
+    }
+
+    let mut prompt = sections
+        .iter()
+        .map(|section| format!("{}\n\n{}", section.heading, section.lines.join("\n")))
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    if !request.examples.is_empty() {
+        prompt.push_str(
+            "\n\nPast diary entries the developer liked; match their tone, structure and \
+             level of detail, but describe only the work above:",
+        );
+        for example in &request.examples {
+            prompt.push_str(&format!("\n\n{}:\n{}", example.period, example.summary));
+        }
+    }
+
+    let diffs = trimmed_diffs(&request.commits, request.diff_tokens);
+    if !diffs.is_empty() {
+        prompt.push_str(
+            "\n\nDiffs of the commits, trimmed to fit; use them to describe what changed \
+             more precisely than the messages do:",
+        );
+        for (commit, diff) in diffs {
+            prompt.push_str(&format!(
+                "\n\n{}:\n```diff\n{}\n```",
+                commit.subject(),
+                diff
+            ));
+        }
+    }
+
+    if request.omitted_commits > 0 {
+        prompt.push_str(&format!(
+            "\n\nNote: {} older commits were left out of this list to keep the prompt small. \
+             Mention that the summary only covers the most recent work.",
+            request.omitted_commits
+        ));
+    }
+
+    let related = related_commits(&request.commits);
+    if !related.is_empty() {
+        prompt.push_str(
+            "\n\nThese commits look like one logical change each, such as a feature and its \
+             fixups; describe each group as a single piece of work rather than counting it \
+             several times:",
+        );
+        for group in related.iter().take(MAX_RELATED_GROUPS) {
+            prompt.push_str(&format!("\n- {}", group));
+        }
+    }
+
+    if let Some(tests) = &request.tests {
+        prompt.push_str(&format!(
+            "\n\nLocal test runs in the period: {}. Mention how the test suite fared, \
+             such as \"fixed 3 failing tests, suite now green\", without listing every test.",
+            tests
+        ));
+    }
+
+    let claims = performance_claims(&request.commits);
+    if !claims.is_empty() {
+        let figures: Vec<String> = claims.into_iter().flat_map(|claim| claim.figures).collect();
+        prompt.push_str(
+            "\n\nSome commits are performance work. Describe what was sped up or slimmed down, \
+             quote measured improvements exactly as the commits state them, and never estimate \
+             figures the commits don't give.",
+        );
+        if !figures.is_empty() {
+            prompt.push_str(&format!(" Claimed figures: {}.", figures.join("; ")));
+        }
+    }
+
+    if let Some(language) = &request.language {
+        prompt.push_str(&format!(
+            "\n\nWrite the diary entry in {}, whatever language the commit messages use.",
+            language_name(language)
+        ));
+    }
+
+    if request.topics {
+        prompt.push_str(&format!(
+            "\n\nAfter the diary entry, on a line of its own, add a JSON object with up to {} \
+             short lowercase English topic tags for the work, such as \
+             {{\"topics\": [\"billing\", \"ci\"]}}.",
+            MAX_TOPICS
+        ));
+    }
+
+    prompt
+}
+
+/// Each commit's diff cut to its share of `tokens`, in commit order
+///
+/// Diffs no larger than an equal share of `tokens` are quoted whole, so a
+/// one-line fix isn't crowded out by a large refactor. The tokens left are
+/// shared out among the larger diffs in proportion to their size, and the
+/// diffs never take more than `tokens` in total. Diffs are cut at line
+/// boundaries; commits whose share holds no line are left out.
+fn trimmed_diffs(commits: &[Commit], tokens: u32) -> Vec<(&Commit, String)> {
+    let diffs: Vec<(&Commit, &str)> = commits
+        .iter()
+        .filter_map(|commit| Some((commit, commit.diff.as_deref()?)))
+        .filter(|(_, diff)| !diff.trim().is_empty())
+        .collect();
+    if tokens == 0 || diffs.is_empty() {
+        return Vec::new();
+    }
+
+    let equal_share = tokens / diffs.len() as u32;
+    let sizes: Vec<u32> = diffs.iter().map(|(_, diff)| diff_tokens(diff)).collect();
+    let small: u32 = sizes.iter().filter(|&&size| size <= equal_share).sum();
+    let needs: Vec<(f64, u32)> = sizes
+        .iter()
+        .map(|&size| {
+            if size <= equal_share {
+                (0.0, 0)
+            } else {
+                (size as f64, size)
+            }
+        })
+        .collect();
+    let budget = PromptBudget {
+        total_tokens: tokens - small,
+        ..PromptBudget::default()
+    };
+    let shares = budget.allocate(&needs);
+
+    diffs
+        .into_iter()
+        .zip(sizes.into_iter().zip(shares))
+        .filter_map(|((commit, diff), (size, share))| {
+            let share = if size <= equal_share { size } else { share };
+            truncate_diff(diff, share).map(|diff| (commit, diff))
+        })
+        .collect()
+}
+
+fn diff_tokens(diff: &str) -> u32 {
+    diff.lines().map(|line| estimate_tokens(line) + 1).sum()
+}
+
+/// The leading lines of `diff` that fit in `tokens`, marked when lines were
+/// cut; the mark counts against `tokens` too
+fn truncate_diff(diff: &str, tokens: u32) -> Option<String> {
+    if diff_tokens(diff) <= tokens {
+        return Some(diff.trim_end_matches('\n').to_string());
+    }
+
+    let lines: Vec<&str> = diff.lines().collect();
+    let tokens = tokens.saturating_sub(diff_tokens(&cut_mark(lines.len())));
+    let mut used = 0;
+    let mut kept = 0;
+    for line in &lines {
+        let cost = estimate_tokens(line) + 1;
+        if used + cost > tokens {
+            break;
+        }
+        used += cost;
+        kept += 1;
+    }
+    if kept == 0 {
+        return None;
+    }
+
+    Some(format!(
+        "{}\n{}",
+        lines[..kept].join("\n"),
+        cut_mark(lines.len() - kept)
+    ))
+}
+
+fn cut_mark(lines: usize) -> String {
+    format!("… ({} more lines cut)", lines)
+}
+
+/// English name of a language code such as `ja`, for prompts; unknown codes are returned as given
+pub fn language_name(code: &str) -> &str {
+    match code.to_ascii_lowercase().as_str() {
+        "en" => "English",
+        "ja" => "Japanese",
+        "zh" => "Chinese",
+        "ko" => "Korean",
+        "de" => "German",
+        "fr" => "French",
+        "es" => "Spanish",
+        "pt" => "Portuguese",
+        "it" => "Italian",
+        "nl" => "Dutch",
+        "ru" => "Russian",
+        _ => code,
+    }
+}
+
+/// Instructions for turning months of work into resume bullet points
+pub const HIGHLIGHTS_SYSTEM_PROMPT: &str = "You are an expert career coach who writes resumes for software engineers. Your task is to find the most significant achievements in months of development work.
+
+Instructions:
+1. Read the diary summaries and commit messages, which cover a long period
+2. Pick the 5-10 most significant achievements: shipped features, architectural changes, performance or reliability wins, and leadership or collaboration
+3. Merge related work spread over many weeks into a single achievement
+4. Skip routine maintenance, small fixes and work that was attempted and reverted
+5. Write each achievement as one resume bullet starting with a strong past-tense verb
+6. Mention scale or impact when the input supports it, and never invent numbers
+
+Respond with only the bullet list, one \"- \" bullet per line.";
+
+/// Builds the user message for highlights, oldest material first
+pub fn build_highlights_prompt(request: &HighlightsRequest) -> String {
+    let mut sections = Vec::new();
+
+    if !request.diaries.is_empty() {
+        sections.push(format!(
+            "Diary summaries:\n\n{}",
+            request
+                .diaries
+                .iter()
+                .map(|diary| format!("### {}\n\n{}", diary.period, diary.summary.trim()))
+                .collect::<Vec<String>>()
+                .join("\n\n")
+        ));
+    }
+
+    if !request.commits.is_empty() {
+        sections.push(format!(
+            "Commit messages:\n\n{}",
+            request
+                .commits
+                .iter()
+                .map(commit_line)
+                .collect::<Vec<String>>()
+                .join("\n")
+        ));
+    }
+
+    if request.omitted_commits > 0 {
+        sections.push(format!(
+            "Note: {} older commits were left out of this list to keep the prompt small.",
+            request.omitted_commits
+        ));
+    }
+
+    sections.join("\n\n")
+}
+
+fn commit_line(commit: &Commit) -> String {
+    let descriptions = describe_renames(&commit.renames);
+    if descriptions.is_empty() {
+        return commit.to_string();
+    }
+
+    let mut shown = descriptions[..descriptions.len().min(MAX_RENAME_DESCRIPTIONS)].join("; ");
+    if descriptions.len() > MAX_RENAME_DESCRIPTIONS {
+        shown.push_str(&format!(
+            "; and {} more",
+            descriptions.len() - MAX_RENAME_DESCRIPTIONS
+        ));
+    }
+    format!("{} [{}]", commit, shown)
+}
+
+/// Describes renames in a few words, collapsing files that moved together
+///
+/// Files that keep their path below a moved directory are reported once as
+/// "moved module X to Y", so a mass rename doesn't read as hundreds of
+/// unrelated changes.
+pub fn describe_renames(renames: &[FileRename]) -> Vec<String> {
+    // Moved directories, with the first file seen and the number of files
+    let mut modules: Vec<(String, String, &FileRename, usize)> = Vec::new();
+    let mut singles = Vec::new();
+
+    for rename in renames {
+        match moved_directory(rename) {
+            Some((from, to)) if !rename.copied => {
+                match modules
+                    .iter_mut()
+                    .find(|module| module.0 == from && module.1 == to)
+                {
+                    Some(module) => module.3 += 1,
+                    None => modules.push((from, to, rename, 1)),
+                }
+            }
+            _ => singles.push(rename),
+        }
+    }
+
+    let mut descriptions = Vec::new();
+    let mut lone_moves = Vec::new();
+    for (from, to, first, count) in modules {
+        if count > 1 {
+            descriptions.push(format!("moved module {} to {} ({} files)", from, to, count));
+        } else {
+            lone_moves.push(first);
+        }
+    }
+    for rename in lone_moves.into_iter().chain(singles) {
+        let verb = if rename.copied { "copied" } else { "moved" };
+        descriptions.push(format!("{} {} to {}", verb, rename.from, rename.to));
+    }
+
+    descriptions
+}
+
+/// Splits off the path components both sides share at the end, returning the
+/// directories that differ, such as `src/old` and `src/new` for
+/// `src/old/a/b.rs` -> `src/new/a/b.rs`
+fn moved_directory(rename: &FileRename) -> Option<(String, String)> {
+    let from: Vec<&str> = rename.from.split('/').collect();
+    let to: Vec<&str> = rename.to.split('/').collect();
+    let shared = from
+        .iter()
+        .rev()
+        .zip(to.iter().rev())
+        .take_while(|(from, to)| from == to)
+        .count();
+
+    if shared == 0 || shared == from.len() || shared == to.len() {
+        return None;
+    }
+
+    Some((
+        from[..from.len() - shared].join("/"),
+        to[..to.len() - shared].join("/"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{DiarySummary, GlossaryTerm, Note, Review, TicketTransition};
+    use crate::testruns::TestHealth;
+
+    #[test]
+    fn test_build_user_prompt() {
+        let request = SummaryRequest::new(vec![
+            Commit::new("Initial commit".to_string(), 1704067200),
+            Commit::new("Add README.md".to_string(), 1704153600),
+        ]);
+
+        let prompt = build_user_prompt(&request);
+
+        assert!(prompt.starts_with("Commit messages to summarize:"));
+        assert!(prompt.contains("Initial commit"));
+        assert!(prompt.contains("Add README.md"));
+        assert!(!prompt.contains("left out"));
+    }
+
+    #[test]
+    fn test_build_user_prompt_notes_truncation() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Latest commit".to_string(), 1704067200)]);
+        request.omitted_commits = 42;
+
+        let prompt = build_user_prompt(&request);
+
+        assert!(prompt.contains("42 older commits were left out"));
+    }
+
+    #[test]
+    fn test_system_prompt_length() {
+        assert!(system_prompt(SummaryLength::default()).contains("Aim for 3-5 sentences"));
+        assert!(system_prompt(SummaryLength::Short).contains("Aim for 1-2 sentences"));
+        assert!(system_prompt(SummaryLength::Long).contains("Aim for 10-15 sentences"));
+        assert!(SummaryLength::Short.max_tokens() < SummaryLength::Long.max_tokens());
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_notes() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Fix cache bug".to_string(), 1704067200)]);
+        request.notes = vec![Note::new(
+            "Paired with Alice on the cache bug".to_string(),
+            1704070800,
+        )];
+
+        let prompt = build_user_prompt(&request);
+
+        assert!(prompt.contains(
+            "Manual notes from the developer, to use as extra context:\n\n2024-01-01 01:00:00: Paired with Alice on the cache bug"
+        ));
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_context() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Fix cache bug".to_string(), 1704067200)]);
+        assert!(!build_user_prompt(&request).contains("Other activities"));
+
+        request.context = vec!["I also spent 3 hours in design review".to_string()];
+
+        assert!(build_user_prompt(&request).contains(
+            "Other activities the developer mentioned, which left no commits; include them in the summary:\n\n- I also spent 3 hours in design review"
+        ));
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_reviews() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Fix cache bug".to_string(), 1704067200)]);
+        request.reviews = vec![Review::new(
+            12,
+            "Retry failed payments".to_string(),
+            "approved".to_string(),
+        )
+        .with_submissions(2)];
+
+        assert!(build_user_prompt(&request).contains(
+            "Pull requests the developer reviewed; mention them briefly, such as \"Reviewed 4 PRs, notably the payment retry logic\":\n\n- #12 Retry failed payments (approved, 2 reviews)"
+        ));
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_glossary() {
+        let mut request = SummaryRequest::new(vec![Commit::new(
+            "Fix sync retries".to_string(),
+            1704067200,
+        )]);
+        request.glossary = vec![
+            GlossaryTerm::new("SyncEngine".to_string())
+                .with_description(Some("the offline sync module".to_string())),
+            GlossaryTerm::new("ledger_v2".to_string()),
+        ];
+
+        assert!(build_user_prompt(&request).contains(
+            "use these names exactly as written instead of paraphrasing them:\n\n- SyncEngine: the offline sync module\n- ledger_v2"
+        ));
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_tickets() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Add CSV export".to_string(), 1704067200)]);
+        assert!(!build_user_prompt(&request).contains("tracker"));
+
+        request.tickets = vec![TicketTransition::new(
+            "PAY-142".to_string(),
+            "Retry failed payments".to_string(),
+            "In Review".to_string(),
+        )];
+        assert!(build_user_prompt(&request).contains(
+            "even when no commit mentions them:\n\n- PAY-142 Retry failed payments (moved to In Review)"
+        ));
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_goals() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Add CSV export".to_string(), 1704067200)]);
+        assert!(!build_user_prompt(&request).contains("goals"));
+
+        request.goals = vec![
+            "Ship the exporter".to_string(),
+            "Write the migration guide".to_string(),
+        ];
+        let prompt = build_user_prompt(&request);
+        assert!(prompt.contains("one line per goal"));
+        assert!(prompt.contains(":\n\n- Ship the exporter\n- Write the migration guide"));
+    }
+
+    #[test]
+    fn test_build_user_prompt_includes_examples() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Add CSV export".to_string(), 1704067200)]);
+        assert!(!build_user_prompt(&request).contains("liked"));
+
+        request.examples = vec![DiarySummary {
+            period: "2024-05-06 – 2024-05-12".to_string(),
+            summary: "Shipped the exporter.".to_string(),
+        }];
+        let prompt = build_user_prompt(&request);
+        assert!(prompt.contains("diary entries the developer liked"));
+        assert!(prompt.contains(":\n\n2024-05-06 – 2024-05-12:\nShipped the exporter."));
+    }
+
+    #[test]
+    fn test_prompt_budget_allocate() {
+        let budget = PromptBudget {
+            total_tokens: 1000,
+            ..PromptBudget::default()
+        };
+
+        // Small sections keep everything and hand the rest to the others
+        assert_eq!(
+            budget.allocate(&[(0.6, 2000), (0.2, 50), (0.2, 900)]),
+            vec![712, 50, 237]
+        );
+        assert_eq!(budget.allocate(&[(0.6, 300), (0.4, 200)]), vec![300, 200]);
+        assert_eq!(budget.allocate(&[(0.0, 10)]), vec![0]);
+    }
+
+    #[test]
+    fn test_build_user_prompt_with_budget() {
+        let commits = (0..40)
+            .map(|i| Commit::new(format!("Commit number {} of the day", i), 1704067200 - i))
+            .collect();
+        let mut request = SummaryRequest::new(commits);
+        request.notes = (0..20)
+            .map(|i| {
+                Note::new(
+                    format!("A long note about the design number {}", i),
+                    1704067200,
+                )
+            })
+            .collect();
+        request.goals = vec!["Ship the exporter".to_string()];
+        request.budget = Some(PromptBudget {
+            total_tokens: 200,
+            ..PromptBudget::default()
+        });
+
+        let prompt = build_user_prompt(&request);
+
+        // Commits come newest first, so the oldest are the ones left out
+        assert!(prompt.contains("Commit number 0 of the day"));
+        assert!(!prompt.contains("Commit number 39 of the day"));
+        assert!(prompt.contains("more commits left out to fit the prompt budget)"));
+        assert!(prompt.contains("more notes left out to fit the prompt budget)"));
+        assert!(prompt.contains("- Ship the exporter"));
+        assert_eq!(prompt, build_user_prompt(&request));
+
+        request.budget = None;
+        assert!(!build_user_prompt(&request).contains("prompt budget"));
+    }
+
+    #[test]
+    fn test_build_user_prompt_performance_work() {
+        let mut request = SummaryRequest::new(vec![
+            Commit::new(
+                "perf: Cache configs, 2.3x faster startup".to_string(),
+                1704067200,
+            ),
+            Commit::new("Fix typo".to_string(), 1704070800),
+        ]);
+        let prompt = build_user_prompt(&request);
+        assert!(prompt.contains("Some commits are performance work."));
+        assert!(prompt.contains("Claimed figures: 2.3x faster."));
+
+        request.commits.remove(0);
+        assert!(!build_user_prompt(&request).contains("performance work"));
+    }
+
+    #[test]
+    fn test_build_user_prompt_diff_budget() {
+        let small = "--- a/src/cache.rs\n+++ b/src/cache.rs\n-    ttl: 60,\n+    ttl: 300,\n";
+        let large: String = (0..200)
+            .map(|i| format!("+    let field_{} = parse(input)?;\n", i))
+            .collect();
+        let mut request = SummaryRequest::new(vec![
+            Commit::new("Raise cache TTL".to_string(), 1704067200).with_diff(small.to_string()),
+            Commit::new("Add parser".to_string(), 1704067200).with_diff(large.clone()),
+            Commit::new("Tag release".to_string(), 1704067200),
+        ]);
+
+        assert!(!build_user_prompt(&request).contains("```diff"));
+
+        request.diff_tokens = 2000;
+        let prompt = build_user_prompt(&request);
+
+        let quoted: Vec<(&Commit, String)> = trimmed_diffs(&request.commits, 2000);
+        assert_eq!(quoted.len(), 2);
+        assert!(
+            quoted
+                .iter()
+                .map(|(_, diff)| diff_tokens(diff))
+                .sum::<u32>()
+                <= 2000
+        );
+        // The small diff fits its share and is quoted whole; the large one
+        // gets the rest
+        assert_eq!(quoted[0].1, small.trim_end());
+        assert!(quoted[1].1.ends_with("more lines cut)"));
+        assert!(diff_tokens(&quoted[1].1) > 2000 - diff_tokens(small) - 20);
+        assert!(prompt.contains("Raise cache TTL:\n```diff\n--- a/src/cache.rs\n+++ b/src/cache.rs\n-    ttl: 60,\n+    ttl: 300,\n```"));
+        assert!(prompt.contains("Add parser:\n```diff\n+    let field_0 = parse(input)?;"));
+        assert!(!prompt.contains("field_199"));
+
+        request.diff_tokens = 100_000;
+        let prompt = build_user_prompt(&request);
+        assert!(prompt.contains("+    ttl: 300,\n```"));
+        assert!(prompt.contains("field_199"));
+    }
+
+    #[test]
+    fn test_trimmed_diffs_shares_by_size() {
+        let lines = |count: usize| -> String {
+            (0..count)
+                .map(|i| format!("+    let field_{} = parse(input)?;\n", i))
+                .collect()
+        };
+        let commits = vec![
+            Commit::new("Add lexer".to_string(), 1704067200).with_diff(lines(100)),
+            Commit::new("Add parser".to_string(), 1704067200).with_diff(lines(300)),
+        ];
+
+        let quoted = trimmed_diffs(&commits, 1000);
+
+        let lexer = diff_tokens(&quoted[0].1);
+        let parser = diff_tokens(&quoted[1].1);
+        assert!(lexer + parser <= 1000);
+        // The parser diff is three times the size, so it gets about three times the tokens
+        assert!(parser > lexer * 2 && parser < lexer * 4);
+    }
+
+    #[test]
+    fn test_build_user_prompt_topics() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Fix cache bug".to_string(), 1704067200)]);
+        assert!(!build_user_prompt(&request).contains("topic tags"));
+
+        request.topics = true;
+        assert!(
+            build_user_prompt(&request).ends_with("such as {\"topics\": [\"billing\", \"ci\"]}.")
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_language() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Fix cache bug".to_string(), 1704067200)]);
+        assert!(!build_user_prompt(&request).contains("Write the diary entry in"));
+
+        request.language = Some("ja".to_string());
+        assert!(build_user_prompt(&request).ends_with(
+            "Write the diary entry in Japanese, whatever language the commit messages use."
+        ));
+
+        request.language = Some("sv".to_string());
+        assert!(build_user_prompt(&request).contains("Write the diary entry in sv,"));
+    }
+
+    #[test]
+    fn test_describe_renames() {
+        let mut renames: Vec<FileRename> = ["mod.rs", "parser.rs", "lexer/token.rs"]
+            .iter()
+            .map(|file| {
+                FileRename::new(
+                    format!("src/syntax/{}", file),
+                    format!("crates/syntax/src/{}", file),
+                )
+            })
+            .collect();
+        renames.push(FileRename::new(
+            "docs/old.md".to_string(),
+            "docs/new.md".to_string(),
+        ));
+        renames.push(FileRename::copy(
+            "ci/base.yml".to_string(),
+            "ci/nightly/base.yml".to_string(),
+        ));
+
+        assert_eq!(
+            describe_renames(&renames),
+            vec![
+                "moved module src/syntax to crates/syntax/src (3 files)",
+                "moved docs/old.md to docs/new.md",
+                "copied ci/base.yml to ci/nightly/base.yml",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_user_prompt_mentions_renames() {
+        let renames = (0..200)
+            .map(|i| FileRename::new(format!("old/f{}.rs", i), format!("new/f{}.rs", i)))
+            .collect();
+        let request = SummaryRequest::new(vec![Commit::new(
+            "Restructure crate".to_string(),
+            1704067200,
+        )
+        .with_renames(renames)]);
+
+        let prompt = build_user_prompt(&request);
+
+        assert!(prompt.contains("Restructure crate [moved module old to new (200 files)]"));
+    }
+
+    #[test]
+    fn test_build_user_prompt_related_commits() {
+        let request = SummaryRequest::new(vec![
+            Commit::new("fixup! Add CSV export".to_string(), 1704070800),
+            Commit::new("Add CSV export".to_string(), 1704067200),
+        ]);
+
+        let prompt = build_user_prompt(&request);
+
+        assert!(prompt.contains("describe each group as a single piece of work"));
+        assert!(prompt.contains("\n- \"Add CSV export\"; \"fixup! Add CSV export\" (fixups)"));
+        assert!(!build_user_prompt(&SummaryRequest::new(vec![Commit::new(
+            "Add CSV export".to_string(),
+            1704067200
+        )]))
+        .contains("logical change"));
+    }
+
+    #[test]
+    fn test_build_user_prompt_test_health() {
+        let mut request =
+            SummaryRequest::new(vec![Commit::new("Fix parser".to_string(), 1704067200)]);
+        request.tests = Some(TestHealth {
+            runs: 2,
+            total: 40,
+            failing: Vec::new(),
+            fixed: vec!["parses_dates".to_string()],
+            broken: Vec::new(),
+        });
+
+        let prompt = build_user_prompt(&request);
+
+        assert!(prompt.contains(
+            "Local test runs in the period: 2 test runs; fixed 1 failing test(s) (parses_dates); \
+             the suite is green (40 tests)."
+        ));
+    }
+
+    #[test]
+    fn test_build_highlights_prompt() {
+        let request = HighlightsRequest {
+            diaries: vec![DiarySummary {
+                period: "2024-05-06 – 2024-05-12".to_string(),
+                summary: "Shipped CSV export.\n".to_string(),
+            }],
+            commits: vec![Commit::new("Add billing API".to_string(), 1704067200)],
+            omitted_commits: 3,
+        };
+
+        let prompt = build_highlights_prompt(&request);
+
+        assert!(prompt.starts_with(
+            "Diary summaries:\n\n### 2024-05-06 – 2024-05-12\n\nShipped CSV export.\n\nCommit messages:\n\n2024-01-01 00:00:00: Add billing API"
+        ));
+        assert!(prompt
+            .ends_with("3 older commits were left out of this list to keep the prompt small."));
+        assert!(HIGHLIGHTS_SYSTEM_PROMPT.contains("5-10"));
+    }
+}
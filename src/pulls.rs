@@ -0,0 +1,925 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::auth::Forge;
+use crate::domain::{Author, Commit, GitRepository, PullRequestResolver, Review, ReviewSource};
+
+/// A repository on a forge, as named by its git remote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteRepository {
+    pub host: String,
+    /// `owner/name`, a nested group path on GitLab, or `scm/PROJECT/name` for
+    /// HTTPS remotes of Bitbucket Server
+    pub path: String,
+}
+
+impl RemoteRepository {
+    /// Parses `https://`, `ssh://` and scp-style (`git@host:owner/repo.git`) remote URLs
+    pub fn parse(url: &str) -> Option<Self> {
+        let (host, path) = match url.split_once("://") {
+            Some((_, rest)) => rest.split_once('/')?,
+            None => url.split_once(':')?,
+        };
+        let host = host.rsplit('@').next()?.split(':').next()?;
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+
+        if host.is_empty() || !path.contains('/') {
+            return None;
+        }
+
+        Some(Self {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Web page of commit `id` on `forge`, for linking to from a diary
+    pub fn commit_url(&self, forge: Forge, id: &str) -> String {
+        match forge {
+            Forge::GitHub => format!("https://{}/{}/commit/{}", self.host, self.path, id),
+            Forge::GitLab => format!("https://{}/{}/-/commit/{}", self.host, self.path, id),
+            Forge::Bitbucket if self.host == "bitbucket.org" => {
+                format!("https://bitbucket.org/{}/commits/{}", self.path, id)
+            }
+            Forge::Bitbucket => {
+                let (project, repo) = self.bitbucket_server_repo();
+                format!(
+                    "https://{}/projects/{}/repos/{}/commits/{}",
+                    self.host, project, repo, id
+                )
+            }
+        }
+    }
+
+    /// Project key and repository slug on Bitbucket Server, whose HTTPS
+    /// remotes put `scm/` in front of them
+    fn bitbucket_server_repo(&self) -> (&str, &str) {
+        let path = self.path.trim_start_matches("scm/");
+        path.split_once('/').unwrap_or(("", path))
+    }
+}
+
+#[derive(Deserialize)]
+struct PullRequest {
+    title: String,
+    /// GitHub's `body`, GitLab's `description`
+    #[serde(alias = "description")]
+    body: Option<String>,
+}
+
+/// Fetches pull request (GitLab: merge request) titles through the forge's REST API
+///
+/// Bitbucket Cloud and Server share a `title` and `description` but not their
+/// API paths; any host other than `bitbucket.org` is taken to be a Server.
+pub struct ForgePullRequestResolver {
+    client: reqwest::Client,
+    forge: Forge,
+    repository: RemoteRepository,
+    token: String,
+}
+
+impl ForgePullRequestResolver {
+    pub fn new(forge: Forge, repository: RemoteRepository, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            forge,
+            repository,
+            token,
+        }
+    }
+
+    fn pull_request_url(&self, number: u64) -> String {
+        let host = &self.repository.host;
+        match self.forge {
+            Forge::GitHub if host == "github.com" => format!(
+                "https://api.github.com/repos/{}/pulls/{}",
+                self.repository.path, number
+            ),
+            Forge::GitHub => format!(
+                "https://{}/api/v3/repos/{}/pulls/{}",
+                host, self.repository.path, number
+            ),
+            Forge::GitLab => format!(
+                "https://{}/api/v4/projects/{}/merge_requests/{}",
+                host,
+                self.repository.path.replace('/', "%2F"),
+                number
+            ),
+            Forge::Bitbucket if host == "bitbucket.org" => format!(
+                "https://api.bitbucket.org/2.0/repositories/{}/pullrequests/{}",
+                self.repository.path, number
+            ),
+            Forge::Bitbucket => {
+                let (project, repo) = self.repository.bitbucket_server_repo();
+                format!(
+                    "https://{}/rest/api/1.0/projects/{}/repos/{}/pull-requests/{}",
+                    host, project, repo, number
+                )
+            }
+        }
+    }
+
+    /// Fetches pull request `number`, or `None` if it doesn't exist
+    async fn fetch(&self, number: u64) -> Result<Option<PullRequest>> {
+        let response = self
+            .client
+            .get(self.pull_request_url(number))
+            .header("Accept", "application/json")
+            .header("User-Agent", "git-diary")
+            .bearer_auth(&self.token)
+            .send()
+            .await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let raw = response.error_for_status()?.text().await?;
+        let pull_request: PullRequest = serde_json::from_str(&raw)
+            .with_context(|| format!("Unexpected pull request response: {}", raw))?;
+
+        Ok(Some(pull_request))
+    }
+}
+
+#[async_trait]
+impl PullRequestResolver for ForgePullRequestResolver {
+    async fn pull_request_title(&self, number: u64) -> Result<Option<String>> {
+        Ok(self
+            .fetch(number)
+            .await?
+            .map(|pull_request| pull_request.title))
+    }
+
+    async fn pull_request_description(&self, number: u64) -> Result<Option<String>> {
+        Ok(self
+            .fetch(number)
+            .await?
+            .and_then(|pull_request| pull_request.body))
+    }
+}
+
+/// Commits per page requested from Bitbucket, the most either API returns
+const COMMITS_PER_PAGE: u32 = 100;
+
+/// A page of Bitbucket Cloud's commit listing
+#[derive(Deserialize)]
+struct CloudCommitPage {
+    values: Vec<CloudCommit>,
+    /// URL of the next page, missing on the last one
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CloudCommit {
+    hash: String,
+    /// RFC 3339 author date
+    date: String,
+    message: String,
+    author: Option<CloudAuthor>,
+}
+
+#[derive(Deserialize)]
+struct CloudAuthor {
+    /// `Name <email>` as recorded in the commit
+    raw: String,
+}
+
+/// A page of Bitbucket Server's commit listing
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerCommitPage {
+    values: Vec<ServerCommit>,
+    is_last_page: bool,
+    next_page_start: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerCommit {
+    id: String,
+    message: String,
+    author: Option<ServerAuthor>,
+    /// Milliseconds since the epoch
+    author_timestamp: i64,
+    committer_timestamp: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ServerAuthor {
+    name: String,
+    email_address: Option<String>,
+}
+
+/// Reads the commits of a Bitbucket Cloud or Server repository through its
+/// REST API, for repositories that are only reachable through Bitbucket
+///
+/// Both APIs list the default branch newest first, so paging stops at the
+/// first page reaching back before the period. Files and line stats aren't
+/// part of the listing and are left out.
+pub struct BitbucketCommitSource {
+    client: reqwest::Client,
+    token: String,
+    api_url: String,
+    /// Bitbucket Cloud rather than Server, whose APIs page differently
+    cloud: bool,
+}
+
+impl BitbucketCommitSource {
+    pub fn new(repository: RemoteRepository, token: String) -> Self {
+        let cloud = repository.host == "bitbucket.org";
+        let api_url = if cloud {
+            format!(
+                "https://api.bitbucket.org/2.0/repositories/{}",
+                repository.path
+            )
+        } else {
+            let (project, repo) = repository.bitbucket_server_repo();
+            format!(
+                "https://{}/rest/api/1.0/projects/{}/repos/{}",
+                repository.host, project, repo
+            )
+        };
+
+        Self {
+            client: reqwest::Client::new(),
+            token,
+            api_url,
+            cloud,
+        }
+    }
+
+    /// Sends requests to another repository API root, such as a mock server
+    pub fn with_api_url(mut self, api_url: String) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Commits made at or after `timestamp`, newest first
+    pub async fn commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
+        let mut commits = Vec::new();
+        if self.cloud {
+            let mut url = Some(format!(
+                "{}/commits?pagelen={}",
+                self.api_url, COMMITS_PER_PAGE
+            ));
+            while let Some(page_url) = url {
+                let page: CloudCommitPage = self.get(&page_url).await?;
+                let mut reached_start = false;
+                for commit in page.values {
+                    let time = DateTime::parse_from_rfc3339(&commit.date)
+                        .with_context(|| format!("Invalid date of commit {}", commit.hash))?
+                        .timestamp();
+                    if time < timestamp {
+                        reached_start = true;
+                        continue;
+                    }
+                    let author = commit
+                        .author
+                        .and_then(|author| Author::parse_ident(&author.raw));
+                    commits.push(bitbucket_commit(
+                        commit.hash,
+                        &commit.message,
+                        time,
+                        time,
+                        author,
+                    ));
+                }
+                url = page.next.filter(|_| !reached_start);
+            }
+        } else {
+            let mut start = Some(0);
+            while let Some(page_start) = start {
+                let page: ServerCommitPage = self
+                    .get(&format!(
+                        "{}/commits?start={}&limit={}",
+                        self.api_url, page_start, COMMITS_PER_PAGE
+                    ))
+                    .await?;
+                let mut reached_start = false;
+                for commit in page.values {
+                    let time = commit.committer_timestamp / 1000;
+                    if time < timestamp {
+                        reached_start = true;
+                        continue;
+                    }
+                    let author = commit.author.map(|author| {
+                        Author::new(author.name, author.email_address.unwrap_or_default())
+                    });
+                    commits.push(bitbucket_commit(
+                        commit.id,
+                        &commit.message,
+                        time,
+                        commit.author_timestamp / 1000,
+                        author,
+                    ));
+                }
+                start = page
+                    .next_page_start
+                    .filter(|_| !page.is_last_page && !reached_start);
+            }
+        }
+
+        Ok(commits)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let raw = self
+            .client
+            .get(url)
+            .header("Accept", "application/json")
+            .header("User-Agent", "git-diary")
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        serde_json::from_str(&raw).with_context(|| format!("Unexpected commit listing: {}", raw))
+    }
+}
+
+impl GitRepository for BitbucketCommitSource {
+    /// Blocks on the requests, so it needs a multi-threaded Tokio runtime
+    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .context("Reading commits from Bitbucket needs a Tokio runtime")?;
+        tokio::task::block_in_place(|| runtime.block_on(self.commits_since(timestamp)))
+    }
+}
+
+/// A listed commit, with the subject and body split as the local sources do
+fn bitbucket_commit(
+    id: String,
+    message: &str,
+    time: i64,
+    author_time: i64,
+    author: Option<Author>,
+) -> Commit {
+    let (subject, body) = message.split_once('\n').unwrap_or((message, ""));
+    let mut commit = Commit::new(format!("commit: {}", subject.trim_end()), time)
+        .with_id(id)
+        .with_author_time(author_time);
+    commit.author = author;
+    let body = body.trim();
+    if !body.is_empty() {
+        commit = commit.with_body(body.to_string());
+    }
+    commit
+}
+
+#[derive(Clone, Deserialize)]
+struct GitHubUser {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct SearchResults {
+    items: Vec<SearchItem>,
+}
+
+#[derive(Deserialize)]
+struct SearchItem {
+    number: u64,
+    title: String,
+}
+
+#[derive(Clone, Deserialize)]
+struct PullRequestReview {
+    user: Option<GitHubUser>,
+    state: String,
+    /// RFC 3339 timestamp; missing while a review is pending
+    submitted_at: Option<String>,
+}
+
+/// Finds the pull requests the authenticated user reviewed, through GitHub's
+/// search and pull request review APIs
+///
+/// Search narrows the candidates to pull requests updated in the period that
+/// the user reviewed at some point; each one's reviews then show whether a
+/// review was actually submitted in the period and how it ended. Listings are
+/// read page by page through their `Link` headers, and each pull request's
+/// reviews are fetched once, however many diaries, such as one per branch,
+/// ask about it.
+pub struct GitHubReviewSource {
+    client: reqwest::Client,
+    repository: RemoteRepository,
+    token: String,
+    api_url: String,
+    /// Reviews already fetched, by pull request number
+    reviews: Mutex<HashMap<u64, Vec<PullRequestReview>>>,
+}
+
+impl GitHubReviewSource {
+    pub fn new(repository: RemoteRepository, token: String) -> Self {
+        let api_url = match repository.host.as_str() {
+            "github.com" => "https://api.github.com".to_string(),
+            host => format!("https://{}/api/v3", host),
+        };
+
+        Self {
+            client: reqwest::Client::new(),
+            repository,
+            token,
+            api_url,
+            reviews: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sends requests to another API root, such as a mock server
+    pub fn with_api_url(mut self, api_url: String) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let (page, _) = self
+            .get_page(format!("{}{}", self.api_url, path), query)
+            .await?;
+        Ok(page)
+    }
+
+    /// Every page of the listing at `path`, following each `Link` header's
+    /// `next` URL, which carries the query along
+    async fn get_pages<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<Vec<T>> {
+        let mut pages = Vec::new();
+        let (page, mut next) = self
+            .get_page(format!("{}{}", self.api_url, path), query)
+            .await?;
+        pages.push(page);
+        while let Some(url) = next {
+            let (page, link) = self.get_page(url, &[]).await?;
+            pages.push(page);
+            next = link;
+        }
+
+        Ok(pages)
+    }
+
+    /// The response at `url` and the URL of the next page, if there is one
+    async fn get_page<T: DeserializeOwned>(
+        &self,
+        url: String,
+        query: &[(&str, String)],
+    ) -> Result<(T, Option<String>)> {
+        let response = self
+            .client
+            .get(&url)
+            .query(query)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "git-diary")
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+        let next = response
+            .headers()
+            .get(reqwest::header::LINK)
+            .and_then(|link| link.to_str().ok())
+            .and_then(next_link);
+        let raw = response.text().await?;
+
+        let page = serde_json::from_str(&raw)
+            .with_context(|| format!("Unexpected response from {}: {}", url, raw))?;
+        Ok((page, next))
+    }
+
+    /// The reviews of pull request `number`, fetched on first use
+    async fn pull_request_reviews(&self, number: u64) -> Result<Vec<PullRequestReview>> {
+        if let Some(reviews) = self.reviews.lock().unwrap().get(&number) {
+            return Ok(reviews.clone());
+        }
+
+        let pages: Vec<Vec<PullRequestReview>> = self
+            .get_pages(
+                &format!("/repos/{}/pulls/{}/reviews", self.repository.path, number),
+                &[("per_page", "100".to_string())],
+            )
+            .await?;
+        let reviews: Vec<PullRequestReview> = pages.into_iter().flatten().collect();
+        self.reviews.lock().unwrap().insert(number, reviews.clone());
+        Ok(reviews)
+    }
+}
+
+/// The `rel="next"` URL of a `Link` header, such as
+/// `<https://api.github.com/search/issues?page=2>; rel="next", <…>; rel="last"`
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params
+            .split(';')
+            .any(|param| param.trim() == "rel=\"next\"")
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+#[async_trait]
+impl ReviewSource for GitHubReviewSource {
+    async fn reviews_between(&self, since: i64, until: i64) -> Result<Vec<Review>> {
+        let user: GitHubUser = self.get("/user", &[]).await?;
+        let since_date = Utc
+            .timestamp_opt(since, 0)
+            .single()
+            .context("Invalid start of the review period")?
+            .format("%Y-%m-%d");
+        let results: Vec<SearchResults> = self
+            .get_pages(
+                "/search/issues",
+                &[
+                    (
+                        "q",
+                        format!(
+                            "repo:{} is:pr reviewed-by:{} updated:>={}",
+                            self.repository.path, user.login, since_date
+                        ),
+                    ),
+                    ("per_page", "100".to_string()),
+                ],
+            )
+            .await?;
+
+        let mut reviews = Vec::new();
+        for item in results.into_iter().flat_map(|page| page.items) {
+            let submitted = self.pull_request_reviews(item.number).await?;
+            let mut mine: Vec<(i64, String)> = submitted
+                .into_iter()
+                .filter(|review| review.user.as_ref().is_some_and(|u| u.login == user.login))
+                .filter_map(|review| {
+                    let submitted_at = DateTime::parse_from_rfc3339(&review.submitted_at?).ok()?;
+                    Some((submitted_at.timestamp(), review.state))
+                })
+                .filter(|(time, _)| since <= *time && *time < until)
+                .collect();
+            mine.sort_by_key(|(time, _)| *time);
+
+            if let Some((_, state)) = mine.last() {
+                reviews.push(
+                    Review::new(item.number, item.title, review_state(state))
+                        .with_submissions(mine.len()),
+                );
+            }
+        }
+        reviews.sort_by_key(|review| review.number);
+
+        Ok(reviews)
+    }
+}
+
+/// `CHANGES_REQUESTED` becomes `changes requested`
+fn review_state(state: &str) -> String {
+    state.to_lowercase().replace('_', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repository(host: &str, path: &str) -> Option<RemoteRepository> {
+        Some(RemoteRepository {
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_parse_remote_repository() {
+        assert_eq!(
+            RemoteRepository::parse("https://github.com/jane/git-diary.git"),
+            repository("github.com", "jane/git-diary")
+        );
+        assert_eq!(
+            RemoteRepository::parse("git@github.com:jane/git-diary.git"),
+            repository("github.com", "jane/git-diary")
+        );
+        assert_eq!(
+            RemoteRepository::parse("ssh://git@gitlab.example.com:2222/team/tools/diary"),
+            repository("gitlab.example.com", "team/tools/diary")
+        );
+        assert_eq!(
+            RemoteRepository::parse("https://jane@git.example.com/scm/TOOLS/diary.git"),
+            repository("git.example.com", "scm/TOOLS/diary")
+        );
+        assert_eq!(RemoteRepository::parse("/srv/git/diary.git"), None);
+    }
+
+    #[test]
+    fn test_commit_url() {
+        let url = |forge, host, path| repository(host, path).unwrap().commit_url(forge, "3b18e51");
+
+        assert_eq!(
+            url(Forge::GitHub, "github.com", "team/diary"),
+            "https://github.com/team/diary/commit/3b18e51"
+        );
+        assert_eq!(
+            url(Forge::GitLab, "gitlab.example.com", "team/tools/diary"),
+            "https://gitlab.example.com/team/tools/diary/-/commit/3b18e51"
+        );
+        assert_eq!(
+            url(Forge::Bitbucket, "bitbucket.org", "team/diary"),
+            "https://bitbucket.org/team/diary/commits/3b18e51"
+        );
+        assert_eq!(
+            url(Forge::Bitbucket, "git.example.com", "scm/TOOLS/diary"),
+            "https://git.example.com/projects/TOOLS/repos/diary/commits/3b18e51"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitbucket_cloud_commits() -> Result<()> {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/commits"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "values": [
+                    {"hash": "b2", "date": "2024-01-02T10:00:00+00:00", "message": "fix: Retry payments\n"},
+                    {"hash": "a1", "date": "2023-12-30T10:00:00+00:00", "message": "Older"},
+                ],
+                "next": format!("{}/commits?page=3", server.uri()),
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/commits"))
+            .and(query_param("pagelen", "100"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "values": [{
+                    "hash": "c3",
+                    "date": "2024-01-03T10:00:00+00:00",
+                    "message": "feat: Add export\n\nAs CSV",
+                    "author": {"raw": "Jane Doe <jane@example.com>"},
+                }],
+                "next": format!("{}/commits?page=2", server.uri()),
+            })))
+            .mount(&server)
+            .await;
+
+        let source = BitbucketCommitSource::new(
+            repository("bitbucket.org", "team/diary").unwrap(),
+            "token".to_string(),
+        )
+        .with_api_url(server.uri());
+
+        // 2024-01-01 UTC; the third page is never asked for
+        let commits = source.commits_since(1704067200).await?;
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "commit: feat: Add export");
+        assert_eq!(commits[0].body.as_deref(), Some("As CSV"));
+        assert_eq!(commits[0].id.as_deref(), Some("c3"));
+        assert_eq!(
+            commits[0].author,
+            Some(Author::new(
+                "Jane Doe".to_string(),
+                "jane@example.com".to_string()
+            ))
+        );
+        assert_eq!(commits[1].message, "commit: fix: Retry payments");
+        assert_eq!(commits[1].body, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bitbucket_server_commits() -> Result<()> {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/commits"))
+            .and(query_param("start", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "values": [{
+                    "id": "c3",
+                    "message": "feat: Add export",
+                    "author": {"name": "jane", "emailAddress": "jane@example.com"},
+                    "authorTimestamp": 1704200000000_i64,
+                    "committerTimestamp": 1704270000000_i64,
+                }],
+                "isLastPage": false,
+                "nextPageStart": 1,
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/commits"))
+            .and(query_param("start", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "values": [{
+                    "id": "b2",
+                    "message": "fix: Retry payments",
+                    "authorTimestamp": 1704190000000_i64,
+                    "committerTimestamp": 1704190000000_i64,
+                }],
+                "isLastPage": true,
+            })))
+            .mount(&server)
+            .await;
+
+        let source = BitbucketCommitSource::new(
+            repository("git.example.com", "scm/TOOLS/diary").unwrap(),
+            "token".to_string(),
+        )
+        .with_api_url(server.uri());
+
+        let commits = source.commits_since(1704067200).await?;
+
+        let listed: Vec<(&str, i64, Option<i64>)> = commits
+            .iter()
+            .map(|commit| {
+                (
+                    commit.message.as_str(),
+                    commit.timestamp(),
+                    commit.author_time,
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("commit: feat: Add export", 1704270000, Some(1704200000)),
+                ("commit: fix: Retry payments", 1704190000, Some(1704190000)),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_link() {
+        assert_eq!(
+            next_link(
+                r#"<https://api.github.com/x?page=1>; rel="prev", <https://api.github.com/x?page=3>; rel="next""#
+            ),
+            Some("https://api.github.com/x?page=3".to_string())
+        );
+        assert_eq!(
+            next_link(r#"<https://api.github.com/x?page=1>; rel="first""#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pull_request_url() {
+        let resolver = |forge, host| {
+            ForgePullRequestResolver::new(
+                forge,
+                repository(host, "team/tools/diary").unwrap(),
+                "token".to_string(),
+            )
+        };
+
+        assert_eq!(
+            resolver(Forge::GitHub, "github.com").pull_request_url(12),
+            "https://api.github.com/repos/team/tools/diary/pulls/12"
+        );
+        assert_eq!(
+            resolver(Forge::GitHub, "github.example.com").pull_request_url(12),
+            "https://github.example.com/api/v3/repos/team/tools/diary/pulls/12"
+        );
+        assert_eq!(
+            resolver(Forge::GitLab, "gitlab.com").pull_request_url(12),
+            "https://gitlab.com/api/v4/projects/team%2Ftools%2Fdiary/merge_requests/12"
+        );
+
+        let bitbucket = |host, path| {
+            ForgePullRequestResolver::new(
+                Forge::Bitbucket,
+                repository(host, path).unwrap(),
+                "token".to_string(),
+            )
+        };
+        assert_eq!(
+            bitbucket("bitbucket.org", "team/diary").pull_request_url(12),
+            "https://api.bitbucket.org/2.0/repositories/team/diary/pullrequests/12"
+        );
+        assert_eq!(
+            bitbucket("git.example.com", "scm/TOOLS/diary").pull_request_url(12),
+            "https://git.example.com/rest/api/1.0/projects/TOOLS/repos/diary/pull-requests/12"
+        );
+    }
+
+    #[test]
+    fn test_parse_pull_request_description() -> Result<()> {
+        let github: PullRequest =
+            serde_json::from_str(r#"{"title": "Add export", "body": "Adds CSV"}"#)?;
+        assert_eq!(github.body.as_deref(), Some("Adds CSV"));
+
+        let gitlab: PullRequest =
+            serde_json::from_str(r#"{"title": "Add export", "description": null}"#)?;
+        assert_eq!(gitlab.body, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_github_review_source() -> Result<()> {
+        use wiremock::matchers::{method, path, query_param};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/user"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"login": "jane"})),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/search/issues"))
+            .and(query_param(
+                "q",
+                "repo:team/diary is:pr reviewed-by:jane updated:>=2024-01-01",
+            ))
+            .and(query_param("per_page", "100"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header(
+                        "Link",
+                        format!(
+                            "<{}/search/issues?page=2>; rel=\"next\", <{}/search/issues?page=2>; rel=\"last\"",
+                            server.uri(),
+                            server.uri()
+                        ),
+                    )
+                    .set_body_json(serde_json::json!({
+                        "items": [{"number": 14, "title": "Retry failed payments"}]
+                    })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/search/issues"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "items": [{"number": 9, "title": "Reviewed last month"}]
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/team/diary/pulls/14/reviews"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"user": {"login": "jane"}, "state": "CHANGES_REQUESTED", "submitted_at": "2024-01-02T10:00:00Z"},
+                {"user": {"login": "bob"}, "state": "COMMENTED", "submitted_at": "2024-01-03T10:00:00Z"},
+                {"user": {"login": "jane"}, "state": "APPROVED", "submitted_at": "2024-01-04T10:00:00Z"},
+            ])))
+            // Once, though two diaries ask
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/team/diary/pulls/9/reviews"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"user": {"login": "jane"}, "state": "APPROVED", "submitted_at": "2023-12-01T10:00:00Z"},
+            ])))
+            .mount(&server)
+            .await;
+
+        let source = GitHubReviewSource::new(
+            repository("github.com", "team/diary").unwrap(),
+            "token".to_string(),
+        )
+        .with_api_url(server.uri());
+
+        // 2024-01-01 to 2024-01-08 UTC
+        let reviews = source.reviews_between(1704067200, 1704672000).await?;
+        assert_eq!(
+            source.reviews_between(1704067200, 1704672000).await?,
+            reviews
+        );
+
+        assert_eq!(
+            reviews,
+            vec![Review::new(
+                14,
+                "Retry failed payments".to_string(),
+                "approved".to_string()
+            )
+            .with_submissions(2)]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A diary run put off because the AI provider couldn't be reached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedRun {
+    /// Directory the run was started in
+    pub repo: PathBuf,
+    /// Command-line arguments of the run, replayed as given
+    pub args: Vec<String>,
+    /// Start of the period as a Unix timestamp, fixed when queued so a late
+    /// run covers the same commits
+    pub start: i64,
+    /// End of the period as a Unix timestamp
+    pub end: i64,
+    pub queued_at: i64,
+}
+
+/// Runs waiting for the machine to come back online, oldest first
+///
+/// Stored as one JSON file that is replaced atomically on every change, like
+/// the run state, so a crash never loses queued runs. Unlike the run state, a
+/// file that fails to parse is an error rather than a fresh start.
+///
+/// Changes take an exclusive lock on a `.lock` file next to the queue, as the
+/// file itself is replaced, so runs queued at the same time are all kept. The
+/// worker holds a lock of its own for as long as it runs, which the system
+/// releases if it crashes.
+pub struct RunQueue {
+    path: PathBuf,
+}
+
+impl RunQueue {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The queued runs, oldest first
+    pub fn pending(&self) -> Result<Vec<QueuedRun>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let raw = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Failed to parse the run queue {}", self.path.display()))
+    }
+
+    /// Adds `run` behind the others, returning how many runs are now waiting
+    pub fn push(&self, run: QueuedRun) -> Result<usize> {
+        let _lock = self.lock()?;
+        let mut runs = self.pending()?;
+        runs.push(run);
+        self.save(&runs)?;
+        Ok(runs.len())
+    }
+
+    /// Takes `run` off the queue, once it was generated or given up on
+    pub fn remove(&self, run: &QueuedRun) -> Result<()> {
+        let _lock = self.lock()?;
+        let mut runs = self.pending()?;
+        runs.retain(|queued| queued != run);
+        self.save(&runs)
+    }
+
+    /// Becomes the queue's worker, holding the worker lock until the returned
+    /// file is dropped; none when another worker is alive
+    pub fn claim_worker(&self) -> Result<Option<File>> {
+        let file = self.open_lock_file("worker.lock")?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(file)),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e).context("Failed to lock the queue worker"),
+        }
+    }
+
+    /// Whether a worker is working through the queue
+    pub fn has_worker(&self) -> Result<bool> {
+        Ok(self.claim_worker()?.is_none())
+    }
+
+    /// Takes the exclusive lock for changing the queue, held until dropped
+    fn lock(&self) -> Result<File> {
+        let file = self.open_lock_file("lock")?;
+        file.lock().context("Failed to lock the run queue")?;
+        Ok(file)
+    }
+
+    fn open_lock_file(&self, extension: &str) -> Result<File> {
+        self.create_dir()?;
+        let path = self.path.with_extension(format!("json.{}", extension));
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))
+    }
+
+    fn create_dir(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Failed to create the run queue directory")?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, runs: &[QueuedRun]) -> Result<()> {
+        self.create_dir()?;
+
+        let temp_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path).context("Failed to create the run queue")?;
+        file.write_all(serde_json::to_string_pretty(runs)?.as_bytes())
+            .context("Failed to write the run queue")?;
+        file.sync_all().context("Failed to flush the run queue")?;
+
+        fs::rename(&temp_path, &self.path).context("Failed to replace the run queue")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn queued_run(repo: &str, queued_at: i64) -> QueuedRun {
+        QueuedRun {
+            repo: PathBuf::from(repo),
+            args: vec!["--period".to_string(), "today".to_string()],
+            start: 1704067200,
+            end: 1704153600,
+            queued_at,
+        }
+    }
+
+    #[test]
+    fn test_run_queue() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let queue = RunQueue::new(temp_dir.path().join("state/queue.json"));
+        assert!(queue.pending()?.is_empty());
+
+        assert_eq!(queue.push(queued_run("/src/app", 1704070000))?, 1);
+        assert_eq!(queue.push(queued_run("/src/lib", 1704080000))?, 2);
+
+        // A fresh handle sees the queued runs in order
+        let queue = RunQueue::new(temp_dir.path().join("state/queue.json"));
+        let pending = queue.pending()?;
+        assert_eq!(
+            pending
+                .iter()
+                .map(|run| run.repo.to_string_lossy())
+                .collect::<Vec<_>>(),
+            vec!["/src/app", "/src/lib"]
+        );
+
+        queue.remove(&pending[0])?;
+        assert_eq!(queue.pending()?, vec![queued_run("/src/lib", 1704080000)]);
+
+        fs::write(queue.path(), "[{\"repo\": ")?;
+        assert!(queue.pending().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_queue_concurrent_pushes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("queue.json");
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || RunQueue::new(path).push(queued_run("/src/app", i)))
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+
+        assert_eq!(RunQueue::new(path).pending()?.len(), 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_queue_worker_lock() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let queue = RunQueue::new(temp_dir.path().join("state/queue.json"));
+        assert!(!queue.has_worker()?);
+
+        let worker = queue.claim_worker()?;
+        assert!(worker.is_some());
+        assert!(queue.has_worker()?);
+        assert!(queue.claim_worker()?.is_none());
+
+        // A worker that exits, or crashes, frees the queue for the next one
+        drop(worker);
+        assert!(!queue.has_worker()?);
+
+        Ok(())
+    }
+}
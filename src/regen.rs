@@ -0,0 +1,295 @@
+use anyhow::{bail, Result};
+use chrono::{NaiveDateTime, TimeZone};
+use serde_json::Value;
+use std::ops::Range;
+
+use crate::domain::{resolve_local, Author, Commit, Note, SummaryRequest};
+use crate::formatter::{
+    COMMIT_LOGS_HEADING, MANUAL_NOTES_HEADING, OTHER_ACTIVITIES_HEADING, SUMMARY_HEADING,
+    WORK_HOURS_HEADING,
+};
+use crate::sidecar::{commit_tags, ActivityStats};
+
+/// Layout of the timestamps diaries list commits and notes with
+const LOG_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Part of a stored diary `git-diary regen` recomputes, leaving the rest as
+/// it is, manual edits included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum RegenSection {
+    /// The AI-generated summary, asked for again from the diary's commits
+    Summary,
+    /// The working hours section and the sidecar's `stats`
+    Stats,
+    /// The sidecar's conventional-commit `tags`
+    Tags,
+}
+
+/// Byte range of the body of the `## heading` section: everything after the
+/// heading line up to the next `## ` heading or the end of the diary
+pub fn section_span(markdown: &str, heading: &str) -> Option<Range<usize>> {
+    let mut offset = 0;
+    let mut start = None;
+    for line in markdown.split_inclusive('\n') {
+        if let Some(title) = line.strip_prefix("## ") {
+            if start.is_some() {
+                return start.map(|start| start..offset);
+            }
+            if title.trim() == heading {
+                start = Some(offset + line.len());
+            }
+        }
+        offset += line.len();
+    }
+    start.map(|start| start..markdown.len())
+}
+
+/// Replaces the body of the `## heading` section with `body`, keeping every
+/// other byte of the diary
+pub fn replace_section(markdown: &str, heading: &str, body: &str) -> Result<String> {
+    let Some(span) = section_span(markdown, heading) else {
+        bail!("The diary has no \"## {}\" section", heading);
+    };
+    // Keep the blank lines that separated the old body from the next section
+    let old = &markdown[span.clone()];
+    let trailing = &old[old.trim_end().len()..];
+    let trailing = if span.end == markdown.len() {
+        "\n"
+    } else if trailing.matches('\n').count() >= 2 {
+        trailing
+    } else {
+        "\n\n"
+    };
+    Ok(format!(
+        "{}\n{}{}{}",
+        &markdown[..span.start],
+        body.trim(),
+        trailing,
+        &markdown[span.end..]
+    ))
+}
+
+/// The text of the `## heading` section, without surrounding blank lines
+pub fn section_body<'a>(markdown: &'a str, heading: &str) -> Option<&'a str> {
+    section_span(markdown, heading).map(|span| markdown[span].trim())
+}
+
+/// Splits a `2024-01-01 09:30:00: text` line into its time and text
+fn timestamped(line: &str) -> Option<(NaiveDateTime, &str)> {
+    let line = line.trim().strip_prefix("- ")?;
+    let (time, text) = (line.get(..19)?, line.get(19..)?);
+    let time = NaiveDateTime::parse_from_str(time, LOG_TIME_FORMAT).ok()?;
+    Some((time, text.strip_prefix(": ")?))
+}
+
+/// Reads the commits back from a diary's "Commit Logs" section, whose times
+/// are in `tz`; the time zone of each author is lost
+pub fn commits_from_log<Tz: TimeZone>(markdown: &str, tz: &Tz) -> Vec<Commit> {
+    let Some(log) = section_body(markdown, COMMIT_LOGS_HEADING) else {
+        return Vec::new();
+    };
+    log.lines()
+        .filter_map(timestamped)
+        .map(|(time, text)| {
+            let timestamp = resolve_local(tz, time).timestamp();
+            let scoped = text
+                .strip_prefix('[')
+                .and_then(|rest| rest.split_once("] "));
+            match scoped {
+                Some((scope, message)) => {
+                    let mut commit = Commit::new(message.to_string(), timestamp);
+                    commit.scope = Some(scope.to_string());
+                    commit
+                }
+                None => Commit::new(text.to_string(), timestamp),
+            }
+        })
+        .collect()
+}
+
+/// Reads the commits back from a `--sidecar-json` file, which keeps their
+/// authors, time zones and files
+pub fn commits_from_sidecar(sidecar: &Value) -> Vec<Commit> {
+    let Some(commits) = sidecar["commits"].as_array() else {
+        return Vec::new();
+    };
+    commits
+        .iter()
+        .filter_map(|commit| {
+            let mut parsed = Commit::new(
+                commit["message"].as_str()?.to_string(),
+                commit["time"].as_i64()?,
+            );
+            if let (Some(name), Some(email)) = (
+                commit["author"]["name"].as_str(),
+                commit["author"]["email"].as_str(),
+            ) {
+                parsed = parsed.with_author(Author::new(name.to_string(), email.to_string()));
+            }
+            if let Some(author_time) = commit["author_time"].as_i64() {
+                parsed = parsed.with_author_time(author_time);
+            }
+            if let Some(offset) = commit["author_offset"].as_i64() {
+                parsed = parsed.with_author_offset(offset as i32);
+            }
+            if let Some(files) = commit["files"].as_array() {
+                parsed = parsed.with_files(
+                    files
+                        .iter()
+                        .filter_map(|file| file.as_str().map(str::to_string))
+                        .collect(),
+                );
+            }
+            if let Some(body) = commit["body"].as_str() {
+                parsed = parsed.with_body(body.to_string());
+            }
+            parsed.scope = commit["scope"].as_str().map(str::to_string);
+            Some(parsed)
+        })
+        .collect()
+}
+
+/// The summary request for `commits`, with the diary's manual notes and
+/// other activities as context, as edited since
+pub fn summary_request(markdown: &str, commits: &[Commit], max_commits: usize) -> SummaryRequest {
+    let mut request = SummaryRequest::capped(commits, max_commits);
+    request.notes = section_body(markdown, MANUAL_NOTES_HEADING)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(timestamped)
+        // Notes are listed in UTC
+        .map(|(time, text)| Note::new(text.to_string(), time.and_utc().timestamp()))
+        .collect();
+    request.context = section_body(markdown, OTHER_ACTIVITIES_HEADING)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("- "))
+        .map(str::to_string)
+        .collect();
+    request
+}
+
+/// Writes the new `summary`, or the `stats` and `tags` of `commits`, into a sidecar
+pub fn update_sidecar(
+    sidecar: &mut Value,
+    section: RegenSection,
+    commits: &[Commit],
+    summary: Option<&str>,
+) -> Result<()> {
+    match section {
+        RegenSection::Summary => {
+            if let Some(summary) = summary {
+                sidecar["summary"] = summary.into();
+            }
+        }
+        RegenSection::Stats => {
+            let notes = sidecar["notes"].as_array().map_or(0, Vec::len);
+            sidecar["stats"] = serde_json::to_value(ActivityStats::from_commits(commits, notes))?;
+        }
+        RegenSection::Tags => sidecar["tags"] = commit_tags(commits).into(),
+    }
+    Ok(())
+}
+
+/// Heading of the section `section` recomputes in the Markdown diary, if any
+pub fn markdown_heading(section: RegenSection) -> Option<&'static str> {
+    match section {
+        RegenSection::Summary => Some(SUMMARY_HEADING),
+        RegenSection::Stats => Some(WORK_HOURS_HEADING),
+        RegenSection::Tags => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+
+    const DIARY: &str = "# Git Diary (2024-01-01 – 2024-01-01)
+
+## Commit Logs
+
+- 2024-01-01 09:00:00: feat: Add export
+- 2024-01-01 23:30:00: [parser] Fix dates
+
+
+## Manual notes
+
+- 2024-01-01 12:00:00: Paired with Alice (edited by hand)
+
+
+## Other activities
+
+- Design review
+
+
+## AI-generated Summary
+
+Old summary.
+
+## Summary (ja)
+
+古い要約。
+";
+
+    #[test]
+    fn test_replace_section() -> Result<()> {
+        let replaced = replace_section(DIARY, SUMMARY_HEADING, "New summary.\n")?;
+        assert_eq!(
+            replaced,
+            DIARY.replace("Old summary.\n\n## Summary", "New summary.\n\n## Summary")
+        );
+
+        let last = replace_section(DIARY, "Summary (ja)", "新しい要約。")?;
+        assert!(last.ends_with("## Summary (ja)\n\n新しい要約。\n"));
+        assert!(last.contains("(edited by hand)"));
+
+        assert!(replace_section(DIARY, "Working hours", "x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_summary_request_from_diary() {
+        let commits = commits_from_log(DIARY, &Utc);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "feat: Add export");
+        assert_eq!(commits[1].scope.as_deref(), Some("parser"));
+        assert_eq!(commits[1].timestamp(), 1704151800);
+
+        let request = summary_request(DIARY, &commits, 100);
+        // Newest first
+        assert_eq!(request.commits[0].message, "Fix dates");
+        assert_eq!(request.notes[0].text, "Paired with Alice (edited by hand)");
+        assert_eq!(request.context, vec!["Design review"]);
+    }
+
+    #[test]
+    fn test_update_sidecar() -> Result<()> {
+        let mut sidecar = json!({
+            "summary": "Old summary.",
+            "commits": [
+                {"message": "fix: Dates", "time": 1704151800, "author_offset": 0, "files": ["a.rs"],
+                 "author": {"name": "Jane", "email": "jane@example.com"}},
+                {"message": "feat: Export", "time": 1704099600, "author_offset": 0}
+            ],
+            "notes": [{"text": "x", "time": 0}],
+            "stats": {},
+            "tags": []
+        });
+        let commits = commits_from_sidecar(&sidecar);
+        assert_eq!(commits[0].files, vec!["a.rs"]);
+
+        update_sidecar(&mut sidecar, RegenSection::Stats, &commits, None)?;
+        update_sidecar(&mut sidecar, RegenSection::Tags, &commits, None)?;
+        update_sidecar(&mut sidecar, RegenSection::Summary, &commits, Some("New."))?;
+
+        assert_eq!(sidecar["stats"]["commits"], 2);
+        assert_eq!(sidecar["stats"]["notes"], 1);
+        assert_eq!(sidecar["stats"]["late_night_commits"], 1);
+        assert_eq!(sidecar["tags"], json!(["feat", "fix"]));
+        assert_eq!(sidecar["summary"], "New.");
+        Ok(())
+    }
+}
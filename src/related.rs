@@ -0,0 +1,274 @@
+use std::fmt::{self, Display};
+
+use crate::domain::Commit;
+
+/// Prefixes git gives commits meant to be folded into an earlier one
+const FIXUP_PREFIXES: &[&str] = &["fixup! ", "squash! ", "amend! "];
+
+/// Fewest commits that must change the same files to count as one change
+const MIN_REPEATED_COMMITS: usize = 3;
+
+/// Files most commits touch along the way, which say nothing about the change
+const INCIDENTAL_FILES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "CHANGELOG.md",
+];
+
+/// Most shared files named for a group
+const MAX_SHARED_FILES: usize = 3;
+
+/// Commits that look like one logical change, such as a feature and its fixups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedCommits {
+    /// Subjects, oldest first
+    pub subjects: Vec<String>,
+    /// Whether `fixup!` or `squash!` commits tie the group together
+    pub fixups: bool,
+    /// Files several of the commits changed
+    pub shared_files: Vec<String>,
+}
+
+impl Display for RelatedCommits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let subjects: Vec<String> = self
+            .subjects
+            .iter()
+            .map(|subject| format!("\"{}\"", subject))
+            .collect();
+        let mut reasons = Vec::new();
+        if self.fixups {
+            reasons.push("fixups".to_string());
+        }
+        if !self.shared_files.is_empty() {
+            reasons.push(format!(
+                "repeated changes to {}",
+                self.shared_files.join(", ")
+            ));
+        }
+        write!(f, "{} ({})", subjects.join("; "), reasons.join("; "))
+    }
+}
+
+/// The subject a `fixup!`, `squash!` or `amend!` commit targets, through
+/// nested prefixes such as `fixup! fixup! Add export`
+pub fn fixup_target(subject: &str) -> Option<&str> {
+    let mut target = subject.lines().next()?;
+    let mut found = false;
+    while let Some(rest) = FIXUP_PREFIXES
+        .iter()
+        .find_map(|prefix| target.strip_prefix(prefix))
+    {
+        target = rest.trim_start();
+        found = true;
+    }
+    found.then_some(target)
+}
+
+/// Groups commits that form one logical change, in the order of their newest
+/// commit, so summaries don't count the same feature several times
+///
+/// A `fixup!` or `squash!` commit joins the commit in `commits` it targets.
+/// Commits also join when each has at least half of its files in common with
+/// the other, once lock files and changelogs are set aside; such a chain
+/// needs [`MIN_REPEATED_COMMITS`] commits to count, unless a fixup is part of it.
+pub fn related_commits(commits: &[Commit]) -> Vec<RelatedCommits> {
+    let mut groups = Groups::new(commits.len());
+
+    for (index, commit) in commits.iter().enumerate() {
+        let Some(target) = fixup_target(commit.subject()) else {
+            continue;
+        };
+        // Commits come newest first, so the target is further down
+        if let Some(found) = commits[index + 1..]
+            .iter()
+            .position(|other| first_line(other.subject()) == target)
+        {
+            groups.join(index, index + 1 + found);
+            groups.fixups[index] = true;
+        }
+    }
+
+    let files: Vec<Vec<&str>> = commits.iter().map(relevant_files).collect();
+    for (index, own) in files.iter().enumerate() {
+        for (other, theirs) in files.iter().enumerate().skip(index + 1) {
+            let shared = own.iter().filter(|file| theirs.contains(file)).count();
+            if shared > 0 && shared * 2 >= own.len() && shared * 2 >= theirs.len() {
+                groups.join(index, other);
+                groups.shared[index] = true;
+                groups.shared[other] = true;
+            }
+        }
+    }
+
+    let mut related = Vec::new();
+    for members in groups.members() {
+        let fixups = members.iter().any(|&member| groups.fixups[member]);
+        if members.len() < 2 || (!fixups && members.len() < MIN_REPEATED_COMMITS) {
+            continue;
+        }
+
+        let mut shared_files: Vec<String> = Vec::new();
+        if members.iter().any(|&member| groups.shared[member]) {
+            for &member in &members {
+                for file in &files[member] {
+                    let touches = members
+                        .iter()
+                        .filter(|&&other| files[other].contains(file))
+                        .count();
+                    if touches > 1 && !shared_files.iter().any(|shared| shared == file) {
+                        shared_files.push(file.to_string());
+                    }
+                }
+            }
+            shared_files.truncate(MAX_SHARED_FILES);
+        }
+
+        related.push(RelatedCommits {
+            subjects: members
+                .iter()
+                .rev()
+                .map(|&member| first_line(commits[member].subject()).to_string())
+                .collect(),
+            fixups,
+            shared_files,
+        });
+    }
+    related
+}
+
+fn first_line(subject: &str) -> &str {
+    subject.lines().next().unwrap_or_default().trim_end()
+}
+
+fn relevant_files(commit: &Commit) -> Vec<&str> {
+    commit
+        .files
+        .iter()
+        .map(String::as_str)
+        .filter(|file| {
+            let name = file.rsplit('/').next().unwrap_or(file);
+            !INCIDENTAL_FILES.contains(&name)
+        })
+        .collect()
+}
+
+/// Union-find over commit indices
+struct Groups {
+    parents: Vec<usize>,
+    /// Commits joined as the fixup of another
+    fixups: Vec<bool>,
+    /// Commits joined by the files they share
+    shared: Vec<bool>,
+}
+
+impl Groups {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+            fixups: vec![false; len],
+            shared: vec![false; len],
+        }
+    }
+
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        // The newest commit stays the root, keeping groups in commit order
+        self.parents[a.max(b)] = a.min(b);
+    }
+
+    /// Indices of each group, in commit order
+    fn members(&mut self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+        for index in 0..self.parents.len() {
+            let root = self.root(index);
+            match groups.iter_mut().find(|(group, _)| *group == root) {
+                Some((_, members)) => members.push(index),
+                None => groups.push((root, vec![index])),
+            }
+        }
+        groups.into_iter().map(|(_, members)| members).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(message: &str, files: &[&str]) -> Commit {
+        Commit::new(message.to_string(), 1704067200)
+            .with_files(files.iter().map(|file| file.to_string()).collect())
+    }
+
+    #[test]
+    fn test_fixup_target() {
+        assert_eq!(fixup_target("fixup! Add export"), Some("Add export"));
+        assert_eq!(
+            fixup_target("squash! fixup! Add export"),
+            Some("Add export")
+        );
+        assert_eq!(fixup_target("Add export"), None);
+    }
+
+    #[test]
+    fn test_related_commits() {
+        // Newest first, as the reflog lists them
+        let commits = vec![
+            commit(
+                "commit: Escape quotes in exporter",
+                &["src/export.rs", "Cargo.lock"],
+            ),
+            commit("commit: fixup! Add login page", &["src/login.rs"]),
+            commit(
+                "commit: Export headers",
+                &["src/export.rs", "tests/export.rs"],
+            ),
+            commit("commit: Bump deps", &["Cargo.lock", "Cargo.toml"]),
+            commit("commit: Add login page", &["src/login.rs", "src/app.rs"]),
+            commit("commit: Add exporter", &["src/export.rs"]),
+            commit("commit: Tweak README", &["README.md"]),
+            commit("commit: Fix README typo", &["README.md"]),
+        ];
+
+        assert_eq!(
+            related_commits(&commits),
+            vec![
+                RelatedCommits {
+                    subjects: vec![
+                        "Add exporter".to_string(),
+                        "Export headers".to_string(),
+                        "Escape quotes in exporter".to_string(),
+                    ],
+                    fixups: false,
+                    shared_files: vec!["src/export.rs".to_string()],
+                },
+                RelatedCommits {
+                    subjects: vec![
+                        "Add login page".to_string(),
+                        "fixup! Add login page".to_string(),
+                    ],
+                    fixups: true,
+                    shared_files: vec!["src/login.rs".to_string()],
+                },
+            ]
+        );
+        assert_eq!(
+            related_commits(&commits)[1].to_string(),
+            "\"Add login page\"; \"fixup! Add login page\" (fixups; repeated changes to src/login.rs)"
+        );
+
+        // A fixup of work before the period has nothing to join
+        assert!(related_commits(&[commit("fixup! Add login page", &[])]).is_empty());
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::domain::Commit;
+
+/// A `rewrite` entry from the config file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RewriteRuleConfig {
+    /// Regular expression to search for
+    #[serde(rename = "match")]
+    pub pattern: String,
+    /// Replacement text; `$1` or `${name}` refer to capture groups
+    pub replace: String,
+}
+
+struct RewriteRule {
+    pattern: Regex,
+    replace: String,
+}
+
+/// Ordered rewrite rules applied to commit messages before prompting and rendering
+///
+/// Each rule sees the output of the previous one, so later rules can build on
+/// earlier replacements.
+#[derive(Default)]
+pub struct RewritePipeline {
+    rules: Vec<RewriteRule>,
+}
+
+impl RewritePipeline {
+    pub fn new(rules: &[RewriteRuleConfig]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = Regex::new(&rule.pattern)
+                    .with_context(|| format!("Invalid rewrite pattern {:?}", rule.pattern))?;
+                Ok(RewriteRule {
+                    pattern,
+                    replace: rule.replace.clone(),
+                })
+            })
+            .collect::<Result<Vec<RewriteRule>>>()?;
+
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Runs every rule over `text`, in order
+    pub fn apply(&self, text: &str) -> String {
+        self.rules.iter().fold(text.to_string(), |text, rule| {
+            rule.pattern
+                .replace_all(&text, rule.replace.as_str())
+                .into_owned()
+        })
+    }
+
+    /// Rewrites the messages of `commits`, keeping everything else
+    pub fn apply_to_commits(&self, commits: &[Commit]) -> Vec<Commit> {
+        commits
+            .iter()
+            .map(|commit| {
+                let mut commit = commit.clone();
+                commit.message = self.apply(&commit.message);
+                commit
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, replace: &str) -> RewriteRuleConfig {
+        RewriteRuleConfig {
+            pattern: pattern.to_string(),
+            replace: replace.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_pipeline() -> Result<()> {
+        let pipeline = RewritePipeline::new(&[
+            rule(r"JIRA-(\d+)", "ticket $1"),
+            rule(r"ticket (?<id>\d+)", "ticket #${id}"),
+            rule(r"(?i)\bprj-x\b", "the billing service"),
+        ])?;
+
+        assert_eq!(
+            pipeline.apply("Fix JIRA-42 and JIRA-7 in PRJ-X"),
+            "Fix ticket #42 and ticket #7 in the billing service"
+        );
+
+        let commits = pipeline.apply_to_commits(&[Commit::new("JIRA-1".to_string(), 1704067200)]);
+        assert_eq!(commits[0].message, "ticket #1");
+        assert_eq!(commits[0].timestamp(), 1704067200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_pipeline_rejects_invalid_pattern() {
+        let error = RewritePipeline::new(&[rule("JIRA-(", "ticket")])
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("JIRA-("));
+    }
+}
@@ -0,0 +1,51 @@
+use serde::Deserialize;
+
+/// One tier of `routing` in the `[ai]` config table, such as
+/// `{ max_commits = 5, model = "gpt-4o-mini" }`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoutingRule {
+    /// Largest number of commits this model handles; a tier without it takes the rest
+    pub max_commits: Option<usize>,
+    pub model: String,
+}
+
+/// Picks the model for a diary of `commits` commits: the tier with the
+/// smallest `max_commits` that fits, else the open-ended tier
+///
+/// `None` when no tier matches, so the configured model is used.
+pub fn route_model(rules: &[RoutingRule], commits: usize) -> Option<&str> {
+    rules
+        .iter()
+        .filter(|rule| rule.max_commits.is_none_or(|max| commits <= max))
+        .min_by_key(|rule| rule.max_commits.unwrap_or(usize::MAX))
+        .map(|rule| rule.model.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(max_commits: Option<usize>, model: &str) -> RoutingRule {
+        RoutingRule {
+            max_commits,
+            model: model.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_route_model() {
+        let rules = vec![
+            rule(Some(30), "gpt-4o"),
+            rule(Some(5), "gpt-4o-mini"),
+            rule(None, "o3"),
+        ];
+
+        assert_eq!(route_model(&rules, 0), Some("gpt-4o-mini"));
+        assert_eq!(route_model(&rules, 5), Some("gpt-4o-mini"));
+        assert_eq!(route_model(&rules, 6), Some("gpt-4o"));
+        assert_eq!(route_model(&rules, 31), Some("o3"));
+        assert_eq!(route_model(&rules[..2], 31), None);
+        assert_eq!(route_model(&[], 1), None);
+    }
+}
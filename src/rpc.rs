@@ -0,0 +1,283 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+#[cfg(test)]
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{self, Display};
+use std::io::{BufRead, Write};
+
+/// JSON-RPC error code for a message that isn't valid JSON
+pub const PARSE_ERROR: i64 = -32700;
+/// JSON-RPC error code for JSON that isn't a request
+pub const INVALID_REQUEST: i64 = -32600;
+/// JSON-RPC error code for an unknown method
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// JSON-RPC error code for parameters the method can't use
+pub const INVALID_PARAMS: i64 = -32602;
+/// JSON-RPC error code for a method that failed
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// Method that ends the session after it is answered, as in LSP
+pub const SHUTDOWN: &str = "shutdown";
+
+/// Answers the methods an editor calls
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait RpcHandler: Send + Sync {
+    /// Runs `method` with `params`, which is `null` when the request has none
+    ///
+    /// Fail with `UnknownMethod` or `InvalidParams` to get the matching error code.
+    async fn call(&self, method: &str, params: Value) -> Result<Value>;
+}
+
+/// The method isn't one the handler knows
+#[derive(Debug)]
+pub struct UnknownMethod(pub String);
+
+impl Display for UnknownMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown method {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownMethod {}
+
+/// The parameters don't fit the method
+#[derive(Debug)]
+pub struct InvalidParams(pub String);
+
+impl Display for InvalidParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid params: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidParams {}
+
+#[derive(Deserialize)]
+struct Request {
+    /// Missing for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ResponseError>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResponseError {
+    code: i64,
+    message: String,
+}
+
+impl Response {
+    fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn error(id: Value, code: i64, message: String) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(ResponseError { code, message }),
+        }
+    }
+}
+
+/// Reads one message framed by a `Content-Length` header, as in LSP; `None` at end of input
+pub fn read_message(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            if length.is_some() {
+                bail!("Input ended inside a message header");
+            }
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            // The blank line after the headers
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .with_context(|| format!("Invalid Content-Length: {}", value.trim()))?,
+                );
+            }
+        }
+    }
+
+    let mut body = vec![0; length.unwrap_or_default()];
+    reader.read_exact(&mut body)?;
+    Ok(Some(
+        String::from_utf8(body).context("Message is not UTF-8")?,
+    ))
+}
+
+/// Writes `body` with a `Content-Length` header
+pub fn write_message(writer: &mut impl Write, body: &str) -> Result<()> {
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Answers requests from `reader` one at a time until `shutdown` or the end of input
+pub async fn serve<H: RpcHandler + ?Sized>(
+    handler: &H,
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<()> {
+    while let Some(message) = read_message(&mut reader)? {
+        let request = match serde_json::from_str::<Value>(&message) {
+            Ok(json) => serde_json::from_value::<Request>(json),
+            Err(e) => {
+                let response = Response::error(Value::Null, PARSE_ERROR, e.to_string());
+                write_message(&mut writer, &serde_json::to_string(&response)?)?;
+                continue;
+            }
+        };
+        let request = match request {
+            Ok(request) => request,
+            Err(e) => {
+                let response = Response::error(Value::Null, INVALID_REQUEST, e.to_string());
+                write_message(&mut writer, &serde_json::to_string(&response)?)?;
+                continue;
+            }
+        };
+
+        let shutdown = request.method == SHUTDOWN;
+        let outcome = if shutdown {
+            Ok(Value::Null)
+        } else {
+            handler.call(&request.method, request.params).await
+        };
+        if let Some(id) = request.id {
+            let response = match outcome {
+                Ok(result) => Response::result(id, result),
+                Err(e) => Response::error(id, error_code(&e), format!("{:#}", e)),
+            };
+            write_message(&mut writer, &serde_json::to_string(&response)?)?;
+        }
+        if shutdown {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn error_code(error: &anyhow::Error) -> i64 {
+    if error.is::<UnknownMethod>() {
+        METHOD_NOT_FOUND
+    } else if error.is::<InvalidParams>() {
+        INVALID_PARAMS
+    } else {
+        INTERNAL_ERROR
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn framed(messages: &[&str]) -> String {
+        messages
+            .iter()
+            .map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body))
+            .collect()
+    }
+
+    fn responses(output: &[u8]) -> Vec<Value> {
+        let mut reader = output;
+        let mut responses = Vec::new();
+        while let Some(message) = read_message(&mut reader).unwrap() {
+            responses.push(serde_json::from_str(&message).unwrap());
+        }
+        responses
+    }
+
+    #[test]
+    fn test_read_message() -> Result<()> {
+        let input = "Content-Length: 10\r\nContent-Type: application/json\r\n\r\n{\"a\":\"é\"}";
+        let mut reader = input.as_bytes();
+
+        assert_eq!(read_message(&mut reader)?.as_deref(), Some("{\"a\":\"é\"}"));
+        assert_eq!(read_message(&mut reader)?, None);
+        assert!(read_message(&mut "Content-Length: x\r\n\r\n".as_bytes()).is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_serve() -> Result<()> {
+        let mut handler = MockRpcHandler::new();
+        handler
+            .expect_call()
+            .returning(|method, params| match method {
+                "listDiaries" => Ok(json!([{"path": "diaries/a.md", "params": params}])),
+                "generate" => Err(InvalidParams("unknown period".to_string()).into()),
+                "preview" => Err(anyhow::anyhow!("not a git repository")),
+                _ => Err(UnknownMethod(method.to_string()).into()),
+            });
+        let input = framed(&[
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "listDiaries", "params": {"repo": "app"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "generate"}"#,
+            r#"{"jsonrpc": "2.0", "method": "preview"}"#,
+            r#"{"jsonrpc": "2.0", "id": "3", "method": "format"}"#,
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "preview"}"#,
+            r#"not json"#,
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "shutdown"}"#,
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "listDiaries"}"#,
+        ]);
+        let mut output = Vec::new();
+
+        serve(&handler, input.as_bytes(), &mut output).await?;
+
+        let codes: Vec<(Value, Value)> = responses(&output)
+            .into_iter()
+            .map(|response| (response["id"].clone(), response["error"]["code"].clone()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (json!(1), Value::Null),
+                (json!(2), json!(INVALID_PARAMS)),
+                (json!("3"), json!(METHOD_NOT_FOUND)),
+                (json!(4), json!(INTERNAL_ERROR)),
+                (Value::Null, json!(PARSE_ERROR)),
+                (json!(5), Value::Null),
+            ]
+        );
+        assert_eq!(
+            responses(&output)[0]["result"],
+            json!([{"path": "diaries/a.md", "params": {"repo": "app"}}])
+        );
+
+        Ok(())
+    }
+}
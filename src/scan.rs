@@ -0,0 +1,331 @@
+use std::fmt::Display;
+use std::net::Ipv4Addr;
+
+use crate::domain::Commit;
+
+/// Prefixes of well-known API token formats
+const TOKEN_PREFIXES: &[&str] = &[
+    "ghp_",
+    "gho_",
+    "ghu_",
+    "ghs_",
+    "github_pat_",
+    "glpat-",
+    "sk-",
+    "xoxb-",
+    "xoxp-",
+    "xoxa-",
+    "AKIA",
+];
+
+/// Keys whose `key=value` assignments are treated as secrets
+const SECRET_KEYS: &[&str] = &["password", "passwd", "secret", "token", "api_key", "apikey"];
+
+/// Top-level domains only reachable inside private networks
+const INTERNAL_TLDS: &[&str] = &["internal", "local", "corp", "lan", "intranet", "private"];
+
+/// How the generator reacts to sensitive data in commit messages and diffs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SensitiveScan {
+    /// Don't scan
+    #[default]
+    Off,
+    /// Report findings and refuse to send the commits
+    Block,
+    /// Report findings but send the commits anyway
+    Allow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    Email,
+    Secret,
+    InternalHost,
+}
+
+impl Display for FindingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FindingKind::Email => write!(f, "email address"),
+            FindingKind::Secret => write!(f, "possible secret"),
+            FindingKind::InternalHost => write!(f, "internal hostname"),
+        }
+    }
+}
+
+/// Something in a commit message that probably shouldn't leave the machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// The matched text, masked for secrets
+    pub excerpt: String,
+    /// Subject of the commit the match was found in, redacted
+    pub subject: String,
+    /// Whether the match is in the commit's diff rather than its message
+    pub in_diff: bool,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.in_diff {
+            write!(
+                f,
+                "{} {} in the diff of \"{}\"",
+                self.kind, self.excerpt, self.subject
+            )
+        } else {
+            write!(f, "{} {} in \"{}\"", self.kind, self.excerpt, self.subject)
+        }
+    }
+}
+
+/// Looks for email addresses, tokens and internal hostnames in commit messages and diffs
+pub fn scan_commits(commits: &[Commit]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for commit in commits {
+        let subject = redact(commit.subject().lines().next().unwrap_or_default());
+
+        let sources = [(commit.message.as_str(), false)]
+            .into_iter()
+            .chain(commit.diff.as_deref().map(|diff| (diff, true)));
+        for (text, in_diff) in sources {
+            for word in text.split_whitespace() {
+                let word = word.trim_matches(|c: char| "\"'`()[]<>{},;:.!?".contains(c));
+                if let Some(kind) = classify(word) {
+                    let excerpt = match kind {
+                        FindingKind::Secret => mask(word),
+                        _ => word.to_string(),
+                    };
+                    findings.push(Finding {
+                        kind,
+                        excerpt,
+                        subject: subject.clone(),
+                        in_diff,
+                    });
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+/// Renders findings as the report shown before refusing to summarize
+pub fn format_report(findings: &[Finding]) -> String {
+    let mut report = format!(
+        "⚠️  Found {} possible piece(s) of sensitive data in commits:\n",
+        findings.len()
+    );
+    for finding in findings {
+        report.push_str(&format!("  - {}\n", finding));
+    }
+    report
+}
+
+/// Replaces everything `scan_commits` would report with a `[redacted ...]` placeholder
+///
+/// Whitespace and the punctuation around each match are kept.
+pub fn redact(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+
+    for piece in text.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end_matches(char::is_whitespace);
+        let trimmed = word.trim_matches(|c: char| "\"'`()[]<>{},;:.!?".contains(c));
+        match classify(trimmed) {
+            Some(kind) if !trimmed.is_empty() => {
+                let start = word.find(trimmed).unwrap_or_default();
+                output.push_str(&word[..start]);
+                output.push_str(&format!("[redacted {}]", kind));
+                output.push_str(&piece[start + trimmed.len()..]);
+            }
+            _ => output.push_str(piece),
+        }
+    }
+
+    output
+}
+
+fn classify(word: &str) -> Option<FindingKind> {
+    if is_secret(word) {
+        Some(FindingKind::Secret)
+    } else if is_email(word) {
+        Some(FindingKind::Email)
+    } else if is_internal_host(word) {
+        Some(FindingKind::InternalHost)
+    } else {
+        None
+    }
+}
+
+fn is_email(word: &str) -> bool {
+    let Some((local, domain)) = word.split_once('@') else {
+        return false;
+    };
+
+    !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._%+-".contains(c))
+        && is_hostname(domain)
+}
+
+fn is_secret(word: &str) -> bool {
+    if let Some((key, value)) = word.split_once('=') {
+        let key = key.to_ascii_lowercase();
+        if !value.is_empty() && SECRET_KEYS.iter().any(|secret| key.ends_with(secret)) {
+            return true;
+        }
+    }
+
+    if TOKEN_PREFIXES
+        .iter()
+        .any(|prefix| word.starts_with(prefix) && word.len() >= prefix.len() + 16)
+    {
+        return true;
+    }
+
+    // Long random-looking strings; commit hashes are all lowercase hex and don't match
+    word.len() >= 32
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+/=".contains(c))
+        && word.chars().any(|c| c.is_ascii_uppercase())
+        && word.chars().any(|c| c.is_ascii_lowercase())
+        && word.chars().any(|c| c.is_ascii_digit())
+}
+
+fn is_internal_host(word: &str) -> bool {
+    // Strip a scheme, port or path so URLs are checked by their host
+    let host = word.split_once("://").map_or(word, |(_, rest)| rest);
+    let host = host.split(['/', ':']).next().unwrap_or_default();
+
+    if let Ok(ip) = host.parse::<Ipv4Addr>() {
+        return ip.is_private();
+    }
+
+    is_hostname(host)
+        && host
+            .rsplit('.')
+            .next()
+            .is_some_and(|tld| INTERNAL_TLDS.contains(&tld.to_ascii_lowercase().as_str()))
+}
+
+fn is_hostname(host: &str) -> bool {
+    let labels: Vec<&str> = host.split('.').collect();
+
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Keeps the first four characters so the user can still find the secret
+fn mask(secret: &str) -> String {
+    let visible: String = secret.chars().take(4).collect();
+    format!("{}****", visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_commit(message: &str) -> Commit {
+        Commit::new(message.to_string(), 1704067200)
+    }
+
+    #[test]
+    fn test_scan_commits() {
+        let commits = vec![
+            create_test_commit("Send alerts to ops@example.com"),
+            create_test_commit("Use token ghp_abcdefghijklmnop1234 for CI"),
+            create_test_commit("Point staging at db01.corp (10.0.3.7)"),
+            create_test_commit("Set API_KEY=hunter2 in the sample env"),
+        ];
+
+        let findings = scan_commits(&commits);
+
+        let found: Vec<(FindingKind, &str)> = findings
+            .iter()
+            .map(|finding| (finding.kind, finding.excerpt.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (FindingKind::Email, "ops@example.com"),
+                (FindingKind::Secret, "ghp_****"),
+                (FindingKind::InternalHost, "db01.corp"),
+                (FindingKind::InternalHost, "10.0.3.7"),
+                (FindingKind::Secret, "API_****"),
+            ]
+        );
+        assert_eq!(
+            findings[0].subject,
+            "Send alerts to [redacted email address]"
+        );
+    }
+
+    #[test]
+    fn test_scan_commits_checks_diffs() {
+        let commits = vec![create_test_commit("Add sample env")
+            .with_diff("+++ b/.env.sample\n+API_KEY=hunter2\n".to_string())];
+
+        let findings = scan_commits(&commits);
+
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].excerpt, "+API****");
+        assert!(findings[0].in_diff);
+        assert_eq!(
+            findings[0].to_string(),
+            "possible secret +API**** in the diff of \"Add sample env\""
+        );
+    }
+
+    #[test]
+    fn test_scan_ignores_ordinary_messages() {
+        let commits = vec![
+            create_test_commit("Revert \"Add cache\"\n\nThis reverts commit 0123456789abcdef0123456789abcdef01234567."),
+            create_test_commit("Bump version to 1.2.3 and update docs.rs links"),
+            create_test_commit("Read token from the environment"),
+        ];
+
+        assert!(scan_commits(&commits).is_empty());
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact("Mail (jane@example.com) about db.corp.internal:5432,\ttoken=abc"),
+            "Mail ([redacted email address]) about [redacted internal hostname],\t[redacted possible secret]"
+        );
+        assert_eq!(redact("Fix cache bug\n"), "Fix cache bug\n");
+    }
+
+    #[test]
+    fn test_format_report() {
+        let findings = scan_commits(&[create_test_commit(
+            "Ping https://build.internal:8080/status",
+        )]);
+
+        let report = format_report(&findings);
+
+        assert!(report.contains("Found 1 possible piece(s)"));
+        assert!(report.contains(
+            "  - internal hostname https://build.internal:8080/status in \"Ping [redacted internal hostname]\"\n"
+        ));
+    }
+
+    #[test]
+    fn test_format_report_never_shows_secrets() {
+        let findings = scan_commits(&[create_test_commit(
+            "Use token ghp_abcdefghijklmnop1234 for CI",
+        )]);
+
+        let report = format_report(&findings);
+
+        assert!(!report.contains("ghp_abcdefghijklmnop1234"));
+        assert!(report.contains(
+            "  - possible secret ghp_**** in \"Use token [redacted possible secret] for CI\"\n"
+        ));
+    }
+}
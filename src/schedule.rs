@@ -0,0 +1,611 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Timelike};
+use directories::BaseDirs;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::domain::resolve_local;
+use crate::paths::fnv1a;
+
+/// Prefix of the scheduler entries git-diary creates
+const NAME_PREFIX: &str = "git-diary";
+
+/// Variables holding credentials git-diary reads, copied into scheduled runs
+/// as systemd and launchd don't start them from the user's shell
+pub const CREDENTIAL_VARS: &[&str] = &[
+    "OPENAI_API_KEY",
+    "GITHUB_TOKEN",
+    "GH_TOKEN",
+    "GITLAB_TOKEN",
+    "GLAB_TOKEN",
+    "BITBUCKET_TOKEN",
+    "JIRA_API_TOKEN",
+    "WAKATIME_API_KEY",
+];
+
+/// A daily `git-diary` run in one repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledRun {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub repo: PathBuf,
+    /// Local time of day the run starts at
+    pub at: NaiveTime,
+    /// Variables set for the run, such as `OPENAI_API_KEY`
+    pub environment: Vec<(String, String)>,
+}
+
+impl ScheduledRun {
+    pub fn new(program: PathBuf, args: Vec<String>, repo: PathBuf, at: NaiveTime) -> Self {
+        Self {
+            program,
+            args,
+            repo,
+            at,
+            environment: Vec::new(),
+        }
+    }
+
+    /// Sets `environment` for the run; systemd and launchd keep it in a file
+    /// only the user can read
+    pub fn with_environment(mut self, environment: Vec<(String, String)>) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Name of the scheduler entry, such as `git-diary-app-1a2b3c4d`; the hash
+    /// of the path keeps repositories with the same name apart
+    pub fn name(&self) -> String {
+        let repo_name: String = self
+            .repo
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        format!(
+            "{}-{}-{:08x}",
+            NAME_PREFIX,
+            repo_name.trim_matches('-'),
+            fnv1a(self.repo.to_string_lossy().as_bytes())
+        )
+    }
+}
+
+/// The `CREDENTIAL_VARS` set in this process, to pass on to scheduled runs
+pub fn current_credentials() -> Vec<(String, String)> {
+    CREDENTIAL_VARS
+        .iter()
+        .filter_map(|&name| Some((name.to_string(), std::env::var(name).ok()?)))
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+/// The next time `at` comes round after `now`
+pub fn next_run<Tz: TimeZone>(now: &DateTime<Tz>, at: NaiveTime) -> DateTime<Tz> {
+    let today = resolve_local(&now.timezone(), now.date_naive().and_time(at));
+    if today > *now {
+        return today;
+    }
+    let tomorrow = now.date_naive() + Duration::days(1);
+    resolve_local(&now.timezone(), tomorrow.and_time(at))
+}
+
+/// The scheduler of the platform, which starts runs after reboots and catches
+/// up on runs missed while the machine was off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scheduler {
+    /// A systemd user timer and service, in `unit_dir`
+    Systemd { unit_dir: PathBuf },
+    /// A launchd agent, in `agent_dir`, logging to `log_dir`
+    Launchd {
+        agent_dir: PathBuf,
+        log_dir: PathBuf,
+    },
+    /// A Windows Scheduled Task, whose definition is staged in `task_dir`
+    TaskScheduler { task_dir: PathBuf },
+}
+
+impl Scheduler {
+    /// The scheduler of the platform git-diary runs on
+    pub fn for_platform() -> Result<Self> {
+        let dirs =
+            BaseDirs::new().ok_or_else(|| anyhow!("Could not determine the home directory"))?;
+        match std::env::consts::OS {
+            "linux" => Ok(Self::Systemd {
+                unit_dir: dirs.config_dir().join("systemd").join("user"),
+            }),
+            "macos" => Ok(Self::Launchd {
+                agent_dir: dirs.home_dir().join("Library").join("LaunchAgents"),
+                log_dir: dirs.home_dir().join("Library").join("Logs").join(NAME_PREFIX),
+            }),
+            "windows" => Ok(Self::TaskScheduler {
+                task_dir: dirs.data_local_dir().join(NAME_PREFIX).join("tasks"),
+            }),
+            os => bail!(
+                "No supported scheduler on {}; run `git-diary watch` in the background or add a cron entry instead",
+                os
+            ),
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Systemd { .. } => "systemd user timer",
+            Self::Launchd { .. } => "launchd agent",
+            Self::TaskScheduler { .. } => "Scheduled Task",
+        }
+    }
+
+    /// Registers `run`, replacing an earlier registration for the repository,
+    /// and returns the files written
+    pub fn install(&self, run: &ScheduledRun) -> Result<Vec<PathBuf>> {
+        let name = run.name();
+        match self {
+            Self::Systemd { unit_dir } => {
+                let env_path = unit_dir.join(format!("{}.env", name));
+                let (service, timer) = systemd_units(run, &env_path);
+                let service_path = unit_dir.join(format!("{}.service", name));
+                let timer_path = unit_dir.join(format!("{}.timer", name));
+                let mut written = Vec::new();
+                if run.environment.is_empty() {
+                    remove_file(&env_path)?;
+                } else {
+                    write_private_file(&env_path, systemd_environment(run).as_bytes())?;
+                    written.push(env_path);
+                }
+                write_file(&service_path, service.as_bytes())?;
+                write_file(&timer_path, timer.as_bytes())?;
+                run_tool("systemctl", &["--user", "daemon-reload"])?;
+                run_tool(
+                    "systemctl",
+                    &["--user", "enable", "--now", &format!("{}.timer", name)],
+                )?;
+                written.extend([service_path, timer_path]);
+                Ok(written)
+            }
+            Self::Launchd { agent_dir, log_dir } => {
+                let path = agent_dir.join(format!("{}.plist", name));
+                fs::create_dir_all(log_dir)
+                    .with_context(|| format!("Failed to create {}", log_dir.display()))?;
+                // Private, as it may hold credentials
+                write_private_file(
+                    &path,
+                    launchd_plist(run, &log_dir.join(format!("{}.log", name))).as_bytes(),
+                )?;
+                let path_arg = path.to_string_lossy();
+                // Not loaded yet on a first install
+                let _ = run_tool("launchctl", &["unload", &path_arg]);
+                run_tool("launchctl", &["load", "-w", &path_arg])?;
+                Ok(vec![path])
+            }
+            Self::TaskScheduler { task_dir } => {
+                let path = task_dir.join(format!("{}.xml", name));
+                write_file(&path, &utf16_with_bom(&task_xml(run)))?;
+                run_tool(
+                    "schtasks",
+                    &[
+                        "/Create",
+                        "/TN",
+                        &name,
+                        "/XML",
+                        &path.to_string_lossy(),
+                        "/F",
+                    ],
+                )?;
+                Ok(vec![path])
+            }
+        }
+    }
+
+    /// Removes the registration named `name`; `false` when there was none
+    pub fn uninstall(&self, name: &str) -> Result<bool> {
+        match self {
+            Self::Systemd { unit_dir } => {
+                let timer_path = unit_dir.join(format!("{}.timer", name));
+                if !timer_path.exists() {
+                    return Ok(false);
+                }
+                run_tool(
+                    "systemctl",
+                    &["--user", "disable", "--now", &format!("{}.timer", name)],
+                )?;
+                remove_file(&timer_path)?;
+                remove_file(&unit_dir.join(format!("{}.service", name)))?;
+                remove_file(&unit_dir.join(format!("{}.env", name)))?;
+                run_tool("systemctl", &["--user", "daemon-reload"])?;
+                Ok(true)
+            }
+            Self::Launchd { agent_dir, .. } => {
+                let path = agent_dir.join(format!("{}.plist", name));
+                if !path.exists() {
+                    return Ok(false);
+                }
+                run_tool("launchctl", &["unload", "-w", &path.to_string_lossy()])?;
+                remove_file(&path)?;
+                Ok(true)
+            }
+            Self::TaskScheduler { task_dir } => {
+                let path = task_dir.join(format!("{}.xml", name));
+                if !path.exists() {
+                    return Ok(false);
+                }
+                run_tool("schtasks", &["/Delete", "/TN", name, "/F"])?;
+                remove_file(&path)?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// The systemd service running `run` once and the timer starting it daily;
+/// the service reads the run's environment from `env_file`
+pub fn systemd_units(run: &ScheduledRun, env_file: &Path) -> (String, String) {
+    let command = std::iter::once(run.program.to_string_lossy().to_string())
+        .chain(run.args.iter().cloned())
+        .map(|arg| systemd_quote(&arg))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let environment = if run.environment.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "EnvironmentFile={}\n",
+            systemd_quote(&env_file.to_string_lossy())
+        )
+    };
+    let service = format!(
+        "[Unit]\nDescription=git-diary for {repo}\n\n\
+         [Service]\nType=oneshot\nWorkingDirectory={dir}\n{environment}ExecStart={command}\n",
+        repo = run.repo.display(),
+        dir = systemd_quote(&run.repo.to_string_lossy()),
+        environment = environment,
+        command = command,
+    );
+    // Persistent catches up on a run missed while the machine was off
+    let timer = format!(
+        "[Unit]\nDescription=Daily git-diary for {repo}\n\n\
+         [Timer]\nOnCalendar=*-*-* {at}\nPersistent=true\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        repo = run.repo.display(),
+        at = run.at.format("%H:%M:00"),
+    );
+    (service, timer)
+}
+
+/// The run's environment as a systemd `EnvironmentFile`
+pub fn systemd_environment(run: &ScheduledRun) -> String {
+    run.environment
+        .iter()
+        .map(|(name, value)| {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+            format!("{}=\"{}\"\n", name, escaped)
+        })
+        .collect()
+}
+
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%");
+    format!("\"{}\"", escaped)
+}
+
+/// The launchd agent starting `run` daily, logging to `log`
+pub fn launchd_plist(run: &ScheduledRun, log: &Path) -> String {
+    let arguments: String = std::iter::once(run.program.to_string_lossy().to_string())
+        .chain(run.args.iter().cloned())
+        .map(|arg| format!("        <string>{}</string>\n", xml_escape(&arg)))
+        .collect();
+    let environment = if run.environment.is_empty() {
+        String::new()
+    } else {
+        let variables: String = run
+            .environment
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "        <key>{}</key>\n        <string>{}</string>\n",
+                    xml_escape(name),
+                    xml_escape(value)
+                )
+            })
+            .collect();
+        format!(
+            "    <key>EnvironmentVariables</key>\n    <dict>\n{}    </dict>\n",
+            variables
+        )
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>WorkingDirectory</key>
+    <string>{dir}</string>
+{environment}    <key>StartCalendarInterval</key>
+    <dict>
+        <key>Hour</key>
+        <integer>{hour}</integer>
+        <key>Minute</key>
+        <integer>{minute}</integer>
+    </dict>
+    <key>StandardOutPath</key>
+    <string>{log}</string>
+    <key>StandardErrorPath</key>
+    <string>{log}</string>
+</dict>
+</plist>
+"#,
+        label = xml_escape(&run.name()),
+        arguments = arguments,
+        dir = xml_escape(&run.repo.to_string_lossy()),
+        environment = environment,
+        hour = run.at.hour(),
+        minute = run.at.minute(),
+        log = xml_escape(&log.to_string_lossy()),
+    )
+}
+
+/// The Task Scheduler definition starting `run` daily
+///
+/// `StartWhenAvailable` runs the task once the machine is back on after
+/// missing it; the task only runs while the user is logged on, as it needs
+/// their credentials and config. It gets the user's environment variables
+/// rather than `run.environment`, so `OPENAI_API_KEY` has to be set with
+/// `setx` or the system settings, not just in a shell.
+pub fn task_xml(run: &ScheduledRun) -> String {
+    let arguments = run
+        .args
+        .iter()
+        .map(|arg| windows_quote(arg))
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.2" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Description>Daily git-diary for {dir}</Description>
+  </RegistrationInfo>
+  <Triggers>
+    <CalendarTrigger>
+      <StartBoundary>2000-01-01T{at}</StartBoundary>
+      <ScheduleByDay>
+        <DaysInterval>1</DaysInterval>
+      </ScheduleByDay>
+    </CalendarTrigger>
+  </Triggers>
+  <Principals>
+    <Principal id="Author">
+      <LogonType>InteractiveToken</LogonType>
+    </Principal>
+  </Principals>
+  <Settings>
+    <StartWhenAvailable>true</StartWhenAvailable>
+    <DisallowStartIfOnBatteries>false</DisallowStartIfOnBatteries>
+    <StopIfGoingOnBatteries>false</StopIfGoingOnBatteries>
+  </Settings>
+  <Actions Context="Author">
+    <Exec>
+      <Command>{program}</Command>
+      <Arguments>{arguments}</Arguments>
+      <WorkingDirectory>{dir}</WorkingDirectory>
+    </Exec>
+  </Actions>
+</Task>
+"#,
+        dir = xml_escape(&run.repo.to_string_lossy()),
+        at = run.at.format("%H:%M:00"),
+        program = xml_escape(&run.program.to_string_lossy()),
+        arguments = xml_escape(&arguments),
+    )
+}
+
+/// Quotes an argument for the Windows command line when it needs it
+///
+/// Follows the rules programs split their command line by: backslashes only
+/// escape a quote or another backslash that comes before one, so those are
+/// doubled, along with the backslashes before the closing quote.
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                continue;
+            }
+            '"' => quoted.push_str(&"\\".repeat(backslashes * 2 + 1)),
+            _ => quoted.push_str(&"\\".repeat(backslashes)),
+        }
+        quoted.push(c);
+        backslashes = 0;
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `schtasks` reads task definitions as UTF-16
+fn utf16_with_bom(text: &str) -> Vec<u8> {
+    std::iter::once(0xfeff)
+        .chain(text.encode_utf16())
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Writes a file only the user can read, for files holding credentials
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    // Created afresh, so the permissions apply before anything is written
+    remove_file(path)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(e).with_context(|| format!("Failed to remove {}", path.display()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn run_tool(program: &str, args: &[&str]) -> Result<()> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !output.status.success() {
+        bail!(
+            "`{} {}` failed: {}",
+            program,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn scheduled_run() -> ScheduledRun {
+        ScheduledRun::new(
+            PathBuf::from("/usr/local/bin/git-diary"),
+            vec![
+                "generate".to_string(),
+                "--since-last".to_string(),
+                "--context".to_string(),
+                "100% <done>".to_string(),
+            ],
+            PathBuf::from("/home/me/src/my app"),
+            NaiveTime::from_hms_opt(18, 5, 0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_name() {
+        let run = scheduled_run();
+        assert!(run.name().starts_with("git-diary-my-app-"));
+        assert_eq!(run.name().len(), "git-diary-my-app-".len() + 8);
+
+        let mut other = scheduled_run();
+        other.repo = PathBuf::from("/home/me/work/my app");
+        assert_ne!(run.name(), other.name());
+    }
+
+    #[test]
+    fn test_next_run() {
+        let at = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
+        let morning = Utc.with_ymd_and_hms(2024, 5, 7, 9, 0, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2024, 5, 7, 18, 0, 0).unwrap();
+
+        assert_eq!(
+            next_run(&morning, at),
+            Utc.with_ymd_and_hms(2024, 5, 7, 18, 0, 0).unwrap()
+        );
+        assert_eq!(
+            next_run(&evening, at),
+            Utc.with_ymd_and_hms(2024, 5, 8, 18, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_scheduler_definitions() {
+        let run = scheduled_run();
+
+        let (service, timer) = systemd_units(&run, Path::new("/units/run.env"));
+        assert!(service.contains("WorkingDirectory=\"/home/me/src/my app\"\n"));
+        assert!(!service.contains("EnvironmentFile"));
+        assert!(service.contains(
+            "ExecStart=\"/usr/local/bin/git-diary\" \"generate\" \"--since-last\" \"--context\" \"100%% <done>\"\n"
+        ));
+        assert!(timer.contains("OnCalendar=*-*-* 18:05:00\nPersistent=true\n"));
+
+        let plist = launchd_plist(&run, Path::new("/tmp/git-diary.log"));
+        assert!(plist.contains("<string>100% &lt;done&gt;</string>"));
+        assert!(plist.contains("<key>Hour</key>\n        <integer>18</integer>"));
+        assert!(plist.contains("<key>Minute</key>\n        <integer>5</integer>"));
+
+        let task = task_xml(&run);
+        assert!(task.contains("<StartBoundary>2000-01-01T18:05:00</StartBoundary>"));
+        assert!(task.contains(
+            "<Arguments>generate --since-last --context &quot;100% &lt;done&gt;&quot;</Arguments>"
+        ));
+        assert_eq!(&utf16_with_bom("<")[..4], &[0xff, 0xfe, b'<', 0]);
+    }
+
+    #[test]
+    fn test_scheduled_environment() {
+        let run = scheduled_run().with_environment(vec![(
+            "OPENAI_API_KEY".to_string(),
+            "sk-\"a&b\"".to_string(),
+        )]);
+
+        let (service, _) = systemd_units(&run, Path::new("/units/run.env"));
+        assert!(service.contains("EnvironmentFile=\"/units/run.env\"\nExecStart="));
+        assert_eq!(
+            systemd_environment(&run),
+            "OPENAI_API_KEY=\"sk-\\\"a&b\\\"\"\n"
+        );
+
+        let plist = launchd_plist(&run, Path::new("/tmp/git-diary.log"));
+        assert!(plist.contains(
+            "<key>EnvironmentVariables</key>\n    <dict>\n        <key>OPENAI_API_KEY</key>\n        <string>sk-&quot;a&amp;b&quot;</string>\n    </dict>\n"
+        ));
+    }
+
+    #[test]
+    fn test_windows_quote() {
+        assert_eq!(windows_quote("--since-last"), "--since-last");
+        assert_eq!(windows_quote(r"C:\diaries\"), r"C:\diaries\");
+        assert_eq!(windows_quote(""), r#""""#);
+        assert_eq!(windows_quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(windows_quote(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(windows_quote(r"C:\my diaries\"), r#""C:\my diaries\\""#);
+    }
+}
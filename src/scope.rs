@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use crate::domain::Commit;
+
+/// Top-level directories that hold code rather than name a module
+const CONTAINER_DIRS: &[&str] = &[
+    "src", "lib", "app", "apps", "pkg", "packages", "crates", "modules", "internal", "cmd",
+];
+
+/// Infers a scope such as `auth` or `infra` from the files a commit touched
+///
+/// Each file counts towards its first directory below any container
+/// directory like `src/` or `packages/`. The scope is the directory holding
+/// at least half of the files; files in the repository root or in hidden
+/// directories such as `.github/` have no scope.
+pub fn infer_scope(files: &[String]) -> Option<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for file in files {
+        if let Some(module) = module_of(file) {
+            *counts.entry(module).or_default() += 1;
+        }
+    }
+
+    let count = counts.values().copied().max()?;
+    let mut leaders = counts.iter().filter(|(_, n)| **n == count);
+    let (module, _) = leaders.next()?;
+
+    // A tie for first place means there is no dominant directory
+    if leaders.next().is_some() || count * 2 < files.len() {
+        return None;
+    }
+
+    Some(module.to_string())
+}
+
+/// Sets the scope of every commit that doesn't follow conventional commits
+pub fn infer_scopes(commits: &mut [Commit]) {
+    for commit in commits {
+        if commit.conventional_type().is_none() {
+            commit.scope = infer_scope(&commit.files);
+        }
+    }
+}
+
+fn module_of(file: &str) -> Option<&str> {
+    let mut directories: Vec<&str> = file.split('/').collect();
+    directories.pop();
+
+    directories
+        .into_iter()
+        .find(|directory| !CONTAINER_DIRS.contains(directory))
+        .filter(|directory| !directory.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn test_infer_scope() {
+        assert_eq!(
+            infer_scope(&files(&[
+                "src/auth/login.rs",
+                "src/auth/token.rs",
+                "src/ui/form.rs"
+            ])),
+            Some("auth".to_string())
+        );
+        assert_eq!(
+            infer_scope(&files(&["packages/ui/Button.tsx", "README.md"])),
+            Some("ui".to_string())
+        );
+        assert_eq!(
+            infer_scope(&files(&["infra/terraform/main.tf"])),
+            Some("infra".to_string())
+        );
+
+        // Root files, container directories and ties have no scope
+        assert_eq!(infer_scope(&files(&["README.md", "src/main.rs"])), None);
+        assert_eq!(
+            infer_scope(&files(&["src/auth/login.rs", "src/ui/form.rs"])),
+            None
+        );
+        assert_eq!(
+            infer_scope(&files(&[".github/workflows/ci.yml", "Cargo.toml"])),
+            None
+        );
+        assert_eq!(infer_scope(&[]), None);
+    }
+
+    #[test]
+    fn test_infer_scopes_skips_conventional_commits() {
+        let mut commits = vec![
+            Commit::new("Fix login redirect".to_string(), 1704067200)
+                .with_files(files(&["src/auth/login.rs"])),
+            Commit::new("fix: Login redirect".to_string(), 1704067200)
+                .with_files(files(&["src/auth/login.rs"])),
+        ];
+
+        infer_scopes(&mut commits);
+
+        assert_eq!(commits[0].scope.as_deref(), Some("auth"));
+        assert_eq!(
+            commits[0].to_string(),
+            "2024-01-01 00:00:00: [auth] Fix login redirect"
+        );
+        assert_eq!(commits[1].scope, None);
+    }
+}
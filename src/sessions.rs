@@ -0,0 +1,75 @@
+use crate::domain::Commit;
+
+/// Pause between commits that ends a working session, in minutes
+pub const DEFAULT_SESSION_GAP_MINUTES: i64 = 120;
+
+/// Splits commits into working sessions, oldest first, starting a new
+/// session whenever more than `gap_minutes` pass without a commit
+pub fn split_sessions(commits: &[Commit], gap_minutes: i64) -> Vec<Vec<Commit>> {
+    let mut sorted = commits.to_vec();
+    sorted.sort_by_key(Commit::timestamp);
+
+    let mut sessions: Vec<Vec<Commit>> = Vec::new();
+    for commit in sorted {
+        match sessions.last_mut() {
+            Some(session)
+                if session.last().is_some_and(|last| {
+                    commit.timestamp() - last.timestamp() <= gap_minutes * 60
+                }) =>
+            {
+                session.push(commit)
+            }
+            _ => sessions.push(vec![commit]),
+        }
+    }
+
+    sessions
+}
+
+/// Names the part of the day a session starts in, from the local hour
+pub fn part_of_day(hour: u32) -> &'static str {
+    match hour {
+        5..=11 => "Morning",
+        12..=16 => "Afternoon",
+        17..=21 => "Evening",
+        _ => "Night",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sessions() {
+        let commit = |message: &str, minutes: i64| Commit::new(message.to_string(), minutes * 60);
+        let commits = vec![
+            commit("Start migration tooling", 600),
+            commit("Fix flaky auth test", 10),
+            commit("Retry auth on timeout", 100),
+            commit("Add migration dry run", 700),
+            commit("Fix typo", 200),
+        ];
+
+        let sessions: Vec<Vec<String>> = split_sessions(&commits, 120)
+            .into_iter()
+            .map(|session| session.into_iter().map(|c| c.message).collect())
+            .collect();
+        assert_eq!(
+            sessions,
+            vec![
+                vec!["Fix flaky auth test", "Retry auth on timeout", "Fix typo"],
+                vec!["Start migration tooling", "Add migration dry run"],
+            ]
+        );
+        assert!(split_sessions(&[], 120).is_empty());
+    }
+
+    #[test]
+    fn test_part_of_day() {
+        assert_eq!(part_of_day(9), "Morning");
+        assert_eq!(part_of_day(12), "Afternoon");
+        assert_eq!(part_of_day(19), "Evening");
+        assert_eq!(part_of_day(2), "Night");
+    }
+}
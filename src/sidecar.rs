@@ -0,0 +1,188 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::domain::{Commit, DiaryContent};
+use crate::worktime::WorkHours;
+
+/// Version of the sidecar format, raised whenever a field is renamed or
+/// removed; adding fields keeps the version
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Machine-readable companion to a diary, written as `<diary>.json`
+///
+/// Holds everything in the diary plus a few derived numbers so analytics
+/// tools don't have to parse Markdown. The format is described by the schema
+/// in `schema/sidecar.schema.json`.
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ActivitySidecar<'a> {
+    /// `SCHEMA_VERSION` of the writer
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub content: &'a DiaryContent,
+    pub stats: ActivityStats,
+    /// Conventional-commit types such as `feat` or `fix`, sorted and deduplicated
+    pub tags: Vec<String>,
+}
+
+/// Counts derived from a diary's commits and notes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ActivityStats {
+    pub commits: usize,
+    pub notes: usize,
+    /// Number of distinct UTC days with at least one commit
+    pub active_days: usize,
+    /// Number of distinct author emails; commits without an author aren't counted
+    pub authors: usize,
+    /// Commits made between 22:00 and 05:00 in the author's time zone
+    pub late_night_commits: usize,
+    /// Commits made on a Saturday or Sunday in the author's time zone
+    pub weekend_commits: usize,
+}
+
+impl ActivityStats {
+    /// Counts for `commits` and a diary with `notes` manual notes
+    pub fn from_commits(commits: &[Commit], notes: usize) -> Self {
+        let active_days: BTreeSet<String> = commits
+            .iter()
+            .filter_map(|commit| commit.datetime())
+            .map(|datetime| datetime[..10].to_string())
+            .collect();
+        let authors: BTreeSet<String> = commits
+            .iter()
+            .filter_map(|commit| commit.author.as_ref())
+            .map(|author| author.email.to_ascii_lowercase())
+            .collect();
+        let work_hours = WorkHours::from_commits(commits);
+
+        Self {
+            commits: commits.len(),
+            notes,
+            active_days: active_days.len(),
+            authors: authors.len(),
+            late_night_commits: work_hours.late_night,
+            weekend_commits: work_hours.weekend,
+        }
+    }
+}
+
+/// Conventional-commit types of `commits`, sorted and deduplicated
+pub fn commit_tags(commits: &[Commit]) -> Vec<String> {
+    let tags: BTreeSet<String> = commits
+        .iter()
+        .filter_map(Commit::conventional_type)
+        .collect();
+    tags.into_iter().collect()
+}
+
+impl<'a> ActivitySidecar<'a> {
+    pub fn new(content: &'a DiaryContent) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            content,
+            stats: ActivityStats::from_commits(&content.commits, content.notes.len()),
+            tags: commit_tags(&content.commits),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// JSON schema of the sidecar, pinned to the current `SCHEMA_VERSION`
+#[cfg(feature = "schema")]
+pub fn json_schema() -> serde_json::Value {
+    let mut schema = schemars::schema_for!(ActivitySidecar<'static>).to_value();
+    schema["title"] = format!("git-diary sidecar v{}", SCHEMA_VERSION).into();
+    schema["properties"]["schema_version"]["const"] = SCHEMA_VERSION.into();
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Author, CommitOrder, Note};
+    use serde_json::Value;
+
+    #[test]
+    fn test_activity_sidecar() -> serde_json::Result<()> {
+        let author = Author::new("Jane".to_string(), "jane@example.com".to_string());
+        let content = DiaryContent {
+            commits: vec![
+                Commit::new("feat(cli)!: Add sidecar".to_string(), 1704067200)
+                    .with_author(author.clone())
+                    .with_author_offset(0),
+                Commit::new("commit: fix: Handle empty logs".to_string(), 1704070800)
+                    .with_author(Author::new(
+                        "Jane".to_string(),
+                        "JANE@example.com".to_string(),
+                    ))
+                    .with_author_offset(540),
+                Commit::new("Update README: typo".to_string(), 1704153600).with_author_offset(540),
+                Commit::new("feat: Add notes".to_string(), 1704153600)
+                    .with_author(author)
+                    .with_author_offset(540),
+            ],
+            summary: "Busy days.".to_string(),
+            start_date: "2024-01-01".to_string(),
+            end_date: "2024-01-02".to_string(),
+            rollup: None,
+            notes: vec![Note::new("Paired with Alice".to_string(), 1704067200)],
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::default(),
+        };
+
+        let sidecar = ActivitySidecar::new(&content);
+        assert_eq!(
+            sidecar.stats,
+            ActivityStats {
+                commits: 4,
+                notes: 1,
+                active_days: 2,
+                authors: 1,
+                late_night_commits: 1,
+                weekend_commits: 0,
+            }
+        );
+        assert_eq!(sidecar.tags, vec!["feat", "fix"]);
+
+        let json: Value = serde_json::from_str(&sidecar.to_json()?)?;
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["summary"], "Busy days.");
+        assert_eq!(json["start_date"], "2024-01-01");
+        assert_eq!(json["commits"][0]["time"], 1704067200);
+        assert_eq!(json["commits"][0]["author"]["email"], "jane@example.com");
+        assert_eq!(json["commits"][2]["author"], Value::Null);
+        assert_eq!(json["notes"][0]["text"], "Paired with Alice");
+        assert_eq!(json["stats"]["active_days"], 2);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn test_published_schema_is_current() {
+        let published: Value =
+            serde_json::from_str(include_str!("../schema/sidecar.schema.json")).unwrap();
+        assert_eq!(
+            published,
+            json_schema(),
+            "schema/sidecar.schema.json is stale; regenerate it with `git-diary schema`"
+        );
+    }
+}
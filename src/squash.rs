@@ -0,0 +1,191 @@
+use crate::domain::Commit;
+
+/// Fewest top-level bullets a message needs before it is split
+const MIN_BULLETS: usize = 2;
+
+/// Lines that `git merge --squash` and GitLab put in squash commit messages
+const SQUASH_MARKERS: &[&str] = &["Squashed commit of the following:", "See merge request "];
+
+/// Splits squash merges whose message lists the changes as bullets
+///
+/// A squash merge, told by a pull request subject such as `Add billing (#42)`
+/// or a line git or GitLab add to squashed messages, with at least two
+/// top-level `-`, `*`, `+` or `•` bullets after its subject is followed by one
+/// commit per bullet, so the diary lists the individual changes instead of one
+/// long line. Its subject stays as the first entry. The new commits keep the
+/// original time, author and files, and remember the subject they came from
+/// for pull request grouping. Nested bullets and wrapped lines are folded
+/// into their top-level bullet. Other commits are left alone, however their
+/// bodies are written.
+pub fn expand_squash_merges(commits: &[Commit]) -> Vec<Commit> {
+    let mut expanded = Vec::with_capacity(commits.len());
+
+    for commit in commits {
+        let (subject, body) = match &commit.body {
+            Some(body) => (commit.subject(), body.as_str()),
+            None => commit
+                .subject()
+                .split_once('\n')
+                .unwrap_or((commit.subject(), "")),
+        };
+        let bullets = top_level_bullets(body);
+        if !is_squash_merge(commit, body) || bullets.len() < MIN_BULLETS {
+            expanded.push(commit.clone());
+            continue;
+        }
+
+        let subject = subject.trim_end();
+        let prefix = commit
+            .reflog_action()
+            .map(|action| format!("{}: ", action))
+            .unwrap_or_default();
+        let mut head = commit.clone();
+        head.message = format!("{}{}", prefix, subject);
+        head.body = None;
+        head.squashed_from = Some(subject.to_string());
+        expanded.push(head);
+        for bullet in bullets {
+            let mut part = commit.clone();
+            part.message = format!("{}{}", prefix, bullet);
+            part.body = None;
+            part.squashed_from = Some(subject.to_string());
+            // Renames and line counts describe the whole merge, so they are
+            // noted once, on the subject
+            part.renames = Vec::new();
+            part.line_stats = None;
+            expanded.push(part);
+        }
+    }
+
+    expanded
+}
+
+fn is_squash_merge(commit: &Commit, body: &str) -> bool {
+    commit.pull_request().is_some()
+        || body.lines().any(|line| {
+            SQUASH_MARKERS
+                .iter()
+                .any(|marker| line.trim().starts_with(marker))
+        })
+}
+
+fn top_level_bullets(body: &str) -> Vec<String> {
+    let mut bullets: Vec<String> = Vec::new();
+    let mut in_bullet = false;
+
+    for line in body.lines() {
+        if line.trim().is_empty() {
+            in_bullet = false;
+            continue;
+        }
+
+        let indented = line.starts_with("  ") || line.starts_with('\t');
+        match bullet_text(line) {
+            Some(text) if !indented => {
+                bullets.push(text.to_string());
+                in_bullet = true;
+            }
+            // Nested bullets add detail the top-level bullet already sums up
+            Some(_) => {}
+            None if indented && in_bullet => {
+                if let Some(last) = bullets.last_mut() {
+                    last.push(' ');
+                    last.push_str(line.trim());
+                }
+            }
+            None => in_bullet = false,
+        }
+    }
+
+    bullets.retain(|bullet| !bullet.is_empty());
+    bullets
+}
+
+/// Text of a bullet line, without the marker or a task-list checkbox
+fn bullet_text(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let text = ["- ", "* ", "+ ", "• "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))?;
+    let text = ["[ ] ", "[x] ", "[X] "]
+        .iter()
+        .find_map(|checkbox| text.strip_prefix(checkbox))
+        .unwrap_or(text);
+
+    Some(text.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{group_by_pull_request, FileRename};
+
+    #[test]
+    fn test_expand_squash_merges() {
+        let squash = Commit::new("commit: Add billing (#42)".to_string(), 1704067200)
+            .with_body(
+                "* Add invoice model\n  with tax rates\n  - nested detail\n* [x] Add Stripe webhook\n\nCo-authored-by: Jane <jane@example.com>"
+                    .to_string(),
+            )
+            .with_files(vec!["src/billing/invoice.rs".to_string()])
+            .with_renames(vec![FileRename::new(
+                "src/pay.rs".to_string(),
+                "src/billing/pay.rs".to_string(),
+            )]);
+        let plain = Commit::new("Fix typo".to_string(), 1704070800)
+            .with_body("- only one bullet".to_string());
+
+        let expanded = expand_squash_merges(&[squash, plain]);
+
+        let messages: Vec<&str> = expanded.iter().map(|c| c.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "commit: Add billing (#42)",
+                "commit: Add invoice model with tax rates",
+                "commit: Add Stripe webhook",
+                "Fix typo"
+            ]
+        );
+        assert_eq!(expanded[2].timestamp(), 1704067200);
+        assert_eq!(expanded[2].files, vec!["src/billing/invoice.rs"]);
+        assert_eq!(expanded[0].renames.len(), 1);
+        assert!(expanded[1].renames.is_empty());
+
+        // The split entries still group under the pull request they came from
+        let groups = group_by_pull_request(&expanded);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].title, "Add billing");
+        assert_eq!(groups[0].commits.len(), 3);
+    }
+
+    #[test]
+    fn test_expand_squash_merges_reads_multi_line_messages() {
+        // Pasted `git log` output keeps the body in the message
+        let commit = Commit::new(
+            "Release 1.2\n\nSquashed commit of the following:\n\n- Faster startup\n- Dark mode"
+                .to_string(),
+            1704067200,
+        );
+
+        let expanded = expand_squash_merges(&[commit]);
+
+        assert_eq!(expanded.len(), 3);
+        assert_eq!(expanded[0].message, "Release 1.2");
+        assert_eq!(expanded[1].message, "Faster startup");
+        assert_eq!(expanded[2].squashed_from.as_deref(), Some("Release 1.2"));
+    }
+
+    #[test]
+    fn test_expand_squash_merges_leaves_other_commits() {
+        // A commit that explains itself in bullets is not a squash merge
+        let commit = Commit::new("Tune the cache".to_string(), 1704067200)
+            .with_body("- Raise the size limit\n- Evict by age".to_string());
+
+        let expanded = expand_squash_merges(&[commit]);
+
+        assert_eq!(expanded.len(), 1);
+        assert_eq!(expanded[0].message, "Tune the cache");
+        assert!(expanded[0].body.is_some());
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Durable record of the last successful run per repository
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RunState {
+    /// Unix timestamp of the last successful run, keyed by profile or repository
+    last_runs: BTreeMap<String, i64>,
+}
+
+/// Small on-disk store that survives restarts and crashes
+///
+/// Writes go to a temporary file which is then renamed over the state file,
+/// so a crash mid-write never leaves a half-written state behind. A state
+/// file that fails to parse is moved aside and replaced with an empty state.
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    pub fn new(path: String) -> Self {
+        Self {
+            path: PathBuf::from(path),
+        }
+    }
+
+    /// Returns the last successful run timestamp recorded for `key`
+    pub fn last_run(&self, key: &str) -> Result<Option<i64>> {
+        Ok(self.load()?.last_runs.get(key).copied())
+    }
+
+    /// Records a successful run for `key`
+    pub fn record_run(&self, key: &str, timestamp: i64) -> Result<()> {
+        let mut state = self.load()?;
+        state.last_runs.insert(key.to_string(), timestamp);
+        self.save(&state)
+    }
+
+    fn load(&self) -> Result<RunState> {
+        if !self.path.exists() {
+            return Ok(RunState::default());
+        }
+
+        let raw = fs::read_to_string(&self.path).context("Failed to read state file")?;
+        match serde_json::from_str(&raw) {
+            Ok(state) => Ok(state),
+            Err(e) => {
+                let backup = self.path.with_extension("json.corrupt");
+                fs::rename(&self.path, &backup).context("Failed to move corrupt state file")?;
+                eprintln!(
+                    "⚠️ State file was corrupt ({}), starting fresh. Old file kept at {}",
+                    e,
+                    backup.display()
+                );
+                Ok(RunState::default())
+            }
+        }
+    }
+
+    fn save(&self, state: &RunState) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Failed to create state directory")?;
+        }
+
+        let temp_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&temp_path).context("Failed to create state file")?;
+        file.write_all(serde_json::to_string_pretty(state)?.as_bytes())
+            .context("Failed to write state file")?;
+        file.sync_all().context("Failed to flush state file")?;
+
+        fs::rename(&temp_path, &self.path).context("Failed to replace state file")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_test_store(temp_dir: &TempDir) -> StateStore {
+        StateStore::new(state_path(temp_dir).to_string_lossy().to_string())
+    }
+
+    fn state_path(temp_dir: &TempDir) -> PathBuf {
+        temp_dir.path().join("state/state.json")
+    }
+
+    #[test]
+    fn test_state_store_round_trip() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = create_test_store(&temp_dir);
+
+        assert_eq!(store.last_run("repo-a")?, None);
+
+        store.record_run("repo-a", 1704067200)?;
+        store.record_run("repo-b", 1704153600)?;
+        store.record_run("repo-a", 1704240000)?;
+
+        // A fresh handle sees the persisted values
+        let store = create_test_store(&temp_dir);
+        assert_eq!(store.last_run("repo-a")?, Some(1704240000));
+        assert_eq!(store.last_run("repo-b")?, Some(1704153600));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_store_recovers_from_corruption() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let store = create_test_store(&temp_dir);
+
+        store.record_run("repo-a", 1704067200)?;
+        fs::write(state_path(&temp_dir), "{\"last_runs\": {\"repo-a\": 17")?;
+
+        assert_eq!(store.last_run("repo-a")?, None);
+        assert!(state_path(&temp_dir)
+            .with_extension("json.corrupt")
+            .exists());
+
+        // The store keeps working after recovery
+        store.record_run("repo-a", 1704153600)?;
+        assert_eq!(store.last_run("repo-a")?, Some(1704153600));
+
+        Ok(())
+    }
+}
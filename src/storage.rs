@@ -1,17 +1,148 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
 use std::fs::{self, File};
-use std::io::Write;
-use std::path::Path;
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-use crate::domain::{DiaryContent, DiaryStorage};
+use crate::domain::{BranchIndex, DiaryContent, DiaryStorage, OutputCheck};
+use crate::formatter::{format_branch_index, DiaryFormatter, MarkdownFormatter};
+use crate::sidecar::ActivitySidecar;
+
+/// Subdirectory of the base directory that pruned diaries are archived to
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// File name suffix of `--per-branch` indexes, which link diaries instead of being one
+pub const BRANCH_INDEX_SUFFIX: &str = "-branches.md";
+
+/// File briefly created to check the diary directory is writable
+const WRITE_PROBE: &str = ".git-diary-write-check";
+
+/// Longest file name most file systems accept, in bytes
+const MAX_FILE_NAME_BYTES: usize = 255;
+
+/// Most symlinks followed to find the file a diary path points at
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// A diary file, or its JSON sidecar, found in the base directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredDiary {
+    pub path: PathBuf,
+    /// First day the diary covers, taken from its file name
+    pub start_date: NaiveDate,
+    /// Last day the diary covers, taken from its file name
+    pub end_date: NaiveDate,
+}
 
 pub struct DiaryStorageImpl {
     base_dir: String,
+    formatter: Box<dyn DiaryFormatter>,
+    sidecar_json: bool,
 }
 
 impl DiaryStorageImpl {
     pub fn new(base_dir: String) -> Self {
-        Self { base_dir }
+        Self {
+            base_dir,
+            formatter: Box::new(MarkdownFormatter),
+            sidecar_json: false,
+        }
+    }
+
+    /// Writes diaries using `formatter` instead of Markdown
+    pub fn with_formatter(mut self, formatter: Box<dyn DiaryFormatter>) -> Self {
+        self.formatter = formatter;
+        self
+    }
+
+    /// Also writes a JSON sidecar with the same name next to each diary
+    pub fn with_sidecar_json(mut self, sidecar_json: bool) -> Self {
+        self.sidecar_json = sidecar_json;
+        self
+    }
+
+    /// Lists the Markdown diaries stored in the base directory, sorted by name
+    pub fn list_diaries(&self) -> Result<Vec<PathBuf>> {
+        let diary_dir = Path::new(&self.base_dir);
+        if !diary_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut diaries = Vec::new();
+        for entry in fs::read_dir(diary_dir).context("Failed to read diary directory")? {
+            let path = entry?.path();
+            let is_index = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().ends_with(BRANCH_INDEX_SUFFIX));
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "md") && !is_index {
+                diaries.push(path);
+            }
+        }
+        diaries.sort();
+
+        Ok(diaries)
+    }
+
+    /// Lists every diary file in any format, plus JSON sidecars, sorted by name
+    ///
+    /// Only files named like `git-diary-20240101-to-20240107.md`, optionally
+    /// with a branch or index suffix, are listed, so anything else in the
+    /// directory is never touched by pruning.
+    pub fn stored_diaries(&self) -> Result<Vec<StoredDiary>> {
+        let diary_dir = Path::new(&self.base_dir);
+        if !diary_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut diaries = Vec::new();
+        for entry in fs::read_dir(diary_dir).context("Failed to read diary directory")? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let dates = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.strip_prefix("git-diary-"))
+                .and_then(|range| range.split_once("-to-"))
+                .and_then(|(start, end)| {
+                    let start = NaiveDate::parse_from_str(start, "%Y%m%d").ok()?;
+                    let end = NaiveDate::parse_from_str(end.get(..8)?, "%Y%m%d").ok()?;
+                    Some((start, end))
+                });
+            if let Some((start_date, end_date)) = dates {
+                diaries.push(StoredDiary {
+                    path,
+                    start_date,
+                    end_date,
+                });
+            }
+        }
+        diaries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(diaries)
+    }
+
+    /// Deletes a stored diary
+    pub fn delete_diary(&self, diary: &StoredDiary) -> Result<()> {
+        fs::remove_file(&diary.path)
+            .with_context(|| format!("Failed to delete {}", diary.path.display()))
+    }
+
+    /// Moves a stored diary into the archive directory, returning its new path
+    pub fn archive_diary(&self, diary: &StoredDiary) -> Result<PathBuf> {
+        let archive_dir = Path::new(&self.base_dir).join(ARCHIVE_DIR);
+        fs::create_dir_all(&archive_dir).context("Failed to create archive directory")?;
+
+        let file_name = diary
+            .path
+            .file_name()
+            .context("Diary path has no file name")?;
+        let archived = archive_dir.join(file_name);
+        fs::rename(&diary.path, &archived)
+            .with_context(|| format!("Failed to archive {}", diary.path.display()))?;
+
+        Ok(archived)
     }
 }
 
@@ -26,18 +157,26 @@ impl DiaryStorage for DiaryStorageImpl {
         // Generate the file name
         let file_name = self.generate_file_name(content);
 
-        // Create the file
-        let mut file = File::create(&file_name).context("Failed to create diary file")?;
-
         // Format the content
-        let markdown_content = self.format_markdown_content(content);
+        let formatted_content = self.format_content(content);
 
-        // Write to file
-        file.write_all(markdown_content.as_bytes())
-            .context("Failed to write to diary file")?;
+        write_atomic(
+            Path::new(&file_name),
+            formatted_content.as_bytes(),
+            "the diary",
+        )?;
 
         println!("Diary saved to: {}", file_name);
 
+        if self.sidecar_json {
+            let sidecar_path = Path::new(&file_name).with_extension("json");
+            let json = ActivitySidecar::new(content)
+                .to_json()
+                .context("Failed to serialize activity sidecar")?;
+            write_atomic(&sidecar_path, json.as_bytes(), "the activity sidecar")?;
+            println!("Activity JSON saved to: {}", sidecar_path.display());
+        }
+
         Ok(file_name)
     }
 
@@ -51,15 +190,14 @@ impl DiaryStorage for DiaryStorageImpl {
     ///
     /// A String containing the file path
     fn generate_file_name(&self, content: &DiaryContent) -> String {
-        format!(
-            "{}/git-diary-{}-to-{}.md",
-            self.base_dir,
-            content.start_date.replace("-", ""),
-            content.end_date.replace("-", "")
+        self.file_name(
+            &content.start_date,
+            &content.end_date,
+            content.branch.as_deref(),
         )
     }
 
-    /// Formats the diary content as Markdown
+    /// Formats the diary content with the configured formatter
     ///
     /// # Arguments
     ///
@@ -67,26 +205,333 @@ impl DiaryStorage for DiaryStorageImpl {
     ///
     /// # Returns
     ///
-    /// A String containing the formatted Markdown content
-    fn format_markdown_content(&self, content: &DiaryContent) -> String {
-        // Format commit logs
-        let mut commit_logs = String::new();
-        for commit in content.commits.iter().rev() {
-            commit_logs.push_str(&format!("- {}\n", commit));
-        }
+    /// A String containing the formatted content, Markdown by default
+    fn format_content(&self, content: &DiaryContent) -> String {
+        self.formatter.format(content)
+    }
 
-        // Create markdown content
+    /// Writes the index as Markdown, whatever format the diaries use
+    fn save_branch_index(&self, index: &BranchIndex) -> Result<String> {
+        fs::create_dir_all(&self.base_dir).context("Failed to create diary directory")?;
+
+        let file_name = format!(
+            "{}/git-diary-{}-to-{}{}",
+            self.base_dir,
+            index.start_date.replace("-", ""),
+            index.end_date.replace("-", ""),
+            BRANCH_INDEX_SUFFIX
+        );
+        write_atomic(
+            Path::new(&file_name),
+            format_branch_index(index).as_bytes(),
+            "the branch index",
+        )?;
+        println!("Branch index saved to: {}", file_name);
+
+        Ok(file_name)
+    }
+}
+
+impl DiaryStorageImpl {
+    fn file_name(&self, start_date: &str, end_date: &str, branch: Option<&str>) -> String {
+        let branch = branch
+            .map(|branch| format!("-branch-{}", slug(branch)))
+            .unwrap_or_default();
         format!(
-            "# Git Diary ({} – {})\n\n## Commit Logs\n\n{}\n\n## AI-generated Summary\n\n{}\n",
-            content.start_date, content.end_date, commit_logs, content.summary
+            "{}/git-diary-{}-to-{}{}.{}",
+            self.base_dir,
+            start_date.replace("-", ""),
+            end_date.replace("-", ""),
+            branch,
+            self.formatter.extension()
         )
     }
 }
 
+impl OutputCheck for DiaryStorageImpl {
+    /// Creates the diary directory, writes a probe file to it and checks the
+    /// diary's file name is one the file system takes
+    fn check_output(&self, start_date: &str, end_date: &str, branch: Option<String>) -> Result<()> {
+        let diary_dir = Path::new(&self.base_dir);
+        fs::create_dir_all(diary_dir).with_context(|| {
+            format!(
+                "Can't create the diary directory {}; create it yourself or pick another with --output",
+                diary_dir.display()
+            )
+        })?;
+        let probe = diary_dir.join(WRITE_PROBE);
+        File::create(&probe)
+            .and_then(|file| {
+                drop(file);
+                fs::remove_file(&probe)
+            })
+            .with_context(|| {
+                format!(
+                    "Can't write to the diary directory {}; fix its permissions or pick another with --output",
+                    diary_dir.display()
+                )
+            })?;
+
+        let file_name = self.file_name(start_date, end_date, branch.as_deref());
+        let path = Path::new(&file_name);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let invalid = path.parent() != Some(diary_dir)
+            || name.len() > MAX_FILE_NAME_BYTES
+            || name.chars().any(|c| {
+                c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*')
+            });
+        if invalid {
+            bail!(
+                "The diary would be saved as {:?}, which isn't a valid file name; use plain dates like 2024-05-07",
+                file_name
+            );
+        }
+        if path.is_dir() {
+            bail!(
+                "{} is a directory; move it away so the diary can be saved there",
+                path.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Storage that remembers every file it saved, so a run that is stopped
+/// halfway can remove the diaries it already wrote
+pub struct TrackedStorage {
+    inner: Arc<dyn DiaryStorage>,
+    saved: Mutex<Vec<String>>,
+}
+
+impl TrackedStorage {
+    pub fn new(inner: Arc<dyn DiaryStorage>) -> Self {
+        Self {
+            inner,
+            saved: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Paths saved so far, oldest first
+    pub fn saved(&self) -> Vec<String> {
+        self.saved.lock().unwrap().clone()
+    }
+
+    fn track(&self, saved: Result<String>) -> Result<String> {
+        if let Ok(path) = &saved {
+            self.saved.lock().unwrap().push(path.clone());
+        }
+        saved
+    }
+}
+
+impl DiaryStorage for TrackedStorage {
+    fn save_diary(&self, content: &DiaryContent) -> Result<String> {
+        self.track(self.inner.save_diary(content))
+    }
+
+    fn generate_file_name(&self, content: &DiaryContent) -> String {
+        self.inner.generate_file_name(content)
+    }
+
+    fn format_content(&self, content: &DiaryContent) -> String {
+        self.inner.format_content(content)
+    }
+
+    fn save_branch_index(&self, index: &BranchIndex) -> Result<String> {
+        self.track(self.inner.save_branch_index(index))
+    }
+}
+
+/// Why a write to the diary directory failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteFailure {
+    /// The directory or file may not be written, or the file system is read-only
+    Permission,
+    /// The network file system holding the directory, such as NFS or SMB,
+    /// went away or timed out
+    Network,
+    /// The local disk reported an I/O error
+    Io,
+    /// The disk is full, or anything else
+    Other,
+}
+
+#[cfg(unix)]
+/// File system types of network mounts, as `/proc/mounts` names them
+const NETWORK_FILE_SYSTEMS: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "fuse.sshfs",
+    "davfs",
+];
+
+impl WriteFailure {
+    /// Why writing `path` failed with `error`
+    pub fn classify(error: &io::Error, path: &Path) -> Self {
+        match error.kind() {
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => Self::Permission,
+            ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::TimedOut
+            | ErrorKind::NotConnected
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::HostUnreachable
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::NetworkDown => Self::Network,
+            // EIO, which NFS and CIFS clients report when the server stops
+            // answering, but a local disk when it is failing
+            #[cfg(unix)]
+            _ if error.raw_os_error() == Some(5) => {
+                if on_network_mount(path) {
+                    Self::Network
+                } else {
+                    Self::Io
+                }
+            }
+            _ => Self::Other,
+        }
+    }
+}
+
+#[cfg(unix)]
+/// Whether `path` is on a network file system; only Linux is checked, through
+/// `/proc/mounts`, so elsewhere nothing is
+fn on_network_mount(path: &Path) -> bool {
+    let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+        return false;
+    };
+    // The file may not exist yet, so the nearest directory that does stands in
+    let path = path
+        .ancestors()
+        .find_map(|dir| dir.canonicalize().ok())
+        .unwrap_or_else(|| path.to_path_buf());
+    is_network_mount(&mounts, &path)
+}
+
+#[cfg(unix)]
+/// Whether the deepest mount in `mounts`, in `/proc/mounts` format, holding
+/// `path` is a network file system
+fn is_network_mount(mounts: &str, path: &Path) -> bool {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            let file_system = fields.next()?;
+            Some((PathBuf::from(mount_point), file_system))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .is_some_and(|(_, file_system)| NETWORK_FILE_SYSTEMS.contains(&file_system))
+}
+
+/// Writes `contents` to `path` through a temporary file in the same
+/// directory, flushed to disk before it replaces the old file, so a write cut
+/// off on a network mount never leaves a half-written file behind
+///
+/// A symlinked `path` is written at the file it points to, keeping the link.
+pub fn write_atomic(path: &Path, contents: &[u8], what: &str) -> Result<()> {
+    let target = follow_symlinks(path);
+    let file_name = target
+        .file_name()
+        .with_context(|| format!("{} has no file name", target.display()))?;
+    // The temporary file sits next to the target, as renames across file
+    // systems aren't atomic
+    let temp_path = target.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    // The new file keeps the old one's permissions, such as a private diary's
+    let permissions = fs::metadata(&target).ok().map(|old| old.permissions());
+    let written = File::create(&temp_path)
+        .and_then(|mut file| {
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, &target));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(explain_write_error(e, what, &target));
+    }
+
+    // Persist the rename too; not every platform or file system can sync a directory
+    if let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
+/// The file `path` ends up at through any chain of symlinks, which may not exist yet
+fn follow_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINK_HOPS {
+        let Ok(link) = fs::read_link(&path) else {
+            break;
+        };
+        path = match path.parent() {
+            Some(dir) if link.is_relative() => dir.join(link),
+            _ => link,
+        };
+    }
+    path
+}
+
+fn explain_write_error(error: io::Error, what: &str, path: &Path) -> anyhow::Error {
+    let message = match WriteFailure::classify(&error, path) {
+        WriteFailure::Permission => format!(
+            "Permission denied writing {} to {}; fix the directory's permissions or pick another with --output",
+            what,
+            path.display()
+        ),
+        WriteFailure::Network => format!(
+            "The network file system holding {} stopped responding while writing {}; \
+             the old file was left as it was, so retry once the mount is back",
+            path.display(),
+            what
+        ),
+        WriteFailure::Io => format!(
+            "I/O error writing {} to {}; the disk may be failing, and the old file was left as it was",
+            what,
+            path.display()
+        ),
+        WriteFailure::Other => format!("Failed to write {} to {}", what, path.display()),
+    };
+    anyhow::Error::new(error).context(message)
+}
+
+/// Turns a branch name into a file name part: `feature/sync` becomes `feature-sync`
+fn slug(branch: &str) -> String {
+    branch
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::domain::Commit;
+    use crate::domain::{BranchDiary, Commit, CommitOrder};
     use std::fs;
     use tempfile::TempDir;
 
@@ -104,6 +549,23 @@ mod tests {
             summary: "Test summary".to_string(),
             start_date: "2024-01-01".to_string(),
             end_date: "2024-01-07".to_string(),
+            rollup: None,
+            notes: Vec::new(),
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::default(),
         }
     }
 
@@ -117,6 +579,23 @@ mod tests {
             summary: "Test".to_string(),
             start_date: "2024-01-01".to_string(),
             end_date: "2024-01-07".to_string(),
+            rollup: None,
+            notes: Vec::new(),
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::default(),
         };
 
         let file_name = storage.generate_file_name(&content);
@@ -128,6 +607,23 @@ mod tests {
             summary: "Test".to_string(),
             start_date: "2024/01/01".to_string(),
             end_date: "2024/01/07".to_string(),
+            rollup: None,
+            notes: Vec::new(),
+            pull_requests: Vec::new(),
+            translations: Vec::new(),
+            context: Vec::new(),
+            branch: None,
+            reviews: Vec::new(),
+            tickets: Vec::new(),
+            model: None,
+            health: None,
+            sessions: Vec::new(),
+            work_hours: None,
+            coding_time: None,
+            attachments: Vec::new(),
+            performance: Vec::new(),
+            topics: Vec::new(),
+            commit_order: CommitOrder::default(),
         };
 
         let file_name_2 = storage.generate_file_name(&content_2);
@@ -137,12 +633,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_check_output() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let diary_dir = temp_dir.path().join("diaries");
+        let storage = DiaryStorageImpl::new(diary_dir.to_string_lossy().to_string());
+
+        storage.check_output("2024-01-01", "2024-01-07", Some("feature/sync".to_string()))?;
+        assert!(diary_dir.is_dir());
+        assert_eq!(fs::read_dir(&diary_dir)?.count(), 0);
+
+        let error = storage
+            .check_output("2024/01/01", "2024/01/07", None)
+            .unwrap_err();
+        assert!(error.to_string().contains("isn't a valid file name"));
+
+        // The directory can't be created below a file
+        fs::write(temp_dir.path().join("file"), "")?;
+        let storage = DiaryStorageImpl::new(
+            temp_dir
+                .path()
+                .join("file/diaries")
+                .to_string_lossy()
+                .to_string(),
+        );
+        let error = storage
+            .check_output("2024-01-01", "2024-01-07", None)
+            .unwrap_err();
+        assert!(error.to_string().contains("--output"));
+
+        Ok(())
+    }
+
     #[test]
     fn test_diary_storage_markdown_format() {
         let storage = DiaryStorageImpl::new("test".to_string());
         let content = create_test_diary_content();
 
-        let markdown = storage.format_markdown_content(&content);
+        let markdown = storage.format_content(&content);
 
         // Check markdown structure
         assert!(markdown.starts_with("# Git Diary"));
@@ -187,6 +715,121 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_permissions() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("diary.md");
+        fs::write(&path, "first")?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+        write_atomic(&path, b"second", "the diary")?;
+
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_network_mount() {
+        let mounts = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                      server:/export /mnt/diaries nfs4 rw 0 0\n\
+                      /dev/sdb1 /mnt/diaries/local ext4 rw 0 0\n\
+                      //nas/share /mnt/my\\040share cifs rw 0 0\n";
+
+        assert!(is_network_mount(mounts, Path::new("/mnt/diaries/a.md")));
+        assert!(is_network_mount(mounts, Path::new("/mnt/my share/a.md")));
+        assert!(!is_network_mount(
+            mounts,
+            Path::new("/mnt/diaries/local/a.md")
+        ));
+        assert!(!is_network_mount(mounts, Path::new("/home/me/a.md")));
+        assert!(!is_network_mount(
+            mounts,
+            Path::new("/mnt/diaries-old/a.md")
+        ));
+    }
+
+    #[test]
+    fn test_write_atomic() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("diary.md");
+
+        write_atomic(&path, b"first", "the diary")?;
+        write_atomic(&path, b"second", "the diary")?;
+        assert_eq!(fs::read_to_string(&path)?, "second");
+        // No temporary file is left behind
+        assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+        let error =
+            write_atomic(&temp_dir.path().join("missing/diary.md"), b"x", "the diary").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Failed to write the diary to"));
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_keeps_symlinks() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let mounted = temp_dir.path().join("mounted");
+        fs::create_dir(&mounted)?;
+        fs::write(mounted.join("diary.md"), "old")?;
+        std::os::unix::fs::symlink(&mounted, temp_dir.path().join("diaries"))?;
+        std::os::unix::fs::symlink("diary.md", mounted.join("latest.md"))?;
+
+        write_atomic(
+            &temp_dir.path().join("diaries/latest.md"),
+            b"new",
+            "the diary",
+        )?;
+
+        assert!(fs::symlink_metadata(mounted.join("latest.md"))?
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(mounted.join("diary.md"))?, "new");
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_failure_classify() {
+        let local = Path::new("/");
+        let kind = |kind: ErrorKind| WriteFailure::classify(&io::Error::from(kind), local);
+        assert_eq!(kind(ErrorKind::PermissionDenied), WriteFailure::Permission);
+        assert_eq!(
+            kind(ErrorKind::StaleNetworkFileHandle),
+            WriteFailure::Network
+        );
+        assert_eq!(kind(ErrorKind::TimedOut), WriteFailure::Network);
+        assert_eq!(kind(ErrorKind::StorageFull), WriteFailure::Other);
+
+        let error = explain_write_error(
+            io::Error::from(ErrorKind::PermissionDenied),
+            "the diary",
+            Path::new("/mnt/diaries/a.md"),
+        );
+        assert!(error
+            .to_string()
+            .starts_with("Permission denied writing the diary"));
+    }
+
+    #[test]
+    fn test_tracked_storage() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let base_dir = temp_dir.path().to_string_lossy().to_string();
+        let storage = TrackedStorage::new(Arc::new(DiaryStorageImpl::new(base_dir)));
+        assert!(storage.saved().is_empty());
+
+        let path = storage.save_diary(&create_test_diary_content())?;
+        assert_eq!(storage.saved(), vec![path]);
+
+        Ok(())
+    }
+
     #[test]
     fn test_diary_storage_create_directory() -> Result<()> {
         let temp_dir = TempDir::new()?;
@@ -208,5 +851,149 @@ mod tests {
 
         Ok(())
     }
-}
 
+    #[test]
+    fn test_diary_storage_list_diaries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let base_dir = temp_dir.path().to_string_lossy().to_string();
+        let storage = DiaryStorageImpl::new(base_dir.clone());
+
+        // A missing directory has no diaries
+        assert!(DiaryStorageImpl::new(format!("{}/missing", base_dir))
+            .list_diaries()?
+            .is_empty());
+
+        storage.save_diary(&create_test_diary_content())?;
+        fs::write(temp_dir.path().join("notes.txt"), "not a diary")?;
+
+        let diaries = storage.list_diaries()?;
+        assert_eq!(diaries.len(), 1);
+        assert!(diaries[0].ends_with("git-diary-20240101-to-20240107.md"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diary_storage_sidecar_json() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let base_dir = temp_dir.path().to_string_lossy().to_string();
+
+        DiaryStorageImpl::new(base_dir.clone()).save_diary(&create_test_diary_content())?;
+        let sidecar_path = temp_dir.path().join("git-diary-20240101-to-20240107.json");
+        assert!(!sidecar_path.exists());
+
+        DiaryStorageImpl::new(base_dir)
+            .with_sidecar_json(true)
+            .save_diary(&create_test_diary_content())?;
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sidecar_path)?)?;
+        assert_eq!(json["summary"], "Test summary");
+        assert_eq!(json["stats"]["commits"], 2);
+
+        // The sidecar isn't listed as a diary
+        assert_eq!(
+            DiaryStorageImpl::new(temp_dir.path().to_string_lossy().to_string())
+                .list_diaries()?
+                .len(),
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diary_storage_prune_apis() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let base_dir = temp_dir.path().to_string_lossy().to_string();
+        let storage = DiaryStorageImpl::new(base_dir.clone()).with_sidecar_json(true);
+
+        storage.save_diary(&create_test_diary_content())?;
+        fs::write(temp_dir.path().join("git-diary-notes.md"), "not a diary")?;
+        fs::write(temp_dir.path().join(".notes.json"), "{}")?;
+
+        let diaries = storage.stored_diaries()?;
+        let names: Vec<String> = diaries
+            .iter()
+            .map(|diary| {
+                diary
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "git-diary-20240101-to-20240107.json",
+                "git-diary-20240101-to-20240107.md"
+            ]
+        );
+        assert_eq!(
+            diaries[0].start_date,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()
+        );
+        assert_eq!(
+            diaries[0].end_date,
+            NaiveDate::from_ymd_opt(2024, 1, 7).unwrap()
+        );
+
+        let archived = storage.archive_diary(&diaries[1])?;
+        assert!(archived.ends_with("archive/git-diary-20240101-to-20240107.md"));
+        assert!(archived.exists());
+        storage.delete_diary(&diaries[0])?;
+
+        // Archived diaries are out of the way of later runs
+        assert!(storage.stored_diaries()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diary_storage_with_formatter() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let base_dir = temp_dir.path().to_string_lossy().to_string();
+        let storage = DiaryStorageImpl::new(base_dir.clone())
+            .with_formatter(Box::new(crate::formatter::ConfluenceFormatter));
+
+        let file_path = storage.save_diary(&create_test_diary_content())?;
+
+        assert!(file_path.ends_with("git-diary-20240101-to-20240107.confluence"));
+        assert!(fs::read_to_string(&file_path)?.starts_with("h1. Git Diary"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diary_storage_branch_diaries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let storage = DiaryStorageImpl::new(temp_dir.path().to_string_lossy().to_string());
+        let mut content = create_test_diary_content();
+        content.branch = Some("feature/sync".to_string());
+
+        let path = storage.save_diary(&content)?;
+        assert!(path.ends_with("git-diary-20240101-to-20240107-branch-feature-sync.md"));
+
+        let index = storage.save_branch_index(&BranchIndex {
+            start_date: "2024-01-01".to_string(),
+            end_date: "2024-01-07".to_string(),
+            diaries: vec![BranchDiary {
+                branch: "feature/sync".to_string(),
+                path,
+                commits: 2,
+            }],
+        })?;
+        assert!(fs::read_to_string(&index)?
+            .contains("[feature/sync](git-diary-20240101-to-20240107-branch-feature-sync.md)"));
+
+        // The index isn't a diary, but both are pruned with the period
+        assert_eq!(storage.list_diaries()?.len(), 1);
+        let stored = storage.stored_diaries()?;
+        assert_eq!(stored.len(), 2);
+        assert!(stored
+            .iter()
+            .all(|diary| diary.end_date == NaiveDate::from_ymd_opt(2024, 1, 7).unwrap()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Most topic tags kept for a diary
+pub const MAX_TOPICS: usize = 5;
+
+/// Parses JSON a model wrote, repairing the mistakes of models without
+/// structured output support
+///
+/// Reads the first object or array in `raw`, so Markdown fences and prose
+/// around it don't matter, drops trailing commas, and closes strings and
+/// brackets left open when the output was cut off at `max_tokens`, giving up
+/// members that were cut off halfway.
+pub fn repair_json(raw: &str) -> Option<Value> {
+    let start = raw.find(['{', '['])?;
+    let scanned = Scanned::of(&raw[start..]);
+    if scanned.open.is_empty() {
+        return serde_json::from_str(&scanned.json).ok();
+    }
+
+    let mut cut = scanned.json.as_str();
+    loop {
+        if let Some(value) = Scanned::of(cut).closed() {
+            return Some(value);
+        }
+        cut = &cut[..cut.rfind(',')?];
+    }
+}
+
+/// [`repair_json`] into `T`; `None` when nothing usable could be read
+pub fn parse_structured<T: DeserializeOwned>(raw: &str) -> Option<T> {
+    serde_json::from_value(repair_json(raw)?).ok()
+}
+
+/// The first JSON value in a text, as far as it goes
+struct Scanned {
+    /// Copy of the value without trailing commas
+    json: String,
+    /// Closing brackets still owed, innermost last
+    open: Vec<char>,
+    in_string: bool,
+}
+
+impl Scanned {
+    fn of(text: &str) -> Self {
+        let mut scanned = Self {
+            json: String::new(),
+            open: Vec::new(),
+            in_string: false,
+        };
+        let mut escaped = false;
+        for c in text.chars() {
+            if scanned.in_string {
+                scanned.json.push(c);
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => scanned.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match c {
+                '"' => scanned.in_string = true,
+                '{' => scanned.open.push('}'),
+                '[' => scanned.open.push(']'),
+                '}' | ']' => {
+                    strip_trailing_comma(&mut scanned.json);
+                    // A stray bracket ends the value; parsing it reports the error
+                    if scanned.open.pop() != Some(c) {
+                        scanned.open.clear();
+                    }
+                }
+                _ => {}
+            }
+            scanned.json.push(c);
+            if scanned.open.is_empty() && matches!(c, '}' | ']') {
+                break;
+            }
+        }
+        scanned
+    }
+
+    /// Parses the value with whatever is still open closed
+    fn closed(mut self) -> Option<Value> {
+        if self.in_string {
+            if self.json.ends_with('\\') {
+                self.json.pop();
+            }
+            self.json.push('"');
+        }
+        strip_trailing_comma(&mut self.json);
+        self.json.extend(self.open.iter().rev());
+        serde_json::from_str(&self.json).ok()
+    }
+}
+
+fn strip_trailing_comma(json: &mut String) {
+    let kept = json.trim_end().trim_end_matches(',').len();
+    json.truncate(kept);
+}
+
+#[derive(Deserialize)]
+struct TopicsOutput {
+    topics: Vec<String>,
+}
+
+/// Splits the topic tags the summary was asked to end with off it
+///
+/// The tags are read as JSON, repaired if need be, or from a plain
+/// `Topics: a, b` line for models that ignore the format. A summary with
+/// neither is kept whole and has no topics.
+pub fn split_topics(summary: &str) -> (String, Vec<String>) {
+    let lines: Vec<&str> = summary.trim_end().lines().collect();
+    let Some(last) = lines.last() else {
+        return (String::new(), Vec::new());
+    };
+
+    if let Some(mut start) = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with('{'))
+    {
+        if let Some(output) = parse_structured::<TopicsOutput>(&lines[start..].join("\n")) {
+            // Take the fence opening the block along
+            if start > 0 && lines[start - 1].trim_start().starts_with("```") {
+                start -= 1;
+            }
+            let body = lines[..start].join("\n").trim_end().to_string();
+            return (body, normalize_topics(output.topics));
+        }
+    }
+
+    let plain = last.trim().trim_matches(['*', '_']);
+    let label = plain.split_once(':').filter(|(label, _)| {
+        let label = label.trim_matches(['*', '_', ' ']).to_lowercase();
+        label == "topics" || label == "tags"
+    });
+    match label {
+        Some((_, topics)) => {
+            let topics = topics
+                .trim_matches(['*', '_'])
+                .split(',')
+                .map(str::to_string);
+            let body = lines[..lines.len() - 1].join("\n").trim_end().to_string();
+            (body, normalize_topics(topics.collect()))
+        }
+        None => (summary.to_string(), Vec::new()),
+    }
+}
+
+/// Lowercases topics and drops `#` marks, blanks and repeats
+fn normalize_topics(topics: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for topic in topics {
+        let topic = topic.trim().trim_start_matches('#').trim().to_lowercase();
+        if !topic.is_empty() && !normalized.contains(&topic) {
+            normalized.push(topic);
+        }
+    }
+    normalized.truncate(MAX_TOPICS);
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repair_json() {
+        assert_eq!(
+            repair_json("Sure! Here you go:\n```json\n{\"topics\": [\"ci\", \"billing\",],}\n```"),
+            Some(json!({"topics": ["ci", "billing"]}))
+        );
+        // Cut off at max_tokens
+        assert_eq!(
+            repair_json("{\"topics\": [\"ci\", \"bill"),
+            Some(json!({"topics": ["ci", "bill"]}))
+        );
+        assert_eq!(
+            repair_json("{\"a\": 1, \"b\": {\"c\": [1, 2], \"d\""),
+            Some(json!({"a": 1, "b": {"c": [1, 2]}}))
+        );
+        assert_eq!(repair_json("[1, 2] and [3]"), Some(json!([1, 2])));
+        assert_eq!(repair_json("no JSON here"), None);
+        assert_eq!(repair_json("{\"a\": }"), None);
+    }
+
+    #[test]
+    fn test_split_topics() {
+        assert_eq!(
+            split_topics("Shipped the exporter.\n\n```json\n{\"topics\": [\"Export\", \"#ci\", \"export\"]}\n```"),
+            (
+                "Shipped the exporter.".to_string(),
+                vec!["export".to_string(), "ci".to_string()]
+            )
+        );
+        // Models that ignore the format
+        assert_eq!(
+            split_topics("Shipped the exporter.\n**Topics:** export, ci"),
+            (
+                "Shipped the exporter.".to_string(),
+                vec!["export".to_string(), "ci".to_string()]
+            )
+        );
+        assert_eq!(
+            split_topics("Shipped the exporter.\n{\"topics\": [\"exp"),
+            ("Shipped the exporter.".to_string(), vec!["exp".to_string()])
+        );
+        assert_eq!(
+            split_topics("Shipped the exporter."),
+            ("Shipped the exporter.".to_string(), Vec::new())
+        );
+    }
+}
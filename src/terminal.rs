@@ -0,0 +1,192 @@
+/// ANSI escape sequences used when rendering
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Width of the line `---` is drawn as
+const RULE_WIDTH: usize = 60;
+
+/// Renders a diary's Markdown for the terminal: headings, bullets, numbered
+/// lists, bold, italics, inline code, links, fences and rules
+///
+/// Without `styled`, such as when the output isn't a terminal or `NO_COLOR`
+/// is set, the same layout is produced without escape sequences.
+pub fn render_markdown(markdown: &str, styled: bool) -> String {
+    let mut output = Vec::new();
+    let mut in_code_block = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            output.push(format!("    {}", paint(styled, DIM, line)));
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        let heading_level = trimmed.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&heading_level) && trimmed[heading_level..].starts_with(' ') {
+            let title = inline(trimmed[heading_level..].trim(), styled);
+            if heading_level == 1 {
+                output.push(paint(styled, &format!("{}{}", BOLD, UNDERLINE), &title));
+            } else if heading_level == 2 {
+                output.push(paint(styled, &format!("{}{}", BOLD, CYAN), &title));
+            } else {
+                output.push(paint(styled, BOLD, &title));
+            }
+        } else if is_rule(trimmed) {
+            output.push(paint(styled, DIM, &"─".repeat(RULE_WIDTH)));
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+        {
+            output.push(format!("{}  • {}", indent, inline(item, styled)));
+        } else if let Some((number, item)) = numbered(trimmed) {
+            output.push(format!("{}  {}. {}", indent, number, inline(item, styled)));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            output.push(format!(
+                "  {} {}",
+                paint(styled, DIM, "│"),
+                paint(styled, ITALIC, &inline(quote.trim(), styled))
+            ));
+        } else {
+            output.push(format!("{}{}", indent, inline(trimmed, styled)));
+        }
+    }
+
+    let mut rendered = output.join("\n");
+    rendered.push('\n');
+    rendered
+}
+
+fn paint(styled: bool, style: &str, text: &str) -> String {
+    if styled {
+        format!("{}{}{}", style, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+fn is_rule(line: &str) -> bool {
+    let line = line.trim_end();
+    line.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&marker| line.chars().all(|c| c == marker))
+}
+
+fn numbered(line: &str) -> Option<(&str, &str)> {
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    Some((&line[..digits], line[digits..].strip_prefix(". ")?))
+}
+
+/// Renders `**bold**`, `*italic*`, `` `code` `` and `[text](url)`
+fn inline(text: &str, styled: bool) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("**") {
+            if let Some(end) = after.find("**") {
+                output.push_str(&paint(styled, BOLD, &after[..end]));
+                rest = &after[end + 2..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('`') {
+            if let Some(end) = after.find('`') {
+                output.push_str(&paint(styled, YELLOW, &after[..end]));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('*') {
+            if let Some(end) = after.find('*').filter(|&end| end > 0) {
+                output.push_str(&paint(styled, ITALIC, &after[..end]));
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        if let Some(after) = rest.strip_prefix('[') {
+            let link = after.split_once("](").and_then(|(label, tail)| {
+                let (url, tail) = tail.split_once(')')?;
+                Some((label, url, tail))
+            });
+            if let Some((label, url, tail)) = link.filter(|(label, ..)| !label.contains(']')) {
+                output.push_str(&paint(styled, UNDERLINE, label));
+                output.push_str(&paint(styled, DIM, &format!(" ({})", url)));
+                rest = tail;
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        output.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIARY: &str = "# Git Diary (2024-01-01 – 2024-01-01)
+
+## Commit Logs
+
+- 2024-01-01 09:00:00: feat: Add **export**
+  * nested with `code`
+1. First *step*
+
+---
+> Quoted [docs](https://example.com/docs)
+
+```
+let x = 1;
+```
+";
+
+    #[test]
+    fn test_render_markdown_plain() {
+        assert_eq!(
+            render_markdown(DIARY, false),
+            format!(
+                "Git Diary (2024-01-01 – 2024-01-01)
+
+Commit Logs
+
+  • 2024-01-01 09:00:00: feat: Add export
+    • nested with code
+  1. First step
+
+{}
+  │ Quoted docs (https://example.com/docs)
+
+    let x = 1;
+",
+                "─".repeat(RULE_WIDTH)
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_styled() {
+        let rendered = render_markdown(DIARY, true);
+        assert!(rendered.starts_with("\x1b[1m\x1b[4mGit Diary"));
+        assert!(rendered.contains("\x1b[1m\x1b[36mCommit Logs\x1b[0m"));
+        assert!(rendered.contains("Add \x1b[1mexport\x1b[0m"));
+        assert!(rendered.contains("\x1b[33mcode\x1b[0m"));
+        assert!(rendered.contains("First \x1b[3mstep\x1b[0m"));
+        // A lone asterisk is left alone
+        assert_eq!(inline("2 * 3", true), "2 * 3");
+    }
+}
@@ -0,0 +1,360 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+/// A `<testcase>` element, self-closing or with its children
+static TEST_CASE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?s)<testcase\b([^>]*?)(?:/>|>(.*?)</testcase>)").expect("valid test case pattern")
+});
+
+/// An XML attribute such as `name="parses_dates"`
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([\w:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("valid attribute pattern")
+});
+
+/// Most test names listed for fixed or failing tests
+const MAX_NAMED_TESTS: usize = 3;
+
+/// How a test ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TestOutcome {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// One test of a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestCase {
+    /// `classname::name`, or the name alone when the report gives no class
+    pub name: String,
+    pub outcome: TestOutcome,
+}
+
+/// The tests of one JUnit report, such as from `cargo nextest` or `pytest --junitxml`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRun {
+    /// When the report was written, as a Unix timestamp
+    pub finished_at: i64,
+    pub cases: Vec<TestCase>,
+}
+
+impl TestRun {
+    pub fn failed(&self) -> impl Iterator<Item = &TestCase> {
+        self.cases
+            .iter()
+            .filter(|case| case.outcome == TestOutcome::Failed)
+    }
+}
+
+/// Reads the test cases of a JUnit XML report written at `finished_at`
+///
+/// A test case with a `<failure>` or `<error>` failed, one with `<skipped>`
+/// was skipped, and any other passed.
+pub fn parse_junit(xml: &str, finished_at: i64) -> Result<TestRun> {
+    if !xml.contains("<testsuite") {
+        bail!("Not a JUnit report: no <testsuite> element");
+    }
+
+    let cases = TEST_CASE
+        .captures_iter(xml)
+        .map(|captures| {
+            let attributes = &captures[1];
+            let children = captures.get(2).map_or("", |children| children.as_str());
+            let name = attribute(attributes, "name").unwrap_or_default();
+            let name = match attribute(attributes, "classname") {
+                Some(class) if !class.is_empty() => format!("{}::{}", class, name),
+                _ => name,
+            };
+            let outcome = if children.contains("<failure") || children.contains("<error") {
+                TestOutcome::Failed
+            } else if children.contains("<skipped") {
+                TestOutcome::Skipped
+            } else {
+                TestOutcome::Passed
+            };
+            TestCase { name, outcome }
+        })
+        .collect();
+    Ok(TestRun { finished_at, cases })
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    ATTRIBUTE
+        .captures_iter(attributes)
+        .find(|captures| &captures[1] == name)
+        .map(|captures| {
+            let value = captures
+                .get(2)
+                .or(captures.get(3))
+                .map_or("", |v| v.as_str());
+            unescape(value)
+        })
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// How the test suite fared over a diary period's test runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestHealth {
+    pub runs: usize,
+    /// Tests in the last run
+    pub total: usize,
+    /// Tests failing in the last run
+    pub failing: Vec<String>,
+    /// Tests that failed in an earlier run and passed in the last one they ran in
+    pub fixed: Vec<String>,
+    /// Tests that passed in an earlier run and fail in the last one
+    pub broken: Vec<String>,
+}
+
+impl TestHealth {
+    /// Compares `runs`, in any order; `None` without runs
+    pub fn from_runs(runs: &[TestRun]) -> Option<Self> {
+        let mut runs: Vec<&TestRun> = runs.iter().collect();
+        runs.sort_by_key(|run| run.finished_at);
+        let last = *runs.last()?;
+
+        let mut fixed = Vec::new();
+        let mut broken = Vec::new();
+        // The outcome of each test in the last run that included it
+        let mut latest: Vec<(&str, TestOutcome, bool)> = Vec::new();
+        for run in &runs {
+            for case in &run.cases {
+                match latest.iter_mut().find(|(name, ..)| *name == case.name) {
+                    Some((_, outcome, failed_before)) => {
+                        *failed_before |= *outcome == TestOutcome::Failed;
+                        *outcome = case.outcome;
+                    }
+                    None => latest.push((&case.name, case.outcome, false)),
+                }
+            }
+        }
+        for (name, outcome, failed_before) in latest {
+            let passed_before = runs[..runs.len() - 1].iter().any(|run| {
+                run.cases
+                    .iter()
+                    .any(|case| case.name == name && case.outcome == TestOutcome::Passed)
+            });
+            match outcome {
+                TestOutcome::Passed if failed_before => fixed.push(name.to_string()),
+                TestOutcome::Failed
+                    if passed_before && last.cases.iter().any(|case| case.name == name) =>
+                {
+                    broken.push(name.to_string())
+                }
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            runs: runs.len(),
+            total: last.cases.len(),
+            failing: last.failed().map(|case| case.name.clone()).collect(),
+            fixed,
+            broken,
+        })
+    }
+
+    pub fn is_green(&self) -> bool {
+        self.failing.is_empty()
+    }
+}
+
+impl Display for TestHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![format!(
+            "{} test run{}",
+            self.runs,
+            if self.runs == 1 { "" } else { "s" }
+        )];
+        if !self.fixed.is_empty() {
+            parts.push(format!(
+                "fixed {} failing test(s) ({})",
+                self.fixed.len(),
+                named(&self.fixed)
+            ));
+        }
+        if !self.broken.is_empty() {
+            parts.push(format!(
+                "{} test(s) started failing ({})",
+                self.broken.len(),
+                named(&self.broken)
+            ));
+        }
+        if self.is_green() {
+            parts.push(format!("the suite is green ({} tests)", self.total));
+        } else {
+            parts.push(format!(
+                "{} of {} tests still fail ({})",
+                self.failing.len(),
+                self.total,
+                named(&self.failing)
+            ));
+        }
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+fn named(tests: &[String]) -> String {
+    let mut shown = tests[..tests.len().min(MAX_NAMED_TESTS)].join(", ");
+    if tests.len() > MAX_NAMED_TESTS {
+        shown.push_str(&format!(" and {} more", tests.len() - MAX_NAMED_TESTS));
+    }
+    shown
+}
+
+/// JUnit reports written in the repository, found by globs such as
+/// `target/nextest/**/junit.xml`
+#[cfg(feature = "fs")]
+pub struct JunitReports {
+    root: std::path::PathBuf,
+    globs: Vec<String>,
+}
+
+#[cfg(feature = "fs")]
+impl JunitReports {
+    /// `globs` are relative to `root`, usually the repository
+    pub fn new(root: std::path::PathBuf, globs: Vec<String>) -> Self {
+        Self { root, globs }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl crate::domain::TestRunSource for JunitReports {
+    /// Reports last written between `since` and `until`; unreadable ones
+    /// only warrant a warning, as test tools leave partial files behind
+    fn test_runs_between(&self, since: i64, until: i64) -> Result<Vec<TestRun>> {
+        use anyhow::Context;
+
+        let mut runs = Vec::new();
+        for pattern in &self.globs {
+            let pattern = self.root.join(pattern);
+            let paths = glob::glob(&pattern.to_string_lossy())
+                .with_context(|| format!("Invalid test report glob {:?}", pattern))?;
+            for path in paths.flatten() {
+                let modified = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+                    .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|age| age.as_secs() as i64);
+                let Some(finished_at) = modified.filter(|at| (since..until).contains(at)) else {
+                    continue;
+                };
+                let report = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|xml| parse_junit(&xml, finished_at));
+                match report {
+                    Ok(run) => runs.push(run),
+                    Err(e) => println!("⚠️ Skipped test report {}: {}", path.display(), e),
+                }
+            }
+        }
+        Ok(runs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEXTEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="nextest-run" tests="3" failures="1" errors="0">
+    <testsuite name="app" tests="3" disabled="0" errors="0" failures="1">
+        <testcase name="parses_dates" classname="app::parser" time="0.001"/>
+        <testcase name="exports_csv" classname="app::export" time="0.002">
+            <failure type="test failure">assertion failed: &lt;left == right&gt;</failure>
+        </testcase>
+        <testcase name="needs_network" classname="app::sync" time="0.000"><skipped/></testcase>
+    </testsuite>
+</testsuites>"#;
+
+    fn run(finished_at: i64, cases: &[(&str, TestOutcome)]) -> TestRun {
+        TestRun {
+            finished_at,
+            cases: cases
+                .iter()
+                .map(|(name, outcome)| TestCase {
+                    name: name.to_string(),
+                    outcome: *outcome,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_junit() -> Result<()> {
+        assert_eq!(
+            parse_junit(NEXTEST, 10)?,
+            run(
+                10,
+                &[
+                    ("app::parser::parses_dates", TestOutcome::Passed),
+                    ("app::export::exports_csv", TestOutcome::Failed),
+                    ("app::sync::needs_network", TestOutcome::Skipped),
+                ]
+            )
+        );
+
+        let pytest = "<testsuites><testsuite name='pytest' tests='1'>\
+            <testcase classname='tests.test_api' name='test_login[a&amp;b]'>\
+            <error message='boom'/></testcase></testsuite></testsuites>";
+        assert_eq!(
+            parse_junit(pytest, 0)?.cases,
+            vec![TestCase {
+                name: "tests.test_api::test_login[a&b]".to_string(),
+                outcome: TestOutcome::Failed,
+            }]
+        );
+        assert!(parse_junit("<html></html>", 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_test_health() {
+        use TestOutcome::*;
+        let runs = [
+            run(
+                30,
+                &[("a", Passed), ("b", Passed), ("c", Passed), ("d", Failed)],
+            ),
+            run(
+                10,
+                &[("a", Failed), ("b", Failed), ("c", Passed), ("d", Passed)],
+            ),
+            run(20, &[("a", Passed), ("b", Failed)]),
+        ];
+
+        let health = TestHealth::from_runs(&runs).unwrap();
+        assert_eq!(health.runs, 3);
+        assert_eq!(health.fixed, vec!["a", "b"]);
+        assert_eq!(health.broken, vec!["d"]);
+        assert_eq!(
+            health.to_string(),
+            "3 test runs; fixed 2 failing test(s) (a, b); 1 test(s) started failing (d); \
+             1 of 4 tests still fail (d)"
+        );
+
+        let green = TestHealth::from_runs(
+            &runs[1..2]
+                .iter()
+                .cloned()
+                .chain([run(40, &[("a", Passed), ("b", Passed)])])
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(
+            green.to_string(),
+            "2 test runs; fixed 2 failing test(s) (a, b); the suite is green (2 tests)"
+        );
+        assert!(TestHealth::from_runs(&[]).is_none());
+    }
+}
@@ -0,0 +1,222 @@
+use anyhow::{bail, Result};
+use chrono::{NaiveDate, NaiveTime, TimeZone};
+
+use crate::domain::{resolve_local, Commit, GitRepository};
+
+/// Context given to the summarizer, whose instructions talk about commits
+pub const TEXT_LOG_CONTEXT: &str =
+    "The commit messages are entries from a plain-text work log, such as meetings, reviews and \
+     research, rather than commits; summarize them as the day's activities.";
+
+/// Time layouts accepted at the start of a log line, after an optional date
+const TIME_FORMATS: &[&str] = &["%H:%M:%S", "%H:%M"];
+
+/// Parses a plain-text work log into activity items, in file order
+///
+/// A line starting with a time such as `09:30`, `[14:05]` or
+/// `2024-05-07 09:30` starts a timestamped block; the lines after it, up to
+/// the next timestamp or blank line, are joined to the same item with `; `,
+/// as diaries list one line per item. Any other
+/// line is an item of its own, at the time of the last timestamp. A line that
+/// is only a date, like `## 2024-05-07`, sets the day for the times below it;
+/// until one does, times fall on `date`. Bullet markers are dropped.
+pub fn parse_text_log<Tz: TimeZone>(text: &str, tz: &Tz, date: NaiveDate) -> Result<Vec<Commit>> {
+    let mut items: Vec<Commit> = Vec::new();
+    let mut date = date;
+    let mut time = NaiveTime::MIN;
+    let mut in_block = false;
+
+    for line in text.lines() {
+        let line = strip_bullet(line.trim());
+        if line.is_empty() {
+            in_block = false;
+            continue;
+        }
+        if let Some(day) = date_heading(line) {
+            date = day;
+            time = NaiveTime::MIN;
+            in_block = false;
+            continue;
+        }
+
+        let text = match split_timestamp(line) {
+            Some((day, at, rest)) => {
+                date = day.unwrap_or(date);
+                time = at;
+                in_block = true;
+                items.push(Commit::new(
+                    String::new(),
+                    resolve_local(tz, date.and_time(time)).timestamp(),
+                ));
+                rest
+            }
+            None if in_block => line,
+            None => {
+                items.push(Commit::new(
+                    String::new(),
+                    resolve_local(tz, date.and_time(time)).timestamp(),
+                ));
+                line
+            }
+        };
+
+        if let Some(item) = items.last_mut() {
+            if !text.is_empty() {
+                if !item.message.is_empty() {
+                    item.message.push_str("; ");
+                }
+                item.message.push_str(text);
+            }
+        }
+    }
+
+    // A timestamp with nothing after it isn't an activity
+    items.retain(|item| !item.message.is_empty());
+    if items.is_empty() {
+        bail!("The log has no activity items");
+    }
+    Ok(items)
+}
+
+/// `- `, `* ` or `+ ` at the start of a line
+fn strip_bullet(line: &str) -> &str {
+    ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+        .map_or(line, str::trim_start)
+}
+
+/// The date of a line that holds nothing else, such as `2024-05-07` or
+/// `## 2024-05-07:`
+fn date_heading(line: &str) -> Option<NaiveDate> {
+    let line = line.trim_start_matches('#').trim().trim_end_matches(':');
+    NaiveDate::parse_from_str(line, "%Y-%m-%d").ok()
+}
+
+/// Splits a leading `[date time]`, `date time` or `time` off `line`,
+/// returning the rest without separators such as ` - ` or `: `
+fn split_timestamp(line: &str) -> Option<(Option<NaiveDate>, NaiveTime, &str)> {
+    let (stamp, rest) = match line.strip_prefix('[') {
+        Some(bracketed) => bracketed.split_once(']')?,
+        None => {
+            // The date and time are the first one or two words
+            let mut words = line.splitn(3, ' ');
+            let first = words.next()?;
+            match (first.len() == 10, words.next()) {
+                (true, Some(second)) if second.contains(':') => {
+                    let end = first.len() + 1 + second.len();
+                    (&line[..end], &line[end..])
+                }
+                _ => (first, &line[first.len()..]),
+            }
+        }
+    };
+
+    let stamp = stamp.trim().trim_end_matches([':', '-']);
+    let (date, time) = match stamp.split_once([' ', 'T']) {
+        Some((date, time)) => (
+            Some(NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?),
+            time,
+        ),
+        None => (None, stamp),
+    };
+    let time = TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(time, format).ok())?;
+
+    let rest =
+        rest.trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '–' | ':' | '|'));
+    Some((date, time, rest))
+}
+
+/// Activity items from a text log, served as commits so they go through the
+/// same filters, prompt and storage as a repository's
+pub struct TextLogRepository {
+    items: Vec<Commit>,
+}
+
+impl TextLogRepository {
+    pub fn new(items: Vec<Commit>) -> Self {
+        Self { items }
+    }
+
+    pub fn items(&self) -> &[Commit] {
+        &self.items
+    }
+}
+
+impl GitRepository for TextLogRepository {
+    fn get_commits_since(&self, timestamp: i64) -> Result<Vec<Commit>> {
+        let mut items: Vec<Commit> = self
+            .items
+            .iter()
+            .filter(|item| item.timestamp() >= timestamp)
+            .cloned()
+            .collect();
+        // Match the reflog ordering: newest first
+        items.sort_by_key(|item| std::cmp::Reverse(item.timestamp()));
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn date(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y-%m-%d").unwrap()
+    }
+
+    fn at(datetime: &str) -> i64 {
+        chrono::NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc()
+            .timestamp()
+    }
+
+    #[test]
+    fn test_parse_text_log() -> Result<()> {
+        let log = "\
+Triaged the inbox
+
+09:30 - Standup
+  Agreed to ship the exporter first
+[11:00] Design review: billing
+- Took notes for the RFC
+
+## 2024-05-08
+2024-05-08 14:05 Interview
+10:00:
+";
+        let items = parse_text_log(log, &Utc, date("2024-05-07"))?;
+        let messages: Vec<(&str, i64)> = items
+            .iter()
+            .map(|item| (item.message.as_str(), item.timestamp()))
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                ("Triaged the inbox", at("2024-05-07 00:00")),
+                (
+                    "Standup; Agreed to ship the exporter first",
+                    at("2024-05-07 09:30")
+                ),
+                (
+                    "Design review: billing; Took notes for the RFC",
+                    at("2024-05-07 11:00")
+                ),
+                ("Interview", at("2024-05-08 14:05")),
+            ]
+        );
+
+        let repository = TextLogRepository::new(items);
+        let recent = repository.get_commits_since(at("2024-05-07 10:00"))?;
+        assert_eq!(recent[0].message, "Interview");
+        assert_eq!(recent.len(), 2);
+
+        assert!(parse_text_log("\n## 2024-05-07\n09:30\n", &Utc, date("2024-05-07")).is_err());
+        Ok(())
+    }
+}
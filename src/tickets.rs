@@ -0,0 +1,602 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Days, TimeZone, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::domain::{TicketSource, TicketTransition};
+use crate::pulls::RemoteRepository;
+
+/// Statuses listed when the config doesn't name any
+const DEFAULT_STATUSES: &[&str] = &["In Review", "Done"];
+
+/// Issues asked for per page of results
+const ISSUES_PER_PAGE: usize = 100;
+
+/// Issue trackers ticket status changes can be read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TicketTracker {
+    /// Jira Cloud, or Jira Server/Data Center with a personal access token
+    Jira,
+    /// The Status field of GitHub Projects boards, for issues of `origin`,
+    /// with the GitHub credentials from `[auth]`
+    GithubProjects,
+}
+
+/// The `[tickets]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TicketsConfig {
+    /// Tracker to add a "Tickets" section from; none when unset
+    pub provider: Option<TicketTracker>,
+    /// Address of the Jira site, such as `https://acme.atlassian.net`
+    pub jira_url: Option<String>,
+    /// Account email for Jira Cloud API tokens; without it the token is sent
+    /// as a Jira Server personal access token
+    pub jira_email: Option<String>,
+    /// Jira API token; read from `JIRA_API_TOKEN` when unset
+    pub jira_token: Option<String>,
+    /// Statuses worth listing, compared case-insensitively; "In Review" and
+    /// "Done" when empty
+    pub statuses: Vec<String>,
+}
+
+impl TicketsConfig {
+    /// Statuses worth listing, with the defaults filled in
+    pub fn statuses(&self) -> Vec<String> {
+        if self.statuses.is_empty() {
+            DEFAULT_STATUSES.iter().map(|s| s.to_string()).collect()
+        } else {
+            self.statuses.clone()
+        }
+    }
+
+    /// The Jira source for `provider = "jira"`
+    pub fn jira_source(&self) -> Result<JiraTicketSource> {
+        let url = self
+            .jira_url
+            .clone()
+            .context("Jira needs `jira_url` in [tickets]")?;
+        let token = match &self.jira_token {
+            Some(token) => token.clone(),
+            None => std::env::var("JIRA_API_TOKEN")
+                .context("Jira needs `jira_token` in [tickets] or JIRA_API_TOKEN")?,
+        };
+        Ok(JiraTicketSource::new(url, self.jira_email.clone(), token)
+            .with_statuses(self.statuses()))
+    }
+}
+
+/// Whether `status` is one of `statuses`, ignoring case
+fn is_listed(statuses: &[String], status: &str) -> bool {
+    statuses
+        .iter()
+        .any(|listed| listed.eq_ignore_ascii_case(status))
+}
+
+/// Keeps the last move of each issue within `since..until`, in key order
+fn last_moves(
+    mut moves: Vec<(i64, TicketTransition)>,
+    since: i64,
+    until: i64,
+    statuses: &[String],
+) -> Vec<TicketTransition> {
+    moves.retain(|(time, _)| since <= *time && *time < until);
+    moves.sort_by_key(|(time, _)| *time);
+
+    let mut tickets: Vec<TicketTransition> = Vec::new();
+    for (_, ticket) in moves {
+        match tickets.iter_mut().find(|known| known.key == ticket.key) {
+            Some(known) => *known = ticket,
+            None => tickets.push(ticket),
+        }
+    }
+    // An issue moved on from a listed status, such as back to "In Progress", is left out
+    tickets.retain(|ticket| is_listed(statuses, &ticket.status));
+    tickets.sort_by(|a, b| a.key.cmp(&b.key));
+    tickets
+}
+
+#[derive(Deserialize)]
+struct JiraUser {
+    /// Jira Cloud's id
+    #[serde(rename = "accountId")]
+    account_id: Option<String>,
+    /// Jira Server's user name
+    name: Option<String>,
+}
+
+impl JiraUser {
+    fn is(&self, other: &JiraUser) -> bool {
+        match (&self.account_id, &other.account_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.name.is_some() && self.name == other.name,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JiraSearch {
+    issues: Vec<JiraIssue>,
+    #[serde(default)]
+    total: usize,
+}
+
+#[derive(Deserialize)]
+struct JiraIssue {
+    key: String,
+    fields: JiraFields,
+    changelog: Option<JiraChangelog>,
+}
+
+#[derive(Deserialize)]
+struct JiraFields {
+    summary: String,
+}
+
+#[derive(Deserialize)]
+struct JiraChangelog {
+    histories: Vec<JiraHistory>,
+}
+
+#[derive(Deserialize)]
+struct JiraHistory {
+    author: Option<JiraUser>,
+    created: String,
+    items: Vec<JiraChange>,
+}
+
+#[derive(Deserialize)]
+struct JiraChange {
+    field: String,
+    #[serde(rename = "toString")]
+    to: Option<String>,
+}
+
+/// Reads the status changes the developer made from Jira's issue changelogs
+pub struct JiraTicketSource {
+    client: reqwest::Client,
+    base_url: String,
+    email: Option<String>,
+    token: String,
+    statuses: Vec<String>,
+}
+
+impl JiraTicketSource {
+    /// With an `email` the token is a Jira Cloud API token, otherwise a Jira
+    /// Server personal access token
+    pub fn new(base_url: String, email: Option<String>, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            email,
+            token,
+            statuses: DEFAULT_STATUSES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Lists moves to `statuses` instead of "In Review" and "Done"
+    pub fn with_statuses(mut self, statuses: Vec<String>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<T> {
+        let request = self
+            .client
+            .get(format!("{}{}", self.base_url, path))
+            .query(query)
+            .header("Accept", "application/json")
+            .header("User-Agent", "git-diary");
+        let request = match &self.email {
+            Some(email) => request.basic_auth(email, Some(&self.token)),
+            None => request.bearer_auth(&self.token),
+        };
+        let raw = request.send().await?.error_for_status()?.text().await?;
+
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Unexpected response from {}: {}", path, raw))
+    }
+}
+
+#[async_trait]
+impl TicketSource for JiraTicketSource {
+    async fn transitions_between(&self, since: i64, until: i64) -> Result<Vec<TicketTransition>> {
+        let me: JiraUser = self.get("/rest/api/2/myself", &[]).await?;
+        // JQL dates are in the Jira user's time zone, so the search is a day
+        // wider on each side and the changelog times decide
+        let day = |timestamp: i64, shift: fn(DateTime<Utc>) -> Option<DateTime<Utc>>| {
+            Utc.timestamp_opt(timestamp, 0)
+                .single()
+                .and_then(shift)
+                .map(|time| time.format("%Y/%m/%d").to_string())
+                .context("Invalid ticket period")
+        };
+        let jql = format!(
+            "status CHANGED BY currentUser() DURING (\"{}\", \"{}\") ORDER BY key",
+            day(since, |time| time.checked_sub_days(Days::new(1)))?,
+            day(until, |time| time.checked_add_days(Days::new(1)))?,
+        );
+        let mut issues = Vec::new();
+        loop {
+            let search: JiraSearch = self
+                .get(
+                    "/rest/api/2/search",
+                    &[
+                        ("jql", jql.clone()),
+                        ("fields", "summary".to_string()),
+                        ("expand", "changelog".to_string()),
+                        ("startAt", issues.len().to_string()),
+                        ("maxResults", ISSUES_PER_PAGE.to_string()),
+                    ],
+                )
+                .await?;
+            let empty = search.issues.is_empty();
+            issues.extend(search.issues);
+            if empty || issues.len() >= search.total {
+                break;
+            }
+        }
+
+        let mut moves = Vec::new();
+        for issue in issues {
+            let histories = issue.changelog.map(|log| log.histories).unwrap_or_default();
+            for history in histories {
+                if !history.author.as_ref().is_some_and(|author| author.is(&me)) {
+                    continue;
+                }
+                let Ok(created) =
+                    DateTime::parse_from_str(&history.created, "%Y-%m-%dT%H:%M:%S%.f%z")
+                else {
+                    continue;
+                };
+                for change in history.items {
+                    if let (true, Some(status)) = (change.field == "status", change.to) {
+                        moves.push((
+                            created.timestamp(),
+                            TicketTransition::new(
+                                issue.key.clone(),
+                                issue.fields.summary.clone(),
+                                status,
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(last_moves(moves, since, until, &self.statuses))
+    }
+}
+
+/// Issues of `origin` and the moves between Status columns on their GitHub
+/// Projects boards; one GraphQL query also names the viewer
+const GITHUB_PROJECTS_QUERY: &str = r#"
+query($search: String!, $since: DateTime!, $first: Int!, $after: String) {
+  viewer { login }
+  search(query: $search, type: ISSUE, first: $first, after: $after) {
+    pageInfo { hasNextPage endCursor }
+    nodes {
+      ... on Issue {
+        number
+        title
+        timelineItems(since: $since, first: 100, itemTypes: [PROJECT_V2_ITEM_STATUS_CHANGED_EVENT]) {
+          nodes {
+            ... on ProjectV2ItemStatusChangedEvent { actor { login } createdAt status }
+          }
+        }
+      }
+    }
+  }
+}"#;
+
+#[derive(Deserialize)]
+struct GraphQlResponse {
+    data: Option<ProjectsData>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectsData {
+    viewer: GitHubLogin,
+    search: SearchPage,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    page_info: PageInfo,
+    nodes: Vec<ProjectIssue>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Nodes<T> {
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct GitHubLogin {
+    login: String,
+}
+
+/// An issue, or an empty object for other search results such as pull requests
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProjectIssue {
+    number: Option<u64>,
+    title: Option<String>,
+    timeline_items: Option<Nodes<StatusChange>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusChange {
+    actor: Option<GitHubLogin>,
+    created_at: Option<String>,
+    status: Option<String>,
+}
+
+/// Reads the issues of a GitHub repository the developer moved between
+/// Status columns of GitHub Projects boards
+pub struct GitHubProjectsSource {
+    client: reqwest::Client,
+    repository: RemoteRepository,
+    token: String,
+    graphql_url: String,
+    statuses: Vec<String>,
+}
+
+impl GitHubProjectsSource {
+    pub fn new(repository: RemoteRepository, token: String) -> Self {
+        let graphql_url = match repository.host.as_str() {
+            "github.com" => "https://api.github.com/graphql".to_string(),
+            host => format!("https://{}/api/graphql", host),
+        };
+
+        Self {
+            client: reqwest::Client::new(),
+            repository,
+            token,
+            graphql_url,
+            statuses: DEFAULT_STATUSES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Sends queries to another endpoint, such as a mock server
+    pub fn with_graphql_url(mut self, graphql_url: String) -> Self {
+        self.graphql_url = graphql_url;
+        self
+    }
+
+    /// Lists moves to `statuses` instead of "In Review" and "Done"
+    pub fn with_statuses(mut self, statuses: Vec<String>) -> Self {
+        self.statuses = statuses;
+        self
+    }
+}
+
+impl GitHubProjectsSource {
+    /// One page of issues updated since `start`, after the `after` cursor
+    async fn search_page(&self, start: DateTime<Utc>, after: Option<&str>) -> Result<ProjectsData> {
+        let body = json!({
+            "query": GITHUB_PROJECTS_QUERY,
+            "variables": {
+                "search": format!(
+                    "repo:{} is:issue updated:>={}",
+                    self.repository.path,
+                    start.format("%Y-%m-%d")
+                ),
+                "since": start.to_rfc3339(),
+                "first": ISSUES_PER_PAGE,
+                "after": after,
+            },
+        });
+        let raw = self
+            .client
+            .post(&self.graphql_url)
+            .header("User-Agent", "git-diary")
+            .header("Content-Type", "application/json")
+            .bearer_auth(&self.token)
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let response: GraphQlResponse = serde_json::from_str(&raw)
+            .with_context(|| format!("Unexpected response from GitHub: {}", raw))?;
+        match response.data {
+            Some(data) => Ok(data),
+            None => {
+                let messages: Vec<String> =
+                    response.errors.into_iter().map(|e| e.message).collect();
+                anyhow::bail!("GitHub refused the projects query: {}", messages.join("; "))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TicketSource for GitHubProjectsSource {
+    async fn transitions_between(&self, since: i64, until: i64) -> Result<Vec<TicketTransition>> {
+        let start = Utc
+            .timestamp_opt(since, 0)
+            .single()
+            .context("Invalid start of the ticket period")?;
+
+        let mut moves = Vec::new();
+        let mut after = None;
+        loop {
+            let data = self.search_page(start, after.as_deref()).await?;
+            for issue in data.search.nodes {
+                let (Some(number), Some(title)) = (issue.number, issue.title) else {
+                    continue;
+                };
+                for change in issue.timeline_items.map_or(Vec::new(), |items| items.nodes) {
+                    let by_viewer = change
+                        .actor
+                        .is_some_and(|actor| actor.login == data.viewer.login);
+                    let created = change
+                        .created_at
+                        .and_then(|time| DateTime::parse_from_rfc3339(&time).ok());
+                    if let (true, Some(created), Some(status)) = (by_viewer, created, change.status)
+                    {
+                        moves.push((
+                            created.timestamp(),
+                            TicketTransition::new(format!("#{}", number), title.clone(), status),
+                        ));
+                    }
+                }
+            }
+
+            let page = data.search.page_info;
+            match page.end_cursor {
+                Some(cursor) if page.has_next_page => after = Some(cursor),
+                _ => break,
+            }
+        }
+
+        Ok(last_moves(moves, since, until, &self.statuses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // 2024-01-01 to 2024-01-08 UTC
+    const SINCE: i64 = 1704067200;
+    const UNTIL: i64 = 1704672000;
+
+    fn ticket(key: &str, title: &str, status: &str) -> TicketTransition {
+        TicketTransition::new(key.to_string(), title.to_string(), status.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_jira_transitions() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/myself"))
+            .and(header(
+                "Authorization",
+                "Basic amFuZUBleGFtcGxlLmNvbTp0b2tlbg==",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"accountId": "me"})))
+            .mount(&server)
+            .await;
+        let history = |author: &str, created: &str, status: &str| {
+            json!({"author": {"accountId": author}, "created": created,
+                "items": [{"field": "status", "toString": status}]})
+        };
+        Mock::given(method("GET"))
+            .and(path("/rest/api/2/search"))
+            .and(query_param("expand", "changelog"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"total": 3, "issues": [
+                {"key": "PAY-142", "fields": {"summary": "Retry failed payments"}, "changelog": {"histories": [
+                    history("me", "2024-01-02T10:00:00.000+0000", "In Review"),
+                    history("me", "2024-01-03T10:00:00.000+0900", "Done"),
+                ]}},
+                {"key": "PAY-7", "fields": {"summary": "Refund flow"}, "changelog": {"histories": [
+                    history("me", "2024-01-02T10:00:00.000+0000", "In Review"),
+                    history("me", "2024-01-04T10:00:00.000+0000", "In Progress"),
+                ]}},
+                {"key": "PAY-9", "fields": {"summary": "Receipts"}, "changelog": {"histories": [
+                    history("alice", "2024-01-02T10:00:00.000+0000", "Done"),
+                    history("me", "2023-12-30T10:00:00.000+0000", "Done"),
+                ]}}
+            ]})))
+            .mount(&server)
+            .await;
+
+        let source = JiraTicketSource::new(
+            server.uri(),
+            Some("jane@example.com".to_string()),
+            "token".to_string(),
+        );
+        assert_eq!(
+            source.transitions_between(SINCE, UNTIL).await?,
+            vec![ticket("PAY-142", "Retry failed payments", "Done")]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_github_projects_transitions() -> Result<()> {
+        let server = MockServer::start().await;
+        let change = |login: &str, at: &str, status: &str| json!({"actor": {"login": login}, "createdAt": at, "status": status});
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(header("Authorization", "Bearer token"))
+            .and(body_partial_json(json!({"variables": {"after": null}})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": {
+                "viewer": {"login": "jane"},
+                "search": {"pageInfo": {"hasNextPage": true, "endCursor": "page-2"}, "nodes": [
+                    {"number": 57, "title": "Dark mode", "timelineItems": {"nodes": [
+                        change("jane", "2024-01-03T09:00:00Z", "in review"),
+                        change("bob", "2024-01-04T09:00:00Z", "Done"),
+                    ]}},
+                ]}
+            }})))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(json!({"variables": {"after": "page-2"}})))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"data": {
+                "viewer": {"login": "jane"},
+                "search": {"pageInfo": {"hasNextPage": false, "endCursor": null}, "nodes": [
+                    {"number": 60, "title": "Export PDF", "timelineItems": {"nodes": [
+                        change("jane", "2024-01-05T09:00:00Z", "Todo"),
+                    ]}},
+                    {"number": 61, "title": "Sync settings", "timelineItems": {"nodes": [
+                        change("jane", "2024-01-06T09:00:00Z", "Done"),
+                    ]}},
+                    {}
+                ]}
+            }})))
+            .mount(&server)
+            .await;
+
+        let source = GitHubProjectsSource::new(
+            RemoteRepository::parse("https://github.com/team/app.git").unwrap(),
+            "token".to_string(),
+        )
+        .with_graphql_url(format!("{}/graphql", server.uri()));
+        assert_eq!(
+            source.transitions_between(SINCE, UNTIL).await?,
+            vec![
+                ticket("#57", "Dark mode", "in review"),
+                ticket("#61", "Sync settings", "Done")
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tickets_config_statuses() {
+        assert_eq!(
+            TicketsConfig::default().statuses(),
+            vec!["In Review", "Done"]
+        );
+        assert!(TicketsConfig::default().jira_source().is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::codingtime::CodingTime;
+use crate::domain::CodingTimeSource;
+
+/// WakaTime's API root
+const WAKATIME_API_URL: &str = "https://wakatime.com/api/v1";
+/// Where ActivityWatch listens by default
+const ACTIVITYWATCH_URL: &str = "http://localhost:5600";
+/// Bucket type of ActivityWatch's editor watchers
+const EDITOR_BUCKET_TYPE: &str = "app.editor.activity";
+
+/// Time trackers coding time can be read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeTracker {
+    /// wakatime.com, or a compatible server such as Wakapi through `wakatime_url`
+    Wakatime,
+    /// A local ActivityWatch server with an editor watcher
+    Activitywatch,
+}
+
+/// The `[time_tracking]` config table
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimeTrackingConfig {
+    /// Tracker to add a "Time by project and language" section from; none when unset
+    pub provider: Option<TimeTracker>,
+    /// WakaTime API key; read from `WAKATIME_API_KEY` when unset
+    pub wakatime_api_key: Option<String>,
+    /// API root of a WakaTime-compatible server; wakatime.com when unset
+    pub wakatime_url: Option<String>,
+    /// Address of the ActivityWatch server; `http://localhost:5600` when unset
+    pub activitywatch_url: Option<String>,
+}
+
+impl TimeTrackingConfig {
+    /// The configured tracker, or `None` when no provider is set
+    pub fn source(&self) -> Result<Option<Arc<dyn CodingTimeSource>>> {
+        let source: Arc<dyn CodingTimeSource> = match self.provider {
+            None => return Ok(None),
+            Some(TimeTracker::Wakatime) => {
+                let api_key = match &self.wakatime_api_key {
+                    Some(api_key) => api_key.clone(),
+                    None => std::env::var("WAKATIME_API_KEY").context(
+                        "WakaTime needs `wakatime_api_key` in [time_tracking] or WAKATIME_API_KEY",
+                    )?,
+                };
+                let mut source = WakaTimeSource::new(api_key);
+                if let Some(api_url) = &self.wakatime_url {
+                    source = source.with_api_url(api_url.clone());
+                }
+                Arc::new(source)
+            }
+            Some(TimeTracker::Activitywatch) => {
+                let mut source = ActivityWatchSource::new();
+                if let Some(url) = &self.activitywatch_url {
+                    source = source.with_url(url.clone());
+                }
+                Arc::new(source)
+            }
+        };
+        Ok(Some(source))
+    }
+}
+
+async fn get_json<T: DeserializeOwned>(request: reqwest::RequestBuilder, what: &str) -> Result<T> {
+    let raw = request
+        .header("User-Agent", "git-diary")
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    serde_json::from_str(&raw).with_context(|| format!("Unexpected {} response: {}", what, raw))
+}
+
+/// Reads coding time from WakaTime's daily summaries
+pub struct WakaTimeSource {
+    client: reqwest::Client,
+    api_key: String,
+    api_url: String,
+}
+
+impl WakaTimeSource {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key,
+            api_url: WAKATIME_API_URL.to_string(),
+        }
+    }
+
+    /// Sends requests to another API root, such as a Wakapi server or a mock server
+    pub fn with_api_url(mut self, api_url: String) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[derive(Deserialize)]
+struct WakaTimeSummaries {
+    data: Vec<WakaTimeDay>,
+}
+
+#[derive(Deserialize)]
+struct WakaTimeDay {
+    grand_total: WakaTimeTotal,
+    #[serde(default)]
+    projects: Vec<WakaTimeEntry>,
+    #[serde(default)]
+    languages: Vec<WakaTimeEntry>,
+}
+
+#[derive(Deserialize)]
+struct WakaTimeTotal {
+    total_seconds: f64,
+}
+
+#[derive(Deserialize)]
+struct WakaTimeEntry {
+    name: String,
+    total_seconds: f64,
+}
+
+#[async_trait]
+impl CodingTimeSource for WakaTimeSource {
+    /// Summaries are per day, so the whole first and last day are counted
+    async fn coding_time_between(&self, since: i64, until: i64) -> Result<CodingTime> {
+        let start = local_date(since)?;
+        let end = local_date((until - 1).max(since))?;
+        let summaries: WakaTimeSummaries = get_json(
+            self.client
+                .get(format!("{}/users/current/summaries", self.api_url))
+                // In a header rather than the query, so it never shows up in
+                // the URL that request errors print
+                .header(
+                    "Authorization",
+                    format!("Basic {}", base64_encode(self.api_key.as_bytes())),
+                )
+                .query(&[("start", start), ("end", end)]),
+            "WakaTime",
+        )
+        .await?;
+
+        let mut total = 0.0;
+        let mut projects = HashMap::new();
+        let mut languages = HashMap::new();
+        for day in summaries.data {
+            total += day.grand_total.total_seconds;
+            for entry in day.projects {
+                *projects.entry(entry.name).or_default() += entry.total_seconds;
+            }
+            for entry in day.languages {
+                *languages.entry(entry.name).or_default() += entry.total_seconds;
+            }
+        }
+        Ok(CodingTime::new(total, projects, languages))
+    }
+}
+
+fn local_date(timestamp: i64) -> Result<String> {
+    let time = DateTime::from_timestamp(timestamp, 0).context("Invalid timestamp")?;
+    Ok(time.with_timezone(&Local).format("%Y-%m-%d").to_string())
+}
+
+/// Reads coding time from the editor watchers of a local ActivityWatch server
+pub struct ActivityWatchSource {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl ActivityWatchSource {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: ACTIVITYWATCH_URL.to_string(),
+        }
+    }
+
+    /// Reads from a server at another address, such as a mock server
+    pub fn with_url(mut self, url: String) -> Self {
+        self.url = url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+impl Default for ActivityWatchSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Deserialize)]
+struct Bucket {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Deserialize)]
+struct EditorEvent {
+    duration: f64,
+    data: EditorActivity,
+}
+
+#[derive(Deserialize)]
+struct EditorActivity {
+    project: Option<String>,
+    language: Option<String>,
+}
+
+#[async_trait]
+impl CodingTimeSource for ActivityWatchSource {
+    async fn coding_time_between(&self, since: i64, until: i64) -> Result<CodingTime> {
+        let buckets: HashMap<String, Bucket> = get_json(
+            self.client.get(format!("{}/api/0/buckets/", self.url)),
+            "ActivityWatch",
+        )
+        .await?;
+        let mut editor_buckets: Vec<&String> = buckets
+            .iter()
+            .filter(|(_, bucket)| bucket.kind == EDITOR_BUCKET_TYPE)
+            .map(|(id, _)| id)
+            .collect();
+        if editor_buckets.is_empty() {
+            bail!("ActivityWatch has no editor activity; install an editor watcher such as aw-watcher-vscode");
+        }
+        editor_buckets.sort();
+
+        let mut total = 0.0;
+        let mut projects = HashMap::new();
+        let mut languages = HashMap::new();
+        for id in editor_buckets {
+            let events: Vec<EditorEvent> = get_json(
+                self.client
+                    .get(format!("{}/api/0/buckets/{}/events", self.url, id))
+                    .query(&[("start", rfc3339(since)?), ("end", rfc3339(until)?)]),
+                "ActivityWatch",
+            )
+            .await?;
+            for event in events {
+                total += event.duration;
+                if let Some(project) = event.data.project.as_deref().and_then(project_name) {
+                    *projects.entry(project).or_default() += event.duration;
+                }
+                if let Some(language) = event.data.language.filter(|l| !l.is_empty()) {
+                    *languages.entry(language).or_default() += event.duration;
+                }
+            }
+        }
+        Ok(CodingTime::new(total, projects, languages))
+    }
+}
+
+fn rfc3339(timestamp: i64) -> Result<String> {
+    let time: DateTime<Utc> =
+        DateTime::from_timestamp(timestamp, 0).context("Invalid timestamp")?;
+    Ok(time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Editor watchers report the project as its folder; the folder name is the project
+fn project_name(project: &str) -> Option<String> {
+    let name = Path::new(project.trim_end_matches(['/', '\\']))
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| project.to_string());
+    (!name.is_empty()).then_some(name)
+}
+
+/// Standard base64 with padding, as WakaTime expects the key in Basic auth
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_wakatime_source() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/users/current/summaries"))
+            .and(header("Authorization", "Basic d2FrYV8xMjM="))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": [
+                    {
+                        "grand_total": {"total_seconds": 5400.0},
+                        "projects": [
+                            {"name": "git-diary", "total_seconds": 4200.0},
+                            {"name": "dotfiles", "total_seconds": 1200.0},
+                        ],
+                        "languages": [{"name": "Rust", "total_seconds": 5400.0}],
+                    },
+                    {
+                        "grand_total": {"total_seconds": 1800.0},
+                        "projects": [{"name": "git-diary", "total_seconds": 1800.0}],
+                        "languages": [{"name": "Markdown", "total_seconds": 1800.0}],
+                    },
+                ]
+            })))
+            .mount(&server)
+            .await;
+        let source = WakaTimeSource::new("waka_123".to_string()).with_api_url(server.uri());
+
+        let time = source.coding_time_between(1704067200, 1704240000).await?;
+
+        assert_eq!(
+            time.lines(),
+            vec![
+                "2h 0m in total",
+                "Projects: git-diary 1h 40m, dotfiles 20m",
+                "Languages: Rust 1h 30m, Markdown 30m",
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_activitywatch_source() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/0/buckets/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "aw-watcher-vscode_laptop": {"type": "app.editor.activity"},
+                "aw-watcher-window_laptop": {"type": "currentwindow"},
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/0/buckets/aw-watcher-vscode_laptop/events"))
+            .and(query_param("start", "2024-01-01T00:00:00Z"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"duration": 3000.0, "data": {"project": "/home/jane/src/git-diary/", "language": "rust", "file": "src/main.rs"}},
+                {"duration": 600.0, "data": {"project": "/home/jane/src/git-diary", "language": "markdown", "file": "README.md"}},
+                {"duration": 900.0, "data": {"project": "", "language": "", "file": "Untitled-1"}},
+            ])))
+            .mount(&server)
+            .await;
+        let source = ActivityWatchSource::new().with_url(server.uri());
+
+        let time = source.coding_time_between(1704067200, 1704153600).await?;
+
+        assert_eq!(
+            time.lines(),
+            vec![
+                "1h 15m in total",
+                "Projects: git-diary 1h 0m",
+                "Languages: rust 50m, markdown 10m",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_tracking_config() -> Result<()> {
+        let config: TimeTrackingConfig =
+            toml::from_str("provider = \"activitywatch\"\nactivitywatch_url = \"http://aw:5600\"")?;
+        assert_eq!(config.provider, Some(TimeTracker::Activitywatch));
+        assert!(config.source()?.is_some());
+        assert!(TimeTrackingConfig::default().source()?.is_none());
+        assert!(toml::from_str::<TimeTrackingConfig>("provider = \"toggl\"").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_encode(b"waka_123"), "d2FrYV8xMjM=");
+    }
+}
@@ -0,0 +1,487 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use crate::domain::Commit;
+use crate::formatter::COMMIT_LOGS_HEADING;
+use crate::ledger::LedgerEntry;
+use crate::parser::parse_diary;
+use crate::storage::DiaryStorageImpl;
+
+/// A stored Markdown diary, as listed in the browser
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserEntry {
+    pub path: PathBuf,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub markdown: String,
+    /// Conventional-commit types found in the commit logs, sorted
+    pub tags: Vec<String>,
+    /// The latest ledger record for this file, if any
+    pub usage: Option<LedgerEntry>,
+}
+
+/// Loads every stored Markdown diary, newest first
+///
+/// Ledger entries are matched to diaries by file name, so they still match
+/// when the ledger recorded a relative path.
+pub fn load_entries(
+    storage: &DiaryStorageImpl,
+    ledger: &[LedgerEntry],
+) -> Result<Vec<BrowserEntry>> {
+    let mut entries = Vec::new();
+
+    for diary in storage.stored_diaries()? {
+        if diary.path.extension().is_none_or(|ext| ext != "md") {
+            continue;
+        }
+
+        let markdown = fs::read_to_string(&diary.path)
+            .with_context(|| format!("Failed to read {}", diary.path.display()))?;
+        let file_name = diary.path.file_name();
+        let usage = ledger
+            .iter()
+            .rev()
+            .find(|entry| PathBuf::from(&entry.path).file_name() == file_name)
+            .cloned();
+
+        entries.push(BrowserEntry {
+            tags: diary_tags(&markdown),
+            path: diary.path,
+            start_date: diary.start_date,
+            end_date: diary.end_date,
+            markdown,
+            usage,
+        });
+    }
+    entries.sort_by(|a, b| b.end_date.cmp(&a.end_date).then(b.path.cmp(&a.path)));
+
+    Ok(entries)
+}
+
+/// Conventional-commit types of the `- <time>: <message>` lines in the commit logs
+fn diary_tags(markdown: &str) -> Vec<String> {
+    let Some(logs) = parse_diary(markdown)
+        .ok()
+        .and_then(|diary| diary.section(COMMIT_LOGS_HEADING).cloned())
+    else {
+        return Vec::new();
+    };
+
+    let tags: BTreeSet<String> = logs
+        .body
+        .lines()
+        .filter_map(|line| line.strip_prefix("- "))
+        .filter_map(|line| line.split_once(": "))
+        .filter_map(|(_, message)| Commit::new(message.to_string(), 0).conventional_type())
+        .collect();
+
+    tags.into_iter().collect()
+}
+
+/// What the user picked before leaving the browser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BrowserAction {
+    Quit,
+    OpenInEditor(PathBuf),
+    /// Generate the diary for these dates again
+    Regenerate(NaiveDate, NaiveDate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    Search,
+}
+
+/// State of the diary browser: the entries, the filters and the selection
+pub struct Browser {
+    entries: Vec<BrowserEntry>,
+    query: String,
+    tag: Option<String>,
+    mode: Mode,
+    list: ListState,
+    scroll: u16,
+}
+
+impl Browser {
+    pub fn new(entries: Vec<BrowserEntry>) -> Self {
+        let mut browser = Self {
+            entries,
+            query: String::new(),
+            tag: None,
+            mode: Mode::Browse,
+            list: ListState::default(),
+            scroll: 0,
+        };
+        browser.clamp_selection();
+        browser
+    }
+
+    /// Swaps in freshly loaded entries, keeping the filters and, where possible, the selection
+    pub fn set_entries(&mut self, entries: Vec<BrowserEntry>) {
+        let selected = self.selected().map(|entry| entry.path.clone());
+        self.entries = entries;
+        let index =
+            selected.and_then(|path| self.visible().iter().position(|entry| entry.path == path));
+        self.list.select(index);
+        self.clamp_selection();
+    }
+
+    /// Entries matching the search query and tag filter
+    pub fn visible(&self) -> Vec<&BrowserEntry> {
+        let query = self.query.to_lowercase();
+        self.entries
+            .iter()
+            .filter(|entry| self.tag.as_ref().is_none_or(|tag| entry.tags.contains(tag)))
+            .filter(|entry| query.is_empty() || entry.markdown.to_lowercase().contains(&query))
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&BrowserEntry> {
+        self.visible().get(self.list.selected()?).copied()
+    }
+
+    /// Updates the state for a key press, returning the action that ends browsing
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<BrowserAction> {
+        if self.mode == Mode::Search {
+            match key.code {
+                KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Esc => {
+                    self.query.clear();
+                    self.mode = Mode::Browse;
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                }
+                KeyCode::Char(c) => self.query.push(c),
+                _ => {}
+            }
+            self.clamp_selection();
+            return None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(BrowserAction::Quit),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Char('t') => {
+                self.cycle_tag();
+                self.clamp_selection();
+            }
+            KeyCode::Enter | KeyCode::Char('e') => {
+                return self
+                    .selected()
+                    .map(|entry| BrowserAction::OpenInEditor(entry.path.clone()));
+            }
+            KeyCode::Char('r') => {
+                return self
+                    .selected()
+                    .map(|entry| BrowserAction::Regenerate(entry.start_date, entry.end_date));
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let count = self.visible().len();
+        if count == 0 {
+            return;
+        }
+        let current = self.list.selected().unwrap_or(0);
+        let next = current.saturating_add_signed(offset).min(count - 1);
+        self.list.select(Some(next));
+        self.scroll = 0;
+    }
+
+    /// Moves to the next tag in alphabetical order, then back to no tag filter
+    fn cycle_tag(&mut self) {
+        let tags: BTreeSet<&String> = self.entries.iter().flat_map(|entry| &entry.tags).collect();
+        self.tag = match &self.tag {
+            None => tags.first().map(|tag| tag.to_string()),
+            Some(current) => tags
+                .range::<&String, _>((
+                    std::ops::Bound::Excluded(current),
+                    std::ops::Bound::Unbounded,
+                ))
+                .next()
+                .map(|tag| tag.to_string()),
+        };
+    }
+
+    /// Keeps the selection inside the visible entries after a filter change
+    fn clamp_selection(&mut self) {
+        let count = self.visible().len();
+        let selected = match self.list.selected() {
+            _ if count == 0 => None,
+            Some(index) => Some(index.min(count - 1)),
+            None => Some(0),
+        };
+        if selected != self.list.selected() {
+            self.scroll = 0;
+        }
+        self.list.select(selected);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [body, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+                .areas(body);
+
+        let visible = self.visible();
+        let items: Vec<ListItem> = visible
+            .iter()
+            .map(|entry| {
+                let mut line = format!("{} – {}", entry.start_date, entry.end_date);
+                if !entry.tags.is_empty() {
+                    line.push_str(&format!("  [{}]", entry.tags.join(", ")));
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("Diaries ({})", visible.len())),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let (title, preview) = match self.selected() {
+            Some(entry) => (preview_title(entry), entry.markdown.clone()),
+            None => (
+                "Preview".to_string(),
+                "No diaries match the current filters.".to_string(),
+            ),
+        };
+        let preview = Paragraph::new(preview)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false })
+            .scroll((self.scroll, 0));
+
+        let status_line =
+            match self.mode {
+                Mode::Search => format!("Search: {}▏  (Enter to keep, Esc to clear)", self.query),
+                Mode::Browse => format!(
+                "tag: {}  search: {}  │  ↑↓ move  / search  t tag  e open  r regenerate  q quit",
+                self.tag.as_deref().unwrap_or("all"),
+                if self.query.is_empty() { "-" } else { &self.query }
+            ),
+            };
+
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+        frame.render_widget(preview, preview_area);
+        frame.render_widget(Line::from(status_line), status);
+    }
+}
+
+fn preview_title(entry: &BrowserEntry) -> String {
+    let name = entry
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match &entry.usage {
+        Some(usage) => format!(
+            "{} · {} · {} tokens{}",
+            name,
+            usage.model,
+            usage.prompt_tokens + usage.completion_tokens,
+            usage
+                .cost
+                .map(|cost| format!(" · ~${:.4}", cost))
+                .unwrap_or_default()
+        ),
+        None => name,
+    }
+}
+
+/// Shows the browser until the user quits or picks an action
+///
+/// The terminal is restored before returning, so the caller can hand it to
+/// an editor and call this again afterwards.
+pub fn run(browser: &mut Browser) -> Result<BrowserAction> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        bail!("The diary browser needs a terminal; use `git-diary list` in scripts");
+    }
+    let mut terminal = match ratatui::try_init() {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            // Raw mode may already be on when the alternate screen fails
+            ratatui::restore();
+            return Err(e).context("Failed to set up the terminal");
+        }
+    };
+    let result = (|| loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(action) = browser.handle_key(key) {
+                return Ok(action);
+            }
+        }
+    })();
+    ratatui::restore();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::crossterm::event::KeyModifiers;
+    use tempfile::TempDir;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn entry(start: u32, tags: &[&str], markdown: &str) -> BrowserEntry {
+        BrowserEntry {
+            path: PathBuf::from(format!("diaries/git-diary-202401{:02}.md", start)),
+            start_date: NaiveDate::from_ymd_opt(2024, 1, start).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2024, 1, start).unwrap(),
+            markdown: markdown.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            usage: None,
+        }
+    }
+
+    #[test]
+    fn test_load_entries() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let dir = temp_dir.path();
+        fs::write(
+            dir.join("git-diary-20240101-to-20240107.md"),
+            "# Git Diary\n\n## Commit Logs\n\n- 2024-01-01 00:00:00: commit: feat(cli): Add browse\n- 2024-01-02 00:00:00: Fix: typo\n- 2024-01-03 00:00:00: fix: Crash\n\n## AI-generated Summary\n\nBusy.\n",
+        )?;
+        fs::write(
+            dir.join("git-diary-20240108-to-20240114.md"),
+            "# Git Diary\n",
+        )?;
+        fs::write(dir.join("git-diary-20240108-to-20240114.json"), "{}")?;
+
+        let ledger: Vec<LedgerEntry> = serde_json::from_str(
+            r#"[{"generated_at":1,"repo":"/src/app","start_date":"2024-01-01","end_date":"2024-01-07","path":"diaries/git-diary-20240101-to-20240107.md","model":"gpt-4","prompt_tokens":10,"completion_tokens":5,"cost":0.5}]"#,
+        )?;
+        let storage = DiaryStorageImpl::new(dir.to_string_lossy().into_owned());
+        let entries = load_entries(&storage, &ledger)?;
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].end_date,
+            NaiveDate::from_ymd_opt(2024, 1, 14).unwrap()
+        );
+        assert!(entries[0].tags.is_empty());
+        assert_eq!(entries[1].tags, vec!["feat", "fix"]);
+        assert_eq!(
+            preview_title(&entries[1]),
+            "git-diary-20240101-to-20240107.md · gpt-4 · 15 tokens · ~$0.5000"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_browser_filters_and_actions() {
+        let mut browser = Browser::new(vec![
+            entry(3, &["feat"], "Added the cache"),
+            entry(2, &["fix"], "Fixed the CACHE eviction"),
+            entry(1, &[], "Wrote docs"),
+        ]);
+        assert_eq!(
+            browser.selected().unwrap().start_date.to_string(),
+            "2024-01-03"
+        );
+
+        // Search is case-insensitive and typed keys don't trigger actions
+        browser.handle_key(key(KeyCode::Char('/')));
+        for c in "cache".chars() {
+            assert_eq!(browser.handle_key(key(KeyCode::Char(c))), None);
+        }
+        browser.handle_key(key(KeyCode::Enter));
+        assert_eq!(browser.visible().len(), 2);
+
+        browser.handle_key(key(KeyCode::Char('j')));
+        browser.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(
+            browser.selected().unwrap().start_date.to_string(),
+            "2024-01-02"
+        );
+
+        // Tags cycle alphabetically, then back to all entries
+        browser.handle_key(key(KeyCode::Char('t')));
+        assert_eq!(browser.tag.as_deref(), Some("feat"));
+        assert_eq!(
+            browser.selected().unwrap().start_date.to_string(),
+            "2024-01-03"
+        );
+        browser.handle_key(key(KeyCode::Char('t')));
+        browser.handle_key(key(KeyCode::Char('t')));
+        assert_eq!(browser.tag, None);
+
+        assert_eq!(
+            browser.handle_key(key(KeyCode::Char('r'))),
+            Some(BrowserAction::Regenerate(
+                NaiveDate::from_ymd_opt(2024, 1, 3).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 1, 3).unwrap()
+            ))
+        );
+        assert_eq!(
+            browser.handle_key(key(KeyCode::Char('e'))),
+            Some(BrowserAction::OpenInEditor(PathBuf::from(
+                "diaries/git-diary-20240103.md"
+            )))
+        );
+
+        browser.handle_key(key(KeyCode::Char('/')));
+        browser.handle_key(key(KeyCode::Char('x')));
+        browser.handle_key(key(KeyCode::Enter));
+        assert_eq!(browser.selected(), None);
+        assert_eq!(browser.handle_key(key(KeyCode::Char('e'))), None);
+        assert_eq!(
+            browser.handle_key(key(KeyCode::Char('q'))),
+            Some(BrowserAction::Quit)
+        );
+    }
+
+    #[test]
+    fn test_browser_draws_list_and_preview() -> Result<()> {
+        let mut browser = Browser::new(vec![entry(3, &["feat"], "# Git Diary\n\nAdded the cache")]);
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 10))?;
+
+        terminal.draw(|frame| browser.draw(frame))?;
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("Diaries (1)"));
+        assert!(screen.contains("2024-01-03 – 2024-01-03"));
+        assert!(screen.contains("Added the cache"));
+        assert!(screen.contains("tag: all"));
+
+        Ok(())
+    }
+}
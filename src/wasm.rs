@@ -0,0 +1,127 @@
+use anyhow::{bail, Result};
+use chrono::DateTime;
+use wasm_bindgen::prelude::*;
+
+use crate::deps::group_dependency_updates;
+use crate::domain::{
+    group_by_pull_request, Commit, CommitOrder, DiaryContent, SummaryRequest, DEFAULT_MAX_COMMITS,
+};
+use crate::formatter::{ConfluenceFormatter, DiaryFormatter, MarkdownFormatter};
+use crate::gitlog::parse_git_log;
+use crate::prompt::{build_user_prompt, system_prompt as build_system_prompt, SummaryLength};
+use crate::squash::expand_squash_merges;
+
+/// Returns the system prompt sent with every summary request
+#[wasm_bindgen(js_name = systemPrompt)]
+pub fn system_prompt() -> String {
+    build_system_prompt(SummaryLength::default())
+}
+
+/// Builds the user prompt for pasted `git log` output
+#[wasm_bindgen(js_name = previewPrompt)]
+pub fn preview_prompt(git_log: &str, max_commits: Option<usize>) -> Result<String, JsError> {
+    build_prompt_preview(git_log, max_commits.unwrap_or(DEFAULT_MAX_COMMITS)).map_err(to_js_error)
+}
+
+/// Renders the diary for pasted `git log` output with a hand-written summary
+///
+/// `format` is `markdown` or `confluence`.
+#[wasm_bindgen(js_name = previewDiary)]
+pub fn preview_diary(git_log: &str, summary: &str, format: &str) -> Result<String, JsError> {
+    build_diary_preview(git_log, summary, format).map_err(to_js_error)
+}
+
+fn build_prompt_preview(git_log: &str, max_commits: usize) -> Result<String> {
+    let commits = group_dependency_updates(&expand_squash_merges(&parse_git_log(git_log)?));
+
+    Ok(build_user_prompt(&SummaryRequest::capped(
+        &commits,
+        max_commits,
+    )))
+}
+
+fn build_diary_preview(git_log: &str, summary: &str, format: &str) -> Result<String> {
+    let formatter: Box<dyn DiaryFormatter> = match format {
+        "markdown" => Box::new(MarkdownFormatter),
+        "confluence" => Box::new(ConfluenceFormatter),
+        other => bail!("Unknown format {:?}", other),
+    };
+
+    let commits = group_dependency_updates(&expand_squash_merges(&parse_git_log(git_log)?));
+    let start_date = format_date(commits.iter().map(Commit::timestamp).min());
+    let end_date = format_date(commits.iter().map(Commit::timestamp).max());
+    let pull_requests = group_by_pull_request(&commits);
+
+    Ok(formatter.format(&DiaryContent {
+        commits,
+        summary: summary.to_string(),
+        start_date,
+        end_date,
+        rollup: None,
+        notes: Vec::new(),
+        pull_requests,
+        translations: Vec::new(),
+        context: Vec::new(),
+        branch: None,
+        reviews: Vec::new(),
+        tickets: Vec::new(),
+        model: None,
+        health: None,
+        sessions: Vec::new(),
+        work_hours: None,
+        coding_time: None,
+        attachments: Vec::new(),
+        performance: Vec::new(),
+        topics: Vec::new(),
+        commit_order: CommitOrder::default(),
+    }))
+}
+
+fn format_date(timestamp: Option<i64>) -> String {
+    timestamp
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn to_js_error(error: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "commit 2
+Date:   Wed Jan 3 10:00:00 2024 +0000
+
+    Add playground
+
+commit 1
+Date:   Mon Jan 1 10:00:00 2024 +0000
+
+    Initial commit
+";
+
+    #[test]
+    fn test_build_prompt_preview() -> Result<()> {
+        let prompt = build_prompt_preview(LOG, 1)?;
+
+        assert!(prompt.contains("Add playground"));
+        assert!(!prompt.contains("Initial commit"));
+        assert!(prompt.contains("1 older commits were left out"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_diary_preview() -> Result<()> {
+        let diary = build_diary_preview(LOG, "Built a playground.", "markdown")?;
+
+        assert!(diary.starts_with("# Git Diary (2024-01-01 – 2024-01-03)"));
+        assert!(diary.contains("- 2024-01-01 10:00:00: Initial commit\n- 2024-01-03"));
+        assert!(build_diary_preview(LOG, "", "html").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::{DateTime, Datelike, FixedOffset, Local, Offset, Timelike, Weekday};
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+use crate::domain::Commit;
+
+/// Local hour from which a commit counts as made late at night
+pub const LATE_NIGHT_START_HOUR: u32 = 22;
+/// Local hour until which a commit still counts as made late at night
+pub const LATE_NIGHT_END_HOUR: u32 = 5;
+
+/// How many commits were made late at night or on weekends, in each author's
+/// own time zone
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct WorkHours {
+    pub commits: usize,
+    /// Commits made between 22:00 and 05:00
+    pub late_night: usize,
+    /// Commits made on a Saturday or Sunday
+    pub weekend: usize,
+}
+
+impl WorkHours {
+    /// Counts the commits by the time they were written, which a rebase keeps
+    ///
+    /// Commits without a recorded offset are read in this machine's time zone.
+    pub fn from_commits(commits: &[Commit]) -> Self {
+        let mut hours = Self::default();
+        for time in commits.iter().filter_map(author_local_time) {
+            hours.commits += 1;
+            if is_late_night(time.hour()) {
+                hours.late_night += 1;
+            }
+            if matches!(time.weekday(), Weekday::Sat | Weekday::Sun) {
+                hours.weekend += 1;
+            }
+        }
+        hours
+    }
+}
+
+impl Display for WorkHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.late_night > 0 {
+            parts.push(format!(
+                "{}% of commits after {}:00",
+                percent(self.late_night, self.commits),
+                LATE_NIGHT_START_HOUR
+            ));
+        }
+        if self.weekend > 0 {
+            parts.push(format!(
+                "{}% on weekends",
+                percent(self.weekend, self.commits)
+            ));
+        }
+        if parts.is_empty() {
+            return write!(f, "All commits during regular hours");
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+fn is_late_night(hour: u32) -> bool {
+    !(LATE_NIGHT_END_HOUR..LATE_NIGHT_START_HOUR).contains(&hour)
+}
+
+fn percent(part: usize, total: usize) -> usize {
+    (part * 100 + total / 2) / total.max(1)
+}
+
+/// When the author wrote the commit, on their own clock
+fn author_local_time(commit: &Commit) -> Option<DateTime<FixedOffset>> {
+    let time = commit.author_time.unwrap_or(commit.timestamp());
+    let utc = DateTime::from_timestamp(time, 0)?;
+    let offset = match commit.author_offset {
+        Some(minutes) => FixedOffset::east_opt(minutes * 60)?,
+        None => utc.with_timezone(&Local).offset().fix(),
+    };
+    Some(utc.with_timezone(&offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_work_hours() {
+        // Wednesday 2024-05-01 21:30 UTC and Saturday 2024-05-04 10:00 UTC
+        let wednesday = 1714599000;
+        let saturday = 1714816800;
+        let commits = vec![
+            // 23:30 in Berlin
+            Commit::new("Fix parser".to_string(), wednesday).with_author_offset(120),
+            // 17:30 in New York
+            Commit::new("Add exporter".to_string(), wednesday).with_author_offset(-240),
+            // Rebased on Saturday, written on Wednesday evening at UTC
+            Commit::new("Tidy docs".to_string(), saturday)
+                .with_author_time(wednesday)
+                .with_author_offset(0),
+            Commit::new("Release".to_string(), saturday).with_author_offset(0),
+        ];
+
+        let hours = WorkHours::from_commits(&commits);
+
+        assert_eq!(
+            hours,
+            WorkHours {
+                commits: 4,
+                late_night: 1,
+                weekend: 1,
+            }
+        );
+        assert_eq!(
+            hours.to_string(),
+            "25% of commits after 22:00, 25% on weekends"
+        );
+        assert_eq!(
+            WorkHours::from_commits(&commits[1..3]).to_string(),
+            "All commits during regular hours"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! End-to-end tests of the `git-diary` binary against a scripted repository
+//! and a fake OpenAI-compatible API
+//!
+//! Generated Markdown is compared with the files in `tests/golden`; run with
+//! `UPDATE_GOLDEN=1` to rewrite them after an intended output change.
+#![cfg(feature = "cli")]
+
+use git2::{Repository, Signature, Time};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use tempfile::TempDir;
+use tokio::process::Command;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A git repository with scripted commits plus private config, data and
+/// cache directories, so runs never touch the real home directory
+struct Fixture {
+    root: TempDir,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let root = TempDir::new().unwrap();
+        let repo = Repository::init(root.path().join("app")).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Jane Doe").unwrap();
+        config.set_str("user.email", "jane@example.com").unwrap();
+
+        Self { root }
+    }
+
+    fn repo_dir(&self) -> PathBuf {
+        self.root.path().join("app")
+    }
+
+    /// Commits `file` with `contents`, dated a few minutes ago
+    fn commit(&self, message: &str, file: &str, contents: &str) -> &Self {
+        let repo = Repository::open(self.repo_dir()).unwrap();
+        fs::write(self.repo_dir().join(file), contents).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new(file)).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let now = chrono::Local::now() - chrono::Duration::minutes(5);
+        let signature = Signature::new(
+            "Jane Doe",
+            "jane@example.com",
+            &Time::new(now.timestamp(), now.offset().local_minus_utc() / 60),
+        )
+        .unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        self
+    }
+
+    /// Points the repository config at the fake API
+    fn use_api(&self, server: &MockServer) -> &Self {
+        fs::write(
+            self.repo_dir().join(".git-diary.toml"),
+            format!(
+                "[ai]\nmodel = \"gpt-4o-mini\"\nbase_url = \"{}\"\n",
+                server.uri()
+            ),
+        )
+        .unwrap();
+        self
+    }
+
+    async fn run(&self, dir: &Path, args: &[&str]) -> Output {
+        let home = self.root.path().join("home");
+        Command::new(env!("CARGO_BIN_EXE_git-diary"))
+            .args(args)
+            .current_dir(dir)
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env("XDG_DATA_HOME", home.join(".local/share"))
+            .env("XDG_CACHE_HOME", home.join(".cache"))
+            .env("XDG_STATE_HOME", home.join(".local/state"))
+            .env("OPENAI_API_KEY", "test-key")
+            .output()
+            .await
+            .unwrap()
+    }
+
+    /// Diaries saved for the repository, whose directory is named after it
+    /// and a hash of its path
+    fn diaries(&self) -> Vec<PathBuf> {
+        let dir = self.root.path().join("home/.local/share/git-diary/diaries");
+        let Ok(repos) = fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut diaries: Vec<PathBuf> = repos
+            .map(|entry| entry.unwrap().path())
+            .filter(|repo| {
+                repo.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("app-")
+            })
+            .flat_map(|repo| fs::read_dir(repo).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        diaries.sort();
+        diaries
+    }
+}
+
+/// Serves `summary` as a streamed chat completion, as OpenAI does
+fn completion(summary: &str) -> ResponseTemplate {
+    let chunk = |delta: serde_json::Value, finish_reason: Option<&str>| {
+        serde_json::json!({
+            "id": "chatcmpl-test",
+            "object": "chat.completion.chunk",
+            "created": 1704067200,
+            "model": "gpt-4o-mini",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+        })
+    };
+    let usage = serde_json::json!({
+        "id": "chatcmpl-test",
+        "object": "chat.completion.chunk",
+        "created": 1704067200,
+        "model": "gpt-4o-mini",
+        "choices": [],
+        "usage": {"prompt_tokens": 120, "completion_tokens": 30, "total_tokens": 150},
+    });
+
+    let events = [
+        chunk(
+            serde_json::json!({"role": "assistant", "content": summary}),
+            None,
+        ),
+        chunk(serde_json::json!({}), Some("stop")),
+        usage,
+    ];
+    let mut body = String::new();
+    for event in events {
+        body.push_str(&format!("data: {}\n\n", event));
+    }
+    body.push_str("data: [DONE]\n\n");
+
+    ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+}
+
+/// Replaces the parts of a diary that change from run to run
+fn normalize(markdown: &str) -> String {
+    let date = Regex::new(r"\d{4}-\d{2}-\d{2}").unwrap();
+    let time = Regex::new(r"\d{2}:\d{2}(:\d{2})?").unwrap();
+    let markdown = date.replace_all(markdown, "YYYY-MM-DD");
+    time.replace_all(&markdown, "HH:MM").to_string()
+}
+
+/// Compares `actual` with a golden file, or rewrites it under `UPDATE_GOLDEN`
+fn assert_golden(name: &str, actual: &str) {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden)
+        .unwrap_or_else(|e| panic!("Failed to read {}: {}", golden.display(), e));
+    assert_eq!(actual, expected, "diary differs from {}", golden.display());
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+#[tokio::test]
+async fn test_generate_matches_golden_markdown() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/chat/completions"))
+        .respond_with(completion(
+            "Added the `SyncEngine` retry loop and documented the setup.",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let fixture = Fixture::new();
+    fixture
+        .commit("Add README", "README.md", "# App\n")
+        .commit("Add SyncEngine retries", "sync.rs", "fn retry() {}\n")
+        .use_api(&server);
+
+    let output = fixture
+        .run(&fixture.repo_dir(), &["--days", "1", "--deterministic"])
+        .await;
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let diaries = fixture.diaries();
+    assert_eq!(diaries.len(), 1);
+    assert_golden(
+        "generate.md",
+        &normalize(&fs::read_to_string(&diaries[0]).unwrap()),
+    );
+}
+
+#[tokio::test]
+async fn test_generate_fails_on_api_errors() {
+    for (status, body) in [
+        (
+            500,
+            r#"{"error": {"message": "The server had an error", "type": "server_error"}}"#,
+        ),
+        (
+            401,
+            r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error"}}"#,
+        ),
+    ] {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/chat/completions"))
+            .respond_with(ResponseTemplate::new(status).set_body_string(body))
+            .mount(&server)
+            .await;
+
+        let fixture = Fixture::new();
+        fixture
+            .commit("Add README", "README.md", "# App\n")
+            .use_api(&server);
+
+        let output = fixture.run(&fixture.repo_dir(), &["--days", "1"]).await;
+
+        assert!(!output.status.success(), "HTTP {} should fail", status);
+        assert!(
+            stderr(&output).contains("❌ Error generating diary"),
+            "{}",
+            stderr(&output)
+        );
+        assert!(fixture.diaries().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_generate_outside_a_repository() {
+    let fixture = Fixture::new();
+    let plain_dir = fixture.root.path().join("plain");
+    fs::create_dir_all(&plain_dir).unwrap();
+
+    let output = fixture.run(&plain_dir, &["--days", "1"]).await;
+
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("is not a git repository"),
+        "{}",
+        stderr(&output)
+    );
+}